@@ -0,0 +1,82 @@
+//! Reading a log of ten years of entries whole, and reading one month of it
+//! for a report, with `schema::read` and through the memory map, on one
+//! thread and on all cores.
+//!
+//! Run with `cargo bench --bench read`.
+
+use chrono::{Duration, TimeZone, Utc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use itracker::{
+    mapped::MappedLog,
+    schema::{self, Record},
+    Storage,
+};
+
+/// Ten years of about 27 entries a day.
+const ENTRIES: usize = 100_000;
+
+fn log_file(dir: &tempfile::TempDir) -> String {
+    let first = Utc.with_ymd_and_hms(2015, 1, 1, 9, 0, 0).unwrap();
+    let records: Vec<Record> = (1..=ENTRIES)
+        .map(|index| Record {
+            index,
+            start_time: (first + Duration::minutes(53 * index as i64)).to_rfc2822(),
+            message: format!("Worked on task {}", index),
+            elapsed_time: "1800".to_string(),
+            paused_time: "0".to_string(),
+            title: format!("Task {}", index % 40),
+            project: format!("project-{}", index % 7),
+            tags: "work,review".to_string(),
+            estimate: Some(30),
+            ..Record::default()
+        })
+        .collect();
+    let path = dir.path().join("logs.csv");
+    schema::write(&path, &records).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn read(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = log_file(&dir);
+    let storage = Storage::new(path.as_str());
+    let from = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+    let to = Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap();
+
+    let mut group = c.benchmark_group("read");
+    group.sample_size(20);
+    group.bench_function("whole log, schema::read", |b| {
+        b.iter(|| black_box(storage.entries().unwrap()))
+    });
+    group.bench_function("whole log, memory map", |b| {
+        b.iter(|| {
+            let mapped = MappedLog::open(&path).unwrap().unwrap();
+            black_box(mapped.entries_where(|_| true).unwrap())
+        })
+    });
+    group.bench_function("one month, schema::read", |b| {
+        b.iter(|| {
+            let mut entries = storage.entries().unwrap();
+            entries.retain(|entry| {
+                entry
+                    .start()
+                    .is_some_and(|start| start >= from && start < to)
+            });
+            black_box(entries)
+        })
+    });
+    let one_thread = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    group.bench_function("one month, memory map, one thread", |b| {
+        b.iter(|| one_thread.install(|| black_box(storage.entries_started(from, to).unwrap())))
+    });
+    group.bench_function("one month, memory map", |b| {
+        b.iter(|| black_box(storage.entries_started(from, to).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, read);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+fn main() {
+    // The gRPC code is generated from proto/itracker.proto with a bundled protoc
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("bundled protoc");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/itracker.proto").expect("valid proto file");
+    }
+}
@@ -0,0 +1,167 @@
+# Meldungen der Kommandozeile und des Betrachters, auf Deutsch.
+
+## Fehler und Warnungen
+
+error = Fehler: { $message }
+
+## Timer
+
+timer-started = Timer für den Eintrag mit Index { $index } gestartet.
+timer-not-started = Keine Aufgabe eingegeben, Timer nicht gestartet.
+timer-paused = Timer für den Eintrag mit Index { $index } pausiert.
+timer-not-resumed = Timer nicht fortgesetzt.
+timer-resumed = Timer für den Eintrag mit Index { $index } fortgesetzt. Pausen insgesamt: { $paused }
+timer-logged = Eintrag mit Index { $index } über { $elapsed } erfasst.
+timer-stopped = Timer um { $time } gestoppt. Dauer: { $elapsed }, Pausen insgesamt: { $paused }
+
+## Änderungen
+
+output-file = Ausgabedatei: { $file }
+
+delete-confirm = Eintrag { $index } aus { $file } löschen?
+delete-cancelled = Löschen abgebrochen.
+deleted = Eintrag mit Index { $index } aus { $file } gelöscht.
+delete-none = Keine Einträge in { $file } passen, nichts gelöscht.
+delete-matching = { $count ->
+    [one] Ein Eintrag in { $file } passt:
+   *[other] { $count } Einträge in { $file } passen:
+}
+delete-hidden = ... { $count } weitere
+delete-matching-confirm = { $count ->
+    [one] Diesen Eintrag aus { $file } löschen?
+   *[other] Diese { $count } Einträge aus { $file } löschen?
+}
+deleted-matching = { $count ->
+    [one] Ein Eintrag aus { $file } gelöscht.
+   *[other] { $count } Einträge aus { $file } gelöscht.
+}
+dedupe-confirm = { $count ->
+    [one] Ein Duplikat aus { $file } löschen?
+   *[other] { $count } Duplikate aus { $file } löschen?
+}
+deduped = { $count ->
+    [one] Ein Duplikat aus { $file } gelöscht.
+   *[other] { $count } Duplikate aus { $file } gelöscht.
+}
+restore-confirm = { $file } durch { $backup } ersetzen?
+restore-cancelled = Wiederherstellen abgebrochen.
+restored = { $file } aus { $backup } wiederhergestellt; der ersetzte Inhalt wurde gesichert.
+compact-confirm = Einträge von { $file } neu nummerieren und bereinigen?
+compact-cancelled = Bereinigen abgebrochen.
+compact-unchanged = { $file } ist bereits bereinigt.
+compact-reordered = Nach Startzeit verschobene Einträge: { $count }
+compact-trimmed = Von Leerraum oder leeren Tags befreite Einträge: { $count }
+compact-padded = Um fehlende Spalten ergänzte Zeilen: { $count }
+compact-amendments = Übernommene Änderungen des Journals: { $count }
+compact-headers = Kopfzeile in kanonische Reihenfolge gebracht.
+edit-unchanged = Eintrag { $index } ist unverändert.
+edit-again = Den Eintrag erneut bearbeiten?
+edit-updated = Eintrag { $index } aktualisiert.
+undone = { $action } in { $file } vom { $time } rückgängig gemacht.
+
+## Relative Zeiten
+
+relative-now = gerade eben
+relative-minutes = { $minutes ->
+    [one] vor einer Minute
+   *[other] vor { $minutes } Minuten
+}
+relative-today = heute { $time }
+relative-yesterday = gestern { $time }
+relative-weekday = { $weekday } { $time }
+
+## Berichte
+
+report-day = Bericht { $day }
+report-range = Bericht { $from } bis { $to }
+report-per-project = Pro Projekt
+report-expenses = inkl. { $amount } Auslagen
+report-budgets = Budgets, Woche vom { $week }
+report-billable = Abrechenbar
+report-overtime = Überstunden
+report-overtime-days = { $days ->
+    [one] an einem Tag
+   *[other] an { $days } Tagen
+}
+report-total = Gesamt
+report-entries = { $entries ->
+    [one] ein Eintrag
+   *[other] { $entries } Einträge
+}
+report-retainer = Kontingent { $project }, { $month }: { $hours }
+report-left = noch { $duration }
+
+## Tabellenköpfe
+
+table-index = #
+table-start = Beginn
+table-tracked = Erfasst
+table-title = Titel
+table-project = Projekt
+table-tags = Schlagwörter
+table-day = Tag
+table-goal = Ziel
+table-amount = Betrag
+table-expenses = Auslagen
+table-used = Verbraucht
+table-budget = Budget
+
+## Betrachter
+
+tui-entry = Eintrag { $index }: { $title }
+tui-details = Details
+tui-confirm = Bestätigen
+tui-today = Heute
+tui-per-project = Zeit pro Projekt
+tui-timeline = Zeitleiste { $day }
+tui-find = Eintrag suchen ({ $matches }/{ $total })
+tui-keys = Tasten (beliebige Taste schließt)
+tui-suggested = Vorschläge, Nummer drücken zum Starten
+tui-sorted = Sortiert nach { $column } { $arrow }  { $hint }
+tui-help-hint = { $key }: Hilfe
+tui-sort-index = Index
+tui-sort-start = Startzeit
+tui-sort-elapsed = Dauer
+tui-sort-message = Beschreibung
+tui-entry-details = Start: { $start }
+    Beschreibung:
+    { $message }
+    Dauer: { $elapsed }
+    Pausen: { $paused }
+    Projekt: { $project }  Tags: { $tags }
+tui-running = läuft
+tui-read-failed = Das Protokoll konnte nicht gelesen werden: { $error }
+tui-locked = Eintrag { $index } gehört zu einem abgeschlossenen Monat und kann nicht gelöscht werden
+tui-invoiced = Eintrag { $index } wurde als { $number } abgerechnet und kann nicht gelöscht werden
+tui-delete-confirm = Eintrag { $index } löschen: { $title }?
+tui-delete-cancelled = Löschen abgebrochen
+tui-deleted = Eintrag { $index } gelöscht
+tui-delete-failed = Eintrag { $index } konnte nicht gelöscht werden: { $error }
+tui-started = Timer für Eintrag { $index } gestartet (stoppen mit --stop { $index })
+tui-start-failed = Timer konnte nicht gestartet werden: { $error }
+tui-no-entries = Keine Einträge
+tui-no-time = Noch keine Zeit erfasst
+tui-nothing-today = Heute nichts erfasst
+tui-hours-per-day = Stunden pro Tag (letzte { $days } Tage)
+tui-day-total = Gesamt { $duration }
+detail-index = Index
+detail-title = Titel
+detail-project = Projekt
+detail-tags = Tags
+detail-estimate = Schätzung
+detail-minutes = { $minutes } Minuten
+detail-started = Beginn
+detail-unparsable = { $start } (nicht lesbar)
+detail-ended = Ende
+detail-paused-now = pausiert
+detail-elapsed = Dauer
+detail-paused = Pausen
+detail-tracked = Erfasst
+detail-description = Beschreibung
+form-title = Titel
+form-description = Beschreibung
+form-project = Projekt
+form-tags = Tags (durch Kommas getrennt)
+form-estimate = Schätzung (Minuten)
+form-help = Tab/Umschalt-Tab: Feld wechseln · Esc: speichern und starten · Strg-C: abbrechen · F1: Hilfe
+form-estimate-invalid = Die Schätzung muss eine ganze Zahl von Minuten sein, nicht '{ $estimate }'
@@ -0,0 +1,147 @@
+# Messages of the command line and the viewer, in English. Every other
+# catalog translates these ids; missing ones fall back to this file.
+
+## Errors and warnings
+
+error = Error: { $message }
+
+## Timers
+
+timer-started = Timer started for log entry at index { $index }.
+timer-not-started = No task entered, timer not started.
+timer-paused = Timer paused for log entry at index { $index }.
+timer-not-resumed = Timer not resumed.
+timer-resumed = Timer resumed for log entry at index { $index }. Total paused time: { $paused }
+timer-logged = Logged entry at index { $index } of { $elapsed }.
+timer-stopped = Timer stopped at { $time }. Elapsed time: { $elapsed }, Total paused time: { $paused }
+
+## Changes
+
+output-file = Using output file: { $file }
+
+delete-confirm = Delete log entry { $index } from { $file }?
+delete-cancelled = Deletion cancelled.
+deleted = Log entry at index { $index } deleted from { $file }.
+delete-none = No entries of { $file } match, nothing deleted.
+delete-matching = { $count } entries of { $file } match:
+delete-hidden = ... { $count } more
+delete-matching-confirm = Delete these { $count } entries from { $file }?
+deleted-matching = { $count } entries deleted from { $file }.
+dedupe-confirm = Delete { $count } duplicates from { $file }?
+deduped = { $count } duplicates deleted from { $file }.
+restore-confirm = Replace { $file } with { $backup }?
+restore-cancelled = Restore cancelled.
+restored = Restored { $file } from { $backup }; the replaced contents were backed up.
+compact-confirm = Renumber and normalize the entries of { $file }?
+compact-cancelled = Compaction cancelled.
+compact-unchanged = { $file } is already compact.
+compact-reordered = Entries moved to sort by start time: { $count }
+compact-trimmed = Entries trimmed of stray whitespace or empty tags: { $count }
+compact-padded = Rows given the columns they were missing: { $count }
+compact-amendments = Journal amendments folded in: { $count }
+compact-headers = Headers rewritten in canonical order.
+edit-unchanged = Log entry { $index } is unchanged.
+edit-again = Edit the entry again?
+edit-updated = Log entry { $index } updated.
+undone = Undid the { $action } in { $file } of { $time }.
+
+## Relative times
+
+relative-now = just now
+relative-minutes = { $minutes ->
+    [one] 1 minute ago
+   *[other] { $minutes } minutes ago
+}
+relative-today = today { $time }
+relative-yesterday = yesterday { $time }
+relative-weekday = { $weekday } { $time }
+
+## Reports
+
+report-day = Report { $day }
+report-range = Report { $from } to { $to }
+report-per-project = Per project
+report-expenses = incl. { $amount } expenses
+report-budgets = Budgets, week of { $week }
+report-billable = Billable
+report-overtime = Overtime
+report-overtime-days = on { $days } days
+report-total = Total
+report-entries = { $entries } entries
+report-retainer = Retainer { $project }, { $month }: { $hours }
+report-left = { $duration } left
+
+## Table headers
+
+table-index = #
+table-start = Start
+table-tracked = Tracked
+table-title = Title
+table-project = Project
+table-tags = Tags
+table-day = Day
+table-goal = Goal
+table-amount = Amount
+table-expenses = Expenses
+table-used = Used
+table-budget = Budget
+
+## Viewer
+
+tui-entry = Log Entry { $index }: { $title }
+tui-details = Details
+tui-confirm = Confirm
+tui-today = Today
+tui-per-project = Time per project
+tui-timeline = Timeline { $day }
+tui-find = Find entry ({ $matches }/{ $total })
+tui-keys = Keys (any key to close)
+tui-suggested = Suggested, press the number to start
+tui-sorted = Sorted by { $column } { $arrow }  { $hint }
+tui-help-hint = { $key }: help
+tui-sort-index = index
+tui-sort-start = start time
+tui-sort-elapsed = elapsed time
+tui-sort-message = message
+tui-entry-details = Start Time: { $start }
+    Message:
+    { $message }
+    Elapsed Time: { $elapsed }
+    Paused Time: { $paused }
+    Project: { $project }  Tags: { $tags }
+tui-running = running
+tui-read-failed = Failed to read the log: { $error }
+tui-locked = Log entry { $index } belongs to a closed month and cannot be deleted
+tui-invoiced = Log entry { $index } was invoiced as { $number } and cannot be deleted
+tui-delete-confirm = Delete log entry { $index }: { $title }?
+tui-delete-cancelled = Delete cancelled
+tui-deleted = Log entry { $index } deleted
+tui-delete-failed = Failed to delete log entry { $index }: { $error }
+tui-started = Timer started for log entry { $index } (stop it with --stop { $index })
+tui-start-failed = Failed to start a timer: { $error }
+tui-no-entries = No log entries
+tui-no-time = No tracked time yet
+tui-nothing-today = Nothing tracked today
+tui-hours-per-day = Hours per day (last { $days } days)
+tui-day-total = Total { $duration }
+detail-index = Index
+detail-title = Title
+detail-project = Project
+detail-tags = Tags
+detail-estimate = Estimate
+detail-minutes = { $minutes } minutes
+detail-started = Started
+detail-unparsable = { $start } (unparsable)
+detail-ended = Ended
+detail-paused-now = paused
+detail-elapsed = Elapsed
+detail-paused = Paused
+detail-tracked = Tracked
+detail-description = Description
+form-title = Title
+form-description = Description
+form-project = Project
+form-tags = Tags (comma separated)
+form-estimate = Estimate (minutes)
+form-help = Tab/Shift-Tab: switch field · Esc: save and start · Ctrl-C: cancel · F1: help
+form-estimate-invalid = Estimate must be a whole number of minutes, got '{ $estimate }'
@@ -0,0 +1,445 @@
+//! Opt-in tracking of the focused window while the daemon runs.
+//!
+//! Every `interval_seconds` the app and title of the focused window are
+//! sampled and recorded in `<log>.activity` along with the entry whose timer
+//! was running at the time, if any. Samples of the same window in a row are
+//! kept as one row. The recorded activity shows what the time of an entry
+//! went into, and periods no timer ran for become suggestions for entries.
+//!
+//! The focused window is asked for with the tools of the desktop: `swaymsg`
+//! or `hyprctl` on Wayland, `xprop` on X11 and `osascript` on macOS. Any
+//! other desktop can set `command` to a program printing the app name on the
+//! first line and the window title on the second.
+
+use crate::{
+    crypto,
+    error::ITrackerError,
+    schema::{self, Record},
+    storage::Storage,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process, thread,
+};
+
+/// Seconds between samples unless configured otherwise.
+pub const DEFAULT_INTERVAL_SECONDS: u64 = 30;
+
+/// Days samples are kept unless configured otherwise.
+pub const DEFAULT_KEEP_DAYS: u64 = 30;
+
+/// Untracked periods shorter than this are not suggested as entries.
+pub const MIN_SUGGESTION_SECONDS: u64 = 5 * 60;
+
+/// The `[activity]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActivityConfig {
+    /// Sample the focused window while the daemon runs, off by default.
+    pub enabled: Option<bool>,
+    pub interval_seconds: Option<u64>,
+    /// Days samples are kept.
+    pub keep_days: Option<u64>,
+    /// Program printing the app and the title of the focused window on two
+    /// lines, instead of the desktop's own tools.
+    pub command: Option<String>,
+    /// Apps never recorded, matched case-insensitively against part of the
+    /// app name.
+    pub exclude: Option<Vec<String>>,
+}
+
+/// The focused window.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Window {
+    pub app: String,
+    pub title: String,
+}
+
+/// A row of `<log>.activity`: one window focused for a while.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sample {
+    #[serde(rename = "Start")]
+    pub start: DateTime<Utc>,
+    #[serde(rename = "Seconds")]
+    pub seconds: u64,
+    #[serde(rename = "App")]
+    pub app: String,
+    #[serde(rename = "Title")]
+    pub title: String,
+    /// Index of the entry whose timer ran, `None` while none ran.
+    #[serde(rename = "Entry")]
+    pub entry: Option<usize>,
+}
+
+impl Sample {
+    pub fn end(&self) -> DateTime<Utc> {
+        self.start + Duration::seconds(self.seconds as i64)
+    }
+}
+
+/// A period no timer ran for, spent mostly in one app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub start: DateTime<Utc>,
+    pub seconds: u64,
+    pub app: String,
+    /// The title the window had longest.
+    pub title: String,
+}
+
+/// Samples the focused window.
+#[derive(Debug, Clone)]
+pub struct ActivityTracker {
+    interval: u64,
+    keep_days: u64,
+    command: Option<String>,
+    /// Lowercase.
+    exclude: Vec<String>,
+}
+
+impl ActivityTracker {
+    /// Resolves the `[activity]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Some(ActivityTracker))`: If activity tracking is enabled.
+    /// - `Ok(None)`: If it is off, as it is by default.
+    /// - `Err(ITrackerError)`: `Config` if the interval is zero.
+    pub fn from_config(config: Option<&ActivityConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(config) = config.filter(|config| config.enabled.unwrap_or(false)) else {
+            return Ok(None);
+        };
+        let interval = config.interval_seconds.unwrap_or(DEFAULT_INTERVAL_SECONDS);
+        if interval == 0 {
+            return Err(ITrackerError::Config(
+                "interval_seconds must be at least 1".to_string(),
+            ));
+        }
+        Ok(Some(ActivityTracker {
+            interval,
+            keep_days: config.keep_days.unwrap_or(DEFAULT_KEEP_DAYS),
+            command: config.command.clone().filter(|command| !command.is_empty()),
+            exclude: config
+                .exclude
+                .iter()
+                .flatten()
+                .map(|app| app.to_lowercase())
+                .collect(),
+        }))
+    }
+
+    /// Samples the focused window every interval in the background, for the
+    /// daemon. Failures are reported once, until sampling works again.
+    pub fn schedule(self, log: PathBuf) {
+        thread::spawn(move || {
+            // Cached, so the log is only parsed again after it changed
+            let storage = Storage::new(log.to_string_lossy()).with_cache();
+            let mut failing = false;
+            loop {
+                match self.sample(&storage, Utc::now()) {
+                    Ok(_) => failing = false,
+                    Err(e) if !failing => {
+                        eprintln!("Cannot record the focused window: {}", e);
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+                thread::sleep(std::time::Duration::from_secs(self.interval));
+            }
+        });
+    }
+
+    /// Records the focused window in the activity file of the log of
+    /// `storage`, along with its running entry unless that is paused.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Window))`: The window recorded.
+    /// - `Ok(None)`: If no window has the focus or its app is excluded.
+    /// - `Err(ITrackerError)`: If the window cannot be asked for, or a file
+    ///   cannot be read or written.
+    pub fn sample(
+        &self,
+        storage: &Storage,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Window>, ITrackerError> {
+        let Some(window) = self.focused_window()? else {
+            return Ok(None);
+        };
+        let app = window.app.to_lowercase();
+        if self.exclude.iter().any(|excluded| app.contains(excluded)) {
+            return Ok(None);
+        }
+        let entry = storage
+            .running()?
+            .filter(|entry| entry.paused_since().is_none())
+            .map(|entry| entry.index);
+
+        let log = Path::new(storage.path());
+        let mut samples = read_samples(log)?;
+        let interval = Duration::seconds(self.interval as i64);
+        match samples.last_mut() {
+            // Still the same window, with at most one sample missed
+            Some(last)
+                if last.app == window.app
+                    && last.title == window.title
+                    && last.entry == entry
+                    && last.end() >= now - interval
+                    && last.start <= now =>
+            {
+                last.seconds = (now + interval - last.start).num_seconds() as u64;
+            }
+            _ => samples.push(Sample {
+                start: now,
+                seconds: self.interval,
+                app: window.app.clone(),
+                title: window.title.clone(),
+                entry,
+            }),
+        }
+        let oldest = now - Duration::days(self.keep_days as i64);
+        samples.retain(|sample| sample.end() >= oldest);
+        write_samples(log, &samples)?;
+        Ok(Some(window))
+    }
+
+    /// The focused window, asked for with the configured command or the
+    /// tools of the desktop.
+    fn focused_window(&self) -> Result<Option<Window>, ITrackerError> {
+        if let Some(command) = &self.command {
+            let output = run("sh", &["-c", command])?;
+            let mut lines = output.lines();
+            return Ok(lines
+                .next()
+                .filter(|app| !app.trim().is_empty())
+                .map(|app| Window {
+                    app: app.trim().to_string(),
+                    title: lines.next().unwrap_or_default().trim().to_string(),
+                }));
+        }
+        if cfg!(target_os = "macos") {
+            return mac_window();
+        }
+        if std::env::var_os("SWAYSOCK").is_some() {
+            return sway_window();
+        }
+        if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            return hyprland_window();
+        }
+        if std::env::var_os("DISPLAY").is_some() {
+            return x11_window();
+        }
+        Err(ITrackerError::Activity(
+            "No supported desktop found, set `activity.command` to a program printing the app and title of the focused window".to_string(),
+        ))
+    }
+}
+
+/// The samples recorded for `log`, oldest first, decrypted if the file is
+/// encrypted.
+pub fn read_samples(log: &Path) -> Result<Vec<Sample>, ITrackerError> {
+    let path = schema::activity_path(log);
+    let Some(data) = crypto::read_sidecar(&path)? else {
+        return Ok(Vec::new());
+    };
+    let mut reader = csv::Reader::from_reader(data.as_slice());
+    reader
+        .deserialize()
+        .collect::<Result<Vec<Sample>, _>>()
+        .map_err(ITrackerError::csv(&path))
+}
+
+/// Replaces the samples recorded for `log`, encrypted like the log files.
+fn write_samples(log: &Path, samples: &[Sample]) -> Result<(), ITrackerError> {
+    let path = schema::activity_path(log);
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for sample in samples {
+        writer
+            .serialize(sample)
+            .map_err(ITrackerError::csv(&path))?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|e| ITrackerError::file(&path)(e.into_error()))?;
+    crypto::write_sidecar(&path, data)
+}
+
+/// The time spent in each window while the timer of entry `index` ran,
+/// longest first.
+pub fn by_window(samples: &[Sample], index: usize) -> Vec<(Window, u64)> {
+    let mut seconds: HashMap<Window, u64> = HashMap::new();
+    for sample in samples.iter().filter(|sample| sample.entry == Some(index)) {
+        let window = Window {
+            app: sample.app.clone(),
+            title: sample.title.clone(),
+        };
+        *seconds.entry(window).or_default() += sample.seconds;
+    }
+    let mut windows: Vec<(Window, u64)> = seconds.into_iter().collect();
+    windows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.app.cmp(&b.0.app)));
+    windows
+}
+
+/// Periods of at least `min_seconds` no timer ran for, as entries to add.
+///
+/// Untracked samples of one app follow each other into one period as long
+/// as the gaps between them are shorter than `min_seconds`; the time spent
+/// in other apps in between counts towards neither.
+pub fn suggestions(samples: &[Sample], min_seconds: u64) -> Vec<Suggestion> {
+    let mut periods: Vec<(Suggestion, HashMap<String, u64>)> = Vec::new();
+    let mut open: HashMap<String, usize> = HashMap::new();
+    for sample in samples.iter().filter(|sample| sample.entry.is_none()) {
+        let gap = Duration::seconds(min_seconds as i64);
+        let continued = open.get(&sample.app).copied().filter(|&i| {
+            let period = &periods[i].0;
+            period.start + Duration::seconds(period.seconds as i64) + gap >= sample.start
+        });
+        let i = match continued {
+            Some(i) => i,
+            None => {
+                periods.push((
+                    Suggestion {
+                        start: sample.start,
+                        seconds: 0,
+                        app: sample.app.clone(),
+                        title: String::new(),
+                    },
+                    HashMap::new(),
+                ));
+                open.insert(sample.app.clone(), periods.len() - 1);
+                periods.len() - 1
+            }
+        };
+        let (period, titles) = &mut periods[i];
+        period.seconds = (sample.end() - period.start).num_seconds().max(0) as u64;
+        *titles.entry(sample.title.clone()).or_default() += sample.seconds;
+    }
+
+    periods
+        .into_iter()
+        .filter(|(period, _)| period.seconds >= min_seconds)
+        .map(|(mut period, titles)| {
+            period.title = titles
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                .map(|(title, _)| title)
+                .unwrap_or_default();
+            period
+        })
+        .collect()
+}
+
+/// A stopped entry for a suggestion, to be appended under `index`.
+pub fn suggested_record(suggestion: &Suggestion, index: usize) -> Record {
+    Record {
+        index,
+        start_time: suggestion.start.to_rfc2822(),
+        message: suggestion.title.clone(),
+        elapsed_time: suggestion.seconds.to_string(),
+        paused_time: "0".to_string(),
+        title: suggestion.app.clone(),
+        ..Record::default()
+    }
+}
+
+/// Runs a program and returns what it printed.
+fn run(program: &str, args: &[&str]) -> Result<String, ITrackerError> {
+    let output = process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| ITrackerError::Activity(format!("Cannot run {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(ITrackerError::Activity(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn mac_window() -> Result<Option<Window>, ITrackerError> {
+    let script = r#"tell application "System Events"
+  set frontApp to first application process whose frontmost is true
+  set appName to name of frontApp
+  set windowTitle to ""
+  try
+    set windowTitle to name of front window of frontApp
+  end try
+end tell
+return appName & linefeed & windowTitle"#;
+    let output = run("osascript", &["-e", script])?;
+    let mut lines = output.lines();
+    Ok(lines
+        .next()
+        .filter(|app| !app.is_empty())
+        .map(|app| Window {
+            app: app.to_string(),
+            title: lines.next().unwrap_or_default().to_string(),
+        }))
+}
+
+fn sway_window() -> Result<Option<Window>, ITrackerError> {
+    let tree: serde_json::Value = serde_json::from_str(&run("swaymsg", &["-t", "get_tree"])?)
+        .map_err(|e| ITrackerError::Activity(format!("Unexpected answer of swaymsg: {}", e)))?;
+    fn focused(node: &serde_json::Value) -> Option<&serde_json::Value> {
+        if node["focused"].as_bool() == Some(true) && node["type"] == "con" {
+            return Some(node);
+        }
+        ["nodes", "floating_nodes"]
+            .iter()
+            .filter_map(|key| node[key].as_array())
+            .flatten()
+            .find_map(focused)
+    }
+    Ok(focused(&tree).map(|node| Window {
+        app: node["app_id"]
+            .as_str()
+            .or_else(|| node["window_properties"]["class"].as_str())
+            .unwrap_or_default()
+            .to_string(),
+        title: node["name"].as_str().unwrap_or_default().to_string(),
+    }))
+}
+
+fn hyprland_window() -> Result<Option<Window>, ITrackerError> {
+    let window: serde_json::Value = serde_json::from_str(&run("hyprctl", &["activewindow", "-j"])?)
+        .unwrap_or(serde_json::Value::Null);
+    Ok(window["class"].as_str().map(|app| Window {
+        app: app.to_string(),
+        title: window["title"].as_str().unwrap_or_default().to_string(),
+    }))
+}
+
+fn x11_window() -> Result<Option<Window>, ITrackerError> {
+    // _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+    let active = run("xprop", &["-root", "_NET_ACTIVE_WINDOW"])?;
+    let Some(id) = active
+        .rsplit(' ')
+        .next()
+        .map(str::trim)
+        .filter(|id| id.starts_with("0x") && *id != "0x0")
+    else {
+        return Ok(None);
+    };
+    // WM_CLASS(STRING) = "navigator", "firefox"
+    // _NET_WM_NAME(UTF8_STRING) = "Docs - Mozilla Firefox"
+    let properties = run("xprop", &["-id", id, "WM_CLASS", "_NET_WM_NAME"])?;
+    let value = |name: &str| -> Vec<String> {
+        properties
+            .lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.split_once(" = "))
+            .map(|(_, values)| {
+                values
+                    .split("\", \"")
+                    .map(|value| value.trim().trim_matches('"').to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let app = value("WM_CLASS").pop().unwrap_or_default();
+    let title = value("_NET_WM_NAME").join("\", \"");
+    Ok(Some(Window { app, title }))
+}
@@ -1,12 +1,17 @@
-use clap::Parser;
-use std::path::PathBuf;
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use itracker::{
+    color::ColorChoice, export::Format as ExportFormat, idle::IdleAnswer, invoice::Detail,
+    merge::Resolution, output::Format as OutputFormat, status::Shell,
+};
+use std::{path::PathBuf, str::FromStr};
 
 #[derive(Parser, Debug, Default)]
 #[command(author, version, about, long_about)]
 pub struct Args {
-    /// Timezone for logging
-    #[arg(short = 'z', long = "timezone", default_value = "UTC")]
-    pub timezone: String,
+    /// Timezone for displaying times and counting days, e.g. Europe/Berlin [default: UTC]
+    #[arg(short = 'z', long = "timezone")]
+    pub timezone: Option<String>,
 
     // Path to the log file
     #[clap(short, long)]
@@ -24,15 +29,475 @@ pub struct Args {
     #[arg(short = 'a', long="add",action = clap::ArgAction::SetTrue)]
     pub add: bool,
 
-    /// Pause the timer
-    #[arg(short = 'p', long = "pause", action = clap::ArgAction::SetTrue)]
-    pub pause: bool,
+    /// Pause the running timer, or the one of the given entry
+    #[arg(short = 'p', long = "pause", value_name = "INDEX", num_args = 0..=1)]
+    pub pause: Option<Option<usize>>,
 
-    /// Resume the timer
-    #[arg(short = 'r', long = "resume", action = clap::ArgAction::SetTrue)]
-    pub resume: bool,
+    /// Resume the paused timer, or the one of the given entry
+    #[arg(short = 'r', long = "resume", value_name = "INDEX", num_args = 0..=1)]
+    pub resume: Option<Option<usize>>,
 
-    /// Delete a specific log entry by index
-    #[arg(short = 'd', long = "delete-log", value_name = "INDEX")]
-    pub delete_log: Option<usize>,
+    /// On resume after idle detection paused the timer, keep the idle time
+    /// as work, discard it, or move it to another task, instead of asking
+    #[arg(long = "idle", value_name = "ANSWER", requires = "resume")]
+    pub idle: Option<IdleAnswer>,
+
+    /// Task the idle time is moved to, implies `--idle move`
+    #[arg(long = "idle-task", value_name = "TITLE", requires = "resume")]
+    pub idle_task: Option<String>,
+
+    /// With --add or --stop, when the timer started or stopped instead of now,
+    /// e.g. 9:30, "yesterday 3pm" or "20 minutes ago"
+    #[arg(long = "at", value_name = "TIME")]
+    pub at: Option<String>,
+
+    /// With --add, log a finished entry this long instead of starting a timer,
+    /// e.g. 1h30m or "90 minutes"; it ends now, or starts at --at
+    #[arg(long = "for", value_name = "DURATION", requires = "add")]
+    pub length: Option<String>,
+
+    /// With --add, a value kept in the entry's metadata, e.g. `--meta ticket=PROJ-123`;
+    /// may be given several times
+    #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_option, requires = "add")]
+    pub meta: Vec<(String, String)>,
+
+    /// Delete a specific log entry by index, or without one the entries
+    /// matching --before, --tag and --project
+    #[arg(short = 'd', long = "delete-log", value_name = "INDEX", num_args = 0..=1)]
+    pub delete_log: Option<Option<usize>>,
+
+    /// With --delete-log, entries started before this time, e.g. 2023-01-01
+    #[arg(long, value_name = "TIME", requires = "delete_log")]
+    pub before: Option<String>,
+
+    /// With --delete-log, entries with this tag
+    #[arg(long, value_name = "TAG", requires = "delete_log")]
+    pub tag: Option<String>,
+
+    /// With --delete-log, entries of this project
+    #[arg(long, value_name = "NAME", requires = "delete_log")]
+    pub project: Option<String>,
+
+    /// Configuration profile to use, e.g. work or personal
+    #[arg(long = "profile", value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Run as the team server that the clients of `[team]` report to
+    #[arg(long = "server", action = clap::ArgAction::SetTrue)]
+    pub server: bool,
+
+    /// Skip confirmation prompts before destructive actions, and the task form
+    /// of `--add` when the task is prefilled from the git branch
+    #[arg(short = 'y', long = "yes", action = clap::ArgAction::SetTrue)]
+    pub yes: bool,
+
+    /// Change or delete entries although they were invoiced
+    #[arg(long = "force", action = clap::ArgAction::SetTrue)]
+    pub force: bool,
+
+    /// Color the output: auto (on a terminal without NO_COLOR), always or never
+    #[arg(
+        long = "color",
+        value_name = "WHEN",
+        default_value = "auto",
+        global = true
+    )]
+    pub color: ColorChoice,
+
+    /// Print `list` and `report` as plain lines instead of tables, as when piped
+    #[arg(long = "no-table", action = clap::ArgAction::SetTrue, global = true)]
+    pub no_table: bool,
+
+    /// Show start times in `list` and the viewer as configured by
+    /// `time_format`, instead of relative to now like "25 minutes ago"
+    #[arg(long = "absolute", action = clap::ArgAction::SetTrue, global = true)]
+    pub absolute: bool,
+
+    /// Print `list`, `status` and `report` as table (for people), json, yaml
+    /// or csv (for scripts)
+    #[arg(
+        long = "format",
+        value_name = "FORMAT",
+        default_value = "table",
+        global = true
+    )]
+    pub output_format: OutputFormat,
+
+    /// Print `status` and `list` entries as this template, e.g.
+    /// '{{title}} [{{elapsed}}]', or as the preset of `[templates]` of that name
+    #[arg(long = "template", value_name = "TEMPLATE", global = true)]
+    pub template: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Validate, lock, report and archive a finished month
+    CloseMonth {
+        /// Month to close, e.g. 2024-06
+        #[arg(value_name = "YYYY-MM")]
+        month: String,
+    },
+    /// Sort the entries by start time, renumber them 1, 2, 3, ... and rewrite
+    /// the file in canonical form, printing what changed
+    Compact,
+    /// Move the start or end of an entry, e.g. `amend 42 --start 9:05 --end 11:00`
+    Amend {
+        /// Index of the entry to amend
+        index: usize,
+        /// New start; a time of day alone is on the entry's day. The end stays
+        #[arg(long, value_name = "TIME", required_unless_present = "end")]
+        start: Option<String>,
+        /// New end, stopping a running entry; a time of day alone is on the
+        /// entry's day
+        #[arg(long, value_name = "TIME")]
+        end: Option<String>,
+    },
+    /// Split an entry in two at a time, e.g. `split 42 --at 10:30`
+    Split {
+        /// Index of the entry to split; the second part gets a new index
+        index: usize,
+        /// Where the second part starts; a time of day alone is on the entry's day
+        #[arg(long, value_name = "TIME")]
+        at: String,
+        /// Title of the second part instead of the entry's
+        #[arg(long)]
+        title: Option<String>,
+        /// Description of the second part instead of the entry's
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Open an entry in $VISUAL or $EDITOR and write back the changes
+    Edit {
+        /// Index of the entry to edit
+        index: usize,
+    },
+    /// Merge the entries of another log file into a log file, e.g. one
+    /// tracked on another machine
+    ///
+    /// Given indices instead, e.g. `merge 42 43`, merge those entries of the
+    /// output file into the one that started first
+    Merge {
+        /// The log file to merge into (backed up first) and the one to take the
+        /// entries from (left unchanged), or two or more entry indices
+        #[arg(value_name = "FILE|INDEX", num_args = 2.., required = true)]
+        targets: Vec<String>,
+        /// Resolve every conflict this way instead of asking: ours, theirs or both
+        #[arg(long, value_name = "SIDE")]
+        prefer: Option<Resolution>,
+    },
+    /// Move entries to another project, or to the end of another log file
+    Move {
+        /// Indices of the entries to move
+        #[arg(required = true)]
+        indices: Vec<usize>,
+        /// Project the entries are moved to
+        #[arg(
+            long,
+            value_name = "NAME",
+            required_unless_present = "to_file",
+            conflicts_with = "to_file"
+        )]
+        to_project: Option<String>,
+        /// Log file the entries are moved to, where they get new indices
+        #[arg(long, value_name = "FILE")]
+        to_file: Option<String>,
+    },
+    /// List entries with the same start and description as an earlier one
+    Dedupe {
+        /// Delete the duplicates, keeping the first entry of each
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Take back the last delete, stop, edit, amend, split, merge, dedupe or
+    /// move between projects of entries
+    Undo,
+    /// Roll the log file back to a backup taken before it was rewritten
+    Restore {
+        /// Backup to restore, as printed by `--list`; the latest by default
+        backup: Option<String>,
+        /// List the backups instead of restoring one
+        #[arg(long)]
+        list: bool,
+    },
+    /// Upload, list, restore and prune backups, see `[backup.remote]`
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+    /// Write all data and the config file into one file, to move to another machine
+    Dump {
+        /// File to write, a gzipped tar file
+        file: PathBuf,
+    },
+    /// Put the data and config file of a dump in place; replaced files are backed up
+    Load {
+        /// Dump written by `dump`
+        file: PathBuf,
+    },
+    /// Run in the foreground and apply the timer commands of other invocations
+    ///
+    /// Starting, stopping, pausing, resuming, amending, splitting and merging
+    /// go through the daemon, as do timers started in the viewer. Edits,
+    /// deletes, compaction, restores and moves between files, also those of
+    /// the viewer, still write the log file themselves, so run them while no
+    /// other invocation changes the same file
+    Daemon,
+    /// Show the windows focused while timers ran, recorded by the daemon with `[activity]`
+    Activity {
+        /// Show the time spent in each window while this entry's timer ran
+        #[arg(long, value_name = "INDEX", conflicts_with = "suggest")]
+        entry: Option<usize>,
+        /// Suggest entries for the periods no timer ran for
+        #[arg(long)]
+        suggest: bool,
+        /// Add the suggested entries to the output file
+        #[arg(long, requires = "suggest")]
+        add: bool,
+    },
+    /// Serve a JSON API for the output file on localhost
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+    /// Serve the gRPC API of proto/itracker.proto for the output file on localhost
+    #[cfg(feature = "grpc")]
+    Grpc {
+        /// Port to listen on
+        #[arg(long, default_value_t = 50051)]
+        port: u16,
+    },
+    /// Show a tray icon with the running timer and buttons to pause, resume and stop it
+    #[cfg(feature = "tray")]
+    Tray,
+    /// Print the running timer for status bars, without parsing the log while it is unchanged
+    ///
+    /// Besides the formats of all read commands, `--format` takes waybar (JSON),
+    /// polybar and i3blocks here
+    Status,
+    /// Print the running timer as a colored tmux segment, for `#(Itraker tmux)` in status-right
+    Tmux,
+    /// Print the running timer for a shell prompt, nothing while no timer runs
+    Prompt {
+        /// Escape for PS1 in bash or PROMPT in zsh; plain leaves the text as is
+        #[arg(long, default_value = "plain")]
+        shell: Shell,
+    },
+    /// Sync the output file with the WebDAV server of `[webdav]` and the
+    /// devices sharing the folder of `[crdt]`, and the data directory with the
+    /// git remote of `[sync]`; push new entries to the accounts of `[toggl]`
+    /// and `[clockify]`
+    Sync {
+        /// Only push the entries to this service: toggl or clockify
+        service: Option<SyncService>,
+    },
+    /// Print the entries, running ones in green and paused ones in yellow
+    List {
+        /// First day, all entries by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        from: Option<NaiveDate>,
+        /// Last day, today by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        to: Option<NaiveDate>,
+        /// Only entries started since then, e.g. "last monday" or "2 hours ago"
+        #[arg(long, value_name = "TIME")]
+        since: Option<String>,
+        /// Only entries with this metadata value; may be given several times
+        #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_option)]
+        meta: Vec<(String, String)>,
+    },
+    /// Print the time tracked per day and project, this week by default
+    Report {
+        /// Only today, with the progress toward `daily_goal_hours`
+        #[arg(long, conflicts_with_all = ["from", "to"])]
+        today: bool,
+        /// First day, the first day of this week by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        from: Option<NaiveDate>,
+        /// Last day, the last day of the first day's week by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        to: Option<NaiveDate>,
+        /// Only entries of this project (client), with the burn-down of its retainer
+        #[arg(long, value_name = "PROJECT")]
+        client: Option<String>,
+        /// Only entries with this metadata value; may be given several times
+        #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_option)]
+        meta: Vec<(String, String)>,
+    },
+    /// Print the billable time of a range as invoice line items at the configured `rate`
+    Invoice {
+        /// Only entries of this project (client)
+        #[arg(long, value_name = "PROJECT")]
+        client: Option<String>,
+        /// First day, the first day of this month by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        from: Option<NaiveDate>,
+        /// Last day, the last day of the first day's month by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        to: Option<NaiveDate>,
+        /// One line per entry, per day and project, or per project and task
+        #[arg(long, value_name = "entries|daily|task", default_value = "task")]
+        detail: Detail,
+        /// Issue the invoice under this number: its entries are marked as
+        /// invoiced and left out of later invoices
+        #[arg(long, value_name = "NUMBER")]
+        number: Option<String>,
+    },
+    /// Record an expense, billed on the client's next invoice in its currency
+    Expense {
+        /// The amount, e.g. 42.50
+        amount: f64,
+        /// What was paid for, e.g. "train ticket"
+        description: String,
+        /// The project (client) the expense is billed to
+        #[arg(long, value_name = "PROJECT")]
+        client: Option<String>,
+        /// The day paid, today by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        date: Option<NaiveDate>,
+    },
+    /// Write the stopped entries in the import format of another time tracker
+    Export {
+        /// harvest (CSV of Harvest's time import)
+        format: ExportFormat,
+        /// File to write, stdout by default
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// First day, the first entry by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        from: Option<NaiveDate>,
+        /// Last day, the last entry by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        to: Option<NaiveDate>,
+        /// Only entries of this project (client)
+        #[arg(long, value_name = "PROJECT")]
+        client: Option<String>,
+    },
+    /// Report to the team server of `[team]` and read the team's reports
+    Team {
+        #[command(subcommand)]
+        action: TeamAction,
+    },
+    /// List the plugins of the plugins directory and export, import or sync with them
+    Plugin {
+        #[command(subcommand)]
+        action: PluginAction,
+    },
+    /// Read and change settings in the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TeamAction {
+    /// Replace your entries on the team server with those of the output file
+    Push,
+    /// Print the time the team tracked per user and project
+    Report {
+        /// First day, today by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        from: Option<NaiveDate>,
+        /// Last day, the first day or today, whichever is later, by default
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        to: Option<NaiveDate>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PluginAction {
+    /// List the plugins and what they can do
+    List,
+    /// Export the output file with a plugin
+    Export {
+        name: String,
+        /// File to write, stdout by default
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Setting passed to the plugin, repeatable
+        #[arg(long = "option", value_name = "KEY=VALUE", value_parser = parse_option)]
+        options: Vec<(String, String)>,
+    },
+    /// Add the entries a plugin reads from a file to the output file
+    Import {
+        name: String,
+        file: PathBuf,
+        /// Setting passed to the plugin, repeatable
+        #[arg(long = "option", value_name = "KEY=VALUE", value_parser = parse_option)]
+        options: Vec<(String, String)>,
+    },
+    /// Sync the output file with a plugin, adding the entries it sends back
+    Sync {
+        name: String,
+        /// Setting passed to the plugin, repeatable
+        #[arg(long = "option", value_name = "KEY=VALUE", value_parser = parse_option)]
+        options: Vec<(String, String)>,
+    },
+}
+
+/// A time tracking service `sync` can push entries to on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncService {
+    Toggl,
+    Clockify,
+}
+
+impl FromStr for SyncService {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "toggl" => Ok(SyncService::Toggl),
+            "clockify" => Ok(SyncService::Clockify),
+            other => Err(format!(
+                "Unknown service '{}', expected toggl or clockify",
+                other
+            )),
+        }
+    }
+}
+
+fn parse_option(option: &str) -> Result<(String, String), String> {
+    option
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", option))
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the value of a setting, e.g. `output_file` or `profiles.work.rate`
+    Get { key: String },
+    /// Change a setting, checking that the new value is valid
+    Set {
+        key: String,
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+    },
+    /// Print all settings
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupAction {
+    /// Take a snapshot of the data directory and the output file and upload it
+    Now,
+    /// List the snapshots in the bucket, oldest first
+    List,
+    /// Download a snapshot and put its files back in place
+    Restore {
+        /// Snapshot to restore, as printed by `list`; the latest by default
+        name: Option<String>,
+    },
+    /// Remove the local backups and remote snapshots the `keep` settings no longer keep
+    Prune {
+        /// Only print what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
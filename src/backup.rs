@@ -0,0 +1,288 @@
+use crate::{config, error::ITrackerError, schema, snapshot::RemoteBackupConfig};
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Backups kept per log file unless configured otherwise.
+pub const DEFAULT_KEEP: usize = 10;
+
+/// How backup files are named, so they sort oldest first.
+const NAME_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// The `[backup]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Directory the backups are kept in, `backups` in the data directory by default.
+    pub dir: Option<String>,
+    /// Number of backups kept per log file, `0` turns backups off.
+    pub keep: Option<usize>,
+    /// Days whose last backup is kept besides the latest ones.
+    pub keep_daily: Option<usize>,
+    /// Weeks whose last backup is kept besides the latest ones.
+    pub keep_weekly: Option<usize>,
+    /// Encrypted snapshots uploaded to S3-compatible storage.
+    pub remote: Option<RemoteBackupConfig>,
+}
+
+/// Which of a series of backups are kept.
+///
+/// Besides the latest `last` backups, the latest backup of each of the last
+/// `daily` days and `weekly` (ISO) weeks that have backups is kept, so older
+/// states stay available at a coarser grain. Days and weeks are in UTC.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    pub last: usize,
+    pub daily: usize,
+    pub weekly: usize,
+}
+
+impl Retention {
+    /// Which backups to keep.
+    ///
+    /// # Arguments
+    /// - `times`: When the backups were taken, oldest first.
+    ///
+    /// # Returns
+    /// Whether to keep each backup, in the order of `times`.
+    pub fn select(&self, times: &[DateTime<Utc>]) -> Vec<bool> {
+        let mut keep = vec![false; times.len()];
+        let (mut days, mut weeks) = (Vec::new(), Vec::new());
+        for (position, time) in times.iter().enumerate().rev() {
+            let newest_first = times.len() - 1 - position;
+            let day = time.date_naive();
+            let week = time.iso_week();
+            if newest_first < self.last {
+                keep[position] = true;
+            }
+            if days.len() < self.daily && !days.contains(&day) {
+                days.push(day);
+                keep[position] = true;
+            }
+            if weeks.len() < self.weekly && !weeks.contains(&week) {
+                weeks.push(week);
+                keep[position] = true;
+            }
+        }
+        keep
+    }
+}
+
+/// Copies of log files taken before they are rewritten, e.g. by a delete.
+///
+/// Each log file gets its own subdirectory named after its absolute path,
+/// holding copies named by the time they were taken, so they sort oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backups {
+    dir: PathBuf,
+    retention: Retention,
+}
+
+impl Backups {
+    /// Keeps the latest `keep` backups of each log file, none if `0`.
+    pub fn new(dir: impl Into<PathBuf>, keep: usize) -> Self {
+        Backups {
+            dir: dir.into(),
+            retention: Retention {
+                last: keep,
+                ..Retention::default()
+            },
+        }
+    }
+
+    /// Also keeps the last backup of each of the last `daily` days and
+    /// `weekly` weeks, see [`Retention`].
+    pub fn with_history(mut self, daily: usize, weekly: usize) -> Self {
+        self.retention.daily = daily;
+        self.retention.weekly = weekly;
+        self
+    }
+
+    /// Resolves the `[backup]` section, using the defaults for unset values.
+    ///
+    /// # Returns
+    /// - `Ok(Backups)`: The backup settings.
+    /// - `Err(ITrackerError)`: If the default directory cannot be created.
+    pub fn from_config(config: Option<&BackupConfig>) -> Result<Self, ITrackerError> {
+        let keep = config.and_then(|c| c.keep).unwrap_or(DEFAULT_KEEP);
+        let dir = match config.and_then(|c| c.dir.as_deref()) {
+            Some(dir) => PathBuf::from(dir),
+            None => config::data_dir()?.join("backups"),
+        };
+        Ok(Backups::new(dir, keep).with_history(
+            config.and_then(|c| c.keep_daily).unwrap_or(0),
+            config.and_then(|c| c.keep_weekly).unwrap_or(0),
+        ))
+    }
+
+    /// Copies `log` (and its journal) into the backup directory and removes
+    /// the backups the retention policy no longer keeps.
+    ///
+    /// # Returns
+    /// - `Ok(Some(PathBuf))`: The new backup.
+    /// - `Ok(None)`: If backups are turned off or `log` does not exist yet.
+    /// - `Err(ITrackerError)`: If copying or cleaning up fails.
+    pub fn save(&self, log: impl AsRef<Path>) -> Result<Option<PathBuf>, ITrackerError> {
+        let log = log.as_ref();
+        if self.retention.last == 0 || !log.exists() {
+            return Ok(None);
+        }
+
+        let dir = self.dir_for(log)?;
+        fs::create_dir_all(&dir).map_err(ITrackerError::file(&dir))?;
+        // A backup taken in the same millisecond, e.g. by a restore, is kept
+        let mut taken = Utc::now();
+        let backup = loop {
+            let backup = dir.join(format!("{}.csv", taken.format(NAME_FORMAT)));
+            if !backup.exists() {
+                break backup;
+            }
+            taken += Duration::milliseconds(1);
+        };
+        copy_with_journal(log, &backup)?;
+        self.prune_dir(&dir, false)?;
+        Ok(Some(backup))
+    }
+
+    /// Removes the backups of every log file that the retention policy no
+    /// longer keeps, e.g. after it was tightened.
+    ///
+    /// # Arguments
+    /// - `dry_run`: Only report what would be removed.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<PathBuf>)`: The backups removed, oldest first per log file.
+    /// - `Err(ITrackerError)`: If the directory cannot be read or a backup
+    ///   cannot be removed.
+    pub fn prune(&self, dry_run: bool) -> Result<Vec<PathBuf>, ITrackerError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(ITrackerError::file(&self.dir)(e)),
+        };
+        let mut dirs = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(ITrackerError::file(&self.dir))?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+        dirs.sort();
+
+        let mut removed = Vec::new();
+        for dir in dirs {
+            removed.extend(self.prune_dir(&dir, dry_run)?);
+        }
+        Ok(removed)
+    }
+
+    /// Removes the backups in the directory of one log file that the
+    /// retention policy no longer keeps. Backups turned off keep none.
+    fn prune_dir(&self, dir: &Path, dry_run: bool) -> Result<Vec<PathBuf>, ITrackerError> {
+        let backups = backups_in(dir)?;
+        // Files not named by the time they were taken are left alone
+        let dated: Vec<(PathBuf, DateTime<Utc>)> = backups
+            .into_iter()
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_str()?;
+                let taken = NaiveDateTime::parse_from_str(name, NAME_FORMAT).ok()?;
+                Some((path, taken.and_utc()))
+            })
+            .collect();
+        let times: Vec<DateTime<Utc>> = dated.iter().map(|(_, taken)| *taken).collect();
+
+        let mut removed = Vec::new();
+        for ((path, _), keep) in dated.into_iter().zip(self.retention.select(&times)) {
+            if !keep {
+                if !dry_run {
+                    remove_with_journal(&path)?;
+                }
+                removed.push(path);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// The backups of `log`, oldest first.
+    pub fn list(&self, log: impl AsRef<Path>) -> Result<Vec<PathBuf>, ITrackerError> {
+        backups_in(&self.dir_for(log.as_ref())?)
+    }
+
+    /// Replaces `log` with one of its backups, backing up the current
+    /// contents first so the restore can be undone.
+    ///
+    /// # Arguments
+    /// - `log`: The log file to roll back.
+    /// - `backup`: A backup of `log` as returned by `list`.
+    pub fn restore(&self, log: impl AsRef<Path>, backup: &Path) -> Result<(), ITrackerError> {
+        let log = log.as_ref();
+        self.save(log)?;
+        copy_with_journal(backup, log)
+    }
+
+    /// The subdirectory holding the backups of `log`.
+    fn dir_for(&self, log: &Path) -> Result<PathBuf, ITrackerError> {
+        let absolute = std::path::absolute(log).map_err(ITrackerError::file(log))?;
+        let name: String = absolute
+            .to_string_lossy()
+            .trim_start_matches(['/', '\\'])
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Ok(self.dir.join(name))
+    }
+}
+
+/// The backups in the directory of one log file, oldest first.
+fn backups_in(dir: &Path) -> Result<Vec<PathBuf>, ITrackerError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(ITrackerError::file(dir)(e)),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(ITrackerError::file(dir))?.path();
+        if path.extension().is_some_and(|extension| extension == "csv") {
+            backups.push(path);
+        }
+    }
+    backups.sort();
+    Ok(backups)
+}
+
+/// Copies a log file along with its journal, or removes the journal at
+/// `to` if `from` has none. The files at `to` are replaced, not written
+/// over, see [`schema::replace`].
+pub(crate) fn copy_with_journal(from: &Path, to: &Path) -> Result<(), ITrackerError> {
+    schema::replace(to, &fs::read(from).map_err(ITrackerError::file(from))?)?;
+    let (from, to) = (schema::journal_path(from), schema::journal_path(to));
+    if from.exists() {
+        schema::replace(&to, &fs::read(&from).map_err(ITrackerError::file(&from))?)?;
+    } else {
+        remove_if_exists(&to)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn remove_with_journal(backup: &Path) -> Result<(), ITrackerError> {
+    remove_if_exists(backup)?;
+    remove_if_exists(&schema::journal_path(backup))
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), ITrackerError> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(ITrackerError::file(path)(e)),
+        _ => Ok(()),
+    }
+}
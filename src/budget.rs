@@ -0,0 +1,260 @@
+//! Weekly time budgets per project, e.g. at most 10 hours a week for a client,
+//! and monthly retainers of clients.
+//!
+//! Budgets are only checked, never enforced: starting and stopping a timer
+//! warns once a project used up its budget, and reports show how much of it
+//! each week consumed. Weeks follow `week_start`. Retainers count the billed
+//! time of a calendar month; timers warn from 80% of a retainer on, and
+//! `report --client` shows its burn-down.
+
+use crate::{
+    error::ITrackerError,
+    invoice::Billing,
+    locale,
+    log::LogEntry,
+    report::{self, Goal, Week},
+    timefmt::DurationFormat,
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+/// The budgets of the `[budgets]` section, in seconds per week.
+#[derive(Debug, Default, Clone)]
+pub struct Budgets {
+    limits: BTreeMap<String, u64>,
+    week: Week,
+}
+
+/// The time a project tracked in a week, measured against its budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub project: String,
+    /// Seconds tracked in the week, running timers included.
+    pub used: u64,
+    /// Seconds the project may use per week.
+    pub budget: u64,
+}
+
+impl Usage {
+    /// Whether the budget is used up; any more time exceeds it.
+    pub fn is_exhausted(&self) -> bool {
+        self.used >= self.budget
+    }
+
+    /// The usage as progress toward the budget, for its percentage and bar.
+    pub fn progress(&self) -> Goal {
+        Goal {
+            tracked: self.used,
+            target: self.budget,
+        }
+    }
+
+    /// The warning printed when a timer of an exhausted project starts or stops.
+    pub fn warning(&self, durations: DurationFormat) -> String {
+        format!(
+            "Warning: {} tracked {} this week, {}% of its weekly budget of {}.",
+            self.project,
+            durations.format(self.used),
+            self.progress().percent(),
+            durations.format(self.budget)
+        )
+    }
+}
+
+impl Budgets {
+    /// Resolves the `[budgets]` section.
+    ///
+    /// # Arguments
+    /// - `budgets`: Hours per week by project name.
+    /// - `week`: The week the budgets apply to.
+    ///
+    /// # Returns
+    /// - `Ok(Budgets)`: The budgets in seconds.
+    /// - `Err(ITrackerError)`: If a budget is not a positive number of hours.
+    pub fn from_config(budgets: &BTreeMap<String, f64>, week: Week) -> Result<Self, ITrackerError> {
+        let limits = budgets
+            .iter()
+            .map(|(project, hours)| {
+                if !(*hours > 0.0 && *hours <= 168.0) {
+                    return Err(ITrackerError::Config(format!(
+                        "The weekly budget of {} must be between 0 and 168 hours, got {}",
+                        project, hours
+                    )));
+                }
+                Ok((project.trim().to_string(), (hours * 3600.0).round() as u64))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Budgets { limits, week })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// The usage of `project` in the week containing `day`.
+    ///
+    /// # Returns
+    /// `None` if the project has no budget.
+    pub fn usage(
+        &self,
+        entries: &[LogEntry],
+        project: &str,
+        day: NaiveDate,
+        now: DateTime<Utc>,
+        tz: Tz,
+    ) -> Option<Usage> {
+        let project = project.trim();
+        let budget = *self.limits.get(project)?;
+        let (from, to) = self.week.range(day);
+        let used = report::entries_between(entries, from, to, tz)
+            .into_iter()
+            .filter(|entry| entry.project.trim() == project)
+            .map(|entry| report::tracked_seconds(entry, now))
+            .sum();
+        Some(Usage {
+            project: project.to_string(),
+            used,
+            budget,
+        })
+    }
+
+    /// The usage of every budgeted project in the week containing `day`.
+    pub fn week_usage(
+        &self,
+        entries: &[LogEntry],
+        day: NaiveDate,
+        now: DateTime<Utc>,
+        tz: Tz,
+    ) -> Vec<Usage> {
+        self.limits
+            .keys()
+            .filter_map(|project| self.usage(entries, project, day, now, tz))
+            .collect()
+    }
+
+    /// The first days of the weeks overlapping `from` to `to`.
+    pub fn weeks(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let mut weeks = Vec::new();
+        let mut day = self.week.range(from).0;
+        while day <= to {
+            weeks.push(day);
+            day = self.week.range(day).1 + Duration::days(1);
+        }
+        weeks
+    }
+}
+
+/// Percentages of a retainer from which timers warn.
+pub const RETAINER_ALERTS: [u64; 2] = [80, 100];
+
+/// The billed time of a client in one month, measured against its retainer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Retainer {
+    pub project: String,
+    /// The first day of the month.
+    pub month: NaiveDate,
+    /// Seconds the retainer covers.
+    pub budget: u64,
+    /// Billed seconds per day with entries, running timers included.
+    pub per_day: BTreeMap<NaiveDate, u64>,
+}
+
+impl Retainer {
+    /// The retainer of `project` in the month containing `day`.
+    ///
+    /// # Returns
+    /// `None` if the project has no retainer.
+    pub fn of(
+        entries: &[LogEntry],
+        project: &str,
+        day: NaiveDate,
+        billing: &Billing,
+        now: DateTime<Utc>,
+        tz: Tz,
+    ) -> Option<Self> {
+        let project = project.trim();
+        let budget = billing.retainer_for(project)?;
+        let (from, to) = month_range(day);
+        let mut per_day = BTreeMap::new();
+        for entry in report::entries_between(entries, from, to, tz) {
+            if entry.project.trim() != project {
+                continue;
+            }
+            let Some(date) = report::local_date(entry, tz) else {
+                continue;
+            };
+            let seconds = billing.billed_seconds(project, report::tracked_seconds(entry, now));
+            *per_day.entry(date).or_default() += seconds;
+        }
+        Some(Retainer {
+            project: project.to_string(),
+            month: from,
+            budget,
+            per_day,
+        })
+    }
+
+    /// The billed seconds of the month.
+    pub fn used(&self) -> u64 {
+        self.per_day.values().sum()
+    }
+
+    /// The usage as progress toward the retainer, for its percentage and bar.
+    pub fn progress(&self) -> Goal {
+        Goal {
+            tracked: self.used(),
+            target: self.budget,
+        }
+    }
+
+    /// The seconds used up to and including each day with entries, and the
+    /// seconds left of the retainer after it.
+    pub fn burn_down(&self) -> Vec<(NaiveDate, u64, u64)> {
+        let mut used = 0;
+        self.per_day
+            .iter()
+            .map(|(day, seconds)| {
+                used += seconds;
+                (*day, used, self.budget.saturating_sub(used))
+            })
+            .collect()
+    }
+
+    /// The highest of [`RETAINER_ALERTS`] the usage reached, `None` below all.
+    pub fn alert(&self) -> Option<u64> {
+        let percent = self.progress().percent();
+        RETAINER_ALERTS
+            .into_iter()
+            .rev()
+            .find(|alert| percent >= *alert)
+    }
+
+    /// The warning printed when a timer of the client starts or stops, `None`
+    /// below the first alert.
+    pub fn warning(&self, durations: DurationFormat) -> Option<String> {
+        let alert = self.alert()?;
+        let state = if alert >= 100 {
+            "used up"
+        } else {
+            "nearly used up"
+        };
+        Some(format!(
+            "Warning: the retainer of {} for {} is {}, {} of {} ({}%).",
+            self.project,
+            locale::month(self.month),
+            state,
+            durations.format(self.used()),
+            durations.format(self.budget),
+            self.progress().percent()
+        ))
+    }
+}
+
+/// The first and last day of the month containing `day`.
+pub fn month_range(day: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let first = day - Duration::days(i64::from(day.day0()));
+    let next_month = first + Duration::days(32);
+    let last = next_month - Duration::days(i64::from(next_month.day()));
+    (first, last)
+}
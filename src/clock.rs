@@ -0,0 +1,114 @@
+use chrono::{DateTime, Duration, Utc};
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
+
+/// Source of the current time for starting, pausing and stopping timers.
+///
+/// Timers take the time from a `Clock` instead of asking the system
+/// directly, so tests can control it with a [`MockClock`].
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock, never going back.
+///
+/// Every call reads the wall clock, so times stay right after the computer
+/// slept or the system time was corrected, e.g. in a daemon running for
+/// days. Should the wall clock go back (NTP, daylight saving mistakes,
+/// manual changes), the time advances monotonically from the latest time
+/// given instead, so elapsed times cannot go negative. Clones share that
+/// latest time.
+#[derive(Debug, Clone, Default)]
+pub struct SystemClock {
+    latest: Arc<Mutex<Option<Given>>>,
+}
+
+/// The latest time given and when it was given.
+type Given = (DateTime<Utc>, Instant);
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock::default()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        let wall = Utc::now();
+        let mut latest = self.latest.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = match *latest {
+            Some((time, at)) if wall < time => {
+                time + Duration::from_std(at.elapsed()).unwrap_or_default()
+            }
+            _ => wall,
+        };
+        *latest = Some((now, Instant::now()));
+        now
+    }
+}
+
+/// A fixed time is a clock standing still at it, e.g. for entries started
+/// at a time the user gave.
+impl Clock for DateTime<Utc> {
+    fn now(&self) -> DateTime<Utc> {
+        *self
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// Clones share the same time, so a test can keep one and advance it while
+/// a [`Tracker`](crate::Tracker) uses another:
+///
+/// ```no_run
+/// use chrono::{Duration, TimeZone, Utc};
+/// use itracker::{clock::MockClock, Storage, TaskDetails, Tracker};
+/// use std::sync::Arc;
+///
+/// let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap());
+/// let tracker = Tracker::new(Storage::new("time.csv")).with_clock(Arc::new(clock.clone()));
+/// let entry = tracker.start(&TaskDetails::default())?;
+/// clock.advance(Duration::minutes(90));
+/// assert_eq!(tracker.stop(entry.index)?.elapsed_time, "5400");
+/// # Ok::<(), itracker::ITrackerError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl MockClock {
+    /// A clock standing still at `now`.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Moves the clock to `now`, which may also be in the past.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.lock() = now;
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.lock() += duration;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DateTime<Utc>> {
+        // A panic while holding the lock cannot leave the time half written
+        self.now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.lock()
+    }
+}
@@ -0,0 +1,251 @@
+//! Pushing entries to Clockify through its v1 API.
+//!
+//! Works like the Toggl push, see [`crate::toggl`]: `sync clockify` creates
+//! a time entry for every stopped entry not pushed before and keeps its ID
+//! under `clockify` in the entry's metadata. The workspace is given by ID or
+//! name, the active workspace of the key's user by default. Projects and
+//! tags are found by name and created if missing, since Clockify only takes
+//! their IDs. Requests Clockify turns down for its rate limit are retried
+//! after the wait it asks for, or after a growing pause.
+
+use crate::{error::ITrackerError, log::LogEntry, report};
+use chrono::{Duration, SecondsFormat, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, thread, time};
+
+/// The API entries are pushed to unless `clockify.api_url` is set.
+pub const DEFAULT_API_URL: &str = "https://api.clockify.me/api/v1";
+
+/// Metadata key holding the ID of the Clockify time entry an entry was pushed as.
+pub const METADATA_KEY: &str = "clockify";
+
+/// Attempts of a request before a rate limit fails the push.
+const ATTEMPTS: u32 = 5;
+
+/// The `[clockify]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockifyConfig {
+    /// API key of the profile settings, better given as `ITRACKER_CLOCKIFY__API_KEY`.
+    pub api_key: Option<String>,
+    /// ID or name of the workspace entries are created in, the active
+    /// workspace of the key's user if unset.
+    pub workspace: Option<String>,
+    /// Base URL of the API, e.g. of a regional or self-hosted server.
+    pub api_url: Option<String>,
+}
+
+/// A workspace, project or tag: Clockify names everything by ID.
+#[derive(Debug, Deserialize)]
+struct Named {
+    id: String,
+    name: String,
+}
+
+/// A Clockify account entries are pushed to.
+#[derive(Debug, Clone)]
+pub struct Clockify {
+    /// Without a trailing slash.
+    api_url: String,
+    api_key: String,
+    workspace: Option<String>,
+}
+
+impl Clockify {
+    /// Resolves the `[clockify]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Clockify))`: If an API key is configured.
+    /// - `Ok(None)`: If entries are not pushed to Clockify.
+    /// - `Err(ITrackerError)`: `Config` if the API URL is not an http(s) URL.
+    pub fn from_config(config: Option<&ClockifyConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        let Some(api_key) = config.api_key.as_deref().filter(|key| !key.is_empty()) else {
+            return Ok(None);
+        };
+        let api_url = config.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+        if !(api_url.starts_with("https://") || api_url.starts_with("http://")) {
+            return Err(ITrackerError::Config(format!(
+                "api_url must start with https:// or http://, got {}",
+                api_url
+            )));
+        }
+        Ok(Some(Clockify {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            workspace: config
+                .workspace
+                .as_deref()
+                .map(str::trim)
+                .filter(|workspace| !workspace.is_empty())
+                .map(str::to_string),
+        }))
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Creates a time entry for every stopped entry without a Clockify ID.
+    ///
+    /// # Arguments
+    /// - `entries`: The entries of the output file.
+    /// - `pushed`: Called with each entry and its new Clockify ID right
+    ///   after it was created, to remember the ID even if a later entry fails.
+    ///
+    /// # Returns
+    /// - `Ok(usize)`: The number of entries pushed.
+    /// - `Err(ITrackerError)`: `Sync` if Clockify cannot be reached, refuses
+    ///   an entry or keeps limiting the rate, or the error of `pushed`.
+    pub fn push(
+        &self,
+        entries: &[LogEntry],
+        mut pushed: impl FnMut(&LogEntry, &str) -> Result<(), ITrackerError>,
+    ) -> Result<usize, ITrackerError> {
+        let pending: Vec<&LogEntry> = entries
+            .iter()
+            .filter(|entry| !entry.is_running() && entry.start().is_some())
+            .filter(|entry| !entry.metadata.contains_key(METADATA_KEY))
+            .collect();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        let workspace = self.workspace()?;
+        let base = format!("/workspaces/{}", workspace);
+        let mut projects = self.names(&format!("{}/projects?page-size=5000", base))?;
+        let mut tags = self.names(&format!("{}/tags?page-size=5000", base))?;
+
+        for entry in &pending {
+            let project = match entry.project.trim() {
+                "" => None,
+                name => Some(self.resolve(&mut projects, &format!("{}/projects", base), name)?),
+            };
+            let tag_ids = entry
+                .tags
+                .iter()
+                .map(|tag| self.resolve(&mut tags, &format!("{}/tags", base), tag))
+                .collect::<Result<Vec<_>, _>>()?;
+            let start = entry
+                .start()
+                .map(|start| start.with_timezone(&Utc))
+                .unwrap_or_default();
+            let end = start + Duration::seconds(report::tracked_seconds(entry, Utc::now()) as i64);
+            let created: Named = self.request(
+                "POST",
+                &format!("{}/time-entries", base),
+                Some(&json!({
+                    "start": start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    "end": end.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    "description": entry.display_title(),
+                    "projectId": project,
+                    "tagIds": tag_ids,
+                })),
+            )?;
+            pushed(entry, &created.id)?;
+        }
+        Ok(pending.len())
+    }
+
+    /// The ID of the configured workspace, or of the user's active one.
+    fn workspace(&self) -> Result<String, ITrackerError> {
+        let Some(wanted) = &self.workspace else {
+            let user: Value = self.request("GET", "/user", None)?;
+            return user["activeWorkspace"]
+                .as_str()
+                .or(user["defaultWorkspace"].as_str())
+                .map(str::to_string)
+                .ok_or_else(|| self.error("the user has no workspace, set `clockify.workspace`"));
+        };
+        let workspaces: Vec<Named> = self.request("GET", "/workspaces", None)?;
+        workspaces
+            .into_iter()
+            .find(|workspace| {
+                workspace.id == *wanted || workspace.name.eq_ignore_ascii_case(wanted)
+            })
+            .map(|workspace| workspace.id)
+            .ok_or_else(|| self.error(format!("no workspace {} for this API key", wanted)))
+    }
+
+    /// The IDs of the projects or tags at `path` by lowercase name.
+    fn names(&self, path: &str) -> Result<HashMap<String, String>, ITrackerError> {
+        let named: Vec<Named> = self.request("GET", path, None)?;
+        Ok(named
+            .into_iter()
+            .map(|named| (named.name.to_lowercase(), named.id))
+            .collect())
+    }
+
+    /// The ID of `name` in `known`, creating it at `path` if missing.
+    fn resolve(
+        &self,
+        known: &mut HashMap<String, String>,
+        path: &str,
+        name: &str,
+    ) -> Result<String, ITrackerError> {
+        if let Some(id) = known.get(&name.to_lowercase()) {
+            return Ok(id.clone());
+        }
+        let created: Named = self.request("POST", path, Some(&json!({ "name": name })))?;
+        known.insert(name.to_lowercase(), created.id.clone());
+        Ok(created.id)
+    }
+
+    /// Sends a request, waiting and retrying while the rate is limited.
+    fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<T, ITrackerError> {
+        let url = format!("{}{}", self.api_url, path);
+        let mut attempt = 0;
+        let response = loop {
+            let request = ureq::request(method, &url).set("X-Api-Key", &self.api_key);
+            let result = match body {
+                Some(body) => request
+                    .set("Content-Type", "application/json")
+                    .send_string(&body.to_string()),
+                None => request.call(),
+            };
+            attempt += 1;
+            match result {
+                Err(ureq::Error::Status(429, response)) if attempt < ATTEMPTS => {
+                    // Clockify names the wait in seconds, else back off 1s, 2s, 4s, ...
+                    let wait = response
+                        .header("Retry-After")
+                        .and_then(|seconds| seconds.trim().parse().ok())
+                        .unwrap_or(1 << (attempt - 1));
+                    thread::sleep(time::Duration::from_secs(wait));
+                }
+                result => break result.map_err(|e| self.failure(e))?,
+            }
+        };
+        let body = response.into_string().map_err(|e| self.error(e))?;
+        serde_json::from_str(&body).map_err(|e| self.error(format!("unexpected answer: {}", e)))
+    }
+
+    /// Describes a failed request, with Clockify's own message if it sent one.
+    fn failure(&self, error: ureq::Error) -> ITrackerError {
+        match error {
+            ureq::Error::Status(status, response) => {
+                let message = response
+                    .into_string()
+                    .ok()
+                    .and_then(|body| serde_json::from_str::<Value>(&body).ok())
+                    .and_then(|body| body["message"].as_str().map(str::to_string));
+                match message {
+                    Some(message) => self.error(format!("HTTP status {}: {}", status, message)),
+                    None => self.error(format!("HTTP status {}", status)),
+                }
+            }
+            e => self.error(e),
+        }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> ITrackerError {
+        ITrackerError::Sync(format!("{}: {}", self.api_url, message))
+    }
+}
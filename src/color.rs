@@ -0,0 +1,88 @@
+//! Colors of the command line output, chosen with `--color`.
+//!
+//! `auto` colors output written to a terminal unless `NO_COLOR` is set (see
+//! <https://no-color.org>), `always` and `never` force the choice. Only
+//! plain ANSI styles are used, so the terminal's palette decides the shades.
+
+use std::{io::IsTerminal, str::FromStr, sync::OnceLock};
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// When output is colored, the value of `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(format!(
+                "Unknown color choice '{}', expected auto, always or never",
+                other
+            )),
+        }
+    }
+}
+
+/// How a piece of output is styled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paint {
+    /// A running timer, green.
+    Running,
+    /// A paused timer, yellow.
+    Paused,
+    /// Entries of earlier days and empty days, dimmed.
+    Old,
+    /// Headings and totals, bold.
+    Heading,
+    /// Budgets exceeded and retainers nearly used up, red.
+    Exceeded,
+}
+
+impl Paint {
+    fn code(self) -> &'static str {
+        match self {
+            Paint::Running => "32",
+            Paint::Paused => "33",
+            Paint::Old => "2",
+            Paint::Heading => "1",
+            Paint::Exceeded => "31",
+        }
+    }
+}
+
+/// Decides once per run whether stdout is colored.
+pub fn select(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            std::io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        }
+    };
+    let _ = ENABLED.set(enabled);
+}
+
+/// Whether output is colored, `false` until [`select`] decided.
+pub fn enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+/// `text` in the style of `paint`, unchanged while colors are off.
+pub fn paint(text: impl AsRef<str>, paint: Paint) -> String {
+    let text = text.as_ref();
+    if !enabled() || text.is_empty() {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{}\x1b[0m", paint.code(), text)
+}
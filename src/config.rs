@@ -1,51 +1,839 @@
-use config::{Config, File, FileFormat};
+use crate::{
+    activity::{ActivityConfig, ActivityTracker},
+    backup::BackupConfig,
+    budget::Budgets,
+    clockify::{Clockify, ClockifyConfig},
+    crdt::{CrdtConfig, CrdtSync},
+    crypto::EncryptionConfig,
+    currency::{CurrenciesConfig, Currency, Exchange},
+    error::ITrackerError,
+    hooks::HooksConfig,
+    i18n,
+    icons::{Icons, IconsConfig},
+    idle::{IdleConfig, IdleDetector},
+    invoice::{Billing, ClientConfig, TaxConfig},
+    keys::{Keymap, KeysConfig},
+    locale::Locale,
+    month,
+    notify::{NotificationsConfig, Notifier},
+    plugin::PluginsConfig,
+    repo,
+    report::Week,
+    rounding::{Rounding, RoundingConfig},
+    snapshot::RemoteBackups,
+    sync::SyncConfig,
+    team::{TeamClient, TeamConfig, TeamServer},
+    template::Template,
+    theme::{Theme, ThemeConfig},
+    timefmt::{DurationFormat, TimeFormat},
+    toggl::{Toggl, TogglConfig},
+    webdav::{WebDav, WebDavConfig},
+};
+use chrono_tz::Tz;
+use config::{Config, Environment, File, FileFormat};
+use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Name of the configuration file inside the config directory.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Per-directory project file, searched upward from the current directory.
+pub const PROJECT_FILE: &str = ".itracker.toml";
+
+/// Config files read from the current directory by earlier versions.
+const LEGACY_CONFIG_FILES: [&str; 2] = ["config", "config.toml"];
 
 /// Structure representing the configuration data.
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct ConfigData {
     /// Optional output file path.
     pub output_file: Option<String>,
+    /// Optional timezone for logging, overridden by `--timezone`.
+    pub timezone: Option<String>,
+    /// How timestamps are displayed: `rfc2822`, `iso`, `rfc3339` or a strftime pattern.
+    pub time_format: Option<String>,
+    /// How durations are displayed: `human`, `iso`, `decimal` or `clock`.
+    pub duration_format: Option<String>,
+    /// Language of messages, e.g. `de`; the locale of `LANG` if unset.
+    pub language: Option<String>,
+    /// How numbers, amounts, dates and times are written, e.g. `de_DE`;
+    /// the locale of `LC_ALL`, `LC_NUMERIC` or `LANG` if unset.
+    pub locale: Option<String>,
+    /// Output templates by name for `--template`, e.g.
+    /// `bar = "{{icon}} {{title}} {{elapsed}}"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, String>,
+    /// Project prefilled when adding a task.
+    pub default_project: Option<String>,
+    /// Description prefilled when adding a task inside a git repository,
+    /// with `{repo}`, `{branch}` and `{path}` filled in; empty turns it off.
+    pub task_template: Option<String>,
+    /// Hourly rate used for billing.
+    pub rate: Option<f64>,
+    /// Currency of `rate`, e.g. `EUR`.
+    pub currency: Option<String>,
+    /// Rates and currencies of single projects (clients), overriding `rate` and `currency`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub clients: BTreeMap<String, ClientConfig>,
+    /// The currency reports convert amounts into, and the exchange rates to it.
+    pub currencies: Option<CurrenciesConfig>,
+    /// Tax of `[taxes]` charged on invoices of clients that select none.
+    pub tax: Option<String>,
+    /// Your tax ID, e.g. a VAT number, printed on invoices.
+    pub tax_id: Option<String>,
+    /// Taxes charged on invoices by name, e.g. the VAT of a jurisdiction.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub taxes: BTreeMap<String, TaxConfig>,
+    /// First day of the week, e.g. `monday` or `sunday`.
+    pub week_start: Option<String>,
+    /// Days that count as workdays, e.g. `["mon", "tue", "wed", "thu", "fri"]`.
+    pub workdays: Option<Vec<String>>,
+    /// Hours to track per day, shown as progress by `status`, `report` and the viewer.
+    pub daily_goal_hours: Option<f64>,
+    /// Hours of a workday; time tracked beyond it counts as overtime in reports.
+    pub workday_hours: Option<f64>,
+    /// Threads reports read and aggregate the log with, all cores if unset or `0`.
+    pub report_threads: Option<usize>,
+    /// Hours per week each project may use, e.g. `acme = 10`; exceeding one only warns.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub budgets: BTreeMap<String, f64>,
+    /// Months (`YYYY-MM`) closed with `close-month`; their entries can no longer be changed.
+    #[serde(default)]
+    pub locked_months: Vec<String>,
+    /// How durations are rounded in reports, globally and per project.
+    pub rounding: Option<RoundingConfig>,
+    /// Where log files are backed up before they are rewritten, and how many backups are kept.
+    pub backup: Option<BackupConfig>,
+    /// The passphrase or key file log files are encrypted with.
+    pub encryption: Option<EncryptionConfig>,
+    /// Which timer events the daemon shows desktop notifications for.
+    pub notifications: Option<NotificationsConfig>,
+    /// Shell commands run when a timer starts, pauses, resumes or stops.
+    pub hooks: Option<HooksConfig>,
+    /// The git remote the data directory is synced with by `sync`.
+    pub sync: Option<SyncConfig>,
+    /// The WebDAV server the output file is synced with by `sync`.
+    pub webdav: Option<WebDavConfig>,
+    /// The shared folder the output file is synced through by `sync`.
+    pub crdt: Option<CrdtConfig>,
+    /// The team server entries are reported to, or the users of `--server`.
+    pub team: Option<TeamConfig>,
+    /// The Toggl Track account `sync` pushes entries to.
+    pub toggl: Option<TogglConfig>,
+    /// The Clockify account `sync` pushes entries to.
+    pub clockify: Option<ClockifyConfig>,
+    /// Where plugins are found, and which of them `sync` runs.
+    pub plugins: Option<PluginsConfig>,
+    /// Whether the daemon records the focused window, and how often.
+    pub activity: Option<ActivityConfig>,
+    /// Whether the daemon pauses the running timer while the user is away, and after how long.
+    pub idle: Option<IdleConfig>,
+    /// Optional TUI color theme.
+    pub theme: Option<ThemeConfig>,
+    /// Symbols of running, paused and stopped timers.
+    pub icons: Option<IconsConfig>,
+    /// Optional TUI key bindings.
+    pub keys: Option<KeysConfig>,
+    /// Named profiles selected with `--profile`, overriding the settings above.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// Name of the profile selected for this run.
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+    /// Settings of the `.itracker.toml` found for the current directory.
+    #[serde(skip)]
+    pub project: Option<ProjectConfig>,
+    /// Timezone given with `--timezone`, taking precedence over the settings.
+    #[serde(skip)]
+    pub timezone_override: Option<Tz>,
+}
+
+/// A `.itracker.toml` file, taking precedence over profiles and top-level settings.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Project of tasks added below this directory.
+    pub project: Option<String>,
+    /// Tags of tasks added below this directory.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Output file, relative to the directory of the `.itracker.toml`.
+    pub output_file: Option<String>,
+}
+
+/// A `[profiles.<name>]` section; unset values fall back to the top-level settings.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub output_file: Option<String>,
+    pub timezone: Option<String>,
+    pub default_project: Option<String>,
+    pub rate: Option<f64>,
+    pub currency: Option<String>,
+}
+
+impl ConfigData {
+    /// Selects the profile whose settings take precedence for this run.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: If no profile with this name is configured.
+    pub fn select_profile(&mut self, name: &str) -> Result<(), ITrackerError> {
+        if !self.profiles.contains_key(name) {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            return Err(ITrackerError::Config(format!(
+                "Unknown profile '{}', configured profiles: {}",
+                name,
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            )));
+        }
+        self.active_profile = Some(name.to_string());
+        Ok(())
+    }
+
+    fn profile(&self) -> Option<&ProfileConfig> {
+        self.profiles.get(self.active_profile.as_deref()?)
+    }
+
+    /// The output file of the project file, the active profile, or the top-level one.
+    pub fn output_file(&self) -> Option<&str> {
+        self.project
+            .as_ref()
+            .and_then(|project| project.output_file.as_deref())
+            .or(self
+                .profile()
+                .and_then(|profile| profile.output_file.as_deref()))
+            .or(self.output_file.as_deref())
+    }
+
+    /// Sets the output file of the active profile, or the top-level one without a profile.
+    pub fn set_output_file(&mut self, file: String) {
+        let profile = self.active_profile.clone();
+        match profile.and_then(|name| self.profiles.get_mut(&name)) {
+            Some(profile) => profile.output_file = Some(file),
+            None => self.output_file = Some(file),
+        }
+    }
+
+    /// The project of the project file, the active profile's default, or the top-level one.
+    pub fn default_project(&self) -> Option<&str> {
+        self.project
+            .as_ref()
+            .and_then(|project| project.project.as_deref())
+            .or(self
+                .profile()
+                .and_then(|profile| profile.default_project.as_deref()))
+            .or(self.default_project.as_deref())
+    }
+
+    /// The description of tasks added inside a git repository, see
+    /// [`crate::repo`]; `None` if `task_template` is empty.
+    pub fn task_template(&self) -> Option<&str> {
+        match self.task_template.as_deref() {
+            Some(template) if template.trim().is_empty() => None,
+            Some(template) => Some(template),
+            None => Some(repo::DEFAULT_TEMPLATE),
+        }
+    }
+
+    /// The hourly rate of the active profile or the top-level one.
+    pub fn rate(&self) -> Option<f64> {
+        self.profile()
+            .and_then(|profile| profile.rate)
+            .or(self.rate)
+    }
+
+    /// The currency of the active profile or the top-level one.
+    pub fn currency(&self) -> Option<&str> {
+        self.profile()
+            .and_then(|profile| profile.currency.as_deref())
+            .or(self.currency.as_deref())
+    }
+
+    /// The rates projects are billed at, per client and by default, and the taxes charged.
+    pub fn billing(&self) -> Result<Billing, ITrackerError> {
+        Billing::from_config(
+            self.rate(),
+            self.currency(),
+            &self.clients,
+            Rounding::from_config(self.rounding.as_ref())?,
+        )?
+        .with_taxes(&self.taxes, self.tax.as_deref(), self.tax_id.as_deref())
+    }
+
+    /// The conversion of amounts into the reporting currency of `[currencies]`.
+    pub fn exchange(&self) -> Result<Exchange, ITrackerError> {
+        Exchange::from_config(self.currencies.as_ref())
+    }
+
+    /// The week used by the week view and per-week totals, Monday to Friday by default.
+    pub fn week(&self) -> Week {
+        Week::from_config(self.week_start.as_deref(), self.workdays.as_deref()).unwrap_or_default()
+    }
+
+    /// The daily goal in seconds, `None` unless one is configured.
+    pub fn daily_goal(&self) -> Option<u64> {
+        self.daily_goal_hours
+            .filter(|hours| *hours > 0.0 && hours.is_finite())
+            .map(|hours| (hours * 3600.0).round() as u64)
+    }
+
+    /// The length of a workday in seconds, `None` unless one is configured.
+    pub fn workday(&self) -> Option<u64> {
+        self.workday_hours
+            .filter(|hours| *hours > 0.0 && hours.is_finite())
+            .map(|hours| (hours * 3600.0).round() as u64)
+    }
+
+    /// The threads reports use, `0` for one per core.
+    pub fn report_threads(&self) -> usize {
+        self.report_threads.unwrap_or(0)
+    }
+
+    /// The weekly budgets per project, in the configured week.
+    pub fn budgets(&self) -> Result<Budgets, ITrackerError> {
+        Budgets::from_config(&self.budgets, self.week())
+    }
+
+    /// How durations are displayed, `human` unless configured otherwise.
+    pub fn duration_format(&self) -> DurationFormat {
+        DurationFormat::from_config(self.duration_format.as_deref()).unwrap_or_default()
+    }
+
+    /// The timezone used to display times and to count days: `--timezone`,
+    /// the active profile's, the top-level one, or UTC.
+    ///
+    /// Stored times stay in UTC regardless.
+    pub fn timezone(&self) -> Tz {
+        self.timezone_override
+            .or_else(|| {
+                self.profile()
+                    .and_then(|profile| profile.timezone.as_deref())
+                    .or(self.timezone.as_deref())
+                    .and_then(|name| parse_timezone(name).ok())
+            })
+            .unwrap_or(Tz::UTC)
+    }
+
+    /// Tags prefilled when adding a task, set by the project file.
+    pub fn default_tags(&self) -> &[String] {
+        self.project
+            .as_ref()
+            .map(|project| project.tags.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// Parses an IANA timezone name such as `Europe/Berlin` or `UTC`.
+pub fn parse_timezone(name: &str) -> Result<Tz, ITrackerError> {
+    name.parse::<Tz>().map_err(|_| {
+        ITrackerError::Config(format!(
+            "Unknown timezone '{}', expected an IANA name like Europe/Berlin or UTC",
+            name
+        ))
+    })
+}
+
+/// Searches `dir` and its parents for a `.itracker.toml` and reads the nearest one.
+///
+/// # Arguments
+/// - `dir`: The directory to start from, usually the current directory.
+///
+/// # Returns
+/// - `Ok(Some(ProjectConfig))`: The project settings, with the output file resolved
+///   against the directory of the file.
+/// - `Ok(None)`: If no project file exists up to the root.
+/// - `Err(ITrackerError)`: If the nearest file cannot be read or parsed.
+pub fn find_project_config(dir: &Path) -> Result<Option<ProjectConfig>, ITrackerError> {
+    let Some(path) = dir
+        .ancestors()
+        .map(|ancestor| ancestor.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+    else {
+        return Ok(None);
+    };
+
+    let text = fs::read_to_string(&path).map_err(ITrackerError::file(&path))?;
+    let mut project: ProjectConfig = toml::from_str(&text)
+        .map_err(|e| ITrackerError::Config(format!("Invalid {}: {}", path.display(), e)))?;
+    if let (Some(file), Some(root)) = (&project.output_file, path.parent()) {
+        project.output_file = Some(root.join(file).to_string_lossy().into_owned());
+    }
+
+    Ok(Some(project))
+}
+
+/// Platform directories of the application, `None` if no home directory is known.
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "itracker")
+}
+
+/// Path of the configuration file, e.g. `$XDG_CONFIG_HOME/itracker/config.toml` on Linux.
+///
+/// Falls back to `config.toml` in the current directory if no home directory is known.
+pub fn config_path() -> PathBuf {
+    match project_dirs() {
+        Some(dirs) => dirs.config_dir().join(CONFIG_FILE),
+        None => PathBuf::from(CONFIG_FILE),
+    }
+}
+
+/// Directory for data files such as the default log, e.g. `$XDG_DATA_HOME/itracker` on Linux.
+///
+/// The directory is created if it does not exist yet.
+pub fn data_dir() -> Result<PathBuf, ITrackerError> {
+    let dir = match project_dirs() {
+        Some(dirs) => dirs.data_dir().to_path_buf(),
+        None => PathBuf::from("."),
+    };
+    fs::create_dir_all(&dir).map_err(ITrackerError::file(&dir))?;
+    Ok(dir)
 }
 
-/// Loads the configuration from the specified config file.
+/// Directory for runtime files such as the daemon socket, e.g.
+/// `$XDG_RUNTIME_DIR/itracker` on Linux, or the data directory where there is none.
 ///
-/// This function attempts to read a TOML configuration file named `config`
-/// (or `config.toml` if the specified file is required) and deserialize it
-/// into a `ConfigData` struct. If the file does not exist, it will return
-/// an error unless the file is marked as optional.
+/// The directory is created if it does not exist yet.
+pub fn runtime_dir() -> Result<PathBuf, ITrackerError> {
+    let Some(dir) = project_dirs().and_then(|dirs| dirs.runtime_dir().map(Path::to_path_buf))
+    else {
+        return data_dir();
+    };
+    fs::create_dir_all(&dir).map_err(ITrackerError::file(&dir))?;
+    Ok(dir)
+}
+
+/// The config file in effect: the one at `config_path()`, or a file left in the
+/// current directory by earlier versions if only that one exists.
+pub fn existing_config_file() -> Option<PathBuf> {
+    let path = config_path();
+    if path.is_file() {
+        return Some(path);
+    }
+    LEGACY_CONFIG_FILES
+        .iter()
+        .map(PathBuf::from)
+        .find(|file| file.is_file())
+}
+
+/// Checks the contents of a config file without applying the environment.
+///
+/// # Arguments
+/// - `text`: The TOML contents.
+/// - `file`: Where they come from, for the error message.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` naming the offending key and line.
+pub fn check_config(text: &str, file: &Path) -> Result<(), ITrackerError> {
+    let from_file = toml::from_str::<ConfigData>(text).map_err(|e| {
+        ITrackerError::Config(format!("Invalid config file {}: {}", file.display(), e))
+    })?;
+    from_file.validate().map_err(|problem| {
+        let location = match line_of(text, &problem.key) {
+            Some(line) => format!("{} at line {}", file.display(), line),
+            None => file.display().to_string(),
+        };
+        ITrackerError::Config(format!(
+            "Invalid config value `{}` in {}: {}",
+            problem.key, location, problem.message
+        ))
+    })
+}
+
+/// Loads the configuration from the config file.
+///
+/// This function reads the TOML file at `config_path()` and deserializes it
+/// into a `ConfigData` struct. A missing file yields the default configuration.
+/// If only a config file from an earlier version exists in the current
+/// directory, that one is read instead and the next save moves it to
+/// `config_path()`.
+///
+/// Environment variables prefixed with `ITRACKER_` override the file, e.g.
+/// `ITRACKER_OUTPUT_FILE`. Nested keys use a double underscore
+/// (`ITRACKER_THEME__ACCENT`) and lists are comma separated
+/// (`ITRACKER_LOCKED_MONTHS=2024-05,2024-06`).
 ///
 /// # Returns
 /// - `Ok(ConfigData)`: The loaded configuration data if successful.
-/// - `Err(Box<dyn std::error::Error>)`: An error if the loading or deserialization fails.
-pub fn load_config() -> Result<ConfigData, Box<dyn std::error::Error>> {
-    let config = Config::builder()
-        .add_source(File::new("config", FileFormat::Toml).required(false))
-        .build()?
-        .try_deserialize::<ConfigData>()?;
+/// - `Err(ITrackerError)`: An error if the loading or deserialization fails.
+pub fn load_config() -> Result<ConfigData, ITrackerError> {
+    let file = existing_config_file();
+    if let Some(legacy) = file.as_ref().filter(|file| **file != config_path()) {
+        eprintln!(
+            "Reading {} from the current directory, it will be saved to {} from now on.",
+            legacy.display(),
+            config_path().display()
+        );
+    }
+
+    // Check the file on its own first, toml errors point at the offending line
+    if let Some(file) = &file {
+        let text = fs::read_to_string(file).map_err(ITrackerError::file(file))?;
+        check_config(&text, file)?;
+    }
+
+    let environment = Environment::with_prefix("ITRACKER")
+        .prefix_separator("_")
+        .separator("__")
+        .try_parsing(true)
+        .list_separator(",")
+        .with_list_parse_key("locked_months")
+        .with_list_parse_key("workdays");
+
+    let mut builder = Config::builder();
+    if let Some(file) = &file {
+        builder = builder.add_source(File::from(file.as_path()).format(FileFormat::Toml));
+    }
+    let config = builder
+        .add_source(environment)
+        .build()
+        .and_then(|config| config.try_deserialize::<ConfigData>())
+        .map_err(|e| {
+            ITrackerError::Config(format!("Invalid configuration from the environment: {}", e))
+        })?;
+
+    // The file was fine on its own, so remaining problems come from ITRACKER_* variables
+    config.validate().map_err(|problem| {
+        ITrackerError::Config(format!(
+            "Invalid config value `{}` from the environment: {}",
+            problem.key, problem.message
+        ))
+    })?;
 
     Ok(config)
 }
 
+/// A config value that parsed but makes no sense, named by its dotted key.
+struct Problem {
+    key: String,
+    message: String,
+}
+
+impl ConfigData {
+    /// Checks values the types alone cannot, e.g. positive rates and existing directories.
+    fn validate(&self) -> Result<(), Problem> {
+        let problem = |key: &str, message: String| Problem {
+            key: key.to_string(),
+            message,
+        };
+
+        validate_output_file("output_file", self.output_file.as_deref())?;
+        validate_rate("rate", self.rate)?;
+        Exchange::from_config(self.currencies.as_ref())
+            .map_err(|e| problem("currencies", e.to_string()))?;
+        validate_timezone("timezone", self.timezone.as_deref())?;
+        if let Some(format) = &self.time_format {
+            TimeFormat::from_config(Some(format))
+                .map_err(|e| problem("time_format", e.to_string()))?;
+        }
+        DurationFormat::from_config(self.duration_format.as_deref())
+            .map_err(|e| problem("duration_format", e.to_string()))?;
+        if let Some(language) = &self.language {
+            i18n::validate(language).map_err(|e| problem("language", e.to_string()))?;
+        }
+        if let Some(locale) = &self.locale {
+            locale
+                .parse::<Locale>()
+                .map_err(|e| problem("locale", e.to_string()))?;
+        }
+        for (name, value) in &self.templates {
+            Template::parse(value)
+                .map_err(|e| problem(&format!("templates.{}", name), e.to_string()))?;
+        }
+        for month in &self.locked_months {
+            month::parse_month(month).map_err(|e| problem("locked_months", e.to_string()))?;
+        }
+        Week::from_config(self.week_start.as_deref(), None)
+            .map_err(|e| problem("week_start", e.to_string()))?;
+        Week::from_config(None, self.workdays.as_deref())
+            .map_err(|e| problem("workdays", e.to_string()))?;
+        validate_hours_per_day("daily_goal_hours", self.daily_goal_hours)?;
+        validate_hours_per_day("workday_hours", self.workday_hours)?;
+        Budgets::from_config(&self.budgets, Week::default())
+            .map_err(|e| problem("budgets", e.to_string()))?;
+        let rounding = Rounding::from_config(self.rounding.as_ref())
+            .map_err(|e| problem("rounding", e.to_string()))?;
+        Billing::from_config(self.rate, self.currency.as_deref(), &self.clients, rounding)
+            .map_err(|e| problem("clients", e.to_string()))?;
+        Notifier::from_config(self.notifications.as_ref())
+            .map_err(|e| problem("notifications", e.to_string()))?;
+        if let Some(encryption) = &self.encryption {
+            encryption
+                .key()
+                .map_err(|e| problem("encryption", e.to_string()))?;
+        }
+        RemoteBackups::from_config(self.backup.as_ref().and_then(|b| b.remote.as_ref()))
+            .map_err(|e| problem("backup.remote", e.to_string()))?;
+        WebDav::from_config(self.webdav.as_ref())
+            .map_err(|e| problem("webdav.url", e.to_string()))?;
+        CrdtSync::from_config(self.crdt.as_ref())
+            .map_err(|e| problem("crdt.dir", e.to_string()))?;
+        TeamClient::from_config(self.team.as_ref()).map_err(|e| problem("team", e.to_string()))?;
+        Toggl::from_config(self.toggl.as_ref())
+            .map_err(|e| problem("toggl.api_url", e.to_string()))?;
+        Clockify::from_config(self.clockify.as_ref())
+            .map_err(|e| problem("clockify.api_url", e.to_string()))?;
+        if self.team.as_ref().is_some_and(|team| team.users.is_some()) {
+            TeamServer::from_config(self.team.as_ref(), PathBuf::new())
+                .map_err(|e| problem("team.users", e.to_string()))?;
+        }
+        ActivityTracker::from_config(self.activity.as_ref())
+            .map_err(|e| problem("activity.interval_seconds", e.to_string()))?;
+        IdleDetector::from_config(self.idle.as_ref())
+            .map_err(|e| problem("idle", e.to_string()))?;
+        Theme::from_config(self.theme.as_ref()).map_err(|e| problem("theme", e.to_string()))?;
+        Icons::from_config(self.icons.as_ref()).map_err(|e| problem("icons", e.to_string()))?;
+        Keymap::from_config(self.keys.as_ref()).map_err(|e| problem("keys", e.to_string()))?;
+
+        for (name, profile) in &self.profiles {
+            let key = |field: &str| format!("profiles.{}.{}", name, field);
+            validate_output_file(&key("output_file"), profile.output_file.as_deref())?;
+            validate_rate(&key("rate"), profile.rate)?;
+            if let Some(currency) = &profile.currency {
+                currency
+                    .parse::<Currency>()
+                    .map_err(|e| problem(&key("currency"), e.to_string()))?;
+            }
+            validate_timezone(&key("timezone"), profile.timezone.as_deref())?;
+        }
+        Ok(())
+    }
+}
+
+/// The directory of the output file must exist, the file itself is created on demand.
+fn validate_output_file(key: &str, file: Option<&str>) -> Result<(), Problem> {
+    let Some(file) = file else {
+        return Ok(());
+    };
+    match Path::new(file).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => Err(Problem {
+            key: key.to_string(),
+            message: format!("directory {} does not exist", dir.display()),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn validate_timezone(key: &str, name: Option<&str>) -> Result<(), Problem> {
+    match name.map(parse_timezone) {
+        Some(Err(e)) => Err(Problem {
+            key: key.to_string(),
+            message: e.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn validate_hours_per_day(key: &str, hours: Option<f64>) -> Result<(), Problem> {
+    match hours {
+        Some(hours) if !(hours > 0.0 && hours <= 24.0) => Err(Problem {
+            key: key.to_string(),
+            message: format!("must be between 0 and 24 hours, got {}", hours),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn validate_rate(key: &str, rate: Option<f64>) -> Result<(), Problem> {
+    match rate {
+        Some(rate) if !(rate > 0.0 && rate.is_finite()) => Err(Problem {
+            key: key.to_string(),
+            message: format!("rate must be a positive number, got {}", rate),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Finds the 1-based line defining a dotted key such as `profiles.work.rate`,
+/// either as `key = ...` inside its table or as a `[table]` header.
+fn line_of(text: &str, key: &str) -> Option<usize> {
+    let (table, field) = match key.rsplit_once('.') {
+        Some((table, field)) => (table, field),
+        None => ("", key),
+    };
+
+    let mut current = String::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = header.trim().to_string();
+            if current == key {
+                return Some(number + 1);
+            }
+        } else if current == table {
+            let name = line.split('=').next().unwrap_or_default().trim();
+            if line.contains('=') && name == field {
+                return Some(number + 1);
+            }
+        }
+    }
+    None
+}
+
 /// Saves the updated configuration to the config file.
 ///
 /// This function serializes the given `ConfigData` struct into TOML format
-/// and writes it to a file named `config.toml`. If the file already exists,
-/// it will be overwritten.
+/// and writes it to `config_path()`, creating the config directory if needed.
+/// If the file already exists, it will be overwritten.
 ///
 /// # Arguments
 /// - `config`: A reference to the `ConfigData` struct that needs to be saved.
 ///
 /// # Returns
 /// - `Ok(())`: If the saving process is successful.
-/// - `Err(Box<dyn std::error::Error>)`: An error if the serialization or writing fails.
-pub fn save_config(config: &ConfigData) -> Result<(), Box<dyn std::error::Error>> {
+/// - `Err(ITrackerError)`: An error if the serialization or writing fails.
+pub fn save_config(config: &ConfigData) -> Result<(), ITrackerError> {
     // Serialize config into TOML format
-    let toml_str = toml::to_string(&config)?;
+    let toml_str = toml::to_string(&config).map_err(serialize_error)?;
 
     // Write the serialized config back to the config file
-    fs::write("config.toml", toml_str)?;
+    write_config_file(&toml_str)
+}
+
+/// Writes the config file, creating the config directory if needed.
+fn write_config_file(contents: &str) -> Result<(), ITrackerError> {
+    let path = config_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(ITrackerError::file(dir))?;
+    }
+    fs::write(&path, contents).map_err(ITrackerError::file(&path))
+}
+
+fn serialize_error(e: toml::ser::Error) -> ITrackerError {
+    ITrackerError::Config(format!("Cannot serialize the configuration: {}", e))
+}
+
+/// Reads a value of the effective configuration by its dotted key, e.g. `profiles.work.rate`.
+///
+/// # Returns
+/// - `Ok(String)`: The value, strings without quotes and everything else as TOML.
+/// - `Err(ITrackerError)`: If the key is not set.
+pub fn get_value(config: &ConfigData, key: &str) -> Result<String, ITrackerError> {
+    let root = toml::Value::try_from(config).map_err(serialize_error)?;
+    let value = key
+        .split('.')
+        .try_fold(&root, |value, part| value.get(part))
+        .ok_or_else(|| ITrackerError::Config(format!("`{}` is not set", key)))?;
+
+    Ok(match value {
+        toml::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    })
+}
+
+/// Lists every value of the effective configuration as `key = value` lines.
+pub fn list_values(config: &ConfigData) -> Result<Vec<String>, ITrackerError> {
+    fn flatten(prefix: &str, table: &toml::Table, lines: &mut Vec<String>) {
+        for (name, value) in table {
+            let key = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            match value {
+                toml::Value::Table(table) => flatten(&key, table, lines),
+                value => lines.push(format!("{} = {}", key, value)),
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    if let toml::Value::Table(table) = toml::Value::try_from(config).map_err(serialize_error)? {
+        flatten("", &table, &mut lines);
+    }
+    Ok(lines)
+}
+
+/// Sets a value in the config file by its dotted key, refusing values that
+/// would make the configuration invalid.
+///
+/// The value is read as TOML if possible (`95`, `true`, `["a", "b"]`) and as a
+/// plain string otherwise, whichever the key accepts. Environment overrides are
+/// not written to the file. A file whose settings are invalid already only
+/// needs the value to be valid on its own, so settings can be fixed one at a
+/// time; a file that is not valid TOML is refused.
+///
+/// # Arguments
+/// - `key`: The dotted key, e.g. `output_file` or `profiles.work.rate`.
+/// - `value`: The new value as typed on the command line.
+pub fn set_value(key: &str, value: &str) -> Result<(), ITrackerError> {
+    let table: toml::Table = match existing_config_file() {
+        Some(file) => fs::read_to_string(&file)
+            .map_err(ITrackerError::file(&file))?
+            .parse()
+            .map_err(|e| {
+                ITrackerError::Config(format!("Invalid config file {}: {}", file.display(), e))
+            })?,
+        None => toml::Table::new(),
+    };
+
+    let literal = format!("value = {}", value)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"));
+    let candidates = literal
+        .into_iter()
+        .chain([toml::Value::String(value.to_string())]);
+
+    let valid = |table: &toml::Table| -> Result<(), String> {
+        let config: ConfigData = toml::Value::Table(table.clone())
+            .try_into()
+            .map_err(|e: toml::de::Error| e.message().to_string())?;
+        config.validate().map_err(|problem| problem.message)
+    };
+    let was_valid = valid(&table).is_ok();
+
+    let mut first_error = None;
+    for candidate in candidates {
+        let mut alone = toml::Table::new();
+        let mut updated = table.clone();
+        let checked = insert(&mut alone, key, candidate.clone())
+            .and_then(|()| valid(&alone))
+            .and_then(|()| insert(&mut updated, key, candidate))
+            .and_then(|()| if was_valid { valid(&updated) } else { Ok(()) });
+
+        match checked {
+            Ok(()) => {
+                return write_config_file(&toml::to_string(&updated).map_err(serialize_error)?);
+            }
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    Err(ITrackerError::Config(format!(
+        "Cannot set `{}` to '{}': {}",
+        key,
+        value,
+        first_error.unwrap_or_default()
+    )))
+}
+
+/// Inserts a value at a dotted key, creating the tables on the way.
+fn insert(table: &mut toml::Table, key: &str, value: toml::Value) -> Result<(), String> {
+    let (tables, field) = match key.rsplit_once('.') {
+        Some((tables, field)) => (tables.split('.').collect::<Vec<_>>(), field),
+        None => (Vec::new(), key),
+    };
 
+    let mut current = table;
+    for name in tables {
+        current = current
+            .entry(name)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| format!("`{}` is not a table", name))?;
+    }
+    current.insert(field.to_string(), value);
     Ok(())
 }
@@ -0,0 +1,382 @@
+//! Offline-first syncing of the output file between devices through a shared
+//! folder, e.g. one kept in sync by Syncthing or Dropbox, or a USB stick.
+//!
+//! Every device keeps the entries in an [Automerge](https://automerge.org)
+//! document and only ever writes its own copy, `<actor>.automerge` in the
+//! folder, so the transport never sees two writers of one file. `sync`
+//! records the local changes since the last sync in the document and merges
+//! in the copies of all other devices. Concurrent edits merge the same way
+//! on every device: edits of different fields of an entry are all kept, and
+//! of two edits of the same field the same one wins everywhere.
+//!
+//! Entries are identified by an id given out by the device that created
+//! them; indices stay local. Which id each local index stands for is kept
+//! with the entries as of the last sync in `<log>.crdt`.
+
+use crate::{
+    backup::Backups,
+    crypto,
+    error::ITrackerError,
+    schema::{self, Record},
+};
+use automerge::{transaction::Transactable, ActorId, AutoCommit, ObjId, ObjType, ReadDoc, ROOT};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const EXTENSION: &str = "automerge";
+
+/// The `[crdt]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CrdtConfig {
+    /// Folder shared between the devices; syncing this way is off without one.
+    pub dir: Option<String>,
+}
+
+/// What `<log>.crdt` remembers about the last sync.
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+    /// This device's actor in the documents, in hex.
+    actor: String,
+    /// Number in the id of the next entry created here.
+    next_id: u64,
+    /// The id of each local index.
+    ids: BTreeMap<usize, String>,
+    /// Contents of the log file as synced, to find the local changes since.
+    contents: String,
+}
+
+/// The outcome of a sync.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Synced {
+    /// Number of other devices whose copies were merged in.
+    pub devices: usize,
+    /// Indices of entries added on other devices.
+    pub added: Vec<usize>,
+    /// Indices of entries changed on other devices.
+    pub changed: Vec<usize>,
+    /// Indices of entries deleted on other devices.
+    pub removed: Vec<usize>,
+    /// Copies of other devices that could not be read, e.g. while the
+    /// transport was still writing them; they are merged next time.
+    pub unreadable: Vec<PathBuf>,
+}
+
+/// A folder the devices exchange their copies of the entries through.
+#[derive(Debug, Clone)]
+pub struct CrdtSync {
+    dir: PathBuf,
+}
+
+impl CrdtSync {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        CrdtSync { dir: dir.into() }
+    }
+
+    /// Resolves the `[crdt]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Some(CrdtSync))`: If a folder is configured.
+    /// - `Ok(None)`: If syncing this way is off.
+    /// - `Err(ITrackerError)`: `Config` if the folder does not exist.
+    pub fn from_config(config: Option<&CrdtConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(dir) = config
+            .and_then(|config| config.dir.as_deref())
+            .filter(|dir| !dir.is_empty())
+        else {
+            return Ok(None);
+        };
+        if !Path::new(dir).is_dir() {
+            return Err(ITrackerError::Config(format!(
+                "{} is no directory; create it or mount the shared folder first",
+                dir
+            )));
+        }
+        Ok(Some(CrdtSync::new(dir)))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Merges the local log file with the copies of the other devices and
+    /// writes this device's copy.
+    ///
+    /// # Arguments
+    /// - `log`: The local log file.
+    /// - `backups`: Where the local file is backed up before it is replaced.
+    ///
+    /// # Returns
+    /// - `Ok(Synced)`: What the other devices changed.
+    /// - `Err(ITrackerError)`: `Sync` if this device's copy is damaged, or an
+    ///   error of reading or writing the files.
+    pub fn sync(&self, log: &str, backups: &Backups) -> Result<Synced, ITrackerError> {
+        let state_path = schema::crdt_path(log);
+        let state: Option<State> = match fs::read_to_string(&state_path) {
+            Ok(contents) => serde_json::from_str(&contents).ok(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(ITrackerError::file(&state_path)(e)),
+        };
+        let (actor, mut next_id, mut ids, base) = match state {
+            Some(state) => {
+                let base = schema::parse(&state_path, state.contents.as_bytes())?;
+                let actor = state
+                    .actor
+                    .parse::<ActorId>()
+                    .map_err(|e| self.error(&state_path, e))?;
+                (actor, state.next_id, state.ids, base)
+            }
+            None => (ActorId::random(), 1, BTreeMap::new(), Vec::new()),
+        };
+        let ours = if Path::new(log).exists() {
+            schema::read(log)?
+        } else {
+            Vec::new()
+        };
+
+        let own = self
+            .dir
+            .join(format!("{}.{}", actor.to_hex_string(), EXTENSION));
+        let mut doc = match self.load(&own)? {
+            Some(doc) => doc.with_actor(actor.clone()),
+            None => AutoCommit::new().with_actor(actor.clone()),
+        };
+        let mut synced = Synced::default();
+        for path in self.copies()? {
+            if path == own {
+                continue;
+            }
+            match self.load(&path) {
+                Ok(Some(mut other)) => {
+                    doc.merge(&mut other).map_err(|e| self.error(&path, e))?;
+                    synced.devices += 1;
+                }
+                Ok(None) => {}
+                Err(_) => synced.unreadable.push(path),
+            }
+        }
+
+        // Record what changed here since the last sync
+        let base: HashMap<usize, &Record> = base.iter().map(|r| (r.index, r)).collect();
+        let local: HashSet<usize> = ours.iter().map(|record| record.index).collect();
+        for (index, id) in &ids {
+            if !local.contains(index) && doc.get(ROOT, id.as_str()).ok().flatten().is_some() {
+                doc.delete(ROOT, id.as_str())
+                    .map_err(|e| self.error(&own, e))?;
+            }
+        }
+        ids.retain(|index, _| local.contains(index));
+        let known: HashSet<String> = ids.values().cloned().collect();
+        let mut unclaimed: Vec<(String, Record)> = entries(&doc)
+            .into_iter()
+            .filter(|(id, _)| !known.contains(id))
+            .collect();
+        for record in &ours {
+            match ids.get(&record.index) {
+                Some(id) => {
+                    let Some(object) = entry(&doc, id) else {
+                        // Deleted on another device
+                        continue;
+                    };
+                    let before = base.get(&record.index).map(|r| fields(r));
+                    for (i, (name, value)) in fields(record).into_iter().enumerate() {
+                        if before.as_ref().is_none_or(|before| before[i].1 != value) {
+                            doc.put(&object, name, value)
+                                .map_err(|e| self.error(&own, e))?;
+                        }
+                    }
+                }
+                None => {
+                    // The same entry may already be there, e.g. if the file
+                    // was copied to every device before syncing this way
+                    let same = unclaimed
+                        .iter()
+                        .position(|(_, entry)| fields(entry) == fields(record));
+                    let id = match same {
+                        Some(position) => unclaimed.remove(position).0,
+                        None => {
+                            let id = format!("{}-{}", actor.to_hex_string(), next_id);
+                            next_id += 1;
+                            let object = doc
+                                .put_object(ROOT, id.as_str(), ObjType::Map)
+                                .map_err(|e| self.error(&own, e))?;
+                            for (name, value) in fields(record) {
+                                doc.put(&object, name, value)
+                                    .map_err(|e| self.error(&own, e))?;
+                            }
+                            id
+                        }
+                    };
+                    ids.insert(record.index, id);
+                }
+            }
+        }
+
+        // Read the merged entries back, giving new ones the next free indices
+        let mut merged = entries(&doc);
+        let indices: HashMap<String, usize> =
+            ids.iter().map(|(index, id)| (id.clone(), *index)).collect();
+        merged.sort_by_key(|(id, record)| {
+            (
+                !indices.contains_key(id),
+                DateTime::parse_from_rfc2822(&record.start_time).ok(),
+                id.clone(),
+            )
+        });
+        // Entries deleted here since the last sync keep their indices taken
+        let synced_before = base.keys().max().map_or(0, |highest| highest + 1);
+        let mut next_index = schema::next_index(log, &ours)?.max(synced_before);
+        let mut records = Vec::new();
+        for (id, mut record) in merged {
+            record.index = match indices.get(&id) {
+                Some(index) => *index,
+                None => {
+                    next_index += 1;
+                    ids.insert(next_index - 1, id);
+                    synced.added.push(next_index - 1);
+                    next_index - 1
+                }
+            };
+            records.push(record);
+        }
+        let kept: HashSet<usize> = records.iter().map(|record| record.index).collect();
+        ids.retain(|index, _| kept.contains(index));
+        let ours_by_index: HashMap<usize, &Record> = ours.iter().map(|r| (r.index, r)).collect();
+        for record in &records {
+            if ours_by_index
+                .get(&record.index)
+                .is_some_and(|before| **before != *record)
+            {
+                synced.changed.push(record.index);
+            }
+        }
+        synced.removed = ours
+            .iter()
+            .map(|record| record.index)
+            .filter(|index| !kept.contains(index))
+            .collect();
+        records.sort_by_key(|record| record.index);
+
+        if records != ours {
+            backups.save(log)?;
+            if !synced.removed.is_empty() {
+                // Indices of entries deleted elsewhere are not given out again
+                schema::set_last_index(log, next_index - 1)?;
+            }
+            schema::write(log, &records)?;
+        }
+        self.save(&mut doc, &own)?;
+        let state = State {
+            actor: actor.to_hex_string(),
+            next_id,
+            ids,
+            contents: schema::contents(log, &records)?,
+        };
+        let json = serde_json::to_string(&state)
+            .map_err(|e| ITrackerError::Sync(format!("Cannot save the sync state: {}", e)))?;
+        fs::write(&state_path, json).map_err(ITrackerError::file(&state_path))?;
+        Ok(synced)
+    }
+
+    /// The copies of all devices in the folder, sorted.
+    fn copies(&self) -> Result<Vec<PathBuf>, ITrackerError> {
+        let mut copies = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(ITrackerError::file(&self.dir))? {
+            let path = entry.map_err(ITrackerError::file(&self.dir))?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                copies.push(path);
+            }
+        }
+        copies.sort();
+        Ok(copies)
+    }
+
+    /// Reads the copy at `path`, decrypting it if it is encrypted.
+    ///
+    /// # Returns
+    /// - `Ok(None)`: If there is no file at `path`.
+    fn load(&self, path: &Path) -> Result<Option<AutoCommit>, ITrackerError> {
+        let Some(data) = crypto::read_sidecar(path)? else {
+            return Ok(None);
+        };
+        AutoCommit::load(&data)
+            .map(Some)
+            .map_err(|e| self.error(path, e))
+    }
+
+    /// Writes this device's copy, encrypted like the log files, replacing the
+    /// old one at once so other devices never read half of it.
+    fn save(&self, doc: &mut AutoCommit, path: &Path) -> Result<(), ITrackerError> {
+        crypto::write_sidecar(path, doc.save())
+    }
+
+    fn error(&self, path: &Path, error: impl std::fmt::Display) -> ITrackerError {
+        ITrackerError::Sync(format!("{}: {}", path.display(), error))
+    }
+}
+
+/// The fields of a record as stored in the document, the index left out.
+fn fields(record: &Record) -> [(&'static str, String); 10] {
+    [
+        ("start_time", record.start_time.clone()),
+        ("message", record.message.clone()),
+        ("elapsed_time", record.elapsed_time.clone()),
+        ("paused_time", record.paused_time.clone()),
+        ("title", record.title.clone()),
+        ("project", record.project.clone()),
+        ("tags", record.tags.clone()),
+        (
+            "estimate",
+            record.estimate.map(|e| e.to_string()).unwrap_or_default(),
+        ),
+        ("paused_since", record.paused_since.clone()),
+        ("metadata", record.metadata.clone()),
+    ]
+}
+
+/// The map object of the entry `id`, if it is in the document.
+fn entry(doc: &AutoCommit, id: &str) -> Option<ObjId> {
+    match doc.get(ROOT, id).ok().flatten()? {
+        (automerge::Value::Object(ObjType::Map), object) => Some(object),
+        _ => None,
+    }
+}
+
+/// The entries in the document by id, with index 0.
+fn entries(doc: &AutoCommit) -> Vec<(String, Record)> {
+    let mut entries = Vec::new();
+    for id in doc.keys(ROOT) {
+        let Some(object) = entry(doc, &id) else {
+            continue;
+        };
+        let field = |name: &str| -> String {
+            match doc.get(&object, name).ok().flatten() {
+                Some((value, _)) => value.to_str().unwrap_or_default().to_string(),
+                None => String::new(),
+            }
+        };
+        let record = Record {
+            index: 0,
+            start_time: field("start_time"),
+            message: field("message"),
+            elapsed_time: field("elapsed_time"),
+            paused_time: field("paused_time"),
+            title: field("title"),
+            project: field("project"),
+            tags: field("tags"),
+            estimate: field("estimate").parse().ok(),
+            paused_since: field("paused_since"),
+            metadata: field("metadata"),
+        };
+        entries.push((id, record));
+    }
+    entries
+}
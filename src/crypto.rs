@@ -0,0 +1,187 @@
+//! Encryption in the age format, of log files at rest and of remote backups.
+//!
+//! Data is encrypted either with a passphrase or with an age key file, as
+//! written by `age-keygen`; both can be decrypted with the `age` tool too.
+//!
+//! Log files are encrypted once a key is unlocked with [`unlock`]: every
+//! read and write of [`crate::schema`] then decrypts and encrypts them, so
+//! the rest of the program never sees the difference.
+
+use crate::error::ITrackerError;
+use age::{scrypt, secrecy::SecretString, x25519};
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path, str::FromStr, sync::OnceLock};
+
+/// First line of an ASCII-armored age file, as log files are stored.
+const ARMOR_HEADER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+/// Start of a binary age file.
+const BINARY_HEADER: &[u8] = b"age-encryption.org/";
+
+/// The key log files are encrypted with in this process.
+static KEY: OnceLock<Key> = OnceLock::new();
+
+/// The `[encryption]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptionConfig {
+    /// Passphrase the log files are encrypted with, better given as
+    /// `ITRACKER_ENCRYPTION__PASSPHRASE`.
+    pub passphrase: Option<String>,
+    /// age key file (`age-keygen`) the log files are encrypted with instead.
+    pub key_file: Option<String>,
+}
+
+impl EncryptionConfig {
+    /// The key configured, see [`Key::from_config`].
+    pub fn key(&self) -> Result<Option<Key>, ITrackerError> {
+        Key::from_config(self.passphrase.as_deref(), self.key_file.as_deref())
+    }
+}
+
+/// Encrypts log files with `key` from now on, and decrypts them with it.
+///
+/// # Returns
+/// - `false`: If a key was unlocked before; it stays in use.
+pub fn unlock(key: Key) -> bool {
+    KEY.set(key).is_ok()
+}
+
+/// The key unlocked with [`unlock`], `None` if log files stay plain text.
+pub fn key() -> Option<&'static Key> {
+    KEY.get()
+}
+
+/// Whether `data` is an age file, armored or binary.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(ARMOR_HEADER) || data.starts_with(BINARY_HEADER)
+}
+
+/// Reads a file kept next to a log file, e.g. its expenses, decrypting it
+/// with the unlocked key if it is encrypted.
+///
+/// # Returns
+/// - `Ok(None)`: If there is no file at `path`.
+/// - `Err(ITrackerError)`: `Encryption` if it is encrypted and no key is
+///   unlocked or the key does not fit, or an IO error.
+pub fn read_sidecar(path: &Path) -> Result<Option<Vec<u8>>, ITrackerError> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ITrackerError::file(path)(e)),
+    };
+    if !is_encrypted(&data) {
+        return Ok(Some(data));
+    }
+    let key = key().ok_or_else(|| {
+        ITrackerError::Encryption(format!(
+            "{} is encrypted, set encryption.passphrase or encryption.key_file",
+            path.display()
+        ))
+    })?;
+    key.decrypt(&data).map(Some)
+}
+
+/// Replaces a file kept next to a log file with `data`, encrypted like the
+/// log files, at once so that no reader sees half of it.
+pub fn write_sidecar(path: &Path, data: Vec<u8>) -> Result<(), ITrackerError> {
+    let data = match key() {
+        Some(key) => key.encrypt(&data)?,
+        None => data,
+    };
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, data).map_err(ITrackerError::file(path))?;
+    fs::rename(&temporary, path).map_err(ITrackerError::file(path))
+}
+
+/// What data is encrypted with.
+#[derive(Clone)]
+pub enum Key {
+    /// A passphrase, stretched with scrypt so it is slow to guess.
+    Passphrase(SecretString),
+    /// The first identity (`AGE-SECRET-KEY-1...`) of an age key file.
+    Identity(x25519::Identity),
+}
+
+impl fmt::Debug for Key {
+    /// Names the kind of key without revealing it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Passphrase(_) => f.write_str("Passphrase(..)"),
+            Key::Identity(identity) => write!(f, "Identity({})", identity.to_public()),
+        }
+    }
+}
+
+impl Key {
+    /// Resolves the `passphrase` and `key_file` settings of a section.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Key))`: The key set, reading the key file if that is the one.
+    /// - `Ok(None)`: If neither is set.
+    /// - `Err(ITrackerError)`: `Config` if both are set or the key file holds
+    ///   no age identity, `File` if it cannot be read.
+    pub fn from_config(
+        passphrase: Option<&str>,
+        key_file: Option<&str>,
+    ) -> Result<Option<Key>, ITrackerError> {
+        match (passphrase, key_file) {
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => Err(ITrackerError::Config(
+                "set either passphrase or key_file, not both".to_string(),
+            )),
+            (Some(passphrase), None) => Ok(Some(Key::Passphrase(SecretString::from(
+                passphrase.to_string(),
+            )))),
+            (None, Some(path)) => {
+                let contents = fs::read_to_string(path).map_err(ITrackerError::file(path))?;
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+                    .and_then(|line| x25519::Identity::from_str(line).ok())
+                    .map(|identity| Some(Key::Identity(identity)))
+                    .ok_or_else(|| {
+                        ITrackerError::Config(format!("{} holds no age secret key", path))
+                    })
+            }
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ITrackerError> {
+        let encrypted = match self {
+            Key::Passphrase(passphrase) => {
+                age::encrypt(&scrypt::Recipient::new(passphrase.clone()), plaintext)
+            }
+            Key::Identity(identity) => age::encrypt(&identity.to_public(), plaintext),
+        };
+        encrypted.map_err(|e| ITrackerError::Encryption(format!("Cannot encrypt: {}", e)))
+    }
+
+    /// Encrypts into ASCII armor, for files that should stay text.
+    pub fn encrypt_armored(&self, plaintext: &[u8]) -> Result<String, ITrackerError> {
+        let encrypted = match self {
+            Key::Passphrase(passphrase) => {
+                age::encrypt_and_armor(&scrypt::Recipient::new(passphrase.clone()), plaintext)
+            }
+            Key::Identity(identity) => age::encrypt_and_armor(&identity.to_public(), plaintext),
+        };
+        encrypted.map_err(|e| ITrackerError::Encryption(format!("Cannot encrypt: {}", e)))
+    }
+
+    /// Decrypts data encrypted with the same key, armored or binary.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The plaintext.
+    /// - `Err(ITrackerError)`: `Encryption` if the key does not fit or the data
+    ///   was tampered with.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, ITrackerError> {
+        let decrypted = match self {
+            Key::Passphrase(passphrase) => {
+                age::decrypt(&scrypt::Identity::new(passphrase.clone()), ciphertext)
+            }
+            Key::Identity(identity) => age::decrypt(identity, ciphertext),
+        };
+        decrypted.map_err(|e| ITrackerError::Encryption(format!("Cannot decrypt: {}", e)))
+    }
+}
@@ -0,0 +1,192 @@
+//! Currencies of hourly rates, and their conversion into a reporting currency.
+//!
+//! Amounts are kept in the currency of the rate they were billed at. Reports
+//! can additionally show them in one reporting currency, converted with the
+//! static rates of the `[currencies]` section; nothing is fetched online.
+
+use crate::{error::ITrackerError, locale};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// An ISO 4217 currency code such as `EUR` or `USD`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Currency(String);
+
+impl FromStr for Currency {
+    type Err = ITrackerError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let code = code.trim();
+        if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(ITrackerError::Config(format!(
+                "Invalid currency '{}', expected a three-letter code like EUR or USD",
+                code
+            )));
+        }
+        Ok(Currency(code.to_ascii_uppercase()))
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Currency {
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+
+    /// The symbol written before amounts, `None` for codes written after them.
+    fn symbol(&self) -> Option<&'static str> {
+        match self.code() {
+            "USD" => Some("$"),
+            "EUR" => Some("€"),
+            "GBP" => Some("£"),
+            "JPY" => Some("¥"),
+            "INR" => Some("₹"),
+            _ => None,
+        }
+    }
+
+    /// Digits after the decimal point of the smallest unit, e.g. 2 for cents.
+    pub fn decimals(&self) -> usize {
+        match self.code() {
+            "JPY" | "KRW" | "ISK" | "CLP" | "VND" => 0,
+            "BHD" | "KWD" | "OMR" | "JOD" | "TND" => 3,
+            _ => 2,
+        }
+    }
+
+    /// Rounds an amount to the smallest unit of the currency.
+    pub fn round(&self, amount: f64) -> f64 {
+        let unit = 10f64.powi(self.decimals() as i32);
+        (amount * unit).round() / unit
+    }
+
+    /// An amount with the symbol or code, e.g. `$1234.50`, `¥1500` or
+    /// `1234.50 CHF`, written as the current locale writes amounts.
+    pub fn format(&self, amount: f64) -> String {
+        if let Some(locale) = locale::current() {
+            return locale.money(amount, self.decimals(), self.symbol(), &self.0);
+        }
+        let sign = if amount < 0.0 { "-" } else { "" };
+        let digits = format!("{:.*}", self.decimals(), amount.abs());
+        match self.symbol() {
+            Some(symbol) => format!("{}{}{}", sign, symbol, digits),
+            None => format!("{}{} {}", sign, digits, self.0),
+        }
+    }
+}
+
+/// An amount in a currency, or in none if no currency is configured.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Money {
+    pub amount: f64,
+    pub currency: Option<Currency>,
+}
+
+impl Money {
+    /// An amount rounded to the smallest unit of `currency`, cents without one.
+    pub fn new(amount: f64, currency: Option<Currency>) -> Self {
+        let amount = match &currency {
+            Some(currency) => currency.round(amount),
+            None => (amount * 100.0).round() / 100.0,
+        };
+        Money { amount, currency }
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.currency {
+            Some(currency) => write!(f, "{}", currency.format(self.amount)),
+            None => write!(f, "{}", locale::number(self.amount, 2)),
+        }
+    }
+}
+
+/// The `[currencies]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CurrenciesConfig {
+    /// Currency reports convert amounts into, e.g. `EUR`.
+    pub reporting: Option<String>,
+    /// Units of the reporting currency one unit of another currency is worth,
+    /// e.g. `USD = 0.92`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rates: BTreeMap<String, f64>,
+}
+
+/// Converts amounts into the reporting currency at fixed rates.
+#[derive(Debug, Default, Clone)]
+pub struct Exchange {
+    reporting: Option<Currency>,
+    rates: BTreeMap<Currency, f64>,
+}
+
+impl Exchange {
+    /// Resolves the `[currencies]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Exchange)`: The rates, converting nothing without a reporting currency.
+    /// - `Err(ITrackerError)`: `Config` if a code is invalid or a rate is not positive.
+    pub fn from_config(config: Option<&CurrenciesConfig>) -> Result<Self, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(Exchange::default());
+        };
+        let reporting = config.reporting.as_deref().map(str::parse).transpose()?;
+        let rates = config
+            .rates
+            .iter()
+            .map(|(code, rate)| {
+                if !(*rate > 0.0 && rate.is_finite()) {
+                    return Err(ITrackerError::Config(format!(
+                        "The exchange rate of {} must be a positive number, got {}",
+                        code, rate
+                    )));
+                }
+                Ok((code.parse()?, *rate))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Exchange { reporting, rates })
+    }
+
+    /// The currency amounts are converted into, `None` if none is configured.
+    pub fn reporting(&self) -> Option<&Currency> {
+        self.reporting.as_ref()
+    }
+
+    /// Converts an amount into the reporting currency.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Money))`: The converted amount, unchanged if already in it.
+    /// - `Ok(None)`: If no reporting currency is configured.
+    /// - `Err(ITrackerError)`: `Config` if the amount has no currency or
+    ///   its currency has no rate.
+    pub fn convert(&self, money: &Money) -> Result<Option<Money>, ITrackerError> {
+        let Some(reporting) = &self.reporting else {
+            return Ok(None);
+        };
+        let currency = money.currency.as_ref().ok_or_else(|| {
+            ITrackerError::Config(format!(
+                "Amounts without a currency cannot be converted to {}, set `currency`",
+                reporting
+            ))
+        })?;
+        if currency == reporting {
+            return Ok(Some(money.clone()));
+        }
+        let rate = self.rates.get(currency).ok_or_else(|| {
+            ITrackerError::Config(format!(
+                "No exchange rate for {}, set `currencies.rates.{}`",
+                currency, currency
+            ))
+        })?;
+        Ok(Some(Money::new(
+            money.amount * rate,
+            Some(reporting.clone()),
+        )))
+    }
+}
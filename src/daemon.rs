@@ -0,0 +1,427 @@
+//! The `daemon` command and the protocol the command line talks to it with.
+//!
+//! The daemon owns the timers of every log file it is asked about and applies
+//! one request at a time, so concurrent invocations cannot interleave their
+//! read-modify-write cycles. Each client is read on its own thread, so a slow
+//! one keeps no other waiting. Requests and responses are single lines of
+//! JSON on a Unix domain socket. In the background it checks the running
+//! timers of these files and shows the desktop notifications that are due.
+//!
+//! Only the timer operations of [`Operation`] go through the daemon; edits,
+//! deletes and the other rewrites of a log still write the file themselves.
+
+use crate::{
+    config,
+    error::ITrackerError,
+    idle::IdleChoice,
+    log::{LogEntry, TaskDetails},
+    tracker::Tracker,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{self, PathBuf};
+
+/// File name of the socket in the runtime directory.
+const SOCKET_FILE: &str = "daemon.sock";
+
+/// A timer operation on one log file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    /// The log file the operation applies to.
+    pub file: String,
+    pub operation: Operation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Operation {
+    /// Start a timer for a new task.
+    Start { task: TaskDetails },
+    /// Start a timer for a new task as of `at`.
+    StartAt {
+        task: TaskDetails,
+        at: DateTime<Utc>,
+    },
+    /// Stop the entry with the given index, or the running one.
+    Stop { index: Option<usize> },
+    /// Stop the entry with the given index as of `at`.
+    StopAt { index: usize, at: DateTime<Utc> },
+    /// Move the start or end of the entry with the given index.
+    Amend {
+        index: usize,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    },
+    /// Split the entry with the given index at `at`, answered with the
+    /// second part; `task` is the task of that part if it differs.
+    Split {
+        index: usize,
+        at: DateTime<Utc>,
+        task: Option<TaskDetails>,
+    },
+    /// Merge stopped entries into the one that started first.
+    Merge { indices: Vec<usize> },
+    /// Pause the entry with the given index, or the running one.
+    Pause { index: Option<usize> },
+    /// Resume the entry with the given index, or the running one.
+    Resume { index: Option<usize> },
+    /// Pause the entry with the given index, or the running one, as of
+    /// `since`, when the user went idle.
+    PauseIdle {
+        index: Option<usize>,
+        since: DateTime<Utc>,
+    },
+    /// Resume an entry paused by idle detection, keeping, discarding or
+    /// moving the idle time as chosen.
+    ResumeIdle {
+        index: Option<usize>,
+        choice: IdleChoice,
+    },
+    /// Return the running entry without changing it.
+    Running,
+}
+
+/// The answer to a [`Request`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    /// The entry the operation applied to.
+    Entry(Box<LogEntry>),
+    /// Why the operation failed, as it would be reported without a daemon.
+    Error(String),
+}
+
+/// Applies an operation with `tracker`; the daemon does the same for requests.
+///
+/// # Returns
+/// - `Ok(LogEntry)`: The entry the operation applied to.
+/// - `Err(ITrackerError)`: If the operation failed, e.g. `NoActiveTimer`.
+pub fn execute(tracker: &Tracker, operation: &Operation) -> Result<LogEntry, ITrackerError> {
+    match operation {
+        Operation::Start { task } => tracker.start(task),
+        Operation::StartAt { task, at } => tracker.start_at(task, *at),
+        Operation::Stop { index: Some(index) } => tracker.stop(*index),
+        Operation::StopAt { index, at } => tracker.stop_at(*index, *at),
+        Operation::Stop { index: None } => tracker.stop_running(),
+        Operation::Amend { index, start, end } => tracker.amend(*index, *start, *end),
+        Operation::Split { index, at, task } => tracker
+            .split(*index, *at, task.as_ref())
+            .map(|(_, second)| second),
+        Operation::Merge { indices } => tracker.merge(indices),
+        Operation::Pause { index } => tracker.pause(*index),
+        Operation::Resume { index } => tracker.resume(*index),
+        Operation::PauseIdle { index, since } => tracker.pause_idle(*index, *since),
+        Operation::ResumeIdle { index, choice } => tracker.resume_idle(*index, choice),
+        Operation::Running => tracker
+            .running()?
+            .ok_or_else(|| ITrackerError::NoActiveTimer {
+                path: tracker.storage().path().into(),
+            }),
+    }
+}
+
+/// Applies operations on one log file through the daemon while one is
+/// running, and directly on the file otherwise.
+#[derive(Debug, Clone)]
+pub struct Timers {
+    tracker: Tracker,
+    /// The log file as the daemon needs it, with an absolute path.
+    file: String,
+    socket: Option<PathBuf>,
+}
+
+impl Timers {
+    pub fn new(tracker: Tracker) -> Result<Self, ITrackerError> {
+        let file = path::absolute(tracker.storage().path())?
+            .to_string_lossy()
+            .into_owned();
+        Ok(Timers {
+            tracker,
+            file,
+            socket: socket_path().ok(),
+        })
+    }
+
+    pub fn tracker(&self) -> &Tracker {
+        &self.tracker
+    }
+
+    /// Applies `operation`, asking the daemon if one accepts connections.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The entry the operation applied to.
+    /// - `Err(ITrackerError)`: As for [`execute`]; failures reported by the
+    ///   daemon are `Daemon` errors with the same message.
+    pub fn run(&self, operation: Operation) -> Result<LogEntry, ITrackerError> {
+        match self
+            .socket
+            .as_deref()
+            .and_then(|socket| Client::connect(socket).ok())
+        {
+            Some(client) => client.send(&Request {
+                file: self.file.clone(),
+                operation,
+            }),
+            None => execute(&self.tracker, &operation),
+        }
+    }
+}
+
+/// The socket the daemon listens on, in the runtime directory
+/// (`$XDG_RUNTIME_DIR/itracker` on Linux) or else the data directory.
+pub fn socket_path() -> Result<PathBuf, ITrackerError> {
+    Ok(config::runtime_dir()?.join(SOCKET_FILE))
+}
+
+#[cfg(unix)]
+pub use unix::{serve, Client};
+
+#[cfg(unix)]
+mod unix {
+    use super::{execute, Request, Response};
+    use crate::{
+        backup::Backups, error::ITrackerError, hooks::Hooks, log::LogEntry, notify::Notifier,
+        storage::Storage, tracker::Tracker,
+    };
+    use chrono::Utc;
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+        io::{BufRead, BufReader, Write},
+        os::unix::net::{UnixListener, UnixStream},
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex, PoisonError},
+        thread,
+        time::Duration,
+    };
+
+    /// How long a client may take to send its request.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// How often the running timers are checked for notifications.
+    const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+    /// Log files the daemon knows about, by absolute path.
+    type Files = Arc<Mutex<HashSet<String>>>;
+
+    /// The trackers of the log files, held while a request is applied.
+    type Trackers = Arc<Mutex<HashMap<String, Tracker>>>;
+
+    /// Serves requests on `socket` until the process is stopped.
+    ///
+    /// A socket left behind by a daemon that did not shut down cleanly is
+    /// replaced; a socket a daemon still answers on is an error.
+    ///
+    /// # Arguments
+    /// - `socket`: The socket to listen on.
+    /// - `backups`: Where log files are backed up before rewrites.
+    /// - `hooks`: Run after the timers of requests changed.
+    /// - `notifier`: Decides which notifications to show for running timers.
+    /// - `watched`: Log files to check for notifications from the start; files
+    ///   named in requests are added.
+    pub fn serve(
+        socket: &Path,
+        backups: Backups,
+        hooks: Hooks,
+        notifier: Notifier,
+        watched: Vec<String>,
+    ) -> Result<(), ITrackerError> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(ITrackerError::Daemon(format!(
+                    "A daemon is already listening on {}",
+                    socket.display()
+                )));
+            }
+            fs::remove_file(socket).map_err(ITrackerError::file(socket))?;
+        }
+        let listener = UnixListener::bind(socket).map_err(ITrackerError::file(socket))?;
+        println!("Listening on {}", socket.display());
+
+        let files: Files = Arc::new(Mutex::new(watched.into_iter().collect()));
+        watch(files.clone(), notifier);
+
+        let trackers = Trackers::default();
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    continue;
+                }
+            };
+            let (trackers, backups, hooks, files) = (
+                trackers.clone(),
+                backups.clone(),
+                hooks.clone(),
+                files.clone(),
+            );
+            // A misbehaving client must not stop the daemon, nor keep others waiting
+            thread::spawn(move || {
+                if let Err(e) = answer(stream, &trackers, &backups, &hooks, &files) {
+                    eprintln!("Error: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads one request from `stream` and writes the response. Only
+    /// applying it waits for the requests of other clients.
+    fn answer(
+        stream: UnixStream,
+        trackers: &Trackers,
+        backups: &Backups,
+        hooks: &Hooks,
+        files: &Files,
+    ) -> Result<(), ITrackerError> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        // Clients probing whether a daemon is running hang up without a request
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                files
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(request.file.clone());
+                let mut trackers = trackers.lock().unwrap_or_else(PoisonError::into_inner);
+                let tracker = trackers.entry(request.file.clone()).or_insert_with(|| {
+                    Tracker::new(Storage::new(request.file).with_backups(backups.clone()))
+                        .with_hooks(hooks.clone())
+                });
+                match execute(tracker, &request.operation) {
+                    Ok(entry) => Response::Entry(Box::new(entry)),
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
+            Err(e) => Response::Error(format!("Invalid request: {}", e)),
+        };
+        send(&stream, &response)
+    }
+
+    /// Checks the running timers of `files` in the background and shows the
+    /// notifications that are due.
+    fn watch(files: Files, mut notifier: Notifier) {
+        thread::spawn(move || loop {
+            let watched: Vec<String> = files
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .cloned()
+                .collect();
+            for file in watched {
+                // Missing and broken files are reported by the commands using them
+                let Ok(entries) = Storage::new(file.as_str()).entries() else {
+                    continue;
+                };
+                for notification in notifier.check(&file, &entries, Utc::now()) {
+                    if let Err(e) = notification.show() {
+                        eprintln!("Error: {}", e);
+                    }
+                }
+            }
+            thread::sleep(CHECK_INTERVAL);
+        });
+    }
+
+    fn send<T: serde::Serialize>(
+        mut stream: &UnixStream,
+        message: &T,
+    ) -> Result<(), ITrackerError> {
+        let mut line = serde_json::to_string(message)
+            .map_err(|e| ITrackerError::Daemon(format!("Cannot encode message: {}", e)))?;
+        line.push('\n');
+        stream.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Connection to a running daemon.
+    #[derive(Debug, Clone)]
+    pub struct Client {
+        socket: PathBuf,
+    }
+
+    impl Client {
+        /// Connects to the daemon listening on `socket`.
+        ///
+        /// # Returns
+        /// - `Ok(Client)`: If a daemon accepted the connection.
+        /// - `Err(ITrackerError)`: If no daemon is running.
+        pub fn connect(socket: &Path) -> Result<Client, ITrackerError> {
+            UnixStream::connect(socket).map_err(ITrackerError::file(socket))?;
+            Ok(Client {
+                socket: socket.to_path_buf(),
+            })
+        }
+
+        /// Sends a request and waits for the daemon to apply it.
+        ///
+        /// # Returns
+        /// - `Ok(LogEntry)`: The entry the operation applied to.
+        /// - `Err(ITrackerError)`: `Daemon` with the daemon's error message, or
+        ///   if the daemon cannot be reached.
+        pub fn send(&self, request: &Request) -> Result<LogEntry, ITrackerError> {
+            let stream =
+                UnixStream::connect(&self.socket).map_err(ITrackerError::file(&self.socket))?;
+            send(&stream, request)?;
+
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line)?;
+            match serde_json::from_str(&line) {
+                Ok(Response::Entry(entry)) => Ok(*entry),
+                Ok(Response::Error(message)) => Err(ITrackerError::Daemon(message)),
+                Err(e) => Err(ITrackerError::Daemon(format!(
+                    "Invalid response from the daemon: {}",
+                    e
+                ))),
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub use other::{serve, Client};
+
+#[cfg(not(unix))]
+mod other {
+    use super::Request;
+    use crate::{
+        backup::Backups, error::ITrackerError, hooks::Hooks, log::LogEntry, notify::Notifier,
+    };
+    use std::path::Path;
+
+    fn unsupported() -> ITrackerError {
+        ITrackerError::Daemon(
+            "The daemon needs Unix domain sockets, which this platform lacks".to_string(),
+        )
+    }
+
+    pub fn serve(
+        _socket: &Path,
+        _backups: Backups,
+        _hooks: Hooks,
+        _notifier: Notifier,
+        _watched: Vec<String>,
+    ) -> Result<(), ITrackerError> {
+        Err(unsupported())
+    }
+
+    /// Connection to a running daemon; there never is one on this platform.
+    #[derive(Debug, Clone)]
+    pub struct Client;
+
+    impl Client {
+        pub fn connect(_socket: &Path) -> Result<Client, ITrackerError> {
+            Err(unsupported())
+        }
+
+        pub fn send(&self, _request: &Request) -> Result<LogEntry, ITrackerError> {
+            Err(unsupported())
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! The D-Bus service the `daemon` command offers with the `dbus` feature.
+//!
+//! GNOME extensions and KDE widgets control the timers of the output file
+//! through the `io.github.inkvisto.ITracker1` interface on the session bus.
+//! Calls go through the daemon socket like every other invocation, and
+//! `StateChanged` is emitted whenever the running timer changes, no matter
+//! which program changed it.
+
+use crate::{
+    daemon::{Operation, Timers},
+    error::ITrackerError,
+    log::{LogEntry, TaskDetails},
+    report, status,
+    status::State,
+};
+use chrono::Utc;
+use std::{thread, time::Duration};
+use zbus::{blocking::connection, fdo, interface, object_server::SignalEmitter};
+
+/// Well-known name the daemon owns on the session bus.
+pub const BUS_NAME: &str = "io.github.inkvisto.ITracker";
+
+/// Path of the object implementing [`INTERFACE`].
+pub const OBJECT_PATH: &str = "/io/github/inkvisto/ITracker";
+
+pub const INTERFACE: &str = "io.github.inkvisto.ITracker1";
+
+/// How often the log file is checked for changes to signal.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// State, index, title, project and tracked seconds of the running timer.
+type Status = (String, u32, String, String, u64);
+
+fn status_of(running: Option<&LogEntry>) -> Status {
+    match running {
+        Some(entry) => (
+            State::of(running).to_string(),
+            entry.index as u32,
+            entry.display_title().to_string(),
+            entry.project.clone(),
+            report::tracked_seconds(entry, Utc::now()),
+        ),
+        None => (State::Idle.to_string(), 0, String::new(), String::new(), 0),
+    }
+}
+
+fn failed(error: ITrackerError) -> fdo::Error {
+    fdo::Error::Failed(error.to_string())
+}
+
+struct Service {
+    timers: Timers,
+}
+
+impl Service {
+    /// Applies `operation` and returns the index of the entry it applied to.
+    fn run(&self, operation: Operation) -> fdo::Result<u32> {
+        self.timers
+            .run(operation)
+            .map(|entry| entry.index as u32)
+            .map_err(failed)
+    }
+}
+
+#[interface(name = "io.github.inkvisto.ITracker1")]
+impl Service {
+    /// Starts a timer for a new task and returns the index of its entry.
+    fn start_timer(&self, title: String, project: String) -> fdo::Result<u32> {
+        self.run(Operation::Start {
+            task: TaskDetails {
+                title,
+                project,
+                ..TaskDetails::default()
+            },
+        })
+    }
+
+    /// Stops the running timer and returns the index of its entry.
+    fn stop_timer(&self) -> fdo::Result<u32> {
+        self.run(Operation::Stop { index: None })
+    }
+
+    fn pause_timer(&self) -> fdo::Result<u32> {
+        self.run(Operation::Pause { index: None })
+    }
+
+    fn resume_timer(&self) -> fdo::Result<u32> {
+        self.run(Operation::Resume { index: None })
+    }
+
+    /// The running timer; `idle` with empty values while none runs.
+    #[zbus(out_args("state", "index", "title", "project", "tracked_seconds"))]
+    fn get_status(&self) -> fdo::Result<Status> {
+        let running = status::active(self.timers.tracker().storage().path()).map_err(failed)?;
+        Ok(status_of(running.as_ref()))
+    }
+
+    /// Emitted when a timer starts, stops, pauses or resumes.
+    #[zbus(signal)]
+    async fn state_changed(
+        emitter: &SignalEmitter<'_>,
+        state: &str,
+        index: u32,
+        title: &str,
+        project: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// Offers the service for the log file of `timers` on the session bus until
+/// the process ends.
+///
+/// # Returns
+/// - `Ok(())`: Once the name is owned; requests are answered in the background.
+/// - `Err(ITrackerError)`: `Daemon` if there is no session bus or another
+///   daemon owns the name.
+pub fn serve(timers: Timers) -> Result<(), ITrackerError> {
+    let path = timers.tracker().storage().path().to_string();
+    let bus_error =
+        |e: zbus::Error| ITrackerError::Daemon(format!("Cannot serve on the session bus: {}", e));
+    let connection = connection::Builder::session()
+        .and_then(|builder| builder.name(BUS_NAME))
+        .and_then(|builder| builder.serve_at(OBJECT_PATH, Service { timers }))
+        .and_then(|builder| builder.build())
+        .map_err(bus_error)?;
+    println!("Serving {} on the session bus", BUS_NAME);
+
+    // Timers also change through the command line and the file itself, so
+    // changes are noticed by watching the file rather than the calls above
+    thread::spawn(move || {
+        let mut last = None;
+        loop {
+            match status::active(&path) {
+                Ok(running) => {
+                    let (state, index, title, project, _) = status_of(running.as_ref());
+                    let current = Some((state.clone(), index));
+                    if last.is_some() && last != current {
+                        let signal = (state.as_str(), index, title.as_str(), project.as_str());
+                        if let Err(e) = connection.emit_signal(
+                            None::<&str>,
+                            OBJECT_PATH,
+                            INTERFACE,
+                            "StateChanged",
+                            &signal,
+                        ) {
+                            eprintln!("Error: {}", bus_error(e));
+                        }
+                    }
+                    last = current;
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,384 @@
+//! Dumps of everything the tracker keeps, for moving it to another machine.
+//!
+//! A dump is a gzipped tar file laid out like a remote snapshot, see
+//! [`crate::snapshot`]: the data directory under `data/` and, if it lives
+//! elsewhere, the output file with its closed-month archive under `output/`.
+//! It adds the config file under `config/` and a `manifest.toml` naming the
+//! version that wrote it. Unlike snapshots, dumps are not encrypted; log
+//! files encrypted at rest stay encrypted in them, but key files are never
+//! included.
+
+use crate::{backup::Backups, config, error::ITrackerError, schema};
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+/// Layout version of dumps written by this version, raised whenever older
+/// versions could not load them.
+const FORMAT: u32 = 1;
+const MANIFEST: &str = "manifest.toml";
+const CONFIG: &str = "config/config.toml";
+
+/// Files that are rebuilt on demand or only matter on this machine; the
+/// `.crdt` state names this device, which another one must not pose as.
+const SKIPPED_SUFFIXES: [&str; 5] = [".active", ".crdt", ".journal", ".tmp", ".webdav"];
+/// Directories of the data directory left out: the git repository of
+/// `sync` and the local backups.
+const SKIPPED_DIRS: [&str; 2] = [".git", "backups"];
+
+/// What a dump says about itself.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub format: u32,
+    /// Version of the tracker that wrote the dump.
+    pub version: String,
+    pub created: DateTime<Utc>,
+}
+
+/// A dump read into memory.
+#[derive(Debug)]
+pub struct Dump {
+    pub manifest: Manifest,
+    /// The contents of the config file, if one was dumped.
+    config: Option<Vec<u8>>,
+    /// `(name in the dump, contents)` of the data files.
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// Writes everything into a dump at `path`.
+///
+/// # Arguments
+/// - `path`: The dump to write; an existing file is replaced.
+/// - `data_dir`: The data directory.
+/// - `output_file`: The output file, included separately if it lives
+///   outside the data directory.
+///
+/// # Returns
+/// - `Ok(usize)`: The number of files dumped, the config file included.
+/// - `Err(ITrackerError)`: If a file cannot be read or the dump written.
+pub fn write(path: &Path, data_dir: &Path, output_file: &Path) -> Result<usize, ITrackerError> {
+    let manifest = Manifest {
+        format: FORMAT,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created: Utc::now(),
+    };
+    let manifest = toml::to_string(&manifest)
+        .map_err(|e| ITrackerError::Backup(format!("Cannot write the manifest: {}", e)))?;
+    let mut files = vec![(PathBuf::from(MANIFEST), manifest.into_bytes())];
+    if let Some(config_file) = config::existing_config_file() {
+        let contents = fs::read(&config_file).map_err(ITrackerError::file(&config_file))?;
+        files.push((PathBuf::from(CONFIG), contents));
+    }
+    files.extend(read_files(data_dir, output_file)?);
+
+    let count = files.len() - 1;
+    let packed = pack(files)?;
+    fs::write(path, packed).map_err(ITrackerError::file(path))?;
+    Ok(count)
+}
+
+impl Dump {
+    /// Reads the dump at `path`.
+    ///
+    /// # Returns
+    /// - `Ok(Dump)`: The dump.
+    /// - `Err(ITrackerError)`: `File` if it cannot be read, `Backup` if it is
+    ///   damaged, no dump, or written by a newer version in a newer format.
+    pub fn read(path: &Path) -> Result<Self, ITrackerError> {
+        let archive = fs::read(path).map_err(ITrackerError::file(path))?;
+        let name = path.display().to_string();
+        let mut manifest = None;
+        let mut config = None;
+        let mut files = Vec::new();
+        for (file, contents) in unpack(&archive, &name)? {
+            if file == Path::new(MANIFEST) {
+                let text = String::from_utf8_lossy(&contents);
+                manifest = Some(toml::from_str::<Manifest>(&text).map_err(|e| {
+                    ITrackerError::Backup(format!("{} has an invalid manifest: {}", name, e))
+                })?);
+            } else if file == Path::new(CONFIG) {
+                config = Some(contents);
+            } else {
+                files.push((file, contents));
+            }
+        }
+
+        let Some(manifest) = manifest else {
+            return Err(ITrackerError::Backup(format!(
+                "{} is no dump, it has no {}",
+                name, MANIFEST
+            )));
+        };
+        if manifest.format > FORMAT {
+            return Err(ITrackerError::Backup(format!(
+                "{} was written by version {} in a newer format, update to load it",
+                name, manifest.version
+            )));
+        }
+        Ok(Dump {
+            manifest,
+            config,
+            files,
+        })
+    }
+
+    /// Whether the dump holds a config file.
+    pub fn has_config(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// The number of data files in the dump, the config file left out.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Puts the config file of the dump in place at `path`. A different
+    /// config file there is kept as `<path>.bak`.
+    ///
+    /// # Returns
+    /// - `Ok(true)`: If the config file was written.
+    /// - `Ok(false)`: If the dump holds none or the same one is in place.
+    /// - `Err(ITrackerError)`: `Config` if the dumped config file is invalid
+    ///   in this version, or an error of writing.
+    pub fn restore_config(&self, path: &Path) -> Result<bool, ITrackerError> {
+        let Some(contents) = &self.config else {
+            return Ok(false);
+        };
+        let text = String::from_utf8_lossy(contents);
+        config::check_config(&text, Path::new(CONFIG))?;
+        if fs::read(path).is_ok_and(|current| current == *contents) {
+            return Ok(false);
+        }
+
+        if path.exists() {
+            let mut kept = path.as_os_str().to_owned();
+            kept.push(".bak");
+            fs::copy(path, &kept).map_err(ITrackerError::file(path))?;
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(ITrackerError::file(dir))?;
+        }
+        schema::replace(path, contents)?;
+        Ok(true)
+    }
+
+    /// Puts the data files of the dump in place, see [`put_back`].
+    pub fn restore(
+        &self,
+        data_dir: &Path,
+        output_file: &Path,
+        backups: &Backups,
+    ) -> Result<Vec<PathBuf>, ITrackerError> {
+        let name = format!("The dump of {}", self.manifest.created.to_rfc3339());
+        put_back(&self.files, data_dir, output_file, backups, &name)
+    }
+}
+
+/// The files of the data directory and the output file, as `(name in the
+/// archive, contents)`. Log files are read with their journals folded in.
+pub(crate) fn read_files(
+    data_dir: &Path,
+    output_file: &Path,
+) -> Result<Vec<(PathBuf, Vec<u8>)>, ITrackerError> {
+    let mut files = Vec::new();
+    collect(data_dir, Path::new("data"), true, &mut files)?;
+
+    let absolute = |path: &Path| std::path::absolute(path).map_err(ITrackerError::file(path));
+    let output_file = absolute(output_file)?;
+    if !output_file.starts_with(absolute(data_dir)?) && output_file.exists() {
+        let output_dir = output_file.parent().unwrap_or(Path::new("/"));
+        let name = output_file.file_name().unwrap_or_default();
+        files.push((Path::new("output").join(name), output_file.clone()));
+        let last_index = schema::last_index_path(&output_file);
+        if last_index.exists() {
+            let name = last_index.file_name().unwrap_or_default();
+            files.push((Path::new("output").join(name), last_index));
+        }
+        let archive = output_dir.join("archive");
+        if archive.is_dir() {
+            collect(
+                &archive,
+                &Path::new("output").join("archive"),
+                false,
+                &mut files,
+            )?;
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|(name, path)| {
+            let contents = if schema::journal_path(&path).exists() {
+                schema::contents(&path, &schema::read(&path)?)?.into_bytes()
+            } else {
+                fs::read(&path).map_err(ITrackerError::file(&path))?
+            };
+            Ok((name, contents))
+        })
+        .collect()
+}
+
+/// Builds a gzipped tar file of `(name, contents)` pairs.
+pub(crate) fn pack(files: Vec<(PathBuf, Vec<u8>)>) -> Result<Vec<u8>, ITrackerError> {
+    let packing = |e: io::Error| ITrackerError::Backup(format!("Cannot pack the files: {}", e));
+    let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let mtime = Utc::now().timestamp().max(0) as u64;
+    for (name, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(mtime);
+        tar.append_data(&mut header, &name, contents.as_slice())
+            .map_err(packing)?;
+    }
+    tar.into_inner()
+        .and_then(GzEncoder::finish)
+        .map_err(packing)
+}
+
+/// The `(name, contents)` pairs of a gzipped tar file.
+///
+/// # Arguments
+/// - `name`: What the archive is called in errors.
+pub(crate) fn unpack(archive: &[u8], name: &str) -> Result<Vec<(PathBuf, Vec<u8>)>, ITrackerError> {
+    let damaged = |e: io::Error| ITrackerError::Backup(format!("{} is damaged: {}", name, e));
+    let mut files = Vec::new();
+    let mut tar = tar::Archive::new(GzDecoder::new(archive));
+    for entry in tar.entries().map_err(damaged)? {
+        let mut entry = entry.map_err(damaged)?;
+        let path = entry.path().map_err(damaged)?.into_owned();
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents).map_err(damaged)?;
+        files.push((path, contents));
+    }
+    Ok(files)
+}
+
+/// Writes the files of an archive back in place.
+///
+/// Files that differ from the archive are backed up before they are
+/// replaced; files that are not in the archive are left alone.
+///
+/// # Arguments
+/// - `files`: `(name, contents)` pairs below `data/` and `output/`.
+/// - `data_dir`: The data directory to restore `data/` into.
+/// - `output_file`: The output file, whose directory `output/` is restored into.
+/// - `backups`: Where replaced files are backed up.
+/// - `name`: What the archive is called in errors.
+///
+/// # Returns
+/// - `Ok(Vec<PathBuf>)`: The files that were replaced or created.
+/// - `Err(ITrackerError)`: `Backup` if a file would end up outside the data,
+///   or an error of writing.
+pub(crate) fn put_back(
+    files: &[(PathBuf, Vec<u8>)],
+    data_dir: &Path,
+    output_file: &Path,
+    backups: &Backups,
+    name: &str,
+) -> Result<Vec<PathBuf>, ITrackerError> {
+    let output_dir = output_file.parent().unwrap_or(Path::new(""));
+    let mut restored = Vec::new();
+    for (path, contents) in files {
+        let Some(target) = target(path, data_dir, output_dir) else {
+            return Err(ITrackerError::Backup(format!(
+                "{} holds a file outside the data: {}",
+                name,
+                path.display()
+            )));
+        };
+        if fs::read(&target).is_ok_and(|current| current == *contents) {
+            continue;
+        }
+
+        if !target.to_string_lossy().ends_with(".last-index") {
+            backups.save(&target)?;
+        }
+        if let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(ITrackerError::file(dir))?;
+        }
+        schema::replace(&target, contents)?;
+        // The archive holds logs with their journals folded in
+        for stale in [
+            schema::journal_path(&target),
+            schema::active_path(&target),
+            schema::index_path(&target),
+            schema::rows_path(&target),
+        ] {
+            match fs::remove_file(&stale) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(ITrackerError::file(&stale)(e))
+                }
+                _ => {}
+            }
+        }
+        restored.push(target);
+    }
+    Ok(restored)
+}
+
+/// Where the file at `path` inside an archive is restored to, or `None` if
+/// the path would leave the data.
+fn target(path: &Path, data_dir: &Path, output_dir: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+    let root = match components.next()? {
+        Component::Normal(root) if root == "data" => data_dir,
+        Component::Normal(root) if root == "output" => output_dir,
+        _ => return None,
+    };
+    let rest = components.as_path();
+    if rest.as_os_str().is_empty()
+        || !rest
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(root.join(rest))
+}
+
+/// Adds the files below `dir` to `files` as `(name in the archive, path)`.
+///
+/// # Arguments
+/// - `top`: Whether `dir` is the data directory, whose own `.git` and
+///   `backups` are left out.
+fn collect(
+    dir: &Path,
+    name: &Path,
+    top: bool,
+    files: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), ITrackerError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(ITrackerError::file(dir)(e)),
+    };
+    let mut paths = Vec::new();
+    for entry in entries {
+        paths.push(entry.map_err(ITrackerError::file(dir))?.path());
+    }
+    paths.sort();
+
+    for path in paths {
+        let file_name = path.file_name().unwrap_or_default();
+        let text = file_name.to_string_lossy();
+        if path.is_dir() {
+            if !(top && SKIPPED_DIRS.contains(&text.as_ref())) {
+                collect(&path, &name.join(file_name), false, files)?;
+            }
+        } else if !SKIPPED_SUFFIXES.iter().any(|suffix| text.ends_with(suffix)) {
+            files.push((name.join(file_name), path));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,235 @@
+//! Entries as TOML for `edit`, changed in the user's editor.
+//!
+//! The start is written as local time in the configured timezone and read
+//! back like `--at`, so `yesterday 9:00` works as well. Durations are
+//! written exactly, e.g. `1h 30m 5s`, and read like `--for`. A running entry
+//! has no `elapsed`; giving it one stops the entry.
+
+use crate::{error::ITrackerError, log::LogEntry, timeparse};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const START_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The fields of an entry that can be edited.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Editable {
+    title: String,
+    description: String,
+    project: String,
+    tags: Vec<String>,
+    start: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    elapsed: Option<String>,
+    paused: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    estimate: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+}
+
+/// Writes `entry` as the TOML the editor opens, with comments on the values.
+///
+/// # Arguments
+/// - `entry`: The entry to edit.
+/// - `tz`: The timezone the start is written in.
+pub fn to_toml(entry: &LogEntry, tz: Tz) -> Result<String, ITrackerError> {
+    let start = match entry.start() {
+        Some(start) => start.with_timezone(&tz).format(START_FORMAT).to_string(),
+        None => entry.start_time.trim().to_string(),
+    };
+    let editable = Editable {
+        title: entry.title.trim().to_string(),
+        description: entry.message.trim().to_string(),
+        project: entry.project.trim().to_string(),
+        tags: entry.tags.clone(),
+        start,
+        elapsed: (!entry.is_running()).then(|| exact(seconds(&entry.elapsed_time))),
+        paused: exact(seconds(&entry.paused_time)),
+        estimate: entry.estimate.map(|minutes| exact(u64::from(minutes) * 60)),
+        metadata: entry.metadata.clone(),
+    };
+    let body = toml::to_string(&editable)
+        .map_err(|e| ITrackerError::Config(format!("Cannot write entry as TOML: {}", e)))?;
+    Ok(format!(
+        "# Log entry {}. Save to change it, or quit without saving to keep it.\n\
+         # start: a time in {}, e.g. 2024-06-03 14:00 or \"yesterday 9:00\"\n\
+         # elapsed, paused and estimate: durations, e.g. 1h30m or \"90 minutes\"\n\
+         # A running entry has no elapsed; adding one stops it.\n\n{}",
+        entry.index, tz, body
+    ))
+}
+
+/// Reads the edited TOML back into a copy of `entry`.
+///
+/// # Arguments
+/// - `entry`: The entry as it was before editing.
+/// - `text`: The edited TOML.
+/// - `now`: The current time, which the entry may not start or end after.
+/// - `tz`: The timezone the start is read in.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` naming the field that is invalid.
+pub fn from_toml(
+    entry: &LogEntry,
+    text: &str,
+    now: DateTime<Utc>,
+    tz: Tz,
+) -> Result<LogEntry, ITrackerError> {
+    let editable: Editable = toml::from_str(text)
+        .map_err(|e| ITrackerError::Config(format!("Cannot read the entry: {}", e.message())))?;
+    let field = |name: &str, e: ITrackerError| ITrackerError::Config(format!("{}: {}", name, e));
+    if editable.title.trim().is_empty() && editable.description.trim().is_empty() {
+        return Err(ITrackerError::Config(
+            "title and description: give at least one of them".to_string(),
+        ));
+    }
+
+    let start = timeparse::moment(&editable.start, now, tz).map_err(|e| field("start", e))?;
+    if start > now {
+        return Err(ITrackerError::Config(
+            "start: lies in the future, entries only start in the past".to_string(),
+        ));
+    }
+    let elapsed = match &editable.elapsed {
+        Some(elapsed) => {
+            let elapsed = timeparse::duration(elapsed).map_err(|e| field("elapsed", e))?;
+            if elapsed.num_seconds() <= 0 {
+                return Err(ITrackerError::Config(
+                    "elapsed: must be longer than zero, remove it to keep the entry running"
+                        .to_string(),
+                ));
+            }
+            if start + elapsed > now {
+                return Err(ITrackerError::Config(
+                    "elapsed: the entry would end in the future".to_string(),
+                ));
+            }
+            elapsed.num_seconds()
+        }
+        None if entry.is_running() => 0,
+        None => {
+            return Err(ITrackerError::Config(
+                "elapsed: a stopped entry cannot be started again".to_string(),
+            ))
+        }
+    };
+    let paused = timeparse::duration(&editable.paused)
+        .map_err(|e| field("paused", e))?
+        .num_seconds();
+    if paused < 0 || (elapsed > 0 && paused > elapsed) {
+        return Err(ITrackerError::Config(
+            "paused: must lie between zero and the elapsed time".to_string(),
+        ));
+    }
+    let estimate = match editable.estimate.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(estimate) => {
+            let minutes = timeparse::duration(estimate)
+                .map_err(|e| field("estimate", e))?
+                .num_minutes();
+            Some(u32::try_from(minutes).map_err(|_| {
+                ITrackerError::Config("estimate: must be between zero and a lifetime".to_string())
+            })?)
+        }
+    };
+
+    let mut edited = entry.clone();
+    edited.title = editable.title.trim().to_string();
+    edited.message = editable.description.trim().to_string();
+    edited.project = editable.project.trim().to_string();
+    edited.tags = editable
+        .tags
+        .iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    // Unchanged starts keep their stored form, so nothing changes by saving as is
+    if entry.start().map(|stored| stored.with_timezone(&Utc)) != Some(start) {
+        edited.start_time = start.with_timezone(&tz).to_rfc2822();
+    }
+    edited.elapsed_time = elapsed.to_string();
+    edited.paused_time = paused.to_string();
+    if elapsed > 0 {
+        edited.paused_since.clear();
+    }
+    edited.estimate = estimate;
+    edited.metadata = editable.metadata;
+    Ok(edited)
+}
+
+/// Opens `path` in `$VISUAL`, `$EDITOR` or `vi`, waiting until it is closed.
+///
+/// The editor is run by the shell, so it may come with arguments, e.g.
+/// `code --wait`.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` if the editor cannot be run or fails.
+pub fn open(path: &Path) -> Result<(), ITrackerError> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .map_err(|e| ITrackerError::Config(format!("Cannot run editor '{}': {}", editor, e)))?;
+    if !status.success() {
+        return Err(ITrackerError::Config(format!(
+            "Editor '{}' failed: {}, the entry is unchanged",
+            editor, status
+        )));
+    }
+    Ok(())
+}
+
+/// The file an entry is edited in, in the temporary directory.
+pub fn scratch_file(index: usize) -> PathBuf {
+    env::temp_dir().join(format!(
+        "itracker-entry-{}-{}.toml",
+        index,
+        std::process::id()
+    ))
+}
+
+/// Writes `text` to `path`, opens it in the editor and reads it back.
+pub fn edit_text(path: &Path, text: &str) -> Result<String, ITrackerError> {
+    fs::write(path, text).map_err(ITrackerError::file(path))?;
+    open(path)?;
+    fs::read_to_string(path).map_err(ITrackerError::file(path))
+}
+
+fn seconds(stored: &str) -> u64 {
+    stored.trim().parse().unwrap_or(0)
+}
+
+/// `seconds` down to the second, e.g. `1h 30m 5s`, which
+/// [`timeparse::duration`] reads back.
+fn exact(seconds: u64) -> String {
+    let parts: Vec<String> = [
+        (seconds / 3600, "h"),
+        (seconds / 60 % 60, "m"),
+        (seconds % 60, "s"),
+    ]
+    .into_iter()
+    .filter(|(amount, _)| *amount > 0)
+    .map(|(amount, unit)| format!("{}{}", amount, unit))
+    .collect();
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
@@ -0,0 +1,117 @@
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+/// Everything that can go wrong while tracking time.
+///
+/// File related variants carry the path involved, and the record number
+/// where a single entry is at fault, so messages point at what to fix.
+#[derive(Debug, Error)]
+pub enum ITrackerError {
+    /// Reading or writing a file failed.
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// Terminal and other IO that is not tied to a file.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The log file is not valid CSV.
+    #[error("{}: {source}", path.display())]
+    Csv {
+        path: PathBuf,
+        #[source]
+        source: csv::Error,
+    },
+    /// A record is valid CSV but holds a value that cannot be used.
+    #[error("{}: record {record}: {message}", path.display())]
+    InvalidRecord {
+        path: PathBuf,
+        record: usize,
+        message: String,
+    },
+    /// The configuration, an environment override or a setting given on the command line is invalid.
+    #[error("{0}")]
+    Config(String),
+    /// Pause, resume or stop was requested but no entry is being timed.
+    #[error("No active timer in {}", path.display())]
+    NoActiveTimer { path: PathBuf },
+    /// The entry was already stopped.
+    #[error("Log entry {index} is not running")]
+    NotRunning { index: usize },
+    /// Pause was requested for an entry that is already paused.
+    #[error("Log entry {index} is already paused")]
+    AlreadyPaused { index: usize },
+    /// Resume was requested for an entry that is not paused.
+    #[error("Log entry {index} is not paused")]
+    NotPaused { index: usize },
+    /// A stop time was given that does not lie after the start of the entry.
+    #[error("Log entry {index} has to stop after it started")]
+    StopsBeforeStart { index: usize },
+    /// An entry was moved onto the time of another one.
+    #[error("Log entry {index} would overlap log entry {other}")]
+    Overlaps { index: usize, other: usize },
+    /// No entry with this index exists in the log file.
+    #[error("Log entry {index} not found in {}", path.display())]
+    EntryNotFound { path: PathBuf, index: usize },
+    /// Several records of the log file share an index, e.g. written by old versions.
+    #[error(
+        "Log entry {index} appears more than once in {}, run `compact` to renumber the entries",
+        path.display()
+    )]
+    DuplicateIndex { path: PathBuf, index: usize },
+    /// The entry belongs to a month closed with `close-month`.
+    #[error("Log entry {index} belongs to a closed month and cannot be changed")]
+    Locked { index: usize },
+    /// The entry was billed on an invoice; `--force` changes it anyway.
+    #[error("Log entry {index} was invoiced as {number}, pass --force to change it anyway")]
+    Invoiced { index: usize, number: String },
+    /// A month cannot be closed, e.g. because entries are still running.
+    #[error("{0}")]
+    CloseMonth(String),
+    /// No backup to restore was found.
+    #[error("{0}")]
+    Backup(String),
+    /// The daemon refused a request or could not be talked to.
+    #[error("{0}")]
+    Daemon(String),
+    /// Syncing with the git remote failed, e.g. because entries changed on both sides.
+    #[error("{0}")]
+    Sync(String),
+    /// Data could not be encrypted or decrypted, e.g. because of a wrong passphrase.
+    #[error("{0}")]
+    Encryption(String),
+    /// Talking to the S3 storage of remote backups failed.
+    #[error("{0}")]
+    S3(String),
+    /// The team server could not be reached or refused a request.
+    #[error("{0}")]
+    Team(String),
+    /// A plugin could not be found or run, or reported an error.
+    #[error("{0}")]
+    Plugin(String),
+    /// The focused window could not be asked for or recorded.
+    #[error("{0}")]
+    Activity(String),
+    /// The time since the last input could not be asked for.
+    #[error("{0}")]
+    Idle(String),
+    /// A destructive action needs a confirmation that cannot be asked for.
+    #[error("{0}")]
+    NotConfirmed(String),
+}
+
+impl ITrackerError {
+    /// Wraps an IO error with the file it happened on, for use with `map_err`.
+    pub fn file(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| ITrackerError::File { path, source }
+    }
+
+    /// Wraps a CSV error with the file it happened on, for use with `map_err`.
+    pub fn csv(path: impl Into<PathBuf>) -> impl FnOnce(csv::Error) -> Self {
+        let path = path.into();
+        move |source| ITrackerError::Csv { path, source }
+    }
+}
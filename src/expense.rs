@@ -0,0 +1,117 @@
+//! Expenses, e.g. a train ticket, recorded next to the tracked time.
+//!
+//! Expenses are kept in a CSV file next to the log file (see
+//! [`schema::expenses_path`]), encrypted like the log files. Invoices bill
+//! the expenses of their range and client along with the time, and reports
+//! add them to the amounts of their projects.
+
+use crate::{
+    crypto,
+    currency::{Currency, Money},
+    error::ITrackerError,
+    report::NO_PROJECT,
+    schema,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// One expense, a row of the expenses file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Expense {
+    /// Number of the expense, one above the highest recorded before.
+    #[serde(rename = "Id")]
+    pub id: usize,
+    #[serde(rename = "Date")]
+    pub date: NaiveDate,
+    #[serde(rename = "Amount")]
+    pub amount: f64,
+    /// Currency code of the amount, empty if no currency is configured.
+    #[serde(default, rename = "Currency")]
+    pub currency: String,
+    #[serde(rename = "Description")]
+    pub description: String,
+    /// The project (client) the expense is billed to, may be empty.
+    #[serde(default, rename = "Client")]
+    pub client: String,
+    /// Number of the invoice the expense was billed on, empty until then.
+    #[serde(default, rename = "Invoice")]
+    pub invoice: String,
+}
+
+impl Expense {
+    /// The amount in its currency.
+    pub fn money(&self) -> Money {
+        Money::new(self.amount, self.currency.parse::<Currency>().ok())
+    }
+
+    /// The project the expense is billed to, [`NO_PROJECT`] if none.
+    pub fn project(&self) -> &str {
+        match self.client.trim() {
+            "" => NO_PROJECT,
+            client => client,
+        }
+    }
+
+    pub fn is_invoiced(&self) -> bool {
+        !self.invoice.trim().is_empty()
+    }
+}
+
+/// The expenses recorded for the log file `log`, oldest first, decrypted if
+/// the file is encrypted.
+pub fn read_expenses(log: &Path) -> Result<Vec<Expense>, ITrackerError> {
+    let path = schema::expenses_path(log);
+    let Some(data) = crypto::read_sidecar(&path)? else {
+        return Ok(Vec::new());
+    };
+    let mut reader = csv::Reader::from_reader(data.as_slice());
+    reader
+        .deserialize()
+        .collect::<Result<Vec<Expense>, _>>()
+        .map_err(ITrackerError::csv(&path))
+}
+
+/// Replaces the expenses recorded for `log`, encrypted like the log files.
+pub fn write_expenses(log: &Path, expenses: &[Expense]) -> Result<(), ITrackerError> {
+    let path = schema::expenses_path(log);
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for expense in expenses {
+        writer
+            .serialize(expense)
+            .map_err(ITrackerError::csv(&path))?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|e| ITrackerError::file(&path)(e.into_error()))?;
+    crypto::write_sidecar(&path, data)
+}
+
+/// Records an expense for `log`, numbering it after the last one.
+///
+/// # Returns
+/// - `Ok(Expense)`: The expense as recorded.
+/// - `Err(ITrackerError)`: If the amount is not positive, or the file cannot
+///   be read or written.
+pub fn add_expense(log: &Path, mut expense: Expense) -> Result<Expense, ITrackerError> {
+    if !(expense.amount > 0.0 && expense.amount.is_finite()) {
+        return Err(ITrackerError::Config(format!(
+            "The amount of an expense must be a positive number, got {}",
+            expense.amount
+        )));
+    }
+    let mut expenses = read_expenses(log)?;
+    expense.id = expenses.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+    expense.amount = expense.money().amount;
+    expenses.push(expense.clone());
+    write_expenses(log, &expenses)?;
+    Ok(expense)
+}
+
+/// The expenses dated between `from` and `to` (both inclusive).
+pub fn between(expenses: &[Expense], from: NaiveDate, to: NaiveDate) -> Vec<&Expense> {
+    expenses
+        .iter()
+        .filter(|expense| (from..=to).contains(&expense.date))
+        .collect()
+}
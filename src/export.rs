@@ -0,0 +1,114 @@
+//! Exports of the log into the import formats of other time trackers.
+//!
+//! Exports hold the stopped entries with their exact tracked time; the
+//! importing service applies its own rounding. Projects are exported as the
+//! client of their `[clients.<project>]` section, or as a client of the
+//! same name.
+
+use crate::{error::ITrackerError, invoice::Billing, log::LogEntry, progress::Progress, report};
+use chrono::Utc;
+use chrono_tz::Tz;
+use std::{io, str::FromStr};
+
+/// The formats entries can be exported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The CSV of Harvest's bulk import of time entries.
+    Harvest,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "harvest" => Ok(Format::Harvest),
+            other => Err(format!(
+                "Unknown export format '{}', expected harvest",
+                other
+            )),
+        }
+    }
+}
+
+/// Columns of Harvest's time import, in its order.
+const HARVEST_COLUMNS: [&str; 6] = ["Date", "Client", "Project", "Task", "Notes", "Hours"];
+
+/// Writes the stopped entries in `format`, oldest first.
+///
+/// # Arguments
+/// - `entries`: The entries to export.
+/// - `format`: The format to write.
+/// - `billing`: The clients the projects belong to.
+/// - `tz`: The timezone the dates are written in.
+/// - `progress`: Counts each entry gone through.
+///
+/// # Returns
+/// - `Ok((String, usize))`: The export and the number of entries in it.
+/// - `Err(ITrackerError)`: `Io` if the export cannot be written.
+pub fn export(
+    entries: &[LogEntry],
+    format: Format,
+    billing: &Billing,
+    tz: Tz,
+    progress: &Progress,
+) -> Result<(String, usize), ITrackerError> {
+    let mut stopped: Vec<&LogEntry> = entries
+        .iter()
+        .filter(|entry| {
+            // Left out entries count as gone through right away
+            let exported = !entry.is_running() && entry.start().is_some();
+            if !exported {
+                progress.inc();
+            }
+            exported
+        })
+        .collect();
+    stopped.sort_by_key(|entry| entry.start());
+    match format {
+        Format::Harvest => harvest(&stopped, billing, tz, progress).map(|csv| (csv, stopped.len())),
+    }
+}
+
+/// One row per entry: the local date, the client and project, the title as
+/// task, the description as notes and the hours in decimals.
+fn harvest(
+    entries: &[&LogEntry],
+    billing: &Billing,
+    tz: Tz,
+    progress: &Progress,
+) -> Result<String, ITrackerError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(HARVEST_COLUMNS)
+        .map_err(io::Error::from)?;
+    for entry in entries {
+        let project = match entry.project.trim() {
+            "" => report::NO_PROJECT,
+            project => project,
+        };
+        let date = report::local_date(entry, tz)
+            .map(|date| date.to_string())
+            .unwrap_or_default();
+        let hours = report::hours(report::tracked_seconds(entry, Utc::now()));
+        let task = entry.display_title();
+        // Without a title the task already is the description
+        let notes = match entry.message.trim() {
+            notes if notes == task => "",
+            notes => notes,
+        };
+        writer
+            .write_record([
+                date.as_str(),
+                billing.client_name(project),
+                project,
+                task,
+                notes,
+                &format!("{:.2}", hours),
+            ])
+            .map_err(io::Error::from)?;
+        progress.inc();
+    }
+    let data = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
@@ -0,0 +1,230 @@
+//! The `grpc` command: a typed API defined in `proto/itracker.proto`, built
+//! with the `grpc` feature.
+//!
+//! The service works on one log file like the `serve` command and converts
+//! between the generated messages and the library types.
+
+// `Status` is the error type tonic dictates for every handler
+#![allow(clippy::result_large_err)]
+
+use crate::{
+    config::ConfigData,
+    daemon::{Operation, Timers},
+    error::ITrackerError,
+    log::{LogEntry, TaskDetails},
+    month,
+    report::{today, Summary},
+};
+use chrono::{NaiveDate, Utc};
+use std::sync::Arc;
+use tonic::{Code, Request, Response, Status};
+
+/// Messages, client and server generated from `proto/itracker.proto`.
+pub mod proto {
+    tonic::include_proto!("itracker");
+}
+
+use proto::tracker_server::{Tracker as TrackerApi, TrackerServer};
+
+impl From<proto::Task> for TaskDetails {
+    fn from(task: proto::Task) -> Self {
+        TaskDetails {
+            title: task.title,
+            description: task.description,
+            project: task.project,
+            tags: task.tags,
+            estimate: task.estimate,
+            ..TaskDetails::default()
+        }
+    }
+}
+
+impl From<LogEntry> for proto::Entry {
+    fn from(entry: LogEntry) -> Self {
+        proto::Entry {
+            index: entry.index as u64,
+            elapsed_time: entry.elapsed_time.trim().parse().unwrap_or(0),
+            paused_time: entry.paused_time.trim().parse().unwrap_or(0),
+            start_time: entry.start_time,
+            message: entry.message,
+            title: entry.title,
+            project: entry.project,
+            tags: entry.tags,
+            estimate: entry.estimate,
+            paused_since: entry.paused_since,
+        }
+    }
+}
+
+impl From<Summary> for proto::Summary {
+    fn from(summary: Summary) -> Self {
+        proto::Summary {
+            per_day: summary
+                .per_day
+                .into_iter()
+                .map(|(day, seconds)| (day.format("%Y-%m-%d").to_string(), seconds))
+                .collect(),
+            per_project: summary.per_project.into_iter().collect(),
+            total: summary.total,
+            entries: summary.entries as u64,
+        }
+    }
+}
+
+fn status(e: ITrackerError) -> Status {
+    let code = match e {
+        ITrackerError::EntryNotFound { .. } | ITrackerError::NoActiveTimer { .. } => {
+            Code::NotFound
+        }
+        ITrackerError::NotRunning { .. }
+        | ITrackerError::AlreadyPaused { .. }
+        | ITrackerError::NotPaused { .. }
+        | ITrackerError::Locked { .. }
+        | ITrackerError::Invoiced { .. }
+        // The daemon only refuses operations the state of the entry does not allow
+        | ITrackerError::Daemon(_) => Code::FailedPrecondition,
+        _ => Code::Internal,
+    };
+    Status::new(code, e.to_string())
+}
+
+/// The `Tracker` service on one log file.
+#[derive(Debug)]
+struct Service {
+    timers: Timers,
+    config: Arc<ConfigData>,
+}
+
+impl Service {
+    /// Applies an operation to the given entry, or the running one.
+    fn run(
+        &self,
+        index: Option<u64>,
+        operation: fn(Option<usize>) -> Operation,
+    ) -> Result<Response<proto::Entry>, Status> {
+        let index = index.map(|index| index as usize);
+        if let Some(index) = index {
+            month::ensure_unlocked(&self.config, self.timers.tracker().storage(), index, false)
+                .map_err(status)?;
+        }
+        let entry = self.timers.run(operation(index)).map_err(status)?;
+        Ok(Response::new(entry.into()))
+    }
+}
+
+// Requests are handled on a single thread, one after another, so the blocking
+// file access in the handlers cannot interleave.
+#[tonic::async_trait]
+impl TrackerApi for Service {
+    async fn start(&self, request: Request<proto::Task>) -> Result<Response<proto::Entry>, Status> {
+        let task = request.into_inner().into();
+        let entry = self.timers.run(Operation::Start { task }).map_err(status)?;
+        Ok(Response::new(entry.into()))
+    }
+
+    async fn stop(
+        &self,
+        request: Request<proto::EntryRequest>,
+    ) -> Result<Response<proto::Entry>, Status> {
+        self.run(request.into_inner().index, |index| Operation::Stop {
+            index,
+        })
+    }
+
+    async fn pause(
+        &self,
+        request: Request<proto::EntryRequest>,
+    ) -> Result<Response<proto::Entry>, Status> {
+        self.run(request.into_inner().index, |index| Operation::Pause {
+            index,
+        })
+    }
+
+    async fn resume(
+        &self,
+        request: Request<proto::EntryRequest>,
+    ) -> Result<Response<proto::Entry>, Status> {
+        self.run(request.into_inner().index, |index| Operation::Resume {
+            index,
+        })
+    }
+
+    async fn running(
+        &self,
+        _request: Request<proto::RunningRequest>,
+    ) -> Result<Response<proto::Entry>, Status> {
+        let entry = self.timers.run(Operation::Running).map_err(status)?;
+        Ok(Response::new(entry.into()))
+    }
+
+    async fn list(
+        &self,
+        _request: Request<proto::ListRequest>,
+    ) -> Result<Response<proto::EntryList>, Status> {
+        let entries = self.timers.tracker().storage().entries().map_err(status)?;
+        Ok(Response::new(proto::EntryList {
+            entries: entries.into_iter().map(proto::Entry::from).collect(),
+        }))
+    }
+
+    async fn report(
+        &self,
+        request: Request<proto::ReportRequest>,
+    ) -> Result<Response<proto::Summary>, Status> {
+        let request = request.into_inner();
+        let tz = self.config.timezone();
+        let day = today(Utc::now(), tz);
+        let from = parse_date(&request.from, "from")?.unwrap_or(day);
+        let to = parse_date(&request.to, "to")?.unwrap_or(from.max(day));
+        let summary = self
+            .timers
+            .tracker()
+            .summary(from, to, tz)
+            .map_err(status)?;
+        Ok(Response::new(summary.into()))
+    }
+}
+
+fn parse_date(value: &str, field: &str) -> Result<Option<NaiveDate>, Status> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| {
+            Status::invalid_argument(format!(
+                "Invalid {} '{}', expected YYYY-MM-DD",
+                field, value
+            ))
+        })
+}
+
+/// Answers gRPC requests on `127.0.0.1:port` until the process is stopped.
+///
+/// # Arguments
+/// - `port`: The port to listen on.
+/// - `timers`: The log file the service works on.
+/// - `config`: Used for the timezone of reports and for month locks.
+pub fn serve(port: u16, timers: Timers, config: ConfigData) -> Result<(), ITrackerError> {
+    let address = ([127, 0, 0, 1], port).into();
+    println!(
+        "Serving {} over gRPC on {}",
+        timers.tracker().storage().path(),
+        address
+    );
+    let service = Service {
+        timers,
+        config: Arc::new(config),
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime
+        .block_on(
+            tonic::transport::Server::builder()
+                .add_service(TrackerServer::new(service))
+                .serve(address),
+        )
+        .map_err(|e| ITrackerError::Config(format!("Cannot serve on port {}: {}", port, e)))
+}
@@ -0,0 +1,136 @@
+//! Shell commands run when a timer starts, pauses, resumes or stops.
+//!
+//! Hooks run after the change was written, through `sh -c` (`cmd /C` on
+//! Windows), with the fields of the entry in `ITRACKER_*` environment
+//! variables. They run wherever the change is made: in the command line,
+//! the TUI, the servers, or in the daemon while one is running. A failing
+//! hook is reported but does not undo the change.
+
+use crate::log::{format_metadata, LogEntry};
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Stdio};
+
+/// The `[hooks]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HooksConfig {
+    pub on_start: Option<String>,
+    pub on_pause: Option<String>,
+    pub on_resume: Option<String>,
+    pub on_stop: Option<String>,
+}
+
+/// A change of a timer hooks run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    Start,
+    Pause,
+    Resume,
+    Stop,
+}
+
+impl HookEvent {
+    /// The name passed to hooks in `ITRACKER_EVENT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::Start => "start",
+            HookEvent::Pause => "pause",
+            HookEvent::Resume => "resume",
+            HookEvent::Stop => "stop",
+        }
+    }
+}
+
+/// The commands configured per event; none by default.
+#[derive(Debug, Default, Clone)]
+pub struct Hooks {
+    on_start: Option<String>,
+    on_pause: Option<String>,
+    on_resume: Option<String>,
+    on_stop: Option<String>,
+}
+
+impl Hooks {
+    /// Resolves the `[hooks]` section; empty commands count as unset.
+    pub fn from_config(config: Option<&HooksConfig>) -> Self {
+        let Some(config) = config else {
+            return Hooks::default();
+        };
+        let command = |command: &Option<String>| {
+            command
+                .as_ref()
+                .filter(|command| !command.trim().is_empty())
+                .cloned()
+        };
+        Hooks {
+            on_start: command(&config.on_start),
+            on_pause: command(&config.on_pause),
+            on_resume: command(&config.on_resume),
+            on_stop: command(&config.on_stop),
+        }
+    }
+
+    fn command(&self, event: HookEvent) -> Option<&str> {
+        match event {
+            HookEvent::Start => self.on_start.as_deref(),
+            HookEvent::Pause => self.on_pause.as_deref(),
+            HookEvent::Resume => self.on_resume.as_deref(),
+            HookEvent::Stop => self.on_stop.as_deref(),
+        }
+    }
+
+    /// Runs the hook of `event`, if one is configured, and waits for it.
+    ///
+    /// Failures are printed to stderr rather than returned, as the timer
+    /// already changed.
+    ///
+    /// # Arguments
+    /// - `event`: What happened to the timer.
+    /// - `entry`: The entry as written to the log file.
+    /// - `file`: The log file.
+    pub fn run(&self, event: HookEvent, entry: &LogEntry, file: &str) {
+        let Some(command) = self.command(event) else {
+            return;
+        };
+        let mut shell = if cfg!(windows) {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            shell
+        };
+        let status = shell
+            .arg(command)
+            .envs(environment(event, entry, file))
+            .stdin(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Hook on_{} failed: {}", event.name(), status),
+            Err(e) => eprintln!("Hook on_{} could not run: {}", event.name(), e),
+        }
+    }
+}
+
+/// The variables describing `entry` to a hook.
+fn environment(event: HookEvent, entry: &LogEntry, file: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("ITRACKER_EVENT", event.name().to_string()),
+        ("ITRACKER_FILE", file.to_string()),
+        ("ITRACKER_INDEX", entry.index.to_string()),
+        ("ITRACKER_TITLE", entry.display_title().to_string()),
+        ("ITRACKER_DESCRIPTION", entry.message.clone()),
+        ("ITRACKER_PROJECT", entry.project.clone()),
+        ("ITRACKER_TAGS", entry.tags.join(",")),
+        ("ITRACKER_START", entry.start_time.clone()),
+        ("ITRACKER_ELAPSED", entry.elapsed_time.trim().to_string()),
+        ("ITRACKER_PAUSED", entry.paused_time.trim().to_string()),
+        (
+            "ITRACKER_ESTIMATE",
+            entry.estimate.map(|e| e.to_string()).unwrap_or_default(),
+        ),
+        ("ITRACKER_METADATA", format_metadata(&entry.metadata)),
+    ]
+}
@@ -0,0 +1,108 @@
+//! Translations of the messages of the command line and the viewer.
+//!
+//! Messages live in Fluent catalogs under `locales/`, one per language and
+//! compiled into the binary. The language is chosen once per run: the
+//! `language` setting, else the locale of `LC_ALL`, `LC_MESSAGES` or `LANG`,
+//! else English. Messages a catalog lacks fall back to English.
+
+use crate::error::ITrackerError;
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+/// The catalogs by language, English first.
+const CATALOGS: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+];
+
+static ENGLISH: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+static SELECTED: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+
+/// The languages with a catalog, e.g. `de`.
+pub fn languages() -> impl Iterator<Item = &'static str> {
+    CATALOGS.iter().map(|(language, _)| *language)
+}
+
+/// Checks the `language` setting.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` if no catalog exists for the language.
+pub fn validate(language: &str) -> Result<(), ITrackerError> {
+    if catalog(language).is_some() {
+        return Ok(());
+    }
+    Err(ITrackerError::Config(format!(
+        "No translation for '{}', available languages: {}",
+        language,
+        languages().collect::<Vec<_>>().join(", ")
+    )))
+}
+
+/// Selects the language of this run, the `language` setting if set, else
+/// the one of the environment's locale.
+///
+/// # Returns
+/// - `false`: If a language was selected before; it stays in use.
+pub fn select(language: Option<&str>) -> bool {
+    let from_env = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    let language = language
+        .map(str::to_string)
+        .or(from_env)
+        .and_then(|locale| catalog(&locale))
+        .unwrap_or("en");
+    SELECTED.set(bundle(language)).is_ok()
+}
+
+/// The message `id` in the selected language, e.g. `tr("report-total")`.
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+/// The message `id` in the selected language with its variables filled in,
+/// e.g. `tr_args("timer-started", &[("index", 3.into())])`.
+pub fn tr_args(id: &str, args: &[(&str, FluentValue)]) -> String {
+    let english = ENGLISH.get_or_init(|| bundle("en"));
+    let bundle = SELECTED.get().unwrap_or(english);
+    let message = bundle
+        .get_message(id)
+        .map(|message| (bundle, message))
+        .or_else(|| english.get_message(id).map(|message| (english, message)));
+    let Some((bundle, pattern)) = message.and_then(|(b, m)| Some((b, m.value()?))) else {
+        return id.to_string();
+    };
+    let mut values = FluentArgs::new();
+    for (name, value) in args {
+        values.set(*name, value.clone());
+    }
+    let mut errors = Vec::new();
+    bundle
+        .format_pattern(pattern, Some(&values), &mut errors)
+        .into_owned()
+}
+
+/// The catalog language of a locale such as `de_DE.UTF-8`, `None` without one.
+fn catalog(locale: &str) -> Option<&'static str> {
+    let tag = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = tag.replace('_', "-").parse::<LanguageIdentifier>().ok()?;
+    languages().find(|known| *known == language.language.as_str())
+}
+
+fn bundle(language: &str) -> FluentBundle<FluentResource> {
+    let source = CATALOGS
+        .iter()
+        .find(|(known, _)| *known == language)
+        .map(|(_, source)| *source)
+        .unwrap_or(CATALOGS[0].1);
+    let resource =
+        FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, _)| resource);
+    let id = language.parse().unwrap_or_default();
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Terminals render the isolation marks around variables as garbage
+    bundle.set_use_isolating(false);
+    let _ = bundle.add_resource(resource);
+    bundle
+}
@@ -0,0 +1,108 @@
+//! Symbols of the timer states, shown by `status`, `list`, `prompt`, `tmux`
+//! and the viewer.
+//!
+//! The `[icons]` section picks a preset, `unicode` (the default), `emoji`,
+//! `nerd` (Nerd Font glyphs) or `ascii`, and each symbol can be overridden.
+
+use crate::error::ITrackerError;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+static SELECTED: OnceLock<Icons> = OnceLock::new();
+
+/// The `[icons]` section of the configuration file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IconsConfig {
+    /// Base symbols: `unicode` (default), `emoji`, `nerd` or `ascii`.
+    pub preset: Option<String>,
+    pub running: Option<String>,
+    pub paused: Option<String>,
+    pub stopped: Option<String>,
+    /// Running timers in shell prompts; `running` if only that is overridden.
+    pub prompt: Option<String>,
+}
+
+/// The symbol of each timer state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icons {
+    pub running: String,
+    pub paused: String,
+    /// Entries that were stopped, and the viewer without a running timer.
+    pub stopped: String,
+    /// A running timer in shell prompts, which show the time first.
+    pub prompt: String,
+}
+
+impl Default for Icons {
+    fn default() -> Self {
+        Icons::preset("unicode").unwrap_or_else(|| Icons::new("▶", "⏸", "■", "⏱"))
+    }
+}
+
+impl Icons {
+    fn new(running: &str, paused: &str, stopped: &str, prompt: &str) -> Self {
+        Icons {
+            running: running.to_string(),
+            paused: paused.to_string(),
+            stopped: stopped.to_string(),
+            prompt: prompt.to_string(),
+        }
+    }
+
+    fn preset(name: &str) -> Option<Self> {
+        match name {
+            "unicode" => Some(Icons::new("▶", "⏸", "■", "⏱")),
+            "emoji" => Some(Icons::new("🟢", "🟡", "⚪", "🕑")),
+            "nerd" => Some(Icons::new(
+                "\u{f040a}",
+                "\u{f03e4}",
+                "\u{f04db}",
+                "\u{f051b}",
+            )),
+            "ascii" => Some(Icons::new(">", "=", "-", "*")),
+            _ => None,
+        }
+    }
+
+    /// Builds the symbols from the `[icons]` section.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: `Config` if the preset is unknown.
+    pub fn from_config(config: Option<&IconsConfig>) -> Result<Self, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(Icons::default());
+        };
+        let preset = config.preset.as_deref().unwrap_or("unicode");
+        let mut icons = Icons::preset(preset).ok_or_else(|| {
+            ITrackerError::Config(format!(
+                "Unknown icons preset '{}', expected unicode, emoji, nerd or ascii",
+                preset
+            ))
+        })?;
+        if let Some(running) = &config.running {
+            icons.running = running.clone();
+            icons.prompt = running.clone();
+        }
+        if let Some(paused) = &config.paused {
+            icons.paused = paused.clone();
+        }
+        if let Some(stopped) = &config.stopped {
+            icons.stopped = stopped.clone();
+        }
+        if let Some(prompt) = &config.prompt {
+            icons.prompt = prompt.clone();
+        }
+        Ok(icons)
+    }
+}
+
+/// Selects the symbols of this run.
+pub fn select(icons: Icons) {
+    let _ = SELECTED.set(icons);
+}
+
+/// The symbols of this run, the `unicode` preset until [`select`] was called.
+pub fn current() -> &'static Icons {
+    SELECTED.get_or_init(Icons::default)
+}
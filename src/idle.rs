@@ -0,0 +1,222 @@
+//! Opt-in pausing of the running timer while the user is away.
+//!
+//! While the daemon runs, the time since the last keyboard or mouse input is
+//! checked every `interval_seconds`. Once it reaches `minutes`, the running
+//! timer is paused as of the last input and the entry is marked, so that
+//! resuming it from the command line asks what the idle time was: work to
+//! keep, a break to discard, or time spent on another task.
+//!
+//! The idle time is asked for with `ioreg` on macOS and `xprintidle` on X11.
+//! Any other desktop can set `command` to a program printing the idle time
+//! in seconds.
+
+use crate::{
+    daemon::{Operation, Timers},
+    error::ITrackerError,
+    log::{LogEntry, TaskDetails},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{process, str::FromStr, thread};
+
+/// Minutes without input after which the timer is paused, unless configured otherwise.
+pub const DEFAULT_MINUTES: u32 = 10;
+
+/// Seconds between checks unless configured otherwise.
+pub const DEFAULT_INTERVAL_SECONDS: u64 = 30;
+
+/// Metadata key marking an entry paused by idle detection.
+pub const METADATA_KEY: &str = "idle";
+
+/// The `[idle]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdleConfig {
+    /// Pause the running timer while the daemon runs, off by default.
+    pub enabled: Option<bool>,
+    /// Minutes without input after which the timer is paused.
+    pub minutes: Option<u32>,
+    pub interval_seconds: Option<u64>,
+    /// Program printing the seconds since the last input, instead of the
+    /// desktop's own tools.
+    pub command: Option<String>,
+}
+
+/// What the idle time of an entry paused by idle detection was.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IdleChoice {
+    /// Work after all: the idle time counts as tracked.
+    Keep,
+    /// A break: the idle time stays paused, as a plain resume leaves it.
+    Discard,
+    /// Work on another task: the idle time stays paused and becomes a
+    /// stopped entry of `task`.
+    Move { task: TaskDetails },
+}
+
+/// The answer to what the idle time was, given with `--idle` on resume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAnswer {
+    Keep,
+    Discard,
+    Move,
+}
+
+impl FromStr for IdleAnswer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "keep" => Ok(IdleAnswer::Keep),
+            "discard" => Ok(IdleAnswer::Discard),
+            "move" => Ok(IdleAnswer::Move),
+            _ => Err(format!("expected keep, discard or move, got '{}'", s)),
+        }
+    }
+}
+
+/// Pauses the running timer when no input came for a while.
+#[derive(Debug, Clone)]
+pub struct IdleDetector {
+    /// Seconds without input after which the timer is paused.
+    threshold: u64,
+    interval: u64,
+    command: Option<String>,
+}
+
+impl IdleDetector {
+    /// Resolves the `[idle]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Some(IdleDetector))`: If idle detection is enabled.
+    /// - `Ok(None)`: If it is off, as it is by default.
+    /// - `Err(ITrackerError)`: `Config` if the minutes or the interval are zero.
+    pub fn from_config(config: Option<&IdleConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(config) = config.filter(|config| config.enabled.unwrap_or(false)) else {
+            return Ok(None);
+        };
+        let minutes = config.minutes.unwrap_or(DEFAULT_MINUTES);
+        if minutes == 0 {
+            return Err(ITrackerError::Config(
+                "minutes must be at least 1".to_string(),
+            ));
+        }
+        let interval = config.interval_seconds.unwrap_or(DEFAULT_INTERVAL_SECONDS);
+        if interval == 0 {
+            return Err(ITrackerError::Config(
+                "interval_seconds must be at least 1".to_string(),
+            ));
+        }
+        Ok(Some(IdleDetector {
+            threshold: u64::from(minutes) * 60,
+            interval,
+            command: config.command.clone().filter(|command| !command.is_empty()),
+        }))
+    }
+
+    /// Checks the idle time every interval in the background, for the daemon,
+    /// and pauses the running timer of `timers` once it is long enough.
+    /// Failures are reported once, until checking works again.
+    pub fn schedule(self, timers: Timers) {
+        thread::spawn(move || {
+            let mut failing = false;
+            loop {
+                let checked = self
+                    .idle_seconds()
+                    .and_then(|idle| self.check(&timers, idle, Utc::now()));
+                match checked {
+                    Ok(_) => failing = false,
+                    Err(e) if !failing => {
+                        eprintln!("Cannot pause the timer while idle: {}", e);
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+                thread::sleep(std::time::Duration::from_secs(self.interval));
+            }
+        });
+    }
+
+    /// Pauses the running timer as of the last input if `idle` seconds
+    /// passed since, which reach the threshold.
+    ///
+    /// # Returns
+    /// - `Ok(Some(LogEntry))`: The entry paused.
+    /// - `Ok(None)`: If the user was not idle long enough, or no timer runs
+    ///   unpaused.
+    /// - `Err(ITrackerError)`: If the log file cannot be read or written.
+    pub fn check(
+        &self,
+        timers: &Timers,
+        idle: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Option<LogEntry>, ITrackerError> {
+        if idle < self.threshold {
+            return Ok(None);
+        }
+        // Reading is safe next to the daemon, only changes go through it
+        let running = timers
+            .tracker()
+            .running()?
+            .filter(|entry| entry.paused_since().is_none());
+        let Some(running) = running else {
+            return Ok(None);
+        };
+        let since = now - Duration::seconds(idle as i64);
+        timers
+            .run(Operation::PauseIdle {
+                index: Some(running.index),
+                since,
+            })
+            .map(Some)
+    }
+
+    /// Seconds since the last keyboard or mouse input.
+    fn idle_seconds(&self) -> Result<u64, ITrackerError> {
+        if let Some(command) = &self.command {
+            let output = run("sh", &["-c", command])?;
+            return parse(output.trim(), 1);
+        }
+        if cfg!(target_os = "macos") {
+            // Nanoseconds, in a line like `"HIDIdleTime" = 1234567890`
+            let output = run("ioreg", &["-c", "IOHIDSystem", "-d", "4"])?;
+            let value = output
+                .lines()
+                .find_map(|line| line.split_once("\"HIDIdleTime\" = "))
+                .map(|(_, value)| value.trim())
+                .ok_or_else(|| ITrackerError::Idle("ioreg printed no HIDIdleTime".to_string()))?;
+            return parse(value, 1_000_000_000);
+        }
+        if std::env::var_os("DISPLAY").is_some() {
+            // Milliseconds
+            return parse(run("xprintidle", &[])?.trim(), 1000);
+        }
+        Err(ITrackerError::Idle(
+            "No supported desktop found, set `idle.command` to a program printing the seconds since the last input".to_string(),
+        ))
+    }
+}
+
+/// Reads an idle time printed in `1 / per_second` seconds.
+fn parse(value: &str, per_second: u64) -> Result<u64, ITrackerError> {
+    value
+        .parse::<u64>()
+        .map(|value| value / per_second)
+        .map_err(|_| ITrackerError::Idle(format!("Invalid idle time '{}'", value)))
+}
+
+fn run(program: &str, args: &[&str]) -> Result<String, ITrackerError> {
+    let output = process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| ITrackerError::Idle(format!("Cannot run {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(ITrackerError::Idle(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,608 @@
+//! An index of a log file, so the running timer, the highest index and
+//! single entries are found without parsing the whole log, however many
+//! years it holds.
+//!
+//! The index comes in two files. `<log>.index` is small and read on every
+//! use: the number of rows, the highest index, the last row and the entries
+//! running, with the size and modification time of the log and its journal
+//! they were taken from. `<log>.rows` holds the byte offset and start of
+//! every row and is only read to find entries that are not running, e.g.
+//! pages of the viewer.
+//!
+//! Both are kept up to date as the log changes. Rows appended since are
+//! indexed on their own and added to the end of `<log>.rows`. Amendments
+//! made through [`Storage::update`](crate::storage::Storage::update) update
+//! the running entries in place; others are noticed by the journal's stamp,
+//! and its at most [`schema::JOURNAL_LIMIT`] amendments are read again. A
+//! log replaced, e.g. by a delete, loses its index (see [`schema::replace`])
+//! and it is built again. Encrypted logs cannot be read by offset and have
+//! no index.
+
+use crate::{
+    crypto,
+    error::ITrackerError,
+    log::LogEntry,
+    schema::{self, Record},
+    status::{self, Stamp},
+};
+use chrono::{DateTime, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::OnceCell,
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// Where a row is, and its start, one line of `<log>.rows`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Row {
+    /// Byte offset of the row in the log.
+    offset: u64,
+    /// Unix seconds, `None` if the start cannot be read.
+    start: Option<i64>,
+}
+
+/// The rows of `<log>.rows`, by index.
+type Rows = BTreeMap<usize, Row>;
+
+/// Contents of `<log>.index`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Summary {
+    /// The log file when it was indexed.
+    log: Stamp,
+    /// The journal when `running` was brought up to date, `None` without one.
+    journal: Option<Stamp>,
+    /// The byte the indexed part of the log ends at.
+    end: u64,
+    /// Offset of the last row, or 0 without rows.
+    tail: u64,
+    /// Hash of the bytes from `tail` to `end`, to tell appends from other changes.
+    tail_hash: String,
+    /// The bytes at the start of `<log>.rows` that belong to this index.
+    rows_len: u64,
+    count: usize,
+    /// The highest index of the rows, 0 without rows.
+    highest: usize,
+    /// The index of the last row, `None` without rows.
+    last: Option<usize>,
+    /// Offsets of the rows running as written in the log, before amendments.
+    running_rows: BTreeMap<usize, u64>,
+    /// The entries running with the amendments of the journal applied.
+    running: BTreeMap<usize, LogEntry>,
+}
+
+/// The index of a log file, with the amendments of its journal.
+#[derive(Debug, Clone)]
+pub struct LogIndex {
+    path: PathBuf,
+    summary: Summary,
+    /// The rows of `<log>.rows`, read when first needed.
+    rows: OnceCell<Rows>,
+    /// The latest amendment of each entry, read when first needed.
+    amended: OnceCell<HashMap<usize, LogEntry>>,
+}
+
+impl LogIndex {
+    /// Loads the index of the log file at `path`, bringing it up to date first.
+    ///
+    /// # Returns
+    /// - `Ok(None)`: If the log file does not exist or is encrypted, so
+    ///   callers read it whole.
+    /// - `Err(ITrackerError)`: `DuplicateIndex` if rows share an index, or
+    ///   if the log cannot be read or a row is invalid.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>, ITrackerError> {
+        let path = path.as_ref();
+        if crypto::key().is_some() {
+            return Ok(None);
+        }
+        let Some(log) = status::stamp(path)? else {
+            return Ok(None);
+        };
+        let journal = status::stamp(&schema::journal_path(path))?;
+        let stored = read_summary(path).filter(|stored| {
+            // Rows cut off, e.g. by a crash while adding them, are indexed again
+            fs::metadata(schema::rows_path(path)).is_ok_and(|rows| rows.len() >= stored.rows_len)
+        });
+        if let Some(summary) = stored
+            .as_ref()
+            .filter(|stored| stored.log == log && stored.journal == journal)
+        {
+            return Ok(Some(LogIndex::new(path, summary.clone(), None)));
+        }
+
+        let (summary, built, added) = match stored {
+            Some(stored) if stored.log == log => (stored, None, Vec::new()),
+            stored => {
+                let appended = match stored {
+                    Some(stored) => appended(path, stored, log)?,
+                    None => None,
+                };
+                match appended {
+                    Some((summary, added)) => (summary, None, added),
+                    None => match build(path, log)? {
+                        Some((summary, rows)) => (summary, Some(rows), Vec::new()),
+                        None => return Ok(None),
+                    },
+                }
+            }
+        };
+        let is_built = built.is_some();
+        let mut index = LogIndex::new(path, summary, built);
+        index.refresh(journal)?;
+
+        // Without the index later calls are only slower. One built from a
+        // log replaced meanwhile is not kept, as its removal by
+        // `schema::replace` may already have happened
+        if status::stamp(path)? == Some(log) {
+            let _ = index.save(is_built, &added);
+        }
+        Ok(Some(index))
+    }
+
+    fn new(path: &Path, summary: Summary, rows: Option<Rows>) -> Self {
+        LogIndex {
+            path: path.to_path_buf(),
+            summary,
+            rows: rows.map(OnceCell::from).unwrap_or_default(),
+            amended: OnceCell::new(),
+        }
+    }
+
+    /// The number of entries in the log.
+    pub fn len(&self) -> usize {
+        self.summary.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.summary.count == 0
+    }
+
+    /// The highest index of the entries in the log, 0 without entries.
+    pub fn highest_index(&self) -> usize {
+        self.summary.highest
+    }
+
+    /// The indices of the entries in file order.
+    pub fn indices(&self) -> Result<Vec<usize>, ITrackerError> {
+        let mut indices: Vec<(u64, usize)> = self
+            .rows()?
+            .iter()
+            .map(|(index, row)| (row.offset, *index))
+            .collect();
+        indices.sort_unstable();
+        Ok(indices.into_iter().map(|(_, index)| index).collect())
+    }
+
+    /// The start of the entry with `index`, `None` if there is no such entry
+    /// or its start cannot be read.
+    pub fn start(&self, index: usize) -> Result<Option<DateTime<Utc>>, ITrackerError> {
+        let start = match self.current(index)? {
+            Some(entry) => entry.start().map(|start| start.timestamp()),
+            None => self.rows()?.get(&index).and_then(|row| row.start),
+        };
+        Ok(start.and_then(|start| DateTime::from_timestamp(start, 0)))
+    }
+
+    /// Reads the entry with `index`.
+    ///
+    /// # Returns
+    /// - `Ok(None)`: If no entry has this index.
+    /// - `Err(ITrackerError)`: If its row cannot be read.
+    pub fn entry(&self, index: usize) -> Result<Option<LogEntry>, ITrackerError> {
+        Ok(self.entries(&[index])?.pop())
+    }
+
+    /// Reads the entries with `indices` in their order, opening the log once;
+    /// indices no entry has are skipped. Running and amended entries are
+    /// found without reading `<log>.rows`.
+    pub fn entries(&self, indices: &[usize]) -> Result<Vec<LogEntry>, ITrackerError> {
+        let mut file = None;
+        let mut entries = Vec::with_capacity(indices.len());
+        for &index in indices {
+            if let Some(entry) = self.current(index)? {
+                entries.push(entry.clone());
+                continue;
+            }
+            let Some(row) = self.rows()?.get(&index) else {
+                continue;
+            };
+            let file = match &mut file {
+                Some(file) => file,
+                None => {
+                    file.insert(File::open(&self.path).map_err(ITrackerError::file(&self.path))?)
+                }
+            };
+            entries.push(read_row(&self.path, file, index, row.offset)?);
+        }
+        Ok(entries)
+    }
+
+    /// Reads the last entry of the log file, e.g. the one just appended.
+    pub fn last(&self) -> Result<Option<LogEntry>, ITrackerError> {
+        match self.summary.last {
+            Some(index) => self.entry(index),
+            None => Ok(None),
+        }
+    }
+
+    /// The most recently started entry that is still running, like
+    /// [`Tracker::running`](crate::tracker::Tracker::running), found in
+    /// `<log>.index` alone.
+    pub fn running(&self) -> Result<Option<LogEntry>, ITrackerError> {
+        Ok(self
+            .summary
+            .running
+            .values()
+            .filter_map(|entry| entry.start().map(|start| (start, entry.index, entry)))
+            .max_by_key(|(start, index, _)| (*start, *index))
+            .map(|(_, _, entry)| entry.clone()))
+    }
+
+    /// Reads the entries started at or after `since`, in file order.
+    pub fn started_since(&self, since: DateTime<Utc>) -> Result<Vec<LogEntry>, ITrackerError> {
+        let mut indices = Vec::new();
+        for (index, row) in self.rows()? {
+            let start = match self.current(*index)? {
+                Some(entry) => entry.start().map(|start| start.timestamp()),
+                None => row.start,
+            };
+            if start.is_some_and(|start| start >= since.timestamp()) {
+                indices.push((row.offset, *index));
+            }
+        }
+        indices.sort_unstable();
+        let indices: Vec<usize> = indices.into_iter().map(|(_, index)| index).collect();
+        self.entries(&indices)
+    }
+
+    /// The entry with `index` if it is running or amended, as it is now.
+    fn current(&self, index: usize) -> Result<Option<&LogEntry>, ITrackerError> {
+        match self.summary.running.get(&index) {
+            Some(entry) => Ok(Some(entry)),
+            None => Ok(self.amended()?.get(&index)),
+        }
+    }
+
+    /// The amendments of the journal, read the first time.
+    fn amended(&self) -> Result<&HashMap<usize, LogEntry>, ITrackerError> {
+        if let Some(amended) = self.amended.get() {
+            return Ok(amended);
+        }
+        let amended = schema::read_journal(&self.path)?
+            .into_iter()
+            .map(|record| (record.index, LogEntry::from(record)))
+            .collect();
+        Ok(self.amended.get_or_init(|| amended))
+    }
+
+    /// The rows of `<log>.rows`, read the first time. Rows that cannot be
+    /// read there are indexed from the log again, and the index is removed
+    /// so that the next load builds it anew.
+    fn rows(&self) -> Result<&Rows, ITrackerError> {
+        if let Some(rows) = self.rows.get() {
+            return Ok(rows);
+        }
+        let rows = match read_rows(&self.path, self.summary.rows_len) {
+            Some(rows) => rows,
+            None => {
+                let _ = fs::remove_file(schema::index_path(&self.path));
+                let log = status::stamp(&self.path)?.unwrap_or(self.summary.log);
+                build(&self.path, log)?
+                    .map(|(_, rows)| rows)
+                    .unwrap_or_default()
+            }
+        };
+        Ok(self.rows.get_or_init(|| rows))
+    }
+
+    /// Brings the running entries up to date with the journal, whose stamp
+    /// is `journal`. Only rows running in the log are read, and the rows
+    /// of the log only if an amendment started an entry again.
+    fn refresh(&mut self, journal: Option<Stamp>) -> Result<(), ITrackerError> {
+        if self.summary.journal == journal {
+            return Ok(());
+        }
+        let amended = self.amended()?.clone();
+        let mut running = BTreeMap::new();
+        let mut file = None;
+        for (&index, &offset) in &self.summary.running_rows {
+            if amended.contains_key(&index) {
+                continue;
+            }
+            let file = match &mut file {
+                Some(file) => file,
+                None => {
+                    file.insert(File::open(&self.path).map_err(ITrackerError::file(&self.path))?)
+                }
+            };
+            running.insert(index, read_row(&self.path, file, index, offset)?);
+        }
+        for (index, entry) in amended {
+            // Amendments of entries deleted since are dropped, like `schema::read` does
+            let in_log = self.summary.running_rows.contains_key(&index)
+                || (index <= self.summary.highest && self.rows()?.contains_key(&index));
+            if entry.is_running() && in_log {
+                running.insert(index, entry);
+            }
+        }
+        self.summary.running = running;
+        self.summary.journal = journal;
+        Ok(())
+    }
+
+    /// Writes `<log>.index`, after adding the `added` lines to `<log>.rows`
+    /// or writing all of it if the rows were `built`.
+    fn save(&mut self, built: bool, added: &[u8]) -> io::Result<()> {
+        let rows_path = schema::rows_path(&self.path);
+        if let Some(rows) = self.rows.get().filter(|_| built) {
+            let mut rows: Vec<(usize, Row)> =
+                rows.iter().map(|(index, row)| (*index, *row)).collect();
+            rows.sort_unstable_by_key(|(_, row)| row.offset);
+            let lines = format_rows(rows);
+            write_replacing(&rows_path, lines.as_bytes())?;
+            self.summary.rows_len = lines.len() as u64;
+        } else if !added.is_empty() {
+            // Lines left by a writer that did not get to the index are dropped
+            let mut file = OpenOptions::new().write(true).open(&rows_path)?;
+            let at = self.summary.rows_len - added.len() as u64;
+            file.set_len(at)?;
+            file.seek(SeekFrom::Start(at))?;
+            file.write_all(added)?;
+        }
+        let contents = serde_json::to_string(&self.summary).map_err(io::Error::other)?;
+        write_replacing(&schema::index_path(&self.path), contents.as_bytes())
+    }
+}
+
+/// Updates the running entries in the index of the log at `path` after
+/// `record` was appended to its journal, so the next load need not read
+/// the journal. Nothing is done if the index was not current `before`.
+///
+/// # Arguments
+/// - `before`: The stamps of the log and its journal before the amendment.
+pub(crate) fn amended(
+    path: &Path,
+    before: (Option<Stamp>, Option<Stamp>),
+    record: &Record,
+) -> Result<(), ITrackerError> {
+    let Some(mut summary) = read_summary(path) else {
+        return Ok(());
+    };
+    let log = status::stamp(path)?;
+    // An encrypted log is rewritten instead, which removes the index
+    if before.0 != Some(summary.log) || log != before.0 || summary.journal != before.1 {
+        return Ok(());
+    }
+    let entry = LogEntry::from(record.clone());
+    if entry.is_running() {
+        summary.running.insert(entry.index, entry);
+    } else {
+        summary.running.remove(&entry.index);
+    }
+    summary.journal = status::stamp(&schema::journal_path(path))?;
+    // Without the update the next load reads the journal, which is only slower
+    if let Ok(contents) = serde_json::to_string(&summary) {
+        let _ = write_replacing(&schema::index_path(path), contents.as_bytes());
+    }
+    Ok(())
+}
+
+fn read_summary(path: &Path) -> Option<Summary> {
+    fs::read_to_string(schema::index_path(path))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+}
+
+/// Reads the first `len` bytes of `<log>.rows`, `None` if they cannot be read.
+fn read_rows(path: &Path, len: u64) -> Option<Rows> {
+    let mut contents = Vec::new();
+    File::open(schema::rows_path(path))
+        .ok()?
+        .take(len)
+        .read_to_end(&mut contents)
+        .ok()?;
+    if contents.len() as u64 != len {
+        return None;
+    }
+    let mut rows = BTreeMap::new();
+    for line in std::str::from_utf8(&contents).ok()?.lines() {
+        let mut fields = line.split(',');
+        let index = fields.next()?.parse().ok()?;
+        let offset = fields.next()?.parse().ok()?;
+        let start = match fields.next()? {
+            "" => None,
+            start => Some(start.parse().ok()?),
+        };
+        rows.insert(index, Row { offset, start });
+    }
+    Some(rows)
+}
+
+/// The lines of `<log>.rows` for `rows`: index, offset and start.
+fn format_rows(rows: impl IntoIterator<Item = (usize, Row)>) -> String {
+    rows.into_iter()
+        .map(|(index, row)| {
+            let start = row.start.map(|start| start.to_string()).unwrap_or_default();
+            format!("{},{},{}\n", index, row.offset, start)
+        })
+        .collect()
+}
+
+/// Writes `path` through a temporary file renamed over it, so readers see
+/// the old or the new contents.
+fn write_replacing(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(format!(".{}", std::process::id()));
+    let partial = PathBuf::from(partial);
+    let written = fs::write(&partial, contents).and_then(|()| fs::rename(&partial, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    written
+}
+
+/// Reads the row of the entry with `index` at `offset` of the log.
+fn read_row(
+    path: &Path,
+    file: &mut File,
+    index: usize,
+    offset: u64,
+) -> Result<LogEntry, ITrackerError> {
+    file.seek(SeekFrom::Start(offset))
+        .map_err(ITrackerError::file(path))?;
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(BufReader::new(file));
+    let mut row = StringRecord::new();
+    let found = reader
+        .read_record(&mut row)
+        .map_err(ITrackerError::csv(path))?;
+    let record = if found {
+        Some(schema::record_of(path, index, &row)?)
+    } else {
+        None
+    };
+    match record {
+        Some(record) if record.index == index => Ok(LogEntry::from(record)),
+        _ => Err(ITrackerError::file(path)(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("entry {} moved while the log was read, try again", index),
+        ))),
+    }
+}
+
+/// Indexes the whole log.
+///
+/// # Returns
+/// `None` if the log is encrypted.
+fn build(path: &Path, log: Stamp) -> Result<Option<(Summary, Rows)>, ITrackerError> {
+    let contents = fs::read(path).map_err(ITrackerError::file(path))?;
+    if crypto::is_encrypted(&contents) {
+        return Ok(None);
+    }
+    let mut summary = Summary {
+        log,
+        journal: None,
+        end: 0,
+        tail: 0,
+        tail_hash: String::new(),
+        rows_len: 0,
+        count: 0,
+        highest: 0,
+        last: None,
+        running_rows: BTreeMap::new(),
+        running: BTreeMap::new(),
+    };
+    let mut rows = Rows::new();
+    for (index, row) in index_rows(path, &contents, 0, 0, &mut summary)? {
+        // Refused like `schema::read` does, rather than finding one of them
+        if rows.insert(index, row).is_some() {
+            return Err(ITrackerError::DuplicateIndex {
+                path: path.into(),
+                index,
+            });
+        }
+    }
+    Ok(Some((summary, rows)))
+}
+
+/// Indexes the rows appended to the log since `stored` was built.
+///
+/// # Returns
+/// The updated summary and the lines to add to `<log>.rows`, or `None` if
+/// the log changed in other ways, so it has to be indexed again.
+fn appended(
+    path: &Path,
+    mut stored: Summary,
+    log: Stamp,
+) -> Result<Option<(Summary, Vec<u8>)>, ITrackerError> {
+    if log.len < stored.end {
+        return Ok(None);
+    }
+    let mut file = File::open(path).map_err(ITrackerError::file(path))?;
+    file.seek(SeekFrom::Start(stored.tail))
+        .map_err(ITrackerError::file(path))?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .map_err(ITrackerError::file(path))?;
+    let Some(known) = contents.get(..(stored.end - stored.tail) as usize) else {
+        return Ok(None);
+    };
+    if hash(known) != stored.tail_hash {
+        return Ok(None);
+    }
+    stored.log = log;
+    let (base, from, mut highest) = (stored.tail, known.len(), stored.highest);
+    let rows = index_rows(path, &contents, base, from, &mut stored)?;
+    // An index not above those before may be taken already, which only a
+    // full build can tell
+    for (index, _) in &rows {
+        if *index <= highest {
+            return Ok(None);
+        }
+        highest = *index;
+    }
+    let added = format_rows(rows).into_bytes();
+    stored.rows_len += added.len() as u64;
+    Ok(Some((stored, added)))
+}
+
+/// Indexes the rows in `contents` from its byte `from` on, adding them to
+/// `summary`.
+///
+/// # Arguments
+/// - `contents`: The log from byte `base` on, up to its end.
+/// - `from`: Where the rows not indexed yet start in `contents`.
+///
+/// # Returns
+/// The rows indexed, in file order.
+fn index_rows(
+    path: &Path,
+    contents: &[u8],
+    base: u64,
+    from: usize,
+    summary: &mut Summary,
+) -> Result<Vec<(usize, Row)>, ITrackerError> {
+    let start = base + from as u64;
+    let mut reader = ReaderBuilder::new()
+        .has_headers(start == 0)
+        .flexible(true)
+        .from_reader(&contents[from..]);
+    let mut row = StringRecord::new();
+    let mut rows = Vec::new();
+    while reader
+        .read_record(&mut row)
+        .map_err(ITrackerError::csv(path))?
+    {
+        let offset = start + row.position().map_or(0, |position| position.byte());
+        let entry = LogEntry::from(schema::record_of(path, summary.count + 1, &row)?);
+        let index = entry.index;
+        rows.push((
+            index,
+            Row {
+                offset,
+                start: entry.start().map(|start| start.timestamp()),
+            },
+        ));
+        if entry.is_running() {
+            summary.running_rows.insert(index, offset);
+            summary.running.insert(index, entry);
+        }
+        summary.count += 1;
+        summary.highest = summary.highest.max(index);
+        summary.last = Some(index);
+        summary.tail = offset;
+    }
+    summary.end = base + contents.len() as u64;
+    summary.tail_hash = hash(&contents[(summary.tail - base) as usize..]);
+    Ok(rows)
+}
+
+fn hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
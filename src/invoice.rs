@@ -0,0 +1,859 @@
+//! Invoices: the billable time of a range, grouped into line items.
+//!
+//! Every stopped entry of the range is rounded by the rounding profile of its
+//! client or the `billing` profile, else by the `[rounding]` rule of its
+//! project as in monthly reports, and then added to the line item of its
+//! task, its day or its own, depending on the chosen [`Detail`]. Lines are
+//! billed at the rate of their project's `[clients.<project>]` section, or at
+//! the top-level `rate`, in that rate's currency. Amounts are rounded to the
+//! smallest unit of the currency per line, so the lines always add up to the
+//! total. The [expenses](crate::expense) of the range are billed below the
+//! lines, in their client's currency, and taxed like them.
+
+use crate::{
+    currency::{Currency, Exchange, Money},
+    error::ITrackerError,
+    expense::{self, Expense},
+    locale,
+    log::LogEntry,
+    report::{self, NO_PROJECT},
+    rounding::Rounding,
+};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Write, str::FromStr};
+
+/// Metadata key holding the number of the invoice an entry was billed on.
+pub const METADATA_KEY: &str = "invoice";
+
+/// The number of the invoice `entry` was billed on, `None` if it was not.
+pub fn number(entry: &LogEntry) -> Option<&str> {
+    entry
+        .metadata
+        .get(METADATA_KEY)
+        .map(|number| number.trim())
+        .filter(|number| !number.is_empty())
+}
+
+/// A `[clients.<project>]` section: how the entries of a project are billed.
+/// Unset values fall back to the top-level settings.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientConfig {
+    /// The client's name in exports, the project name by default.
+    pub name: Option<String>,
+    /// Hourly rate of the client.
+    pub rate: Option<f64>,
+    /// Currency of the rate, e.g. `USD`.
+    pub currency: Option<String>,
+    /// Rounding profile of `[rounding.profiles]` the client's invoices round by.
+    pub rounding: Option<String>,
+    /// Tax of `[taxes]` charged on the client's invoices, e.g. `de`.
+    pub tax: Option<String>,
+    /// The client's tax ID, e.g. a VAT number, printed on its invoices.
+    pub tax_id: Option<String>,
+    /// Hours of a monthly retainer, reports show how much of it is used.
+    pub retainer: Option<f64>,
+}
+
+/// A `[taxes.<name>]` section: a tax charged on invoices, e.g. the VAT of a
+/// jurisdiction.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaxConfig {
+    /// Percent of the net amount, e.g. 19; 0 for reverse charge.
+    pub rate: f64,
+    /// The name on invoices, e.g. `VAT`; the section name by default.
+    pub label: Option<String>,
+}
+
+/// A tax charged on the net amount of invoices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tax {
+    pub label: String,
+    pub percent: f64,
+}
+
+/// The tax charged on the lines of one tax, e.g. `VAT 19%`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxLine {
+    pub tax: Tax,
+    /// The sum of the lines it is charged on.
+    pub net: Money,
+    pub amount: Money,
+}
+
+/// An amount per hour in a currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rate {
+    pub per_hour: f64,
+    pub currency: Option<Currency>,
+}
+
+impl Rate {
+    /// The amount billed for `seconds`, rounded to the currency's smallest unit.
+    pub fn bill(&self, seconds: u64) -> Money {
+        Money::new(
+            report::hours(seconds) * self.per_hour,
+            self.currency.clone(),
+        )
+    }
+}
+
+/// The resolved settings of a `[clients.<project>]` section.
+#[derive(Debug, Default, Clone)]
+struct Client {
+    name: Option<String>,
+    rate: Option<f64>,
+    currency: Option<Currency>,
+    rounding: Option<String>,
+    tax: Option<String>,
+    tax_id: Option<String>,
+    /// Seconds of the monthly retainer.
+    retainer: Option<u64>,
+}
+
+/// How the configuration bills time: the rates per client and the default
+/// one, the rounding of billed time, and the taxes charged.
+#[derive(Debug, Default, Clone)]
+pub struct Billing {
+    rate: Option<f64>,
+    currency: Option<Currency>,
+    clients: BTreeMap<String, Client>,
+    rounding: Rounding,
+    taxes: BTreeMap<String, Tax>,
+    /// The tax of clients that select none.
+    tax: Option<String>,
+    /// Your own tax ID.
+    tax_id: Option<String>,
+}
+
+impl Billing {
+    /// Resolves the top-level rate and currency and the `[clients]` sections.
+    ///
+    /// # Arguments
+    /// - `rate`: The default hourly rate.
+    /// - `currency`: The currency of the default rate.
+    /// - `clients`: The `[clients]` sections by project.
+    /// - `rounding`: The rounding rules, including the profiles clients select.
+    ///
+    /// # Returns
+    /// - `Ok(Billing)`: The resolved settings.
+    /// - `Err(ITrackerError)`: `Config` if a rate is not positive, a currency
+    ///   is invalid or a rounding profile is unknown.
+    pub fn from_config(
+        rate: Option<f64>,
+        currency: Option<&str>,
+        clients: &BTreeMap<String, ClientConfig>,
+        rounding: Rounding,
+    ) -> Result<Self, ITrackerError> {
+        let currency = currency.map(str::parse).transpose()?;
+        let clients = clients
+            .iter()
+            .map(|(project, client)| {
+                if client
+                    .rate
+                    .is_some_and(|rate| !(rate > 0.0 && rate.is_finite()))
+                {
+                    return Err(ITrackerError::Config(format!(
+                        "The rate of {} must be a positive number",
+                        project
+                    )));
+                }
+                if client
+                    .retainer
+                    .is_some_and(|hours| !(hours > 0.0 && hours <= 744.0))
+                {
+                    return Err(ITrackerError::Config(format!(
+                        "The retainer of {} must be between 0 and 744 hours a month",
+                        project
+                    )));
+                }
+                let currency = client.currency.as_deref().map(str::parse).transpose()?;
+                if let Some(name) = &client.rounding {
+                    rounding.profile(name).map_err(|e| {
+                        ITrackerError::Config(format!("clients.{}.rounding: {}", project, e))
+                    })?;
+                }
+                let client = Client {
+                    name: client
+                        .name
+                        .as_deref()
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_string),
+                    rate: client.rate,
+                    currency,
+                    rounding: client
+                        .rounding
+                        .as_deref()
+                        .map(|name| name.trim().to_string()),
+                    tax: client.tax.as_deref().map(|name| name.trim().to_string()),
+                    tax_id: client.tax_id.clone(),
+                    retainer: client.retainer.map(|hours| (hours * 3600.0).round() as u64),
+                };
+                Ok((project.trim().to_string(), client))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Billing {
+            rate,
+            currency,
+            clients,
+            rounding,
+            ..Billing::default()
+        })
+    }
+
+    /// Adds the taxes charged on invoices and the tax ID printed on them.
+    ///
+    /// # Arguments
+    /// - `taxes`: The `[taxes]` sections by name.
+    /// - `tax`: The tax of clients that select none, `None` for no tax.
+    /// - `tax_id`: Your own tax ID.
+    ///
+    /// # Returns
+    /// - `Ok(Billing)`: The settings with the taxes.
+    /// - `Err(ITrackerError)`: `Config` if a rate is not between 0 and 100
+    ///   percent, or a client or `tax` names no tax.
+    pub fn with_taxes(
+        mut self,
+        taxes: &BTreeMap<String, TaxConfig>,
+        tax: Option<&str>,
+        tax_id: Option<&str>,
+    ) -> Result<Self, ITrackerError> {
+        self.taxes = taxes
+            .iter()
+            .map(|(name, tax)| {
+                if !(0.0..=100.0).contains(&tax.rate) {
+                    return Err(ITrackerError::Config(format!(
+                        "The rate of tax {} must be between 0 and 100 percent, got {}",
+                        name, tax.rate
+                    )));
+                }
+                let label = tax.label.clone().unwrap_or_else(|| name.clone());
+                Ok((
+                    name.trim().to_string(),
+                    Tax {
+                        label,
+                        percent: tax.rate,
+                    },
+                ))
+            })
+            .collect::<Result<_, _>>()?;
+        self.tax = tax.map(|name| name.trim().to_string());
+        let selected = self
+            .clients
+            .iter()
+            .filter_map(|(project, client)| {
+                Some((format!("clients.{}.tax", project), client.tax.as_ref()?))
+            })
+            .chain(self.tax.iter().map(|name| ("tax".to_string(), name)));
+        for (key, name) in selected {
+            if !self.taxes.contains_key(name) {
+                return Err(ITrackerError::Config(format!(
+                    "{}: unknown tax '{}', configured taxes: {}",
+                    key,
+                    name,
+                    self.taxes.keys().cloned().collect::<Vec<_>>().join(", ")
+                )));
+            }
+        }
+        self.tax_id = tax_id.map(str::to_string);
+        Ok(self)
+    }
+
+    /// The tax charged on the entries of `project`, `None` if none is.
+    pub fn tax_for(&self, project: &str) -> Option<&Tax> {
+        let client = self.clients.get(project.trim());
+        client
+            .and_then(|client| client.tax.as_ref())
+            .or(self.tax.as_ref())
+            .and_then(|name| self.taxes.get(name))
+    }
+
+    /// Your own tax ID, and the one of the client billed for `project`.
+    pub fn tax_ids(&self, project: Option<&str>) -> (Option<&str>, Option<&str>) {
+        let client = project
+            .and_then(|project| self.clients.get(project.trim()))
+            .and_then(|client| client.tax_id.as_deref());
+        (self.tax_id.as_deref(), client)
+    }
+
+    /// The rate entries of `project` are billed at, `None` if neither the
+    /// client nor the top level sets one.
+    pub fn rate_for(&self, project: &str) -> Option<Rate> {
+        let client = self.clients.get(project.trim());
+        Some(Rate {
+            per_hour: client.and_then(|client| client.rate).or(self.rate)?,
+            currency: self.currency_for(project),
+        })
+    }
+
+    /// The currency `project` is billed in, `None` if none is configured.
+    pub fn currency_for(&self, project: &str) -> Option<Currency> {
+        self.clients
+            .get(project.trim())
+            .and_then(|client| client.currency.clone())
+            .or_else(|| self.currency.clone())
+    }
+
+    /// The name of the client billed for `project`, the project itself
+    /// unless its `[clients.<project>]` section names one.
+    pub fn client_name<'a>(&'a self, project: &'a str) -> &'a str {
+        self.clients
+            .get(project.trim())
+            .and_then(|client| client.name.as_deref())
+            .unwrap_or(project.trim())
+    }
+
+    /// The seconds of the monthly retainer of `project`, `None` without one.
+    pub fn retainer_for(&self, project: &str) -> Option<u64> {
+        self.clients.get(project.trim())?.retainer
+    }
+
+    /// The billed seconds of an entry of `project` that tracked `seconds`,
+    /// rounded by the client's rounding profile, the `billing` profile or
+    /// the rule reports use.
+    pub fn billed_seconds(&self, project: &str, seconds: u64) -> u64 {
+        let profile = self
+            .clients
+            .get(project.trim())
+            .and_then(|client| client.rounding.as_deref());
+        self.rounding
+            .billing_rule_for(project, profile)
+            .apply(seconds)
+    }
+}
+
+/// How finely the entries of an invoice are itemized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Detail {
+    /// One line per entry.
+    Entries,
+    /// One line per day and project, listing the tasks worked on.
+    Daily,
+    /// One line per project and task.
+    #[default]
+    Task,
+}
+
+impl FromStr for Detail {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "entries" => Ok(Detail::Entries),
+            "daily" => Ok(Detail::Daily),
+            "task" => Ok(Detail::Task),
+            other => Err(format!(
+                "Unknown detail '{}', expected entries, daily or task",
+                other
+            )),
+        }
+    }
+}
+
+/// A line by day (unless per task), project and, unless daily, task.
+type LineKey = (Option<NaiveDate>, String, String);
+
+/// One billed line of an invoice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineItem {
+    /// The day of the line, `None` for lines per task.
+    pub date: Option<NaiveDate>,
+    pub project: String,
+    /// The task, or the tasks of the day joined by commas.
+    pub description: String,
+    /// Billed seconds, rounded per entry.
+    pub seconds: u64,
+    pub rate: Rate,
+    /// The billed hours times the rate.
+    pub amount: Money,
+}
+
+/// The line items billed for a range of days.
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    /// The number the invoice is issued as, `None` for a preview.
+    pub number: Option<String>,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// The project the invoice is limited to, all projects if `None`.
+    pub client: Option<String>,
+    pub detail: Detail,
+    /// The currency of all lines, `None` if no currency is configured.
+    pub currency: Option<Currency>,
+    pub items: Vec<LineItem>,
+    /// The indices of the entries billed.
+    pub entries: Vec<usize>,
+    /// The expenses billed below the lines.
+    pub expenses: Vec<Expense>,
+    /// Entries of the range left out because their timer still runs.
+    pub running: usize,
+    /// Entries of the range left out because they were invoiced before.
+    pub invoiced: usize,
+    /// The taxes charged on the lines, in the order first charged.
+    pub taxes: Vec<TaxLine>,
+    /// Your own tax ID, and the client's if the invoice bills one project.
+    pub tax_ids: (Option<String>, Option<String>),
+}
+
+impl Invoice {
+    /// Groups the stopped entries between `from` and `to` that were not
+    /// invoiced yet into line items, and adds the expenses of those days that
+    /// were not invoiced yet either.
+    ///
+    /// # Arguments
+    /// - `entries`: The entries of the log file.
+    /// - `expenses`: The recorded expenses.
+    /// - `(from, to)`: The first and last day billed, both inclusive.
+    /// - `client`: Only bill entries of this project if set.
+    /// - `detail`: How the entries are grouped into lines.
+    /// - `billing`: The rates the projects are billed at and the rounding of
+    ///   each entry.
+    /// - `tz`: The timezone the days are counted in.
+    ///
+    /// # Returns
+    /// - `Ok(Invoice)`: The invoice.
+    /// - `Err(ITrackerError)`: `Config` if a project has no rate, or the
+    ///   projects are billed in different currencies.
+    pub fn build(
+        entries: &[LogEntry],
+        expenses: &[Expense],
+        (from, to): (NaiveDate, NaiveDate),
+        client: Option<&str>,
+        detail: Detail,
+        billing: &Billing,
+        tz: Tz,
+    ) -> Result<Self, ITrackerError> {
+        let client = client.map(str::trim).filter(|client| !client.is_empty());
+        let (mut running, mut invoiced) = (0, 0);
+        let billed: Vec<_> = report::entries_between(entries, from, to, tz)
+            .into_iter()
+            .filter(|entry| client.is_none_or(|client| entry.project.trim() == client))
+            .filter(|entry| {
+                if entry.is_running() {
+                    running += 1;
+                } else if number(entry).is_some() {
+                    invoiced += 1;
+                } else {
+                    return true;
+                }
+                false
+            })
+            .collect();
+        let indices = billed.iter().map(|entry| entry.index).collect();
+        let items = line_items(billed, detail, billing, tz)
+            .into_iter()
+            .map(|line| {
+                let rate = billing.rate_for(&line.project).ok_or_else(|| {
+                    ITrackerError::Config(format!(
+                        "No hourly rate for {}, set `rate` or `clients.{}.rate` to invoice",
+                        line.project, line.project
+                    ))
+                })?;
+                Ok(LineItem {
+                    date: line.date,
+                    project: line.project,
+                    description: line.description,
+                    seconds: line.seconds,
+                    amount: rate.bill(line.seconds),
+                    rate,
+                })
+            })
+            .collect::<Result<Vec<_>, ITrackerError>>()?;
+        let expenses: Vec<Expense> = expense::between(expenses, from, to)
+            .into_iter()
+            .filter(|expense| client.is_none_or(|client| expense.project() == client))
+            .filter(|expense| !expense.is_invoiced())
+            .cloned()
+            .collect();
+        // Lines and expenses by project, all of which must share a currency
+        let amounts: Vec<(&str, Money)> = items
+            .iter()
+            .map(|item| (item.project.as_str(), item.amount.clone()))
+            .chain(
+                expenses
+                    .iter()
+                    .map(|expense| (expense.project(), expense.money())),
+            )
+            .collect();
+        let currency = amounts
+            .first()
+            .and_then(|(_, amount)| amount.currency.clone());
+        if let Some((other, _)) = amounts
+            .iter()
+            .find(|(_, amount)| amount.currency != currency)
+        {
+            return Err(ITrackerError::Config(format!(
+                "{} and {} are billed in different currencies, invoice them separately with --client",
+                amounts[0].0, other
+            )));
+        }
+
+        let mut taxes: Vec<TaxLine> = Vec::new();
+        for (project, amount) in &amounts {
+            let Some(tax) = billing.tax_for(project) else {
+                continue;
+            };
+            match taxes.iter_mut().find(|line| line.tax == *tax) {
+                Some(line) => line.net.amount += amount.amount,
+                None => taxes.push(TaxLine {
+                    tax: tax.clone(),
+                    net: amount.clone(),
+                    amount: Money::new(0.0, currency.clone()),
+                }),
+            }
+        }
+        for line in &mut taxes {
+            line.net = Money::new(line.net.amount, currency.clone());
+            line.amount = Money::new(line.net.amount * line.tax.percent / 100.0, currency.clone());
+        }
+        let project = client.or_else(|| {
+            let (first, _) = amounts.first()?;
+            amounts
+                .iter()
+                .all(|(project, _)| project == first)
+                .then_some(*first)
+        });
+        let (own, client_id) = billing.tax_ids(project);
+        let tax_ids = (own.map(str::to_string), client_id.map(str::to_string));
+
+        Ok(Invoice {
+            number: None,
+            from,
+            to,
+            client: client.map(str::to_string),
+            detail,
+            currency,
+            items,
+            entries: indices,
+            expenses,
+            running,
+            invoiced,
+            taxes,
+            tax_ids,
+        })
+    }
+
+    /// The billed seconds of all lines.
+    pub fn seconds(&self) -> u64 {
+        self.items.iter().map(|item| item.seconds).sum()
+    }
+
+    /// The sum of the line amounts and expenses, before taxes.
+    pub fn amount(&self) -> Money {
+        let expenses: f64 = self.expenses.iter().map(|expense| expense.amount).sum();
+        Money::new(
+            self.items
+                .iter()
+                .map(|item| item.amount.amount)
+                .sum::<f64>()
+                + expenses,
+            self.currency.clone(),
+        )
+    }
+
+    /// The sum of the line amounts, the expenses and the taxes.
+    pub fn gross(&self) -> Money {
+        let taxes: f64 = self.taxes.iter().map(|line| line.amount.amount).sum();
+        Money::new(self.amount().amount + taxes, self.currency.clone())
+    }
+
+    /// The invoice as a plain-text table, hours in decimals.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "Invoice ");
+        if let Some(number) = &self.number {
+            let _ = write!(out, "{}, ", number);
+        }
+        let _ = write!(
+            out,
+            "{} to {}",
+            locale::date(self.from),
+            locale::date(self.to)
+        );
+        if let Some(client) = &self.client {
+            let _ = write!(out, ", {}", client);
+        }
+        out.push('\n');
+        if let Some(own) = &self.tax_ids.0 {
+            let _ = writeln!(out, "Tax ID: {}", own);
+        }
+        if let Some(client) = &self.tax_ids.1 {
+            let _ = writeln!(out, "Client tax ID: {}", client);
+        }
+
+        let dated = self.detail != Detail::Task;
+        let project_width = column_width("Project", self.items.iter().map(|i| &i.project));
+        let mut task_width = column_width("Task", self.items.iter().map(|i| &i.description));
+        let date_width = locale::date(self.to).chars().count();
+        let mut header = String::new();
+        if dated {
+            header.push_str(&format!("{:<width$}  ", "Date", width = date_width));
+        }
+        let tax_labels: Vec<String> = self
+            .taxes
+            .iter()
+            .map(|line| format!("{} {}%", line.tax.label, line.tax.percent))
+            .collect();
+        let expense_labels: Vec<String> = self
+            .expenses
+            .iter()
+            .map(|expense| {
+                format!(
+                    "{}  {}  {}",
+                    locale::date(expense.date),
+                    expense.project(),
+                    expense.description
+                )
+            })
+            .collect();
+        // Tax and expense labels share the columns left of the hours with the lines
+        let widest = tax_labels
+            .iter()
+            .chain(&expense_labels)
+            .map(|label| label.chars().count())
+            .max();
+        task_width = task_width.max(
+            widest
+                .unwrap_or(0)
+                .saturating_sub(header.len() + project_width + 2),
+        );
+        let _ = writeln!(
+            out,
+            "{}{:<pw$}  {:<tw$}  {:>8}  {:>8}  {:>10}",
+            header,
+            "Project",
+            "Task",
+            "Hours",
+            "Rate",
+            "Amount",
+            pw = project_width,
+            tw = task_width
+        );
+        for item in &self.items {
+            let date = item
+                .date
+                .filter(|_| dated)
+                .map(|date| format!("{:<w$}  ", locale::date(date), w = date_width))
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "{}{:<pw$}  {:<tw$}  {:>8}  {:>8}  {:>10}",
+                date,
+                item.project,
+                item.description,
+                locale::number(report::hours(item.seconds), 2),
+                Money::new(item.rate.per_hour, item.rate.currency.clone()).to_string(),
+                item.amount.to_string(),
+                pw = project_width,
+                tw = task_width
+            );
+        }
+        let label_width = header.len() + project_width + 2 + task_width;
+        if !self.expenses.is_empty() {
+            let _ = writeln!(out, "Expenses");
+        }
+        for (expense, label) in self.expenses.iter().zip(expense_labels) {
+            let _ = writeln!(
+                out,
+                "{:<lw$}  {:>8}  {:>8}  {:>10}",
+                label,
+                "",
+                "",
+                expense.money().to_string(),
+                lw = label_width
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{:<lw$}  {:>8}  {:>8}  {:>10}",
+            if self.taxes.is_empty() {
+                "Total"
+            } else {
+                "Net"
+            },
+            locale::number(report::hours(self.seconds()), 2),
+            "",
+            self.amount().to_string(),
+            lw = label_width
+        );
+        if self.taxes.is_empty() {
+            return out;
+        }
+        for (line, label) in self.taxes.iter().zip(tax_labels) {
+            let _ = writeln!(
+                out,
+                "{:<lw$}  {:>8}  {:>8}  {:>10}",
+                label,
+                "",
+                "",
+                line.amount.to_string(),
+                lw = label_width
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{:<lw$}  {:>8}  {:>8}  {:>10}",
+            "Gross",
+            "",
+            "",
+            self.gross().to_string(),
+            lw = label_width
+        );
+        out
+    }
+}
+
+/// What the stopped entries of each project are worth, for reports.
+#[derive(Debug, Default, Clone)]
+pub struct Earnings {
+    /// The amount per project with a rate, in the currency of its rate, and
+    /// converted into the reporting currency if one is configured.
+    pub per_project: BTreeMap<String, (Money, Option<Money>)>,
+    /// The expenses per project with any, included in `per_project`.
+    pub expenses: BTreeMap<String, Money>,
+    /// The sum in the reporting currency, or in the currency all projects are
+    /// billed in; `None` if neither exists.
+    pub total: Option<Money>,
+}
+
+impl Earnings {
+    /// Bills the stopped entries between `from` and `to` per project, as an
+    /// invoice per task would, leaving out projects without a rate, and adds
+    /// the expenses of those days. Entries and expenses invoiced before count
+    /// as well.
+    ///
+    /// # Returns
+    /// - `Ok(Earnings)`: The amounts.
+    /// - `Err(ITrackerError)`: `Config` if an expense is not in the currency
+    ///   of its project, or an amount cannot be converted into the reporting
+    ///   currency.
+    pub fn of(
+        entries: &[LogEntry],
+        expenses: &[Expense],
+        range: (NaiveDate, NaiveDate),
+        billing: &Billing,
+        exchange: &Exchange,
+        tz: Tz,
+    ) -> Result<Self, ITrackerError> {
+        let stopped = report::entries_between(entries, range.0, range.1, tz)
+            .into_iter()
+            .filter(|entry| !entry.is_running())
+            .collect();
+        let lines = line_items(stopped, Detail::Task, billing, tz);
+        let mut amounts: BTreeMap<String, Money> = BTreeMap::new();
+        for line in lines {
+            let Some(rate) = billing.rate_for(&line.project) else {
+                continue;
+            };
+            let amount = rate.bill(line.seconds);
+            amounts
+                .entry(line.project)
+                .and_modify(|sum| sum.amount += amount.amount)
+                .or_insert(amount);
+        }
+        let mut spent: BTreeMap<String, Money> = BTreeMap::new();
+        for expense in expense::between(expenses, range.0, range.1) {
+            let amount = expense.money();
+            for sums in [&mut amounts, &mut spent] {
+                let sum = sums
+                    .entry(expense.project().to_string())
+                    .or_insert_with(|| Money::new(0.0, amount.currency.clone()));
+                if sum.currency != amount.currency {
+                    return Err(ITrackerError::Config(format!(
+                        "Expense {} of {} is not in the currency {} is billed in",
+                        expense.id,
+                        expense.project(),
+                        expense.project()
+                    )));
+                }
+                sum.amount += amount.amount;
+            }
+        }
+        let per_project = amounts
+            .into_iter()
+            .map(|(project, amount)| {
+                let converted = exchange.convert(&amount)?;
+                Ok((project, (amount, converted)))
+            })
+            .collect::<Result<BTreeMap<_, _>, ITrackerError>>()?;
+
+        let mut sums = per_project
+            .values()
+            .map(|(amount, converted)| converted.as_ref().unwrap_or(amount));
+        let total = sums.next().cloned().and_then(|first| {
+            sums.try_fold(first, |mut total, amount| {
+                (amount.currency == total.currency).then(|| {
+                    total.amount += amount.amount;
+                    total
+                })
+            })
+        });
+        let total = total.map(|total| Money::new(total.amount, total.currency));
+        Ok(Earnings {
+            per_project,
+            expenses: spent,
+            total,
+        })
+    }
+}
+
+/// A line before it is billed at its project's rate.
+struct Line {
+    date: Option<NaiveDate>,
+    project: String,
+    description: String,
+    seconds: u64,
+}
+
+/// Groups stopped entries into lines.
+fn line_items(mut billed: Vec<&LogEntry>, detail: Detail, billing: &Billing, tz: Tz) -> Vec<Line> {
+    // Daily lines collect their tasks in the order first worked on
+    let mut lines: BTreeMap<LineKey, (u64, Vec<String>)> = BTreeMap::new();
+    billed.sort_by_key(|entry| entry.start());
+    for (position, entry) in billed.into_iter().enumerate() {
+        let project = match entry.project.trim() {
+            "" => NO_PROJECT.to_string(),
+            project => project.to_string(),
+        };
+        let title = entry.display_title().to_string();
+        let date = report::local_date(entry, tz);
+        let key = match detail {
+            // The position keeps entries with the same task apart
+            Detail::Entries => (date, project, format!("{:08}", position)),
+            Detail::Daily => (date, project, String::new()),
+            Detail::Task => (None, project, title.to_lowercase()),
+        };
+        let seconds = report::tracked_seconds(entry, Utc::now());
+        let line = lines.entry(key).or_default();
+        line.0 += billing.billed_seconds(&entry.project, seconds);
+        if !line.1.contains(&title) {
+            line.1.push(title);
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|((date, project, _), (seconds, titles))| Line {
+            date,
+            project,
+            description: match detail {
+                Detail::Daily => titles.join(", "),
+                // The first spelling of a task names its line
+                _ => titles.into_iter().next().unwrap_or_default(),
+            },
+            seconds,
+        })
+        .collect()
+}
+
+fn column_width<'a>(title: &str, values: impl Iterator<Item = &'a String>) -> usize {
+    values
+        .map(|value| value.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(title.len())
+}
@@ -0,0 +1,426 @@
+use crate::error::ITrackerError;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+/// The `[keys]` section of the configuration file.
+///
+/// Any action can be rebound by listing its key sequences, e.g.
+/// `scroll_down = ["j", "Down"]`. Keys inside a sequence are written next to
+/// each other (`gg`) or separated by spaces when named (`Ctrl-w j`).
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct KeysConfig {
+    /// Base bindings: `default` or `vim`.
+    pub preset: Option<String>,
+    /// Per-action overrides, replacing the preset bindings of that action.
+    #[serde(flatten)]
+    pub bindings: HashMap<String, Vec<String>>,
+}
+
+/// Actions that can be triggered from the log viewer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    SortIndex,
+    SortStart,
+    SortElapsed,
+    SortMessage,
+    Delete,
+    StartTimer,
+    Find,
+    PrevPeriod,
+    NextPeriod,
+    NextScreen,
+    PrevScreen,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 19] = [
+        Action::Quit,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::PageUp,
+        Action::PageDown,
+        Action::Top,
+        Action::Bottom,
+        Action::SortIndex,
+        Action::SortStart,
+        Action::SortElapsed,
+        Action::SortMessage,
+        Action::Delete,
+        Action::StartTimer,
+        Action::Find,
+        Action::PrevPeriod,
+        Action::NextPeriod,
+        Action::NextScreen,
+        Action::PrevScreen,
+        Action::Help,
+    ];
+
+    /// Name of the action in the `[keys]` config section.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollDown => "scroll_down",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::SortIndex => "sort_index",
+            Action::SortStart => "sort_start",
+            Action::SortElapsed => "sort_elapsed",
+            Action::SortMessage => "sort_message",
+            Action::Delete => "delete",
+            Action::StartTimer => "start_timer",
+            Action::Find => "find",
+            Action::PrevPeriod => "prev_period",
+            Action::NextPeriod => "next_period",
+            Action::NextScreen => "next_screen",
+            Action::PrevScreen => "prev_screen",
+            Action::Help => "help",
+        }
+    }
+
+    /// Human readable description shown in the help overlay.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::ScrollUp => "Select previous entry",
+            Action::ScrollDown => "Select next entry",
+            Action::PageUp => "Page up",
+            Action::PageDown => "Page down",
+            Action::Top => "Jump to first entry",
+            Action::Bottom => "Jump to last entry",
+            Action::SortIndex => "Sort by index (again to reverse)",
+            Action::SortStart => "Sort by start time (again to reverse)",
+            Action::SortElapsed => "Sort by elapsed time (again to reverse)",
+            Action::SortMessage => "Sort by message (again to reverse)",
+            Action::Delete => "Delete selected entry",
+            Action::StartTimer => "Start a new timer for the selected task",
+            Action::Find => "Fuzzy find an entry",
+            Action::PrevPeriod => "Previous week in the calendar",
+            Action::NextPeriod => "Next week in the calendar",
+            Action::NextScreen => "Next screen",
+            Action::PrevScreen => "Previous screen",
+            Action::Help => "Show this help",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// A single key press with its modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is already part of the key itself (`G` vs `g`, BackTab vs Tab)
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeyChord { code, modifiers }
+    }
+}
+
+/// Maps key sequences to actions, including multi-key sequences such as `gg`.
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyChord>, Action)>,
+    pending: Vec<KeyChord>,
+}
+
+impl Keymap {
+    /// The built-in bindings: arrows, paging keys and single-letter sorting.
+    pub fn default_preset() -> Self {
+        Keymap::from_specs(&[
+            (Action::Quit, &["Esc"]),
+            (Action::ScrollUp, &["Up"]),
+            (Action::ScrollDown, &["Down"]),
+            (Action::PageUp, &["PageUp"]),
+            (Action::PageDown, &["PageDown"]),
+            (Action::Top, &["Home"]),
+            (Action::Bottom, &["End"]),
+            (Action::SortIndex, &["i"]),
+            (Action::SortStart, &["t"]),
+            (Action::SortElapsed, &["e"]),
+            (Action::SortMessage, &["m"]),
+            (Action::Delete, &["Delete"]),
+            (Action::StartTimer, &["Enter", "s"]),
+            (Action::Find, &["Ctrl-p"]),
+            (Action::PrevPeriod, &["Left"]),
+            (Action::NextPeriod, &["Right"]),
+            (Action::NextScreen, &["Tab"]),
+            (Action::PrevScreen, &["BackTab"]),
+            (Action::Help, &["?", "F1"]),
+        ])
+    }
+
+    /// Vim-style bindings on top of the arrow keys.
+    pub fn vim_preset() -> Self {
+        Keymap::from_specs(&[
+            (Action::Quit, &["Esc", "q"]),
+            (Action::ScrollUp, &["k", "Up"]),
+            (Action::ScrollDown, &["j", "Down"]),
+            (Action::PageUp, &["Ctrl-b", "PageUp"]),
+            (Action::PageDown, &["Ctrl-f", "PageDown"]),
+            (Action::Top, &["gg", "Home"]),
+            (Action::Bottom, &["G", "End"]),
+            (Action::SortIndex, &["i"]),
+            (Action::SortStart, &["t"]),
+            (Action::SortElapsed, &["e"]),
+            (Action::SortMessage, &["m"]),
+            (Action::Delete, &["dd"]),
+            (Action::StartTimer, &["Enter", "s"]),
+            (Action::Find, &["Ctrl-p", "/"]),
+            (Action::PrevPeriod, &["h", "Left"]),
+            (Action::NextPeriod, &["l", "Right"]),
+            (Action::NextScreen, &["Tab"]),
+            (Action::PrevScreen, &["BackTab"]),
+            (Action::Help, &["?", "F1"]),
+        ])
+    }
+
+    fn from_specs(specs: &[(Action, &[&str])]) -> Self {
+        let bindings = specs
+            .iter()
+            .flat_map(|(action, keys)| {
+                keys.iter().map(move |spec| {
+                    let sequence = parse_sequence(spec).expect("built-in key spec is valid");
+                    (sequence, *action)
+                })
+            })
+            .collect();
+
+        Keymap {
+            bindings,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Builds the keymap described by the configuration.
+    ///
+    /// # Arguments
+    /// - `config`: The optional `[keys]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Keymap)`: The preset with all overrides applied.
+    /// - `Err(ITrackerError)`: If the preset, an action or a key is unknown.
+    pub fn from_config(config: Option<&KeysConfig>) -> Result<Self, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(Keymap::default_preset());
+        };
+
+        let mut keymap = match config.preset.as_deref() {
+            None | Some("default") => Keymap::default_preset(),
+            Some("vim") => Keymap::vim_preset(),
+            Some(other) => {
+                return Err(ITrackerError::Config(format!(
+                    "Unknown key preset '{}', expected default or vim",
+                    other
+                )))
+            }
+        };
+
+        for (name, specs) in &config.bindings {
+            let action = Action::from_name(name)
+                .ok_or_else(|| ITrackerError::Config(format!("Unknown key action '{}'", name)))?;
+
+            keymap.bindings.retain(|(_, bound)| *bound != action);
+            for spec in specs {
+                keymap.bindings.push((parse_sequence(spec)?, action));
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// Feeds a key press into the keymap.
+    ///
+    /// Returns the action once a complete sequence has been typed. Keys that
+    /// start a longer sequence are buffered until the sequence either
+    /// completes or is broken, in which case the latest key is tried alone.
+    pub fn resolve(&mut self, key: KeyEvent) -> Option<Action> {
+        let chord = KeyChord::new(key.code, key.modifiers);
+        self.pending.push(chord);
+
+        if let Some(action) = self.lookup(&self.pending) {
+            self.pending.clear();
+            return Some(action);
+        }
+        if self.is_prefix(&self.pending) {
+            return None;
+        }
+
+        // The sequence is broken, start over with the latest key
+        self.pending.clear();
+        if let Some(action) = self.lookup(&[chord]) {
+            return Some(action);
+        }
+        if self.is_prefix(&[chord]) {
+            self.pending.push(chord);
+        }
+        None
+    }
+
+    /// Key sequences bound to an action, formatted like in the config file.
+    pub fn keys_for(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(keys, _)| format_sequence(keys))
+            .collect()
+    }
+
+    /// Rows of `(keys, description)` for every bound action, in `Action::ALL` order.
+    pub fn help(&self) -> Vec<(String, String)> {
+        Action::ALL
+            .into_iter()
+            .map(|action| (self.keys_for(action).join(", "), action.description()))
+            .filter(|(keys, _)| !keys.is_empty())
+            .map(|(keys, description)| (keys, description.to_string()))
+            .collect()
+    }
+
+    fn lookup(&self, sequence: &[KeyChord]) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(keys, _)| keys == sequence)
+            .map(|(_, action)| *action)
+    }
+
+    fn is_prefix(&self, sequence: &[KeyChord]) -> bool {
+        self.bindings
+            .iter()
+            .any(|(keys, _)| keys.len() > sequence.len() && keys.starts_with(sequence))
+    }
+}
+
+/// Parses a key sequence such as `gg`, `G`, `Ctrl-f`, `PageDown` or `Ctrl-w j`.
+fn parse_sequence(spec: &str) -> Result<Vec<KeyChord>, ITrackerError> {
+    let mut sequence = Vec::new();
+
+    for token in spec.split_whitespace() {
+        if let Some(chord) = parse_named(token)? {
+            sequence.push(chord);
+        } else {
+            sequence.extend(
+                token
+                    .chars()
+                    .map(|c| KeyChord::new(KeyCode::Char(c), KeyModifiers::NONE)),
+            );
+        }
+    }
+
+    if sequence.is_empty() {
+        return Err(ITrackerError::Config(format!(
+            "Empty key sequence '{}'",
+            spec
+        )));
+    }
+    Ok(sequence)
+}
+
+/// Keys written by name in key sequences.
+const NAMED_KEYS: [(&str, KeyCode); 15] = [
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("Esc", KeyCode::Esc),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Delete", KeyCode::Delete),
+    ("Backspace", KeyCode::Backspace),
+    ("Space", KeyCode::Char(' ')),
+];
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+
+        match NAMED_KEYS.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => match self.code {
+                KeyCode::Char(c) => write!(f, "{}", c),
+                KeyCode::F(n) => write!(f, "F{}", n),
+                other => write!(f, "{:?}", other),
+            },
+        }
+    }
+}
+
+/// Formats a sequence the way it is written in the config: plain characters
+/// are joined (`gg`), named keys are separated by spaces (`Ctrl-w j`).
+fn format_sequence(sequence: &[KeyChord]) -> String {
+    let mut formatted = String::new();
+    let mut previous_plain = false;
+
+    for chord in sequence {
+        let text = chord.to_string();
+        let plain = text.chars().count() == 1;
+        if !formatted.is_empty() && (!plain || !previous_plain) {
+            formatted.push(' ');
+        }
+        formatted.push_str(&text);
+        previous_plain = plain;
+    }
+
+    formatted
+}
+
+/// Parses a named key with optional modifiers, returning `None` for plain characters.
+fn parse_named(token: &str) -> Result<Option<KeyChord>, ITrackerError> {
+    let (modifiers, key) = if let Some(key) = token.strip_prefix("Ctrl-") {
+        (KeyModifiers::CONTROL, key)
+    } else if let Some(key) = token.strip_prefix("Alt-") {
+        (KeyModifiers::ALT, key)
+    } else {
+        (KeyModifiers::NONE, token)
+    };
+
+    let named = NAMED_KEYS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, code)| *code)
+        .or_else(|| match key.strip_prefix('F')?.parse::<u8>() {
+            Ok(n @ 1..=12) => Some(KeyCode::F(n)),
+            _ => None,
+        });
+
+    let code = match named {
+        Some(code) => code,
+        _ if modifiers != KeyModifiers::NONE && key.chars().count() == 1 => {
+            KeyCode::Char(key.chars().next().unwrap_or_default())
+        }
+        _ if modifiers != KeyModifiers::NONE => {
+            return Err(ITrackerError::Config(format!("Unknown key '{}'", token)))
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(KeyChord::new(code, modifiers)))
+}
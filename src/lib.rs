@@ -0,0 +1,72 @@
+//! Time tracking on plain CSV log files.
+//!
+//! The `Itraker` binary is a thin command line and terminal UI layer over this
+//! library; other programs can embed the same logic through [`Tracker`],
+//! which starts and stops timers on a [`Storage`] holding [`Entry`] records.
+
+pub mod activity;
+pub mod backup;
+pub mod budget;
+pub mod clock;
+pub mod clockify;
+pub mod color;
+pub mod config;
+pub mod crdt;
+pub mod crypto;
+pub mod currency;
+pub mod daemon;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod dump;
+pub mod edit;
+pub mod error;
+pub mod expense;
+pub mod export;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hooks;
+pub mod i18n;
+pub mod icons;
+pub mod idle;
+pub mod index;
+pub mod invoice;
+pub mod keys;
+pub mod locale;
+pub mod log;
+pub mod mapped;
+pub mod merge;
+pub mod month;
+pub mod notify;
+pub mod output;
+pub mod plugin;
+pub mod progress;
+pub mod repo;
+pub mod report;
+pub mod rounding;
+pub mod s3;
+pub mod schema;
+pub mod server;
+pub mod snapshot;
+pub mod status;
+pub mod storage;
+pub mod suggest;
+pub mod sync;
+pub mod table;
+pub mod team;
+pub mod template;
+pub mod theme;
+pub mod timefmt;
+pub mod timeparse;
+pub mod timer;
+pub mod toggl;
+pub mod tracker;
+pub mod undo;
+pub mod webdav;
+
+pub use error::ITrackerError;
+/// One tracked task as stored in a row of the log file.
+pub use log::LogEntry as Entry;
+pub use log::TaskDetails;
+pub use report::Summary;
+pub use storage::Storage;
+pub use tracker::Tracker;
@@ -0,0 +1,199 @@
+//! Numbers, amounts, dates and times rendered the way a locale writes them.
+//!
+//! The locale is chosen once per run and independently of the language of
+//! messages (see [`crate::i18n`]): the `locale` setting, else the locale of
+//! `LC_ALL`, `LC_NUMERIC` or `LANG`. Without one, or with `C`/`POSIX`,
+//! numbers keep a plain decimal point and dates are written as `2024-06-03`.
+//! Files, exports and APIs are never localized.
+
+use crate::error::ITrackerError;
+use chrono::{DateTime, NaiveDate, TimeZone};
+use pure_rust_locales::locale_match;
+use std::{fmt::Display, str::FromStr, sync::OnceLock};
+
+static SELECTED: OnceLock<Option<Locale>> = OnceLock::new();
+
+/// A locale of the glibc locale data, e.g. `de_DE` or `en_US`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale(chrono::Locale);
+
+impl FromStr for Locale {
+    type Err = ITrackerError;
+
+    /// Parses `de_DE`, `de-DE`, `de_DE.UTF-8` or just `de` (as `de_DE`).
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let tag = name.split(['.', '@']).next().unwrap_or_default();
+        let tag = tag.trim().replace('-', "_");
+        let full = match tag.split_once('_') {
+            Some((language, region)) => format!("{}_{}", language, region.to_ascii_uppercase()),
+            None => format!("{}_{}", tag, tag.to_ascii_uppercase()),
+        };
+        chrono::Locale::try_from(full.as_str())
+            .or_else(|_| chrono::Locale::try_from(tag.as_str()))
+            .map(Locale)
+            .map_err(|_| {
+                ITrackerError::Config(format!(
+                    "Unknown locale '{}', expected a name like de_DE or en-US",
+                    name
+                ))
+            })
+    }
+}
+
+impl Locale {
+    /// A number with the locale's decimal point and thousands separator.
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let point = locale_match!(self.0 => LC_NUMERIC::DECIMAL_POINT);
+        let separator = locale_match!(self.0 => LC_NUMERIC::THOUSANDS_SEP);
+        group(value, decimals, point, separator)
+    }
+
+    /// An amount with the locale's separators, and the currency `symbol`
+    /// placed where the locale puts it; `code` always goes after the amount.
+    pub fn money(&self, amount: f64, decimals: usize, symbol: Option<&str>, code: &str) -> String {
+        let point = locale_match!(self.0 => LC_MONETARY::MON_DECIMAL_POINT);
+        let separator = locale_match!(self.0 => LC_MONETARY::MON_THOUSANDS_SEP);
+        let sign = if amount < 0.0 { "-" } else { "" };
+        let digits = group(amount.abs(), decimals, point, separator);
+        let Some(symbol) = symbol else {
+            return format!("{}{} {}", sign, digits, code);
+        };
+        let space = if locale_match!(self.0 => LC_MONETARY::P_SEP_BY_SPACE) == 1 {
+            " "
+        } else {
+            ""
+        };
+        if locale_match!(self.0 => LC_MONETARY::P_CS_PRECEDES) == 1 {
+            format!("{}{}{}{}", sign, symbol, space, digits)
+        } else {
+            format!("{}{}{}{}", sign, digits, space, symbol)
+        }
+    }
+
+    /// A date in the locale's short form, e.g. `03.06.2024` or `06/03/2024`.
+    pub fn date(&self, day: NaiveDate) -> String {
+        day.format(locale_match!(self.0 => LC_TIME::D_FMT))
+            .to_string()
+    }
+
+    /// The month and year of a day, e.g. `Juni 2024`.
+    pub fn month(&self, day: NaiveDate) -> String {
+        day.format_localized("%B %Y", self.0).to_string()
+    }
+
+    /// The name of the day of the week, e.g. `Montag`.
+    pub fn weekday(&self, day: NaiveDate) -> String {
+        day.format_localized("%A", self.0).to_string()
+    }
+
+    /// A time of day in minutes, on a 12-hour clock where the locale uses one.
+    pub fn time<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: Display,
+    {
+        let pattern = if self.twelve_hours() {
+            "%I:%M %p"
+        } else {
+            "%H:%M"
+        };
+        time.format_localized(pattern, self.0).to_string()
+    }
+
+    fn twelve_hours(&self) -> bool {
+        let format = locale_match!(self.0 => LC_TIME::T_FMT);
+        let am_pm = locale_match!(self.0 => LC_TIME::AM_PM);
+        (format.contains("%I") || format.contains("%r") || format.contains("%p"))
+            && am_pm.iter().any(|mark| !mark.is_empty())
+    }
+}
+
+/// Selects the locale of this run, the `locale` setting if set, else the one
+/// of the environment.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` if the setting names no known locale;
+///   unknown locales of the environment are ignored.
+pub fn select(setting: Option<&str>) -> Result<(), ITrackerError> {
+    let locale = match setting {
+        Some(name) => Some(name.parse()?),
+        None => ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .filter(|value| !matches!(value.as_str(), "C" | "POSIX") && !value.starts_with("C."))
+            .and_then(|value| value.parse().ok()),
+    };
+    let _ = SELECTED.set(locale);
+    Ok(())
+}
+
+/// The locale of this run, `None` for plain numbers and ISO dates.
+pub fn current() -> Option<Locale> {
+    SELECTED.get().copied().flatten()
+}
+
+/// A number in the current locale, e.g. `1.234,50`; plain `1234.50` without one.
+pub fn number(value: f64, decimals: usize) -> String {
+    match current() {
+        Some(locale) => locale.number(value, decimals),
+        None => format!("{:.*}", decimals, value),
+    }
+}
+
+/// A date in the current locale, `2024-06-03` without one.
+pub fn date(day: NaiveDate) -> String {
+    match current() {
+        Some(locale) => locale.date(day),
+        None => day.to_string(),
+    }
+}
+
+/// The month of a day in the current locale, `June 2024` without one.
+pub fn month(day: NaiveDate) -> String {
+    match current() {
+        Some(locale) => locale.month(day),
+        None => day.format("%B %Y").to_string(),
+    }
+}
+
+/// The day of the week in the current locale, `Monday` without one.
+pub fn weekday(day: NaiveDate) -> String {
+    match current() {
+        Some(locale) => locale.weekday(day),
+        None => day.format("%A").to_string(),
+    }
+}
+
+/// A time of day in the current locale, `14:30` without one.
+pub fn time<Tz: TimeZone>(time: &DateTime<Tz>) -> String
+where
+    Tz::Offset: Display,
+{
+    match current() {
+        Some(locale) => locale.time(time),
+        None => time.format("%H:%M").to_string(),
+    }
+}
+
+/// Writes `value` with `decimals` digits after `point`, and `separator`
+/// between groups of three integer digits.
+fn group(value: f64, decimals: usize, point: &str, separator: &str) -> String {
+    let digits = format!("{:.*}", decimals, value.abs());
+    let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    let sign = if value < 0.0 && digits.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        "-"
+    } else {
+        ""
+    };
+    match fraction {
+        "" => format!("{}{}", sign, grouped),
+        fraction => format!("{}{}{}{}", sign, grouped, point, fraction),
+    }
+}
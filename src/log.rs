@@ -1,112 +1,206 @@
-use csv::{ReaderBuilder, WriterBuilder};
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufReader, BufWriter};
+use crate::{
+    error::ITrackerError,
+    schema::{self, Record},
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Represents a single log entry with an index, start time, message, elapsed time, and paused time.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
-    pub index: usize,         // Index of the log entry
-    pub start_time: String,   // Start time of the log entry
-    pub message: String,      // Message associated with the log entry
-    pub elapsed_time: String, // Elapsed time recorded in the log entry
-    pub paused_time: String,  // Paused time recorded in the log entry
+    pub index: usize,                       // Index of the log entry
+    pub start_time: String,                 // Start time of the log entry
+    pub message: String,                    // Message associated with the log entry
+    pub elapsed_time: String,               // Elapsed time recorded in the log entry
+    pub paused_time: String,                // Paused time recorded in the log entry
+    pub title: String,                      // Short title of the task, may be empty
+    pub project: String,                    // Project the task belongs to, may be empty
+    pub tags: Vec<String>,                  // Tags attached to the task
+    pub estimate: Option<u32>,              // Estimated duration in minutes
+    pub paused_since: String,               // Start of the current pause, empty unless paused
+    pub metadata: BTreeMap<String, String>, // Extra values, e.g. the git repository
 }
 
-/// Reads logs from a specified file and returns a vector of `LogEntry`.
-///
-/// # Arguments
-/// - `file_path`: The path to the log file.
-///
-/// # Returns
-/// - `Ok(Vec<LogEntry>)`: A vector of log entries if successful.
-/// - `Err(std::io::Error)`: An error if file operations fail.
-pub fn read_logs_from_file(file_path: &str) -> Result<Vec<LogEntry>, std::io::Error> {
-    // Open the CSV file for reading
-    let file = File::open(file_path)?;
-    let mut reader = ReaderBuilder::new()
-        .has_headers(true)
-        .from_reader(BufReader::new(file));
-
-    let mut entries = Vec::new();
-
-    // Iterate over each record in the CSV file
-    for result in reader.records() {
-        let record = result.map_err(|e| io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-
-        // Parse each field from the CSV into the LogEntry struct
-        let entry = LogEntry {
-            index: record[0]
-                .parse::<usize>()
-                .map_err(|e| io::Error::new(std::io::ErrorKind::InvalidData, e))?,
-            start_time: record[1].to_string(),
-            message: record[2].to_string(),
-            elapsed_time: record[3].to_string(),
-            paused_time: record.get(4).unwrap_or(&"0".to_string()).to_string(), // Default to "0" if not present
-        };
-        entries.push(entry);
+impl LogEntry {
+    /// Whether the timer is still running, i.e. no elapsed time was recorded yet.
+    pub fn is_running(&self) -> bool {
+        self.elapsed_time.trim().parse::<u64>().unwrap_or(0) == 0
     }
 
-    Ok(entries)
+    /// Records that the timer stopped after `seconds`. At least a second is
+    /// recorded even for a stop in the same second as the start or a clock
+    /// set back, since no elapsed time would keep the entry running.
+    pub fn set_elapsed(&mut self, seconds: i64) {
+        self.elapsed_time = seconds.max(1).to_string();
+    }
+
+    /// When the current pause started, `None` if the entry is not paused.
+    pub fn paused_since(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc2822(self.paused_since.trim()).ok()
+    }
+
+    /// Parses the start time, returning `None` if it is malformed.
+    pub fn start(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc2822(self.start_time.trim()).ok()
+    }
+
+    /// The title if set, otherwise the first line of the description.
+    pub fn display_title(&self) -> &str {
+        if self.title.trim().is_empty() {
+            self.message.lines().next().unwrap_or_default().trim()
+        } else {
+            self.title.trim()
+        }
+    }
 }
 
-/// Deletes a log entry by its index from the specified log file.
-///
-/// This function searches for a log entry by its index and removes it along with
-/// the associated information (up to the next delimiter).
-///
-/// # Arguments
-/// - `log_file`: The path to the log file.
-/// - `index`: The index of the log entry to delete.
-///
-/// # Returns
-/// - `Ok(())`: If the deletion is successful.
-/// - `Err(std::io::Error)`: An error if file operations fail.
-pub fn delete_log_entry(log_file: &str, index: usize) -> Result<(), io::Error> {
-    // Open the CSV file for reading
-    let file = File::open(log_file)?;
-    let mut reader = ReaderBuilder::new()
-        .has_headers(true)
-        .from_reader(BufReader::new(file));
-
-    // Read all existing records and filter out the entry with the specified index
-    let mut updated_records = Vec::new();
-    for result in reader.records() {
-        let record = result.map_err(|e| io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        let record_index: usize = record[0]
-            .parse()
-            .map_err(|e| io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-
-        if record_index != index {
-            updated_records.push(record.clone());
+impl From<Record> for LogEntry {
+    fn from(record: Record) -> Self {
+        LogEntry {
+            index: record.index,
+            start_time: record.start_time,
+            message: record.message,
+            elapsed_time: record.elapsed_time,
+            paused_time: record.paused_time,
+            title: record.title,
+            project: record.project,
+            tags: parse_tags(&record.tags),
+            estimate: record.estimate,
+            paused_since: record.paused_since,
+            metadata: parse_metadata(&record.metadata),
+        }
+    }
+}
+
+impl From<&LogEntry> for Record {
+    fn from(entry: &LogEntry) -> Self {
+        Record {
+            index: entry.index,
+            start_time: entry.start_time.clone(),
+            message: entry.message.clone(),
+            elapsed_time: entry.elapsed_time.clone(),
+            paused_time: entry.paused_time.clone(),
+            title: entry.title.clone(),
+            project: entry.project.clone(),
+            tags: entry.tags.join(","),
+            estimate: entry.estimate,
+            paused_since: entry.paused_since.clone(),
+            metadata: format_metadata(&entry.metadata),
+        }
+    }
+}
+
+/// Details of a new task entered by the user.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskDetails {
+    pub title: String,
+    pub description: String,
+    pub project: String,
+    pub tags: Vec<String>,
+    /// Estimated duration in minutes.
+    pub estimate: Option<u32>,
+    /// Extra values stored with the entry, e.g. the git repository it was
+    /// started in.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl From<&LogEntry> for TaskDetails {
+    /// Copies the task of an existing entry, e.g. to time it again.
+    fn from(entry: &LogEntry) -> Self {
+        TaskDetails {
+            title: entry.title.clone(),
+            description: entry.message.clone(),
+            project: entry.project.clone(),
+            tags: entry.tags.clone(),
+            estimate: entry.estimate,
+            metadata: entry.metadata.clone(),
         }
     }
+}
+
+impl TaskDetails {
+    pub fn is_empty(&self) -> bool {
+        self.title.trim().is_empty() && self.description.trim().is_empty()
+    }
+}
+
+/// Which entries a bulk change applies to; criteria that are not set match
+/// every entry, the others all have to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryFilter {
+    /// Entries started before this time; entries without a valid start never match.
+    pub before: Option<DateTime<Utc>>,
+    /// Entries with this tag.
+    pub tag: Option<String>,
+    /// Entries of this project.
+    pub project: Option<String>,
+    /// Entries with all of these metadata values.
+    pub metadata: Vec<(String, String)>,
+}
+
+impl EntryFilter {
+    /// Whether no criterion is set, so that every entry would match.
+    pub fn is_empty(&self) -> bool {
+        *self == EntryFilter::default()
+    }
 
-    // Open the CSV file for writing (truncate it to start fresh)
-    let output_file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .open(log_file)?;
-
-    let mut writer = WriterBuilder::new()
-        .has_headers(true)
-        .from_writer(BufWriter::new(output_file));
-
-    // Write the header to the CSV file
-    writer.write_record(&[
-        "Index",
-        "Start Time",
-        "Task Description",
-        "Elapsed Time (seconds)",
-        "Paused Time (seconds)",
-    ])?;
-
-    // Write the remaining records back to the file
-    for record in updated_records {
-        writer
-            .write_record(&record)
-            .map_err(|e| io::Error::new(std::io::ErrorKind::WriteZero, e))?;
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.before
+            .is_none_or(|before| entry.start().is_some_and(|start| start < before))
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| entry.tags.iter().any(|t| t == tag.trim()))
+            && self
+                .project
+                .as_ref()
+                .is_none_or(|project| entry.project.trim() == project.trim())
+            && self
+                .metadata
+                .iter()
+                .all(|(key, value)| entry.metadata.get(key) == Some(value))
     }
+}
+
+/// Splits a comma separated tag list, dropping empty tags.
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
 
-    writer.flush()?;
-    Ok(())
+/// Reads the metadata column, a JSON object of strings; anything else counts
+/// as no metadata.
+pub fn parse_metadata(metadata: &str) -> BTreeMap<String, String> {
+    if metadata.trim().is_empty() {
+        return BTreeMap::new();
+    }
+    serde_json::from_str(metadata).unwrap_or_default()
+}
+
+/// The metadata column for `metadata`, empty if there is none.
+pub fn format_metadata(metadata: &BTreeMap<String, String>) -> String {
+    if metadata.is_empty() {
+        return String::new();
+    }
+    serde_json::to_string(metadata).unwrap_or_default()
+}
+
+/// Reads logs from a specified file and returns a vector of `LogEntry`.
+///
+/// # Arguments
+/// - `file_path`: The path to the log file.
+///
+/// # Returns
+/// - `Ok(Vec<LogEntry>)`: A vector of log entries if successful.
+/// - `Err(ITrackerError)`: If the file cannot be read or a record is invalid.
+pub fn read_logs_from_file(file_path: &str) -> Result<Vec<LogEntry>, ITrackerError> {
+    Ok(schema::read(file_path)?
+        .into_iter()
+        .map(LogEntry::from)
+        .collect())
 }
@@ -1,59 +1,1865 @@
 mod args;
-mod config;
-mod log;
-mod timer;
+#[cfg(feature = "tray")]
+mod tray;
 mod tui;
 
-use args::Args;
-use chrono::{DateTime, Utc};
+use args::{Args, BackupAction, Command, ConfigAction, PluginAction, SyncService, TeamAction};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
 use clap::{error::ErrorKind as ClapErrorKind, Parser};
-use config::{load_config, save_config};
-use csv::ReaderBuilder;
-use log::read_logs_from_file;
+use itracker::{
+    activity::{self, ActivityTracker},
+    backup::Backups,
+    budget::{self, Retainer, Usage},
+    clockify::{self, Clockify},
+    color::{self, paint, Paint},
+    config::{self, load_config, save_config, ConfigData},
+    crdt::CrdtSync,
+    crypto,
+    daemon::{self, Operation, Timers},
+    dump::{self, Dump},
+    edit,
+    expense::{self, Expense},
+    export::{self, Format as ExportFormat},
+    hooks::Hooks,
+    i18n::{self, tr, tr_args},
+    icons::{self, Icons},
+    idle::{self, IdleAnswer, IdleChoice, IdleDetector},
+    invoice::{self, Detail, Earnings, Invoice},
+    keys::Keymap,
+    locale,
+    log::{read_logs_from_file, EntryFilter, LogEntry, TaskDetails},
+    merge::{self, Conflict, ConflictKind, Resolution},
+    month,
+    notify::Notifier,
+    output::{self, Format as OutputFormat},
+    plugin::{self, Added, Plugin, Plugins},
+    progress::Progress,
+    repo::Repository,
+    report::{self, Goal, Summary},
+    schema::{self, Record},
+    server,
+    snapshot::RemoteBackups,
+    status::{self, State, StatusFormat},
+    suggest,
+    sync::GitSync,
+    table::{Align, Table},
+    team::{TeamClient, TeamReport, TeamServer},
+    template::{self, Template},
+    theme::Theme,
+    timefmt::{DurationFormat, TimeFormat},
+    timeparse,
+    toggl::{self, Toggl},
+    undo::UndoLog,
+    webdav::WebDav,
+    ITrackerError, Storage, Tracker,
+};
 use std::{
-    fs::{File, OpenOptions},
-    io::{BufReader, Error, ErrorKind},
-    time::{Duration, SystemTime},
+    collections::BTreeMap,
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
 };
-use timer::{TaskLog, Timer};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", tr_args("error", &[("message", e.to_string().into())]));
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), ITrackerError> {
     // Parse command line arguments
     let args = parse_args();
+    color::select(args.color);
+
+    // Setting a value must work even if other settings of the file are invalid,
+    // so it skips loading; `config::set_value` checks the value on its own then
+    if let Some(Command::Config {
+        action: ConfigAction::Set { ref key, ref value },
+    }) = args.command
+    {
+        config::set_value(key, value)?;
+        println!("Set {} in {}.", key, config::config_path().display());
+        return Ok(());
+    }
+    let mut config = load_config()?;
+    if let Some(ref profile) = args.profile {
+        config.select_profile(profile)?;
+    }
+    i18n::select(config.language.as_deref());
+    locale::select(config.locale.as_deref())?;
+    icons::select(Icons::from_config(config.icons.as_ref())?);
+    if let Some(ref timezone) = args.timezone {
+        config.timezone_override = Some(config::parse_timezone(timezone)?);
+    }
+
+    match args.command {
+        Some(Command::Config { ref action }) => return run_config(&config, action),
+        // Loading replaces the config file, so it goes before anything else reads it
+        Some(Command::Load { ref file }) => return run_load(&args, &config, file),
+        _ => {}
+    }
+    if let Some(key) = config
+        .encryption
+        .as_ref()
+        .map(|e| e.key())
+        .transpose()?
+        .flatten()
+    {
+        crypto::unlock(key);
+    }
+    config.project = config::find_project_config(&std::env::current_dir()?)?;
+
+    if args.server {
+        let server =
+            TeamServer::from_config(config.team.as_ref(), config::data_dir()?.join("team"))?;
+        return server.serve(config.timezone());
+    }
 
     // Handle log deletion if specified
     if let Some(index) = args.delete_log {
-        let log_file = args.log.as_deref().unwrap_or("logs.txt");
-        log::delete_log_entry(log_file, index)?;
-        println!("Log entry at index {} deleted from {}.", index, log_file);
+        return run_delete(&args, &config, index);
+    }
+
+    // Load or save configuration
+    let output_file = manage_config(&args, &mut config)?;
+    let context = Context {
+        args: &args,
+        config: &config,
+        output_file: &output_file,
+    };
+
+    // Status bars read stdout, so this goes before any other output
+    match args.command {
+        Some(Command::Status) => return run_status(&context),
+        Some(Command::Tmux) => return run_tmux(&output_file),
+        Some(Command::Prompt { shell }) => return run_prompt(&output_file, shell),
+        _ => {}
+    }
+
+    // Scripts parse the data formats, so nothing else goes with them
+    if !args.output_format.is_data() {
+        println!(
+            "{}",
+            tr_args("output-file", &[("file", output_file.as_str().into())])
+        );
+    }
+
+    if let Some(Command::Daemon) = args.command {
+        return run_daemon(&config, &output_file);
+    }
+
+    // Times that cannot be read fail before the task form asks for anything
+    let given = Given::of(&args, &config)?;
+
+    // The log the timer commands below read and change, parsed once for all of them
+    let log = storage(&config, &output_file)?;
+    // Render TUI if necessary and capture the details of a new task
+    let task = read_task(&args, &config, &log)?;
+
+    match args.command {
+        Some(Command::Sync {
+            service: Some(service),
+        }) => return run_sync_service(&context, service),
+        Some(Command::Sync { service: None }) => return run_sync(&context),
+        Some(Command::Plugin { ref action }) => return run_plugin(&context, action),
+        Some(Command::Report {
+            today,
+            from,
+            to,
+            ref client,
+            ref meta,
+        }) => return run_report(&context, today, (from, to), client, meta),
+        Some(Command::List {
+            from,
+            to,
+            ref since,
+            ref meta,
+        }) => return run_list(&context, (from, to), since, meta),
+        Some(Command::Invoice {
+            ref client,
+            from,
+            to,
+            detail,
+            ref number,
+        }) => return run_invoice(&context, client, (from, to), detail, number),
+        Some(Command::Export {
+            format,
+            ref output,
+            from,
+            to,
+            ref client,
+        }) => return run_export(&context, format, output, (from, to), client),
+        Some(Command::Expense {
+            amount,
+            ref description,
+            ref client,
+            date,
+        }) => return run_expense(&context, amount, description, client, date),
+        Some(Command::Team { ref action }) => return run_team(&context, action),
+        Some(Command::Activity {
+            entry,
+            suggest,
+            add,
+        }) => return run_activity(&context, entry, suggest, add),
+        Some(Command::Compact) => return run_compact(&context),
+        Some(Command::Amend {
+            index,
+            ref start,
+            ref end,
+        }) => return run_amend(&context, index, start, end),
+        Some(Command::Split {
+            index,
+            ref at,
+            ref title,
+            ref description,
+        }) => return run_split(&context, index, at, title, description),
+        Some(Command::Edit { index }) => return run_edit(&context, index),
+        Some(Command::Merge {
+            ref targets,
+            prefer,
+        }) => return run_merge(&context, targets, prefer),
+        Some(Command::Move {
+            ref indices,
+            ref to_project,
+            ref to_file,
+        }) => return run_move(&context, indices, to_project, to_file),
+        Some(Command::Dedupe { remove }) => return run_dedupe(&context, remove),
+        Some(Command::Undo) => return run_undo(&context),
+        Some(Command::Restore { ref backup, list }) => return run_restore(&context, backup, list),
+        Some(Command::Dump { ref file }) => return run_dump(&context, file),
+        Some(Command::Backup {
+            action: BackupAction::Prune { dry_run },
+        }) => return run_backup_prune(&config, dry_run),
+        Some(Command::Backup { ref action }) => return run_backup(&context, action),
+        Some(Command::Serve { port }) => {
+            let timers = Timers::new(tracker(&config, &output_file)?)?;
+            return server::serve(port, timers, &config);
+        }
+        #[cfg(feature = "tray")]
+        Some(Command::Tray) => {
+            let timers = Timers::new(tracker(&config, &output_file)?)?;
+            return tray::run(timers, config.duration_format());
+        }
+        _ => {}
+    }
+
+    // Closing locks the month in the configuration, which the others only read
+    if let Some(Command::CloseMonth { ref month }) = args.command {
+        return month::close_month(&output_file, month, &mut config);
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(Command::Grpc { port }) = args.command {
+        let timers = Timers::new(tracker(&config, &output_file)?)?;
+        return itracker::grpc::serve(port, timers, config);
+    }
+
+    for index in [args.stop, args.pause.flatten(), args.resume.flatten()]
+        .into_iter()
+        .flatten()
+    {
+        month::ensure_unlocked(&config, &log, index, args.force)?;
+    }
+
+    if args.add || args.pause.is_some() || args.resume.is_some() || args.stop.is_some() {
+        pull_before_change(&config);
+    }
+
+    // Handle timer commands like start, pause, resume, and stop
+    let tracker = Tracker::new(log).with_hooks(Hooks::from_config(config.hooks.as_ref()));
+    handle_commands(args, task, tracker, &config, given)?;
+
+    Ok(())
+}
+
+/// Opens the viewer of `--log`, or asks for the task `--add` starts; the
+/// projects and tags of `log` are suggested.
+fn read_task(
+    args: &Args,
+    config: &ConfigData,
+    log: &Storage,
+) -> Result<Option<TaskDetails>, ITrackerError> {
+    let theme = Theme::from_config(config.theme.as_ref())?;
+    let time_format = shown_times(args, config)?;
+    if let Some(ref log_file) = args.log {
+        let keymap = Keymap::from_config(config.keys.as_ref())?;
+        tui::view_logs(
+            storage(config, log_file)?,
+            config,
+            &theme,
+            &time_format,
+            keymap,
+            !args.yes,
+        )?;
+        Ok(None)
+    } else if args.add {
+        // Existing projects and tags are only suggestions, a missing or broken file is fine
+        let known = log.entries().unwrap_or_default();
+        let mut defaults = TaskDetails {
+            project: config.default_project().unwrap_or_default().to_string(),
+            tags: config.default_tags().to_vec(),
+            ..TaskDetails::default()
+        };
+        if let Some(template) = config.task_template() {
+            if let Some(repository) = Repository::discover(&std::env::current_dir()?) {
+                defaults.description = repository.describe(template);
+                defaults.metadata = repository.metadata();
+            }
+        }
+        defaults.metadata.extend(args.meta.iter().cloned());
+        // Scripts and `--yes` start the prefilled task without asking
+        if !defaults.is_empty() && (args.yes || !io::stdin().is_terminal()) {
+            Ok(Some(defaults))
+        } else {
+            let suggested =
+                suggest::likely_tasks(&known, Utc::now(), config.timezone(), suggest::MAX_TASKS);
+            Ok(tui::input_task(&theme, &known, &defaults, suggested)?)
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// What the subcommands work with: the arguments, the configuration with
+/// the active profile and the output file.
+struct Context<'a> {
+    args: &'a Args,
+    config: &'a ConfigData,
+    output_file: &'a str,
+}
+
+/// Prints one setting or all of them for `config get` and `config list`.
+fn run_config(config: &ConfigData, action: &ConfigAction) -> Result<(), ITrackerError> {
+    match action {
+        ConfigAction::Get { key } => println!("{}", config::get_value(config, key)?),
+        ConfigAction::List => {
+            for line in config::list_values(config)? {
+                println!("{}", line);
+            }
+        }
+        ConfigAction::Set { .. } => {}
+    }
+    Ok(())
+}
+
+/// Puts the data and config file of a dump in place for `load`.
+fn run_load(args: &Args, config: &ConfigData, file: &Path) -> Result<(), ITrackerError> {
+    let dump = Dump::read(file)?;
+    let question = format!(
+        "Replace the {} with the dump of {}?",
+        if dump.has_config() {
+            "config file and data"
+        } else {
+            "data"
+        },
+        dump.manifest.created.to_rfc3339()
+    );
+    if !args.yes && !confirm(config, &question)? {
+        println!("Load cancelled.");
+        return Ok(());
+    }
+    let config_file = config::config_path();
+    if dump.restore_config(&config_file)? {
+        println!("Restored {}", config_file.display());
+    }
+    let mut config = load_config()?;
+    if let Some(ref profile) = args.profile {
+        config.select_profile(profile)?;
+    }
+    let output_file = match config.output_file() {
+        Some(file) => file.to_string(),
+        None => default_data_file("default_output.txt")?,
+    };
+    let backups = Backups::from_config(config.backup.as_ref())?;
+    let restored = dump.restore(&config::data_dir()?, Path::new(&output_file), &backups)?;
+    for path in &restored {
+        println!("Restored {}", path.display());
+    }
+    println!(
+        "Loaded {} of {} files; the replaced ones were backed up.",
+        restored.len(),
+        dump.len()
+    );
+    Ok(())
+}
+
+/// Deletes the entry of `--delete-log`, or the entries matching its filters
+/// without an index.
+fn run_delete(args: &Args, config: &ConfigData, index: Option<usize>) -> Result<(), ITrackerError> {
+    let default_log = default_data_file("logs.txt")?;
+    let log_file = args.log.as_deref().unwrap_or(&default_log);
+    let filter = EntryFilter {
+        before: args
+            .before
+            .as_deref()
+            .map(|before| timeparse::moment(before, Utc::now(), config.timezone()))
+            .transpose()?,
+        tag: args.tag.clone(),
+        project: args.project.clone(),
+        ..EntryFilter::default()
+    };
+    let Some(index) = index else {
+        return delete_matching(args, config, log_file, &filter);
+    };
+    if !filter.is_empty() {
+        return Err(ITrackerError::Config(
+            "Delete either the entry of an index or the entries matching --before, --tag and --project".to_string(),
+        ));
+    }
+    let storage = storage(config, log_file)?;
+    month::ensure_unlocked(config, &storage, index, args.force)?;
+    let question = tr_args(
+        "delete-confirm",
+        &[("index", index.into()), ("file", log_file.into())],
+    );
+    if !args.yes && !confirm(config, &question)? {
+        println!("{}", tr("delete-cancelled"));
         return Ok(());
     }
+    pull_before_change(config);
+    UndoLog::in_data_dir()?.record(log_file, &format!("delete of entry {}", index), || {
+        storage.delete(index)
+    })?;
+    println!(
+        "{}",
+        tr_args(
+            "deleted",
+            &[("index", index.into()), ("file", log_file.into())]
+        )
+    );
+    Ok(())
+}
 
-    // Read logs from the specified file if provided
-    let logs = if let Some(ref log_file) = args.log {
-        read_logs_from_file(log_file)?
+/// Prints the running timer for `status`.
+fn run_status(context: &Context) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let running = status::active(output_file)?;
+    let now = Utc::now();
+    let template = shown_template(args, config)?;
+    if let (Some(template), OutputFormat::Table) = (template, args.output_format) {
+        let times = shown_times(args, config)?;
+        // Idle prints an empty line, as the text format does
+        let line = running
+            .as_ref()
+            .map(|entry| {
+                template.render(&template::fields(
+                    entry,
+                    now,
+                    config.duration_format(),
+                    &times,
+                    config.timezone(),
+                ))
+            })
+            .unwrap_or_default();
+        println!("{}", line);
+        return Ok(());
+    }
+    let goal = match config.daily_goal() {
+        Some(target) => Some(Goal {
+            tracked: status::tracked_today(output_file, now, config.timezone())?,
+            target,
+        }),
+        None => None,
+    };
+    if args.output_format.is_data() {
+        let row = output::StatusRow::of(running.as_ref(), now, goal);
+        print!("{}", output::render(args.output_format, &row)?);
+        return Ok(());
+    }
+    let format = args.output_format.status();
+    let line = status::render(
+        format,
+        running.as_ref(),
+        now,
+        config.duration_format(),
+        goal,
+        config.timezone(),
+    );
+    // Bars parse the other formats, only the text is for people
+    match (format, State::of(running.as_ref())) {
+        (StatusFormat::Text, State::Running) => println!("{}", paint(line, Paint::Running)),
+        (StatusFormat::Text, State::Paused) => println!("{}", paint(line, Paint::Paused)),
+        _ => println!("{}", line),
+    }
+    Ok(())
+}
+
+/// Prints the running timer as a tmux segment for `tmux`.
+fn run_tmux(output_file: &str) -> Result<(), ITrackerError> {
+    let running = status::active(output_file)?;
+    println!("{}", status::tmux(running.as_ref(), Utc::now()));
+    Ok(())
+}
+
+/// Prints the running timer for a shell prompt for `prompt`.
+fn run_prompt(output_file: &str, shell: status::Shell) -> Result<(), ITrackerError> {
+    let segment = status::prompt(status::active(output_file)?.as_ref(), Utc::now(), shell);
+    // Nothing at all when idle, so prompts can test for empty output
+    if !segment.is_empty() {
+        println!("{}", segment);
+    }
+    Ok(())
+}
+
+/// Runs the daemon for `daemon`, with the schedules the configuration sets up.
+fn run_daemon(config: &ConfigData, output_file: &str) -> Result<(), ITrackerError> {
+    let backups = Backups::from_config(config.backup.as_ref())?;
+    let notifier = Notifier::from_config(config.notifications.as_ref())?
+        .with_workday(config.workday(), config.timezone());
+    let watched = std::path::absolute(output_file)?
+        .to_string_lossy()
+        .into_owned();
+    if let Some(remote) = remote_backups(config)? {
+        remote.schedule(config::data_dir()?, output_file.into());
+    }
+    if let Some(tracker) = ActivityTracker::from_config(config.activity.as_ref())? {
+        tracker.schedule(output_file.into());
+    }
+    if let Some(detector) = IdleDetector::from_config(config.idle.as_ref())? {
+        detector.schedule(Timers::new(tracker(config, output_file)?)?);
+    }
+    #[cfg(feature = "dbus")]
+    itracker::dbus::serve(Timers::new(tracker(config, output_file)?)?)?;
+    let hooks = Hooks::from_config(config.hooks.as_ref());
+    daemon::serve(
+        &daemon::socket_path()?,
+        backups,
+        hooks,
+        notifier,
+        vec![watched],
+    )
+}
+
+/// Pushes the entries to one service for `sync toggl` and `sync clockify`.
+fn run_sync_service(context: &Context, service: SyncService) -> Result<(), ITrackerError> {
+    let Context {
+        config,
+        output_file,
+        ..
+    } = *context;
+    match service {
+        SyncService::Toggl => {
+            let toggl = Toggl::from_config(config.toggl.as_ref())?.ok_or_else(|| {
+                ITrackerError::Sync("No Toggl account configured, set `toggl.token`".to_string())
+            })?;
+            pull_before_change(config);
+            push_to_toggl(&toggl, config, output_file)?;
+        }
+        SyncService::Clockify => {
+            let clockify = Clockify::from_config(config.clockify.as_ref())?.ok_or_else(|| {
+                ITrackerError::Sync(
+                    "No Clockify account configured, set `clockify.api_key`".to_string(),
+                )
+            })?;
+            pull_before_change(config);
+            push_to_clockify(&clockify, config, output_file)?;
+        }
+    }
+    Ok(())
+}
+
+/// Syncs the output file with everything configured for `sync`.
+fn run_sync(context: &Context) -> Result<(), ITrackerError> {
+    let Context {
+        config,
+        output_file,
+        ..
+    } = *context;
+    let git = GitSync::from_config(config.sync.as_ref())?;
+    let webdav = WebDav::from_config(config.webdav.as_ref())?;
+    let crdt = CrdtSync::from_config(config.crdt.as_ref())?;
+    let team = TeamClient::from_config(config.team.as_ref())?;
+    let toggl = Toggl::from_config(config.toggl.as_ref())?;
+    let clockify = Clockify::from_config(config.clockify.as_ref())?;
+    let plugins = Plugins::from_config(config.plugins.as_ref()).sync_targets()?;
+    if git.is_none()
+        && webdav.is_none()
+        && crdt.is_none()
+        && team.is_none()
+        && toggl.is_none()
+        && clockify.is_none()
+        && plugins.is_empty()
+    {
+        return Err(ITrackerError::Sync(
+            "Nothing to sync with, set `sync.remote` to a git remote, `webdav.url` to a WebDAV file, `crdt.dir` to a shared folder, `team.server` to a team server, `toggl.token` to a Toggl account, `clockify.api_key` to a Clockify account or `plugins.sync` to sync plugins".to_string(),
+        ));
+    }
+    if let Some(crdt) = crdt {
+        let backups = Backups::from_config(config.backup.as_ref())?;
+        let synced = crdt.sync(output_file, &backups)?;
+        for path in &synced.unreadable {
+            eprintln!(
+                "Warning: cannot read {} yet, it is merged next time.",
+                path.display()
+            );
+        }
+        let list = |indices: &[usize]| -> String {
+            let indices: Vec<String> = indices.iter().map(usize::to_string).collect();
+            indices.join(", ")
+        };
+        for (what, indices) in [
+            ("added", &synced.added),
+            ("changed", &synced.changed),
+            ("removed", &synced.removed),
+        ] {
+            if !indices.is_empty() {
+                println!(
+                    "Entries of {} {} on other devices: {}.",
+                    output_file,
+                    what,
+                    list(indices)
+                );
+            }
+        }
+        println!(
+            "Synced {} with {} other devices through {}.",
+            output_file,
+            synced.devices,
+            crdt.dir().display()
+        );
+    }
+    if let Some(webdav) = webdav {
+        let backups = Backups::from_config(config.backup.as_ref())?;
+        let synced = webdav.sync(output_file, &backups)?;
+        for (old, new) in synced.renumbered {
+            println!("Log entry {} of {} is now {}.", old, output_file, new);
+        }
+        let change = match (synced.pulled, synced.pushed) {
+            (true, true) => "merged with",
+            (true, false) => "updated from",
+            (false, true) => "uploaded to",
+            (false, false) => "already in sync with",
+        };
+        println!("{} {} {}.", output_file, change, webdav.url());
+    }
+    if let Some(git) = git {
+        for (file, old, new) in git.sync()?.renumbered {
+            println!("Log entry {} of {} is now {}.", old, file, new);
+        }
+        println!("Synced {} with {}.", git.dir().display(), git.remote());
+    }
+    // Last, so the team sees what the other syncs brought in
+    if let Some(team) = team {
+        let count = team.push(output_file)?;
+        println!(
+            "Reported {} entries to {} as {}.",
+            count,
+            team.server(),
+            team.user()
+        );
+    }
+    if let Some(toggl) = toggl {
+        push_to_toggl(&toggl, config, output_file)?;
+    }
+    if let Some(clockify) = clockify {
+        push_to_clockify(&clockify, config, output_file)?;
+    }
+    for plugin in plugins {
+        sync_with_plugin(&plugin, output_file, &BTreeMap::new())?;
+    }
+    Ok(())
+}
+
+/// Lists the plugins or exports, imports and syncs with one for `plugin`.
+fn run_plugin(context: &Context, action: &PluginAction) -> Result<(), ITrackerError> {
+    let Context {
+        config,
+        output_file,
+        ..
+    } = *context;
+    let plugins = Plugins::from_config(config.plugins.as_ref());
+    match action {
+        PluginAction::List => {
+            let listed = plugins.list()?;
+            if listed.is_empty() {
+                println!("No plugins in {}.", plugins.dir().display());
+            }
+            for (name, plugin) in listed {
+                match plugin {
+                    Ok(plugin) => {
+                        let capabilities: Vec<&str> =
+                            plugin.capabilities.iter().map(|c| c.name()).collect();
+                        println!(
+                            "{}  [{}]  {}",
+                            name,
+                            capabilities.join(", "),
+                            plugin.description
+                        );
+                    }
+                    Err(e) => println!("{}  unusable: {}", name, e),
+                }
+            }
+        }
+        PluginAction::Export {
+            name,
+            output,
+            options,
+        } => {
+            let plugin = plugins.find(name)?;
+            let entries = read_logs_from_file(output_file)?;
+            let options = options.iter().cloned().collect();
+            let exported = plugin.export(output_file, &entries, &options)?;
+            match output {
+                Some(path) => {
+                    fs::write(path, exported).map_err(ITrackerError::file(path))?;
+                    println!(
+                        "Exported {} entries to {} with {}.",
+                        entries.len(),
+                        path.display(),
+                        name
+                    );
+                }
+                None => print!("{}", exported),
+            }
+        }
+        PluginAction::Import {
+            name,
+            file,
+            options,
+        } => {
+            let plugin = plugins.find(name)?;
+            let input = fs::read_to_string(file).map_err(ITrackerError::file(file))?;
+            let entries = plugin.import(&input, &options.iter().cloned().collect())?;
+            pull_before_change(config);
+            let progress = Progress::new(entries.len(), "Importing");
+            let added = plugin::add_entries(output_file, &entries, &progress)?;
+            progress.finish();
+            report_failed(&added);
+            println!(
+                "Imported {} of {} entries from {} into {}, {} skipped as already there, {} failed.",
+                added.created,
+                entries.len(),
+                file.display(),
+                output_file,
+                added.skipped,
+                added.failed.len()
+            );
+        }
+        PluginAction::Sync { name, options } => {
+            let plugin = plugins.find(name)?;
+            pull_before_change(config);
+            sync_with_plugin(&plugin, output_file, &options.iter().cloned().collect())?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the time tracked per day and project for `report`.
+fn run_report(
+    context: &Context,
+    today: bool,
+    (from, to): (Option<NaiveDate>, Option<NaiveDate>),
+    client: &Option<String>,
+    meta: &[(String, String)],
+) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let tz = config.timezone();
+    let current = report::today(Utc::now(), tz);
+    let (from, to) = if today {
+        (current, current)
     } else {
-        vec![]
+        let from = from.unwrap_or_else(|| config.week().range(current).0);
+        (from, to.unwrap_or_else(|| config.week().range(from).1))
+    };
+    let client = client.as_deref().map(str::trim);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(config.report_threads())
+        .build_global()
+        .map_err(|e| ITrackerError::Config(format!("cannot start report threads: {}", e)))?;
+    // Only the months the range touches and the weeks around it are
+    // reported on, with a day to spare for the time zone
+    let first = budget::month_range(from)
+        .0
+        .min(from - chrono::Duration::days(7))
+        - chrono::Duration::days(1);
+    let last = budget::month_range(to)
+        .1
+        .max(to + chrono::Duration::days(7))
+        + chrono::Duration::days(2);
+    let mut entries = storage(config, output_file)?.entries_started(
+        first.and_time(NaiveTime::MIN).and_utc(),
+        last.and_time(NaiveTime::MIN).and_utc(),
+    )?;
+    let mut expenses = expense::read_expenses(Path::new(&output_file))?;
+    if let Some(client) = client {
+        entries.retain(|entry| entry.project.trim() == client);
+        expenses.retain(|expense| expense.project() == client);
+    }
+    let filter = EntryFilter {
+        metadata: meta.to_vec(),
+        ..EntryFilter::default()
     };
+    entries.retain(|entry| filter.matches(entry));
+    let now = Utc::now();
+    let billing = config.billing()?;
+    let format = args.output_format.for_command("report")?;
+    if format.is_data() {
+        let rows = output::report_rows(report::entries_between(&entries, from, to, tz), now, tz);
+        print!("{}", output::render(format, &rows)?);
+        return Ok(());
+    }
+    let summary =
+        report::summarize_in_parallel(&report::entries_between(&entries, from, to, tz), now, tz);
+    let earnings = Earnings::of(
+        &entries,
+        &expenses,
+        (from, to),
+        &billing,
+        &config.exchange()?,
+        tz,
+    )?;
+    let budgets = config.budgets()?;
+    let usage: Vec<_> = budgets
+        .weeks(from, to)
+        .into_iter()
+        .map(|week| {
+            let mut usage = budgets.week_usage(&entries, week, now, tz);
+            usage.retain(|usage| client.is_none_or(|client| usage.project == client));
+            (week, usage)
+        })
+        .filter(|(_, usage)| !usage.is_empty())
+        .collect();
+    print_report(
+        &summary,
+        (from, to),
+        (config.daily_goal(), config.workday()),
+        &usage,
+        &earnings,
+        config.duration_format(),
+        Layout::of(args),
+    );
+    if let Some(client) = client {
+        // Every month the range touches
+        let mut month = budget::month_range(from).0;
+        while month <= to {
+            if let Some(retainer) = Retainer::of(&entries, client, month, &billing, now, tz) {
+                print_retainer(&retainer, config.duration_format());
+            }
+            month = budget::month_range(month).1 + chrono::Duration::days(1);
+        }
+    }
+    Ok(())
+}
 
-    // Render TUI if necessary and capture title and description
-    let data = if args.log.is_some() {
-        tui::render(Some(logs))?
-    } else if args.add {
-        tui::render(None)?
+/// Prints the entries for `list`.
+fn run_list(
+    context: &Context,
+    (from, to): (Option<NaiveDate>, Option<NaiveDate>),
+    since: &Option<String>,
+    meta: &[(String, String)],
+) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let tz = config.timezone();
+    let now = Utc::now();
+    let today = report::today(now, tz);
+    let entries = storage(config, output_file)?.entries()?;
+    let entries = report::entries_between(
+        &entries,
+        from.unwrap_or(NaiveDate::MIN),
+        to.unwrap_or(today),
+        tz,
+    );
+    let since = since
+        .as_deref()
+        .map(|since| timeparse::moment(since, now, tz))
+        .transpose()?;
+    let filter = EntryFilter {
+        metadata: meta.to_vec(),
+        ..EntryFilter::default()
+    };
+    let entries: Vec<_> = entries
+        .into_iter()
+        .filter(|entry| {
+            since.is_none_or(|since| entry.start().is_some_and(|start| start >= since))
+                && filter.matches(entry)
+        })
+        .collect();
+    let format = args.output_format.for_command("list")?;
+    if format.is_data() {
+        let rows: Vec<_> = entries
+            .iter()
+            .map(|entry| output::EntryRow::of(entry, now))
+            .collect();
+        print!("{}", output::render(format, &rows)?);
+        return Ok(());
+    }
+    let time_format = shown_times(args, config)?;
+    let durations = config.duration_format();
+    let layout = Layout::of(args);
+    let template = shown_template(args, config)?;
+    let mut table = Table::new()
+        .column(tr("table-index"), Align::Right)
+        .column(tr("table-start"), Align::Left)
+        .column(tr("table-tracked"), Align::Right)
+        .column("", Align::Left)
+        .shrinking(tr("table-title"))
+        .shrinking(tr("table-project"))
+        .shrinking(tr("table-tags"));
+    for entry in entries {
+        let state = State::of(Some(entry).filter(|entry| entry.is_running()));
+        let style = match state {
+            State::Running => Some(Paint::Running),
+            State::Paused => Some(Paint::Paused),
+            State::Idle if report::local_date(entry, tz) < Some(today) => Some(Paint::Old),
+            State::Idle => None,
+        };
+        if let Some(template) = &template {
+            let line = template.render(&template::fields(entry, now, durations, &time_format, tz));
+            match style {
+                Some(style) => println!("{}", paint(line, style)),
+                None => println!("{}", line),
+            }
+            continue;
+        }
+        let cells = vec![
+            entry.index.to_string(),
+            time_format.format_stored(&entry.start_time, tz),
+            durations.format(report::tracked_seconds(entry, now)),
+            state.icon().to_string(),
+            entry.display_title().to_string(),
+            entry.project.trim().to_string(),
+            entry
+                .tags
+                .iter()
+                .map(|tag| format!("#{}", tag))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ];
+        if let Layout::Table(_) = layout {
+            match style {
+                Some(style) => table.painted_row(cells, style),
+                None => table.row(cells),
+            }
+            continue;
+        }
+        let line = format!(
+            "{:>4}  {}  {:>10}  {} {}{}{}",
+            cells[0],
+            cells[1],
+            cells[2],
+            match state.icon() {
+                "" => " ",
+                icon => icon,
+            },
+            cells[4],
+            match cells[5].as_str() {
+                "" => String::new(),
+                project => format!("  [{}]", project),
+            },
+            entry
+                .tags
+                .iter()
+                .map(|tag| format!(" #{}", tag))
+                .collect::<String>()
+        );
+        match style {
+            Some(style) => println!("{}", paint(line, style)),
+            None => println!("{}", line),
+        }
+    }
+    if let (Layout::Table(width), None) = (layout, &template) {
+        print!("{}", table.render(width));
+    }
+    Ok(())
+}
+
+/// Prints the invoice of a range for `invoice`, issuing it with `--number`.
+fn run_invoice(
+    context: &Context,
+    client: &Option<String>,
+    (from, to): (Option<NaiveDate>, Option<NaiveDate>),
+    detail: Detail,
+    number: &Option<String>,
+) -> Result<(), ITrackerError> {
+    let Context {
+        config,
+        output_file,
+        ..
+    } = *context;
+    let tz = config.timezone();
+    let from = from.unwrap_or_else(|| budget::month_range(report::today(Utc::now(), tz)).0);
+    let to = to.unwrap_or_else(|| budget::month_range(from).1);
+    let storage = storage(config, output_file)?;
+    let entries = storage.entries()?;
+    let mut expenses = expense::read_expenses(Path::new(&output_file))?;
+    let mut invoice = Invoice::build(
+        &entries,
+        &expenses,
+        (from, to),
+        client.as_deref(),
+        detail,
+        &config.billing()?,
+        tz,
+    )?;
+    if let Some(number) = number.as_deref().map(str::trim) {
+        if invoice.entries.is_empty() && invoice.expenses.is_empty() {
+            return Err(ITrackerError::Config(
+                "Nothing to invoice in this range".to_string(),
+            ));
+        }
+        if entries
+            .iter()
+            .any(|entry| invoice::number(entry) == Some(number))
+            || expenses.iter().any(|expense| expense.invoice == number)
+        {
+            return Err(ITrackerError::Config(format!(
+                "Invoice {} was issued before, choose another number",
+                number
+            )));
+        }
+        invoice.number = Some(number.to_string());
+    }
+    print!("{}", invoice.render());
+    if invoice.running > 0 {
+        eprintln!(
+            "Left out {} entries whose timer still runs.",
+            invoice.running
+        );
+    }
+    if invoice.invoiced > 0 {
+        eprintln!("Left out {} entries invoiced before.", invoice.invoiced);
+    }
+    if let Some(number) = &invoice.number {
+        pull_before_change(config);
+        for mut entry in entries
+            .into_iter()
+            .filter(|entry| invoice.entries.contains(&entry.index))
+        {
+            entry
+                .metadata
+                .insert(invoice::METADATA_KEY.to_string(), number.clone());
+            storage.update(&entry)?;
+        }
+        eprintln!(
+            "Marked {} entries as invoiced as {}.",
+            invoice.entries.len(),
+            number
+        );
+        if !invoice.expenses.is_empty() {
+            for expense in &mut expenses {
+                if invoice
+                    .expenses
+                    .iter()
+                    .any(|billed| billed.id == expense.id)
+                {
+                    expense.invoice = number.clone();
+                }
+            }
+            expense::write_expenses(Path::new(&output_file), &expenses)?;
+            eprintln!(
+                "Marked {} expenses as invoiced as {}.",
+                invoice.expenses.len(),
+                number
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Writes the stopped entries for another time tracker for `export`.
+fn run_export(
+    context: &Context,
+    format: ExportFormat,
+    output: &Option<PathBuf>,
+    (from, to): (Option<NaiveDate>, Option<NaiveDate>),
+    client: &Option<String>,
+) -> Result<(), ITrackerError> {
+    let Context {
+        config,
+        output_file,
+        ..
+    } = *context;
+    let tz = config.timezone();
+    let mut entries = storage(config, output_file)?.entries()?;
+    if let Some(client) = client.as_deref().map(str::trim) {
+        entries.retain(|entry| entry.project.trim() == client);
+    }
+    if from.is_some() || to.is_some() {
+        let from = from.unwrap_or(NaiveDate::MIN);
+        let to = to.unwrap_or(NaiveDate::MAX);
+        entries = report::entries_between(&entries, from, to, tz)
+            .into_iter()
+            .cloned()
+            .collect();
+    }
+    let progress = Progress::new(entries.len(), "Exporting");
+    let (exported, count) = export::export(&entries, format, &config.billing()?, tz, &progress)?;
+    progress.finish();
+    match output {
+        Some(path) => {
+            fs::write(path, exported).map_err(ITrackerError::file(path))?;
+            println!(
+                "Exported {} entries to {}, {} running or without a start skipped.",
+                count,
+                path.display(),
+                entries.len() - count
+            );
+        }
+        None => print!("{}", exported),
+    }
+    Ok(())
+}
+
+/// Records an expense for `expense`.
+fn run_expense(
+    context: &Context,
+    amount: f64,
+    description: &str,
+    client: &Option<String>,
+    date: Option<NaiveDate>,
+) -> Result<(), ITrackerError> {
+    let Context {
+        config,
+        output_file,
+        ..
+    } = *context;
+    let client = client.as_deref().map(str::trim).unwrap_or_default();
+    let currency = config.billing()?.currency_for(client);
+    pull_before_change(config);
+    let expense = expense::add_expense(
+        Path::new(&output_file),
+        Expense {
+            id: 0,
+            date: date.unwrap_or_else(|| report::today(Utc::now(), config.timezone())),
+            amount,
+            currency: currency.map(|c| c.to_string()).unwrap_or_default(),
+            description: description.trim().to_string(),
+            client: client.to_string(),
+            invoice: String::new(),
+        },
+    )?;
+    println!(
+        "Recorded expense {}: {} for {} on {}.",
+        expense.id,
+        expense.money(),
+        expense.description,
+        locale::date(expense.date)
+    );
+    Ok(())
+}
+
+/// Reports to the team server or prints its report for `team`.
+fn run_team(context: &Context, action: &TeamAction) -> Result<(), ITrackerError> {
+    let Context {
+        config,
+        output_file,
+        ..
+    } = *context;
+    let team = TeamClient::from_config(config.team.as_ref())?.ok_or_else(|| {
+        ITrackerError::Team(
+            "No team server configured, set `team.server`, `team.user` and `team.token`"
+                .to_string(),
+        )
+    })?;
+    match action {
+        TeamAction::Push => {
+            let count = team.push(output_file)?;
+            println!(
+                "Reported {} entries to {} as {}.",
+                count,
+                team.server(),
+                team.user()
+            );
+        }
+        TeamAction::Report { from, to } => {
+            let report = team.report(*from, *to)?;
+            print_team_report(&report, config.duration_format());
+        }
+    }
+    Ok(())
+}
+
+/// Prints the recorded activity, or the entries it suggests, for `activity`.
+fn run_activity(
+    context: &Context,
+    entry: Option<usize>,
+    suggest: bool,
+    add: bool,
+) -> Result<(), ITrackerError> {
+    let Context {
+        config,
+        output_file,
+        ..
+    } = *context;
+    let samples = activity::read_samples(Path::new(&output_file))?;
+    let durations = config.duration_format();
+    let tz = config.timezone();
+    if let Some(index) = entry {
+        let windows = activity::by_window(&samples, index);
+        if windows.is_empty() {
+            println!("No activity recorded for entry {}.", index);
+        }
+        for (window, seconds) in windows {
+            println!(
+                "{:>10}  {}  {}",
+                durations.format(seconds),
+                window.app,
+                window.title
+            );
+        }
+    } else if suggest {
+        let suggestions = activity::suggestions(&samples, activity::MIN_SUGGESTION_SECONDS);
+        if suggestions.is_empty() {
+            println!("No untracked activity to suggest entries for.");
+        }
+        for suggestion in &suggestions {
+            println!(
+                "{} {}  {:>10}  {}  {}",
+                locale::date(suggestion.start.with_timezone(&tz).date_naive()),
+                locale::time(&suggestion.start.with_timezone(&tz)),
+                durations.format(suggestion.seconds),
+                suggestion.app,
+                suggestion.title
+            );
+        }
+        if add && !suggestions.is_empty() {
+            pull_before_change(config);
+            let storage = storage(config, output_file)?;
+            for suggestion in &suggestions {
+                let index = storage.next_index()?;
+                storage.push(&activity::suggested_record(suggestion, index))?;
+            }
+            println!("Added {} entries to {}.", suggestions.len(), output_file);
+        }
     } else {
-        vec![String::new()]
+        let today = Utc::now().with_timezone(&tz).date_naive();
+        for sample in samples
+            .iter()
+            .filter(|sample| sample.start.with_timezone(&tz).date_naive() == today)
+        {
+            let entry = sample
+                .entry
+                .map_or_else(|| "-".to_string(), |index| index.to_string());
+            println!(
+                "{}-{}  {:>4}  {}  {}",
+                locale::time(&sample.start.with_timezone(&tz)),
+                locale::time(&sample.end().with_timezone(&tz)),
+                entry,
+                sample.app,
+                sample.title
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Renumbers and normalizes the entries for `compact`.
+fn run_compact(context: &Context) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let file = [("file", output_file.into())];
+    if !args.yes && !confirm(config, &tr_args("compact-confirm", &file))? {
+        println!("{}", tr("compact-cancelled"));
+        return Ok(());
+    }
+    let compaction = storage(config, output_file)?.compact()?;
+    if compaction.is_empty() {
+        println!("{}", tr_args("compact-unchanged", &file));
+    }
+    for (old, new) in &compaction.renumbered {
+        println!("{} -> {}", old, new);
+    }
+    let counts = [
+        ("compact-reordered", compaction.reordered),
+        ("compact-trimmed", compaction.trimmed),
+        ("compact-padded", compaction.padded),
+        ("compact-amendments", compaction.amendments),
+    ];
+    for (what, count) in counts.into_iter().filter(|(_, count)| *count > 0) {
+        println!("{}", tr_args(what, &[("count", count.into())]));
+    }
+    if compaction.headers {
+        println!("{}", tr("compact-headers"));
     }
-    .join("");
+    Ok(())
+}
 
-    // Load or save configuration
-    let output_file = manage_config(&args)?;
+/// Moves the start or end of an entry for `amend`.
+fn run_amend(
+    context: &Context,
+    index: usize,
+    start: &Option<String>,
+    end: &Option<String>,
+) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let tracker = tracker(config, output_file)?;
+    month::ensure_unlocked(config, tracker.storage(), index, args.force)?;
+    let tz = config.timezone();
+    let now = Utc::now();
+    let entry = tracker.storage().entry(index)?;
+    let day = entry_day(&entry, now, tz);
+    let read = |text: &Option<String>| {
+        text.as_deref()
+            .map(|text| timeparse::moment_on(text, now, tz, day))
+            .transpose()
+    };
+    let (start, end) = (read(start)?, read(end)?);
+    if let Some(start) = start {
+        month::ensure_open(config, start)?;
+    }
+    pull_before_change(config);
+    let timers = Timers::new(tracker)?;
+    let entry = UndoLog::in_data_dir()?.record_entries(
+        output_file,
+        &format!("amend of entry {}", index),
+        &[index],
+        || timers.run(Operation::Amend { index, start, end }),
+    )?;
+    let time_format = TimeFormat::from_config(config.time_format.as_deref())?;
+    let start = entry.start().unwrap_or_default().with_timezone(&tz);
+    let elapsed = entry.elapsed_time.trim().parse().unwrap_or(0);
+    if entry.is_running() {
+        println!(
+            "Log entry {} now runs since {}.",
+            index,
+            time_format.format(&start)
+        );
+    } else {
+        println!(
+            "Log entry {} now runs from {} to {}, {}.",
+            index,
+            time_format.format(&start),
+            time_format.format(&(start + chrono::Duration::seconds(elapsed as i64))),
+            config.duration_format().format(elapsed)
+        );
+    }
+    Ok(())
+}
 
-    println!("Using output file: {}", output_file);
+/// Splits an entry in two for `split`.
+fn run_split(
+    context: &Context,
+    index: usize,
+    at: &str,
+    title: &Option<String>,
+    description: &Option<String>,
+) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let tracker = tracker(config, output_file)?;
+    month::ensure_unlocked(config, tracker.storage(), index, args.force)?;
+    let tz = config.timezone();
+    let now = Utc::now();
+    let entry = tracker.storage().entry(index)?;
+    let day = entry_day(&entry, now, tz);
+    let at = timeparse::moment_on(at, now, tz, day)?;
+    let task = (title.is_some() || description.is_some()).then(|| {
+        let mut task = TaskDetails::from(&entry);
+        if let Some(title) = title {
+            task.title = title.clone();
+        }
+        if let Some(description) = description {
+            task.description = description.clone();
+        }
+        task
+    });
+    pull_before_change(config);
+    let timers = Timers::new(tracker)?;
+    let second = UndoLog::in_data_dir()?.record(
+        output_file,
+        &format!("split of entry {}", index),
+        || timers.run(Operation::Split { index, at, task }),
+    )?;
+    let time_format = TimeFormat::from_config(config.time_format.as_deref())?;
+    let durations = config.duration_format();
+    let before = (at - entry.start().unwrap_or_default().with_timezone(&Utc)).num_seconds();
+    println!(
+        "Log entry {} split at {}: {} stay with it, the rest is log entry {}{}.",
+        index,
+        time_format.format(&at.with_timezone(&tz)),
+        durations.format(before.unsigned_abs()),
+        second.index,
+        if second.is_running() {
+            ", still running".to_string()
+        } else {
+            format!(
+                " of {}",
+                durations.format(second.elapsed_time.trim().parse().unwrap_or(0))
+            )
+        }
+    );
+    Ok(())
+}
 
-    // Handle timer commands like start, pause, resume, and stop
-    handle_commands(args, data, &output_file)?;
+/// Opens an entry in the editor and writes back the changes for `edit`.
+fn run_edit(context: &Context, index: usize) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let storage = storage(config, output_file)?;
+    month::ensure_unlocked(config, &storage, index, args.force)?;
+    pull_before_change(config);
+    let entry = storage.entry(index)?;
+    let tz = config.timezone();
+    let original = edit::to_toml(&entry, tz)?;
+    let scratch = edit::scratch_file(index);
+    let mut text = original.clone();
+    let edited = loop {
+        text = edit::edit_text(&scratch, &text)?;
+        if text == original {
+            let _ = fs::remove_file(&scratch);
+            println!("{}", tr_args("edit-unchanged", &[("index", index.into())]));
+            return Ok(());
+        }
+        let edited = edit::from_toml(&entry, &text, Utc::now(), tz).and_then(|edited| {
+            if edited.start_time != entry.start_time {
+                let start = edited.start().unwrap_or_default().with_timezone(&Utc);
+                month::ensure_open(config, start)?;
+            }
+            Ok(edited)
+        });
+        match edited {
+            Ok(edited) => break edited,
+            // Mistakes are fixed in the editor rather than typed again
+            Err(e) if io::stdin().is_terminal() && io::stdout().is_terminal() => {
+                eprintln!("{}", e);
+                if !confirm(config, &tr("edit-again"))? {
+                    let _ = fs::remove_file(&scratch);
+                    println!("{}", tr_args("edit-unchanged", &[("index", index.into())]));
+                    return Ok(());
+                }
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&scratch);
+                return Err(e);
+            }
+        }
+    };
+    let _ = fs::remove_file(&scratch);
+    UndoLog::in_data_dir()?.record_entries(
+        output_file,
+        &format!("edit of entry {}", index),
+        &[index],
+        || storage.update(&edited),
+    )?;
+    println!("{}", tr_args("edit-updated", &[("index", index.into())]));
+    Ok(())
+}
+
+/// Merges entries of the output file, or another log file into a log file,
+/// for `merge`.
+fn run_merge(
+    context: &Context,
+    targets: &[String],
+    prefer: Option<Resolution>,
+) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let indices: Option<Vec<usize>> = targets.iter().map(|target| target.parse().ok()).collect();
+    if let Some(indices) = indices {
+        let tracker = tracker(config, output_file)?;
+        for index in &indices {
+            month::ensure_unlocked(config, tracker.storage(), *index, args.force)?;
+        }
+        let listed: Vec<String> = indices.iter().map(usize::to_string).collect();
+        let question = format!(
+            "Merge log entries {} of {}?",
+            listed.join(", "),
+            output_file
+        );
+        if !args.yes && !confirm(config, &question)? {
+            println!("Merge cancelled.");
+            return Ok(());
+        }
+        pull_before_change(config);
+        let timers = Timers::new(tracker)?;
+        let action = format!("merge of entries {}", listed.join(", "));
+        let merged = UndoLog::in_data_dir()?.record(output_file, &action, || {
+            timers.run(Operation::Merge {
+                indices: indices.clone(),
+            })
+        })?;
+        let tracked = report::tracked_seconds(&merged, Utc::now());
+        println!(
+            "Log entries {} merged into {}, {} tracked.",
+            listed.join(", "),
+            merged.index,
+            config.duration_format().format(tracked)
+        );
+        return Ok(());
+    }
+    let [file, other] = targets else {
+        return Err(ITrackerError::Config(
+            "merge takes two log files, or two or more entry indices".to_string(),
+        ));
+    };
+    let ours = schema::read(file)?;
+    let theirs = schema::read(other)?;
+    let next_index = schema::next_index(file, &ours)?;
+    let durations = config.duration_format();
+    let merged = merge::merge_files(&ours, &theirs, next_index, |conflict| match prefer {
+        Some(side) => Ok(Some(side)),
+        None => resolve_conflict(config, conflict, file, other, durations),
+    })?;
+    let Some(merged) = merged else {
+        println!("Merge cancelled, {} is unchanged.", file);
+        return Ok(());
+    };
+
+    if !merged.added.is_empty() || !merged.replaced.is_empty() {
+        Backups::from_config(config.backup.as_ref())?.save(file)?;
+        schema::write(file, &merged.records)?;
+        if let Some(&(_, last)) = merged.added.last() {
+            schema::set_last_index(file, last)?;
+        }
+    }
+    for (old, new) in &merged.added {
+        println!("Entry {} of {} added as {}.", old, other, new);
+    }
+    for index in &merged.replaced {
+        println!("Entry {} of {} replaced.", index, file);
+    }
+    println!(
+        "Merged {} into {}: {} added, {} already there, {} replaced.",
+        other,
+        file,
+        merged.added.len(),
+        merged.duplicates,
+        merged.replaced.len()
+    );
+    Ok(())
+}
+
+/// Moves entries to another project or log file for `move`.
+fn run_move(
+    context: &Context,
+    indices: &[usize],
+    to_project: &Option<String>,
+    to_file: &Option<String>,
+) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let source = storage(config, output_file)?;
+    let entries = source.entries()?;
+    for index in indices {
+        let entry = entries
+            .iter()
+            .find(|entry| entry.index == *index)
+            .ok_or_else(|| ITrackerError::EntryNotFound {
+                path: output_file.into(),
+                index: *index,
+            })?;
+        month::check_unlocked(config, entry, args.force)?;
+    }
+    let listed: Vec<String> = indices.iter().map(usize::to_string).collect();
+    pull_before_change(config);
+
+    if let Some(project) = to_project {
+        let action = format!("move of entries {} to {}", listed.join(", "), project);
+        UndoLog::in_data_dir()?.record_entries(output_file, &action, indices, || {
+            for entry in entries
+                .iter()
+                .filter(|entry| indices.contains(&entry.index))
+            {
+                let mut moved = entry.clone();
+                moved.project = project.trim().to_string();
+                source.update(&moved)?;
+            }
+            Ok(())
+        })?;
+        println!(
+            "Log entries {} moved to project {}.",
+            listed.join(", "),
+            project.trim()
+        );
+    }
+    if let Some(target) = to_file {
+        let same = |path: &str| fs::canonicalize(path).ok();
+        if same(target).is_some() && same(target) == same(output_file) {
+            return Err(ITrackerError::Config(format!(
+                "{} is the file the entries are in already",
+                target
+            )));
+        }
+        for (old, new) in source.move_to(indices, &storage(config, target)?)? {
+            println!(
+                "Log entry {} of {} is now log entry {} of {}.",
+                old, output_file, new, target
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Lists the duplicate entries, deleting them with `--remove`, for `dedupe`.
+fn run_dedupe(context: &Context, remove: bool) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let storage = storage(config, output_file)?;
+    let entries = storage.entries()?;
+    let groups = merge::duplicates(&entries);
+    let time_format = TimeFormat::from_config(config.time_format.as_deref())?;
+    let (durations, tz) = (config.duration_format(), config.timezone());
+    let now = Utc::now();
+    let describe = |index: usize| {
+        let entry = entries.iter().find(|entry| entry.index == index)?;
+        Some(format!(
+            "{}  {}  {}",
+            time_format.format_stored(&entry.start_time, tz),
+            durations.format(report::tracked_seconds(entry, now)),
+            entry.display_title()
+        ))
+    };
+    for group in &groups {
+        println!(
+            "Log entry {}: {}",
+            group[0],
+            describe(group[0]).unwrap_or_default()
+        );
+        for index in &group[1..] {
+            println!(
+                "  duplicate {}: {}",
+                index,
+                describe(*index).unwrap_or_default()
+            );
+        }
+    }
+    let duplicates: Vec<usize> = groups
+        .iter()
+        .flat_map(|group| group[1..].iter().copied())
+        .collect();
+    if duplicates.is_empty() {
+        println!("No duplicates in {}.", output_file);
+        return Ok(());
+    }
+    if !remove {
+        println!(
+            "{} duplicates in {}, pass --remove to delete them.",
+            duplicates.len(),
+            output_file
+        );
+        return Ok(());
+    }
+
+    for entry in entries
+        .iter()
+        .filter(|entry| duplicates.contains(&entry.index))
+    {
+        month::check_unlocked(config, entry, args.force)?;
+    }
+    let counted = [
+        ("count", duplicates.len().into()),
+        ("file", output_file.into()),
+    ];
+    if !args.yes && !confirm(config, &tr_args("dedupe-confirm", &counted))? {
+        println!("{}", tr("delete-cancelled"));
+        return Ok(());
+    }
+    pull_before_change(config);
+    let action = format!("dedupe of {} entries", duplicates.len());
+    UndoLog::in_data_dir()?.record(output_file, &action, || storage.delete_all(&duplicates))?;
+    println!("{}", tr_args("deduped", &counted));
+    Ok(())
+}
+
+/// Takes back the last change for `undo`.
+fn run_undo(context: &Context) -> Result<(), ITrackerError> {
+    let Context { args, config, .. } = *context;
+    let backups = Backups::from_config(config.backup.as_ref())?;
+    let step = UndoLog::in_data_dir()?.undo(&backups, args.force)?;
+    let time_format = TimeFormat::from_config(config.time_format.as_deref())?;
+    let undone = [
+        ("action", step.action.clone().into()),
+        ("file", step.file.display().to_string().into()),
+        (
+            "time",
+            time_format
+                .format(&step.time.with_timezone(&config.timezone()))
+                .into(),
+        ),
+    ];
+    println!("{}", tr_args("undone", &undone));
+    Ok(())
+}
+
+/// Lists the backups of the output file or restores one for `restore`.
+fn run_restore(
+    context: &Context,
+    backup: &Option<String>,
+    list: bool,
+) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let backups = Backups::from_config(config.backup.as_ref())?;
+    let available = backups.list(output_file)?;
+    if list {
+        for path in &available {
+            println!("{}", path.display());
+        }
+        return Ok(());
+    }
+
+    let chosen = match backup {
+        Some(name) => available
+            .iter()
+            .find(|path| path.as_os_str() == name.as_str() || path.ends_with(name)),
+        None => available.last(),
+    };
+    let Some(chosen) = chosen else {
+        return Err(ITrackerError::Backup(format!(
+            "No backup {}of {} found, see `restore --list`",
+            backup
+                .as_ref()
+                .map(|name| format!("{} ", name))
+                .unwrap_or_default(),
+            output_file
+        )));
+    };
 
+    let restored = [
+        ("file", output_file.into()),
+        ("backup", chosen.display().to_string().into()),
+    ];
+    if !args.yes && !confirm(config, &tr_args("restore-confirm", &restored))? {
+        println!("{}", tr("restore-cancelled"));
+        return Ok(());
+    }
+    backups.restore(output_file, chosen)?;
+    println!("{}", tr_args("restored", &restored));
+    Ok(())
+}
+
+/// Writes all data and the config file into one file for `dump`.
+fn run_dump(context: &Context, file: &Path) -> Result<(), ITrackerError> {
+    let Context { output_file, .. } = *context;
+    let count = dump::write(file, &config::data_dir()?, Path::new(&output_file))?;
+    println!("Wrote {} files to {}.", count, file.display());
+    Ok(())
+}
+
+/// Removes the local and remote backups the retention policy no longer keeps
+/// for `backup prune`.
+fn run_backup_prune(config: &ConfigData, dry_run: bool) -> Result<(), ITrackerError> {
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    let removed = Backups::from_config(config.backup.as_ref())?.prune(dry_run)?;
+    for path in &removed {
+        println!("{} {}", verb, path.display());
+    }
+    println!("{} {} local backups.", verb, removed.len());
+    if let Some(remote) = remote_backups(config)? {
+        let deleted = remote.prune(dry_run)?;
+        for snapshot in &deleted {
+            println!("{} {}{}", verb, remote.location(), snapshot.name);
+        }
+        println!("{} {} remote backups.", verb, deleted.len());
+    }
+    Ok(())
+}
+
+/// Uploads, lists and restores remote backups for `backup`.
+fn run_backup(context: &Context, action: &BackupAction) -> Result<(), ITrackerError> {
+    let Context {
+        args,
+        config,
+        output_file,
+    } = *context;
+    let remote = remote_backups(config)?.ok_or_else(|| {
+        ITrackerError::Backup(
+            "No remote backups configured, set `backup.remote.endpoint` and its bucket, credentials and key".to_string(),
+        )
+    })?;
+    let data_dir = config::data_dir()?;
+    let output = Path::new(&output_file);
+    match action {
+        BackupAction::Now => {
+            let snapshot = remote.upload(&data_dir, output)?;
+            println!(
+                "Uploaded {} ({} bytes) to {}.",
+                snapshot.name,
+                snapshot.size,
+                remote.location()
+            );
+            for old in remote.prune(false)? {
+                println!("Removed {}", old.name);
+            }
+        }
+        BackupAction::List => {
+            for snapshot in remote.list()? {
+                println!(
+                    "{}  {}  {} bytes",
+                    snapshot.name,
+                    snapshot.taken.to_rfc3339(),
+                    snapshot.size
+                );
+            }
+        }
+        BackupAction::Restore { name } => {
+            let snapshots = remote.list()?;
+            let chosen = match name {
+                Some(name) => snapshots.iter().find(|snapshot| &snapshot.name == name),
+                None => snapshots.last(),
+            };
+            let Some(chosen) = chosen else {
+                return Err(ITrackerError::Backup(format!(
+                    "No snapshot {}in {}, see `backup list`",
+                    name.as_ref()
+                        .map(|name| format!("{} ", name))
+                        .unwrap_or_default(),
+                    remote.location()
+                )));
+            };
+
+            let question = format!(
+                "Replace the files of {} and {} with snapshot {}?",
+                data_dir.display(),
+                output_file,
+                chosen.name
+            );
+            if !args.yes && !confirm(config, &question)? {
+                println!("{}", tr("restore-cancelled"));
+                return Ok(());
+            }
+            let backups = Backups::from_config(config.backup.as_ref())?;
+            let restored = remote.restore(&chosen.name, &data_dir, output, &backups)?;
+            for path in &restored {
+                println!("Restored {}", path.display());
+            }
+            println!(
+                "Restored {} files from {}; the replaced ones were backed up.",
+                restored.len(),
+                chosen.name
+            );
+        }
+        BackupAction::Prune { .. } => {}
+    }
     Ok(())
 }
 
@@ -68,133 +1874,906 @@ fn parse_args() -> Args {
     })
 }
 
-fn manage_config(args: &Args) -> Result<String, Box<dyn std::error::Error>> {
-    let mut config = load_config()?;
+/// Asks before a destructive action; refuses when there is no terminal to ask on.
+fn remote_backups(config: &ConfigData) -> Result<Option<RemoteBackups>, ITrackerError> {
+    RemoteBackups::from_config(config.backup.as_ref().and_then(|b| b.remote.as_ref()))
+}
 
-    let output_file = if let Some(ref file) = args.output_file {
-        let file_str = file.to_string_lossy().into_owned();
-        config.output_file = Some(file_str.clone());
-        save_config(&config)?;
-        file_str
+/// Hands the output file to a sync plugin and adds the entries it sends back.
+fn sync_with_plugin(
+    plugin: &Plugin,
+    output_file: &str,
+    options: &BTreeMap<String, String>,
+) -> Result<(), ITrackerError> {
+    let entries = read_logs_from_file(output_file)?;
+    let synced = plugin.sync(output_file, &entries, options)?;
+    if let Some(message) = &synced.message {
+        println!("{}: {}", plugin.name, message);
+    }
+    let added = plugin::add_entries(output_file, &synced.entries, &Progress::hidden())?;
+    report_failed(&added);
+    println!(
+        "Synced {} with {}, {} entries added.",
+        output_file, plugin.name, added.created
+    );
+    Ok(())
+}
+
+/// Prints the entries of a plugin that could not be added, on stderr.
+fn report_failed(added: &Added) {
+    for (position, reason) in &added.failed {
+        eprintln!("Entry {} left out: {}", position, reason);
+    }
+}
+
+/// The times of `--at` and `--for`, read once before any timer changes.
+#[derive(Debug, Clone, Copy, Default)]
+struct Given {
+    /// When the timer stops with `--stop`.
+    at: Option<DateTime<Utc>>,
+    /// When a new entry starts: `--at`, or `--for` before now; `None` for now.
+    start: Option<DateTime<Utc>>,
+    length: Option<chrono::Duration>,
+}
+
+impl Given {
+    fn of(args: &Args, config: &ConfigData) -> Result<Self, ITrackerError> {
+        if args.at.is_some() && !args.add && args.stop.is_none() {
+            return Err(ITrackerError::Config(
+                "--at needs --add or --stop, the timer it starts or stops".to_string(),
+            ));
+        }
+        let now = Utc::now();
+        let at = args
+            .at
+            .as_deref()
+            .map(|at| timeparse::moment(at, now, config.timezone()))
+            .transpose()?;
+        let length = args
+            .length
+            .as_deref()
+            .map(timeparse::duration)
+            .transpose()?;
+        if length.is_some_and(|length| length <= chrono::Duration::zero()) {
+            return Err(ITrackerError::Config(
+                "--for needs a duration longer than zero".to_string(),
+            ));
+        }
+        let start = at.or(length.map(|length| now - length));
+        let end = match (start, length) {
+            (Some(start), Some(length)) => start + length,
+            _ => at.unwrap_or(now),
+        };
+        if end > now {
+            return Err(ITrackerError::Config(format!(
+                "{} lies in the future, timers only start and stop in the past",
+                end.with_timezone(&config.timezone()).to_rfc2822()
+            )));
+        }
+        Ok(Given { at, start, length })
+    }
+}
+
+/// Deletes the entries of `log_file` that match `filter` for
+/// `--delete-log` without an index, after showing them and asking.
+fn delete_matching(
+    args: &Args,
+    config: &ConfigData,
+    log_file: &str,
+    filter: &EntryFilter,
+) -> Result<(), ITrackerError> {
+    if filter.is_empty() {
+        return Err(ITrackerError::Config(
+            "Give the index of the entry to delete, or --before, --tag or --project to delete the entries matching them".to_string(),
+        ));
+    }
+    let storage = storage(config, log_file)?;
+    let matching: Vec<LogEntry> = storage
+        .entries()?
+        .into_iter()
+        .filter(|entry| filter.matches(entry))
+        .collect();
+    let file = ("file", log_file.into());
+    if matching.is_empty() {
+        println!("{}", tr_args("delete-none", &[file]));
+        return Ok(());
+    }
+    for entry in &matching {
+        month::check_unlocked(config, entry, args.force)?;
+    }
+
+    // The preview shows the first and last entries of long lists
+    const PREVIEW: usize = 10;
+    let time_format = TimeFormat::from_config(config.time_format.as_deref())?;
+    let tz = config.timezone();
+    let counted = [("count", matching.len().into()), file];
+    println!("{}", tr_args("delete-matching", &counted));
+    let hidden = if matching.len() > PREVIEW {
+        PREVIEW / 2..matching.len() - PREVIEW / 2
     } else {
-        config
-            .output_file
-            .clone()
-            .unwrap_or_else(|| String::from("default_output.txt"))
+        0..0
     };
+    for (position, entry) in matching.iter().enumerate() {
+        if position == hidden.start && !hidden.is_empty() {
+            let count = [("count", hidden.len().into())];
+            println!("  {}", tr_args("delete-hidden", &count));
+        }
+        if hidden.contains(&position) {
+            continue;
+        }
+        println!(
+            "  {:>5}  {}  {}",
+            entry.index,
+            time_format.format_stored(&entry.start_time, tz),
+            entry.display_title()
+        );
+    }
+    if !args.yes && !confirm(config, &tr_args("delete-matching-confirm", &counted))? {
+        println!("{}", tr("delete-cancelled"));
+        return Ok(());
+    }
+    pull_before_change(config);
+    let indices: Vec<usize> = matching.iter().map(|entry| entry.index).collect();
+    let action = format!("delete of {} entries", indices.len());
+    UndoLog::in_data_dir()?.record(log_file, &action, || storage.delete_all(&indices))?;
+    println!("{}", tr_args("deleted-matching", &counted));
+    Ok(())
+}
 
-    Ok(output_file)
+/// The day of the start of `entry`, which times of day given for it fall on.
+fn entry_day(entry: &LogEntry, now: DateTime<Utc>, tz: Tz) -> NaiveDate {
+    entry
+        .start()
+        .unwrap_or_else(|| now.fixed_offset())
+        .with_timezone(&tz)
+        .date_naive()
 }
 
-fn handle_commands(args: Args, data: String, output_file: &str) -> Result<(), std::io::Error> {
-    let mut timer = Timer::new();
+/// How `list` and the viewer show start times: relative to now, or as
+/// configured with `--absolute`.
+fn shown_times(args: &Args, config: &ConfigData) -> Result<TimeFormat, ITrackerError> {
+    if args.absolute {
+        return TimeFormat::from_config(config.time_format.as_deref());
+    }
+    Ok(TimeFormat::Relative)
+}
 
-    if args.add {
-        let log_index = start_timer(&mut timer, &data, output_file)?;
-        println!("Timer started for log entry at index {}.", log_index);
+/// The template of `--template` for `status` and `list`, inline or a
+/// preset of `[templates]`; `None` for the usual output.
+fn shown_template(args: &Args, config: &ConfigData) -> Result<Option<Template>, ITrackerError> {
+    args.template
+        .as_deref()
+        .map(|value| Template::resolve(value, &config.templates))
+        .transpose()
+}
+
+/// How read commands lay out their output.
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    /// Lines aligned with spaces, for pipes and `--no-table`.
+    Plain,
+    /// Tables with headers, fitted into the terminal's width if known.
+    Table(Option<usize>),
+}
+
+impl Layout {
+    fn of(args: &Args) -> Self {
+        if args.no_table || !io::stdout().is_terminal() {
+            return Layout::Plain;
+        }
+        Layout::Table(
+            crossterm::terminal::size()
+                .ok()
+                .map(|(width, _)| width as usize),
+        )
     }
+}
 
-    if args.pause {
-        timer.pause(output_file, 1)?;
+/// Prints the time tracked per day and project, each day with its progress
+/// toward the daily goal and its overtime if those are set, the amount each
+/// project with a rate or expenses earned, and the weekly budgets used.
+fn print_report(
+    summary: &Summary,
+    (from, to): (NaiveDate, NaiveDate),
+    (goal, workday): (Option<u64>, Option<u64>),
+    budgets: &[(NaiveDate, Vec<Usage>)],
+    earnings: &Earnings,
+    durations: DurationFormat,
+    layout: Layout,
+) {
+    if from == to {
+        let day = [("day", locale::date(from).into())];
+        println!("{}", paint(tr_args("report-day", &day), Paint::Heading));
+    } else {
+        let range = [
+            ("from", locale::date(from).into()),
+            ("to", locale::date(to).into()),
+        ];
+        println!("{}", paint(tr_args("report-range", &range), Paint::Heading));
     }
+    // Days without entries still count toward the goal
+    let days: Vec<(NaiveDate, u64, Option<Goal>, u64)> = from
+        .iter_days()
+        .take_while(|day| *day <= to)
+        .map(|day| {
+            let tracked = summary.per_day.get(&day).copied().unwrap_or(0);
+            let extra = workday.map_or(0, |workday| tracked.saturating_sub(workday));
+            let goal = goal.map(|target| Goal { tracked, target });
+            (day, tracked, goal, extra)
+        })
+        .filter(|(_, tracked, _, _)| *tracked > 0 || goal.is_some())
+        .collect();
+    let overtime: u64 = days.iter().map(|(_, _, _, extra)| extra).sum();
+    let overtime_days = days.iter().filter(|(_, _, _, extra)| *extra > 0).count();
+    // Projects with expenses but no time tracked are listed as well
+    let mut projects: BTreeMap<&String, u64> = earnings.expenses.keys().map(|p| (p, 0)).collect();
+    projects.extend(summary.per_project.iter().map(|(p, seconds)| (p, *seconds)));
+    let amount = |project: &String| {
+        earnings
+            .per_project
+            .get(project)
+            .map(
+                |(amount, converted)| match converted.as_ref().filter(|c| *c != amount) {
+                    Some(converted) => format!("{} ({})", amount, converted),
+                    None => amount.to_string(),
+                },
+            )
+    };
+    let width = projects
+        .keys()
+        .copied()
+        .chain(
+            budgets
+                .iter()
+                .flat_map(|(_, usage)| usage.iter().map(|u| &u.project)),
+        )
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(locale::date(to).chars().count());
+    let progress = |goal: Goal| {
+        format!(
+            "{} {}% of {}",
+            goal.bar(report::GOAL_BAR_WIDTH),
+            goal.percent(),
+            durations.format(goal.target)
+        )
+    };
 
-    if args.resume {
-        timer.resume(output_file, 0)?;
-        let elapsed_time = timer.get_elapsed_time(output_file, 1)?;
-        println!("Timer paused. Total elapsed time: {:?}", elapsed_time);
+    if let Layout::Table(width) = layout {
+        let mut table = Table::new()
+            .column(tr("table-day"), Align::Left)
+            .column(tr("table-tracked"), Align::Right);
+        if goal.is_some() {
+            table = table.column(tr("table-goal"), Align::Left);
+        }
+        if workday.is_some() {
+            table = table.column(tr("report-overtime"), Align::Right);
+        }
+        for (day, tracked, goal, extra) in &days {
+            let mut cells = vec![locale::date(*day), durations.format(*tracked)];
+            cells.extend(goal.map(progress));
+            if workday.is_some() {
+                cells.push(match extra {
+                    0 => String::new(),
+                    extra => format!("+{}", durations.format(*extra)),
+                });
+            }
+            match tracked {
+                0 => table.painted_row(cells, Paint::Old),
+                _ => table.row(cells),
+            }
+        }
+        if !table.is_empty() {
+            print!("{}", table.render(width));
+        }
+        let mut table = Table::new()
+            .shrinking(tr("table-project"))
+            .column(tr("table-tracked"), Align::Right)
+            .column(tr("table-amount"), Align::Right)
+            .column(tr("table-expenses"), Align::Right);
+        for (project, seconds) in &projects {
+            table.row(vec![
+                project.to_string(),
+                durations.format(*seconds),
+                amount(project).unwrap_or_default(),
+                earnings
+                    .expenses
+                    .get(*project)
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+            ]);
+        }
+        if !table.is_empty() {
+            print!("{}", table.render(width));
+        }
+        for (week, usage) in budgets {
+            let week = [("week", locale::date(*week).into())];
+            let mut table = Table::new()
+                .shrinking(tr_args("report-budgets", &week))
+                .column(tr("table-used"), Align::Right)
+                .column(tr("table-budget"), Align::Left);
+            for usage in usage {
+                let cells = vec![
+                    usage.project.clone(),
+                    durations.format(usage.used),
+                    progress(usage.progress()),
+                ];
+                if usage.used > usage.budget {
+                    table.painted_row(cells, Paint::Exceeded);
+                } else {
+                    table.row(cells);
+                }
+            }
+            print!("{}", table.render(width));
+        }
+    } else {
+        for (day, tracked, goal, extra) in &days {
+            let mut line = format!(
+                "  {:<width$}  {:>10}",
+                locale::date(*day),
+                durations.format(*tracked),
+                width = width
+            );
+            if let Some(goal) = goal {
+                line.push_str(&format!("  {}", progress(*goal)));
+            }
+            if *extra > 0 {
+                line.push_str(&format!("  +{} overtime", durations.format(*extra)));
+            }
+            if *tracked == 0 {
+                line = paint(line, Paint::Old);
+            }
+            println!("{}", line);
+        }
+        if !projects.is_empty() {
+            println!("{}", paint(tr("report-per-project"), Paint::Heading));
+        }
+        for (project, seconds) in &projects {
+            let mut line = format!(
+                "  {:<width$}  {:>10}",
+                project,
+                durations.format(*seconds),
+                width = width
+            );
+            if let Some((amount, converted)) = earnings.per_project.get(*project) {
+                line.push_str(&format!("  {:>10}", amount.to_string()));
+                if let Some(converted) = converted.as_ref().filter(|c| *c != amount) {
+                    line.push_str(&format!(" ({})", converted));
+                }
+            }
+            if let Some(expenses) = earnings.expenses.get(*project) {
+                let amount = [("amount", expenses.to_string().into())];
+                line.push_str(&format!(" {}", tr_args("report-expenses", &amount)));
+            }
+            println!("{}", line);
+        }
+        for (week, usage) in budgets {
+            let week = [("week", locale::date(*week).into())];
+            println!(
+                "{}",
+                paint(tr_args("report-budgets", &week), Paint::Heading)
+            );
+            for usage in usage {
+                let line = format!(
+                    "  {:<width$}  {:>10}  {}",
+                    usage.project,
+                    durations.format(usage.used),
+                    progress(usage.progress()),
+                    width = width
+                );
+                if usage.used > usage.budget {
+                    println!("{}", paint(line, Paint::Exceeded));
+                } else {
+                    println!("{}", line);
+                }
+            }
+        }
     }
 
-    if args.stop.is_some() {
-        let index = args
-            .stop
-            .unwrap_or_else(|| get_last_index_from_csv(output_file).unwrap_or(0));
-        stop_timer(&mut timer, output_file, index)?;
+    let mut totals = Vec::new();
+    if let Some(total) = &earnings.total {
+        totals.push((
+            tr("report-billable"),
+            String::new(),
+            format!("{:>10}", total),
+        ));
     }
+    if overtime > 0 {
+        totals.push((
+            tr("report-overtime"),
+            durations.format(overtime),
+            tr_args("report-overtime-days", &[("days", overtime_days.into())]),
+        ));
+    }
+    totals.push((
+        tr("report-total"),
+        durations.format(summary.total),
+        tr_args("report-entries", &[("entries", summary.entries.into())]),
+    ));
+    let last = totals.len() - 1;
+    match layout {
+        Layout::Plain => {
+            // The totals line up below the durations of the days and projects
+            for (i, (label, tracked, rest)) in totals.into_iter().enumerate() {
+                let line = format!(
+                    "{:<width$}  {:>10}  {}",
+                    label,
+                    tracked,
+                    rest,
+                    width = width + 2
+                );
+                match i == last {
+                    true => println!("{}", paint(line, Paint::Heading)),
+                    false => println!("{}", line),
+                }
+            }
+        }
+        Layout::Table(width) => {
+            let mut table = Table::new()
+                .column("", Align::Left)
+                .column("", Align::Right)
+                .column("", Align::Left);
+            for (i, (label, tracked, rest)) in totals.into_iter().enumerate() {
+                let cells = vec![label, tracked, rest.trim().to_string()];
+                match i == last {
+                    true => table.painted_row(cells, Paint::Heading),
+                    false => table.row(cells),
+                }
+            }
+            print!("{}", table.render(width));
+        }
+    }
+}
 
+/// Pushes the entries of the output file that are not in Toggl yet, storing
+/// the ID of each in its metadata as soon as it was created.
+fn push_to_toggl(
+    toggl: &Toggl,
+    config: &ConfigData,
+    output_file: &str,
+) -> Result<(), ITrackerError> {
+    let storage = storage(config, output_file)?;
+    let count = toggl.push(&storage.entries()?, |entry, id| {
+        let mut entry = entry.clone();
+        entry
+            .metadata
+            .insert(toggl::METADATA_KEY.to_string(), id.to_string());
+        storage.update(&entry)
+    })?;
+    println!("Pushed {} entries to {}.", count, toggl.api_url());
     Ok(())
 }
 
-fn start_timer(timer: &mut Timer, data: &str, output_file: &str) -> Result<usize, std::io::Error> {
-    // Log the task and return the index of the log entry
-    timer.log_task(data, output_file)?;
+/// Pushes the entries of the output file that are not in Clockify yet,
+/// storing the ID of each in its metadata as soon as it was created.
+fn push_to_clockify(
+    clockify: &Clockify,
+    config: &ConfigData,
+    output_file: &str,
+) -> Result<(), ITrackerError> {
+    let storage = storage(config, output_file)?;
+    let count = clockify.push(&storage.entries()?, |entry, id| {
+        let mut entry = entry.clone();
+        entry
+            .metadata
+            .insert(clockify::METADATA_KEY.to_string(), id.to_string());
+        storage.update(&entry)
+    })?;
+    println!("Pushed {} entries to {}.", count, clockify.api_url());
+    Ok(())
+}
+
+/// Prints the burn-down of a retainer: the time used and left after each day
+/// with entries, and a warning from 80% on.
+fn print_retainer(retainer: &Retainer, durations: DurationFormat) {
+    let heading = [
+        ("project", retainer.project.clone().into()),
+        ("month", locale::month(retainer.month).into()),
+        ("hours", durations.format(retainer.budget).into()),
+    ];
+    println!(
+        "{}",
+        paint(tr_args("report-retainer", &heading), Paint::Heading)
+    );
+    for (day, used, left) in retainer.burn_down() {
+        let progress = Goal {
+            tracked: used,
+            target: retainer.budget,
+        };
+        println!(
+            "  {}  {:>10}  {:>15}  {} {}%",
+            locale::date(day),
+            durations.format(used),
+            tr_args(
+                "report-left",
+                &[("duration", durations.format(left).into())]
+            ),
+            progress.bar(report::GOAL_BAR_WIDTH),
+            progress.percent()
+        );
+    }
+    if let Some(warning) = retainer.warning(durations) {
+        println!("{}", paint(warning, Paint::Exceeded));
+    }
+}
 
-    // Calculate the log index based on the CSV file contents
-    let log_index = {
-        let mut reader = csv::Reader::from_reader(File::open(output_file)?);
-        reader.records().count()
+/// Prints the time tracked by each user and on each project of a team.
+fn print_team_report(report: &TeamReport, durations: DurationFormat) {
+    println!("Team report {} to {}", report.from, report.to);
+    let width = report
+        .users
+        .keys()
+        .chain(report.per_project.keys())
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Total".len());
+    for (user, summary) in &report.users {
+        println!(
+            "  {:<width$}  {:>10}  {} entries",
+            user,
+            durations.format(summary.total),
+            summary.entries,
+            width = width
+        );
+    }
+    if !report.per_project.is_empty() {
+        println!("Per project");
+        for (project, seconds) in &report.per_project {
+            println!(
+                "  {:<width$}  {:>10}",
+                project,
+                durations.format(*seconds),
+                width = width
+            );
+        }
+    }
+    println!(
+        "{:<width$}  {:>10}",
+        "Total",
+        durations.format(report.total),
+        width = width + 2
+    );
+}
+
+/// Asks which side of a merge conflict to keep.
+fn resolve_conflict(
+    config: &ConfigData,
+    conflict: &Conflict,
+    file: &str,
+    other: &str,
+    durations: DurationFormat,
+) -> Result<Option<Resolution>, ITrackerError> {
+    let describe = |record: &Record, path: &str| {
+        let entry = LogEntry::from(record.clone());
+        let seconds = entry.elapsed_time.trim().parse::<u64>().unwrap_or(0);
+        format!(
+            "{} entry {}: {} at {}, {}",
+            path,
+            entry.index,
+            entry.display_title(),
+            entry.start_time,
+            if entry.is_running() {
+                "running".to_string()
+            } else {
+                durations.format(seconds)
+            }
+        )
     };
+    let problem = match conflict.kind {
+        ConflictKind::Changed => "The same entry differs in both files:",
+        ConflictKind::Overlap => "Entries of both files overlap:",
+    };
+    let lines = vec![
+        problem.to_string(),
+        describe(&conflict.ours, file),
+        describe(&conflict.theirs, other),
+    ];
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(ITrackerError::NotConfirmed(format!(
+            "{} Refusing to guess without a terminal, pass --prefer ours, theirs or both",
+            lines.join(" ")
+        )));
+    }
 
-    Ok(log_index)
+    let options = vec![
+        ('o', format!("keep {}", file)),
+        ('t', format!("keep {}", other)),
+        ('b', "keep both".to_string()),
+    ];
+    let theme = Theme::from_config(config.theme.as_ref())?;
+    let chosen = tui::choose("Merge conflict", lines, options, &theme)?;
+    Ok(chosen.map(|position| [Resolution::Ours, Resolution::Theirs, Resolution::Both][position]))
 }
 
-fn stop_timer(timer: &mut Timer, output_file: &str, index: usize) -> Result<(), std::io::Error> {
-    let stopped_time = SystemTime::now();
-    let (start_time, paused_duration) =
-        read_start_time_and_paused_duration_from_csv(output_file, index)?;
+fn confirm(config: &ConfigData, question: &str) -> Result<bool, ITrackerError> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(ITrackerError::NotConfirmed(format!(
+            "{} Refusing without confirmation, pass --yes to skip it",
+            question
+        )));
+    }
 
-    let elapsed_time = stopped_time.duration_since(start_time).unwrap_or_default();
+    let theme = Theme::from_config(config.theme.as_ref())?;
+    Ok(tui::confirm(question, &theme)?)
+}
 
-    timer.update_log_entry_with_elapsed_time(output_file, index, elapsed_time, paused_duration)?;
+fn manage_config(args: &Args, config: &mut ConfigData) -> Result<String, ITrackerError> {
+    let output_file = if let Some(ref file) = args.output_file {
+        let file_str = file.to_string_lossy().into_owned();
+        config.set_output_file(file_str.clone());
+        save_config(config)?;
+        file_str
+    } else {
+        match config.output_file() {
+            Some(file) => file.to_string(),
+            None => default_data_file("default_output.txt")?,
+        }
+    };
 
-    println!(
-        "Timer stopped at {:?}. Elapsed time: {:?}, Total paused time: {:?}",
-        stopped_time,
-        elapsed_time.as_secs(),
-        paused_duration.as_secs()
-    );
+    Ok(output_file)
+}
 
-    Ok(())
+/// The log file at `path`, backed up before rewrites as configured, and
+/// parsed at most once per run.
+fn storage(config: &ConfigData, path: &str) -> Result<Storage, ITrackerError> {
+    Ok(Storage::new(path)
+        .with_backups(Backups::from_config(config.backup.as_ref())?)
+        .with_cache())
 }
 
-fn read_start_time_and_paused_duration_from_csv(
-    output_file: &str,
-    index: usize,
-) -> Result<(SystemTime, Duration), std::io::Error> {
-    let file = OpenOptions::new().read(true).open(output_file)?;
-    let mut reader = ReaderBuilder::new().from_reader(BufReader::new(file));
-
-    for result in reader.records() {
-        let record = result?;
-        if record.len() >= 5 {
-            if let Ok(record_index) = record[0].parse::<usize>() {
-                if record_index == index {
-                    if let Ok(start_time) = DateTime::parse_from_rfc2822(&record[1]) {
-                        let paused_duration = record[4].parse::<u64>().unwrap_or_default();
-                        return Ok((
-                            start_time.with_timezone(&Utc).into(),
-                            Duration::from_secs(paused_duration),
-                        ));
-                    } else {
-                        return Err(Error::new(
-                            ErrorKind::InvalidData,
-                            "Invalid start time format in CSV",
-                        ));
-                    }
-                }
+/// Timers on the log file at `path`, running the configured hooks.
+fn tracker(config: &ConfigData, path: &str) -> Result<Tracker, ITrackerError> {
+    Ok(Tracker::new(storage(config, path)?).with_hooks(Hooks::from_config(config.hooks.as_ref())))
+}
+
+/// Brings a synced data directory up to date before a log file is changed.
+///
+/// Failures only warn, so time can still be tracked offline or with
+/// conflicts that `sync` will report again.
+fn pull_before_change(config: &ConfigData) {
+    let pulled = match GitSync::from_config(config.sync.as_ref()) {
+        Ok(Some(sync)) if sync.pulls_before_changes() => sync.pull(),
+        Ok(_) => return,
+        Err(e) => Err(e),
+    };
+    match pulled {
+        Ok(pulled) => {
+            for (file, old, new) in pulled.renumbered {
+                println!("Log entry {} of {} is now {}.", old, file, new);
             }
         }
+        Err(e) => eprintln!("Warning: not synced: {}", e),
     }
+}
+
+/// Path of a file in the data directory, used when no file is given.
+fn default_data_file(name: &str) -> Result<String, ITrackerError> {
+    Ok(config::data_dir()?
+        .join(name)
+        .to_string_lossy()
+        .into_owned())
+}
 
-    Err(Error::new(
-        ErrorKind::NotFound,
-        "No valid start time or paused duration found for the specified index in CSV",
-    ))
+/// How `--resume` settles the idle time of an entry.
+enum IdlePrompt {
+    /// Not paused by idle detection, or no answer to ask for: a plain resume.
+    Plain,
+    Chosen(IdleChoice),
+    Cancelled,
 }
 
-fn get_last_index_from_csv(output_file: &str) -> Result<usize, std::io::Error> {
-    let file = OpenOptions::new().read(true).open(output_file)?;
-    let mut reader = ReaderBuilder::new().from_reader(BufReader::new(file));
+/// Settles the idle time of the entry `--resume` applies to, if idle
+/// detection paused it: as given with `--idle` and `--idle-task`, or as
+/// answered in a dialog. Without a terminal the idle time stays paused.
+fn idle_choice(
+    args: &Args,
+    index: Option<usize>,
+    timers: &Timers,
+    config: &ConfigData,
+) -> Result<IdlePrompt, ITrackerError> {
+    let entry = match index {
+        Some(index) => Some(timers.tracker().storage().entry(index)?),
+        None => timers.tracker().running()?,
+    };
+    let Some((entry, since)) = entry
+        .filter(|entry| entry.metadata.contains_key(idle::METADATA_KEY))
+        .and_then(|entry| entry.paused_since().map(|since| (entry, since)))
+    else {
+        return Ok(IdlePrompt::Plain);
+    };
+    let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+    let theme = Theme::from_config(config.theme.as_ref())?;
 
-    let mut last_index: Option<usize> = None;
+    let answer = match (args.idle, &args.idle_task) {
+        (Some(answer), _) => answer,
+        (None, Some(_)) => IdleAnswer::Move,
+        (None, None) if !interactive => return Ok(IdlePrompt::Plain),
+        (None, None) => {
+            let idle = (Utc::now() - since.with_timezone(&Utc))
+                .num_seconds()
+                .max(0) as u64;
+            let lines = vec![format!(
+                "{} was paused {} ago, when you went idle. What was the time since?",
+                entry.display_title(),
+                config.duration_format().format(idle)
+            )];
+            let options = vec![
+                ('k', "work on it: keep it".to_string()),
+                ('d', "a break: discard it".to_string()),
+                ('m', "another task: move it".to_string()),
+            ];
+            match tui::choose("Idle time", lines, options, &theme)? {
+                Some(position) => {
+                    [IdleAnswer::Keep, IdleAnswer::Discard, IdleAnswer::Move][position]
+                }
+                None => return Ok(IdlePrompt::Cancelled),
+            }
+        }
+    };
+    let choice = match answer {
+        IdleAnswer::Keep => IdleChoice::Keep,
+        IdleAnswer::Discard => IdleChoice::Discard,
+        IdleAnswer::Move => {
+            let task = match &args.idle_task {
+                Some(title) => Some(TaskDetails {
+                    title: title.clone(),
+                    ..TaskDetails::default()
+                }),
+                None if interactive => {
+                    let known = timers.tracker().storage().entries().unwrap_or_default();
+                    let suggested = suggest::likely_tasks(
+                        &known,
+                        since.with_timezone(&Utc),
+                        config.timezone(),
+                        suggest::MAX_TASKS,
+                    );
+                    tui::input_task(&theme, &known, &TaskDetails::default(), suggested)?
+                }
+                None => {
+                    return Err(ITrackerError::NotConfirmed(
+                        "Moving the idle time needs a task, pass --idle-task".to_string(),
+                    ))
+                }
+            };
+            match task {
+                Some(task) => IdleChoice::Move { task },
+                None => return Ok(IdlePrompt::Cancelled),
+            }
+        }
+    };
+    Ok(IdlePrompt::Chosen(choice))
+}
 
-    for result in reader.records() {
-        let record = result?;
-        if let Some(index_str) = record.get(0) {
-            if let Ok(index) = index_str.parse::<usize>() {
-                last_index = Some(index);
+fn handle_commands(
+    args: Args,
+    task: Option<TaskDetails>,
+    tracker: Tracker,
+    config: &ConfigData,
+    given: Given,
+) -> Result<(), ITrackerError> {
+    let time_format = TimeFormat::from_config(config.time_format.as_deref())?;
+    let (durations, tz) = (config.duration_format(), config.timezone());
+    let (budgets, billing) = (config.budgets()?, config.billing()?);
+    // With a daemon running, it applies the operations so they cannot race with other invocations
+    let timers = Timers::new(tracker)?;
+    // Budgets and retainers only warn; the timer changed either way
+    let check_budget = |entry: &LogEntry| {
+        if budgets.is_empty() && billing.retainer_for(&entry.project).is_none() {
+            return;
+        }
+        let Ok(entries) = timers.tracker().storage().entries() else {
+            return;
+        };
+        let now = Utc::now();
+        let today = report::today(now, tz);
+        let usage = budgets.usage(&entries, &entry.project, today, now, tz);
+        if let Some(usage) = usage.filter(Usage::is_exhausted) {
+            eprintln!("{}", usage.warning(durations));
+        }
+        let retainer = Retainer::of(&entries, &entry.project, today, &billing, now, tz);
+        if let Some(warning) = retainer.and_then(|retainer| retainer.warning(durations)) {
+            eprintln!("{}", warning);
+        }
+    };
+
+    if args.add {
+        match task {
+            Some(task) => {
+                if let Some(at) = given.start {
+                    month::ensure_open(config, at)?;
+                }
+                let entry = match given.start {
+                    Some(at) => timers.run(Operation::StartAt { task, at })?,
+                    None => timers.run(Operation::Start { task })?,
+                };
+                match given.length {
+                    Some(length) => {
+                        let at = entry
+                            .start()
+                            .map(|start| start.with_timezone(&Utc))
+                            .unwrap_or_default()
+                            + length;
+                        let entry = timers.run(Operation::StopAt {
+                            index: entry.index,
+                            at,
+                        })?;
+                        let logged = [
+                            ("index", entry.index.into()),
+                            (
+                                "elapsed",
+                                durations.format(length.num_seconds().unsigned_abs()).into(),
+                            ),
+                        ];
+                        println!("{}", tr_args("timer-logged", &logged));
+                    }
+                    None => println!(
+                        "{}",
+                        tr_args("timer-started", &[("index", entry.index.into())])
+                    ),
+                }
+                check_budget(&entry);
             }
+            None => println!("{}", tr("timer-not-started")),
         }
     }
 
-    last_index.ok_or_else(|| Error::new(ErrorKind::Other, "No valid index found in CSV"))
+    if let Some(index) = args.pause {
+        let entry = timers.run(Operation::Pause { index })?;
+        println!(
+            "{}",
+            tr_args("timer-paused", &[("index", entry.index.into())])
+        );
+    }
+
+    if let Some(index) = args.resume {
+        let operation = match idle_choice(&args, index, &timers, config)? {
+            IdlePrompt::Plain => Operation::Resume { index },
+            IdlePrompt::Chosen(choice) => Operation::ResumeIdle { index, choice },
+            IdlePrompt::Cancelled => {
+                println!("{}", tr("timer-not-resumed"));
+                return Ok(());
+            }
+        };
+        let entry = timers.run(operation)?;
+        let paused = durations.format(entry.paused_time.trim().parse().unwrap_or(0));
+        let resumed = [("index", entry.index.into()), ("paused", paused.into())];
+        println!("{}", tr_args("timer-resumed", &resumed));
+    }
+
+    if let Some(index) = args.stop {
+        let at = given.at.unwrap_or_else(Utc::now);
+        let log_file = timers.tracker().storage().path().to_string();
+        let action = format!("stop of entry {}", index);
+        let entry =
+            UndoLog::in_data_dir()?.record_entries(
+                &log_file,
+                &action,
+                &[index],
+                || match given.at {
+                    Some(at) => timers.run(Operation::StopAt { index, at }),
+                    None => timers.run(Operation::Stop { index: Some(index) }),
+                },
+            )?;
+        let stopped = [
+            ("time", time_format.format(&at.with_timezone(&tz)).into()),
+            (
+                "elapsed",
+                durations
+                    .format(entry.elapsed_time.trim().parse().unwrap_or(0))
+                    .into(),
+            ),
+            (
+                "paused",
+                durations
+                    .format(entry.paused_time.trim().parse().unwrap_or(0))
+                    .into(),
+            ),
+        ];
+        println!("{}", tr_args("timer-stopped", &stopped));
+        check_budget(&entry);
+    }
+
+    Ok(())
 }
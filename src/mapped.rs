@@ -0,0 +1,276 @@
+//! Reading large log files through a memory map, for reports over years of
+//! history.
+//!
+//! The log is mapped instead of copied into a buffer, and each row is parsed
+//! into a [`RowRef`] whose text borrows from one record reused for all
+//! rows, so rows a report does not need are skipped without allocating.
+//! Only the rows kept become [`LogEntry`] values. Amendments in the journal
+//! apply like in [`schema::read`]. Encrypted logs cannot be parsed in place
+//! and are left to [`schema::read`].
+//!
+//! [`MappedLog::entries_started`] splits the rows into chunks and parses
+//! them on the threads of the current rayon pool, which reports size with
+//! `report_threads` of the configuration.
+
+use crate::{
+    crypto,
+    error::ITrackerError,
+    log::{self, LogEntry},
+    schema::{self, Record},
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+/// A row of the log as stored, borrowing its text from the file; the
+/// fields mirror [`Record`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct RowRef<'a> {
+    pub index: usize,
+    pub start_time: &'a str,
+    #[serde(default)]
+    pub message: &'a str,
+    #[serde(default)]
+    pub elapsed_time: &'a str,
+    #[serde(default = "zero")]
+    pub paused_time: &'a str,
+    #[serde(default)]
+    pub title: &'a str,
+    #[serde(default)]
+    pub project: &'a str,
+    #[serde(default)]
+    pub tags: &'a str,
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    pub estimate: Option<u32>,
+    #[serde(default)]
+    pub paused_since: &'a str,
+    #[serde(default)]
+    pub metadata: &'a str,
+}
+
+fn zero<'a>() -> &'a str {
+    "0"
+}
+
+impl<'a> RowRef<'a> {
+    /// Parses the start time, `None` if it is malformed.
+    pub fn start(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc2822(self.start_time.trim()).ok()
+    }
+
+    /// Copies the row out of the file.
+    pub fn to_entry(&self) -> LogEntry {
+        LogEntry {
+            index: self.index,
+            start_time: self.start_time.to_string(),
+            message: self.message.to_string(),
+            elapsed_time: self.elapsed_time.to_string(),
+            paused_time: self.paused_time.to_string(),
+            title: self.title.to_string(),
+            project: self.project.to_string(),
+            tags: log::parse_tags(self.tags),
+            estimate: self.estimate,
+            paused_since: self.paused_since.to_string(),
+            metadata: log::parse_metadata(self.metadata),
+        }
+    }
+}
+
+impl<'a> From<&'a Record> for RowRef<'a> {
+    fn from(record: &'a Record) -> Self {
+        RowRef {
+            index: record.index,
+            start_time: &record.start_time,
+            message: &record.message,
+            elapsed_time: &record.elapsed_time,
+            paused_time: &record.paused_time,
+            title: &record.title,
+            project: &record.project,
+            tags: &record.tags,
+            estimate: record.estimate,
+            paused_since: &record.paused_since,
+            metadata: &record.metadata,
+        }
+    }
+}
+
+/// A log file mapped into memory, with the amendments of its journal.
+#[derive(Debug)]
+pub struct MappedLog {
+    path: PathBuf,
+    /// `None` for an empty file, which cannot be mapped.
+    map: Option<Mmap>,
+    /// The latest amendment of each entry, by index.
+    amended: HashMap<usize, Record>,
+}
+
+impl MappedLog {
+    /// Maps the log file at `path`.
+    ///
+    /// # Returns
+    /// - `Ok(None)`: If the log is encrypted, so callers read it with
+    ///   [`schema::read`].
+    /// - `Err(ITrackerError)`: If the log or its journal cannot be read.
+    pub fn open(path: impl AsRef<Path>) -> Result<Option<Self>, ITrackerError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(ITrackerError::file(path))?;
+        let len = file.metadata().map_err(ITrackerError::file(path))?.len();
+        let map = if len == 0 {
+            None
+        } else {
+            // SAFETY: log files are only ever appended to or replaced by
+            // renaming a new file over them, see `schema::replace`, which
+            // rewrites, backups, undo and restoring dumps all go through, so
+            // the mapped bytes are never truncated or changed under the map.
+            Some(unsafe { Mmap::map(&file) }.map_err(ITrackerError::file(path))?)
+        };
+        if map.as_deref().is_some_and(crypto::is_encrypted) {
+            return Ok(None);
+        }
+        let amended = schema::read_journal(path)?
+            .into_iter()
+            .map(|record| (record.index, record))
+            .collect();
+        Ok(Some(MappedLog {
+            path: path.to_path_buf(),
+            map,
+            amended,
+        }))
+    }
+
+    /// Calls `visit` with every row in file order, amended rows as amended.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: If a row is invalid.
+    pub fn for_each(&self, visit: impl FnMut(RowRef<'_>)) -> Result<(), ITrackerError> {
+        self.read_rows(self.map.as_deref().unwrap_or_default(), true, visit)
+    }
+
+    /// Calls `visit` with the rows in `bytes`, a part of the log of whole rows.
+    fn read_rows(
+        &self,
+        bytes: &[u8],
+        headers: bool,
+        mut visit: impl FnMut(RowRef<'_>),
+    ) -> Result<(), ITrackerError> {
+        let path = self.path.as_path();
+        let mut reader = ReaderBuilder::new()
+            .has_headers(headers)
+            .flexible(true)
+            .from_reader(bytes);
+        let mut row = StringRecord::new();
+        let mut number = 0;
+        while reader
+            .read_record(&mut row)
+            .map_err(ITrackerError::csv(path))?
+        {
+            number += 1;
+            let parsed: RowRef =
+                row.deserialize(None)
+                    .map_err(|e| ITrackerError::InvalidRecord {
+                        path: path.into(),
+                        record: number,
+                        message: schema::field_error(&row, e),
+                    })?;
+            match self.amended.get(&parsed.index) {
+                Some(amendment) => visit(RowRef::from(amendment)),
+                None => visit(parsed),
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits the rows after the headers into at most `count` parts of whole
+    /// rows, of about the same size.
+    fn chunks(&self, count: usize) -> Vec<&[u8]> {
+        let bytes = self.map.as_deref().unwrap_or_default();
+        let mut chunks = Vec::with_capacity(count);
+        // Like the CSV reader, a quote starts a quoted field only at the start
+        // of a field, and in one only a doubled quote does not end it
+        let mut quoted = false;
+        let mut closed = false;
+        let mut previous = b'\n';
+        let mut start = None;
+        let mut target = 0;
+        for (position, &byte) in bytes.iter().enumerate() {
+            match byte {
+                b'"' if quoted => {
+                    quoted = false;
+                    closed = true;
+                }
+                b'"' if closed || matches!(previous, b',' | b'\n' | b'\r') => {
+                    quoted = true;
+                    closed = false;
+                }
+                b'\n' if !quoted && position + 1 >= target => {
+                    if let Some(start) = start {
+                        chunks.push(&bytes[start..=position]);
+                    }
+                    let next = position + 1;
+                    start = Some(next);
+                    target =
+                        next + (bytes.len() - next) / count.saturating_sub(chunks.len()).max(1);
+                }
+                _ => {}
+            }
+            if byte != b'"' {
+                closed = false;
+            }
+            previous = byte;
+        }
+        if let Some(start) = start.filter(|start| *start < bytes.len()) {
+            chunks.push(&bytes[start..]);
+        }
+        chunks
+    }
+
+    /// Reads the entries whose rows `keep` accepts, copying only those.
+    pub fn entries_where(
+        &self,
+        keep: impl Fn(&RowRef<'_>) -> bool,
+    ) -> Result<Vec<LogEntry>, ITrackerError> {
+        let mut entries = Vec::new();
+        self.for_each(|row| {
+            if keep(&row) {
+                entries.push(row.to_entry());
+            }
+        })?;
+        Ok(entries)
+    }
+
+    /// Reads the entries started from `from` up to before `to`, in file
+    /// order; entries without a valid start are left out. The rows are
+    /// parsed in parallel, a few chunks for each thread of the current pool.
+    pub fn entries_started(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<LogEntry>, ITrackerError> {
+        let keep = |row: &RowRef<'_>| row.start().is_some_and(|start| start >= from && start < to);
+        let parts: Result<Vec<Vec<LogEntry>>, ITrackerError> = self
+            .chunks(rayon::current_num_threads() * 4)
+            .into_par_iter()
+            .map(|chunk| {
+                let mut entries = Vec::new();
+                self.read_rows(chunk, false, |row| {
+                    if keep(&row) {
+                        entries.push(row.to_entry());
+                    }
+                })?;
+                Ok(entries)
+            })
+            .collect();
+        match parts {
+            Ok(parts) => Ok(parts.concat()),
+            // Read again in order, so the error names the row in the file
+            Err(_) => self.entries_where(keep),
+        }
+    }
+}
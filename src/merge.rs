@@ -0,0 +1,265 @@
+//! Merging two log files that were kept apart, e.g. one tracked on a laptop
+//! and one on a desktop.
+//!
+//! Unlike [`crate::sync::merge_records`] there is no common version to
+//! compare with, so entries are matched by their start time and
+//! description: an entry found in both files is kept once, and entries that
+//! are the same but differ otherwise, or whose times overlap, are conflicts
+//! for the user to resolve.
+
+use crate::{error::ITrackerError, log::LogEntry, schema::Record};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+/// Why two entries of the merged files cannot both be kept as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Same start and description, but e.g. a different duration or project.
+    Changed,
+    /// Different entries whose times overlap.
+    Overlap,
+}
+
+/// An entry of the other file clashing with one of the file merged into.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub ours: Record,
+    pub theirs: Record,
+}
+
+/// Which side of a conflict is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the entry of the file merged into, drop the other one.
+    Ours,
+    /// Replace the entry of the file merged into with the other one.
+    Theirs,
+    /// Keep both entries.
+    Both,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ours" => Ok(Resolution::Ours),
+            "theirs" => Ok(Resolution::Theirs),
+            "both" => Ok(Resolution::Both),
+            _ => Err(format!("expected ours, theirs or both, got '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Resolution::Ours => "ours",
+            Resolution::Theirs => "theirs",
+            Resolution::Both => "both",
+        })
+    }
+}
+
+/// The outcome of merging another log file into one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileMerge {
+    /// The merged records, ordered by start time.
+    pub records: Vec<Record>,
+    /// Entries taken from the other file, as `(index there, new index)`.
+    pub added: Vec<(usize, usize)>,
+    /// Entries of the other file that were already in the file.
+    pub duplicates: usize,
+    /// Indices of entries replaced by, or dropped for, entries of the other file.
+    pub replaced: Vec<usize>,
+}
+
+/// Merges the records of another log file into those of a file.
+///
+/// Entries of `theirs` are added with new indices, from `next_index` on, as
+/// indices of separate files mean nothing to each other.
+///
+/// # Arguments
+/// - `ours`: The records of the file merged into.
+/// - `theirs`: The records of the other file.
+/// - `next_index`: The first index not given out in the file merged into.
+/// - `resolve`: Decides each conflict; `None` cancels the merge.
+///
+/// # Returns
+/// - `Ok(Some(FileMerge))`: The merged records.
+/// - `Ok(None)`: If `resolve` cancelled the merge.
+/// - `Err(ITrackerError)`: The error of `resolve`.
+pub fn merge_files(
+    ours: &[Record],
+    theirs: &[Record],
+    next_index: usize,
+    mut resolve: impl FnMut(&Conflict) -> Result<Option<Resolution>, ITrackerError>,
+) -> Result<Option<FileMerge>, ITrackerError> {
+    let mut merged = FileMerge {
+        records: ours.to_vec(),
+        ..FileMerge::default()
+    };
+    // Indices of merged records that came from the other file
+    let mut foreign = HashSet::new();
+    let mut next_index = next_index;
+    let now = Utc::now().fixed_offset();
+
+    for record in theirs {
+        // Only entries of our file are compared, the other file's own overlaps are its business
+        let ours = |existing: &&Record| !foreign.contains(&existing.index);
+        if merged
+            .records
+            .iter()
+            .filter(ours)
+            .any(|existing| same_entry(existing, record) && same_values(existing, record))
+        {
+            merged.duplicates += 1;
+            continue;
+        }
+
+        let span = span(record, now);
+        let clash = merged.records.iter().position(|existing| {
+            ours(&existing)
+                && (same_entry(existing, record) || overlaps(self::span(existing, now), span))
+        });
+        let Some(position) = clash else {
+            add(&mut merged, &mut foreign, &mut next_index, record);
+            continue;
+        };
+
+        let ours = merged.records[position].clone();
+        let conflict = Conflict {
+            kind: if same_entry(&ours, record) {
+                ConflictKind::Changed
+            } else {
+                ConflictKind::Overlap
+            },
+            ours,
+            theirs: record.clone(),
+        };
+        let Some(resolution) = resolve(&conflict)? else {
+            return Ok(None);
+        };
+        let index = conflict.ours.index;
+        match (resolution, conflict.kind) {
+            (Resolution::Ours, _) => {}
+            (Resolution::Theirs, ConflictKind::Changed) => {
+                // The same entry keeps its index, only its values change
+                merged.records[position] = Record {
+                    index,
+                    ..record.clone()
+                };
+                merged.replaced.push(index);
+                foreign.insert(index);
+            }
+            (Resolution::Theirs, ConflictKind::Overlap) => {
+                merged.records.remove(position);
+                merged.replaced.push(index);
+                add(&mut merged, &mut foreign, &mut next_index, record);
+            }
+            (Resolution::Both, _) => add(&mut merged, &mut foreign, &mut next_index, record),
+        }
+    }
+
+    merged
+        .records
+        .sort_by_key(|record| LogEntry::from(record.clone()).start());
+    Ok(Some(merged))
+}
+
+/// Adds an entry of the other file under the next free index.
+fn add(
+    merged: &mut FileMerge,
+    foreign: &mut HashSet<usize>,
+    next_index: &mut usize,
+    record: &Record,
+) {
+    merged.records.push(Record {
+        index: *next_index,
+        ..record.clone()
+    });
+    merged.added.push((record.index, *next_index));
+    foreign.insert(*next_index);
+    *next_index += 1;
+}
+
+/// Start and end of an entry; running entries end now.
+fn span(
+    record: &Record,
+    now: DateTime<FixedOffset>,
+) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+    let entry = LogEntry::from(record.clone());
+    let start = entry.start()?;
+    let end = if entry.is_running() {
+        now
+    } else {
+        let seconds = entry.elapsed_time.trim().parse::<i64>().unwrap_or(0);
+        start + Duration::seconds(seconds)
+    };
+    Some((start, end))
+}
+
+fn overlaps(
+    a: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+    b: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+) -> bool {
+    match (a, b) {
+        (Some((a_start, a_end)), Some((b_start, b_end))) => a_start < b_end && b_start < a_end,
+        _ => false,
+    }
+}
+
+/// Whether two records describe the same piece of work: started at the
+/// same instant with the same description.
+fn same_entry(a: &Record, b: &Record) -> bool {
+    let start = |record: &Record| LogEntry::from(record.clone()).start();
+    start(a).is_some()
+        && start(a) == start(b)
+        && a.title.trim() == b.title.trim()
+        && a.message.trim() == b.message.trim()
+}
+
+/// Whether two records hold the same values, apart from the index and
+/// how the start time is written.
+fn same_values(a: &Record, b: &Record) -> bool {
+    Record {
+        index: b.index,
+        start_time: b.start_time.clone(),
+        ..a.clone()
+    } == *b
+}
+
+/// Entries of one log file that describe the same piece of work, as
+/// [`merge_files`] tells them apart: the same start and description.
+///
+/// # Returns
+/// Groups of at least two indices in file order; the first entry of each
+/// group is the one to keep.
+pub fn duplicates(entries: &[LogEntry]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut seen: HashMap<(DateTime<Utc>, &str, &str), usize> = HashMap::new();
+    for entry in entries {
+        let Some(start) = entry.start() else {
+            continue;
+        };
+        let key = (
+            start.with_timezone(&Utc),
+            entry.title.trim(),
+            entry.message.trim(),
+        );
+        match seen.get(&key) {
+            Some(&group) => groups[group].push(entry.index),
+            None => {
+                seen.insert(key, groups.len());
+                groups.push(vec![entry.index]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
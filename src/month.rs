@@ -0,0 +1,379 @@
+use crate::budget;
+use crate::config::{save_config, ConfigData};
+use crate::error::ITrackerError;
+use crate::expense;
+use crate::invoice::{self, Billing, Detail, Invoice};
+use crate::log::{read_logs_from_file, LogEntry};
+use crate::rounding::Rounding;
+use crate::schema::{self, Record};
+use crate::storage::Storage;
+use crate::timefmt::DurationFormat;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Parses a month in `YYYY-MM` format into its year and month number.
+///
+/// # Arguments
+/// - `month`: The month string, e.g. `2024-06`.
+///
+/// # Returns
+/// - `Ok((i32, u32))`: The year and month number.
+/// - `Err(ITrackerError)`: If the string is not a valid month.
+pub fn parse_month(month: &str) -> Result<(i32, u32), ITrackerError> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map(|date| (date.year(), date.month()))
+        .map_err(|_| ITrackerError::Config(format!("Invalid month '{}', expected YYYY-MM", month)))
+}
+
+/// Returns `true` if the entry started in a month that has been closed.
+///
+/// # Arguments
+/// - `config`: The loaded configuration holding the locked months.
+/// - `start_time`: The RFC2822 start time of the entry.
+pub fn is_locked(config: &ConfigData, start_time: &str) -> bool {
+    let tz = config.timezone();
+    DateTime::parse_from_rfc2822(start_time.trim())
+        .map(|start| start.with_timezone(&tz))
+        .map(|start| {
+            let month = format!("{:04}-{:02}", start.year(), start.month());
+            config.locked_months.contains(&month)
+        })
+        .unwrap_or(false)
+}
+
+/// Checks that a new entry may start at `at`, outside the closed months.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` if `at` lies in a closed month.
+pub fn ensure_open(config: &ConfigData, at: DateTime<Utc>) -> Result<(), ITrackerError> {
+    if !is_locked(config, &at.to_rfc2822()) {
+        return Ok(());
+    }
+    let local = at.with_timezone(&config.timezone());
+    Err(ITrackerError::Config(format!(
+        "{:04}-{:02} is closed, no entries can be added to it",
+        local.year(),
+        local.month()
+    )))
+}
+
+/// Closes a month in one guided pass.
+///
+/// The entries of the month are validated (no running entries, no overlaps,
+/// a project on every billable entry), the month is locked in the
+/// configuration, a report and the invoice of the billable entries are
+/// generated and the entries are archived to `archive/<month>/` next to the
+/// output file. Entries are billable if a rate applies to their project.
+///
+/// # Arguments
+/// - `output_file`: The path to the log file.
+/// - `month`: The month to close in `YYYY-MM` format.
+/// - `config`: The configuration in which the month gets locked.
+///
+/// # Returns
+/// - `Ok(())`: If the month was closed.
+/// - `Err(ITrackerError)`: If validation or any file operation fails.
+pub fn close_month(
+    output_file: &str,
+    month: &str,
+    config: &mut ConfigData,
+) -> Result<(), ITrackerError> {
+    let (year, month_number) = parse_month(month)?;
+    let month = format!("{:04}-{:02}", year, month_number);
+
+    if config.locked_months.contains(&month) {
+        return Err(ITrackerError::CloseMonth(format!(
+            "Month {} is already closed",
+            month
+        )));
+    }
+
+    // Collect the entries that started in the requested month, in local time
+    println!("Validating {}...", month);
+    let tz = config.timezone();
+    let mut entries = Vec::new();
+    for (position, entry) in read_logs_from_file(output_file)?.into_iter().enumerate() {
+        let start = DateTime::parse_from_rfc2822(entry.start_time.trim()).map_err(|_| {
+            ITrackerError::InvalidRecord {
+                path: output_file.into(),
+                record: position + 1,
+                message: format!(
+                    "entry {} has an invalid start time '{}'",
+                    entry.index, entry.start_time
+                ),
+            }
+        })?;
+        let start = start.with_timezone(&tz).fixed_offset();
+        if start.year() == year && start.month() == month_number {
+            entries.push((start, entry));
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(ITrackerError::CloseMonth(format!(
+            "No entries found for {}",
+            month
+        )));
+    }
+
+    let billing = config.billing()?;
+    let problems = validate(&mut entries, &billing);
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("  - {}", problem);
+        }
+        return Err(ITrackerError::CloseMonth(format!(
+            "Cannot close {}: {} problem(s) found",
+            month,
+            problems.len()
+        )));
+    }
+
+    // Archive and report before locking so a failed write leaves the month open
+    let archive_dir = archive_dir(output_file, &month);
+    fs::create_dir_all(&archive_dir).map_err(ITrackerError::file(&archive_dir))?;
+
+    let report_path = archive_dir.join("report.txt");
+    let rounding = Rounding::from_config(config.rounding.as_ref())?;
+    write_report(
+        &report_path,
+        &month,
+        &entries,
+        &rounding,
+        config.duration_format(),
+    )
+    .map_err(ITrackerError::file(&report_path))?;
+    println!("Report written to {}", report_path.display());
+
+    let invoice_path = archive_dir.join("invoice.txt");
+    if write_invoice(&invoice_path, output_file, &entries, &billing, config)? {
+        println!("Invoice written to {}", invoice_path.display());
+    }
+
+    let entries_path = archive_dir.join("entries.csv");
+    write_archive(&entries_path, &entries)?;
+    println!(
+        "Archived {} entries to {}",
+        entries.len(),
+        entries_path.display()
+    );
+
+    config.locked_months.push(month.clone());
+    config.locked_months.sort();
+    save_config(config)?;
+    println!("Month {} is closed and locked.", month);
+
+    Ok(())
+}
+
+/// Sorts the entries by start time and reports running or overlapping
+/// entries, and billable ones without a project.
+fn validate(entries: &mut [(DateTime<FixedOffset>, LogEntry)], billing: &Billing) -> Vec<String> {
+    let mut problems = Vec::new();
+    entries.sort_by_key(|(start, _)| *start);
+
+    for (_, entry) in entries.iter() {
+        if entry.elapsed_time.trim().parse::<u64>().unwrap_or(0) == 0 {
+            problems.push(format!("Entry {} is still running", entry.index));
+        } else if entry.project.trim().is_empty() && is_billable(entry, billing) {
+            problems.push(format!(
+                "Entry {} is billable but has no project",
+                entry.index
+            ));
+        }
+    }
+
+    for pair in entries.windows(2) {
+        let (start, entry) = &pair[0];
+        let (next_start, next_entry) = &pair[1];
+        if *next_start < *start + elapsed(entry) {
+            problems.push(format!(
+                "Entries {} and {} overlap",
+                entry.index, next_entry.index
+            ));
+        }
+    }
+
+    problems
+}
+
+fn is_billable(entry: &LogEntry, billing: &Billing) -> bool {
+    billing.rate_for(&entry.project).is_some()
+}
+
+fn elapsed(entry: &LogEntry) -> Duration {
+    Duration::seconds(entry.elapsed_time.trim().parse::<i64>().unwrap_or(0))
+}
+
+fn archive_dir(output_file: &str, month: &str) -> PathBuf {
+    Path::new(output_file)
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("archive")
+        .join(month)
+}
+
+/// Writes a plain-text summary of tracked time per day.
+///
+/// With rounding configured, a `Billed` column holds the worked time of each
+/// entry rounded by the rule of its project.
+fn write_report(
+    path: &Path,
+    month: &str,
+    entries: &[(DateTime<FixedOffset>, LogEntry)],
+    rounding: &Rounding,
+    durations: DurationFormat,
+) -> std::io::Result<()> {
+    let mut per_day: BTreeMap<NaiveDate, (i64, i64, u64)> = BTreeMap::new();
+    for (start, entry) in entries {
+        let day = per_day.entry(start.date_naive()).or_default();
+        let paused = entry.paused_time.trim().parse::<i64>().unwrap_or(0);
+        let worked = (elapsed(entry).num_seconds() - paused).max(0) as u64;
+        day.0 += elapsed(entry).num_seconds();
+        day.1 += paused;
+        day.2 += rounding.apply(&entry.project, worked);
+    }
+    let billed = !rounding.is_exact();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "Monthly report {}", month)?;
+    if billed {
+        writeln!(writer, "Rounding: {}", rounding)?;
+    }
+    writeln!(writer)?;
+    write!(writer, "{:<12} {:>10} {:>10}", "Date", "Time", "Paused")?;
+    if billed {
+        write!(writer, " {:>10}", "Billed")?;
+    }
+    writeln!(writer)?;
+
+    let (mut total, mut total_paused, mut total_billed) = (0, 0, 0);
+    for (date, (seconds, paused, rounded)) in &per_day {
+        write!(
+            writer,
+            "{:<12} {:>10} {:>10}",
+            date,
+            durations.format((*seconds).max(0) as u64),
+            durations.format((*paused).max(0) as u64)
+        )?;
+        if billed {
+            write!(writer, " {:>10}", durations.format(*rounded))?;
+        }
+        writeln!(writer)?;
+        total += seconds;
+        total_paused += paused;
+        total_billed += rounded;
+    }
+
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "Total: {} ({} paused) in {} entries",
+        durations.format(total.max(0) as u64),
+        durations.format(total_paused.max(0) as u64),
+        entries.len()
+    )?;
+    if billed {
+        writeln!(writer, "Billed: {}", durations.format(total_billed))?;
+    }
+    writer.flush()
+}
+
+/// Writes the invoice of the month's billable entries and expenses with
+/// the invoice generator, one line per project and task.
+///
+/// # Returns
+/// - `Ok(bool)`: `false` if there was nothing to invoice and no file was
+///   written.
+/// - `Err(ITrackerError)`: If the invoice cannot be built or written.
+fn write_invoice(
+    path: &Path,
+    output_file: &str,
+    entries: &[(DateTime<FixedOffset>, LogEntry)],
+    billing: &Billing,
+    config: &ConfigData,
+) -> Result<bool, ITrackerError> {
+    let billable: Vec<LogEntry> = entries
+        .iter()
+        .map(|(_, entry)| entry)
+        .filter(|entry| is_billable(entry, billing))
+        .cloned()
+        .collect();
+    let Some((start, _)) = entries.first() else {
+        return Ok(false);
+    };
+    let expenses = expense::read_expenses(Path::new(output_file))?;
+    let invoice = Invoice::build(
+        &billable,
+        &expenses,
+        budget::month_range(start.date_naive()),
+        None,
+        Detail::default(),
+        billing,
+        config.timezone(),
+    )?;
+    if invoice.items.is_empty() && invoice.expenses.is_empty() {
+        return Ok(false);
+    }
+    fs::write(path, invoice.render()).map_err(ITrackerError::file(path))?;
+    Ok(true)
+}
+
+/// Copies the month's entries into a standalone CSV file.
+fn write_archive(
+    path: &Path,
+    entries: &[(DateTime<FixedOffset>, LogEntry)],
+) -> Result<(), ITrackerError> {
+    let records: Vec<Record> = entries.iter().map(|(_, entry)| entry.into()).collect();
+    schema::write(path, &records)
+}
+
+/// Refuses changes to an entry that belongs to a closed month or, unless
+/// forced, was invoiced.
+///
+/// # Arguments
+/// - `config`: The loaded configuration holding the locked months.
+/// - `storage`: The log file, the one the change is then made through.
+/// - `index`: The index of the entry about to be changed.
+/// - `force`: Allow changing invoiced entries; closed months stay locked.
+///
+/// # Returns
+/// - `Ok(())`: If the entry may be changed (or does not exist).
+/// - `Err(ITrackerError)`: `Locked` or `Invoiced` if it may not, or a read error.
+pub fn ensure_unlocked(
+    config: &ConfigData,
+    storage: &Storage,
+    index: usize,
+    force: bool,
+) -> Result<(), ITrackerError> {
+    match storage.entry(index) {
+        Ok(entry) => check_unlocked(config, &entry, force),
+        Err(ITrackerError::EntryNotFound { .. }) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Like [`ensure_unlocked`] for an entry already read, e.g. one of many
+/// about to be changed at once.
+pub fn check_unlocked(
+    config: &ConfigData,
+    entry: &LogEntry,
+    force: bool,
+) -> Result<(), ITrackerError> {
+    let index = entry.index;
+    if is_locked(config, &entry.start_time) {
+        return Err(ITrackerError::Locked { index });
+    }
+    match invoice::number(entry) {
+        Some(number) if !force => Err(ITrackerError::Invoiced {
+            index,
+            number: number.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
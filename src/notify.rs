@@ -0,0 +1,430 @@
+use crate::{error::ITrackerError, log::LogEntry, report};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Length of a Pomodoro unless configured otherwise.
+pub const DEFAULT_POMODORO_MINUTES: u32 = 25;
+
+/// Tracked hours after which a timer counts as running unusually long.
+pub const DEFAULT_LONG_RUNNING_HOURS: f64 = 4.0;
+
+/// Minutes of work without a break after which a break is suggested.
+pub const DEFAULT_BREAK_AFTER_MINUTES: u32 = 90;
+
+/// Minutes without a running timer that count as a break.
+pub const DEFAULT_BREAK_MINUTES: u32 = 5;
+
+/// The `[notifications]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationsConfig {
+    /// Notify after every Pomodoro of tracked time, off by default.
+    pub pomodoro: Option<bool>,
+    pub pomodoro_minutes: Option<u32>,
+    /// Notify when the estimate of the running entry is used up, on by default.
+    pub estimate: Option<bool>,
+    /// Notify once when a timer runs longer than `long_running_hours`, on by default.
+    pub long_running: Option<bool>,
+    pub long_running_hours: Option<f64>,
+    /// Remind to take a break after `break_after_minutes` of work without
+    /// one, off by default.
+    pub breaks: Option<bool>,
+    pub break_after_minutes: Option<u32>,
+    /// Pauses and gaps between timers at least this long count as a break.
+    pub break_minutes: Option<u32>,
+    /// Notify once a day when the time tracked that day exceeds
+    /// `workday_hours` while a timer runs, off by default.
+    pub overtime: Option<bool>,
+}
+
+/// Something worth telling the user about a running timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// The given number of Pomodoros has been tracked.
+    Pomodoro(u64),
+    /// The tracked time reached the estimate.
+    EstimateReached,
+    /// The timer runs longer than the configured limit.
+    LongRunning,
+    /// Work went on without a break for the given number of reminder
+    /// intervals, across entries.
+    Break(u64),
+    /// The time tracked today exceeded the workday.
+    Overtime,
+}
+
+/// A desktop notification about one entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub event: Event,
+    pub summary: String,
+    pub body: String,
+}
+
+impl Notification {
+    /// Shows the notification on the desktop.
+    pub fn show(&self) -> Result<(), ITrackerError> {
+        notify_rust::Notification::new()
+            .appname("ITracker")
+            .summary(&self.summary)
+            .body(&self.body)
+            .show()
+            .map_err(|e| ITrackerError::Io(std::io::Error::other(e)))?;
+        Ok(())
+    }
+}
+
+/// Decides which timer events to notify about, each of them once per entry.
+///
+/// The first time an entry is checked, the events it already passed are only
+/// remembered, so a restarted daemon does not repeat old notifications.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    /// Seconds per Pomodoro, `None` if Pomodoro notifications are off.
+    pomodoro: Option<u64>,
+    estimate: bool,
+    /// Seconds after which a timer runs unusually long, `None` if off.
+    long_running: Option<u64>,
+    /// Events already handled per log file and entry index.
+    seen: HashMap<(String, usize), HashSet<Event>>,
+    /// Seconds of work after which to remind, and seconds a break lasts at
+    /// least; `None` if break reminders are off.
+    breaks: Option<(u64, u64)>,
+    /// The current stretch of work per log file.
+    stretches: HashMap<String, Stretch>,
+    overtime: bool,
+    /// Seconds of a workday and the timezone days are counted in, see
+    /// [`Notifier::with_workday`].
+    workday: Option<(u64, Tz)>,
+    /// The last day overtime was notified about, per log file.
+    overtime_days: HashMap<String, NaiveDate>,
+}
+
+/// Work without a break, as observed by repeated checks.
+#[derive(Debug, Clone)]
+struct Stretch {
+    since: DateTime<Utc>,
+    /// The first and the last check that saw the timers stopped or paused,
+    /// if they are.
+    idle: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// Reminders already shown for this stretch.
+    reminded: u64,
+}
+
+impl Notifier {
+    /// Resolves the `[notifications]` section, using the defaults for unset values.
+    ///
+    /// # Returns
+    /// - `Ok(Notifier)`: The notifier.
+    /// - `Err(ITrackerError)`: `Config` if a length is not positive.
+    pub fn from_config(config: Option<&NotificationsConfig>) -> Result<Self, ITrackerError> {
+        let default = NotificationsConfig::default();
+        let config = config.unwrap_or(&default);
+
+        let minutes = config.pomodoro_minutes.unwrap_or(DEFAULT_POMODORO_MINUTES);
+        if minutes == 0 {
+            return Err(ITrackerError::Config(
+                "pomodoro_minutes must be at least 1".to_string(),
+            ));
+        }
+        let hours = config
+            .long_running_hours
+            .unwrap_or(DEFAULT_LONG_RUNNING_HOURS);
+        if !hours.is_finite() || hours <= 0.0 {
+            return Err(ITrackerError::Config(format!(
+                "long_running_hours must be positive, got {}",
+                hours
+            )));
+        }
+        let break_after = config
+            .break_after_minutes
+            .unwrap_or(DEFAULT_BREAK_AFTER_MINUTES);
+        if break_after == 0 {
+            return Err(ITrackerError::Config(
+                "break_after_minutes must be at least 1".to_string(),
+            ));
+        }
+        let break_length = config.break_minutes.unwrap_or(DEFAULT_BREAK_MINUTES);
+
+        Ok(Notifier {
+            pomodoro: config
+                .pomodoro
+                .unwrap_or(false)
+                .then_some(u64::from(minutes) * 60),
+            estimate: config.estimate.unwrap_or(true),
+            long_running: config
+                .long_running
+                .unwrap_or(true)
+                .then_some((hours * 3600.0) as u64),
+            seen: HashMap::new(),
+            breaks: config
+                .breaks
+                .unwrap_or(false)
+                .then_some((u64::from(break_after) * 60, u64::from(break_length) * 60)),
+            stretches: HashMap::new(),
+            overtime: config.overtime.unwrap_or(false),
+            workday: None,
+            overtime_days: HashMap::new(),
+        })
+    }
+
+    /// Sets the length of a workday, without which overtime is not notified.
+    ///
+    /// # Arguments
+    /// - `workday`: Seconds of a workday, `workday_hours` of the configuration.
+    /// - `tz`: The timezone days are counted in.
+    pub fn with_workday(mut self, workday: Option<u64>, tz: Tz) -> Self {
+        self.workday = workday.map(|workday| (workday, tz));
+        self
+    }
+
+    /// The events `entry` has passed by `now`, at most one Pomodoro (the latest).
+    fn events(&self, entry: &LogEntry, now: DateTime<Utc>) -> Vec<Event> {
+        if !entry.is_running() {
+            return Vec::new();
+        }
+        let tracked = report::tracked_seconds(entry, now);
+        let mut events = Vec::new();
+
+        if let Some(length) = self.pomodoro {
+            if tracked >= length {
+                events.push(Event::Pomodoro(tracked / length));
+            }
+        }
+        if let Some(minutes) = entry.estimate.filter(|_| self.estimate) {
+            if minutes > 0 && tracked >= u64::from(minutes) * 60 {
+                events.push(Event::EstimateReached);
+            }
+        }
+        if let Some(limit) = self.long_running {
+            if tracked >= limit {
+                events.push(Event::LongRunning);
+            }
+        }
+        events
+    }
+
+    /// Checks the entries of `file` and returns the notifications that are due.
+    ///
+    /// # Arguments
+    /// - `file`: The log file the entries are from.
+    /// - `entries`: All entries of the file; only running ones can be due.
+    /// - `now`: The current time.
+    pub fn check(
+        &mut self,
+        file: &str,
+        entries: &[LogEntry],
+        now: DateTime<Utc>,
+    ) -> Vec<Notification> {
+        let mut due = Vec::new();
+        for entry in entries.iter().filter(|entry| entry.is_running()) {
+            let events = self.events(entry, now);
+            let key = (file.to_string(), entry.index);
+            let first_check = !self.seen.contains_key(&key);
+            let seen = self.seen.entry(key).or_default();
+
+            for event in events {
+                if seen.insert(event) && !first_check {
+                    due.push(notification(entry, event));
+                }
+            }
+        }
+        // Forget stopped and deleted entries of this file
+        self.seen.retain(|(seen_file, index), _| {
+            seen_file != file
+                || entries
+                    .iter()
+                    .any(|entry| entry.index == *index && entry.is_running())
+        });
+        due.extend(self.check_break(file, entries, now));
+        due.extend(self.check_overtime(file, entries, now));
+        due
+    }
+
+    /// Returns an overtime notification the first time on a day that the
+    /// time tracked in `file` exceeds the workday while a timer runs.
+    pub fn check_overtime(
+        &mut self,
+        file: &str,
+        entries: &[LogEntry],
+        now: DateTime<Utc>,
+    ) -> Option<Notification> {
+        let (workday, tz) = self.workday.filter(|_| self.overtime)?;
+        let working = entries
+            .iter()
+            .filter(|entry| entry.is_running() && entry.paused_since().is_none())
+            .max_by_key(|entry| entry.start())?;
+        let today = report::today(now, tz);
+        if self.overtime_days.get(file) == Some(&today) {
+            return None;
+        }
+        let tracked =
+            report::summarize(report::entries_between(entries, today, today, tz), now, tz).total;
+        if tracked <= workday {
+            return None;
+        }
+        self.overtime_days.insert(file.to_string(), today);
+        Some(Notification {
+            event: Event::Overtime,
+            summary: "Overtime".to_string(),
+            body: format!(
+                "You tracked {} today, more than your {} workday, and {} is still running.",
+                hours_and_minutes(tracked),
+                hours_and_minutes(workday),
+                working.display_title()
+            ),
+        })
+    }
+
+    /// Returns a break reminder if work on `file` went on for another
+    /// `break_after_minutes` without a break, for the daemon and the viewer.
+    ///
+    /// Unlike the events of entries, a stretch already due when `file` is
+    /// checked the first time is reminded of right away. Later checks follow
+    /// the timers: a pause or stop seen by checks at least `break_minutes`
+    /// apart starts a new stretch.
+    pub fn check_break(
+        &mut self,
+        file: &str,
+        entries: &[LogEntry],
+        now: DateTime<Utc>,
+    ) -> Option<Notification> {
+        let (after, length) = self.breaks?;
+        let Some(working) = entries
+            .iter()
+            .filter(|entry| entry.is_running() && entry.paused_since().is_none())
+            .max_by_key(|entry| entry.start())
+        else {
+            if let Some(stretch) = self.stretches.get_mut(file) {
+                let (since, _) = stretch.idle.unwrap_or((now, now));
+                stretch.idle = Some((since, now));
+            }
+            return None;
+        };
+        let length = Duration::seconds(length as i64);
+
+        let stretch = self
+            .stretches
+            .entry(file.to_string())
+            .or_insert_with(|| Stretch {
+                since: stretch_start(entries, working, length, now),
+                idle: None,
+                reminded: 0,
+            });
+        if let Some((from, to)) = stretch.idle.take() {
+            // Work resumed at some point after the last check that saw none
+            if to - from >= length {
+                stretch.since = to;
+                stretch.reminded = 0;
+            }
+        }
+        let worked = (now - stretch.since).num_seconds().max(0) as u64;
+        let count = worked / after;
+        if count <= stretch.reminded {
+            return None;
+        }
+        stretch.reminded = count;
+        Some(break_notification(working, count, worked))
+    }
+}
+
+/// Where the work of the running entry `working` started without a break:
+/// its start, or that of earlier entries that ended less than `length`
+/// before the next one started. Entries paused for longer than a break
+/// start the stretch now, as it is unknown when their pauses were.
+fn stretch_start(
+    entries: &[LogEntry],
+    working: &LogEntry,
+    length: Duration,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let paused = |entry: &LogEntry| entry.paused_time.trim().parse::<i64>().unwrap_or(0);
+    let Some(mut since) = working.start().map(|start| start.with_timezone(&Utc)) else {
+        return now;
+    };
+    if paused(working) >= length.num_seconds() {
+        return now;
+    }
+    let mut earlier: Vec<(DateTime<Utc>, DateTime<Utc>)> = entries
+        .iter()
+        .filter(|entry| !entry.is_running() && paused(entry) < length.num_seconds())
+        .filter_map(|entry| {
+            let start = entry.start()?.with_timezone(&Utc);
+            let elapsed = entry.elapsed_time.trim().parse::<i64>().unwrap_or(0);
+            Some((start, start + Duration::seconds(elapsed)))
+        })
+        .filter(|(start, _)| *start < since)
+        .collect();
+    earlier.sort_by_key(|(start, _)| std::cmp::Reverse(*start));
+    for (start, end) in earlier {
+        if end + length <= since {
+            break;
+        }
+        since = since.min(start);
+    }
+    since
+}
+
+/// Seconds for a notification, e.g. `45 minutes`, `2 hours` or `1h 30m`.
+fn hours_and_minutes(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{} minutes", minutes),
+        (hours, 0) => format!("{} hours", hours),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+fn break_notification(entry: &LogEntry, count: u64, worked: u64) -> Notification {
+    let time = hours_and_minutes(worked);
+    Notification {
+        event: Event::Break(count),
+        summary: "Time for a break".to_string(),
+        body: format!(
+            "You have been tracking for {} straight, last on {}. Take a break.",
+            time,
+            entry.display_title()
+        ),
+    }
+}
+
+fn notification(entry: &LogEntry, event: Event) -> Notification {
+    let task = entry.display_title();
+    let (summary, body) = match event {
+        Event::Pomodoro(1) => (
+            "Pomodoro done".to_string(),
+            format!("Time for a break from {}.", task),
+        ),
+        Event::Pomodoro(count) => (
+            format!("Pomodoro {} done", count),
+            format!("Time for a break from {}.", task),
+        ),
+        Event::EstimateReached => (
+            "Estimate used up".to_string(),
+            format!(
+                "{} reached its estimate of {} minutes.",
+                task,
+                entry.estimate.unwrap_or_default()
+            ),
+        ),
+        Event::Break(_) => (
+            "Time for a break".to_string(),
+            format!("Time for a break from {}.", task),
+        ),
+        Event::Overtime => ("Overtime".to_string(), format!("Overtime on {}.", task)),
+        Event::LongRunning => (
+            "Timer still running".to_string(),
+            format!(
+                "{} has been running for a long time. Forgot to stop it?",
+                task
+            ),
+        ),
+    };
+    Notification {
+        event,
+        summary,
+        body,
+    }
+}
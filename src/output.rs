@@ -0,0 +1,258 @@
+//! Output formats of the read commands `list`, `status` and `report`,
+//! chosen with `--format`.
+//!
+//! `table` is the output for people: tables on a terminal, aligned lines
+//! otherwise. `json`, `yaml` and `csv` are for scripts and hold the same
+//! rows in each format, with timestamps in RFC 3339 and durations in
+//! seconds; they are never colored or localized. CSV joins lists such as
+//! tags with commas, and metadata as `key=value` pairs. `status` also takes the formats of the status bars.
+
+use crate::{
+    error::ITrackerError,
+    log::LogEntry,
+    report::{self, Goal, NO_PROJECT},
+    status::{State, StatusFormat},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::{collections::BTreeMap, io, str::FromStr};
+
+/// The value of `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+    Csv,
+    /// One of the status bar formats, for `status` only.
+    Bar(StatusFormat),
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            "csv" => Ok(Format::Csv),
+            // `text` is what `status --format` called the table format
+            "text" => Ok(Format::Table),
+            other => other.parse().map(Format::Bar).map_err(|_| {
+                format!(
+                    "Unknown format '{}', expected table, json, yaml or csv, or for status waybar, polybar or i3blocks",
+                    other
+                )
+            }),
+        }
+    }
+}
+
+impl Format {
+    /// Whether this is one of the formats for scripts.
+    pub fn is_data(self) -> bool {
+        matches!(self, Format::Json | Format::Yaml | Format::Csv)
+    }
+
+    /// The format of `status`, whose text is its table.
+    pub fn status(self) -> StatusFormat {
+        match self {
+            Format::Bar(format) => format,
+            _ => StatusFormat::Text,
+        }
+    }
+
+    /// Checks the format is one a command other than `status` prints.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: `Config` for a status bar format.
+    pub fn for_command(self, command: &str) -> Result<Self, ITrackerError> {
+        match self {
+            Format::Bar(_) => Err(ITrackerError::Config(format!(
+                "{} prints table, json, yaml or csv; the status bar formats are for status",
+                command
+            ))),
+            format => Ok(format),
+        }
+    }
+}
+
+/// Renders `value`, a row or a list of rows, in a format for scripts.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` for `table` and the status bar formats,
+///   which callers print themselves.
+pub fn render<T: Serialize>(format: Format, value: &T) -> Result<String, ITrackerError> {
+    match format {
+        Format::Json => {
+            let mut json = serde_json::to_string_pretty(value).map_err(io::Error::other)?;
+            json.push('\n');
+            Ok(json)
+        }
+        Format::Yaml => Ok(serde_yaml::to_string(value).map_err(io::Error::other)?),
+        Format::Csv => csv(serde_yaml::to_value(value).map_err(io::Error::other)?),
+        other => Err(ITrackerError::Config(format!(
+            "{:?} is not a format for scripts",
+            other
+        ))),
+    }
+}
+
+/// One line per row with a header line of the fields of the first row.
+fn csv(value: Value) -> Result<String, ITrackerError> {
+    let rows = match value {
+        Value::Sequence(rows) => rows,
+        row => vec![row],
+    };
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    // Mappings keep the order of the fields, unlike JSON objects
+    let headers: Vec<Value> = match rows.first() {
+        Some(Value::Mapping(fields)) => fields.keys().cloned().collect(),
+        _ => Vec::new(),
+    };
+    if !headers.is_empty() {
+        writer
+            .write_record(headers.iter().map(cell))
+            .map_err(io::Error::from)?;
+    }
+    for row in &rows {
+        let cells = headers
+            .iter()
+            .map(|header| row.get(header).map(cell).unwrap_or_default());
+        writer.write_record(cells).map_err(io::Error::from)?;
+    }
+    let data = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(flag) => flag.to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => text.clone(),
+        Value::Sequence(items) => items.iter().map(cell).collect::<Vec<_>>().join(","),
+        Value::Mapping(fields) => fields
+            .iter()
+            .map(|(key, value)| format!("{}={}", cell(key), cell(value)))
+            .collect::<Vec<_>>()
+            .join(","),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+/// An entry as `list` prints it for scripts.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryRow {
+    pub index: usize,
+    pub start: Option<String>,
+    pub tracked_seconds: u64,
+    pub paused_seconds: u64,
+    /// `running`, `paused` or `stopped`.
+    pub state: String,
+    pub title: String,
+    pub description: String,
+    pub project: String,
+    pub tags: Vec<String>,
+    pub estimate_minutes: Option<u32>,
+    /// Extra values, e.g. a ticket given with `--meta`.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl EntryRow {
+    pub fn of(entry: &LogEntry, now: DateTime<Utc>) -> Self {
+        let state = match State::of(Some(entry).filter(|entry| entry.is_running())) {
+            State::Idle => "stopped".to_string(),
+            state => state.to_string(),
+        };
+        EntryRow {
+            index: entry.index,
+            start: entry.start().map(|start| start.to_rfc3339()),
+            tracked_seconds: report::tracked_seconds(entry, now),
+            paused_seconds: entry.paused_time.trim().parse().unwrap_or(0),
+            state,
+            title: entry.display_title().to_string(),
+            description: entry.message.trim().to_string(),
+            project: entry.project.trim().to_string(),
+            tags: entry.tags.clone(),
+            estimate_minutes: entry.estimate,
+            metadata: entry.metadata.clone(),
+        }
+    }
+}
+
+/// The running timer as `status` prints it for scripts; only `state` is
+/// set when idle, and the goal fields only with a daily goal.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusRow {
+    /// `idle`, `running` or `paused`.
+    pub state: String,
+    pub index: Option<usize>,
+    pub title: Option<String>,
+    pub start: Option<String>,
+    pub tracked_seconds: Option<u64>,
+    pub today_seconds: Option<u64>,
+    pub goal_seconds: Option<u64>,
+}
+
+impl StatusRow {
+    pub fn of(running: Option<&LogEntry>, now: DateTime<Utc>, goal: Option<Goal>) -> Self {
+        StatusRow {
+            state: State::of(running).to_string(),
+            index: running.map(|entry| entry.index),
+            title: running.map(|entry| entry.display_title().to_string()),
+            start: running
+                .and_then(LogEntry::start)
+                .map(|start| start.to_rfc3339()),
+            tracked_seconds: running.map(|entry| report::tracked_seconds(entry, now)),
+            today_seconds: goal.map(|goal| goal.tracked),
+            goal_seconds: goal.map(|goal| goal.target),
+        }
+    }
+}
+
+/// The time tracked on one day for one project, as `report` prints it for
+/// scripts.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportRow {
+    pub date: NaiveDate,
+    pub project: String,
+    pub tracked_seconds: u64,
+    pub entries: usize,
+}
+
+/// The rows of a report of `entries`, by day and then project.
+pub fn report_rows<'a>(
+    entries: impl IntoIterator<Item = &'a LogEntry>,
+    now: DateTime<Utc>,
+    tz: Tz,
+) -> Vec<ReportRow> {
+    let mut rows: BTreeMap<(NaiveDate, String), (u64, usize)> = BTreeMap::new();
+    for entry in entries {
+        let Some(date) = report::local_date(entry, tz) else {
+            continue;
+        };
+        let project = match entry.project.trim() {
+            "" => NO_PROJECT.to_string(),
+            project => project.to_string(),
+        };
+        let row = rows.entry((date, project)).or_default();
+        row.0 += report::tracked_seconds(entry, now);
+        row.1 += 1;
+    }
+    rows.into_iter()
+        .map(|((date, project), (tracked_seconds, entries))| ReportRow {
+            date,
+            project,
+            tracked_seconds,
+            entries,
+        })
+        .collect()
+}
@@ -0,0 +1,466 @@
+//! Exporters, importers and sync targets shipped as separate programs.
+//!
+//! A plugin is an executable in the plugins directory,
+//! `$XDG_CONFIG_HOME/itracker/plugins/` on Linux unless `plugins.dir` is
+//! set, named after the plugin. It is started once per call and reads one
+//! JSON request from stdin, then writes one JSON response to stdout:
+//!
+//! ```text
+//! {"protocol":1,"action":"describe"}
+//! {"description":"Worklogs for Jira","capabilities":["export","sync"]}
+//!
+//! {"protocol":1,"action":"export","file":"/home/me/time.csv","entries":[...],"options":{}}
+//! {"output":"...text written to the export file or stdout..."}
+//!
+//! {"protocol":1,"action":"import","input":"...contents of the imported file...","options":{}}
+//! {"entries":[{"start_time":"2024-06-03T09:00:00+02:00","elapsed_time":3600,"title":"Review"}]}
+//!
+//! {"protocol":1,"action":"sync","file":"/home/me/time.csv","entries":[...],"options":{}}
+//! {"entries":[...entries to add...],"message":"Logged 3 worklogs"}
+//! ```
+//!
+//! Entries sent to plugins look like those of the REST API. Entries coming
+//! back need a start time (RFC 3339 or RFC 2822), others are left out and
+//! reported; entries already in the log file, with the same start, title
+//! and description, are skipped. A
+//! plugin fails by answering `{"error":"..."}` or exiting unsuccessfully;
+//! what it writes to stderr is shown to the user.
+
+use crate::{
+    config, crypto,
+    error::ITrackerError,
+    log::{format_metadata, LogEntry},
+    progress::Progress,
+    schema::{self, Record},
+};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Version of the protocol, sent with every request.
+pub const PROTOCOL: u32 = 1;
+
+/// Name of the plugins directory next to the config file.
+pub const PLUGINS_DIR: &str = "plugins";
+
+/// The `[plugins]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginsConfig {
+    /// Directory plugins are found in instead of the default one.
+    pub dir: Option<String>,
+    /// Plugins `sync` syncs the output file with, after its other syncs.
+    pub sync: Option<Vec<String>>,
+}
+
+/// What a plugin can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Export,
+    Import,
+    Sync,
+}
+
+impl Capability {
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Export => "export",
+            Capability::Import => "import",
+            Capability::Sync => "sync",
+        }
+    }
+}
+
+/// A plugin of [`Plugins::list`] by name, or why it cannot be used.
+pub type Listed = (String, Result<Plugin, ITrackerError>);
+
+/// The plugins directory.
+#[derive(Debug, Clone)]
+pub struct Plugins {
+    dir: PathBuf,
+    sync: Vec<String>,
+}
+
+impl Plugins {
+    /// Resolves the `[plugins]` section; the directory need not exist.
+    pub fn from_config(config: Option<&PluginsConfig>) -> Self {
+        let dir = match config.and_then(|config| config.dir.as_deref()) {
+            Some(dir) => PathBuf::from(dir),
+            None => config::config_path()
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(PLUGINS_DIR),
+        };
+        Plugins {
+            dir,
+            sync: config
+                .and_then(|config| config.sync.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The plugins `sync` runs, in the configured order.
+    pub fn sync_targets(&self) -> Result<Vec<Plugin>, ITrackerError> {
+        self.sync.iter().map(|name| self.find(name)).collect()
+    }
+
+    /// Every executable in the plugins directory, by name, with its
+    /// description or the reason it cannot be used.
+    pub fn list(&self) -> Result<Vec<Listed>, ITrackerError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(ITrackerError::file(&self.dir)(e)),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(ITrackerError::file(&self.dir))?.path();
+            if is_executable(&path) {
+                if let Some(name) = path.file_stem() {
+                    names.push(name.to_string_lossy().into_owned());
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names
+            .into_iter()
+            .map(|name| {
+                let plugin = self.find(&name);
+                (name, plugin)
+            })
+            .collect())
+    }
+
+    /// The plugin called `name`, asked for what it can do.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: `Plugin` if there is no such plugin or it does
+    ///   not describe itself.
+    pub fn find(&self, name: &str) -> Result<Plugin, ITrackerError> {
+        let path = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.file_stem().is_some_and(|stem| stem == name))
+            .find(|path| is_executable(path))
+            .ok_or_else(|| {
+                ITrackerError::Plugin(format!("No plugin '{}' in {}", name, self.dir.display()))
+            })?;
+        let mut plugin = Plugin {
+            name: name.to_string(),
+            path,
+            description: String::new(),
+            capabilities: Vec::new(),
+        };
+        let described: Description = plugin.call(json!({ "action": "describe" }))?;
+        plugin.description = described.description;
+        plugin.capabilities = described.capabilities;
+        Ok(plugin)
+    }
+}
+
+#[derive(Deserialize)]
+struct Description {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    capabilities: Vec<Capability>,
+}
+
+/// A plugin found in the plugins directory.
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    pub name: String,
+    pub path: PathBuf,
+    pub description: String,
+    pub capabilities: Vec<Capability>,
+}
+
+/// What a sync plugin answered.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Synced {
+    /// Entries to add to the log file.
+    pub entries: Vec<PluginEntry>,
+    /// What the plugin did, to show to the user.
+    pub message: Option<String>,
+}
+
+impl Plugin {
+    /// Renders `entries` of the log file `file` in the plugin's format.
+    pub fn export(
+        &self,
+        file: &str,
+        entries: &[LogEntry],
+        options: &BTreeMap<String, String>,
+    ) -> Result<String, ITrackerError> {
+        #[derive(Deserialize)]
+        struct Exported {
+            output: String,
+        }
+        self.require(Capability::Export)?;
+        let exported: Exported = self.call(json!({
+            "action": "export",
+            "file": file,
+            "entries": entries,
+            "options": options,
+        }))?;
+        Ok(exported.output)
+    }
+
+    /// Reads entries from `input`, the contents of a file in the plugin's
+    /// format.
+    pub fn import(
+        &self,
+        input: &str,
+        options: &BTreeMap<String, String>,
+    ) -> Result<Vec<PluginEntry>, ITrackerError> {
+        #[derive(Deserialize)]
+        struct Imported {
+            entries: Vec<PluginEntry>,
+        }
+        self.require(Capability::Import)?;
+        let imported: Imported = self.call(json!({
+            "action": "import",
+            "input": input,
+            "options": options,
+        }))?;
+        Ok(imported.entries)
+    }
+
+    /// Hands the entries of the log file `file` to the plugin, which sends
+    /// them wherever it syncs to and answers with entries to add.
+    pub fn sync(
+        &self,
+        file: &str,
+        entries: &[LogEntry],
+        options: &BTreeMap<String, String>,
+    ) -> Result<Synced, ITrackerError> {
+        self.require(Capability::Sync)?;
+        self.call(json!({
+            "action": "sync",
+            "file": file,
+            "entries": entries,
+            "options": options,
+        }))
+    }
+
+    fn require(&self, capability: Capability) -> Result<(), ITrackerError> {
+        if self.capabilities.contains(&capability) {
+            Ok(())
+        } else {
+            Err(self.error(format!("cannot {}", capability.name())))
+        }
+    }
+
+    /// Runs the plugin with `request` and decodes its response.
+    fn call<T: for<'de> Deserialize<'de>>(&self, mut request: Value) -> Result<T, ITrackerError> {
+        request["protocol"] = json!(PROTOCOL);
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| self.error(e))?;
+        // Written from another thread, so plugins answering before they read
+        // all of a large request cannot block on a full pipe
+        let mut stdin = child.stdin.take().expect("piped stdin");
+        let writer = thread::spawn(move || {
+            let mut line = request.to_string();
+            line.push('\n');
+            // A plugin that does not read its request may close stdin early
+            let _ = stdin.write_all(line.as_bytes());
+        });
+        let output = child.wait_with_output().map_err(|e| self.error(e))?;
+        let _ = writer.join();
+
+        let response: Value = serde_json::from_slice(&output.stdout).map_err(|e| {
+            if output.status.success() {
+                self.error(format!("invalid response: {}", e))
+            } else {
+                self.error(format!("exited with {}", output.status))
+            }
+        })?;
+        if let Some(error) = response.get("error").and_then(Value::as_str) {
+            return Err(self.error(error));
+        }
+        if !output.status.success() {
+            return Err(self.error(format!("exited with {}", output.status)));
+        }
+        serde_json::from_value(response).map_err(|e| self.error(format!("invalid response: {}", e)))
+    }
+
+    fn error(&self, error: impl std::fmt::Display) -> ITrackerError {
+        ITrackerError::Plugin(format!("Plugin {}: {}", self.name, error))
+    }
+}
+
+/// An entry coming from a plugin; unset fields are empty.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PluginEntry {
+    /// RFC 3339 or RFC 2822 timestamp.
+    pub start_time: String,
+    /// Seconds, `0` for a running timer; a number or a string of one.
+    #[serde(deserialize_with = "seconds")]
+    pub elapsed_time: u64,
+    #[serde(deserialize_with = "seconds")]
+    pub paused_time: u64,
+    pub title: String,
+    pub message: String,
+    pub project: String,
+    pub tags: Vec<String>,
+    pub estimate: Option<u32>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl PluginEntry {
+    /// The record of this entry under `index`.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: `Plugin` if the start time cannot be parsed.
+    pub fn to_record(&self, index: usize) -> Result<Record, ITrackerError> {
+        let start = DateTime::parse_from_rfc3339(self.start_time.trim())
+            .or_else(|_| DateTime::parse_from_rfc2822(self.start_time.trim()))
+            .map_err(|_| {
+                ITrackerError::Plugin(format!("Invalid start time '{}'", self.start_time))
+            })?;
+        Ok(Record {
+            index,
+            start_time: start.to_rfc2822(),
+            message: self.message.clone(),
+            elapsed_time: self.elapsed_time.to_string(),
+            paused_time: self.paused_time.to_string(),
+            title: self.title.clone(),
+            project: self.project.clone(),
+            tags: self.tags.join(","),
+            estimate: self.estimate,
+            paused_since: String::new(),
+            metadata: format_metadata(&self.metadata),
+        })
+    }
+}
+
+/// What adding the entries of a plugin to a log file did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Added {
+    /// Entries written under new indices.
+    pub created: usize,
+    /// Entries the log file already held.
+    pub skipped: usize,
+    /// Entries left out, by their position among the plugin's (from 1) and why.
+    pub failed: Vec<(usize, String)>,
+}
+
+/// Appends the entries of a plugin to the log file at `path` under new
+/// indices, skipping those it already holds and those without a valid
+/// start time.
+///
+/// # Arguments
+/// - `path`: The log file.
+/// - `entries`: The entries of the plugin.
+/// - `progress`: Counts each entry gone through.
+///
+/// # Returns
+/// - `Ok(Added)`: How many entries were created, skipped and failed.
+/// - `Err(ITrackerError)`: An error of reading or writing the file.
+pub fn add_entries(
+    path: &str,
+    entries: &[PluginEntry],
+    progress: &Progress,
+) -> Result<Added, ITrackerError> {
+    let records = if Path::new(path).exists() {
+        schema::read(path)?
+    } else {
+        Vec::new()
+    };
+    let mut known: HashSet<_> = records.iter().filter_map(identity).collect();
+    let mut next = schema::next_index(path, &records)?;
+    let mut outcome = Added::default();
+    let mut added = Vec::new();
+    for (position, entry) in entries.iter().enumerate() {
+        progress.inc();
+        let record = match entry.to_record(next) {
+            Ok(record) => record,
+            Err(e) => {
+                outcome.failed.push((position + 1, e.to_string()));
+                continue;
+            }
+        };
+        if identity(&record).is_some_and(|id| !known.insert(id)) {
+            outcome.skipped += 1;
+            continue;
+        }
+        added.push(record);
+        next += 1;
+    }
+    // Encrypted files are rewritten on each append, so they are written once
+    if crypto::key().is_some() && !added.is_empty() {
+        schema::write(path, records.iter().chain(&added))?;
+    } else {
+        for record in &added {
+            schema::append(path, record)?;
+        }
+    }
+    outcome.created = added.len();
+    Ok(outcome)
+}
+
+/// What makes two records the same piece of work: the start, title and
+/// description; `None` without a valid start.
+fn identity(record: &Record) -> Option<(DateTime<FixedOffset>, String, String)> {
+    let start = DateTime::parse_from_rfc2822(record.start_time.trim()).ok()?;
+    Some((
+        start,
+        record.title.trim().to_string(),
+        record.message.trim().to_string(),
+    ))
+}
+
+/// Accepts seconds as a number or as a string holding one, as the log file
+/// stores them.
+fn seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Seconds {
+        Number(u64),
+        Text(String),
+    }
+    match Seconds::deserialize(deserializer)? {
+        Seconds::Number(seconds) => Ok(seconds),
+        Seconds::Text(text) if text.trim().is_empty() => Ok(0),
+        Seconds::Text(text) => text.trim().parse().map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+        && path.extension().is_some_and(|extension| {
+            ["exe", "bat", "cmd"]
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
@@ -0,0 +1,49 @@
+//! Progress bars for imports and exports of many entries.
+//!
+//! Bars are drawn on stderr and only if it is a terminal, so piped output
+//! and scripts see nothing of them. They show the throughput and the time
+//! left, estimated from the recent rate.
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use std::{fmt, io::IsTerminal};
+
+const TEMPLATE: &str = "{msg} [{bar:30}] {human_pos}/{human_len} entries, {rate}/s, {eta} left";
+
+/// A bar counting the entries an operation went through.
+#[derive(Debug, Clone)]
+pub struct Progress(ProgressBar);
+
+impl Progress {
+    /// A bar for `len` entries, labelled with what is done, e.g. `Importing`.
+    pub fn new(len: usize, action: &str) -> Self {
+        if !std::io::stderr().is_terminal() {
+            return Progress::hidden();
+        }
+        let bar = ProgressBar::with_draw_target(Some(len as u64), ProgressDrawTarget::stderr());
+        if let Ok(style) = ProgressStyle::with_template(TEMPLATE) {
+            let style = style
+                .with_key("rate", |state: &ProgressState, w: &mut dyn fmt::Write| {
+                    let _ = write!(w, "{:.0}", state.per_sec());
+                })
+                .progress_chars("=> ");
+            bar.set_style(style);
+        }
+        bar.set_message(action.to_string());
+        Progress(bar)
+    }
+
+    /// A bar that draws nothing, for callers without a terminal of their own.
+    pub fn hidden() -> Self {
+        Progress(ProgressBar::hidden())
+    }
+
+    /// Counts one more entry.
+    pub fn inc(&self) {
+        self.0.inc(1);
+    }
+
+    /// Removes the bar, so the summary takes its line.
+    pub fn finish(&self) {
+        self.0.finish_and_clear();
+    }
+}
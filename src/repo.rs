@@ -0,0 +1,91 @@
+//! The git repository a task is added in, to describe it after the branch.
+//!
+//! Adding a task inside a git repository prefills its description from
+//! `task_template` and keeps the path of the repository in the metadata of
+//! the entry under [`METADATA_KEY`].
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The description of tasks added inside a git repository unless
+/// `task_template` is set.
+pub const DEFAULT_TEMPLATE: &str = "{repo}:{branch}";
+
+/// The metadata key holding the path of the repository.
+pub const METADATA_KEY: &str = "repo";
+
+/// A git working tree and the branch checked out in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    /// The top-level directory of the working tree.
+    pub path: PathBuf,
+    /// The branch, or the short commit hash on a detached head.
+    pub branch: String,
+}
+
+impl Repository {
+    /// Finds the repository `dir` is in.
+    ///
+    /// # Returns
+    /// - `None`: If `dir` is not inside a git working tree, or git is not
+    ///   installed.
+    pub fn discover(dir: &Path) -> Option<Self> {
+        let path = git(dir, &["rev-parse", "--show-toplevel"])?;
+        let branch = git(dir, &["branch", "--show-current"])
+            .or_else(|| git(dir, &["rev-parse", "--short", "HEAD"]))
+            // A repository without commits yet
+            .unwrap_or_default();
+        Some(Repository {
+            path: PathBuf::from(path),
+            branch,
+        })
+    }
+
+    /// The name of the repository, its directory name.
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Fills in `template`: `{repo}` becomes the name of the repository,
+    /// `{branch}` the branch and `{path}` the path of the working tree.
+    pub fn describe(&self, template: &str) -> String {
+        template
+            .replace("{repo}", &self.name())
+            .replace("{branch}", &self.branch)
+            .replace("{path}", &self.path.to_string_lossy())
+    }
+
+    /// The metadata of entries started in this repository.
+    pub fn metadata(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([(
+            METADATA_KEY.to_string(),
+            self.path.to_string_lossy().into_owned(),
+        )])
+    }
+}
+
+/// Runs git in `dir` and returns its first line of output, `None` if it
+/// failed or printed nothing.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let line = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()?
+        .trim()
+        .to_string();
+    (!line.is_empty()).then_some(line)
+}
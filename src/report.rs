@@ -0,0 +1,248 @@
+use crate::{error::ITrackerError, log::LogEntry};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+use chrono_tz::Tz;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Label used for entries without a project.
+pub const NO_PROJECT: &str = "(no project)";
+
+/// Tracked time aggregated per day and per project, in seconds.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Summary {
+    pub per_day: BTreeMap<NaiveDate, u64>,
+    pub per_project: BTreeMap<String, u64>,
+    pub total: u64,
+    pub entries: usize,
+}
+
+impl Summary {
+    /// Adds the time of `other`, a summary of other entries, e.g. of another
+    /// part of the log.
+    pub fn merge(mut self, other: Summary) -> Summary {
+        for (day, seconds) in other.per_day {
+            *self.per_day.entry(day).or_default() += seconds;
+        }
+        for (project, seconds) in other.per_project {
+            *self.per_project.entry(project).or_default() += seconds;
+        }
+        self.total += other.total;
+        self.entries += other.entries;
+        self
+    }
+}
+
+/// Returns the working time of an entry in seconds, excluding pauses.
+///
+/// Entries that are still running (elapsed time of zero) are measured up to `now`,
+/// a pause in progress counts as paused time.
+pub fn tracked_seconds(entry: &LogEntry, now: DateTime<Utc>) -> u64 {
+    let pausing = entry
+        .paused_since()
+        .map(|since| (now - since.with_timezone(&Utc)).num_seconds().max(0) as u64)
+        .unwrap_or(0);
+    let paused = entry.paused_time.trim().parse::<u64>().unwrap_or(0) + pausing;
+    let elapsed = match entry.elapsed_time.trim().parse::<u64>() {
+        Ok(0) | Err(_) => entry
+            .start()
+            .map(|start| (now - start.with_timezone(&Utc)).num_seconds().max(0) as u64)
+            .unwrap_or(0),
+        Ok(elapsed) => elapsed,
+    };
+    elapsed.saturating_sub(paused)
+}
+
+/// Selects the entries that started between `from` and `to` (both inclusive).
+///
+/// # Arguments
+/// - `entries`: The entries to filter.
+/// - `from`: The first day of the range.
+/// - `to`: The last day of the range.
+/// - `tz`: The timezone the days are counted in.
+///
+/// # Returns
+/// The matching entries in their original order; entries with an unparsable
+/// start time are skipped.
+pub fn entries_between(
+    entries: &[LogEntry],
+    from: NaiveDate,
+    to: NaiveDate,
+    tz: Tz,
+) -> Vec<&LogEntry> {
+    entries
+        .iter()
+        .filter(|entry| {
+            local_date(entry, tz)
+                .map(|date| (from..=to).contains(&date))
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// The day an entry started on in the given timezone.
+pub fn local_date(entry: &LogEntry, tz: Tz) -> Option<NaiveDate> {
+    entry
+        .start()
+        .map(|start| start.with_timezone(&tz).date_naive())
+}
+
+/// The current day in the given timezone.
+pub fn today(now: DateTime<Utc>, tz: Tz) -> NaiveDate {
+    now.with_timezone(&tz).date_naive()
+}
+
+/// The first day of the week and the days that count as workdays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Week {
+    pub start: Weekday,
+    pub workdays: Vec<Weekday>,
+}
+
+impl Default for Week {
+    fn default() -> Self {
+        Week {
+            start: Weekday::Mon,
+            workdays: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+        }
+    }
+}
+
+impl Week {
+    /// Builds the week described by the configuration.
+    ///
+    /// # Arguments
+    /// - `start`: The first day of the week, e.g. `monday` or `sun`; Monday if unset.
+    /// - `workdays`: The days that count as workdays; Monday to Friday if unset.
+    ///
+    /// # Returns
+    /// - `Ok(Week)`: The resolved week.
+    /// - `Err(ITrackerError)`: If a day name is unknown.
+    pub fn from_config(
+        start: Option<&str>,
+        workdays: Option<&[String]>,
+    ) -> Result<Self, ITrackerError> {
+        let mut week = Week::default();
+        if let Some(start) = start {
+            week.start = parse_weekday(start)?;
+        }
+        if let Some(workdays) = workdays {
+            week.workdays = workdays
+                .iter()
+                .map(|day| parse_weekday(day))
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(week)
+    }
+
+    /// Returns the first and last day of the week containing `day`.
+    pub fn range(&self, day: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let days_since_start =
+            (7 + day.weekday().num_days_from_monday() - self.start.num_days_from_monday()) % 7;
+        let first = day - Duration::days(i64::from(days_since_start));
+        (first, first + Duration::days(6))
+    }
+
+    pub fn is_workday(&self, day: NaiveDate) -> bool {
+        self.workdays.contains(&day.weekday())
+    }
+}
+
+fn parse_weekday(name: &str) -> Result<Weekday, ITrackerError> {
+    name.trim().parse::<Weekday>().map_err(|_| {
+        ITrackerError::Config(format!(
+            "Unknown weekday '{}', expected a name like monday or mon",
+            name
+        ))
+    })
+}
+
+/// Aggregates the tracked time of the given entries.
+///
+/// # Arguments
+/// - `entries`: The entries to aggregate.
+/// - `now`: The current time, used for entries that are still running.
+/// - `tz`: The timezone the days are counted in.
+pub fn summarize<'a>(
+    entries: impl IntoIterator<Item = &'a LogEntry>,
+    now: DateTime<Utc>,
+    tz: Tz,
+) -> Summary {
+    let mut summary = Summary::default();
+
+    for entry in entries {
+        let Some(day) = local_date(entry, tz) else {
+            continue;
+        };
+        let seconds = tracked_seconds(entry, now);
+
+        *summary.per_day.entry(day).or_default() += seconds;
+
+        let project = if entry.project.trim().is_empty() {
+            NO_PROJECT.to_string()
+        } else {
+            entry.project.trim().to_string()
+        };
+        *summary.per_project.entry(project).or_default() += seconds;
+
+        summary.total += seconds;
+        summary.entries += 1;
+    }
+
+    summary
+}
+
+/// Entries summarized at once by a thread of [`summarize_in_parallel`].
+const CHUNK: usize = 4096;
+
+/// Aggregates entries like [`summarize`], in chunks on the threads of the
+/// current rayon pool, merging the partial summaries.
+pub fn summarize_in_parallel(entries: &[&LogEntry], now: DateTime<Utc>, tz: Tz) -> Summary {
+    entries
+        .par_chunks(CHUNK)
+        .map(|chunk| summarize(chunk.iter().copied(), now, tz))
+        .reduce(Summary::default, Summary::merge)
+}
+
+/// Converts seconds to fractional hours for display.
+pub fn hours(seconds: u64) -> f64 {
+    seconds as f64 / 3600.0
+}
+
+/// Cells of the progress bars drawn by [`Goal::bar`] in status lines and reports.
+pub const GOAL_BAR_WIDTH: usize = 10;
+
+/// Time tracked on a day measured against the daily goal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Goal {
+    /// Seconds tracked so far.
+    pub tracked: u64,
+    /// Seconds to track, `daily_goal_hours` of the configuration.
+    pub target: u64,
+}
+
+impl Goal {
+    /// The share of the goal reached in percent; above 100 once exceeded.
+    pub fn percent(self) -> u64 {
+        if self.target == 0 {
+            return 100;
+        }
+        self.tracked * 100 / self.target
+    }
+
+    /// The goal as a bar of `width` cells, e.g. `██████░░░░` at 60%; full once reached.
+    pub fn bar(self, width: usize) -> String {
+        let filled = if self.target == 0 {
+            width
+        } else {
+            ((self.tracked.min(self.target) as u128 * width as u128) / self.target as u128) as usize
+        };
+        format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+    }
+}
@@ -0,0 +1,262 @@
+use crate::error::ITrackerError;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// One rounding rule as written in the configuration file.
+///
+/// Unset fields of a per-project rule fall back to the global `[rounding]` rule.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoundingRuleConfig {
+    /// `nearest`, `up` or `down`.
+    pub mode: Option<String>,
+    /// Block size in minutes, e.g. 6 or 15.
+    pub increment: Option<u32>,
+    /// Minimum billable minutes per entry.
+    pub minimum: Option<u32>,
+}
+
+/// The `[rounding]` section of the configuration file.
+///
+/// Durations are rounded per entry when reports are written; the log itself
+/// always keeps the exact seconds. Invoices round by the same rules unless a
+/// named profile is selected for billing, so time can be billed in blocks
+/// while reports stay exact.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoundingConfig {
+    /// `nearest`, `up` or `down`.
+    pub mode: Option<String>,
+    /// Block size in minutes, e.g. 6 or 15.
+    pub increment: Option<u32>,
+    /// Minimum billable minutes per entry.
+    pub minimum: Option<u32>,
+    /// Rules for single projects (clients), overriding the global rule.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub projects: BTreeMap<String, RoundingRuleConfig>,
+    /// Named rules for invoices, selected by `billing` or `clients.<project>.rounding`;
+    /// unset fields mean exact durations.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, RoundingRuleConfig>,
+    /// Profile invoices round by unless their client selects another.
+    pub billing: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Nearest,
+    Up,
+    Down,
+}
+
+/// A resolved rounding rule, working in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    mode: Mode,
+    /// Block size in seconds, 0 when durations are not rounded.
+    increment: u64,
+    /// Minimum billable seconds of an entry with any tracked time.
+    minimum: u64,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule {
+            mode: Mode::Nearest,
+            increment: 0,
+            minimum: 0,
+        }
+    }
+}
+
+impl Rule {
+    fn from_config(config: &RoundingRuleConfig, base: Rule) -> Result<Self, ITrackerError> {
+        let mode = match config.mode.as_deref() {
+            None => base.mode,
+            Some("nearest") => Mode::Nearest,
+            Some("up") => Mode::Up,
+            Some("down") => Mode::Down,
+            Some(other) => {
+                return Err(ITrackerError::Config(format!(
+                    "Unknown rounding mode '{}', expected nearest, up or down",
+                    other
+                )))
+            }
+        };
+        Ok(Rule {
+            mode,
+            increment: config
+                .increment
+                .map_or(base.increment, |minutes| u64::from(minutes) * 60),
+            minimum: config
+                .minimum
+                .map_or(base.minimum, |minutes| u64::from(minutes) * 60),
+        })
+    }
+
+    /// Whether the rule leaves every duration unchanged.
+    pub fn is_exact(&self) -> bool {
+        self.increment == 0 && self.minimum == 0
+    }
+
+    /// Rounds a tracked duration in seconds.
+    ///
+    /// Entries without tracked time stay at zero, everything else is rounded
+    /// to the increment and then raised to the minimum.
+    pub fn apply(&self, seconds: u64) -> u64 {
+        if seconds == 0 {
+            return 0;
+        }
+        let rounded = match self.increment {
+            0 => seconds,
+            step => match self.mode {
+                Mode::Nearest => (seconds + step / 2) / step * step,
+                Mode::Up => seconds.div_ceil(step) * step,
+                Mode::Down => seconds / step * step,
+            },
+        };
+        rounded.max(self.minimum)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.increment == 0 {
+            write!(f, "exact")?;
+        } else {
+            let mode = match self.mode {
+                Mode::Nearest => "nearest",
+                Mode::Up => "up",
+                Mode::Down => "down",
+            };
+            write!(f, "{} {} min", mode, self.increment / 60)?;
+        }
+        if self.minimum > 0 {
+            write!(f, ", at least {} min", self.minimum / 60)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rounding rules applied at report time: a global rule and per-project
+/// overrides, and the named profiles invoices may round by instead.
+#[derive(Debug, Default, Clone)]
+pub struct Rounding {
+    default: Rule,
+    projects: BTreeMap<String, Rule>,
+    profiles: BTreeMap<String, Rule>,
+    billing: Option<String>,
+}
+
+impl Rounding {
+    /// Builds the rules described by the configuration.
+    ///
+    /// # Arguments
+    /// - `config`: The optional `[rounding]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Rounding)`: The resolved rules, exact durations if the section is missing.
+    /// - `Err(ITrackerError)`: If a mode is unknown, or `billing` names no profile.
+    pub fn from_config(config: Option<&RoundingConfig>) -> Result<Self, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(Rounding::default());
+        };
+
+        let global = RoundingRuleConfig {
+            mode: config.mode.clone(),
+            increment: config.increment,
+            minimum: config.minimum,
+        };
+        let default = Rule::from_config(&global, Rule::default())?;
+        let projects = config
+            .projects
+            .iter()
+            .map(|(project, rule)| {
+                Rule::from_config(rule, default)
+                    .map(|rule| (project.clone(), rule))
+                    .map_err(|e| ITrackerError::Config(format!("projects.{}: {}", project, e)))
+            })
+            .collect::<Result<_, _>>()?;
+        let profiles = config
+            .profiles
+            .iter()
+            .map(|(name, rule)| {
+                Rule::from_config(rule, Rule::default())
+                    .map(|rule| (name.clone(), rule))
+                    .map_err(|e| ITrackerError::Config(format!("profiles.{}: {}", name, e)))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        let rounding = Rounding {
+            default,
+            projects,
+            profiles,
+            billing: None,
+        };
+        let billing = config.billing.as_deref().map(str::trim);
+        if let Some(name) = billing {
+            rounding
+                .profile(name)
+                .map_err(|e| ITrackerError::Config(format!("billing: {}", e)))?;
+        }
+        Ok(Rounding {
+            billing: billing.map(str::to_string),
+            ..rounding
+        })
+    }
+
+    /// The rounding profile called `name`.
+    ///
+    /// # Returns
+    /// - `Ok(&Rule)`: The rule of the profile.
+    /// - `Err(ITrackerError)`: `Config` if no such profile is configured.
+    pub fn profile(&self, name: &str) -> Result<&Rule, ITrackerError> {
+        self.profiles.get(name.trim()).ok_or_else(|| {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            ITrackerError::Config(format!(
+                "Unknown rounding profile '{}', configured profiles: {}",
+                name,
+                known.join(", ")
+            ))
+        })
+    }
+
+    /// The rule invoices round entries of `project` by: the profile of its
+    /// client, the `billing` profile, or the rule reports use.
+    ///
+    /// # Arguments
+    /// - `project`: The project of the entries.
+    /// - `profile`: The profile selected by the project's client, if any.
+    pub fn billing_rule_for(&self, project: &str, profile: Option<&str>) -> &Rule {
+        profile
+            .or(self.billing.as_deref())
+            .and_then(|name| self.profiles.get(name))
+            .unwrap_or_else(|| self.rule_for(project))
+    }
+
+    /// The rule for entries of `project`, falling back to the global rule.
+    pub fn rule_for(&self, project: &str) -> &Rule {
+        self.projects.get(project.trim()).unwrap_or(&self.default)
+    }
+
+    /// Rounds the tracked seconds of an entry of `project`.
+    pub fn apply(&self, project: &str, seconds: u64) -> u64 {
+        self.rule_for(project).apply(seconds)
+    }
+
+    /// Whether no rule changes any duration.
+    pub fn is_exact(&self) -> bool {
+        self.default.is_exact() && self.projects.values().all(Rule::is_exact)
+    }
+}
+
+impl fmt::Display for Rounding {
+    /// The global rule followed by the project overrides, e.g. `nearest 6 min; acme: up 15 min`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.default)?;
+        for (project, rule) in &self.projects {
+            write!(f, "; {}: {}", project, rule)?;
+        }
+        Ok(())
+    }
+}
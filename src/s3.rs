@@ -0,0 +1,286 @@
+//! A minimal client for S3-compatible object storage (AWS, MinIO, Backblaze
+//! B2, Wasabi, ...), enough to keep remote backups in a bucket.
+//!
+//! Requests are signed with AWS Signature Version 4.
+
+use crate::error::ITrackerError;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+
+/// An object in the bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    pub key: String,
+    /// Size in bytes.
+    pub size: u64,
+}
+
+/// A bucket on an S3-compatible endpoint.
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    /// E.g. `https://s3.eu-central-1.amazonaws.com`, without a trailing slash.
+    endpoint: String,
+    name: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    /// Address the bucket as `<endpoint>/<bucket>` rather than `<bucket>.<endpoint host>`.
+    path_style: bool,
+}
+
+impl Bucket {
+    pub fn new(
+        endpoint: &str,
+        name: impl Into<String>,
+        region: impl Into<String>,
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        Bucket {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            name: name.into(),
+            region: region.into(),
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            path_style: true,
+        }
+    }
+
+    /// Addresses the bucket as a subdomain of the endpoint, as AWS prefers.
+    pub fn with_virtual_host(mut self) -> Self {
+        self.path_style = false;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn put(&self, key: &str, body: &[u8]) -> Result<(), ITrackerError> {
+        self.send("PUT", key, &[], body)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Result<Vec<u8>, ITrackerError> {
+        let response = self.send("GET", key, &[], &[])?;
+        let mut body = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut body)
+            .map_err(|e| self.error(key, e))?;
+        Ok(body)
+    }
+
+    pub fn delete(&self, key: &str) -> Result<(), ITrackerError> {
+        self.send("DELETE", key, &[], &[])?;
+        Ok(())
+    }
+
+    /// The objects whose key starts with `prefix`, sorted by key.
+    pub fn list(&self, prefix: &str) -> Result<Vec<Object>, ITrackerError> {
+        let mut objects = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![
+                ("list-type", "2".to_string()),
+                ("prefix", prefix.to_string()),
+            ];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.clone()));
+            }
+            let body = self
+                .send("GET", "", &query, &[])?
+                .into_string()
+                .map_err(|e| self.error("", e))?;
+
+            for contents in elements(&body, "Contents") {
+                let key = elements(contents, "Key").next().map(unescape);
+                let size = elements(contents, "Size")
+                    .next()
+                    .and_then(|s| s.parse().ok());
+                if let (Some(key), Some(size)) = (key, size) {
+                    objects.push(Object { key, size });
+                }
+            }
+            token = elements(&body, "NextContinuationToken")
+                .next()
+                .map(unescape);
+            if token.is_none() {
+                break;
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    /// Sends a signed request for the object `key`, or the bucket if empty.
+    fn send(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, String)],
+        body: &[u8],
+    ) -> Result<ureq::Response, ITrackerError> {
+        let (host, path) = self.address(key);
+        let scheme = if self.endpoint.starts_with("http://") {
+            "http"
+        } else {
+            "https"
+        };
+        let mut pairs: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (encode(name, true), encode(value, true)))
+            .collect();
+        pairs.sort();
+        let query: Vec<String> = pairs
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        let query = query.join("&");
+
+        let payload_hash = hex::encode(Sha256::digest(body));
+        let headers = self.sign(method, &host, &path, &query, &payload_hash, Utc::now());
+        let url = match query.is_empty() {
+            true => format!("{}://{}{}", scheme, host, path),
+            false => format!("{}://{}{}?{}", scheme, host, path, query),
+        };
+        let mut request = ureq::request(method, &url);
+        for (name, value) in &headers {
+            request = request.set(name, value);
+        }
+        let result = if body.is_empty() && method != "PUT" {
+            request.call()
+        } else {
+            request.send_bytes(body)
+        };
+        result.map_err(|e| match e {
+            ureq::Error::Status(status, response) => {
+                let body = response.into_string().unwrap_or_default();
+                let message = elements(&body, "Message")
+                    .next()
+                    .map(unescape)
+                    .unwrap_or_else(|| format!("HTTP status {}", status));
+                self.error(key, message)
+            }
+            e => self.error(key, e),
+        })
+    }
+
+    /// The host and the encoded path of `key`.
+    fn address(&self, key: &str) -> (String, String) {
+        let host = self
+            .endpoint
+            .split_once("://")
+            .map_or(self.endpoint.as_str(), |(_, host)| host)
+            .trim_end_matches('/');
+        let key = encode(key, false);
+        if self.path_style {
+            (host.to_string(), format!("/{}/{}", self.name, key))
+        } else {
+            (format!("{}.{}", self.name, host), format!("/{}", key))
+        }
+    }
+
+    /// The headers authorizing a request, following AWS Signature Version 4.
+    ///
+    /// # Arguments
+    /// - `method`: The HTTP method.
+    /// - `host`: The `Host` header.
+    /// - `path`: The encoded path.
+    /// - `query`: The encoded query with sorted parameters, may be empty.
+    /// - `payload_hash`: Hex SHA-256 of the body.
+    /// - `now`: The time of the request.
+    pub fn sign(
+        &self,
+        method: &str,
+        host: &str,
+        path: &str,
+        query: &str,
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Vec<(String, String)> {
+        let time = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, query, host, payload_hash, time, signed_headers, payload_hash
+        );
+        let to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            time,
+            scope,
+            hex::encode(Sha256::digest(canonical.as_bytes()))
+        );
+
+        let mut key = format!("AWS4{}", self.secret_key).into_bytes();
+        for part in [date.as_str(), &self.region, "s3", "aws4_request", &to_sign] {
+            key = hmac(&key, part);
+        }
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key,
+            scope,
+            signed_headers,
+            hex::encode(key)
+        );
+        vec![
+            ("Authorization".to_string(), authorization),
+            ("x-amz-content-sha256".to_string(), payload_hash.to_string()),
+            ("x-amz-date".to_string(), time),
+        ]
+    }
+
+    fn error(&self, key: &str, error: impl std::fmt::Display) -> ITrackerError {
+        ITrackerError::S3(format!("s3://{}/{}: {}", self.name, key, error))
+    }
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes everything but unreserved characters, and `/` unless
+/// `slash` is set, as Signature Version 4 expects.
+fn encode(text: &str, slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The contents of every `<tag>...</tag>` element in `xml`, in order.
+///
+/// S3 answers in plain XML without nested elements of the same name, so
+/// searching for the tags is enough.
+fn elements<'a>(xml: &'a str, tag: &str) -> impl Iterator<Item = &'a str> + 'a {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        let start = rest.find(&open)? + open.len();
+        let end = start + rest[start..].find(&close)?;
+        let content = &rest[start..end];
+        rest = &rest[end + close.len()..];
+        Some(content)
+    })
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
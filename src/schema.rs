@@ -0,0 +1,502 @@
+use crate::{crypto, error::ITrackerError};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Amendments kept in a journal before they should be folded into the log file.
+pub const JOURNAL_LIMIT: usize = 100;
+
+/// Column headers of the log file, in the order of the fields of [`Record`].
+///
+/// Files written before the title, project, tags, estimate, paused-since and
+/// metadata columns existed have fewer columns; records are read by position so every
+/// layout is accepted.
+pub const HEADERS: [&str; 11] = [
+    "Index",
+    "Start Time",
+    "Task Description",
+    "Elapsed Time (seconds)",
+    "Paused Time (seconds)",
+    "Title",
+    "Project",
+    "Tags",
+    "Estimate (minutes)",
+    "Paused Since",
+    "Metadata",
+];
+
+/// One row of the log file exactly as it is stored.
+///
+/// Every read and write of log files goes through this struct, so all
+/// writers produce the same columns in the same order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub index: usize,
+    /// RFC 2822 timestamp.
+    pub start_time: String,
+    #[serde(default)]
+    pub message: String,
+    /// Seconds, `0` while the timer is running and at least `1` once stopped.
+    #[serde(default)]
+    pub elapsed_time: String,
+    /// Seconds.
+    #[serde(default = "zero")]
+    pub paused_time: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub project: String,
+    /// Comma separated.
+    #[serde(default)]
+    pub tags: String,
+    /// Minutes, empty (or unparsable) for no estimate.
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    pub estimate: Option<u32>,
+    /// RFC 2822 timestamp, empty unless paused.
+    #[serde(default)]
+    pub paused_since: String,
+    /// JSON object of string values, e.g. `{"repo":"/home/me/src/app"}`,
+    /// empty for none.
+    #[serde(default)]
+    pub metadata: String,
+}
+
+fn zero() -> String {
+    "0".to_string()
+}
+
+/// Reads every record of a log file in file order, with the amendments
+/// from its journal applied.
+///
+/// # Returns
+/// - `Ok(Vec<Record>)`: The records, empty if the file only has headers.
+/// - `Err(ITrackerError)`: `DuplicateIndex` if records share an index, or
+///   if the file cannot be read or a record is invalid.
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<Record>, ITrackerError> {
+    let path = path.as_ref();
+    let records = read_with_duplicates(path)?;
+    let mut seen = HashSet::new();
+    match records.iter().find(|record| !seen.insert(record.index)) {
+        Some(record) => Err(ITrackerError::DuplicateIndex {
+            path: path.into(),
+            index: record.index,
+        }),
+        None => Ok(records),
+    }
+}
+
+/// Reads every record like [`read`], also of a log in which records share
+/// an index, for [`Storage::compact`](crate::storage::Storage::compact) to
+/// renumber them. An amendment of a shared index applies to the last of them.
+pub fn read_with_duplicates(path: impl AsRef<Path>) -> Result<Vec<Record>, ITrackerError> {
+    let path = path.as_ref();
+    let mut records = read_file(path)?;
+
+    let amendments = read_journal(path)?;
+    if !amendments.is_empty() {
+        let positions: HashMap<usize, usize> = records
+            .iter()
+            .enumerate()
+            .map(|(position, record)| (record.index, position))
+            .collect();
+        // Amendments of entries deleted since are dropped
+        for amendment in amendments {
+            if let Some(&position) = positions.get(&amendment.index) {
+                records[position] = amendment;
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Reads the records of a single CSV file, without its journal.
+fn read_file(path: &Path) -> Result<Vec<Record>, ITrackerError> {
+    let file = File::open(path).map_err(ITrackerError::file(path))?;
+    parse(path, file)
+}
+
+/// Reads the amendments in the journal of a log file, oldest first.
+///
+/// A last line without a newline was cut off while being written and is
+/// ignored, so a crash during an update leaves the previous state.
+pub(crate) fn read_journal(path: &Path) -> Result<Vec<Record>, ITrackerError> {
+    let journal = journal_path(path);
+    let contents = match fs::read_to_string(&journal) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(ITrackerError::file(&journal)(e)),
+    };
+    let complete = &contents[..contents.rfind('\n').map_or(0, |end| end + 1)];
+    parse(&journal, complete.as_bytes())
+}
+
+/// Reads records from CSV `input` with a header row, e.g. an old version of
+/// a log file. Encrypted input is decrypted with the unlocked key first.
+///
+/// # Arguments
+/// - `path`: The file the input belongs to, for error messages.
+/// - `input`: The CSV contents, or the contents of an encrypted log file.
+///
+/// # Returns
+/// - `Ok(Vec<Record>)`: The records.
+/// - `Err(ITrackerError)`: `Encryption` if the input is encrypted and no
+///   key or a different one is unlocked, or an error of reading the CSV.
+pub fn parse(path: &Path, input: impl io::Read) -> Result<Vec<Record>, ITrackerError> {
+    let contents = plain_contents(path, input)?;
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(contents.as_slice());
+
+    let mut records = Vec::new();
+    for (position, result) in reader.records().enumerate() {
+        let row = result.map_err(ITrackerError::csv(path))?;
+        records.push(record_of(path, position + 1, &row)?);
+    }
+    Ok(records)
+}
+
+/// Reads the record of a CSV row.
+///
+/// # Arguments
+/// - `path`: The file the row belongs to, for error messages.
+/// - `number`: The number of the row, counted from 1 after the headers.
+/// - `row`: The row.
+pub(crate) fn record_of(
+    path: &Path,
+    number: usize,
+    row: &StringRecord,
+) -> Result<Record, ITrackerError> {
+    row.deserialize(None)
+        .map_err(|e| ITrackerError::InvalidRecord {
+            path: path.into(),
+            record: number,
+            message: field_error(row, e),
+        })
+}
+
+/// The CSV contents of `input`, decrypted with the unlocked key if encrypted.
+fn plain_contents(path: &Path, mut input: impl io::Read) -> Result<Vec<u8>, ITrackerError> {
+    let mut contents = Vec::new();
+    input
+        .read_to_end(&mut contents)
+        .map_err(ITrackerError::file(path))?;
+    if !crypto::is_encrypted(&contents) {
+        return Ok(contents);
+    }
+    let key = crypto::key().ok_or_else(|| {
+        ITrackerError::Encryption(format!(
+            "{} is encrypted, set encryption.passphrase or encryption.key_file",
+            path.display()
+        ))
+    })?;
+    key.decrypt(&contents)
+        .map_err(|e| ITrackerError::Encryption(format!("{}: {}", path.display(), e)))
+}
+
+/// How a log file is laid out on disk, as far as it differs from what
+/// [`write`] gives it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Layout {
+    /// Whether the header row is exactly [`HEADERS`].
+    pub canonical_headers: bool,
+    /// Rows with fewer columns than [`HEADERS`], e.g. written by old versions.
+    pub short_rows: usize,
+    /// Amendments in the journal not yet folded into the file.
+    pub amendments: usize,
+}
+
+/// Reads how the log file at `path` is laid out.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: If the file or its journal cannot be read.
+pub fn layout(path: impl AsRef<Path>) -> Result<Layout, ITrackerError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(ITrackerError::file(path))?;
+    let contents = plain_contents(path, file)?;
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(contents.as_slice());
+    let mut rows = reader.records();
+    let canonical_headers = match rows.next() {
+        Some(headers) => headers
+            .map_err(ITrackerError::csv(path))?
+            .iter()
+            .eq(HEADERS),
+        None => false,
+    };
+    let mut short_rows = 0;
+    for row in rows {
+        if row.map_err(ITrackerError::csv(path))?.len() < HEADERS.len() {
+            short_rows += 1;
+        }
+    }
+    Ok(Layout {
+        canonical_headers,
+        short_rows,
+        amendments: read_journal(path)?.len(),
+    })
+}
+
+/// The journal holding amendments of the log file at `path`.
+pub fn journal_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".journal")
+}
+
+/// The cache of the running entry of the log file at `path`, see [`crate::status`].
+pub fn active_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".active")
+}
+
+/// The state of the last WebDAV sync of the log file at `path`, see [`crate::webdav`].
+pub fn webdav_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".webdav")
+}
+
+/// The state of the last sync through a shared folder of the log file at
+/// `path`, see [`crate::crdt`].
+pub fn crdt_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".crdt")
+}
+
+/// The focused windows recorded while the log file at `path` was in use,
+/// see [`crate::activity`].
+pub fn activity_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".activity")
+}
+
+/// The expenses recorded next to the log file at `path`, see
+/// [`crate::expense`].
+pub fn expenses_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".expenses")
+}
+
+/// The index of the log file at `path`, see [`crate::index`].
+pub fn index_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".index")
+}
+
+/// The offsets of the rows of the log file at `path`, the part of its
+/// index read only to find single rows, see [`crate::index`].
+pub fn rows_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".rows")
+}
+
+/// The file remembering the highest index given out in the log file at `path`.
+pub fn last_index_path(path: impl AsRef<Path>) -> PathBuf {
+    with_suffix(path.as_ref(), ".last-index")
+}
+
+/// The index for a new record: one above every index the log file ever held,
+/// including those of deleted records, so indices are never reused.
+///
+/// # Arguments
+/// - `path`: The log file.
+/// - `records`: Its current records.
+pub fn next_index(path: impl AsRef<Path>, records: &[Record]) -> Result<usize, ITrackerError> {
+    let file = last_index_path(path.as_ref());
+    let last = match fs::read_to_string(&file) {
+        Ok(contents) => contents.trim().parse().unwrap_or(0),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(ITrackerError::file(&file)(e)),
+    };
+    let highest = records.iter().map(|record| record.index).max().unwrap_or(0);
+    Ok(highest.max(last) + 1)
+}
+
+/// Remembers `index` as the highest index given out in the log file, e.g.
+/// before the record holding it is deleted.
+pub fn set_last_index(path: impl AsRef<Path>, index: usize) -> Result<(), ITrackerError> {
+    let file = last_index_path(path.as_ref());
+    fs::write(&file, format!("{}\n", index)).map_err(ITrackerError::file(&file))
+}
+
+/// Replaces the file at `path` with `contents` through a temporary file
+/// renamed over it, so a crash never leaves it half written and readers,
+/// e.g. a [`MappedLog`](crate::mapped::MappedLog), never see it change
+/// under them.
+///
+/// The index of a log is removed with it: rows may have moved even where
+/// the size and the last row did not change, which the index would take
+/// for an append.
+pub(crate) fn replace(path: &Path, contents: &[u8]) -> Result<(), ITrackerError> {
+    let temporary = with_suffix(path, ".tmp");
+    let mut file = File::create(&temporary).map_err(ITrackerError::file(&temporary))?;
+    file.write_all(contents)
+        .map_err(ITrackerError::file(&temporary))?;
+    file.sync_all().map_err(ITrackerError::file(&temporary))?;
+    fs::rename(&temporary, path).map_err(ITrackerError::file(path))?;
+
+    for index in [index_path(path), rows_path(path)] {
+        match fs::remove_file(&index) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(ITrackerError::file(&index)(e))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Describes why a row could not be read, naming the offending column.
+pub(crate) fn field_error(row: &StringRecord, error: csv::Error) -> String {
+    match error.into_kind() {
+        csv::ErrorKind::Deserialize { err, .. } => match err.field() {
+            Some(field) => format!(
+                "invalid {} '{}': {}",
+                HEADERS.get(field as usize).unwrap_or(&"column"),
+                row.get(field as usize).unwrap_or_default(),
+                err.kind()
+            ),
+            None => err.to_string(),
+        },
+        kind => format!("{:?}", kind),
+    }
+}
+
+/// Replaces the contents of a file with the headers and `records`, and
+/// drops its journal. The file is encrypted if a key is unlocked.
+///
+/// The records are written to a temporary file first and moved over the
+/// old file, so a crash leaves either the old or the new contents.
+///
+/// # Returns
+/// - `Ok(())`: If the file was written.
+/// - `Err(ITrackerError)`: If the file cannot be created or written.
+pub fn write<'a>(
+    path: impl AsRef<Path>,
+    records: impl IntoIterator<Item = &'a Record>,
+) -> Result<(), ITrackerError> {
+    let path = path.as_ref();
+    let contents = contents(path, records)?;
+    replace(path, contents.as_bytes())?;
+
+    // Amendments left over by a crash here are applied again, which is harmless
+    let journal = journal_path(path);
+    match fs::remove_file(&journal) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(ITrackerError::file(&journal)(e)),
+        _ => Ok(()),
+    }
+}
+
+/// The contents `write` gives a log file holding `records`: the CSV text,
+/// or its encryption if a key is unlocked.
+///
+/// # Arguments
+/// - `path`: The file the contents are meant for, for error messages.
+/// - `records`: The records, in file order.
+pub fn contents<'a>(
+    path: impl AsRef<Path>,
+    records: impl IntoIterator<Item = &'a Record>,
+) -> Result<String, ITrackerError> {
+    let csv = to_csv(&path, records)?;
+    match crypto::key() {
+        Some(key) => key.encrypt_armored(csv.as_bytes()),
+        None => Ok(csv),
+    }
+}
+
+/// The CSV text of a log file holding `records`, never encrypted.
+///
+/// # Arguments
+/// - `path`: The file the contents are meant for, for error messages.
+/// - `records`: The records, in file order.
+pub fn to_csv<'a>(
+    path: impl AsRef<Path>,
+    records: impl IntoIterator<Item = &'a Record>,
+) -> Result<String, ITrackerError> {
+    let path = path.as_ref();
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer
+        .write_record(HEADERS)
+        .map_err(ITrackerError::csv(path))?;
+    for record in records {
+        writer.serialize(record).map_err(ITrackerError::csv(path))?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| ITrackerError::file(path)(e.into_error()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Records a change to an existing record by appending it to the journal,
+/// instead of rewriting the whole log file.
+///
+/// Encrypted files cannot be appended to, so with a key unlocked the file
+/// is rewritten with the change instead and no journal is kept.
+///
+/// # Returns
+/// - `Ok(usize)`: The number of amendments in the journal; once it reaches
+///   `JOURNAL_LIMIT` the journal should be folded in with `write`.
+/// - `Err(ITrackerError)`: If the journal cannot be written.
+pub fn amend(path: impl AsRef<Path>, record: &Record) -> Result<usize, ITrackerError> {
+    let path = path.as_ref();
+    if crypto::key().is_some() {
+        let mut records = read(path)?;
+        for existing in records.iter_mut().filter(|r| r.index == record.index) {
+            *existing = record.clone();
+        }
+        write(path, &records)?;
+        return Ok(0);
+    }
+    append_row(&journal_path(path), record)?;
+    Ok(read_journal(path)?.len())
+}
+
+/// Adds `record` to the end of a log file, creating it with headers if needed.
+///
+/// With a key unlocked the whole file is rewritten encrypted, which also
+/// encrypts a file that was plain text so far.
+///
+/// # Returns
+/// - `Ok(())`: If the record was written.
+/// - `Err(ITrackerError)`: If the file cannot be opened or written.
+pub fn append(path: impl AsRef<Path>, record: &Record) -> Result<(), ITrackerError> {
+    let path = path.as_ref();
+    if crypto::key().is_some() {
+        let mut records = if path.exists() {
+            read(path)?
+        } else {
+            Vec::new()
+        };
+        records.push(record.clone());
+        return write(path, &records);
+    }
+    append_row(path, record)
+}
+
+/// Appends `record` as a CSV row, writing the headers first into an empty file.
+fn append_row(path: &Path, record: &Record) -> Result<(), ITrackerError> {
+    let file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .map_err(ITrackerError::file(path))?;
+    let is_empty = file.metadata().map_err(ITrackerError::file(path))?.len() == 0;
+
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(BufWriter::new(file));
+    if is_empty {
+        writer
+            .write_record(HEADERS)
+            .map_err(ITrackerError::csv(path))?;
+    }
+    writer.serialize(record).map_err(ITrackerError::csv(path))?;
+    writer.flush().map_err(ITrackerError::file(path))
+}
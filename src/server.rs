@@ -0,0 +1,238 @@
+//! The `serve` command: a JSON API on localhost for browser extensions and
+//! other tools.
+//!
+//! | Request                               | Answer                               |
+//! |---------------------------------------|--------------------------------------|
+//! | `GET /entries`                        | All entries                          |
+//! | `GET /entries/{index}`                | One entry                            |
+//! | `POST /entries` with a task           | The started entry                    |
+//! | `POST /entries/{index}/{action}`      | The stopped, paused or resumed entry |
+//! | `GET /running`                        | The running entry                    |
+//! | `POST /running/{action}`              | As above, for the running entry      |
+//! | `GET /report?from=YYYY-MM-DD&to=...`  | Tracked time per day and project     |
+//!
+//! `GET /` answers with a page built on these requests, showing the running
+//! timer and the latest entries with buttons to start, pause and stop.
+//!
+//! Errors are answered with `{"error": "..."}`. POST requests must be sent
+//! with `Content-Type: application/json`, which web pages cannot do without
+//! the server's permission, so other sites cannot start or stop timers
+//! through the browser. Requests whose `Host` is not `127.0.0.1:<port>` or
+//! `localhost:<port>` are refused with 403, so a site whose name is made to
+//! resolve to 127.0.0.1 (DNS rebinding) cannot read the log either.
+
+use crate::{
+    config::ConfigData,
+    daemon::{Operation, Timers},
+    error::ITrackerError,
+    log::TaskDetails,
+    month,
+    report::today,
+};
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// The web UI, a single page without external resources.
+const PAGE: &str = include_str!("../web/index.html");
+
+/// An answer that is not the JSON the client asked for.
+pub(crate) struct Failure {
+    status: u16,
+    message: String,
+}
+
+impl Failure {
+    pub(crate) fn new(status: u16, message: impl Into<String>) -> Self {
+        Failure {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ITrackerError> for Failure {
+    fn from(e: ITrackerError) -> Self {
+        let status = match e {
+            ITrackerError::EntryNotFound { .. } | ITrackerError::NoActiveTimer { .. } => 404,
+            ITrackerError::NotRunning { .. }
+            | ITrackerError::AlreadyPaused { .. }
+            | ITrackerError::NotPaused { .. }
+            | ITrackerError::Locked { .. }
+            | ITrackerError::Invoiced { .. } => 409,
+            // The daemon only refuses operations the state of the entry does not allow
+            ITrackerError::Daemon(_) => 409,
+            _ => 500,
+        };
+        Failure::new(status, e.to_string())
+    }
+}
+
+/// Answers requests on `127.0.0.1:port` until the process is stopped.
+///
+/// # Arguments
+/// - `port`: The port to listen on.
+/// - `timers`: The log file the API works on.
+/// - `config`: Used for the timezone of reports and for month locks.
+pub fn serve(port: u16, timers: Timers, config: &ConfigData) -> Result<(), ITrackerError> {
+    let server = Server::http(("127.0.0.1", port))
+        .map_err(|e| ITrackerError::Config(format!("Cannot listen on port {}: {}", port, e)))?;
+    println!(
+        "Serving {} on http://127.0.0.1:{}",
+        timers.tracker().storage().path(),
+        port
+    );
+
+    for mut request in server.incoming_requests() {
+        if !is_local(&request, port) {
+            let (status, body) = answer(Err(Failure::new(
+                403,
+                format!(
+                    "Requests must be addressed to 127.0.0.1:{} or localhost:{}",
+                    port, port
+                ),
+            )));
+            send(request, status, body, "application/json");
+        } else if *request.method() == Method::Get
+            && (request.url() == "/" || request.url() == "/index.html")
+        {
+            send(request, 200, PAGE.to_string(), "text/html; charset=utf-8");
+        } else {
+            let (status, body) = answer(handle(&mut request, &timers, config));
+            send(request, status, body, "application/json");
+        }
+    }
+    Ok(())
+}
+
+/// The status and JSON body answering a request, errors as `{"error": "..."}`.
+pub(crate) fn answer(result: Result<String, Failure>) -> (u16, String) {
+    match result {
+        Ok(body) => (200, body),
+        Err(failure) => (
+            failure.status,
+            serde_json::json!({ "error": failure.message }).to_string(),
+        ),
+    }
+}
+
+/// Sends an answer, reporting failures to send it on stderr.
+pub(crate) fn send(request: Request, status: u16, body: String, content_type: &str) {
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", content_type).expect("valid header"));
+    if let Err(e) = request.respond(response) {
+        eprintln!("Error: {}", e);
+    }
+}
+
+/// Routes a request, returning the JSON body of a successful answer.
+fn handle(request: &mut Request, timers: &Timers, config: &ConfigData) -> Result<String, Failure> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let tracker = timers.tracker();
+
+    if *request.method() == Method::Post && !is_json(request) {
+        return Err(Failure::new(
+            415,
+            "POST requests need Content-Type: application/json",
+        ));
+    }
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["entries"]) => json(&tracker.storage().entries()?),
+        (Method::Get, ["entries", index]) => json(&tracker.storage().entry(parse_index(index)?)?),
+        (Method::Post, ["entries"]) => {
+            let mut body = String::new();
+            request
+                .as_reader()
+                .read_to_string(&mut body)
+                .map_err(ITrackerError::from)?;
+            let task: TaskDetails = serde_json::from_str(&body)
+                .map_err(|e| Failure::new(400, format!("Invalid task: {}", e)))?;
+            json(&timers.run(Operation::Start { task })?)
+        }
+        (Method::Post, ["entries", index, action]) => {
+            let index = parse_index(index)?;
+            month::ensure_unlocked(config, tracker.storage(), index, false)?;
+            json(&timers.run(operation(action, Some(index))?)?)
+        }
+        (Method::Get, ["running"]) => json(&timers.run(Operation::Running)?),
+        (Method::Post, ["running", action]) => json(&timers.run(operation(action, None)?)?),
+        (Method::Get, ["report"]) => {
+            let tz = config.timezone();
+            let day = today(Utc::now(), tz);
+            let from = parse_date(query, "from")?.unwrap_or(day);
+            let to = parse_date(query, "to")?.unwrap_or(from.max(day));
+            json(&tracker.summary(from, to, tz)?)
+        }
+        _ => Err(Failure::new(
+            404,
+            format!("No such endpoint: {} {}", request.method(), path),
+        )),
+    }
+}
+
+/// Whether the `Host` of the request is this server under a local name.
+fn is_local(request: &Request, port: u16) -> bool {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Host"))
+        .and_then(|header| header.value.as_str().rsplit_once(':'))
+        .is_some_and(|(host, host_port)| {
+            (host == "127.0.0.1" || host.eq_ignore_ascii_case("localhost"))
+                && host_port.parse() == Ok(port)
+        })
+}
+
+fn is_json(request: &Request) -> bool {
+    request.headers().iter().any(|header| {
+        header.field.equiv("Content-Type") && header.value.as_str().starts_with("application/json")
+    })
+}
+
+pub(crate) fn json<T: Serialize>(value: &T) -> Result<String, Failure> {
+    serde_json::to_string(value).map_err(|e| Failure::new(500, e.to_string()))
+}
+
+fn operation(action: &str, index: Option<usize>) -> Result<Operation, Failure> {
+    match action {
+        "stop" => Ok(Operation::Stop { index }),
+        "pause" => Ok(Operation::Pause { index }),
+        "resume" => Ok(Operation::Resume { index }),
+        _ => Err(Failure::new(
+            404,
+            format!(
+                "Unknown action '{}', expected stop, pause or resume",
+                action
+            ),
+        )),
+    }
+}
+
+fn parse_index(index: &str) -> Result<usize, Failure> {
+    index
+        .parse()
+        .map_err(|_| Failure::new(400, format!("Invalid index '{}'", index)))
+}
+
+/// The date given as `key=YYYY-MM-DD` in the query string, if any.
+pub(crate) fn parse_date(query: &str, key: &str) -> Result<Option<NaiveDate>, Failure> {
+    let Some(value) = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(k, v)| (k == key).then_some(v))
+    else {
+        return Ok(None);
+    };
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| {
+            Failure::new(
+                400,
+                format!("Invalid {} '{}', expected YYYY-MM-DD", key, value),
+            )
+        })
+}
@@ -0,0 +1,323 @@
+//! Encrypted snapshots of all tracked data in S3-compatible storage.
+//!
+//! A snapshot is a gzipped tar file holding the data directory under
+//! `data/` and, if it lives elsewhere, the output file with its closed-month
+//! archive under `output/`, as a [`crate::dump`] does but without the config
+//! file. Log files are stored with their journals folded in. The tar file
+//! is encrypted with age before it leaves the machine, so the storage
+//! provider only ever sees ciphertext.
+
+use crate::{
+    backup::{Backups, Retention},
+    crypto::Key,
+    dump,
+    error::ITrackerError,
+    s3::Bucket,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Hours between scheduled snapshots unless configured otherwise.
+pub const DEFAULT_INTERVAL_HOURS: u64 = 24;
+
+/// Region signed into requests unless configured otherwise; most
+/// S3-compatible providers accept it.
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// Object names are `itracker-<time>.tar.gz.age`.
+const NAME_PREFIX: &str = "itracker-";
+const NAME_SUFFIX: &str = ".tar.gz.age";
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// How often the schedule checks whether a snapshot is due.
+const POLL: Duration = Duration::from_secs(60);
+/// How long the schedule waits after a failed upload before trying again.
+const RETRY: Duration = Duration::from_secs(15 * 60);
+
+/// The `[backup.remote]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteBackupConfig {
+    /// URL of the S3 endpoint, e.g. `https://s3.eu-central-1.amazonaws.com`.
+    pub endpoint: Option<String>,
+    pub bucket: Option<String>,
+    /// Region the bucket is in, `us-east-1` by default.
+    pub region: Option<String>,
+    /// Prefix of the object names, e.g. `laptop/`.
+    pub prefix: Option<String>,
+    pub access_key: Option<String>,
+    /// Better given as `ITRACKER_BACKUP__REMOTE__SECRET_KEY`.
+    pub secret_key: Option<String>,
+    /// Address the bucket as `<bucket>.<endpoint host>` instead of
+    /// `<endpoint>/<bucket>`.
+    pub virtual_host: Option<bool>,
+    /// Passphrase the snapshots are encrypted with.
+    pub passphrase: Option<String>,
+    /// age key file (`age-keygen`) the snapshots are encrypted with instead.
+    pub key_file: Option<String>,
+    /// Hours between snapshots taken by the daemon, `0` turns the schedule off.
+    pub interval_hours: Option<u64>,
+    /// Number of latest snapshots kept; without any `keep` setting all are kept.
+    pub keep: Option<usize>,
+    /// Days whose last snapshot is kept besides the latest ones.
+    pub keep_daily: Option<usize>,
+    /// Weeks whose last snapshot is kept besides the latest ones.
+    pub keep_weekly: Option<usize>,
+}
+
+/// A snapshot stored in the bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Object name without the configured prefix.
+    pub name: String,
+    pub taken: DateTime<Utc>,
+    /// Size of the encrypted snapshot in bytes.
+    pub size: u64,
+}
+
+/// Snapshots kept in a bucket of S3-compatible storage.
+#[derive(Debug, Clone)]
+pub struct RemoteBackups {
+    bucket: Bucket,
+    prefix: String,
+    passphrase: Option<String>,
+    key_file: Option<String>,
+    interval_hours: u64,
+    /// Which snapshots are kept, `None` to keep all.
+    retention: Option<Retention>,
+}
+
+impl RemoteBackups {
+    /// Resolves the `[backup.remote]` section.
+    ///
+    /// The key file is only read when a snapshot is encrypted or decrypted.
+    ///
+    /// # Returns
+    /// - `Ok(Some(RemoteBackups))`: If an endpoint is configured.
+    /// - `Ok(None)`: If there are no remote backups.
+    /// - `Err(ITrackerError)`: `Config` if the endpoint is not an http(s) URL,
+    ///   or the bucket, the credentials or the encryption key are missing.
+    pub fn from_config(config: Option<&RemoteBackupConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        let Some(endpoint) = config.endpoint.as_deref().filter(|e| !e.is_empty()) else {
+            return Ok(None);
+        };
+        if !(endpoint.starts_with("https://") || endpoint.starts_with("http://")) {
+            return Err(ITrackerError::Config(format!(
+                "endpoint must start with https:// or http://, got {}",
+                endpoint
+            )));
+        }
+        let required = |value: &Option<String>, key: &str| {
+            value
+                .clone()
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| ITrackerError::Config(format!("{} is missing", key)))
+        };
+        let bucket = required(&config.bucket, "bucket")?;
+        let access_key = required(&config.access_key, "access_key")?;
+        let secret_key = required(&config.secret_key, "secret_key")?;
+        match (&config.passphrase, &config.key_file) {
+            (None, None) => {
+                return Err(ITrackerError::Config(
+                    "set passphrase or key_file, snapshots are always encrypted".to_string(),
+                ))
+            }
+            (Some(_), Some(_)) => {
+                return Err(ITrackerError::Config(
+                    "set either passphrase or key_file, not both".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        let region = config.region.as_deref().unwrap_or(DEFAULT_REGION);
+        let bucket = Bucket::new(endpoint, bucket, region, access_key, secret_key);
+        Ok(Some(RemoteBackups {
+            bucket: match config.virtual_host {
+                Some(true) => bucket.with_virtual_host(),
+                _ => bucket,
+            },
+            prefix: config.prefix.clone().unwrap_or_default(),
+            passphrase: config.passphrase.clone(),
+            key_file: config.key_file.clone(),
+            interval_hours: config.interval_hours.unwrap_or(DEFAULT_INTERVAL_HOURS),
+            retention: match (config.keep, config.keep_daily, config.keep_weekly) {
+                (None, None, None) => None,
+                (last, daily, weekly) => Some(Retention {
+                    last: last.unwrap_or(0),
+                    daily: daily.unwrap_or(0),
+                    weekly: weekly.unwrap_or(0),
+                }),
+            },
+        }))
+    }
+
+    /// Where snapshots go, for messages.
+    pub fn location(&self) -> String {
+        format!("s3://{}/{}", self.bucket.name(), self.prefix)
+    }
+
+    /// Takes a snapshot and uploads it.
+    ///
+    /// # Arguments
+    /// - `data_dir`: The data directory.
+    /// - `output_file`: The output file, included separately if it lives
+    ///   outside the data directory.
+    ///
+    /// # Returns
+    /// - `Ok(Snapshot)`: The uploaded snapshot.
+    /// - `Err(ITrackerError)`: If a file cannot be read, or encrypting or
+    ///   uploading fails.
+    pub fn upload(&self, data_dir: &Path, output_file: &Path) -> Result<Snapshot, ITrackerError> {
+        let archive = dump::pack(dump::read_files(data_dir, output_file)?)?;
+        let encrypted = self.key()?.encrypt(&archive)?;
+        let taken = Utc::now();
+        let name = format!(
+            "{}{}{}",
+            NAME_PREFIX,
+            taken.format(TIME_FORMAT),
+            NAME_SUFFIX
+        );
+        self.bucket
+            .put(&format!("{}{}", self.prefix, name), &encrypted)?;
+        Ok(Snapshot {
+            name,
+            taken,
+            size: encrypted.len() as u64,
+        })
+    }
+
+    /// The snapshots in the bucket, oldest first.
+    pub fn list(&self) -> Result<Vec<Snapshot>, ITrackerError> {
+        let objects = self
+            .bucket
+            .list(&format!("{}{}", self.prefix, NAME_PREFIX))?;
+        Ok(objects
+            .into_iter()
+            .filter_map(|object| {
+                let name = object.key.strip_prefix(&self.prefix)?.to_string();
+                let time = name.strip_prefix(NAME_PREFIX)?.strip_suffix(NAME_SUFFIX)?;
+                let taken = NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+                    .ok()?
+                    .and_utc();
+                Some(Snapshot {
+                    name,
+                    taken,
+                    size: object.size,
+                })
+            })
+            .collect())
+    }
+
+    /// Deletes the snapshots the retention policy no longer keeps.
+    ///
+    /// # Arguments
+    /// - `dry_run`: Only report what would be deleted.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<Snapshot>)`: The snapshots deleted, oldest first; none
+    ///   without a `keep` setting.
+    /// - `Err(ITrackerError)`: If listing or deleting fails.
+    pub fn prune(&self, dry_run: bool) -> Result<Vec<Snapshot>, ITrackerError> {
+        let Some(retention) = self.retention else {
+            return Ok(Vec::new());
+        };
+        let snapshots = self.list()?;
+        let times: Vec<DateTime<Utc>> = snapshots.iter().map(|s| s.taken).collect();
+        let mut deleted = Vec::new();
+        for (snapshot, keep) in snapshots.into_iter().zip(retention.select(&times)) {
+            if !keep {
+                if !dry_run {
+                    self.bucket
+                        .delete(&format!("{}{}", self.prefix, snapshot.name))?;
+                }
+                deleted.push(snapshot);
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Downloads a snapshot and puts its files back in place.
+    ///
+    /// Files that differ from the snapshot are backed up locally before they
+    /// are replaced; files that are not in the snapshot are left alone.
+    ///
+    /// # Arguments
+    /// - `name`: The snapshot, as returned by `list`.
+    /// - `data_dir`: The data directory to restore `data/` into.
+    /// - `output_file`: The output file, whose directory `output/` is restored into.
+    /// - `backups`: Where replaced files are backed up.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<PathBuf>)`: The files that were replaced or created.
+    /// - `Err(ITrackerError)`: `Encryption` if the key does not fit, `Backup`
+    ///   if the snapshot is damaged, or an error of downloading or writing.
+    pub fn restore(
+        &self,
+        name: &str,
+        data_dir: &Path,
+        output_file: &Path,
+        backups: &Backups,
+    ) -> Result<Vec<PathBuf>, ITrackerError> {
+        let encrypted = self.bucket.get(&format!("{}{}", self.prefix, name))?;
+        let archive = self.key()?.decrypt(&encrypted)?;
+        let files = dump::unpack(&archive, name)?;
+        dump::put_back(&files, data_dir, output_file, backups, name)
+    }
+
+    /// Takes a snapshot every `interval_hours` in the background, for the
+    /// daemon, and prunes the old ones after each. The first one is due when
+    /// the latest snapshot in the bucket is older than the interval.
+    ///
+    /// # Returns
+    /// - `false`: If the schedule is turned off, nothing is started.
+    pub fn schedule(self, data_dir: PathBuf, output_file: PathBuf) -> bool {
+        if self.interval_hours == 0 {
+            return false;
+        }
+        let interval = chrono::Duration::hours(self.interval_hours as i64);
+        thread::spawn(move || {
+            let mut last = match self.list() {
+                Ok(snapshots) => snapshots.last().map(|snapshot| snapshot.taken),
+                Err(e) => {
+                    eprintln!("Cannot list remote backups: {}", e);
+                    None
+                }
+            };
+            loop {
+                if last.is_none_or(|last| Utc::now() - last >= interval) {
+                    match self.upload(&data_dir, &output_file) {
+                        Ok(snapshot) => {
+                            println!("Uploaded backup {} to {}", snapshot.name, self.location());
+                            last = Some(snapshot.taken);
+                            if let Err(e) = self.prune(false) {
+                                eprintln!("Cannot prune remote backups: {}", e);
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("Remote backup failed: {}", e);
+                            thread::sleep(RETRY);
+                            continue;
+                        }
+                    }
+                }
+                thread::sleep(POLL);
+            }
+        });
+        true
+    }
+
+    fn key(&self) -> Result<Key, ITrackerError> {
+        Key::from_config(self.passphrase.as_deref(), self.key_file.as_deref())?.ok_or_else(|| {
+            ITrackerError::Config("set passphrase or key_file in [backup.remote]".to_string())
+        })
+    }
+}
@@ -0,0 +1,448 @@
+//! The running timer in one line, for status bars such as waybar, polybar
+//! and i3blocks, for the tmux status line and for shell prompts.
+//!
+//! Bars ask every few seconds, so the running entry is cached in
+//! `<log>.active` together with the size and modification time of the log
+//! file and its journal. While neither changed, the cache answers without
+//! parsing the log; any write to the log makes the next call read it again.
+//! The entries of the last two days are cached with it, for the progress
+//! toward the daily goal. Encrypted logs are read every time instead.
+
+use crate::{
+    crypto,
+    error::ITrackerError,
+    icons,
+    index::LogIndex,
+    log::LogEntry,
+    report::{self, Goal, GOAL_BAR_WIDTH},
+    schema,
+    storage::Storage,
+    timefmt::{self, DurationFormat},
+};
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path, str::FromStr, time::UNIX_EPOCH};
+
+/// Titles longer than this are shortened to fit into a bar.
+const MAX_TITLE: usize = 30;
+
+/// Size and modification time of a file, to notice when it changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Stamp {
+    pub(crate) len: u64,
+    /// Nanoseconds since the Unix epoch.
+    modified: u128,
+}
+
+/// Contents of `<log>.active`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cache {
+    log: Option<Stamp>,
+    journal: Option<Stamp>,
+    running: Option<LogEntry>,
+    /// The entries started in the two days before the cache was written,
+    /// enough for any later day in any timezone.
+    recent: Vec<LogEntry>,
+}
+
+/// The stamp of `path`, `None` if it does not exist.
+pub(crate) fn stamp(path: &Path) -> Result<Option<Stamp>, ITrackerError> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ITrackerError::file(path)(e)),
+    };
+    let modified = metadata
+        .modified()
+        .map_err(ITrackerError::file(path))?
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos())
+        .unwrap_or(0);
+    Ok(Some(Stamp {
+        len: metadata.len(),
+        modified,
+    }))
+}
+
+/// The most recently started entry that is still running, like
+/// [`Tracker::running`](crate::tracker::Tracker::running), but answered from
+/// the cache while the log is unchanged.
+///
+/// # Returns
+/// - `Ok(None)`: If nothing runs or the log file does not exist yet.
+/// - `Err(ITrackerError)`: If the log has to be read and cannot be.
+pub fn active(path: &str) -> Result<Option<LogEntry>, ITrackerError> {
+    Ok(cached(path)?.and_then(|cache| cache.running))
+}
+
+/// The time tracked today, running timers included, answered from the cache
+/// like [`active`].
+///
+/// # Arguments
+/// - `path`: The log file.
+/// - `now`: The current time.
+/// - `tz`: The timezone the day is counted in.
+pub fn tracked_today(path: &str, now: DateTime<Utc>, tz: Tz) -> Result<u64, ITrackerError> {
+    let Some(cache) = cached(path)? else {
+        return Ok(0);
+    };
+    let today = report::today(now, tz);
+    Ok(report::summarize(
+        report::entries_between(&cache.recent, today, today, tz),
+        now,
+        tz,
+    )
+    .total)
+}
+
+/// Reads `<log>.active`, or the log when the cache is outdated or missing.
+///
+/// # Returns
+/// `None` if the log file does not exist yet.
+fn cached(path: &str) -> Result<Option<Cache>, ITrackerError> {
+    let Some(log) = stamp(Path::new(path))? else {
+        return Ok(None);
+    };
+    let cache_path = schema::active_path(path);
+    // The cache would keep the title of an encrypted log in plain text
+    if crypto::key().is_some() {
+        let _ = fs::remove_file(&cache_path);
+        return read(path, None, None).map(Some);
+    }
+
+    let journal = stamp(&schema::journal_path(path))?;
+    if let Some(cache) = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Cache>(&contents).ok())
+    {
+        if cache.log == Some(log) && cache.journal == journal {
+            return Ok(Some(cache));
+        }
+    }
+
+    // Stamps taken before reading: a write in between makes the next call read again
+    let cache = read(path, Some(log), journal)?;
+    // Without the cache later calls are only slower
+    if let Ok(contents) = serde_json::to_string(&cache) {
+        let partial = cache_path.with_extension(format!("active.{}", std::process::id()));
+        if fs::write(&partial, contents).is_ok() && fs::rename(&partial, &cache_path).is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+    }
+    Ok(Some(cache))
+}
+
+/// Reads the running and recent entries from the log, through its index
+/// unless it is encrypted.
+fn read(path: &str, log: Option<Stamp>, journal: Option<Stamp>) -> Result<Cache, ITrackerError> {
+    let since = Utc::now() - Duration::days(2);
+    if let Some(index) = LogIndex::load(path)? {
+        return Ok(Cache {
+            log,
+            journal,
+            running: index.running()?,
+            recent: index.started_since(since)?,
+        });
+    }
+    let entries = Storage::new(path).entries()?;
+    let running = entries
+        .iter()
+        .filter(|entry| entry.is_running())
+        .filter_map(|entry| entry.start().map(|start| (start, entry)))
+        .max_by_key(|(start, _)| *start)
+        .map(|(_, entry)| entry.clone());
+    let recent = entries
+        .into_iter()
+        .filter(|entry| entry.start().is_some_and(|start| start >= since))
+        .collect();
+    Ok(Cache {
+        log,
+        journal,
+        running,
+        recent,
+    })
+}
+
+/// How `status` prints the running timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusFormat {
+    /// `▶ Review 1h 5m`, nothing when idle; with a daily goal followed by
+    /// the progress toward it, `███░░░░░░░ 30%`, which also shows when idle.
+    #[default]
+    Text,
+    /// JSON for a waybar `custom` module with `"return-type": "json"`.
+    Waybar,
+    /// Text with polybar color tags.
+    Polybar,
+    /// The full text, short text and color lines of an i3blocks block.
+    I3blocks,
+}
+
+impl FromStr for StatusFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(StatusFormat::Text),
+            "waybar" => Ok(StatusFormat::Waybar),
+            "polybar" => Ok(StatusFormat::Polybar),
+            "i3blocks" => Ok(StatusFormat::I3blocks),
+            other => Err(format!(
+                "Unknown status format '{}', expected text, waybar, polybar or i3blocks",
+                other
+            )),
+        }
+    }
+}
+
+/// Whether and how a timer runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Idle,
+    Running,
+    Paused,
+}
+
+impl State {
+    pub fn of(running: Option<&LogEntry>) -> Self {
+        match running {
+            None => State::Idle,
+            Some(entry) if entry.paused_since().is_some() => State::Paused,
+            Some(_) => State::Running,
+        }
+    }
+
+    /// Symbol shown before the task, as configured in `[icons]`.
+    pub fn icon(self) -> &'static str {
+        let icons = icons::current();
+        match self {
+            State::Idle => &icons.stopped,
+            State::Running => &icons.running,
+            State::Paused => &icons.paused,
+        }
+    }
+
+    /// Color for bars that color the segment, as `#rrggbb`.
+    fn color(self) -> &'static str {
+        match self {
+            State::Idle => "#888888",
+            State::Running => "#98c379",
+            State::Paused => "#e5c07b",
+        }
+    }
+}
+
+impl fmt::Display for State {
+    /// `idle`, `running` or `paused`, used as CSS class by waybar.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            State::Idle => "idle",
+            State::Running => "running",
+            State::Paused => "paused",
+        })
+    }
+}
+
+/// The title of `entry`, shortened to fit into a bar.
+pub fn short_title(entry: &LogEntry) -> String {
+    let title = entry.display_title();
+    if title.chars().count() <= MAX_TITLE {
+        return title.to_string();
+    }
+    let mut short: String = title.chars().take(MAX_TITLE - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Renders the running timer in `format`.
+///
+/// # Arguments
+/// - `format`: The output format.
+/// - `running`: The running entry, `None` when idle.
+/// - `now`: The current time, for the tracked time so far.
+/// - `durations`: How the tracked time is shown.
+/// - `goal`: Today's progress toward the daily goal, if one is configured.
+/// - `tz`: The timezone the start time in tooltips is shown in.
+///
+/// # Returns
+/// The lines to print; empty text when idle and without a goal, so bars
+/// hide the segment.
+pub fn render(
+    format: StatusFormat,
+    running: Option<&LogEntry>,
+    now: DateTime<Utc>,
+    durations: DurationFormat,
+    goal: Option<Goal>,
+    tz: Tz,
+) -> String {
+    let state = State::of(running);
+    let (title, tracked) = match running {
+        Some(entry) => (
+            short_title(entry),
+            durations.format(report::tracked_seconds(entry, now)),
+        ),
+        None => (String::new(), String::new()),
+    };
+    let progress = goal
+        .map(|goal| format!("{} {}%", goal.bar(GOAL_BAR_WIDTH), goal.percent()))
+        .unwrap_or_default();
+    let text = match (state, goal) {
+        (State::Idle, _) => progress.clone(),
+        (_, None) => format!("{} {} {}", state.icon(), title, tracked),
+        (_, Some(_)) => format!("{} {} {} {}", state.icon(), title, tracked, progress),
+    };
+
+    match format {
+        StatusFormat::Text => text,
+        StatusFormat::Waybar => {
+            let tooltip = match running {
+                Some(entry) => format!(
+                    "{}\n{} tracked{}{}",
+                    entry.display_title(),
+                    tracked,
+                    match entry.start() {
+                        Some(start) => format!(
+                            ", started {}",
+                            timefmt::relative(&start.with_timezone(&tz), &now.with_timezone(&tz))
+                        ),
+                        None => String::new(),
+                    },
+                    if state == State::Paused {
+                        ", paused"
+                    } else {
+                        ""
+                    }
+                ),
+                None => "No timer running".to_string(),
+            };
+            let mut module = serde_json::json!({
+                "text": text,
+                "alt": state.to_string(),
+                "tooltip": tooltip,
+                "class": state.to_string(),
+            });
+            if let Some(goal) = goal {
+                module["tooltip"] = format!(
+                    "{}\nToday {} of {}",
+                    tooltip,
+                    durations.format(goal.tracked),
+                    durations.format(goal.target)
+                )
+                .into();
+                module["percentage"] = goal.percent().min(100).into();
+            }
+            module.to_string()
+        }
+        StatusFormat::Polybar => match state {
+            State::Idle => progress,
+            _ => format!(
+                "%{{F{}}}{}%{{F-}} {} {}{}",
+                state.color(),
+                state.icon(),
+                title,
+                tracked,
+                if progress.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}", progress)
+                }
+            ),
+        },
+        StatusFormat::I3blocks => match state {
+            State::Idle if progress.is_empty() => String::new(),
+            State::Idle => format!("{}\n{}\n{}", progress, progress, state.color()),
+            _ => format!("{}\n{} {}\n{}", text, state.icon(), tracked, state.color()),
+        },
+    }
+}
+
+/// Tracked time as `h:mm`, short enough for a status line.
+pub fn compact(seconds: u64) -> String {
+    format!("{}:{:02}", seconds / 3600, seconds / 60 % 60)
+}
+
+/// Renders the running timer as a tmux status-line segment, e.g.
+/// `#[fg=#98c379]▶#[default] Review 1:05`, for `#(Itraker tmux)`.
+///
+/// tmux reads style tags such as `#[fg=red]` in the output of `#()`, so `#`
+/// in titles is doubled to show up as is.
+///
+/// # Returns
+/// The segment; empty when idle, so the status line shows nothing.
+pub fn tmux(running: Option<&LogEntry>, now: DateTime<Utc>) -> String {
+    let Some(entry) = running else {
+        return String::new();
+    };
+    let state = State::of(running);
+    format!(
+        "#[fg={}]{}#[default] {} {}",
+        state.color(),
+        state.icon(),
+        short_title(entry).replace('#', "##"),
+        compact(report::tracked_seconds(entry, now))
+    )
+}
+
+/// How `prompt` escapes its output for the shell showing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shell {
+    /// As is, e.g. for starship `custom` modules.
+    #[default]
+    Plain,
+    /// Escapes `\`, `$` and backticks for `PS1`, which bash first decodes
+    /// and then expands, so each gets two backslashes.
+    Bash,
+    /// Doubles `%`, which zsh expands in `PROMPT`.
+    Zsh,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "plain" => Ok(Shell::Plain),
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            other => Err(format!(
+                "Unknown shell '{}', expected plain, bash or zsh",
+                other
+            )),
+        }
+    }
+}
+
+impl Shell {
+    fn escape(self, text: &str) -> String {
+        match self {
+            Shell::Plain => text.to_string(),
+            Shell::Bash => text
+                .replace('\\', "\\\\\\\\")
+                .replace('$', "\\\\$")
+                .replace('`', "\\\\`"),
+            Shell::Zsh => text.replace('%', "%%"),
+        }
+    }
+}
+
+/// Renders the running timer for a shell prompt, e.g. `⏱ 0:42 fix-login`,
+/// with the paused icon instead of the prompt icon while paused.
+///
+/// # Returns
+/// The segment escaped for `shell`; empty when idle.
+pub fn prompt(running: Option<&LogEntry>, now: DateTime<Utc>, shell: Shell) -> String {
+    let Some(entry) = running else {
+        return String::new();
+    };
+    let icon = match State::of(running) {
+        State::Paused => &icons::current().paused,
+        _ => &icons::current().prompt,
+    };
+    shell.escape(&format!(
+        "{} {} {}",
+        icon,
+        compact(report::tracked_seconds(entry, now)),
+        short_title(entry)
+    ))
+}
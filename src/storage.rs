@@ -0,0 +1,522 @@
+use crate::{
+    backup::Backups,
+    error::ITrackerError,
+    index::{self, LogIndex},
+    log::{self, LogEntry, TaskDetails},
+    mapped::MappedLog,
+    schema::{self, Record},
+    status::{self, Stamp},
+    timer::TaskLog,
+};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// The CSV log file that entries are kept in.
+///
+/// Every call reads or rewrites the file, so several `Storage` values (or
+/// processes) can work on the same file one after another. With
+/// [`Storage::with_cache`] the records are parsed once and kept in memory
+/// while the file stays as this `Storage` left it.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    path: String,
+    /// Where the file is copied before it is rewritten, `None` for no backups.
+    backups: Option<Backups>,
+    /// Records read or written so far, shared by clones; `None` without a cache.
+    cache: Option<Arc<Mutex<Option<Cached>>>>,
+}
+
+/// The records of the log file as of the stamps of the file and its journal.
+#[derive(Debug)]
+struct Cached {
+    stamps: Stamps,
+    records: Vec<Record>,
+}
+
+/// The stamps of the log file and of its journal.
+type Stamps = (Option<Stamp>, Option<Stamp>);
+
+impl Storage {
+    /// Uses the log file at `path`; it is created when the first entry is added.
+    pub fn new(path: impl Into<String>) -> Self {
+        Storage {
+            path: path.into(),
+            backups: None,
+            cache: None,
+        }
+    }
+
+    /// Backs the file up before every rewrite, i.e. before deletes,
+    /// compaction and folding in the journal.
+    pub fn with_backups(mut self, backups: Backups) -> Self {
+        self.backups = Some(backups);
+        self
+    }
+
+    /// Keeps the records in memory once read, for a run that reads and
+    /// changes the file several times, e.g. a command of the binary. Writes
+    /// of this `Storage` update them in place; a change by anything else,
+    /// noticed by the size and modification time of the file and its
+    /// journal, makes the next call read the file again.
+    pub fn with_cache(mut self) -> Self {
+        self.cache = Some(Arc::default());
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Reads all entries in file order.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<LogEntry>)`: The entries, empty if the file has no records.
+    /// - `Err(ITrackerError)`: If the file cannot be read or a record is invalid.
+    pub fn entries(&self) -> Result<Vec<LogEntry>, ITrackerError> {
+        self.with_records(|records| records.iter().cloned().map(LogEntry::from).collect())
+    }
+
+    /// Reads the entries started from `from` up to before `to`, in file order.
+    /// The file is read through a memory map and only these entries are
+    /// copied out of it, so reports stay fast on logs of many years.
+    pub fn entries_started(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<LogEntry>, ITrackerError> {
+        let started = |records: &[Record]| -> Vec<LogEntry> {
+            records
+                .iter()
+                .filter(|record| {
+                    DateTime::parse_from_rfc2822(record.start_time.trim())
+                        .is_ok_and(|start| start >= from && start < to)
+                })
+                .cloned()
+                .map(LogEntry::from)
+                .collect()
+        };
+        if let Some(entries) = self.cached(started)? {
+            return Ok(entries);
+        }
+        match MappedLog::open(&self.path)? {
+            Some(mapped) => mapped.entries_started(from, to),
+            None => self.with_records(started),
+        }
+    }
+
+    /// Reads the entry with the given index, found through the index of the
+    /// file rather than by reading all of it.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The entry.
+    /// - `Err(ITrackerError)`: `EntryNotFound` if no entry has this index, or a read error.
+    pub fn entry(&self, index: usize) -> Result<LogEntry, ITrackerError> {
+        let find = |records: &[Record]| {
+            records
+                .iter()
+                .find(|record| record.index == index)
+                .cloned()
+                .map(LogEntry::from)
+        };
+        let entry = match self.cached(find)? {
+            Some(entry) => entry,
+            None => match LogIndex::load(&self.path)? {
+                Some(log_index) => log_index.entry(index)?,
+                None => self.with_records(find)?,
+            },
+        };
+        entry.ok_or_else(|| ITrackerError::EntryNotFound {
+            path: self.path.clone().into(),
+            index,
+        })
+    }
+
+    /// The most recently started entry that has not been stopped yet.
+    pub fn running(&self) -> Result<Option<LogEntry>, ITrackerError> {
+        let running = |records: &[Record]| {
+            records
+                .iter()
+                // Like `LogEntry::is_running`
+                .filter(|record| record.elapsed_time.trim().parse::<u64>().unwrap_or(0) == 0)
+                .filter_map(|record| {
+                    DateTime::parse_from_rfc2822(record.start_time.trim())
+                        .ok()
+                        .map(|start| (start, record))
+                })
+                .max_by_key(|(start, _)| *start)
+                .map(|(_, record)| LogEntry::from(record.clone()))
+        };
+        if let Some(running) = self.cached(running)? {
+            return Ok(running);
+        }
+        match LogIndex::load(&self.path)? {
+            Some(log_index) => log_index.running(),
+            None => self.with_records(running),
+        }
+    }
+
+    /// Appends a running entry for `task`, started now according to `timer`.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The new entry as written to the file.
+    /// - `Err(ITrackerError)`: If the file cannot be read or written.
+    pub fn append(
+        &self,
+        task: &TaskDetails,
+        timer: &mut dyn TaskLog,
+    ) -> Result<LogEntry, ITrackerError> {
+        timer.log_task(task, self)
+    }
+
+    /// The index the next entry appended gets.
+    pub fn next_index(&self) -> Result<usize, ITrackerError> {
+        let highest = |records: &[Record]| records.iter().map(|record| record.index).max();
+        let highest = if !Path::new(&self.path).exists() {
+            None
+        } else if let Some(highest) = self.cached(highest)? {
+            highest
+        } else {
+            match LogIndex::load(&self.path)? {
+                Some(log_index) => Some(log_index.highest_index()),
+                None => self.with_records(highest)?,
+            }
+        };
+        Ok(schema::next_index(&self.path, &[])?.max(highest.unwrap_or(0) + 1))
+    }
+
+    /// Appends `record` to the file, see [`schema::append`].
+    pub fn push(&self, record: &Record) -> Result<(), ITrackerError> {
+        let before = self.stamps()?;
+        schema::append(&self.path, record)?;
+        self.keep(before, |records| records.push(record.clone()))
+    }
+
+    /// Replaces the record with the same index as `entry`.
+    ///
+    /// The change is appended to the journal of the log file rather than
+    /// rewriting it, see [`schema::amend`].
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the entry was written.
+    /// - `Err(ITrackerError)`: `EntryNotFound` if no entry has this index, or an IO error.
+    pub fn update(&self, entry: &LogEntry) -> Result<(), ITrackerError> {
+        self.entry(entry.index)?;
+        let record = Record::from(entry);
+        let before = self.stamps()?;
+        let amendments = schema::amend(&self.path, &record)?;
+        index::amended(Path::new(&self.path), before, &record)?;
+        self.keep(before, |records| {
+            if let Some(amended) = records.iter_mut().find(|r| r.index == record.index) {
+                *amended = record.clone();
+            }
+        })?;
+        if amendments >= schema::JOURNAL_LIMIT {
+            self.rewrite(&self.records()?)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the entry with the given index; its index is not given out again.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the deletion is successful.
+    /// - `Err(ITrackerError)`: `EntryNotFound` if no entry has this index, or an IO error.
+    pub fn delete(&self, index: usize) -> Result<(), ITrackerError> {
+        self.delete_all(&[index])
+    }
+
+    /// Removes the entries with the given indices in one rewrite; their
+    /// indices are not given out again.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the deletion is successful.
+    /// - `Err(ITrackerError)`: `EntryNotFound` for the first index no entry
+    ///   has, with nothing deleted, or an IO error.
+    pub fn delete_all(&self, indices: &[usize]) -> Result<(), ITrackerError> {
+        let mut records = self.records()?;
+        let last = schema::next_index(&self.path, &records)? - 1;
+        if let Some(&index) = indices
+            .iter()
+            .find(|index| !records.iter().any(|record| record.index == **index))
+        {
+            return Err(ITrackerError::EntryNotFound {
+                path: self.path.clone().into(),
+                index,
+            });
+        }
+        let removed: HashSet<usize> = indices.iter().copied().collect();
+        records.retain(|record| !removed.contains(&record.index));
+
+        // Keep the index of the deleted entry from being given out again
+        schema::set_last_index(&self.path, last)?;
+        self.rewrite(&records)
+    }
+
+    /// Replaces the record with the same index as `entry` and removes the
+    /// entries of `merged` in one rewrite, e.g. after folding them into
+    /// `entry`; their indices are not given out again.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the file was written.
+    /// - `Err(ITrackerError)`: `EntryNotFound` if one of the entries does not
+    ///   exist, or an IO error.
+    pub fn merge_into(&self, entry: &LogEntry, merged: &[usize]) -> Result<(), ITrackerError> {
+        let mut records = self.records()?;
+        let last = schema::next_index(&self.path, &records)? - 1;
+        for index in merged.iter().chain([&entry.index]) {
+            if !records.iter().any(|record| record.index == *index) {
+                return Err(ITrackerError::EntryNotFound {
+                    path: self.path.clone().into(),
+                    index: *index,
+                });
+            }
+        }
+        records.retain(|record| !merged.contains(&record.index));
+        for record in records.iter_mut() {
+            if record.index == entry.index {
+                *record = Record::from(entry);
+            }
+        }
+
+        schema::set_last_index(&self.path, last)?;
+        self.rewrite(&records)
+    }
+
+    /// Moves entries to the end of another log file, where they get the next
+    /// indices of that file. Each keeps its times and values, and notes
+    /// where it came from under `moved_from` in its metadata, e.g.
+    /// `time.csv#42`. The other file is written first, so a failure in
+    /// between leaves copies rather than losing entries.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(usize, usize)>)`: The index of each entry here and in `target`.
+    /// - `Err(ITrackerError)`: `EntryNotFound` if one of the entries does not
+    ///   exist, with nothing moved, or an IO error.
+    pub fn move_to(
+        &self,
+        indices: &[usize],
+        target: &Storage,
+    ) -> Result<Vec<(usize, usize)>, ITrackerError> {
+        let records = self.records()?;
+        let mut moving = Vec::new();
+        for &index in indices {
+            let record = records
+                .iter()
+                .find(|record| record.index == index)
+                .ok_or_else(|| ITrackerError::EntryNotFound {
+                    path: self.path.clone().into(),
+                    index,
+                })?;
+            moving.push(LogEntry::from(record.clone()));
+        }
+
+        let mut theirs = target.records()?;
+        let mut next_index = schema::next_index(&target.path, &theirs)?;
+        let mut moved = Vec::new();
+        for mut entry in moving {
+            moved.push((entry.index, next_index));
+            entry.metadata.insert(
+                "moved_from".to_string(),
+                format!("{}#{}", self.path, entry.index),
+            );
+            entry.index = next_index;
+            theirs.push(Record::from(&entry));
+            next_index += 1;
+        }
+        target.rewrite(&theirs)?;
+        schema::set_last_index(&target.path, next_index - 1)?;
+
+        self.delete_all(indices)?;
+        Ok(moved)
+    }
+
+    /// Renormalizes the file: sorts the entries by start time, renumbers
+    /// them 1, 2, 3, ..., trims stray whitespace and empty tags, and
+    /// rewrites it with the headers and all columns in canonical order,
+    /// folding in its journal.
+    ///
+    /// Indices are never reused otherwise, so this is the only way to close
+    /// the gaps left by deleted entries, and to give entries sharing an index
+    /// their own, which every other command refuses to work with. Entries
+    /// starting at the same time keep their order, and those whose start
+    /// cannot be read go last. The file is only rewritten if anything changed.
+    ///
+    /// # Returns
+    /// - `Ok(Compaction)`: What was normalized.
+    /// - `Err(ITrackerError)`: If the file cannot be read or written.
+    pub fn compact(&self) -> Result<Compaction, ITrackerError> {
+        let layout = schema::layout(&self.path)?;
+        let mut records = schema::read_with_duplicates(&self.path)?;
+        let mut compaction = Compaction {
+            headers: !layout.canonical_headers,
+            padded: layout.short_rows,
+            amendments: layout.amendments,
+            ..Compaction::default()
+        };
+
+        let order: Vec<usize> = records.iter().map(|record| record.index).collect();
+        records.sort_by_key(|record| {
+            let start = DateTime::parse_from_rfc2822(record.start_time.trim()).ok();
+            (start.is_none(), start)
+        });
+        compaction.reordered = records
+            .iter()
+            .zip(&order)
+            .filter(|(record, index)| record.index != **index)
+            .count();
+        for (position, record) in records.iter_mut().enumerate() {
+            if trim(record) {
+                compaction.trimmed += 1;
+            }
+            if record.index != position + 1 {
+                compaction.renumbered.push((record.index, position + 1));
+                record.index = position + 1;
+            }
+        }
+
+        if !compaction.is_empty() {
+            self.rewrite(&records)?;
+        }
+        schema::set_last_index(&self.path, records.len())?;
+        Ok(compaction)
+    }
+
+    /// Replaces the file with `records`, backing it up first.
+    fn rewrite(&self, records: &[Record]) -> Result<(), ITrackerError> {
+        if let Some(backups) = &self.backups {
+            backups.save(&self.path)?;
+        }
+        schema::write(&self.path, records)?;
+        if let Some(cache) = &self.cache {
+            *lock(cache) = Some(Cached {
+                stamps: self.stamps()?,
+                records: records.to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    /// The records of the file, see [`schema::read`].
+    fn records(&self) -> Result<Vec<Record>, ITrackerError> {
+        self.with_records(<[Record]>::to_vec)
+    }
+
+    /// Calls `read` with the records of the file, from the cache if it is
+    /// current and read into it otherwise.
+    fn with_records<T>(&self, read: impl FnOnce(&[Record]) -> T) -> Result<T, ITrackerError> {
+        let Some(cache) = &self.cache else {
+            return Ok(read(&schema::read(&self.path)?));
+        };
+        let mut cached = lock(cache);
+        // Stamped first, so a change while reading makes the next call read again
+        let stamps = self.stamps()?;
+        let cached = match &mut *cached {
+            Some(current) if current.stamps == stamps => current,
+            outdated => outdated.insert(Cached {
+                stamps,
+                records: schema::read(&self.path)?,
+            }),
+        };
+        Ok(read(&cached.records))
+    }
+
+    /// Calls `read` with the cached records if they are current, without
+    /// reading the file otherwise.
+    fn cached<T>(&self, read: impl FnOnce(&[Record]) -> T) -> Result<Option<T>, ITrackerError> {
+        let Some(cache) = &self.cache else {
+            return Ok(None);
+        };
+        let cached = lock(cache);
+        match &*cached {
+            Some(current) if current.stamps == self.stamps()? => Ok(Some(read(&current.records))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Applies a write of this `Storage` to the cached records if they were
+    /// current `before` it, and forgets them otherwise.
+    fn keep(
+        &self,
+        before: Stamps,
+        change: impl FnOnce(&mut Vec<Record>),
+    ) -> Result<(), ITrackerError> {
+        let Some(cache) = &self.cache else {
+            return Ok(());
+        };
+        let mut cached = lock(cache);
+        match &mut *cached {
+            Some(current) if current.stamps == before => {
+                change(&mut current.records);
+                current.stamps = self.stamps()?;
+            }
+            outdated => *outdated = None,
+        }
+        Ok(())
+    }
+
+    fn stamps(&self) -> Result<Stamps, ITrackerError> {
+        Ok((
+            status::stamp(Path::new(&self.path))?,
+            status::stamp(&schema::journal_path(&self.path))?,
+        ))
+    }
+}
+
+fn lock(cache: &Mutex<Option<Cached>>) -> MutexGuard<'_, Option<Cached>> {
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// What [`Storage::compact`] normalized in a log file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Compaction {
+    /// The old and new index of every entry whose index changed.
+    pub renumbered: Vec<(usize, usize)>,
+    /// Entries that moved to keep the file sorted by start time.
+    pub reordered: usize,
+    /// Entries with whitespace around their values or empty tags.
+    pub trimmed: usize,
+    /// Rows that had fewer columns than [`schema::HEADERS`].
+    pub padded: usize,
+    /// Whether the header row differed from [`schema::HEADERS`].
+    pub headers: bool,
+    /// Amendments of the journal folded into the file.
+    pub amendments: usize,
+}
+
+impl Compaction {
+    /// Whether the file was normalized already.
+    pub fn is_empty(&self) -> bool {
+        self.renumbered.is_empty()
+            && self.reordered == 0
+            && self.trimmed == 0
+            && self.padded == 0
+            && !self.headers
+            && self.amendments == 0
+    }
+}
+
+/// Trims the values of `record` and drops its empty tags; the description
+/// keeps its inner lines. Returns whether anything changed.
+fn trim(record: &mut Record) -> bool {
+    let before = record.clone();
+    for value in [
+        &mut record.start_time,
+        &mut record.message,
+        &mut record.elapsed_time,
+        &mut record.paused_time,
+        &mut record.title,
+        &mut record.project,
+        &mut record.paused_since,
+        &mut record.metadata,
+    ] {
+        if value.trim().len() != value.len() {
+            *value = value.trim().to_string();
+        }
+    }
+    record.tags = log::parse_tags(&record.tags).join(",");
+    *record != before
+}
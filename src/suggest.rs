@@ -0,0 +1,95 @@
+//! Tasks likely to be started next, guessed from the log.
+//!
+//! Every earlier entry votes for its task (title and project). Votes count
+//! more when the entry started around the same time of day and on the same
+//! day of the week as now, and less the older the entry is, so a standup
+//! tracked every weekday morning comes first on weekday mornings.
+
+use crate::log::{LogEntry, TaskDetails};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+
+/// Suggestions offered when starting a timer, one per key from 1 to 9.
+pub const MAX_TASKS: usize = 9;
+
+/// Entries older than this many days do not vote.
+const HISTORY_DAYS: i64 = 90;
+
+/// A task suggested when starting a timer.
+#[derive(Debug, Clone)]
+pub struct SuggestedTask {
+    /// The task as it was last tracked, without its description.
+    pub task: TaskDetails,
+    /// How often it was tracked within the history.
+    pub count: usize,
+}
+
+/// The tasks most likely to be started at `now`, best first.
+///
+/// # Arguments
+/// - `entries`: The entries of the log file.
+/// - `now`: The current time.
+/// - `tz`: The timezone times of day and weekdays are compared in.
+/// - `limit`: How many tasks to return at most.
+pub fn likely_tasks(
+    entries: &[LogEntry],
+    now: DateTime<Utc>,
+    tz: Tz,
+    limit: usize,
+) -> Vec<SuggestedTask> {
+    let local = now.with_timezone(&tz);
+    let minute_of_day = |hour: u32, minute: u32| i64::from(hour * 60 + minute);
+    let current = minute_of_day(local.hour(), local.minute());
+
+    // Task key, score, count and the latest entry of the task
+    let mut votes: HashMap<(String, String), (f64, usize, &LogEntry)> = HashMap::new();
+    for entry in entries {
+        let title = entry.display_title().trim();
+        let Some(start) = entry.start().map(|start| start.with_timezone(&tz)) else {
+            continue;
+        };
+        let age = (local.date_naive() - start.date_naive()).num_days();
+        if title.is_empty() || !(0..HISTORY_DAYS).contains(&age) {
+            continue;
+        }
+        let mut score = 1.0;
+        // Minutes apart on the clock, across midnight
+        let apart = (minute_of_day(start.hour(), start.minute()) - current).abs();
+        if apart.min(24 * 60 - apart) <= 60 {
+            score += 2.0;
+        }
+        if start.weekday() == local.weekday() {
+            score += 1.0;
+        }
+        score *= 1.0 - age as f64 / HISTORY_DAYS as f64 / 2.0;
+
+        let key = (title.to_lowercase(), entry.project.trim().to_string());
+        let vote = votes.entry(key).or_insert((0.0, 0, entry));
+        vote.0 += score;
+        vote.1 += 1;
+        if entry.start() > vote.2.start() {
+            vote.2 = entry;
+        }
+    }
+
+    let mut ranked: Vec<_> = votes.into_values().collect();
+    ranked.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| a.2.display_title().cmp(b.2.display_title()))
+    });
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(_, count, entry)| SuggestedTask {
+            task: TaskDetails {
+                title: entry.display_title().trim().to_string(),
+                project: entry.project.trim().to_string(),
+                tags: entry.tags.clone(),
+                estimate: entry.estimate,
+                ..TaskDetails::default()
+            },
+            count,
+        })
+        .collect()
+}
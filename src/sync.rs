@@ -0,0 +1,391 @@
+//! Syncing the data directory through a git remote, to track time on
+//! several machines.
+//!
+//! `sync` commits the files of the data directory, brings in what other
+//! machines pushed and pushes the result; before timer changes the same
+//! happens without the push. Local commits are rebased onto the remote ones.
+//! When both sides changed the same log file, git cannot tell entries apart,
+//! so the file is merged entry by entry instead: entries added on either side
+//! are kept, renumbered where their indices clash, and only an entry changed
+//! differently on both sides is a conflict.
+
+use crate::{config, error::ITrackerError, schema, schema::Record};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+/// Branch the data is kept on unless configured otherwise.
+pub const DEFAULT_BRANCH: &str = "main";
+
+/// Name of the remote in the repository of the data directory.
+const REMOTE: &str = "origin";
+
+/// Files that belong to one machine, such as caches and temporary files.
+const GITIGNORE: &str =
+    "*.active\n*.activity\n*.crdt\n*.journal\n*.last-index\n*.tmp\n*.webdav\nbackups/\n";
+
+/// The `[sync]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncConfig {
+    /// URL or path of the git remote; syncing is off without one.
+    pub remote: Option<String>,
+    pub branch: Option<String>,
+    /// Pull before timers are started, paused, resumed or stopped, on by default.
+    pub pull_before_changes: Option<bool>,
+}
+
+/// The outcome of merging two versions of a log file entry by entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Merged {
+    /// The merged records, in file order.
+    pub records: Vec<Record>,
+    /// Local entries given a new index because the other side used theirs, as
+    /// `(old, new)`.
+    pub renumbered: Vec<(usize, usize)>,
+    /// Indices of entries changed differently on both sides, or changed on one
+    /// side and deleted on the other; the records are unusable if any.
+    pub conflicts: Vec<usize>,
+}
+
+/// Merges the records of two versions of a log file that both changed since
+/// a common version.
+///
+/// Entries are matched by index. An entry changed on one side only takes
+/// that change, and one deleted on one side and unchanged on the other is
+/// deleted. New entries on both sides are all kept, those of `theirs` first;
+/// a new entry equal to one on the other side but for its index is only
+/// kept once.
+///
+/// # Arguments
+/// - `base`: The common version, empty if the file is new on both sides.
+/// - `ours`: The local version.
+/// - `theirs`: The version of the remote.
+pub fn merge_records(base: &[Record], ours: &[Record], theirs: &[Record]) -> Merged {
+    let by_index = |records: &[Record]| -> HashMap<usize, Record> {
+        records
+            .iter()
+            .map(|record| (record.index, record.clone()))
+            .collect()
+    };
+    let (base_map, ours_map, theirs_map) = (by_index(base), by_index(ours), by_index(theirs));
+
+    let mut merged = Merged::default();
+    let mut removed = HashSet::new();
+    let mut replaced = HashMap::new();
+    let mut added = Vec::new();
+
+    for record in ours {
+        match (base_map.get(&record.index), theirs_map.get(&record.index)) {
+            (Some(old), _) if old == record => {}
+            (Some(old), Some(theirs)) if theirs == old => {
+                replaced.insert(record.index, record.clone());
+            }
+            (Some(_), Some(theirs)) if theirs == record => {}
+            (Some(_), _) => merged.conflicts.push(record.index),
+            (None, _) => {
+                let duplicate = theirs.iter().any(|other| {
+                    Record {
+                        index: record.index,
+                        ..other.clone()
+                    } == *record
+                });
+                if !duplicate {
+                    added.push(record.clone());
+                }
+            }
+        }
+    }
+    for old in base.iter().filter(|old| !ours_map.contains_key(&old.index)) {
+        match theirs_map.get(&old.index) {
+            Some(theirs) if theirs == old => {
+                removed.insert(old.index);
+            }
+            Some(_) => merged.conflicts.push(old.index),
+            None => {}
+        }
+    }
+
+    merged.records = theirs
+        .iter()
+        .filter(|record| !removed.contains(&record.index))
+        .map(|record| {
+            replaced
+                .remove(&record.index)
+                .unwrap_or_else(|| record.clone())
+        })
+        .collect();
+    let mut taken: HashSet<usize> = base
+        .iter()
+        .chain(theirs)
+        .map(|record| record.index)
+        .collect();
+    let highest = added
+        .iter()
+        .map(|record| record.index)
+        .chain(taken.iter().copied())
+        .max();
+    let mut next = highest.unwrap_or(0) + 1;
+    for mut record in added {
+        if !taken.insert(record.index) {
+            merged.renumbered.push((record.index, next));
+            record.index = next;
+            taken.insert(next);
+            next += 1;
+        }
+        merged.records.push(record);
+    }
+    merged.conflicts.sort_unstable();
+    merged
+}
+
+/// Local entries renumbered while pulling, per log file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pulled {
+    /// `(file, old index, new index)`, with the file relative to the data directory.
+    pub renumbered: Vec<(String, usize, usize)>,
+}
+
+/// The data directory as a git repository synced with a remote.
+#[derive(Debug, Clone)]
+pub struct GitSync {
+    dir: PathBuf,
+    remote: String,
+    branch: String,
+    pull_before_changes: bool,
+    /// Whether git knows whom to record as author, looked up once.
+    identity: OnceLock<bool>,
+}
+
+impl GitSync {
+    pub fn new(dir: impl Into<PathBuf>, remote: impl Into<String>) -> Self {
+        GitSync {
+            dir: dir.into(),
+            remote: remote.into(),
+            branch: DEFAULT_BRANCH.to_string(),
+            pull_before_changes: true,
+            identity: OnceLock::new(),
+        }
+    }
+
+    /// Resolves the `[sync]` section for the data directory.
+    ///
+    /// # Returns
+    /// - `Ok(Some(GitSync))`: If a remote is configured.
+    /// - `Ok(None)`: If syncing is off.
+    /// - `Err(ITrackerError)`: If the data directory cannot be created.
+    pub fn from_config(config: Option<&SyncConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        let Some(remote) = config.remote.as_deref().filter(|remote| !remote.is_empty()) else {
+            return Ok(None);
+        };
+        let mut sync = GitSync::new(config::data_dir()?, remote);
+        if let Some(branch) = &config.branch {
+            sync.branch = branch.clone();
+        }
+        sync.pull_before_changes = config.pull_before_changes.unwrap_or(true);
+        Ok(Some(sync))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn remote(&self) -> &str {
+        &self.remote
+    }
+
+    pub fn pulls_before_changes(&self) -> bool {
+        self.pull_before_changes
+    }
+
+    /// Commits local changes and brings in the commits of the remote, merging
+    /// log files entry by entry where both sides changed them.
+    ///
+    /// # Returns
+    /// - `Ok(Pulled)`: The local entries that had to be renumbered.
+    /// - `Err(ITrackerError)`: `Sync` if git fails or entries conflict; the
+    ///   data directory is left as it was after the local commit.
+    pub fn pull(&self) -> Result<Pulled, ITrackerError> {
+        self.prepare()?;
+        self.commit_local()?;
+
+        let heads = self.git(&["ls-remote", "--heads", REMOTE, &self.branch])?;
+        if heads.trim().is_empty() {
+            return Ok(Pulled::default());
+        }
+        self.git(&["fetch", "-q", REMOTE, &self.branch])?;
+        let upstream = format!("{}/{}", REMOTE, self.branch);
+        if self.git(&["rebase", "-q", &upstream]).is_ok() {
+            return Ok(Pulled::default());
+        }
+        self.git(&["rebase", "--abort"])?;
+        self.merge(&upstream)
+    }
+
+    /// Pulls, then pushes the result to the remote.
+    pub fn sync(&self) -> Result<Pulled, ITrackerError> {
+        let pulled = self.pull()?;
+        self.git(&[
+            "push",
+            "-q",
+            REMOTE,
+            &format!("HEAD:refs/heads/{}", self.branch),
+        ])?;
+        Ok(pulled)
+    }
+
+    /// Makes the data directory a repository with the configured remote.
+    fn prepare(&self) -> Result<(), ITrackerError> {
+        if !self.dir.join(".git").exists() {
+            self.git(&["init", "-q", "-b", &self.branch])?;
+        }
+        let gitignore = self.dir.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(&gitignore, GITIGNORE).map_err(ITrackerError::file(&gitignore))?;
+        }
+        match self.git(&["remote", "get-url", REMOTE]) {
+            Ok(url) if url.trim() == self.remote => Ok(()),
+            Ok(_) => self
+                .git(&["remote", "set-url", REMOTE, &self.remote])
+                .map(drop),
+            Err(_) => self.git(&["remote", "add", REMOTE, &self.remote]).map(drop),
+        }
+    }
+
+    /// Folds journals into their log files, so commits hold complete logs,
+    /// and commits everything that changed.
+    fn commit_local(&self) -> Result<(), ITrackerError> {
+        let entries = fs::read_dir(&self.dir).map_err(ITrackerError::file(&self.dir))?;
+        for entry in entries {
+            let path = entry.map_err(ITrackerError::file(&self.dir))?.path();
+            if let Some(log) = path.to_str().and_then(|p| p.strip_suffix(".journal")) {
+                schema::write(log, &schema::read(log)?)?;
+            }
+        }
+
+        self.git(&["add", "-A"])?;
+        if self.git(&["diff", "--cached", "--quiet"]).is_err() {
+            let message = format!("Track time until {}", Utc::now().to_rfc3339());
+            self.git(&["commit", "-q", "-m", &message])?;
+        }
+        Ok(())
+    }
+
+    /// Merges `upstream`, resolving conflicting log files entry by entry.
+    fn merge(&self, upstream: &str) -> Result<Pulled, ITrackerError> {
+        if self
+            .git(&[
+                "merge",
+                "-q",
+                "--no-edit",
+                "--allow-unrelated-histories",
+                upstream,
+            ])
+            .is_ok()
+        {
+            return Ok(Pulled::default());
+        }
+
+        let result = self.resolve();
+        if result.is_err() {
+            self.git(&["merge", "--abort"])?;
+        }
+        result
+    }
+
+    /// Writes the entry-level merge of every conflicting file and concludes
+    /// the merge.
+    fn resolve(&self) -> Result<Pulled, ITrackerError> {
+        let mut pulled = Pulled::default();
+        let unmerged = self.git(&["diff", "--name-only", "--diff-filter=U"])?;
+        for file in unmerged.lines() {
+            let path = self.dir.join(file);
+            let version = |stage: u8| -> Result<Vec<Record>, ITrackerError> {
+                let contents = self.git(&["show", &format!(":{}:{}", stage, file)])?;
+                schema::parse(&path, contents.as_bytes())
+            };
+            let base = version(1).unwrap_or_default();
+            let (ours, theirs) = match (version(2), version(3)) {
+                (Ok(ours), Ok(theirs)) => (ours, theirs),
+                _ => {
+                    return Err(ITrackerError::Sync(format!(
+                        "{} changed on both sides and is no log file that can be merged",
+                        file
+                    )))
+                }
+            };
+
+            let merged = merge_records(&base, &ours, &theirs);
+            if !merged.conflicts.is_empty() {
+                let indices: Vec<String> = merged.conflicts.iter().map(usize::to_string).collect();
+                return Err(ITrackerError::Sync(format!(
+                    "{}: entries changed on both sides: {}; edit them here to match the other machine and sync again",
+                    file,
+                    indices.join(", ")
+                )));
+            }
+            schema::write(&path, &merged.records)?;
+            self.git(&["add", file])?;
+            pulled.renumbered.extend(
+                merged
+                    .renumbered
+                    .into_iter()
+                    .map(|(old, new)| (file.to_string(), old, new)),
+            );
+        }
+        self.git(&["commit", "-q", "--no-edit"])?;
+        Ok(pulled)
+    }
+
+    /// Runs git in the data directory.
+    ///
+    /// # Returns
+    /// - `Ok(String)`: What git printed.
+    /// - `Err(ITrackerError)`: `Sync` with git's message if it failed.
+    fn git(&self, args: &[&str]) -> Result<String, ITrackerError> {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&self.dir);
+        // Commits must not fail on machines where git was never set up
+        if !*self.identity.get_or_init(|| has_identity(&self.dir)) {
+            command.args([
+                "-c",
+                "user.name=ITracker",
+                "-c",
+                "user.email=itracker@localhost",
+            ]);
+        }
+        let output = command.args(args).output().map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                ITrackerError::Sync("Syncing needs git, which was not found".to_string())
+            }
+            _ => ITrackerError::Io(e),
+        })?;
+        if !output.status.success() {
+            return Err(ITrackerError::Sync(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Whether git knows whom to record as author of commits in `dir`.
+fn has_identity(dir: &Path) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["config", "user.email"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
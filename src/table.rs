@@ -0,0 +1,169 @@
+//! Tables for the command line output of `list` and `report`.
+//!
+//! Columns get the width of their widest cell. When a table is wider than
+//! the terminal, the columns marked as shrinkable give up width, the widest
+//! first, and their cells are cut with `…`; the others, e.g. durations and
+//! amounts, always show in full. Numbers are right-aligned.
+
+use crate::color::{paint, Paint};
+
+/// Space between two columns.
+const GAP: &str = "  ";
+
+/// Width a shrinkable column keeps at least, or its header's if wider.
+const MIN_WIDTH: usize = 8;
+
+/// How the cells of a column are aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+struct Column {
+    header: String,
+    align: Align,
+    shrink: bool,
+}
+
+/// A table built row by row, rendered for a given width.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<(Vec<String>, Option<Paint>)>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Table::default()
+    }
+
+    /// Adds a column that always shows its cells in full.
+    pub fn column(mut self, header: impl Into<String>, align: Align) -> Self {
+        self.columns.push(Column {
+            header: header.into(),
+            align,
+            shrink: false,
+        });
+        self
+    }
+
+    /// Adds a column whose cells are cut when the table is too wide, e.g.
+    /// titles.
+    pub fn shrinking(mut self, header: impl Into<String>) -> Self {
+        self.columns.push(Column {
+            header: header.into(),
+            align: Align::Left,
+            shrink: true,
+        });
+        self
+    }
+
+    /// Adds a row; missing cells are left empty.
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push((cells, None));
+    }
+
+    /// Adds a row printed in the style of `paint`.
+    pub fn painted_row(&mut self, cells: Vec<String>, paint: Paint) {
+        self.rows.push((cells, Some(paint)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The width of each column so the table fits into `max_width` where
+    /// the shrinkable columns allow it; as wide as needed without a limit.
+    pub fn widths(&self, max_width: Option<usize>) -> Vec<usize> {
+        let mut widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                self.rows
+                    .iter()
+                    .filter_map(|(cells, _)| cells.get(i))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(column.header.chars().count())
+            })
+            .collect();
+        let Some(max_width) = max_width else {
+            return widths;
+        };
+        let gaps = GAP.len() * self.columns.len().saturating_sub(1);
+        let mut excess = (widths.iter().sum::<usize>() + gaps).saturating_sub(max_width);
+        while excess > 0 {
+            // Take one character at a time from the widest column that can give one
+            let widest = self
+                .columns
+                .iter()
+                .enumerate()
+                .filter(|(i, column)| {
+                    column.shrink && widths[*i] > MIN_WIDTH.max(column.header.chars().count())
+                })
+                .max_by_key(|(i, _)| widths[*i])
+                .map(|(i, _)| i);
+            let Some(widest) = widest else {
+                break;
+            };
+            widths[widest] -= 1;
+            excess -= 1;
+        }
+        widths
+    }
+
+    /// The table with a bold header line unless all headers are empty, one
+    /// line per row, fitted into `max_width` columns of the terminal if given.
+    pub fn render(&self, max_width: Option<usize>) -> String {
+        let widths = self.widths(max_width);
+        let line = |cells: &[String]| -> String {
+            let cells: Vec<String> = self
+                .columns
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(i, (column, width))| {
+                    let cell = truncate(cells.get(i).map(String::as_str).unwrap_or(""), *width);
+                    match column.align {
+                        Align::Left => format!("{:<width$}", cell, width = width),
+                        Align::Right => format!("{:>width$}", cell, width = width),
+                    }
+                })
+                .collect();
+            cells.join(GAP).trim_end().to_string()
+        };
+        let headers: Vec<String> = self.columns.iter().map(|c| c.header.clone()).collect();
+        let mut out = String::new();
+        // Tables of labelled values, e.g. totals, go without a header line
+        if headers.iter().any(|header| !header.is_empty()) {
+            out.push_str(&paint(line(&headers), Paint::Heading));
+            out.push('\n');
+        }
+        for (cells, style) in &self.rows {
+            let text = line(cells);
+            match style {
+                Some(style) => out.push_str(&paint(text, *style)),
+                None => out.push_str(&text),
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// `text` cut to `width` characters, ending in `…` if it was longer.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut cut: String = text.chars().take(width - 1).collect();
+    cut.push('…');
+    cut
+}
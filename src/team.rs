@@ -0,0 +1,383 @@
+//! Team mode: the clients of several users report their entries to one
+//! server, the same binary started with `--server`, which keeps a log file
+//! per user and answers team reports.
+//!
+//! | Request                              | Answer                              |
+//! |--------------------------------------|-------------------------------------|
+//! | `PUT /users/{user}/entries` with CSV | `{"entries": n}`, replacing the log |
+//! | `GET /users/{user}/entries`          | The user's entries                  |
+//! | `GET /report?from=YYYY-MM-DD&to=...` | Tracked time per user and project   |
+//!
+//! Every request carries `Authorization: Bearer <token>` with the token of
+//! a user of `[team.users]`. Users may read all entries and reports of the
+//! team, but only replace their own entries.
+
+use crate::{
+    error::ITrackerError,
+    log::LogEntry,
+    report::{self, today, Summary},
+    schema,
+    server::{answer, json, parse_date, send, Failure},
+};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tiny_http::{Method, Request, Server};
+
+/// Address the team server listens on unless configured otherwise.
+pub const DEFAULT_LISTEN: &str = "0.0.0.0:7878";
+
+/// The `[team]` section of the configuration file; the server reads
+/// `listen` and `users`, clients `server`, `user` and `token`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeamConfig {
+    /// Address `--server` listens on, e.g. `0.0.0.0:7878`.
+    pub listen: Option<String>,
+    /// The users allowed on the server and their tokens.
+    pub users: Option<BTreeMap<String, String>>,
+    /// URL of the team server clients report to, e.g. `https://time.example.com`.
+    pub server: Option<String>,
+    pub user: Option<String>,
+    /// The user's token, better given as `ITRACKER_TEAM__TOKEN`.
+    pub token: Option<String>,
+}
+
+/// Tracked time of the whole team between two days.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TeamReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Per user, users without entries in the range included.
+    pub users: BTreeMap<String, Summary>,
+    /// Seconds per project over all users.
+    pub per_project: BTreeMap<String, u64>,
+    pub total: u64,
+}
+
+/// The server keeping the entries of the team in `<dir>/<user>.csv`.
+#[derive(Debug, Clone)]
+pub struct TeamServer {
+    dir: PathBuf,
+    listen: String,
+    /// Token of every user.
+    users: BTreeMap<String, String>,
+}
+
+impl TeamServer {
+    /// Resolves the server side of the `[team]` section.
+    ///
+    /// # Arguments
+    /// - `dir`: Where the logs of the users are kept.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: `Config` if no users are configured, or a
+    ///   name or token is unusable.
+    pub fn from_config(
+        config: Option<&TeamConfig>,
+        dir: impl Into<PathBuf>,
+    ) -> Result<Self, ITrackerError> {
+        let users = config
+            .and_then(|config| config.users.clone())
+            .unwrap_or_default();
+        if users.is_empty() {
+            return Err(ITrackerError::Config(
+                "The team server needs users, set `team.users.<name>` to each user's token"
+                    .to_string(),
+            ));
+        }
+        validate_users(&users)?;
+        Ok(TeamServer {
+            dir: dir.into(),
+            listen: config
+                .and_then(|config| config.listen.clone())
+                .unwrap_or_else(|| DEFAULT_LISTEN.to_string()),
+            users,
+        })
+    }
+
+    /// Answers the clients of the team until the process is stopped.
+    ///
+    /// # Arguments
+    /// - `tz`: The timezone days are counted in for reports.
+    pub fn serve(&self, tz: Tz) -> Result<(), ITrackerError> {
+        fs::create_dir_all(&self.dir).map_err(ITrackerError::file(&self.dir))?;
+        let server = Server::http(self.listen.as_str()).map_err(|e| {
+            ITrackerError::Config(format!("Cannot listen on {}: {}", self.listen, e))
+        })?;
+        println!(
+            "Serving the team of {} users from {} on http://{}",
+            self.users.len(),
+            self.dir.display(),
+            self.listen
+        );
+        for mut request in server.incoming_requests() {
+            let (status, body) = answer(self.handle(&mut request, tz));
+            send(request, status, body, "application/json");
+        }
+        Ok(())
+    }
+
+    /// Sums up the entries of every user that started between `from` and
+    /// `to` (both inclusive).
+    pub fn report(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        tz: Tz,
+    ) -> Result<TeamReport, ITrackerError> {
+        let now = Utc::now();
+        let mut team = TeamReport {
+            from,
+            to,
+            ..TeamReport::default()
+        };
+        for user in self.users.keys() {
+            let entries = self.entries(user)?;
+            let summary =
+                report::summarize(report::entries_between(&entries, from, to, tz), now, tz);
+            for (project, seconds) in &summary.per_project {
+                *team.per_project.entry(project.clone()).or_default() += seconds;
+            }
+            team.total += summary.total;
+            team.users.insert(user.clone(), summary);
+        }
+        Ok(team)
+    }
+
+    /// The entries `user` reported, none before the first report.
+    fn entries(&self, user: &str) -> Result<Vec<LogEntry>, ITrackerError> {
+        let log = self.log(user);
+        if !log.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(schema::read(&log)?
+            .into_iter()
+            .map(LogEntry::from)
+            .collect())
+    }
+
+    fn log(&self, user: &str) -> PathBuf {
+        self.dir.join(format!("{}.csv", user))
+    }
+
+    /// Routes a request, returning the JSON body of a successful answer.
+    fn handle(&self, request: &mut Request, tz: Tz) -> Result<String, Failure> {
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let caller = self.caller(request)?;
+
+        match (request.method(), segments.as_slice()) {
+            (Method::Put, ["users", user, "entries"]) => {
+                if *user != caller {
+                    return Err(Failure::new(
+                        403,
+                        format!("{} cannot replace the entries of {}", caller, user),
+                    ));
+                }
+                let mut body = Vec::new();
+                request
+                    .as_reader()
+                    .read_to_end(&mut body)
+                    .map_err(ITrackerError::from)?;
+                let log = self.log(user);
+                let records = schema::parse(&log, body.as_slice())
+                    .map_err(|e| Failure::new(400, format!("Invalid entries: {}", e)))?;
+                schema::write(&log, &records)?;
+                json(&serde_json::json!({ "entries": records.len() }))
+            }
+            (Method::Get, ["users", user, "entries"]) => {
+                if !self.users.contains_key(*user) {
+                    return Err(Failure::new(404, format!("No user {} in the team", user)));
+                }
+                json(&self.entries(user)?)
+            }
+            (Method::Get, ["report"]) => {
+                let day = today(Utc::now(), tz);
+                let from = parse_date(query, "from")?.unwrap_or(day);
+                let to = parse_date(query, "to")?.unwrap_or(from.max(day));
+                json(&self.report(from, to, tz)?)
+            }
+            _ => Err(Failure::new(
+                404,
+                format!("No such endpoint: {} {}", request.method(), path),
+            )),
+        }
+    }
+
+    /// The user whose token the request carries.
+    fn caller(&self, request: &Request) -> Result<String, Failure> {
+        let token = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+            .ok_or_else(|| Failure::new(401, "Send the token as Authorization: Bearer <token>"))?;
+        self.users
+            .iter()
+            .find(|(_, known)| same_token(known, token))
+            .map(|(user, _)| user.clone())
+            .ok_or_else(|| Failure::new(401, "Unknown token"))
+    }
+}
+
+/// A user's connection to the team server.
+#[derive(Debug, Clone)]
+pub struct TeamClient {
+    /// Without a trailing slash.
+    server: String,
+    user: String,
+    token: String,
+}
+
+impl TeamClient {
+    /// Resolves the client side of the `[team]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Some(TeamClient))`: If a server is configured.
+    /// - `Ok(None)`: If the user does not report to a team.
+    /// - `Err(ITrackerError)`: `Config` if the URL is not an http(s) URL, or
+    ///   the user or token is missing.
+    pub fn from_config(config: Option<&TeamConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        let Some(server) = config.server.as_deref().filter(|server| !server.is_empty()) else {
+            return Ok(None);
+        };
+        if !(server.starts_with("https://") || server.starts_with("http://")) {
+            return Err(ITrackerError::Config(format!(
+                "server must start with https:// or http://, got {}",
+                server
+            )));
+        }
+        let (Some(user), Some(token)) = (&config.user, &config.token) else {
+            return Err(ITrackerError::Config(
+                "Set `team.user` and `team.token` to report to the team server".to_string(),
+            ));
+        };
+        validate_user(user)?;
+        Ok(Some(TeamClient {
+            server: server.trim_end_matches('/').to_string(),
+            user: user.clone(),
+            token: token.clone(),
+        }))
+    }
+
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    /// Replaces the user's entries on the server with those of `log`.
+    ///
+    /// # Returns
+    /// - `Ok(usize)`: The number of entries reported.
+    /// - `Err(ITrackerError)`: `Team` if the server cannot be reached or
+    ///   refuses the entries.
+    pub fn push(&self, log: &str) -> Result<usize, ITrackerError> {
+        let records = if Path::new(log).exists() {
+            schema::read(log)?
+        } else {
+            Vec::new()
+        };
+        let url = format!("{}/users/{}/entries", self.server, self.user);
+        self.request("PUT", &url)
+            .set("Content-Type", "text/csv; charset=utf-8")
+            .send_string(&schema::to_csv(log, &records)?)
+            .map_err(|e| self.error(e))?;
+        Ok(records.len())
+    }
+
+    /// The team report between `from` and `to`, both inclusive; the server
+    /// picks today for days not given.
+    pub fn report(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<TeamReport, ITrackerError> {
+        let mut url = format!("{}/report", self.server);
+        let query: Vec<String> = [("from", from), ("to", to)]
+            .iter()
+            .filter_map(|(key, day)| day.map(|day| format!("{}={}", key, day.format("%Y-%m-%d"))))
+            .collect();
+        if !query.is_empty() {
+            url = format!("{}?{}", url, query.join("&"));
+        }
+        let body = self
+            .request("GET", &url)
+            .call()
+            .map_err(|e| self.error(e))?
+            .into_string()
+            .map_err(|e| ITrackerError::Team(format!("{}: {}", self.server, e)))?;
+        serde_json::from_str(&body)
+            .map_err(|e| ITrackerError::Team(format!("{}: unexpected answer: {}", self.server, e)))
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        ureq::request(method, url).set("Authorization", &format!("Bearer {}", self.token))
+    }
+
+    /// Describes a failed request, with the server's own message if it sent one.
+    fn error(&self, error: ureq::Error) -> ITrackerError {
+        let message = match error {
+            ureq::Error::Status(status, response) => response
+                .into_string()
+                .ok()
+                .and_then(|body| serde_json::from_str::<serde_json::Value>(&body).ok())
+                .and_then(|body| body["error"].as_str().map(str::to_string))
+                .unwrap_or_else(|| format!("HTTP status {}", status)),
+            e => e.to_string(),
+        };
+        ITrackerError::Team(format!("{}: {}", self.server, message))
+    }
+}
+
+/// Checks the users of `[team.users]`.
+fn validate_users(users: &BTreeMap<String, String>) -> Result<(), ITrackerError> {
+    for (user, token) in users {
+        validate_user(user)?;
+        if token.len() < 16 {
+            return Err(ITrackerError::Config(format!(
+                "The token of {} is too short, use at least 16 characters",
+                user
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// User names end up in URLs and file names, so only plain ones are allowed.
+fn validate_user(user: &str) -> Result<(), ITrackerError> {
+    if user.is_empty()
+        || user.starts_with('.')
+        || !user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(ITrackerError::Config(format!(
+            "Invalid user name '{}', use letters, digits, '-', '_' and '.'",
+            user
+        )));
+    }
+    Ok(())
+}
+
+/// Compares tokens in time independent of where they differ.
+fn same_token(known: &str, given: &str) -> bool {
+    known.len() == given.len()
+        && known
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
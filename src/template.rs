@@ -0,0 +1,158 @@
+//! Output templates shaping the lines of `status` and `list` for scripts
+//! and bars, e.g. `{{icon}} {{title}} [{{elapsed}}]`.
+//!
+//! A template is text with fields in double braces; nothing else is
+//! special. Templates are given with `--template`, either inline or as the
+//! name of a preset of the `[templates]` section.
+
+use crate::{
+    error::ITrackerError,
+    log::LogEntry,
+    report,
+    status::State,
+    timefmt::{DurationFormat, TimeFormat},
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+/// The fields an entry fills in.
+pub const FIELDS: [&str; 11] = [
+    "index",
+    "title",
+    "description",
+    "project",
+    "tags",
+    "start",
+    "elapsed",
+    "paused",
+    "estimate",
+    "state",
+    "icon",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(&'static str),
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses a template, or looks up the preset of that name.
+    ///
+    /// # Arguments
+    /// - `value`: A template with at least one field, or a preset name.
+    /// - `presets`: The `[templates]` section.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: `Config` if the preset is unknown, a field is
+    ///   not closed, or it names no field of [`FIELDS`].
+    pub fn resolve(value: &str, presets: &BTreeMap<String, String>) -> Result<Self, ITrackerError> {
+        if value.contains("{{") {
+            return Template::parse(value);
+        }
+        match presets.get(value) {
+            Some(template) => Template::parse(template)
+                .map_err(|e| ITrackerError::Config(format!("templates.{}: {}", value, e))),
+            None => Err(ITrackerError::Config(format!(
+                "No template '{}', give one with fields like {{{{title}}}} or set templates.{}",
+                value, value
+            ))),
+        }
+    }
+
+    /// Parses text with `{{field}}` placeholders.
+    pub fn parse(template: &str) -> Result<Self, ITrackerError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(open) = rest.find("{{") {
+            if open > 0 {
+                parts.push(Part::Text(rest[..open].to_string()));
+            }
+            let after = &rest[open + 2..];
+            let close = after.find("}}").ok_or_else(|| {
+                ITrackerError::Config(format!("Unclosed {{{{ in template '{}'", template))
+            })?;
+            let name = after[..close].trim();
+            let field = FIELDS.iter().find(|field| **field == name).ok_or_else(|| {
+                ITrackerError::Config(format!(
+                    "Unknown template field '{}', expected one of {}",
+                    name,
+                    FIELDS.join(", ")
+                ))
+            })?;
+            parts.push(Part::Field(field));
+            rest = &after[close + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Fills in the fields; fields missing from `values` are left empty.
+    pub fn render(&self, values: &BTreeMap<&str, String>) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.as_str(),
+                Part::Field(field) => values.get(field).map(String::as_str).unwrap_or(""),
+            })
+            .collect()
+    }
+}
+
+/// The values of [`FIELDS`] for an entry.
+///
+/// # Arguments
+/// - `entry`: The entry.
+/// - `now`: The current time, for the time a running entry tracked so far.
+/// - `durations`: How `elapsed` and `paused` are shown.
+/// - `times`: How `start` is shown.
+/// - `tz`: The timezone `start` is shown in.
+pub fn fields(
+    entry: &LogEntry,
+    now: DateTime<Utc>,
+    durations: DurationFormat,
+    times: &TimeFormat,
+    tz: Tz,
+) -> BTreeMap<&'static str, String> {
+    let state = State::of(Some(entry).filter(|entry| entry.is_running()));
+    BTreeMap::from([
+        ("index", entry.index.to_string()),
+        ("title", entry.display_title().to_string()),
+        ("description", entry.message.trim().to_string()),
+        ("project", entry.project.trim().to_string()),
+        ("tags", entry.tags.join(",")),
+        ("start", times.format_stored(&entry.start_time, tz)),
+        (
+            "elapsed",
+            durations.format(report::tracked_seconds(entry, now)),
+        ),
+        (
+            "paused",
+            durations.format(entry.paused_time.trim().parse().unwrap_or(0)),
+        ),
+        (
+            "estimate",
+            entry
+                .estimate
+                .map(|minutes| durations.format(u64::from(minutes) * 60))
+                .unwrap_or_default(),
+        ),
+        (
+            "state",
+            match state {
+                State::Idle => "stopped".to_string(),
+                state => state.to_string(),
+            },
+        ),
+        ("icon", state.icon().to_string()),
+    ])
+}
@@ -0,0 +1,123 @@
+use crate::error::ITrackerError;
+use ratatui::style::{Color, Style};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The `[theme]` section of the configuration file.
+///
+/// A preset provides the base palette and each color can be overridden
+/// individually with a hex value (`#rrggbb`) or a color name (`lightblue`).
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// Base palette: `dark` (default) or `light`.
+    pub preset: Option<String>,
+    pub background: Option<String>,
+    pub foreground: Option<String>,
+    pub border: Option<String>,
+    pub accent: Option<String>,
+    pub highlight: Option<String>,
+}
+
+/// Colors applied to every TUI widget.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub background: Color,
+    pub foreground: Color,
+    /// Color of widget borders.
+    pub border: Color,
+    /// Color of titles and headers.
+    pub accent: Color,
+    /// Color of selected or highlighted items.
+    pub highlight: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            background: Color::Black,
+            foreground: Color::White,
+            border: Color::Gray,
+            accent: Color::Cyan,
+            highlight: Color::Yellow,
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            background: Color::White,
+            foreground: Color::Black,
+            border: Color::DarkGray,
+            accent: Color::Blue,
+            highlight: Color::Magenta,
+        }
+    }
+
+    /// Builds the theme described by the configuration.
+    ///
+    /// # Arguments
+    /// - `config`: The optional `[theme]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Theme)`: The preset with all overrides applied.
+    /// - `Err(ITrackerError)`: If the preset or a color value is unknown.
+    pub fn from_config(config: Option<&ThemeConfig>) -> Result<Self, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(Theme::default());
+        };
+
+        let mut theme = match config.preset.as_deref() {
+            None | Some("dark") => Theme::dark(),
+            Some("light") => Theme::light(),
+            Some(other) => {
+                return Err(ITrackerError::Config(format!(
+                    "Unknown theme preset '{}', expected dark or light",
+                    other
+                )))
+            }
+        };
+
+        override_color(&mut theme.background, &config.background)?;
+        override_color(&mut theme.foreground, &config.foreground)?;
+        override_color(&mut theme.border, &config.border)?;
+        override_color(&mut theme.accent, &config.accent)?;
+        override_color(&mut theme.highlight, &config.highlight)?;
+
+        Ok(theme)
+    }
+
+    /// Default style for text on the theme background.
+    pub fn base(&self) -> Style {
+        Style::default().bg(self.background).fg(self.foreground)
+    }
+
+    pub fn border(&self) -> Style {
+        Style::default().fg(self.border)
+    }
+
+    pub fn accent(&self) -> Style {
+        Style::default().fg(self.accent)
+    }
+
+    pub fn highlight(&self) -> Style {
+        Style::default().fg(self.highlight)
+    }
+}
+
+fn override_color(color: &mut Color, value: &Option<String>) -> Result<(), ITrackerError> {
+    if let Some(value) = value {
+        *color = Color::from_str(value).map_err(|_| {
+            ITrackerError::Config(format!(
+                "Invalid theme color '{}', expected #rrggbb or a color name",
+                value
+            ))
+        })?;
+    }
+    Ok(())
+}
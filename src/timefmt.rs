@@ -0,0 +1,166 @@
+use crate::{error::ITrackerError, i18n::tr_args, locale};
+use chrono::{format::StrftimeItems, DateTime, FixedOffset, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt::Display;
+
+/// How timestamps are shown to the user, set with `time_format` in the config.
+///
+/// The log file itself always stores RFC 2822 timestamps.
+#[derive(Debug, Clone, Default)]
+pub enum TimeFormat {
+    /// `Mon, 3 Jun 2024 09:00:00 +0000`, the format of the log file.
+    #[default]
+    Rfc2822,
+    /// `2024-06-03 09:00:00 +00:00`
+    Iso,
+    /// `2024-06-03T09:00:00+00:00`
+    Rfc3339,
+    /// Any strftime pattern, e.g. `%d.%m.%Y %H:%M`.
+    Pattern(String),
+    /// Relative to now, e.g. `25 minutes ago` or `yesterday 14:02`.
+    Relative,
+}
+
+impl TimeFormat {
+    /// Parses a preset name (`rfc2822`, `iso`, `rfc3339`, `relative`) or a
+    /// strftime pattern.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: If the value is neither a preset nor a valid pattern.
+    pub fn from_config(value: Option<&str>) -> Result<TimeFormat, ITrackerError> {
+        let format = match value {
+            None | Some("rfc2822") => TimeFormat::Rfc2822,
+            Some("iso") => TimeFormat::Iso,
+            Some("rfc3339") => TimeFormat::Rfc3339,
+            Some("relative") => TimeFormat::Relative,
+            Some(pattern) => {
+                // Reject patterns chrono cannot render instead of panicking while drawing
+                if StrftimeItems::new(pattern).parse().is_err() {
+                    return Err(ITrackerError::Config(format!(
+                            "Invalid time_format '{}', expected rfc2822, iso, rfc3339, relative or a strftime pattern",
+                            pattern
+                        )));
+                }
+                TimeFormat::Pattern(pattern.to_string())
+            }
+        };
+        Ok(format)
+    }
+
+    /// Renders a timestamp in this format.
+    pub fn format<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: Display,
+    {
+        match self {
+            TimeFormat::Rfc2822 => time.to_rfc2822(),
+            TimeFormat::Iso => time.format("%Y-%m-%d %H:%M:%S %:z").to_string(),
+            TimeFormat::Rfc3339 => time.to_rfc3339(),
+            TimeFormat::Pattern(pattern) => time.format(pattern).to_string(),
+            TimeFormat::Relative => relative(time, &Utc::now().with_timezone(&time.timezone())),
+        }
+    }
+
+    /// Renders a start time as stored in the log file in the given timezone,
+    /// or returns it unchanged if it cannot be parsed.
+    pub fn format_stored(&self, stored: &str, tz: Tz) -> String {
+        match DateTime::<FixedOffset>::parse_from_rfc2822(stored.trim()) {
+            Ok(time) => self.format(&time.with_timezone(&tz)),
+            Err(_) => stored.trim().to_string(),
+        }
+    }
+}
+
+/// `time` as seen from `now`: `just now`, `25 minutes ago`, `today 14:02`,
+/// `yesterday 14:02`, the weekday within the last week, and the date before
+/// that or for times still to come.
+pub fn relative<Tz: TimeZone>(time: &DateTime<Tz>, now: &DateTime<Tz>) -> String
+where
+    Tz::Offset: Display,
+{
+    let minutes = now
+        .clone()
+        .signed_duration_since(time.clone())
+        .num_minutes();
+    let clock = locale::time(time);
+    let days = (now.date_naive() - time.date_naive()).num_days();
+    match (minutes, days) {
+        (0, _) => tr_args("relative-now", &[]),
+        (1..=59, _) => tr_args("relative-minutes", &[("minutes", minutes.into())]),
+        (60.., 0) => tr_args("relative-today", &[("time", clock.into())]),
+        (60.., 1) => tr_args("relative-yesterday", &[("time", clock.into())]),
+        (60.., 2..=6) => tr_args(
+            "relative-weekday",
+            &[
+                ("weekday", locale::weekday(time.date_naive()).into()),
+                ("time", clock.into()),
+            ],
+        ),
+        _ => format!("{} {}", locale::date(time.date_naive()), clock),
+    }
+}
+
+/// How durations are shown to the user, set with `duration_format` in the config.
+///
+/// The log file itself always stores seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DurationFormat {
+    /// `1h 30m`, with seconds only for durations under a minute.
+    #[default]
+    Human,
+    /// `PT1H30M`, ISO 8601.
+    Iso,
+    /// `1.50h`
+    Decimal,
+    /// `1:30:00`
+    Clock,
+}
+
+impl DurationFormat {
+    /// Parses `human`, `iso`, `decimal` or `clock`.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: If the value is none of the formats.
+    pub fn from_config(value: Option<&str>) -> Result<DurationFormat, ITrackerError> {
+        match value {
+            None | Some("human") => Ok(DurationFormat::Human),
+            Some("iso") => Ok(DurationFormat::Iso),
+            Some("decimal") => Ok(DurationFormat::Decimal),
+            Some("clock") => Ok(DurationFormat::Clock),
+            Some(other) => Err(ITrackerError::Config(format!(
+                "Invalid duration_format '{}', expected human, iso, decimal or clock",
+                other
+            ))),
+        }
+    }
+
+    /// Renders a duration given in seconds in this format.
+    pub fn format(&self, seconds: u64) -> String {
+        let (hours, minutes, secs) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+        match self {
+            DurationFormat::Human => match (hours, minutes) {
+                (0, 0) => format!("{}s", secs),
+                (0, _) => format!("{}m", minutes),
+                (_, 0) => format!("{}h", hours),
+                _ => format!("{}h {}m", hours, minutes),
+            },
+            DurationFormat::Iso => {
+                let mut iso = "PT".to_string();
+                if hours > 0 {
+                    iso.push_str(&format!("{}H", hours));
+                }
+                if minutes > 0 {
+                    iso.push_str(&format!("{}M", minutes));
+                }
+                if secs > 0 || seconds == 0 {
+                    iso.push_str(&format!("{}S", secs));
+                }
+                iso
+            }
+            DurationFormat::Decimal => {
+                format!("{}h", locale::number(seconds as f64 / 3600.0, 2))
+            }
+            DurationFormat::Clock => format!("{}:{:02}:{:02}", hours, minutes, secs),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Times and durations as people write them, for `--at`, `--for` and
+//! `--since`.
+//!
+//! Durations are amounts with units, `1h30m`, `90 minutes`, `1.5 hours` or
+//! `2 hours and 15 minutes`, or a clock duration such as `1:30`. Times are
+//! a day, a time of day or both: `yesterday 3pm`, `last monday`,
+//! `friday at 9:30`, `14:00` (today), or `2024-06-03 14:00`; also `now`,
+//! `20 minutes ago`, `in 10 minutes` and RFC 3339. Times of day are read in
+//! the configured timezone; a day alone means its start.
+
+use crate::error::ITrackerError;
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+const DURATION_EXAMPLES: &str = "e.g. 1h30m, 90 minutes or 1:30";
+const TIME_EXAMPLES: &str =
+    "e.g. 14:30, 3pm, yesterday 3pm, last monday, 20 minutes ago or 2024-06-03 14:00";
+
+/// Reads a duration such as `1h30m`, `90 minutes` or `1:30`.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` naming the part that could not be read.
+pub fn duration(text: &str) -> Result<Duration, ITrackerError> {
+    let invalid = |detail: String| {
+        ITrackerError::Config(format!(
+            "Cannot read '{}' as a duration: {}, {}",
+            text.trim(),
+            detail,
+            DURATION_EXAMPLES
+        ))
+    };
+    let normalized = text.trim().to_lowercase();
+    if normalized.is_empty() {
+        return Err(invalid("it is empty".to_string()));
+    }
+    if normalized.contains(':') {
+        return clock_duration(&normalized).ok_or_else(|| invalid("expected h:mm".to_string()));
+    }
+    let normalized = normalized.strip_prefix("pt").unwrap_or(&normalized);
+    let normalized = normalized.replace(',', " ").replace(" and ", " ");
+
+    let mut seconds = 0.0;
+    let mut rest = normalized.trim_start();
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, after) = rest.split_at(number_end);
+        let amount: f64 = number.parse().map_err(|_| {
+            invalid(format!(
+                "expected a number at '{}'",
+                after.split_whitespace().next().unwrap_or(rest)
+            ))
+        })?;
+        let after = after.trim_start();
+        let unit_end = after
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(after.len());
+        let (unit, after) = after.split_at(unit_end);
+        let factor = match unit {
+            "" => return Err(invalid(format!("'{}' has no unit", number))),
+            "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+            "d" | "day" | "days" => 86400.0,
+            other => {
+                return Err(invalid(format!(
+                    "unknown unit '{}', expected seconds, minutes, hours or days",
+                    other
+                )))
+            }
+        };
+        seconds += amount * factor;
+        rest = after.trim_start();
+    }
+    Ok(Duration::seconds(seconds.round() as i64))
+}
+
+/// `h:mm` or `h:mm:ss`.
+fn clock_duration(text: &str) -> Option<Duration> {
+    let parts: Vec<i64> = text
+        .split(':')
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match parts[..] {
+        [hours, minutes] if minutes < 60 => Some(Duration::minutes(hours * 60 + minutes)),
+        [hours, minutes, seconds] if minutes < 60 && seconds < 60 => {
+            Some(Duration::seconds(hours * 3600 + minutes * 60 + seconds))
+        }
+        _ => None,
+    }
+}
+
+/// Reads a time such as `yesterday 3pm`, `last monday` or `20 minutes ago`.
+///
+/// # Arguments
+/// - `text`: The time as given.
+/// - `now`: The time relative times are counted from.
+/// - `tz`: The timezone days and times of day are read in.
+///
+/// # Returns
+/// - `Err(ITrackerError)`: `Config` naming the part that could not be read,
+///   or if the time does not exist in `tz`, e.g. skipped by daylight saving.
+pub fn moment(text: &str, now: DateTime<Utc>, tz: Tz) -> Result<DateTime<Utc>, ITrackerError> {
+    moment_on(text, now, tz, now.with_timezone(&tz).date_naive())
+}
+
+/// Like [`moment`], but a time of day without a day falls on `default_day`
+/// instead of today, e.g. for times of an entry of another day.
+pub fn moment_on(
+    text: &str,
+    now: DateTime<Utc>,
+    tz: Tz,
+    default_day: NaiveDate,
+) -> Result<DateTime<Utc>, ITrackerError> {
+    let trimmed = text.trim();
+    let invalid = |detail: String| {
+        ITrackerError::Config(format!(
+            "Cannot read '{}' as a time: {}, {}",
+            trimmed, detail, TIME_EXAMPLES
+        ))
+    };
+    if let Ok(time) = DateTime::parse_from_rfc3339(trimmed) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc2822(trimmed) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let normalized = trimmed.to_lowercase();
+    if normalized.is_empty() {
+        return Err(invalid("it is empty".to_string()));
+    }
+    if normalized == "now" {
+        return Ok(now);
+    }
+    if let Some(ago) = normalized.strip_suffix(" ago") {
+        return Ok(now - duration(ago)?);
+    }
+    if let Some(ahead) = normalized.strip_prefix("in ") {
+        return Ok(now + duration(ahead)?);
+    }
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&normalized, format) {
+            return local(naive, tz, trimmed);
+        }
+    }
+
+    let today = now.with_timezone(&tz).date_naive();
+    let tokens: Vec<&str> = normalized
+        .split_whitespace()
+        .filter(|token| *token != "at")
+        .collect();
+    let (mut day, mut time) = (None, None);
+    let mut i = 0;
+    while i < tokens.len() {
+        if day.is_none() {
+            if let Some((found, used)) = parse_day(&tokens[i..], today) {
+                day = Some(found);
+                i += used;
+                continue;
+            }
+        }
+        if time.is_none() {
+            if let Some((found, used)) = parse_time(&tokens[i..]) {
+                time = Some(found);
+                i += used;
+                continue;
+            }
+        }
+        return Err(invalid(format!(
+            "'{}' is neither a day nor a time",
+            tokens[i]
+        )));
+    }
+    let day = day.unwrap_or(if time.is_some() { default_day } else { today });
+    let time = time.unwrap_or(NaiveTime::MIN);
+    local(day.and_time(time), tz, trimmed)
+}
+
+/// A day at the start of `tokens` and how many tokens it took.
+fn parse_day(tokens: &[&str], today: NaiveDate) -> Option<(NaiveDate, usize)> {
+    let first = *tokens.first()?;
+    match first {
+        "today" => return Some((today, 1)),
+        "yesterday" => return Some((today.pred_opt()?, 1)),
+        "tomorrow" => return Some((today.succ_opt()?, 1)),
+        "last" | "next" => {
+            let weekday: Weekday = tokens.get(1)?.parse().ok()?;
+            let day = if first == "last" {
+                let back = (today.weekday().num_days_from_monday() + 7
+                    - weekday.num_days_from_monday())
+                    % 7;
+                today - Duration::days(if back == 0 { 7 } else { back.into() })
+            } else {
+                let ahead = (weekday.num_days_from_monday() + 7
+                    - today.weekday().num_days_from_monday())
+                    % 7;
+                today + Duration::days(if ahead == 0 { 7 } else { ahead.into() })
+            };
+            return Some((day, 2));
+        }
+        _ => {}
+    }
+    if let Ok(day) = NaiveDate::parse_from_str(first, "%Y-%m-%d") {
+        return Some((day, 1));
+    }
+    // A weekday alone is the latest one, today included
+    let weekday: Weekday = first.parse().ok()?;
+    let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    Some((today - Duration::days(back.into()), 1))
+}
+
+/// A time of day at the start of `tokens` and how many tokens it took:
+/// `15:30`, `3pm`, `3:30 pm`, `noon` or `midnight`.
+fn parse_time(tokens: &[&str]) -> Option<(NaiveTime, usize)> {
+    let first = *tokens.first()?;
+    match first {
+        "noon" => return Some((NaiveTime::from_hms_opt(12, 0, 0)?, 1)),
+        "midnight" => return Some((NaiveTime::MIN, 1)),
+        _ => {}
+    }
+    let (clock, meridiem, used) = match ["am", "pm"]
+        .into_iter()
+        .find_map(|suffix| first.strip_suffix(suffix).map(|clock| (clock, suffix)))
+    {
+        Some((clock, suffix)) => (clock, Some(suffix), 1),
+        None => match tokens.get(1).copied() {
+            Some(suffix @ ("am" | "pm")) => (first, Some(suffix), 2),
+            _ => (first, None, 1),
+        },
+    };
+    let mut parts = clock.split(':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = match parts.next() {
+        Some(minute) => minute.parse().ok()?,
+        // A bare number could be anything, e.g. a duration missing its unit
+        None if meridiem.is_none() => return None,
+        None => 0,
+    };
+    let second: u32 = match parts.next() {
+        Some(second) => second.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    let hour = match meridiem {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some("am") => hour % 12,
+        Some(_) => hour % 12 + 12,
+        None => hour,
+    };
+    Some((NaiveTime::from_hms_opt(hour, minute, second)?, used))
+}
+
+/// `naive` read in `tz`, the earlier one of times repeated by daylight saving.
+fn local(naive: NaiveDateTime, tz: Tz, text: &str) -> Result<DateTime<Utc>, ITrackerError> {
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| {
+            ITrackerError::Config(format!(
+                "'{}' does not exist in {}, skipped when clocks moved forward",
+                text, tz
+            ))
+        })
+}
@@ -0,0 +1,205 @@
+//! Pushing entries to Toggl Track through its v9 API.
+//!
+//! `sync toggl` creates a Toggl time entry for every stopped entry of the
+//! output file that was not pushed before, and remembers the ID Toggl gave
+//! it under `toggl` in the entry's metadata, so entries are never pushed
+//! twice. Projects are found by name in the workspace and created if
+//! missing; tags are sent by name, Toggl creates the ones it does not know.
+//! Nothing is pulled back, and entries changed after their push stay as
+//! they were in Toggl.
+
+use crate::{error::ITrackerError, log::LogEntry, report};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{SecondsFormat, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// The API entries are pushed to unless `toggl.api_url` is set.
+pub const DEFAULT_API_URL: &str = "https://api.track.toggl.com/api/v9";
+
+/// Metadata key holding the ID of the Toggl time entry an entry was pushed as.
+pub const METADATA_KEY: &str = "toggl";
+
+/// The `[toggl]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TogglConfig {
+    /// API token of the profile page, better given as `ITRACKER_TOGGL__TOKEN`.
+    pub token: Option<String>,
+    /// ID of the workspace entries are created in, the default workspace of
+    /// the token's user if unset.
+    pub workspace: Option<u64>,
+    /// Base URL of the API, for proxies and tests.
+    pub api_url: Option<String>,
+}
+
+/// A project of the workspace.
+#[derive(Debug, Deserialize)]
+struct Project {
+    id: u64,
+    name: String,
+}
+
+/// A Toggl Track account entries are pushed to.
+#[derive(Debug, Clone)]
+pub struct Toggl {
+    /// Without a trailing slash.
+    api_url: String,
+    /// Value of the `Authorization` header.
+    authorization: String,
+    workspace: Option<u64>,
+}
+
+impl Toggl {
+    /// Resolves the `[toggl]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Toggl))`: If a token is configured.
+    /// - `Ok(None)`: If entries are not pushed to Toggl.
+    /// - `Err(ITrackerError)`: `Config` if the API URL is not an http(s) URL.
+    pub fn from_config(config: Option<&TogglConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        let Some(token) = config.token.as_deref().filter(|token| !token.is_empty()) else {
+            return Ok(None);
+        };
+        let api_url = config.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+        if !(api_url.starts_with("https://") || api_url.starts_with("http://")) {
+            return Err(ITrackerError::Config(format!(
+                "api_url must start with https:// or http://, got {}",
+                api_url
+            )));
+        }
+        // Toggl takes the token as user name with the password `api_token`
+        let credentials = STANDARD.encode(format!("{}:api_token", token));
+        Ok(Some(Toggl {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            authorization: format!("Basic {}", credentials),
+            workspace: config.workspace,
+        }))
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Creates a time entry for every stopped entry without a Toggl ID.
+    ///
+    /// # Arguments
+    /// - `entries`: The entries of the output file.
+    /// - `pushed`: Called with each entry and its new Toggl ID right after
+    ///   it was created, to remember the ID even if a later entry fails.
+    ///
+    /// # Returns
+    /// - `Ok(usize)`: The number of entries pushed.
+    /// - `Err(ITrackerError)`: `Sync` if Toggl cannot be reached or refuses
+    ///   an entry, or the error of `pushed`.
+    pub fn push(
+        &self,
+        entries: &[LogEntry],
+        mut pushed: impl FnMut(&LogEntry, u64) -> Result<(), ITrackerError>,
+    ) -> Result<usize, ITrackerError> {
+        let pending: Vec<&LogEntry> = entries
+            .iter()
+            .filter(|entry| !entry.is_running() && entry.start().is_some())
+            .filter(|entry| !entry.metadata.contains_key(METADATA_KEY))
+            .collect();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        let workspace = match self.workspace {
+            Some(workspace) => workspace,
+            None => self.get::<Value>("/me")?["default_workspace_id"]
+                .as_u64()
+                .ok_or_else(|| {
+                    self.error("the user has no default workspace, set `toggl.workspace`")
+                })?,
+        };
+        let mut projects: HashMap<String, u64> = self
+            .get::<Option<Vec<Project>>>(&format!("/workspaces/{}/projects", workspace))?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|project| (project.name.to_lowercase(), project.id))
+            .collect();
+
+        for entry in &pending {
+            let project = match entry.project.trim() {
+                "" => None,
+                name => Some(match projects.get(&name.to_lowercase()) {
+                    Some(id) => *id,
+                    None => {
+                        let created: Project = self.post(
+                            &format!("/workspaces/{}/projects", workspace),
+                            &json!({ "name": name, "active": true }),
+                        )?;
+                        projects.insert(name.to_lowercase(), created.id);
+                        created.id
+                    }
+                }),
+            };
+            let start = entry
+                .start()
+                .map(|start| start.with_timezone(&Utc))
+                .unwrap_or_default();
+            let created: Value = self.post(
+                &format!("/workspaces/{}/time_entries", workspace),
+                &json!({
+                    "created_with": "itracker",
+                    "workspace_id": workspace,
+                    "start": start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    "duration": report::tracked_seconds(entry, Utc::now()),
+                    "description": entry.display_title(),
+                    "project_id": project,
+                    "tags": entry.tags,
+                }),
+            )?;
+            let id = created["id"]
+                .as_u64()
+                .ok_or_else(|| self.error("the created time entry has no ID"))?;
+            pushed(entry, id)?;
+        }
+        Ok(pending.len())
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ITrackerError> {
+        let response = ureq::get(&format!("{}{}", self.api_url, path))
+            .set("Authorization", &self.authorization)
+            .call()
+            .map_err(|e| self.failure(e))?;
+        self.parse(response)
+    }
+
+    fn post<T: DeserializeOwned>(&self, path: &str, body: &Value) -> Result<T, ITrackerError> {
+        let response = ureq::post(&format!("{}{}", self.api_url, path))
+            .set("Authorization", &self.authorization)
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string())
+            .map_err(|e| self.failure(e))?;
+        self.parse(response)
+    }
+
+    fn parse<T: DeserializeOwned>(&self, response: ureq::Response) -> Result<T, ITrackerError> {
+        let body = response.into_string().map_err(|e| self.error(e))?;
+        serde_json::from_str(&body).map_err(|e| self.error(format!("unexpected answer: {}", e)))
+    }
+
+    /// Describes a failed request, with Toggl's own message if it sent one.
+    fn failure(&self, error: ureq::Error) -> ITrackerError {
+        match error {
+            ureq::Error::Status(status, response) => {
+                let message = response.into_string().unwrap_or_default();
+                match message.trim().trim_matches('"') {
+                    "" => self.error(format!("HTTP status {}", status)),
+                    message => self.error(format!("HTTP status {}: {}", status, message)),
+                }
+            }
+            e => self.error(e),
+        }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> ITrackerError {
+        ITrackerError::Sync(format!("{}: {}", self.api_url, message))
+    }
+}
@@ -0,0 +1,582 @@
+use crate::{
+    clock::{Clock, SystemClock},
+    error::ITrackerError,
+    hooks::{HookEvent, Hooks},
+    idle::{self, IdleChoice},
+    log::{LogEntry, TaskDetails},
+    report::{self, Summary},
+    storage::Storage,
+    timer::Timer,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use std::sync::Arc;
+
+/// Starts and stops timers on a log file and summarizes what was tracked.
+///
+/// This is the entry point for programs embedding ITracker:
+///
+/// ```no_run
+/// use itracker::{Storage, TaskDetails, Tracker};
+///
+/// let tracker = Tracker::new(Storage::new("time.csv"));
+/// let task = TaskDetails {
+///     title: "Review".to_string(),
+///     ..TaskDetails::default()
+/// };
+/// let entry = tracker.start(&task)?;
+/// tracker.stop(entry.index)?;
+/// # Ok::<(), itracker::ITrackerError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    storage: Storage,
+    /// Source of the start, pause and stop times.
+    clock: Arc<dyn Clock>,
+    /// Commands run after timers changed.
+    hooks: Hooks,
+}
+
+impl Tracker {
+    /// A tracker on `storage` using the system clock.
+    pub fn new(storage: Storage) -> Self {
+        Tracker {
+            storage,
+            clock: Arc::new(SystemClock::new()),
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// Takes all times from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Runs `hooks` after every start, pause, resume and stop.
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    /// Starts a timer for `task`, returning the new running entry.
+    pub fn start(&self, task: &TaskDetails) -> Result<LogEntry, ITrackerError> {
+        let entry = self
+            .storage
+            .append(task, &mut Timer::with_clock(self.clock.clone()))?;
+        self.hooks
+            .run(HookEvent::Start, &entry, self.storage.path());
+        Ok(entry)
+    }
+
+    /// Starts a timer for `task` as of `at`, e.g. for work begun before the
+    /// timer was.
+    pub fn start_at(
+        &self,
+        task: &TaskDetails,
+        at: DateTime<Utc>,
+    ) -> Result<LogEntry, ITrackerError> {
+        let entry = self
+            .storage
+            .append(task, &mut Timer::with_clock(Arc::new(at)))?;
+        self.hooks
+            .run(HookEvent::Start, &entry, self.storage.path());
+        Ok(entry)
+    }
+
+    /// Stops the timer of the entry with the given index at the current time.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The entry with its elapsed time filled in.
+    /// - `Err(ITrackerError)`: `NotRunning` if the entry was stopped before,
+    ///   or if it does not exist, has an invalid start time, or the file
+    ///   cannot be written.
+    pub fn stop(&self, index: usize) -> Result<LogEntry, ITrackerError> {
+        // A clock set back since the start gives no error, only the shortest time
+        self.finish(index, self.clock.now(), false)
+    }
+
+    /// Stops the timer of the entry with the given index as of `at`.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: `StopsBeforeStart` if `at` does not lie after
+    ///   the start, or the errors of [`Tracker::stop`].
+    pub fn stop_at(&self, index: usize, at: DateTime<Utc>) -> Result<LogEntry, ITrackerError> {
+        self.finish(index, at, true)
+    }
+
+    fn finish(
+        &self,
+        index: usize,
+        at: DateTime<Utc>,
+        given: bool,
+    ) -> Result<LogEntry, ITrackerError> {
+        let mut entry = self.storage.entry(index)?;
+        // Stopping again would overwrite its time with the time since its start
+        if !entry.is_running() {
+            return Err(ITrackerError::NotRunning { index });
+        }
+        let start = entry.start().ok_or_else(|| ITrackerError::InvalidRecord {
+            path: self.storage.path().into(),
+            record: index,
+            message: format!("invalid start time '{}'", entry.start_time),
+        })?;
+
+        if given && at <= start {
+            return Err(ITrackerError::StopsBeforeStart { index });
+        }
+
+        // A pause still in progress ends with the timer
+        if end_pause(&mut entry, at) {
+            entry.metadata.remove(idle::METADATA_KEY);
+        }
+        entry.set_elapsed((at - start.with_timezone(&Utc)).num_seconds());
+        self.storage.update(&entry)?;
+        self.hooks.run(HookEvent::Stop, &entry, self.storage.path());
+        Ok(entry)
+    }
+
+    /// Moves the start or the end of the entry with the given index,
+    /// recomputing its elapsed time; a running entry given an end stops.
+    ///
+    /// # Arguments
+    /// - `index`: The entry to amend.
+    /// - `start`: The new start, `None` to keep it; the end stays in place.
+    /// - `end`: The new end, `None` to keep it or keep the entry running.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The amended entry.
+    /// - `Err(ITrackerError)`: `StopsBeforeStart` if the end would not lie
+    ///   after the start, `Overlaps` if the entry would overlap another one,
+    ///   `Config` if it would end in the future or be shorter than its
+    ///   pauses, or the errors of [`Tracker::stop`].
+    pub fn amend(
+        &self,
+        index: usize,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<LogEntry, ITrackerError> {
+        let mut entry = self.storage.entry(index)?;
+        let old_start = entry
+            .start()
+            .ok_or_else(|| ITrackerError::InvalidRecord {
+                path: self.storage.path().into(),
+                record: index,
+                message: format!("invalid start time '{}'", entry.start_time),
+            })?
+            .with_timezone(&Utc);
+        let now = self.clock.now();
+        let running = entry.is_running();
+        let new_start = start.unwrap_or(old_start);
+        let new_end = match end {
+            Some(end) => Some(end),
+            None if running => None,
+            None => Some(old_start + chrono::Duration::seconds(tracked(&entry.elapsed_time))),
+        };
+
+        if new_end.is_some_and(|end| end <= new_start) {
+            return Err(ITrackerError::StopsBeforeStart { index });
+        }
+        if new_end.unwrap_or(new_start) > now {
+            return Err(ITrackerError::Config(format!(
+                "Log entry {} cannot start or end in the future",
+                index
+            )));
+        }
+        let span = (new_start, new_end.unwrap_or(now));
+        for other in self.storage.entries()? {
+            if other.index == index {
+                continue;
+            }
+            let Some(other_start) = other.start().map(|start| start.with_timezone(&Utc)) else {
+                continue;
+            };
+            let other_end = if other.is_running() {
+                now
+            } else {
+                other_start + chrono::Duration::seconds(tracked(&other.elapsed_time))
+            };
+            if span.0 < other_end && other_start < span.1 {
+                return Err(ITrackerError::Overlaps {
+                    index,
+                    other: other.index,
+                });
+            }
+        }
+
+        if start.is_some() {
+            entry.start_time = new_start.to_rfc2822();
+        }
+        let stopped = running && new_end.is_some();
+        if let Some(end) = new_end {
+            if end_pause(&mut entry, end) {
+                entry.metadata.remove(idle::METADATA_KEY);
+            }
+            let elapsed = (end - new_start).num_seconds();
+            if tracked(&entry.paused_time) > elapsed {
+                return Err(ITrackerError::Config(format!(
+                    "Log entry {} would be shorter than its pauses",
+                    index
+                )));
+            }
+            entry.set_elapsed(elapsed);
+        }
+        self.storage.update(&entry)?;
+        if stopped {
+            self.hooks.run(HookEvent::Stop, &entry, self.storage.path());
+        }
+        Ok(entry)
+    }
+
+    /// Splits the entry with the given index at `at` into two entries, e.g.
+    /// when switching tasks was forgotten. The first part keeps the index,
+    /// the second one is appended with a new index and runs on if the entry
+    /// did. Paused time stays with the first part as far as it fits.
+    ///
+    /// # Arguments
+    /// - `index`: The entry to split.
+    /// - `at`: Where to split, between the start and the end of the entry.
+    /// - `task`: The task of the second part, `None` for the same task.
+    ///
+    /// # Returns
+    /// - `Ok((LogEntry, LogEntry))`: The first and the second part.
+    /// - `Err(ITrackerError)`: `Config` if `at` does not lie within the
+    ///   entry, or a read or write error.
+    pub fn split(
+        &self,
+        index: usize,
+        at: DateTime<Utc>,
+        task: Option<&TaskDetails>,
+    ) -> Result<(LogEntry, LogEntry), ITrackerError> {
+        let mut first = self.storage.entry(index)?;
+        let start = first
+            .start()
+            .ok_or_else(|| ITrackerError::InvalidRecord {
+                path: self.storage.path().into(),
+                record: index,
+                message: format!("invalid start time '{}'", first.start_time),
+            })?
+            .with_timezone(&Utc);
+        let running = first.is_running();
+        let end = if running {
+            self.clock.now()
+        } else {
+            start + chrono::Duration::seconds(tracked(&first.elapsed_time))
+        };
+        if at <= start || at >= end {
+            return Err(ITrackerError::Config(format!(
+                "Log entry {} can only be split at a time after its start and before its end",
+                index
+            )));
+        }
+
+        let task = task.cloned().unwrap_or_else(|| TaskDetails::from(&first));
+        let mut second = self
+            .storage
+            .append(&task, &mut Timer::with_clock(Arc::new(at)))?;
+        let (before, after) = ((at - start).num_seconds(), (end - at).num_seconds());
+        let paused = tracked(&first.paused_time);
+        second.paused_time = (paused - before).max(0).to_string();
+        if running {
+            // A pause in progress goes on in the part still running
+            second.paused_since = std::mem::take(&mut first.paused_since);
+            if let Some(reason) = first.metadata.remove(idle::METADATA_KEY) {
+                second
+                    .metadata
+                    .insert(idle::METADATA_KEY.to_string(), reason);
+            }
+        } else {
+            second.set_elapsed(after);
+        }
+        first.set_elapsed(before);
+        first.paused_time = paused.min(before).to_string();
+        self.storage.update(&second)?;
+        self.storage.update(&first)?;
+        Ok((first, second))
+    }
+
+    /// Merges stopped entries into the one that started first, e.g. a task
+    /// tracked in pieces or twice. Elapsed and paused times are summed, so
+    /// the tracked time stays the same; distinct descriptions are joined by
+    /// lines and tags are combined. The other entries are removed.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The merged entry.
+    /// - `Err(ITrackerError)`: `Config` for fewer than two entries, running
+    ///   entries or entries of different projects, `EntryNotFound`, or a
+    ///   read or write error.
+    pub fn merge(&self, indices: &[usize]) -> Result<LogEntry, ITrackerError> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.len() < 2 {
+            return Err(ITrackerError::Config(
+                "Give at least two different entries to merge".to_string(),
+            ));
+        }
+        let mut entries = Vec::new();
+        for index in &indices {
+            let entry = self.storage.entry(*index)?;
+            if entry.is_running() {
+                return Err(ITrackerError::Config(format!(
+                    "Log entry {} is still running, stop it before merging",
+                    index
+                )));
+            }
+            let start = entry.start().ok_or_else(|| ITrackerError::InvalidRecord {
+                path: self.storage.path().into(),
+                record: *index,
+                message: format!("invalid start time '{}'", entry.start_time),
+            })?;
+            entries.push((start, entry));
+        }
+        entries.sort_by_key(|(start, entry)| (*start, entry.index));
+        let mut projects: Vec<&str> = entries
+            .iter()
+            .map(|(_, entry)| entry.project.trim())
+            .filter(|project| !project.is_empty())
+            .collect();
+        projects.sort_unstable();
+        projects.dedup();
+        if projects.len() > 1 {
+            return Err(ITrackerError::Config(format!(
+                "Entries of different projects cannot be merged: {}",
+                projects.join(", ")
+            )));
+        }
+
+        let (_, mut merged) = entries.remove(0);
+        let (mut elapsed, mut paused) =
+            (tracked(&merged.elapsed_time), tracked(&merged.paused_time));
+        let mut descriptions: Vec<String> = vec![merged.message.trim().to_string()];
+        for (_, entry) in &entries {
+            elapsed += tracked(&entry.elapsed_time);
+            paused += tracked(&entry.paused_time);
+            if merged.title.trim().is_empty() {
+                merged.title = entry.title.clone();
+            }
+            if merged.project.trim().is_empty() {
+                merged.project = entry.project.clone();
+            }
+            let description = entry.message.trim().to_string();
+            if !descriptions.contains(&description) {
+                descriptions.push(description);
+            }
+            for tag in &entry.tags {
+                if !merged.tags.contains(tag) {
+                    merged.tags.push(tag.clone());
+                }
+            }
+            merged.estimate = merged.estimate.or(entry.estimate);
+            for (key, value) in &entry.metadata {
+                merged
+                    .metadata
+                    .entry(key.clone())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+        descriptions.retain(|description| !description.is_empty());
+        merged.message = descriptions.join("\n");
+        merged.set_elapsed(elapsed);
+        merged.paused_time = paused.to_string();
+
+        let removed: Vec<usize> = entries.iter().map(|(_, entry)| entry.index).collect();
+        self.storage.merge_into(&merged, &removed)?;
+        Ok(merged)
+    }
+
+    /// Pauses the entry with the given index, or the running entry if `None`.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The paused entry.
+    /// - `Err(ITrackerError)`: `NoActiveTimer`, `NotRunning` or `AlreadyPaused`
+    ///   if there is nothing to pause, or a read or write error.
+    pub fn pause(&self, index: Option<usize>) -> Result<LogEntry, ITrackerError> {
+        let mut entry = self.target(index)?;
+        if entry.paused_since().is_some() {
+            return Err(ITrackerError::AlreadyPaused { index: entry.index });
+        }
+
+        entry.paused_since = self.clock.now().to_rfc2822();
+        self.storage.update(&entry)?;
+        self.hooks
+            .run(HookEvent::Pause, &entry, self.storage.path());
+        Ok(entry)
+    }
+
+    /// Resumes the entry with the given index, or the running entry if `None`,
+    /// adding the pause to its paused time.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The resumed entry.
+    /// - `Err(ITrackerError)`: `NoActiveTimer`, `NotRunning` or `NotPaused`
+    ///   if there is nothing to resume, or a read or write error.
+    pub fn resume(&self, index: Option<usize>) -> Result<LogEntry, ITrackerError> {
+        let mut entry = self.target(index)?;
+        if !end_pause(&mut entry, self.clock.now()) {
+            return Err(ITrackerError::NotPaused { index: entry.index });
+        }
+        // A plain resume leaves idle time paused
+        entry.metadata.remove(idle::METADATA_KEY);
+
+        self.storage.update(&entry)?;
+        self.hooks
+            .run(HookEvent::Resume, &entry, self.storage.path());
+        Ok(entry)
+    }
+
+    /// Pauses the entry with the given index, or the running entry if `None`,
+    /// as of `since`, when the user went idle, and marks it for
+    /// [`Tracker::resume_idle`].
+    ///
+    /// The pause starts no earlier than the entry.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The paused entry.
+    /// - `Err(ITrackerError)`: As for [`Tracker::pause`].
+    pub fn pause_idle(
+        &self,
+        index: Option<usize>,
+        since: DateTime<Utc>,
+    ) -> Result<LogEntry, ITrackerError> {
+        let mut entry = self.target(index)?;
+        if entry.paused_since().is_some() {
+            return Err(ITrackerError::AlreadyPaused { index: entry.index });
+        }
+
+        let start = entry.start().map(|start| start.with_timezone(&Utc));
+        let since = start
+            .map_or(since, |start| since.max(start))
+            .min(self.clock.now());
+        entry.paused_since = since.to_rfc2822();
+        entry
+            .metadata
+            .insert(idle::METADATA_KEY.to_string(), "true".to_string());
+        self.storage.update(&entry)?;
+        self.hooks
+            .run(HookEvent::Pause, &entry, self.storage.path());
+        Ok(entry)
+    }
+
+    /// Resumes an entry paused by [`Tracker::pause_idle`], like
+    /// [`Tracker::resume`], and settles the idle time as chosen.
+    ///
+    /// # Arguments
+    /// - `index`: The entry, or the running entry if `None`.
+    /// - `choice`: Whether the idle time counts as work, as a pause, or as
+    ///   a new stopped entry of another task, started when the pause did.
+    ///
+    /// # Returns
+    /// - `Ok(LogEntry)`: The resumed entry.
+    /// - `Err(ITrackerError)`: As for [`Tracker::resume`].
+    pub fn resume_idle(
+        &self,
+        index: Option<usize>,
+        choice: &IdleChoice,
+    ) -> Result<LogEntry, ITrackerError> {
+        let mut entry = self.target(index)?;
+        let Some(since) = entry.paused_since() else {
+            return Err(ITrackerError::NotPaused { index: entry.index });
+        };
+        let now = self.clock.now();
+        match choice {
+            IdleChoice::Keep => entry.paused_since.clear(),
+            IdleChoice::Discard | IdleChoice::Move { .. } => {
+                end_pause(&mut entry, now);
+            }
+        }
+        entry.metadata.remove(idle::METADATA_KEY);
+        self.storage.update(&entry)?;
+
+        if let IdleChoice::Move { task } = choice {
+            let mut moved = self
+                .storage
+                .append(task, &mut Timer::with_clock(self.clock.clone()))?;
+            moved.start_time = since.to_rfc2822();
+            moved.set_elapsed((now - since.with_timezone(&Utc)).num_seconds());
+            self.storage.update(&moved)?;
+        }
+        self.hooks
+            .run(HookEvent::Resume, &entry, self.storage.path());
+        Ok(entry)
+    }
+
+    /// The running entry with the given index, or the running entry if `None`.
+    fn target(&self, index: Option<usize>) -> Result<LogEntry, ITrackerError> {
+        match index {
+            Some(index) => {
+                let entry = self.storage.entry(index)?;
+                if entry.is_running() {
+                    Ok(entry)
+                } else {
+                    Err(ITrackerError::NotRunning { index })
+                }
+            }
+            None => self.running()?.ok_or_else(|| ITrackerError::NoActiveTimer {
+                path: self.storage.path().into(),
+            }),
+        }
+    }
+
+    /// Stops the most recently started entry that is still running.
+    ///
+    /// # Returns
+    /// - `Err(ITrackerError)`: `NoActiveTimer` if no entry is running.
+    pub fn stop_running(&self) -> Result<LogEntry, ITrackerError> {
+        let running = self
+            .running()?
+            .ok_or_else(|| ITrackerError::NoActiveTimer {
+                path: self.storage.path().into(),
+            })?;
+        self.stop(running.index)
+    }
+
+    /// The most recently started entry that has not been stopped yet.
+    pub fn running(&self) -> Result<Option<LogEntry>, ITrackerError> {
+        self.storage.running()
+    }
+
+    /// Tracked time of the entries started between `from` and `to` (both inclusive).
+    ///
+    /// # Arguments
+    /// - `from`: The first day of the range.
+    /// - `to`: The last day of the range.
+    /// - `tz`: The timezone the days are counted in.
+    pub fn summary(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        tz: Tz,
+    ) -> Result<Summary, ITrackerError> {
+        let entries = self.storage.entries()?;
+        Ok(report::summarize(
+            report::entries_between(&entries, from, to, tz),
+            self.clock.now(),
+            tz,
+        ))
+    }
+}
+
+/// Seconds as stored in the log file, zero if unreadable.
+fn tracked(seconds: &str) -> i64 {
+    seconds.trim().parse().unwrap_or(0)
+}
+
+/// Adds a pause in progress to the paused time of `entry`.
+///
+/// # Returns
+/// Whether the entry was paused.
+fn end_pause(entry: &mut LogEntry, now: DateTime<Utc>) -> bool {
+    let Some(since) = entry.paused_since() else {
+        return false;
+    };
+    let paused = entry.paused_time.trim().parse::<i64>().unwrap_or(0)
+        + (now - since.with_timezone(&Utc)).num_seconds().max(0);
+    entry.paused_time = paused.to_string();
+    entry.paused_since.clear();
+    true
+}
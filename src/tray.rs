@@ -0,0 +1,202 @@
+use chrono::Utc;
+use itracker::{
+    daemon::{Operation, Timers},
+    log::{LogEntry, TaskDetails},
+    report,
+    timefmt::DurationFormat,
+    ITrackerError,
+};
+use ksni::{
+    blocking::TrayMethods,
+    menu::{MenuItem, StandardItem},
+    ToolTip,
+};
+use std::{io, thread, time::Duration};
+
+/// How often the running timer is read again from the log file.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The tray icon and its menu, showing the state of the log file.
+#[derive(Debug)]
+struct Tray {
+    timers: Timers,
+    durations: DurationFormat,
+    /// The most recently started entry that is still running.
+    running: Option<LogEntry>,
+    /// The last entry of the file, offered to be timed again.
+    last: Option<LogEntry>,
+    /// Why the file could not be read or the last action failed.
+    error: Option<String>,
+}
+
+impl Tray {
+    /// Reads the running and the last entry again.
+    fn refresh(&mut self) {
+        let tracker = self.timers.tracker();
+        match tracker
+            .running()
+            .and_then(|running| Ok((running, tracker.storage().entries()?)))
+        {
+            Ok((running, entries)) => {
+                self.running = running;
+                self.last = entries.into_iter().last();
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Applies a menu action through the daemon if one runs, then shows the result.
+    fn run(&mut self, operation: Operation) {
+        let result = self.timers.run(operation);
+        self.refresh();
+        if let Err(e) = result {
+            self.error = Some(e.to_string());
+        }
+    }
+
+    fn paused(&self) -> bool {
+        self.running
+            .as_ref()
+            .is_some_and(|entry| entry.paused_since().is_some())
+    }
+
+    /// The task and its tracked time, e.g. `Review · 1h 5m`.
+    fn status(&self) -> String {
+        match &self.running {
+            Some(entry) => format!(
+                "{} · {}{}",
+                entry.display_title(),
+                self.durations
+                    .format(report::tracked_seconds(entry, Utc::now())),
+                if self.paused() { " (paused)" } else { "" }
+            ),
+            None => "No timer running".to_string(),
+        }
+    }
+}
+
+impl ksni::Tray for Tray {
+    fn id(&self) -> String {
+        "itracker".into()
+    }
+
+    fn title(&self) -> String {
+        format!("ITracker: {}", self.status())
+    }
+
+    fn icon_name(&self) -> String {
+        match (&self.running, self.paused()) {
+            (Some(_), false) => "media-playback-start",
+            (Some(_), true) => "media-playback-pause",
+            (None, _) => "media-playback-stop",
+        }
+        .into()
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self.status(),
+            description: self.error.clone().unwrap_or_default(),
+            ..ToolTip::default()
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut items: Vec<MenuItem<Self>> = vec![StandardItem {
+            label: self.status(),
+            enabled: false,
+            ..StandardItem::default()
+        }
+        .into()];
+        if let Some(error) = &self.error {
+            items.push(
+                StandardItem {
+                    label: error.clone(),
+                    enabled: false,
+                    icon_name: "dialog-error".into(),
+                    ..StandardItem::default()
+                }
+                .into(),
+            );
+        }
+        items.push(MenuItem::Separator);
+
+        if self.running.is_some() {
+            let (label, operation): (&str, fn() -> Operation) = if self.paused() {
+                ("Resume", || Operation::Resume { index: None })
+            } else {
+                ("Pause", || Operation::Pause { index: None })
+            };
+            items.push(
+                StandardItem {
+                    label: label.into(),
+                    activate: Box::new(move |tray: &mut Self| tray.run(operation())),
+                    ..StandardItem::default()
+                }
+                .into(),
+            );
+            items.push(
+                StandardItem {
+                    label: "Stop".into(),
+                    activate: Box::new(|tray: &mut Self| tray.run(Operation::Stop { index: None })),
+                    ..StandardItem::default()
+                }
+                .into(),
+            );
+        }
+        if let Some(last) = &self.last {
+            let task = TaskDetails::from(last);
+            items.push(
+                StandardItem {
+                    label: format!("Start \"{}\" again", last.display_title()),
+                    activate: Box::new(move |tray: &mut Self| {
+                        tray.run(Operation::Start { task: task.clone() })
+                    }),
+                    ..StandardItem::default()
+                }
+                .into(),
+            );
+        }
+
+        items.push(MenuItem::Separator);
+        items.push(
+            StandardItem {
+                label: "Quit".into(),
+                icon_name: "application-exit".into(),
+                activate: Box::new(|_| std::process::exit(0)),
+                ..StandardItem::default()
+            }
+            .into(),
+        );
+        items
+    }
+}
+
+/// Shows the tray icon until it is quit from its menu.
+///
+/// The state is read from the log file every few seconds, so timers started
+/// or stopped elsewhere show up; actions go through the daemon when one runs.
+pub fn run(timers: Timers, durations: DurationFormat) -> Result<(), ITrackerError> {
+    let mut tray = Tray {
+        timers,
+        durations,
+        running: None,
+        last: None,
+        error: None,
+    };
+    tray.refresh();
+    // Started with the session, the icon may come up before the panel showing it
+    let handle = tray.assume_sni_available(true).spawn().map_err(|e| {
+        ITrackerError::Io(io::Error::other(format!(
+            "Cannot show the tray icon, is a StatusNotifierItem host running? {}",
+            e
+        )))
+    })?;
+
+    while !handle.is_closed() {
+        thread::sleep(REFRESH_INTERVAL);
+        handle.update(Tray::refresh);
+    }
+    Ok(())
+}
@@ -1,124 +1,356 @@
-use crate::log::LogEntry;
+mod calendar;
+mod confirm;
+mod dashboard;
+mod detail;
+mod entries;
+mod finder;
+mod form;
+mod help;
+mod status;
+mod timeline;
+
+use calendar::Calendar;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use confirm::{Choice, Confirm};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, KeyEvent, KeyEventKind, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use entries::Entries;
+use finder::{Finder, FinderEvent};
+use form::{Completions, EntryForm, FormEvent};
+use itracker::{
+    config::ConfigData,
+    daemon::{Operation, Timers},
+    hooks::Hooks,
+    i18n::{tr, tr_args},
+    invoice,
+    keys::{Action, Keymap},
+    log::{LogEntry, TaskDetails},
+    month,
+    notify::Notifier,
+    suggest::SuggestedTask,
+    theme::Theme,
+    timefmt::{DurationFormat, TimeFormat},
+    undo::UndoLog,
+    ITrackerError, Storage, Tracker,
+};
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
-    widgets::{Block, Borders, Paragraph},
-    Terminal,
+    layout::{Constraint, Direction, Layout, Rect},
+    widgets::{Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState},
+    Frame, Terminal,
+};
+use status::StatusBar;
+use std::{
+    io::{self, StdoutLock},
+    time::Duration,
 };
-use std::io;
-use tui_textarea::{Input, Key, TextArea};
+
+type Tui<'a> = Terminal<CrosstermBackend<StdoutLock<'a>>>;
 
 /// Renders the logs in a terminal interface.
 ///
 /// # Arguments
-/// * `logs` - An optional vector of `LogEntry` items to display in the terminal.
+/// * `storage` - The log file to display, read a page at a time as the list scrolls.
+/// * `config` - The loaded configuration, used to protect closed months.
+/// * `theme` - The colors applied to all widgets.
+/// * `time_format` - How start times are displayed.
+/// * `keymap` - The key bindings of the viewer actions.
+/// * `confirm_deletes` - Whether deleting an entry asks for confirmation first.
 ///
 /// # Returns
-/// * `io::Result<Vec<String>>` - A result containing a vector of strings entered in the textarea, or an error.
-pub fn render(logs: Option<Vec<LogEntry>>) -> io::Result<Vec<String>> {
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock();
-
-    // Enable raw mode and set up the terminal
-    enable_raw_mode()?;
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+/// * `io::Result<()>` - An error if drawing or reading input fails.
+pub fn view_logs(
+    storage: Storage,
+    config: &ConfigData,
+    theme: &Theme,
+    time_format: &TimeFormat,
+    mut keymap: Keymap,
+    confirm_deletes: bool,
+) -> io::Result<()> {
+    let entries = Entries::open(&storage).map_err(io::Error::other)?;
+    // Timers started here go through a running daemon like those of the command line
+    let timers = Timers::new(
+        Tracker::new(storage.clone()).with_hooks(Hooks::from_config(config.hooks.as_ref())),
+    )
+    .map_err(io::Error::other)?;
+    let mut terminal = setup_terminal()?;
+    let mut viewer = LogViewer::new(timers, entries, *theme);
+    viewer.confirm_deletes = confirm_deletes;
+    viewer.time_format = time_format.clone();
+    viewer.timezone = config.timezone();
+    viewer.durations = config.duration_format();
+    let (tz, durations) = (viewer.timezone, viewer.durations);
+    let mut screen = Screen::Logs;
+    let mut show_help = false;
+    let mut finder: Option<Finder> = None;
+    let mut calendar = Calendar::new(
+        config.week(),
+        durations,
+        format!(
+            "{} / {}: previous / next week",
+            keymap.keys_for(Action::PrevPeriod).join(", "),
+            keymap.keys_for(Action::NextPeriod).join(", ")
+        ),
+    );
+    let status_bar = StatusBar {
+        tz,
+        durations,
+        goal: config.daily_goal(),
+    };
+    // Only break reminders are shown here, desktop notifications are the daemon's
+    let mut reminders = Notifier::from_config(config.notifications.as_ref()).ok();
+    let help_rows = keymap.help();
+    viewer.help_hint = match keymap.keys_for(Action::Help).first() {
+        Some(key) => tr_args("tui-help-hint", &[("key", key.to_string().into())]),
+        None => String::new(),
+    };
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    // Main loop for handling input and rendering
+    let result = loop {
+        if let Some(reminder) = reminders.as_mut().and_then(|reminders| {
+            reminders.check_break(viewer.storage.path(), viewer.entries.recent(), Utc::now())
+        }) {
+            viewer.message = Some(format!("{}: {}", reminder.summary, reminder.body));
+        }
+        // The list reads pages as it scrolls, the other screens need every entry
+        if screen != Screen::Logs {
+            if let Err(e) = viewer.entries.all() {
+                viewer.message = Some(tr_args(
+                    "tui-read-failed",
+                    &[("error", e.to_string().into())],
+                ));
+                screen = Screen::Logs;
+            }
+        }
+        let drawn = terminal.draw(|f| {
+            let [screen_area, status_area] = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(1)])
+                .areas(f.area());
+            match screen {
+                Screen::Logs => viewer.draw(f, screen_area),
+                Screen::Dashboard => {
+                    dashboard::draw(f, screen_area, viewer.entries.read(), tz, durations, theme)
+                }
+                Screen::Timeline => {
+                    timeline::draw(f, screen_area, viewer.entries.read(), tz, durations, theme)
+                }
+                Screen::Week => calendar.draw(f, screen_area, viewer.entries.read(), tz, theme),
+            }
+            status_bar.draw(
+                f,
+                status_area,
+                viewer.entries.recent(),
+                viewer.storage.path(),
+                theme,
+            );
+            if let Some(finder) = &finder {
+                finder.draw(f, viewer.entries.read(), theme);
+            }
+            if show_help {
+                help::draw(f, &help_rows, theme);
+            }
+        });
+        if let Err(e) = drawn {
+            break Err(e);
+        }
 
-    let mut textarea = TextArea::default();
-    textarea.set_block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title("Write your task"),
-    );
+        // Redraw every second so the running timer in the status bar stays live
+        match event::poll(TICK) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => break Err(e),
+        }
 
-    if let Some(logs) = logs {
-        let mut start_index = 0;
-
-        // Main loop for handling input and rendering
-        loop {
-            terminal.draw(|f| {
-                let size = f.area();
-                let visible_count = (size.height / 6).min(logs.len() as u16); // Adjust this number based on your terminal size
-                let layout = Layout::default()
-                    .direction(Direction::Vertical)
-                    .margin(1)
-                    .constraints(
-                        (0..visible_count)
-                            .map(|_| Constraint::Min(1))
-                            .collect::<Vec<_>>(),
-                    );
-
-                let chunks = layout.split(size);
-
-                // Render only the visible log entries
-                for (i, log) in logs
-                    .iter()
-                    .enumerate()
-                    .skip(start_index)
-                    .take(visible_count.into())
+        let action = match event::read() {
+            // Any key closes the help overlay
+            Ok(event::Event::Key(key)) if key.kind == KeyEventKind::Press && show_help => {
+                show_help = false;
+                None
+            }
+            Ok(event::Event::Key(key)) if key.kind == KeyEventKind::Press && finder.is_some() => {
+                match finder
+                    .as_mut()
+                    .map(|f| f.handle_key(key, viewer.entries.read()))
                 {
-                    let log_block = Block::default()
-                        .title(format!("Log Entry {}", log.index))
-                        .borders(Borders::ALL)
-                        .style(Style::default().bg(Color::Black).fg(Color::White));
-
-                    // Format log details with newlines
-                    let log_details = format!(
-                        "Start Time: {}\nMessage:\n{}\nElapsed Time: {}",
-                        log.start_time.trim(),
-                        log.message.trim(),
-                        log.elapsed_time.trim()
-                    );
-
-                    let log_paragraph = Paragraph::new(log_details).block(log_block);
-                    f.render_widget(log_paragraph, chunks[i - start_index as usize]);
-                    // Adjust the index for visible entries
-                }
-            })?;
-
-            // Handle input for exiting the loop
-            if let event::Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Esc => break, // Exit on Esc key
-                    KeyCode::Down => {
-                        // Scroll down
-                        if start_index + 1 < logs.len() {
-                            start_index += 1;
-                        }
-                    }
-                    KeyCode::Up => {
-                        // Scroll up
-                        if start_index > 0 {
-                            start_index -= 1;
-                        }
+                    Some(FinderEvent::Jump(position)) => {
+                        screen = Screen::Logs;
+                        viewer.select(position);
+                        finder = None;
                     }
+                    Some(FinderEvent::Close) => finder = None,
                     _ => {}
                 }
+                None
+            }
+            Ok(event::Event::Key(key))
+                if key.kind == KeyEventKind::Press && viewer.pending_delete.is_some() =>
+            {
+                viewer.answer_delete(key);
+                None
             }
+            Ok(event::Event::Key(key)) if key.kind == KeyEventKind::Press => keymap.resolve(key),
+            Ok(event::Event::Mouse(mouse)) => match mouse.kind {
+                MouseEventKind::ScrollDown => Some(Action::ScrollDown),
+                MouseEventKind::ScrollUp => Some(Action::ScrollUp),
+                _ => None,
+            },
+            Ok(_) => None,
+            Err(e) => break Err(e),
+        };
+
+        match action {
+            Some(Action::Quit) => break Ok(()),
+            Some(Action::Help) => show_help = true,
+            Some(Action::Find) => match viewer.entries.all() {
+                Ok(logs) => finder = Some(Finder::new(logs)),
+                Err(e) => {
+                    viewer.message = Some(tr_args(
+                        "tui-read-failed",
+                        &[("error", e.to_string().into())],
+                    ))
+                }
+            },
+            Some(Action::PrevPeriod) if screen == Screen::Week => calendar.shift(-1),
+            Some(Action::NextPeriod) if screen == Screen::Week => calendar.shift(1),
+            Some(Action::NextScreen) => screen = screen.cycle(1),
+            Some(Action::PrevScreen) => screen = screen.cycle(Screen::ALL.len() - 1),
+            Some(action) if screen == Screen::Logs => viewer.handle_action(action, config),
+            _ => {}
         }
-    } else {
-        // If no logs are provided, enter input mode
-        loop {
-            terminal.draw(|f| {
-                f.render_widget(&textarea, f.area());
-            })?;
-            match crossterm::event::read()?.into() {
-                Input { key: Key::Esc, .. } => break,
-                input => {
-                    textarea.input(input);
+    };
+
+    restore_terminal(&mut terminal)?;
+    result
+}
+
+/// Lets the user describe a new task in a form.
+///
+/// # Arguments
+/// * `theme` - The colors applied to all widgets.
+/// * `known` - Existing entries whose projects and tags are offered as completions.
+/// * `defaults` - Values the fields start with, e.g. the default project.
+/// * `suggested` - Tasks offered to start with a single key while the form is empty.
+///
+/// # Returns
+/// * `io::Result<Option<TaskDetails>>` - The entered task, `None` if the form was cancelled or left empty.
+pub fn input_task(
+    theme: &Theme,
+    known: &[LogEntry],
+    defaults: &TaskDetails,
+    suggested: Vec<SuggestedTask>,
+) -> io::Result<Option<TaskDetails>> {
+    let mut terminal = setup_terminal()?;
+    let completions = Completions::from_logs(known).with_tasks(suggested);
+    let mut form = EntryForm::new(*theme, completions, defaults);
+
+    let result = loop {
+        if let Err(e) = terminal.draw(|f| form.draw(f)) {
+            break Err(e);
+        }
+        match event::read().map(|event| form.handle_event(event)) {
+            Ok(FormEvent::Continue) => {}
+            Ok(FormEvent::Submit(task)) => break Ok(Some(task)),
+            Ok(FormEvent::Cancel) => break Ok(None),
+            Err(e) => break Err(e),
+        }
+    };
+
+    restore_terminal(&mut terminal)?;
+    result
+}
+
+/// Asks a yes/no question in a dialog, e.g. before a destructive CLI command.
+///
+/// # Arguments
+/// * `question` - The question shown in the dialog.
+/// * `theme` - The colors applied to the dialog.
+///
+/// # Returns
+/// * `io::Result<bool>` - Whether the user answered yes.
+pub fn confirm(question: &str, theme: &Theme) -> io::Result<bool> {
+    let dialog = Confirm::new(question);
+    modal(
+        theme,
+        |f| dialog.draw(f, theme),
+        |key| dialog.handle_key(key),
+    )
+}
+
+/// Asks a question with several answers in a full-screen dialog.
+///
+/// # Arguments
+/// * `title` - The title of the dialog.
+/// * `lines` - The question, one line each.
+/// * `options` - The keys and labels of the answers.
+/// * `theme` - The colors applied to the dialog.
+///
+/// # Returns
+/// * `io::Result<Option<usize>>` - The position of the answer in `options`,
+///   `None` if the user pressed Esc.
+pub fn choose(
+    title: &str,
+    lines: Vec<String>,
+    options: Vec<(char, String)>,
+    theme: &Theme,
+) -> io::Result<Option<usize>> {
+    let dialog = Choice::new(title, lines, options);
+    modal(
+        theme,
+        |f| dialog.draw(f, theme),
+        |key| dialog.handle_key(key),
+    )
+}
+
+/// Shows a dialog on its own screen until `handle_key` returns an answer.
+fn modal<T>(
+    theme: &Theme,
+    draw: impl Fn(&mut Frame),
+    handle_key: impl Fn(KeyEvent) -> Option<T>,
+) -> io::Result<T> {
+    let mut terminal = setup_terminal()?;
+
+    let result = loop {
+        let drawn = terminal.draw(|f| {
+            f.render_widget(Block::default().style(theme.base()), f.area());
+            draw(f);
+        });
+        if let Err(e) = drawn {
+            break Err(e);
+        }
+
+        match event::read() {
+            Ok(event::Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                if let Some(answer) = handle_key(key) {
+                    break Ok(answer);
                 }
             }
+            Ok(_) => {}
+            Err(e) => break Err(e),
         }
-    }
+    };
+
+    restore_terminal(&mut terminal)?;
+    result
+}
 
+fn setup_terminal<'a>() -> io::Result<Tui<'a>> {
+    let mut stdout = io::stdout().lock();
+
+    // Enable raw mode and set up the terminal
+    enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+
+    Terminal::new(CrosstermBackend::new(stdout))
+}
+
+fn restore_terminal(terminal: &mut Tui) -> io::Result<()> {
     // Clean up terminal
     disable_raw_mode()?;
     execute!(
@@ -126,9 +358,453 @@ pub fn render(logs: Option<Vec<LogEntry>>) -> io::Result<Vec<String>> {
         LeaveAlternateScreen,
         DisableMouseCapture
     )?;
-    terminal.show_cursor()?;
+    terminal.show_cursor()
+}
+
+/// Screens of the viewer, switched with the next/previous screen actions.
+#[derive(Clone, Copy, PartialEq)]
+enum Screen {
+    Logs,
+    Dashboard,
+    Timeline,
+    Week,
+}
+
+impl Screen {
+    const ALL: [Screen; 4] = [
+        Screen::Logs,
+        Screen::Dashboard,
+        Screen::Timeline,
+        Screen::Week,
+    ];
+
+    fn cycle(self, steps: usize) -> Screen {
+        let current = Screen::ALL.iter().position(|s| *s == self).unwrap_or(0);
+        Screen::ALL[(current + steps) % Screen::ALL.len()]
+    }
+}
+
+/// How often the viewer redraws without input.
+const TICK: Duration = Duration::from_secs(1);
+
+/// Height of one log entry block: six lines of details plus the borders.
+const ENTRY_HEIGHT: u16 = 8;
+
+/// State of the log viewer kept between frames.
+struct LogViewer {
+    storage: Storage,
+    entries: Entries,
+    /// Position of the highlighted entry in `entries`.
+    selected: usize,
+    /// Position of the first visible entry in `entries`.
+    start_index: usize,
+    /// Number of entries that fit on screen, updated on every draw.
+    page_size: usize,
+    sort: SortKey,
+    descending: bool,
+    /// Feedback shown below the header, e.g. after a failed delete.
+    message: Option<String>,
+    /// Key hint for the help overlay shown in the header.
+    help_hint: String,
+    confirm_deletes: bool,
+    /// Confirmation dialog shown while a delete waits for an answer.
+    pending_delete: Option<Confirm>,
+    time_format: TimeFormat,
+    /// How elapsed and paused times are displayed.
+    durations: DurationFormat,
+    /// Timezone start times are displayed in.
+    timezone: Tz,
+    theme: Theme,
+    /// Starts timers from the viewer, on the same log as `storage`.
+    timers: Timers,
+}
 
-    // Print the lines from the textarea and return them
-    let lines: Vec<String> = textarea.lines().iter().cloned().collect();
-    Ok(lines)
+impl LogViewer {
+    fn new(timers: Timers, entries: Entries, theme: Theme) -> Self {
+        LogViewer {
+            storage: timers.tracker().storage().clone(),
+            entries,
+            theme,
+            selected: 0,
+            start_index: 0,
+            page_size: 1,
+            sort: SortKey::Index,
+            descending: false,
+            message: None,
+            help_hint: String::new(),
+            confirm_deletes: true,
+            pending_delete: None,
+            time_format: TimeFormat::default(),
+            durations: DurationFormat::default(),
+            timezone: Tz::UTC,
+            timers,
+        }
+    }
+
+    fn select_by(&mut self, delta: isize) {
+        self.select(self.selected.saturating_add_signed(delta));
+    }
+
+    /// Moves the highlight and scrolls just enough to keep it visible.
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+        if self.selected < self.start_index {
+            self.start_index = self.selected;
+        } else if self.selected >= self.start_index + self.page_size {
+            self.start_index = self.selected + 1 - self.page_size;
+        }
+    }
+
+    fn handle_action(&mut self, action: Action, config: &ConfigData) {
+        self.message = None;
+        let page = self.page_size as isize;
+
+        let sort = match action {
+            Action::ScrollDown => {
+                self.select_by(1);
+                None
+            }
+            Action::ScrollUp => {
+                self.select_by(-1);
+                None
+            }
+            Action::PageDown => {
+                self.select_by(page);
+                None
+            }
+            Action::PageUp => {
+                self.select_by(-page);
+                None
+            }
+            Action::Top => {
+                self.select(0);
+                None
+            }
+            Action::Bottom => {
+                self.select(usize::MAX);
+                None
+            }
+            Action::Delete => {
+                self.request_delete(config);
+                None
+            }
+            Action::StartTimer => {
+                self.start_selected();
+                None
+            }
+            Action::SortIndex => Some(SortKey::Index),
+            Action::SortStart => Some(SortKey::StartTime),
+            Action::SortElapsed => Some(SortKey::ElapsedTime),
+            Action::SortMessage => Some(SortKey::Message),
+            Action::Quit
+            | Action::NextScreen
+            | Action::PrevScreen
+            | Action::Help
+            | Action::Find
+            | Action::PrevPeriod
+            | Action::NextPeriod => None,
+        };
+
+        // Selecting the active column again reverses the order
+        if let Some(sort) = sort {
+            self.descending = if sort == self.sort {
+                !self.descending
+            } else {
+                false
+            };
+            self.sort = sort;
+            if let Err(e) = self.entries.sort(self.sort, self.descending) {
+                self.message = Some(tr_args(
+                    "tui-read-failed",
+                    &[("error", e.to_string().into())],
+                ));
+            }
+            self.select(0);
+        }
+    }
+
+    /// Asks to delete the highlighted entry, or deletes it right away if
+    /// confirmations are disabled.
+    fn request_delete(&mut self, config: &ConfigData) {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return;
+        };
+
+        if month::is_locked(config, &entry.start_time) {
+            self.message = Some(tr_args("tui-locked", &[("index", entry.index.into())]));
+            return;
+        }
+        if let Some(number) = invoice::number(entry) {
+            let invoiced = [("index", entry.index.into()), ("number", number.into())];
+            self.message = Some(tr_args("tui-invoiced", &invoiced));
+            return;
+        }
+
+        if self.confirm_deletes {
+            self.pending_delete = Some(Confirm::new(tr_args(
+                "tui-delete-confirm",
+                &[
+                    ("index", entry.index.into()),
+                    ("title", entry.display_title().into()),
+                ],
+            )));
+        } else {
+            self.delete_selected();
+        }
+    }
+
+    /// Feeds a key into the pending delete confirmation.
+    fn answer_delete(&mut self, key: KeyEvent) {
+        let Some(answer) = self.pending_delete.as_ref().and_then(|c| c.handle_key(key)) else {
+            return;
+        };
+
+        self.pending_delete = None;
+        if answer {
+            self.delete_selected();
+        } else {
+            self.message = Some(tr("tui-delete-cancelled"));
+        }
+    }
+
+    /// Deletes the highlighted entry from the log file and the list.
+    fn delete_selected(&mut self) {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return;
+        };
+
+        let index = entry.index;
+        // Recorded like `--delete-log`, so `undo` brings the entry back
+        let deleted = UndoLog::in_data_dir().and_then(|undo| {
+            undo.record(
+                self.storage.path(),
+                &format!("delete of entry {}", index),
+                || self.storage.delete(index),
+            )
+        });
+        self.message = Some(match deleted.and_then(|()| self.reload()) {
+            Ok(()) => {
+                self.select(self.selected);
+                tr_args("tui-deleted", &[("index", index.into())])
+            }
+            Err(e) => tr_args(
+                "tui-delete-failed",
+                &[("index", index.into()), ("error", e.to_string().into())],
+            ),
+        });
+    }
+
+    /// Reads the entries again after the log changed, in the same order.
+    fn reload(&mut self) -> Result<(), ITrackerError> {
+        self.entries = Entries::open(&self.storage)?;
+        self.entries.sort(self.sort, self.descending)
+    }
+
+    /// Starts a fresh timer for the task of the highlighted entry.
+    fn start_selected(&mut self) {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return;
+        };
+
+        let task = TaskDetails::from(entry);
+        let started = self
+            .timers
+            .run(Operation::Start { task })
+            .and_then(|entry| {
+                self.reload()?;
+                Ok(entry.index)
+            });
+
+        self.message = Some(match started {
+            Ok(newest) => {
+                let position = self.entries.position_of(newest);
+                self.select(position.unwrap_or(0));
+                tr_args("tui-started", &[("index", newest.into())])
+            }
+            Err(e) => tr_args("tui-start-failed", &[("error", e.to_string().into())]),
+        });
+    }
+
+    fn draw(&mut self, f: &mut Frame, area: Rect) {
+        f.render_widget(Block::default().style(self.theme.base()), area);
+
+        let [header_area, message_area, list_area] = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(u16::from(self.message.is_some())),
+                Constraint::Min(0),
+            ])
+            .areas(area);
+
+        let arrow = if self.descending { "↓" } else { "↑" };
+        let header = Paragraph::new(tr_args(
+            "tui-sorted",
+            &[
+                ("column", tr(self.sort.label()).into()),
+                ("arrow", arrow.into()),
+                ("hint", self.help_hint.as_str().into()),
+            ],
+        ))
+        .style(self.theme.accent());
+        f.render_widget(header, header_area);
+
+        if let Some(message) = &self.message {
+            f.render_widget(
+                Paragraph::new(message.as_str()).style(self.theme.highlight()),
+                message_area,
+            );
+        }
+
+        // The list on the left, the full details of the highlighted entry on the right
+        let [list_area, detail_area] = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+            .areas(list_area);
+
+        // Always show at least one entry, even if it gets clipped on tiny terminals
+        let [entries_area, scrollbar_area] = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .areas(list_area);
+        self.page_size = usize::from(entries_area.height / ENTRY_HEIGHT).max(1);
+        self.select(self.selected);
+        if let Err(e) = self
+            .entries
+            .load(self.start_index..self.start_index + self.page_size)
+        {
+            self.message = Some(tr_args(
+                "tui-read-failed",
+                &[("error", e.to_string().into())],
+            ));
+        }
+
+        let visible_count = self.page_size.min(self.entries.len());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints((0..visible_count).map(|_| Constraint::Length(ENTRY_HEIGHT)))
+            .split(entries_area);
+
+        // Render only the visible log entries
+        for (i, (chunk, log)) in chunks
+            .iter()
+            .zip((self.start_index..self.entries.len()).filter_map(|p| self.entries.get(p)))
+            .enumerate()
+        {
+            let border_style = if self.start_index + i == self.selected {
+                self.theme.highlight()
+            } else {
+                self.theme.border()
+            };
+            let log_block = Block::default()
+                .title(tr_args(
+                    "tui-entry",
+                    &[
+                        ("index", log.index.into()),
+                        ("title", log.display_title().into()),
+                    ],
+                ))
+                .borders(Borders::ALL)
+                .border_style(border_style)
+                .title_style(self.theme.accent())
+                .style(self.theme.base());
+
+            // Format log details with newlines
+            let elapsed = if log.is_running() {
+                tr("tui-running")
+            } else {
+                self.durations
+                    .format(log.elapsed_time.trim().parse().unwrap_or(0))
+            };
+            let details = [
+                (
+                    "start",
+                    self.time_format
+                        .format_stored(&log.start_time, self.timezone)
+                        .into(),
+                ),
+                ("message", log.message.trim().into()),
+                ("elapsed", elapsed.into()),
+                (
+                    "paused",
+                    self.durations
+                        .format(log.paused_time.trim().parse().unwrap_or(0))
+                        .into(),
+                ),
+                ("project", log.project.trim().into()),
+                ("tags", log.tags.join(", ").into()),
+            ];
+            let log_details = tr_args("tui-entry-details", &details);
+
+            let log_paragraph = Paragraph::new(log_details).block(log_block);
+            f.render_widget(log_paragraph, *chunk);
+        }
+
+        // Scrollbar indicating the position within all entries
+        let mut scrollbar_state = ScrollbarState::new(self.entries.len())
+            .position(self.selected)
+            .viewport_content_length(self.page_size);
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .thumb_style(self.theme.highlight())
+                .track_style(self.theme.border()),
+            scrollbar_area,
+            &mut scrollbar_state,
+        );
+
+        detail::draw(
+            f,
+            detail_area,
+            self.entries.get(self.selected),
+            &self.time_format,
+            self.durations,
+            self.timezone,
+            &self.theme,
+        );
+
+        if let Some(confirm) = &self.pending_delete {
+            confirm.draw(f, &self.theme);
+        }
+    }
+}
+
+/// Column the log viewer is sorted by.
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Index,
+    StartTime,
+    ElapsedTime,
+    Message,
+}
+
+impl SortKey {
+    /// The id of the column's name in the catalogs.
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Index => "tui-sort-index",
+            SortKey::StartTime => "tui-sort-start",
+            SortKey::ElapsedTime => "tui-sort-elapsed",
+            SortKey::Message => "tui-sort-message",
+        }
+    }
+}
+
+/// Sorts the log entries in place by the given column.
+///
+/// Start and elapsed times are compared by value; entries whose value cannot be
+/// parsed sort before all others.
+fn sort_logs(logs: &mut [LogEntry], key: SortKey, descending: bool) {
+    match key {
+        SortKey::Index => logs.sort_by_key(|log| log.index),
+        SortKey::StartTime => {
+            logs.sort_by_key(|log| DateTime::parse_from_rfc2822(log.start_time.trim()).ok())
+        }
+        SortKey::ElapsedTime => logs.sort_by_key(|log| log.elapsed_time.trim().parse::<u64>().ok()),
+        SortKey::Message => logs.sort_by_key(|log| log.message.trim().to_lowercase()),
+    }
+
+    if descending {
+        logs.reverse();
+    }
 }
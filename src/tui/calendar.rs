@@ -0,0 +1,132 @@
+use chrono::{Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use itracker::{
+    i18n::tr_args,
+    log::LogEntry,
+    report::{self, Week},
+    theme::Theme,
+    timefmt::DurationFormat,
+};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::Line,
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+/// Week view showing seven columns with each day's entries stacked in start order.
+pub struct Calendar {
+    /// The first day of the week and the workdays, which are marked in the grid.
+    week: Week,
+    durations: DurationFormat,
+    /// Weeks relative to the current one, negative for the past.
+    offset: i64,
+    /// Keys to change the week, shown in the title.
+    hint: String,
+}
+
+impl Calendar {
+    pub fn new(week: Week, durations: DurationFormat, hint: String) -> Self {
+        Calendar {
+            week,
+            durations,
+            offset: 0,
+            hint,
+        }
+    }
+
+    /// Moves the view by a number of weeks, negative for the past.
+    pub fn shift(&mut self, weeks: i64) {
+        self.offset += weeks;
+    }
+
+    /// Draws the selected week, counting days in the timezone `tz`.
+    pub fn draw(&self, f: &mut Frame, area: Rect, logs: &[LogEntry], tz: Tz, theme: &Theme) {
+        let week = &self.week;
+        f.render_widget(Block::default().style(theme.base()), area);
+
+        let now = Utc::now();
+        let today = report::today(now, tz);
+        let (first, last) = week.range(today + Duration::weeks(self.offset));
+        let summary = report::summarize(report::entries_between(logs, first, last, tz), now, tz);
+        let workdays: Vec<NaiveDate> = (0..7)
+            .map(|offset| first + Duration::days(offset))
+            .filter(|day| week.is_workday(*day))
+            .collect();
+        let workday_total: u64 = workdays
+            .iter()
+            .filter_map(|day| summary.per_day.get(day))
+            .sum();
+
+        let [title_area, days_area] = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .areas(area);
+
+        let title = format!(
+            "Week {} – {} · {} · {} per workday  {}",
+            first.format("%Y-%m-%d"),
+            last.format("%Y-%m-%d"),
+            self.durations.format(summary.total),
+            self.durations
+                .format(workday_total / workdays.len().max(1) as u64),
+            self.hint
+        );
+        f.render_widget(Paragraph::new(title).style(theme.accent()), title_area);
+
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 7); 7])
+            .split(days_area);
+
+        for (offset, column) in (0..7).zip(columns.iter()) {
+            let day = first + Duration::days(offset);
+            let mut entries = report::entries_between(logs, day, day, tz);
+            entries.sort_by_key(|entry| entry.start());
+
+            let total = summary.per_day.get(&day).copied().unwrap_or(0);
+            let border_style = if day == today {
+                theme.highlight()
+            } else {
+                theme.border()
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title(day.format("%a %m-%d").to_string())
+                .border_style(border_style)
+                .title_style(if week.is_workday(day) {
+                    theme.accent()
+                } else {
+                    theme.border()
+                })
+                .style(theme.base());
+
+            // The day's total, then two lines per entry: start time and duration, then the title
+            let total = Line::styled(
+                tr_args(
+                    "tui-day-total",
+                    &[("duration", self.durations.format(total).into())],
+                ),
+                theme.highlight(),
+            );
+            let lines: Vec<Line> = std::iter::once(total)
+                .chain(entries.iter().flat_map(|entry| {
+                    let start = entry
+                        .start()
+                        .map(|start| start.with_timezone(&tz).format("%H:%M").to_string())
+                        .unwrap_or_default();
+                    let seconds = report::tracked_seconds(entry, now);
+                    [
+                        Line::styled(
+                            format!("{} {}", start, self.durations.format(seconds)),
+                            theme.accent(),
+                        ),
+                        Line::raw(entry.display_title().to_string()),
+                    ]
+                }))
+                .collect();
+            f.render_widget(Paragraph::new(lines).block(block), *column);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use itracker::{i18n::tr, theme::Theme};
+use ratatui::{
+    layout::{Alignment, Rect},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+/// Modal yes/no question drawn over the current screen.
+pub struct Confirm {
+    question: String,
+}
+
+impl Confirm {
+    pub fn new(question: impl Into<String>) -> Self {
+        Confirm {
+            question: question.into(),
+        }
+    }
+
+    /// Answers the question with a key press.
+    ///
+    /// # Returns
+    /// * `Some(true)` for `y`/Enter, `Some(false)` for `n`/Esc, `None` for any other key.
+    pub fn handle_key(&self, key: KeyEvent) -> Option<bool> {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => Some(true),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn draw(&self, f: &mut Frame, theme: &Theme) {
+        let frame = f.area();
+        let width = (self.question.chars().count() as u16 + 4)
+            .clamp(30, 60)
+            .min(frame.width);
+        let height = 6.min(frame.height);
+        let area = Rect {
+            x: frame.x + (frame.width - width) / 2,
+            y: frame.y + (frame.height - height) / 2,
+            width,
+            height,
+        };
+
+        let text = vec![
+            Line::raw(self.question.as_str()),
+            Line::default(),
+            Line::styled("y/Enter: yes · n/Esc: no", theme.accent()),
+        ];
+        let dialog = Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(tr("tui-confirm"))
+                    .border_style(theme.highlight())
+                    .title_style(theme.accent())
+                    .style(theme.base()),
+            );
+
+        f.render_widget(Clear, area);
+        f.render_widget(dialog, area);
+    }
+}
+
+/// Modal question with several answers, each picked with its key.
+pub struct Choice {
+    title: String,
+    lines: Vec<String>,
+    /// Keys and labels of the answers.
+    options: Vec<(char, String)>,
+}
+
+impl Choice {
+    pub fn new(title: impl Into<String>, lines: Vec<String>, options: Vec<(char, String)>) -> Self {
+        Choice {
+            title: title.into(),
+            lines,
+            options,
+        }
+    }
+
+    /// Picks an answer with a key press.
+    ///
+    /// # Returns
+    /// * `Some(Some(position))` for the key of an answer, `Some(None)` for Esc,
+    ///   `None` for any other key.
+    pub fn handle_key(&self, key: KeyEvent) -> Option<Option<usize>> {
+        match key.code {
+            KeyCode::Esc => Some(None),
+            KeyCode::Char(c) => self
+                .options
+                .iter()
+                .position(|(option, _)| option.eq_ignore_ascii_case(&c))
+                .map(Some),
+            _ => None,
+        }
+    }
+
+    pub fn draw(&self, f: &mut Frame, theme: &Theme) {
+        let frame = f.area();
+        let options: Vec<String> = self
+            .options
+            .iter()
+            .map(|(key, label)| format!("{}: {}", key, label))
+            .collect();
+        let options = format!("{} · Esc: cancel", options.join(" · "));
+        let longest = self
+            .lines
+            .iter()
+            .map(|line| line.chars().count())
+            .chain([options.chars().count()])
+            .max()
+            .unwrap_or(0);
+        let width = (longest as u16 + 4).clamp(30, 100).min(frame.width);
+        let height = (self.lines.len() as u16 + 5).min(frame.height);
+        let area = Rect {
+            x: frame.x + (frame.width - width) / 2,
+            y: frame.y + (frame.height - height) / 2,
+            width,
+            height,
+        };
+
+        let mut text: Vec<Line> = self
+            .lines
+            .iter()
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        text.push(Line::default());
+        text.push(Line::styled(options, theme.accent()));
+        let dialog = Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(self.title.as_str())
+                    .border_style(theme.highlight())
+                    .title_style(theme.accent())
+                    .style(theme.base()),
+            );
+
+        f.render_widget(Clear, area);
+        f.render_widget(dialog, area);
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::{Duration, Utc};
+use chrono_tz::Tz;
+use itracker::{
+    i18n::{tr, tr_args},
+    log::LogEntry,
+    report::{self, hours, Summary},
+    theme::Theme,
+    timefmt::DurationFormat,
+};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    symbols::Marker,
+    text::{Line, Span},
+    widgets::{
+        canvas::{Canvas, Points},
+        Bar, BarChart, BarGroup, Block, Borders, Paragraph,
+    },
+    Frame,
+};
+use std::f64::consts::TAU;
+
+/// Width of one bar in the hours-per-day chart.
+const BAR_WIDTH: u16 = 5;
+
+/// Colors cycled through for the projects in the donut chart.
+const PROJECT_COLORS: [Color; 8] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+    Color::LightCyan,
+    Color::LightMagenta,
+];
+
+/// Draws the statistics dashboard: today's total, hours per day and time per project.
+pub fn draw(
+    f: &mut Frame,
+    area: Rect,
+    logs: &[LogEntry],
+    tz: Tz,
+    durations: DurationFormat,
+    theme: &Theme,
+) {
+    f.render_widget(Block::default().style(theme.base()), area);
+
+    let now = Utc::now();
+    let today = report::today(now, tz);
+
+    let [today_area, charts_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .areas(area);
+    let [days_area, projects_area] = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .areas(charts_area);
+
+    // Today's total
+    let today_summary = report::summarize(report::entries_between(logs, today, today, tz), now, tz);
+    let today_text = format!(
+        "{} in {} entries",
+        durations.format(today_summary.total),
+        today_summary.entries
+    );
+    f.render_widget(
+        Paragraph::new(today_text).block(block(&tr("tui-today"), theme)),
+        today_area,
+    );
+
+    // Hours per day for as many recent days as fit the chart
+    let days = i64::from((days_area.width.saturating_sub(2) / (BAR_WIDTH + 1)).clamp(1, 31));
+    let from = today - Duration::days(days - 1);
+    let summary = report::summarize(report::entries_between(logs, from, today, tz), now, tz);
+
+    let bars: Vec<Bar> = (0..days)
+        .map(|offset| {
+            let day = from + Duration::days(offset);
+            let seconds = summary.per_day.get(&day).copied().unwrap_or(0);
+            Bar::default()
+                .value(seconds / 60)
+                .text_value(format!("{:.1}", hours(seconds)))
+                .label(Line::from(day.format("%m-%d").to_string()))
+        })
+        .collect();
+
+    let chart = BarChart::default()
+        .block(block(
+            &tr_args("tui-hours-per-day", &[("days", days.into())]),
+            theme,
+        ))
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(BAR_WIDTH)
+        .bar_gap(1)
+        .bar_style(theme.accent())
+        .value_style(theme.base().fg(theme.background).bg(theme.accent))
+        .label_style(theme.base());
+    f.render_widget(chart, days_area);
+
+    draw_projects(f, projects_area, &summary, durations, theme);
+}
+
+/// Draws a donut chart of the time per project with a legend below it.
+fn draw_projects(
+    f: &mut Frame,
+    area: Rect,
+    summary: &Summary,
+    durations: DurationFormat,
+    theme: &Theme,
+) {
+    let outer = block(&tr("tui-per-project"), theme);
+    let inner = outer.inner(area);
+    f.render_widget(outer, area);
+
+    if summary.total == 0 {
+        f.render_widget(Paragraph::new(tr("tui-no-time")), inner);
+        return;
+    }
+
+    let legend_height = (summary.per_project.len() as u16).min(inner.height / 2);
+    let [donut_area, legend_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(legend_height)])
+        .areas(inner);
+
+    // Terminal cells are about twice as high as wide, stretch x so the ring stays round
+    let aspect = f64::from(donut_area.width) / (2.0 * f64::from(donut_area.height.max(1)));
+    let slices: Vec<(Vec<(f64, f64)>, Color)> = {
+        let mut start = 0.0;
+        summary
+            .per_project
+            .values()
+            .zip(PROJECT_COLORS.iter().cycle())
+            .map(|(seconds, color)| {
+                let sweep = TAU * *seconds as f64 / summary.total as f64;
+                let points = ring_points(start, start + sweep, aspect);
+                start += sweep;
+                (points, *color)
+            })
+            .collect()
+    };
+
+    let canvas = Canvas::default()
+        .marker(Marker::Braille)
+        .x_bounds([-1.1 * aspect, 1.1 * aspect])
+        .y_bounds([-1.1, 1.1])
+        .paint(|ctx| {
+            for (points, color) in &slices {
+                ctx.draw(&Points {
+                    coords: points,
+                    color: *color,
+                });
+            }
+        });
+    f.render_widget(canvas, donut_area);
+
+    let legend: Vec<Line> = summary
+        .per_project
+        .iter()
+        .zip(PROJECT_COLORS.iter().cycle())
+        .map(|((project, seconds), color)| {
+            Line::from(vec![
+                Span::styled("■ ", Style::default().fg(*color)),
+                Span::raw(format!(
+                    "{} {} ({:.0}%)",
+                    project,
+                    durations.format(*seconds),
+                    100.0 * *seconds as f64 / summary.total as f64
+                )),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(legend), legend_area);
+}
+
+/// Samples points filling the ring segment between two angles (in radians),
+/// stretching x by `aspect`.
+fn ring_points(from: f64, to: f64, aspect: f64) -> Vec<(f64, f64)> {
+    let mut points = Vec::new();
+    let mut radius = 0.55;
+    while radius <= 1.0 {
+        let step = 0.02 / radius;
+        let mut angle = from;
+        while angle < to {
+            points.push((aspect * radius * angle.cos(), radius * angle.sin()));
+            angle += step;
+        }
+        radius += 0.03;
+    }
+    points
+}
+
+fn block<'a>(title: &str, theme: &Theme) -> Block<'a> {
+    Block::default()
+        .borders(Borders::ALL)
+        .title(title.to_string())
+        .border_style(theme.border())
+        .title_style(theme.accent())
+        .style(theme.base())
+}
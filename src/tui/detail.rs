@@ -0,0 +1,113 @@
+use chrono::{Duration, Utc};
+use chrono_tz::Tz;
+use itracker::{
+    i18n::{tr, tr_args},
+    log::LogEntry,
+    report,
+    theme::Theme,
+    timefmt::{DurationFormat, TimeFormat},
+};
+use ratatui::{
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+
+/// Draws every field of the highlighted entry, wrapping the full description.
+///
+/// # Arguments
+/// * `area` - The pane to draw into.
+/// * `entry` - The highlighted entry, `None` if the log is empty.
+/// * `time_format` - How the start and end times are displayed.
+/// * `durations` - How the elapsed, paused and tracked times are displayed.
+/// * `tz` - The timezone the times are shown in.
+/// * `theme` - The colors applied to the pane.
+pub fn draw(
+    f: &mut Frame,
+    area: Rect,
+    entry: Option<&LogEntry>,
+    time_format: &TimeFormat,
+    durations: DurationFormat,
+    tz: Tz,
+    theme: &Theme,
+) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(tr("tui-details"))
+        .border_style(theme.border())
+        .title_style(theme.accent())
+        .style(theme.base());
+
+    let Some(entry) = entry else {
+        f.render_widget(Paragraph::new(tr("tui-no-entries")).block(block), area);
+        return;
+    };
+
+    let field = |id: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{}: ", tr(id)), theme.accent()),
+            Span::raw(value),
+        ])
+    };
+    let or_dash = |value: &str| {
+        if value.trim().is_empty() {
+            "-".to_string()
+        } else {
+            value.trim().to_string()
+        }
+    };
+
+    let start = entry.start().map(|start| start.with_timezone(&tz));
+    let elapsed = entry.elapsed_time.trim().parse::<u64>().unwrap_or(0);
+    let paused = entry.paused_time.trim().parse::<u64>().unwrap_or(0);
+    let started = match start {
+        Some(start) => time_format.format(&start),
+        None => tr_args(
+            "detail-unparsable",
+            &[("start", entry.start_time.trim().into())],
+        ),
+    };
+    let ended = match start {
+        _ if entry.paused_since().is_some() => tr("detail-paused-now"),
+        _ if elapsed == 0 => tr("tui-running"),
+        Some(start) => time_format.format(&(start + Duration::seconds(elapsed as i64))),
+        None => "-".to_string(),
+    };
+    let tracked = report::tracked_seconds(entry, Utc::now());
+
+    let mut lines = vec![
+        field("detail-index", entry.index.to_string()),
+        field("detail-title", or_dash(entry.display_title())),
+        field("detail-project", or_dash(&entry.project)),
+        field("detail-tags", or_dash(&entry.tags.join(", "))),
+        field(
+            "detail-estimate",
+            entry
+                .estimate
+                .map(|minutes| tr_args("detail-minutes", &[("minutes", minutes.into())]))
+                .unwrap_or_else(|| "-".to_string()),
+        ),
+        Line::default(),
+        field("detail-started", started),
+        field("detail-ended", ended),
+        field("detail-elapsed", durations.format(elapsed)),
+        field("detail-paused", durations.format(paused)),
+        field("detail-tracked", durations.format(tracked)),
+        Line::default(),
+        Line::styled(tr("detail-description"), theme.accent()),
+    ];
+    lines.extend(
+        entry
+            .message
+            .lines()
+            .map(|line| Line::raw(line.to_string())),
+    );
+
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
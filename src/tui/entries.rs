@@ -0,0 +1,162 @@
+use super::{sort_logs, SortKey};
+use chrono::{Duration, Utc};
+use itracker::{index::LogIndex, log::LogEntry, ITrackerError, Storage};
+use std::{collections::HashMap, ops::Range};
+
+/// Entries read at once when the list scrolls to ones not read yet.
+const PAGE: usize = 200;
+
+/// The entries shown by the viewer, read a page at a time through the index
+/// of the log, so that it opens at once on logs of any size. Screens that
+/// need every entry, e.g. the dashboard, read the whole log when opened.
+pub struct Entries {
+    path: String,
+    /// Indices of the entries in display order.
+    order: Vec<usize>,
+    /// Entries read so far, by index.
+    loaded: HashMap<usize, LogEntry>,
+    /// The index pages are read through, `None` for logs without one.
+    index: Option<LogIndex>,
+    /// Every entry in display order, once read.
+    all: Option<Vec<LogEntry>>,
+    /// Entries of the last two days and the running one, for the status bar.
+    recent: Vec<LogEntry>,
+}
+
+impl Entries {
+    /// Opens the entries of `storage`, reading only the recent ones; logs
+    /// without an index, e.g. encrypted ones, are read whole.
+    pub fn open(storage: &Storage) -> Result<Self, ITrackerError> {
+        let path = storage.path().to_string();
+        let Some(index) = LogIndex::load(&path)? else {
+            let logs = storage.entries()?;
+            return Ok(Entries {
+                path,
+                order: logs.iter().map(|log| log.index).collect(),
+                loaded: HashMap::new(),
+                index: None,
+                recent: Vec::new(),
+                all: Some(logs),
+            });
+        };
+        let mut recent = index.started_since(Utc::now() - Duration::days(2))?;
+        if let Some(running) = index.running()? {
+            if !recent.iter().any(|log| log.index == running.index) {
+                recent.push(running);
+            }
+        }
+        Ok(Entries {
+            path,
+            order: index.indices()?,
+            loaded: HashMap::new(),
+            index: Some(index),
+            all: None,
+            recent,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// The entry at `position` in display order, if it was read already.
+    pub fn get(&self, position: usize) -> Option<&LogEntry> {
+        match &self.all {
+            Some(all) => all.get(position),
+            None => self.loaded.get(self.order.get(position)?),
+        }
+    }
+
+    /// The position of the entry with `index` in display order.
+    pub fn position_of(&self, index: usize) -> Option<usize> {
+        self.order.iter().position(|&i| i == index)
+    }
+
+    /// Reads the pages holding the entries at `positions` that were not read yet.
+    pub fn load(&mut self, positions: Range<usize>) -> Result<(), ITrackerError> {
+        let Some(index) = &self.index else {
+            return Ok(());
+        };
+        if self.all.is_some() {
+            return Ok(());
+        }
+        let first = positions.start / PAGE * PAGE;
+        let last = positions.end.div_ceil(PAGE) * PAGE;
+        let missing: Vec<usize> = self.order
+            [first.min(self.order.len())..last.min(self.order.len())]
+            .iter()
+            .copied()
+            .filter(|i| !self.loaded.contains_key(i))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        for log in index.entries(&missing)? {
+            self.loaded.insert(log.index, log);
+        }
+        Ok(())
+    }
+
+    /// Every entry in display order, reading the whole log the first time.
+    pub fn all(&mut self) -> Result<&[LogEntry], ITrackerError> {
+        if self.all.is_none() {
+            let mut logs: HashMap<usize, LogEntry> = Storage::new(self.path.as_str())
+                .entries()?
+                .into_iter()
+                .map(|log| (log.index, log))
+                .collect();
+            let all = self
+                .order
+                .iter()
+                .filter_map(|index| logs.remove(index))
+                .collect();
+            self.loaded.clear();
+            self.all = Some(all);
+        }
+        Ok(self.all.as_deref().unwrap_or_default())
+    }
+
+    /// Every entry in display order if read already, none otherwise.
+    pub fn read(&self) -> &[LogEntry] {
+        self.all.as_deref().unwrap_or_default()
+    }
+
+    /// The entries started in the last two days and the running one, enough
+    /// for today's total and the running timer.
+    pub fn recent(&self) -> &[LogEntry] {
+        match &self.all {
+            Some(all) => all,
+            None => &self.recent,
+        }
+    }
+
+    /// Sorts the entries by `key`. Index and start are known from the index
+    /// of the log; the other keys read the whole log.
+    pub fn sort(&mut self, key: SortKey, descending: bool) -> Result<(), ITrackerError> {
+        match (&self.index, key) {
+            (Some(index), SortKey::Index | SortKey::StartTime) if self.all.is_none() => {
+                match key {
+                    SortKey::Index => self.order.sort(),
+                    _ => {
+                        let mut starts = HashMap::with_capacity(self.order.len());
+                        for &i in &self.order {
+                            starts.insert(i, index.start(i)?);
+                        }
+                        self.order.sort_by_key(|i| starts[i]);
+                    }
+                }
+                if descending {
+                    self.order.reverse();
+                }
+            }
+            _ => {
+                self.all()?;
+                if let Some(all) = &mut self.all {
+                    sort_logs(all, key, descending);
+                    self.order = all.iter().map(|log| log.index).collect();
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use itracker::{i18n::tr_args, log::LogEntry, theme::Theme};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+/// Outcome of feeding a key into the finder.
+pub enum FinderEvent {
+    Continue,
+    /// Jump to the entry at this position of the viewer's list.
+    Jump(usize),
+    Close,
+}
+
+/// Ctrl-P style fuzzy search over the titles and descriptions of all entries.
+pub struct Finder {
+    query: String,
+    matcher: SkimMatcherV2,
+    /// Positions of the matching entries, best match first.
+    matches: Vec<usize>,
+    /// Highlighted row of `matches`.
+    selected: usize,
+}
+
+impl Finder {
+    pub fn new(logs: &[LogEntry]) -> Self {
+        let mut finder = Finder {
+            query: String::new(),
+            matcher: SkimMatcherV2::default(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        finder.update(logs);
+        finder
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, logs: &[LogEntry]) -> FinderEvent {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return FinderEvent::Close,
+            KeyCode::Char('c') if ctrl => return FinderEvent::Close,
+            KeyCode::Enter => {
+                return match self.matches.get(self.selected) {
+                    Some(position) => FinderEvent::Jump(*position),
+                    None => FinderEvent::Close,
+                }
+            }
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('n') if ctrl => self.move_selection(1),
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Char('p') if ctrl => self.move_selection(-1),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update(logs);
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.update(logs);
+            }
+            _ => {}
+        }
+        FinderEvent::Continue
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Re-ranks all entries against the current query.
+    fn update(&mut self, logs: &[LogEntry]) {
+        let mut scored: Vec<(i64, usize)> = logs
+            .iter()
+            .enumerate()
+            .filter_map(|(position, log)| {
+                self.matcher
+                    .fuzzy_match(&searchable(log), &self.query)
+                    .map(|score| (score, position))
+            })
+            .collect();
+        // Best score first, ties keep the order of the list
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        self.matches = scored.into_iter().map(|(_, position)| position).collect();
+        self.selected = 0;
+    }
+
+    pub fn draw(&self, f: &mut Frame, logs: &[LogEntry], theme: &Theme) {
+        let frame = f.area();
+        let width = (frame.width * 3 / 4).max(20).min(frame.width);
+        let height = (frame.height * 3 / 4).max(5).min(frame.height);
+        let area = Rect {
+            x: frame.x + (frame.width - width) / 2,
+            y: frame.y + (frame.height - height) / 2,
+            width,
+            height,
+        };
+
+        let outer = Block::default()
+            .borders(Borders::ALL)
+            .title(tr_args(
+                "tui-find",
+                &[
+                    ("matches", self.matches.len().into()),
+                    ("total", logs.len().into()),
+                ],
+            ))
+            .border_style(theme.highlight())
+            .title_style(theme.accent())
+            .style(theme.base());
+        let inner = outer.inner(area);
+        f.render_widget(Clear, area);
+        f.render_widget(outer, area);
+
+        let [query_area, list_area] = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(2), Constraint::Min(0)])
+            .areas(inner);
+
+        let prompt = Line::from(vec![
+            Span::styled("> ", theme.accent()),
+            Span::raw(self.query.as_str()),
+            Span::styled(" ", theme.base().add_modifier(Modifier::REVERSED)),
+        ]);
+        f.render_widget(Paragraph::new(prompt), query_area);
+
+        // Only the visible rows are highlighted, matching is redone for them
+        let visible = usize::from(list_area.height);
+        let offset = self.selected.saturating_sub(visible.saturating_sub(1));
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .skip(offset)
+            .take(visible)
+            .map(|position| ListItem::new(self.highlighted(&logs[*position], theme)))
+            .collect();
+        let list = List::new(items)
+            .highlight_style(theme.base().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("▶ ");
+        let mut state = ListState::default().with_selected(Some(self.selected - offset));
+        f.render_stateful_widget(list, list_area, &mut state);
+    }
+
+    /// One result row with the matched characters emphasized.
+    fn highlighted<'a>(&self, log: &LogEntry, theme: &Theme) -> Line<'a> {
+        let text = searchable(log);
+        let indices = self
+            .matcher
+            .fuzzy_indices(&text, &self.query)
+            .map(|(_, indices)| indices)
+            .unwrap_or_default();
+
+        let mut spans = vec![Span::styled(format!("{:>5} ", log.index), theme.accent())];
+        spans.extend(text.chars().enumerate().map(|(i, c)| {
+            if indices.contains(&i) {
+                Span::styled(
+                    c.to_string(),
+                    theme.highlight().add_modifier(Modifier::BOLD),
+                )
+            } else {
+                Span::raw(c.to_string())
+            }
+        }));
+        Line::from(spans)
+    }
+}
+
+/// Text an entry is matched against: its title followed by the description on one line.
+fn searchable(log: &LogEntry) -> String {
+    let description = log.message.split_whitespace().collect::<Vec<_>>().join(" ");
+    if log.title.trim().is_empty() {
+        description
+    } else {
+        format!("{} · {}", log.title.trim(), description)
+    }
+}
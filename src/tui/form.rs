@@ -0,0 +1,475 @@
+use super::help;
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use itracker::{
+    i18n::{tr, tr_args},
+    log::{parse_tags, LogEntry, TaskDetails},
+    suggest::SuggestedTask,
+    theme::Theme,
+};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Modifier,
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use std::collections::{BTreeMap, HashMap};
+use tui_textarea::{CursorMove, Input, Key, TextArea};
+
+/// Message ids of the field labels.
+const LABELS: [&str; 5] = [
+    "form-title",
+    "form-description",
+    "form-project",
+    "form-tags",
+    "form-estimate",
+];
+
+const TITLE: usize = 0;
+const DESCRIPTION: usize = 1;
+const PROJECT: usize = 2;
+const TAGS: usize = 3;
+const ESTIMATE: usize = 4;
+
+/// Keys of the form listed in its help overlay.
+const HELP: [(&str, &str); 8] = [
+    ("1-9", "Start a suggested task, while the form is empty"),
+    ("Tab", "Next field, or accept the highlighted completion"),
+    ("Shift-Tab", "Previous field"),
+    ("Up, Down", "Pick a completion"),
+    (
+        "Enter",
+        "Next field, new line in the description, save on the estimate",
+    ),
+    ("Esc", "Save and start the timer"),
+    ("Ctrl-C", "Cancel"),
+    ("F1", "Show this help"),
+];
+
+/// Maximum number of completions shown in the dropdown.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Projects and tags already used in the log file, most frequent first, and
+/// the tasks likely to be started now.
+#[derive(Default)]
+pub struct Completions {
+    projects: Vec<String>,
+    tags: Vec<String>,
+    /// Offered while the form is empty, picked with the keys 1 to 9.
+    tasks: Vec<SuggestedTask>,
+}
+
+impl Completions {
+    pub fn from_logs(logs: &[LogEntry]) -> Self {
+        let projects = by_frequency(logs.iter().map(|log| log.project.trim()));
+        let tags = by_frequency(
+            logs.iter()
+                .flat_map(|log| log.tags.iter().map(String::as_str)),
+        );
+        Completions {
+            projects,
+            tags,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Offers `tasks` to start with a single key while the form is empty.
+    pub fn with_tasks(mut self, tasks: Vec<SuggestedTask>) -> Self {
+        self.tasks = tasks;
+        self
+    }
+}
+
+fn by_frequency<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for value in values.filter(|value| !value.is_empty()) {
+        *counts.entry(value).or_default() += 1;
+    }
+
+    let mut values: Vec<(&str, usize)> = counts.into_iter().collect();
+    values.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    values
+        .into_iter()
+        .map(|(value, _)| value.to_string())
+        .collect()
+}
+
+/// Outcome of feeding an event into the form.
+pub enum FormEvent {
+    Continue,
+    Submit(TaskDetails),
+    Cancel,
+}
+
+/// Form with one input per task column; Tab and Shift-Tab move between fields.
+pub struct EntryForm<'a> {
+    fields: Vec<TextArea<'a>>,
+    focus: usize,
+    /// Validation error shown below the fields.
+    error: Option<String>,
+    completions: Completions,
+    /// Highlighted row of the completion dropdown.
+    suggestion: usize,
+    show_help: bool,
+    theme: Theme,
+    /// Metadata of the defaults, kept as is since the form cannot edit it.
+    metadata: BTreeMap<String, String>,
+}
+
+impl EntryForm<'_> {
+    pub fn new(theme: Theme, completions: Completions, defaults: &TaskDetails) -> Self {
+        let initial = [
+            defaults.title.clone(),
+            defaults.description.clone(),
+            defaults.project.clone(),
+            defaults.tags.join(", "),
+            defaults.estimate.map(|e| e.to_string()).unwrap_or_default(),
+        ];
+        let fields = initial
+            .into_iter()
+            .map(|value| {
+                let mut field = TextArea::new(value.lines().map(str::to_string).collect());
+                field.set_style(theme.base());
+                field.move_cursor(CursorMove::Bottom);
+                field.move_cursor(CursorMove::End);
+                field
+            })
+            .collect();
+
+        let mut form = EntryForm {
+            fields,
+            focus: TITLE,
+            error: None,
+            completions,
+            suggestion: 0,
+            show_help: false,
+            theme,
+            metadata: defaults.metadata.clone(),
+        };
+        form.update_blocks();
+        form
+    }
+
+    pub fn handle_event(&mut self, event: Event) -> FormEvent {
+        if let Event::Key(key) = event {
+            if key.kind != KeyEventKind::Press {
+                return FormEvent::Continue;
+            }
+            // Any key closes the help overlay
+            if self.show_help || key.code == KeyCode::F(1) {
+                self.show_help = !self.show_help;
+                return FormEvent::Continue;
+            }
+            match key.code {
+                KeyCode::BackTab => {
+                    self.focus_field((self.focus + LABELS.len() - 1) % LABELS.len());
+                    return FormEvent::Continue;
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return FormEvent::Cancel;
+                }
+                _ => {}
+            }
+        }
+
+        // A digit picks a suggested task until anything is typed; a title
+        // starting with a digit can be typed after a space
+        if let Event::Key(key) = event {
+            if let KeyCode::Char(digit @ '1'..='9') = key.code {
+                let position = digit as usize - '1' as usize;
+                if key.modifiers.is_empty() && self.is_blank() {
+                    if let Some(suggested) = self.completions.tasks.get(position) {
+                        return FormEvent::Submit(TaskDetails {
+                            metadata: self.metadata.clone(),
+                            ..suggested.task.clone()
+                        });
+                    }
+                }
+            }
+        }
+
+        let suggestions = self.suggestions();
+        match event.into() {
+            Input { key: Key::Esc, .. } => return self.submit(),
+            // While the dropdown is open, Tab accepts and Up/Down pick a completion
+            Input { key: Key::Tab, .. } if !suggestions.is_empty() => {
+                let accepted = suggestions[self.suggestion.min(suggestions.len() - 1)].clone();
+                self.accept(&accepted);
+            }
+            Input { key: Key::Down, .. } if !suggestions.is_empty() => {
+                self.suggestion = (self.suggestion + 1) % suggestions.len();
+            }
+            Input { key: Key::Up, .. } if !suggestions.is_empty() => {
+                self.suggestion = (self.suggestion + suggestions.len() - 1) % suggestions.len();
+            }
+            Input { key: Key::Tab, .. } => self.focus_field((self.focus + 1) % LABELS.len()),
+            // Only the description spans multiple lines, Enter moves on elsewhere
+            Input {
+                key: Key::Enter, ..
+            } if self.focus != DESCRIPTION => {
+                if self.focus == ESTIMATE {
+                    return self.submit();
+                }
+                self.focus_field(self.focus + 1);
+            }
+            input => {
+                if self.fields[self.focus].input(input) {
+                    self.suggestion = 0;
+                }
+            }
+        }
+        FormEvent::Continue
+    }
+
+    /// Completions matching the word being typed in the project or tags field.
+    fn suggestions(&self) -> Vec<String> {
+        let (candidates, typed, exclude) = match self.focus {
+            PROJECT => (&self.completions.projects, self.value(PROJECT), vec![]),
+            TAGS => {
+                let value = self.value(TAGS);
+                let (done, typed) = split_last_tag(&value);
+                (&self.completions.tags, typed.to_string(), parse_tags(done))
+            }
+            _ => return vec![],
+        };
+
+        if typed.is_empty() {
+            return vec![];
+        }
+        let typed_lower = typed.to_lowercase();
+
+        candidates
+            .iter()
+            .filter(|candidate| {
+                candidate.to_lowercase().starts_with(&typed_lower)
+                    && **candidate != typed
+                    && !exclude.contains(candidate)
+            })
+            .take(MAX_SUGGESTIONS)
+            .cloned()
+            .collect()
+    }
+
+    /// Replaces the word being typed with the accepted completion.
+    fn accept(&mut self, completion: &str) {
+        let text = match self.focus {
+            TAGS => {
+                let value = self.value(TAGS);
+                let (done, _) = split_last_tag(&value);
+                if done.is_empty() {
+                    completion.to_string()
+                } else {
+                    format!("{}, {}", done, completion)
+                }
+            }
+            _ => completion.to_string(),
+        };
+
+        let mut field = TextArea::new(vec![text]);
+        field.set_style(self.theme.base());
+        field.move_cursor(CursorMove::End);
+        self.fields[self.focus] = field;
+        self.suggestion = 0;
+        self.update_blocks();
+    }
+
+    fn submit(&mut self) -> FormEvent {
+        let estimate = self.value(ESTIMATE);
+        let estimate = if estimate.is_empty() {
+            None
+        } else {
+            match estimate.parse::<u32>() {
+                Ok(minutes) => Some(minutes),
+                Err(_) => {
+                    self.error = Some(tr_args(
+                        "form-estimate-invalid",
+                        &[("estimate", estimate.clone().into())],
+                    ));
+                    self.focus_field(ESTIMATE);
+                    return FormEvent::Continue;
+                }
+            }
+        };
+
+        let task = TaskDetails {
+            title: self.value(TITLE),
+            description: self.fields[DESCRIPTION]
+                .lines()
+                .join("\n")
+                .trim()
+                .to_string(),
+            project: self.value(PROJECT),
+            tags: parse_tags(&self.value(TAGS)),
+            estimate,
+            metadata: self.metadata.clone(),
+        };
+
+        if task.is_empty() {
+            FormEvent::Cancel
+        } else {
+            FormEvent::Submit(task)
+        }
+    }
+
+    /// Whether nothing was entered in any field, not even spaces.
+    fn is_blank(&self) -> bool {
+        self.fields
+            .iter()
+            .all(|field| field.lines().iter().all(String::is_empty))
+    }
+
+    fn value(&self, field: usize) -> String {
+        self.fields[field].lines().concat().trim().to_string()
+    }
+
+    fn focus_field(&mut self, field: usize) {
+        self.focus = field;
+        self.update_blocks();
+    }
+
+    /// Highlights the border of the focused field and shows the cursor only there.
+    fn update_blocks(&mut self) {
+        for (i, field) in self.fields.iter_mut().enumerate() {
+            let focused = i == self.focus;
+            let border_style = if focused {
+                self.theme.highlight()
+            } else {
+                self.theme.border()
+            };
+            field.set_block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border_style)
+                    .title_style(self.theme.accent())
+                    .style(self.theme.base())
+                    .title(tr(LABELS[i])),
+            );
+            field.set_cursor_style(if focused {
+                self.theme.base().add_modifier(Modifier::REVERSED)
+            } else {
+                self.theme.base()
+            });
+        }
+    }
+
+    pub fn draw(&self, f: &mut Frame) {
+        f.render_widget(Block::default().style(self.theme.base()), f.area());
+
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(4),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ])
+            .split(f.area());
+
+        for (field, area) in self.fields.iter().zip(areas.iter()) {
+            f.render_widget(field, *area);
+        }
+
+        self.draw_suggestions(f, areas[self.focus]);
+        if self.is_blank() {
+            self.draw_tasks(f, areas[DESCRIPTION]);
+        }
+
+        let help = match &self.error {
+            Some(error) => Paragraph::new(error.as_str()).style(self.theme.highlight()),
+            None => Paragraph::new(tr("form-help")).style(self.theme.accent()),
+        };
+        f.render_widget(help, areas[LABELS.len()]);
+
+        if self.show_help {
+            help::draw(f, &HELP, &self.theme);
+        }
+    }
+
+    /// Draws the completion dropdown just below the focused field.
+    fn draw_suggestions(&self, f: &mut Frame, field_area: Rect) {
+        let suggestions = self.suggestions();
+        if suggestions.is_empty() {
+            return;
+        }
+
+        let width = suggestions
+            .iter()
+            .map(|s| s.chars().count())
+            .max()
+            .unwrap_or(0) as u16
+            + 4;
+        let frame = f.area();
+        let y = (field_area.y + field_area.height - 1).min(frame.height.saturating_sub(1));
+        let area = Rect {
+            x: field_area.x + 1,
+            y,
+            width: width.min(frame.width.saturating_sub(field_area.x + 1)),
+            height: (suggestions.len() as u16 + 2).min(frame.height - y),
+        };
+
+        let items: Vec<ListItem> = suggestions
+            .iter()
+            .map(|s| ListItem::new(s.as_str()))
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(self.theme.highlight())
+                    .style(self.theme.base()),
+            )
+            .highlight_style(self.theme.base().add_modifier(Modifier::REVERSED));
+        let mut state =
+            ListState::default().with_selected(Some(self.suggestion.min(suggestions.len() - 1)));
+
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Lists the suggested tasks with their keys inside `area`.
+    fn draw_tasks(&self, f: &mut Frame, area: Rect) {
+        if self.completions.tasks.is_empty() {
+            return;
+        }
+        let items: Vec<ListItem> = self
+            .completions
+            .tasks
+            .iter()
+            .zip('1'..='9')
+            .map(|(suggested, key)| {
+                let task = &suggested.task;
+                let mut line = format!("{}  {}", key, task.title);
+                if !task.project.is_empty() {
+                    line.push_str(&format!(" ({})", task.project));
+                }
+                line.push_str(&format!(", {}×", suggested.count));
+                ListItem::new(line)
+            })
+            .collect();
+        let area = Rect {
+            x: area.x + 1,
+            y: area.y + 1,
+            width: area.width.saturating_sub(2),
+            height: (items.len() as u16 + 2).min(area.height.saturating_sub(1)),
+        };
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(self.theme.border())
+                .title_style(self.theme.accent())
+                .title(tr("tui-suggested"))
+                .style(self.theme.base()),
+        );
+        f.render_widget(Clear, area);
+        f.render_widget(list, area);
+    }
+}
+
+/// Splits a tag list into the finished tags and the tag being typed.
+fn split_last_tag(value: &str) -> (&str, &str) {
+    match value.rfind(',') {
+        Some(pos) => (&value[..pos], value[pos + 1..].trim()),
+        None => ("", value.trim()),
+    }
+}
@@ -0,0 +1,59 @@
+use itracker::{i18n::tr, theme::Theme};
+use ratatui::{
+    layout::{Constraint, Rect},
+    widgets::{Block, Borders, Cell, Clear, Row, Table},
+    Frame,
+};
+
+/// Draws a centered overlay listing keys and what they do.
+///
+/// # Arguments
+/// * `rows` - Pairs of `(keys, description)` in display order.
+/// * `theme` - The colors applied to the overlay.
+pub fn draw<K: AsRef<str>, D: AsRef<str>>(f: &mut Frame, rows: &[(K, D)], theme: &Theme) {
+    let title = tr("tui-keys");
+    let keys_width = rows
+        .iter()
+        .map(|(keys, _)| keys.as_ref().chars().count())
+        .max()
+        .unwrap_or(0) as u16;
+    let description_width = rows
+        .iter()
+        .map(|(_, description)| description.as_ref().chars().count())
+        .max()
+        .unwrap_or(0) as u16;
+
+    let frame = f.area();
+    let width = (keys_width + description_width + 5)
+        .max(title.chars().count() as u16 + 2)
+        .min(frame.width);
+    let height = (rows.len() as u16 + 2).min(frame.height);
+    let area = Rect {
+        x: frame.x + (frame.width - width) / 2,
+        y: frame.y + (frame.height - height) / 2,
+        width,
+        height,
+    };
+
+    let table = Table::new(
+        rows.iter().map(|(keys, description)| {
+            Row::new([
+                Cell::from(keys.as_ref().to_string()).style(theme.accent()),
+                Cell::from(description.as_ref().to_string()),
+            ])
+        }),
+        [Constraint::Length(keys_width), Constraint::Min(0)],
+    )
+    .column_spacing(2)
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title.as_str())
+            .border_style(theme.highlight())
+            .title_style(theme.accent())
+            .style(theme.base()),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
@@ -0,0 +1,98 @@
+use chrono::Utc;
+use chrono_tz::Tz;
+use itracker::{
+    log::LogEntry,
+    report::{self, Goal, GOAL_BAR_WIDTH},
+    status::State,
+    theme::Theme,
+    timefmt::DurationFormat,
+};
+use ratatui::{
+    layout::Rect,
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+
+/// The one-line status bar below every screen.
+pub struct StatusBar {
+    /// Timezone today is counted in.
+    pub tz: Tz,
+    pub durations: DurationFormat,
+    /// The daily goal in seconds, shown as a progress bar after today's total.
+    pub goal: Option<u64>,
+}
+
+impl StatusBar {
+    /// Draws the running task with its live elapsed time, today's total with
+    /// the progress toward the daily goal and the file the viewer works on.
+    pub fn draw(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        logs: &[LogEntry],
+        log_file: &str,
+        theme: &Theme,
+    ) {
+        let (tz, durations) = (self.tz, self.durations);
+        let now = Utc::now();
+        let today = report::today(now, tz);
+
+        // The most recently started entry that has not been stopped yet
+        let running = logs
+            .iter()
+            .filter(|log| log.is_running())
+            .filter_map(|log| log.start().map(|start| (start, log)))
+            .max_by_key(|(start, _)| *start)
+            .map(|(_, log)| log);
+
+        let running = match running {
+            Some(log) => format!(
+                "{} {} {} ",
+                State::of(Some(log)).icon(),
+                log.display_title(),
+                format_clock(report::tracked_seconds(log, now))
+            ),
+            None => format!("{} No running timer ", State::Idle.icon()),
+        };
+        let today_total =
+            report::summarize(report::entries_between(logs, today, today, tz), now, tz).total;
+
+        let status = Line::from(vec![
+            Span::styled(
+                running,
+                theme.base().fg(theme.background).bg(theme.highlight),
+            ),
+            Span::raw(match self.goal {
+                Some(target) => {
+                    let goal = Goal {
+                        tracked: today_total,
+                        target,
+                    };
+                    format!(
+                        " Today {} {} {}% │ {}",
+                        durations.format(today_total),
+                        goal.bar(GOAL_BAR_WIDTH),
+                        goal.percent(),
+                        log_file
+                    )
+                }
+                None => format!(" Today {} │ {}", durations.format(today_total), log_file),
+            }),
+        ]);
+        f.render_widget(
+            Paragraph::new(status).style(theme.base().fg(theme.background).bg(theme.accent)),
+            area,
+        );
+    }
+}
+
+/// Formats seconds as `H:MM:SS`.
+fn format_clock(seconds: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
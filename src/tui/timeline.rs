@@ -0,0 +1,190 @@
+use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
+use chrono_tz::Tz;
+use itracker::{
+    i18n::{tr, tr_args},
+    log::LogEntry,
+    report,
+    theme::Theme,
+    timefmt::DurationFormat,
+};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+/// Width of the entry labels left of the bars.
+const LABEL_WIDTH: usize = 20;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// One bar of the timeline, in seconds since midnight.
+struct Bar<'a> {
+    log: &'a LogEntry,
+    from: u32,
+    to: u32,
+    overlaps: bool,
+}
+
+/// Draws today's entries as bars on a 24-hour axis, marking overlapping entries.
+pub fn draw(
+    f: &mut Frame,
+    area: Rect,
+    logs: &[LogEntry],
+    tz: Tz,
+    durations: DurationFormat,
+    theme: &Theme,
+) {
+    f.render_widget(Block::default().style(theme.base()), area);
+
+    let now = Utc::now();
+    let today = report::today(now, tz);
+    let bars = day_bars(logs, today, now, tz);
+
+    let outer = Block::default()
+        .borders(Borders::ALL)
+        .title(tr_args(
+            "tui-timeline",
+            &[("day", today.to_string().into())],
+        ))
+        .border_style(theme.border())
+        .title_style(theme.accent())
+        .style(theme.base());
+    let inner = outer.inner(area);
+    f.render_widget(outer, area);
+
+    let [axis_area, bars_area, summary_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(inner);
+
+    let track_width = usize::from(inner.width)
+        .saturating_sub(LABEL_WIDTH + 1)
+        .max(1);
+    let column = |seconds: u32| {
+        ((f64::from(seconds) / SECONDS_PER_DAY) * track_width as f64).round() as usize
+    };
+
+    // Hour ticks, as many as fit without touching
+    let mut axis = vec![' '; track_width];
+    let step = [1, 2, 3, 4, 6, 12]
+        .into_iter()
+        .find(|step| track_width / (24 / step) >= 3)
+        .unwrap_or(24);
+    for hour in (0..24).step_by(step) {
+        for (i, c) in format!("{:02}", hour).chars().enumerate() {
+            if let Some(cell) = axis.get_mut(column(hour * 3600) + i) {
+                *cell = c;
+            }
+        }
+    }
+    let axis = format!(
+        "{:width$} {}",
+        "",
+        String::from_iter(axis),
+        width = LABEL_WIDTH
+    );
+    f.render_widget(Paragraph::new(axis).style(theme.accent()), axis_area);
+
+    if bars.is_empty() {
+        f.render_widget(Paragraph::new(tr("tui-nothing-today")), bars_area);
+        return;
+    }
+
+    let lines: Vec<Line> = bars
+        .iter()
+        .map(|bar| {
+            let from = column(bar.from).min(track_width - 1);
+            let to = column(bar.to).clamp(from + 1, track_width);
+            let bar_style = if bar.overlaps {
+                theme.highlight()
+            } else {
+                theme.accent()
+            };
+            Line::from(vec![
+                Span::raw(format!("{:<LABEL_WIDTH$} ", label(bar.log))),
+                Span::styled("·".repeat(from), theme.border()),
+                Span::styled("█".repeat(to - from), bar_style),
+                Span::styled("·".repeat(track_width - to), theme.border()),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), bars_area);
+
+    let tracked: u64 = report::summarize(bars.iter().map(|bar| bar.log), now, tz).total;
+    let overlapping = bars.iter().filter(|bar| bar.overlaps).count();
+    let summary = format!(
+        "Tracked {} · gaps {} · {} overlapping entries",
+        durations.format(tracked),
+        durations.format(gap_seconds(&bars)),
+        overlapping
+    );
+    f.render_widget(Paragraph::new(summary).style(theme.accent()), summary_area);
+}
+
+/// Places the entries started on `day` on the axis, sorted by start time.
+/// Running entries end at `now`, entries running past local midnight are cut off.
+fn day_bars(logs: &[LogEntry], day: NaiveDate, now: DateTime<Utc>, tz: Tz) -> Vec<Bar<'_>> {
+    let mut bars: Vec<Bar> = report::entries_between(logs, day, day, tz)
+        .into_iter()
+        .filter_map(|log| {
+            let start: DateTime<Tz> = log.start()?.with_timezone(&tz);
+            let elapsed = match log.elapsed_time.trim().parse::<i64>() {
+                Ok(0) | Err(_) => (now - start.with_timezone(&Utc)).num_seconds().max(0),
+                Ok(elapsed) => elapsed,
+            };
+            let from = start.num_seconds_from_midnight();
+            let end = start + Duration::seconds(elapsed);
+            let to = if end.date_naive() == day {
+                end.num_seconds_from_midnight()
+            } else {
+                SECONDS_PER_DAY as u32
+            };
+            Some(Bar {
+                log,
+                from,
+                to,
+                overlaps: false,
+            })
+        })
+        .collect();
+    bars.sort_by_key(|bar| (bar.from, bar.to));
+
+    for i in 0..bars.len() {
+        let (from, to) = (bars[i].from, bars[i].to);
+        bars[i].overlaps = bars
+            .iter()
+            .enumerate()
+            .any(|(j, other)| j != i && other.from < to && from < other.to);
+    }
+    bars
+}
+
+/// Untracked time between the first start and the last end of the day.
+fn gap_seconds(bars: &[Bar]) -> u64 {
+    let mut gaps = 0;
+    let mut covered_until: Option<u32> = None;
+    for bar in bars {
+        if let Some(until) = covered_until {
+            gaps += u64::from(bar.from.saturating_sub(until));
+        }
+        covered_until = Some(covered_until.map_or(bar.to, |until| until.max(bar.to)));
+    }
+    gaps
+}
+
+fn label(log: &LogEntry) -> String {
+    let title = format!("{} {}", log.index, log.display_title());
+    if title.chars().count() > LABEL_WIDTH {
+        let mut short: String = title.chars().take(LABEL_WIDTH - 1).collect();
+        short.push('…');
+        short
+    } else {
+        title
+    }
+}
@@ -0,0 +1,276 @@
+//! The undo journal: log files as they were before each of the last changes
+//! to entries, so `undo` can take the latest one back.
+//!
+//! Steps are kept in `undo/` in the data directory, the last
+//! [`DEFAULT_KEEP`] across all log files: a copy of the log file (and its
+//! journal) taken before the change, and a fingerprint of the file after
+//! it. Undoing puts the copy back once the fingerprint shows nothing else
+//! changed the file since; the contents replaced are backed up like before
+//! any rewrite, so an undo can itself be rolled back with `restore`.
+//!
+//! Changes of single entries, e.g. stopping a timer, keep a copy of these
+//! entries only and the size and modification time of the file as its
+//! fingerprint, so they stay as cheap as the change on logs of any length.
+//! Undoing one amends the entries back.
+
+use crate::{
+    backup::{copy_with_journal, remove_with_journal, Backups},
+    config,
+    error::ITrackerError,
+    log::LogEntry,
+    schema::{self, Record},
+    status,
+    storage::Storage,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Steps kept across all log files.
+pub const DEFAULT_KEEP: usize = 20;
+
+/// One change that can be undone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    /// What was done, e.g. `delete entry 42`.
+    pub action: String,
+    pub time: DateTime<Utc>,
+    /// The absolute path of the log file changed.
+    pub file: PathBuf,
+    /// The copy of the log file taken before the change.
+    snapshot: PathBuf,
+    /// The hash of the log file and its journal after the change, or their
+    /// stamps for a change of entries.
+    fingerprint: String,
+    /// Whether `snapshot` holds only the entries changed, see
+    /// [`UndoLog::record_entries`].
+    #[serde(default)]
+    entries: bool,
+}
+
+/// The steps of the undo journal, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoLog {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl UndoLog {
+    /// Keeps the steps and copies in `dir`, the last `keep` of them.
+    pub fn new(dir: impl Into<PathBuf>, keep: usize) -> Self {
+        UndoLog {
+            dir: dir.into(),
+            keep,
+        }
+    }
+
+    /// The journal in `undo/` in the data directory.
+    pub fn in_data_dir() -> Result<Self, ITrackerError> {
+        Ok(UndoLog::new(config::data_dir()?.join("undo"), DEFAULT_KEEP))
+    }
+
+    /// Runs `change` on `log`, keeping what it changed as the latest step.
+    /// A change that fails keeps no step.
+    ///
+    /// # Arguments
+    /// - `log`: The log file `change` writes.
+    /// - `action`: What `change` does, shown by `undo`.
+    /// - `change`: The change.
+    pub fn record<T>(
+        &self,
+        log: &str,
+        action: &str,
+        change: impl FnOnce() -> Result<T, ITrackerError>,
+    ) -> Result<T, ITrackerError> {
+        let log = Path::new(log);
+        if !log.exists() {
+            return change();
+        }
+        fs::create_dir_all(&self.dir).map_err(ITrackerError::file(&self.dir))?;
+        let time = Utc::now();
+        let snapshot = self
+            .dir
+            .join(format!("{}.csv", time.format("%Y%m%dT%H%M%S%.6fZ")));
+        copy_with_journal(log, &snapshot)?;
+        let changed = match change() {
+            Ok(changed) => changed,
+            Err(e) => {
+                remove_with_journal(&snapshot)?;
+                return Err(e);
+            }
+        };
+
+        self.push(Step {
+            action: action.to_string(),
+            time,
+            file: std::path::absolute(log).map_err(ITrackerError::file(log))?,
+            snapshot,
+            fingerprint: fingerprint(log)?,
+            entries: false,
+        })?;
+        Ok(changed)
+    }
+
+    /// Like [`record`](Self::record) for a change of the entries with
+    /// `indices` alone, e.g. stopping a timer or editing an entry: only these
+    /// entries are copied, and undoing amends them back.
+    pub fn record_entries<T>(
+        &self,
+        log: &str,
+        action: &str,
+        indices: &[usize],
+        change: impl FnOnce() -> Result<T, ITrackerError>,
+    ) -> Result<T, ITrackerError> {
+        let path = Path::new(log);
+        if !path.exists() {
+            return change();
+        }
+        let storage = Storage::new(log);
+        let mut records = Vec::new();
+        for &index in indices {
+            records.push(Record::from(&storage.entry(index)?));
+        }
+        fs::create_dir_all(&self.dir).map_err(ITrackerError::file(&self.dir))?;
+        let time = Utc::now();
+        let snapshot = self
+            .dir
+            .join(format!("{}.csv", time.format("%Y%m%dT%H%M%S%.6fZ")));
+        schema::write(&snapshot, &records)?;
+        let changed = match change() {
+            Ok(changed) => changed,
+            Err(e) => {
+                remove_with_journal(&snapshot)?;
+                return Err(e);
+            }
+        };
+
+        self.push(Step {
+            action: action.to_string(),
+            time,
+            file: std::path::absolute(path).map_err(ITrackerError::file(path))?,
+            snapshot,
+            fingerprint: stamps(path)?,
+            entries: true,
+        })?;
+        Ok(changed)
+    }
+
+    /// The latest step, if any.
+    pub fn last(&self) -> Result<Option<Step>, ITrackerError> {
+        Ok(self.steps()?.pop())
+    }
+
+    /// Takes the latest step back, backing up the contents of its log file
+    /// that are replaced.
+    ///
+    /// # Arguments
+    /// - `backups`: Where the contents replaced are backed up.
+    /// - `force`: Undo even if the file changed since the step. Putting back
+    ///   a copy of the whole file loses those changes too, while entries
+    ///   amended back only lose what changed in these entries since.
+    ///
+    /// # Returns
+    /// - `Ok(Step)`: The step taken back.
+    /// - `Err(ITrackerError)`: `Backup` if there is nothing to undo or the
+    ///   file changed since, or an IO error.
+    pub fn undo(&self, backups: &Backups, force: bool) -> Result<Step, ITrackerError> {
+        let mut steps = self.steps()?;
+        let Some(step) = steps.pop() else {
+            return Err(ITrackerError::Backup("Nothing to undo".to_string()));
+        };
+        let current = if step.entries {
+            stamps(&step.file)?
+        } else {
+            fingerprint(&step.file)?
+        };
+        if !force && current != step.fingerprint {
+            let lost = if step.entries {
+                "the changes of these entries"
+            } else {
+                "those changes"
+            };
+            return Err(ITrackerError::Backup(format!(
+                "{} changed since the {}, pass --force to undo it anyway and lose {}",
+                step.file.display(),
+                step.action,
+                lost
+            )));
+        }
+        if step.entries {
+            let storage = Storage::new(step.file.to_string_lossy());
+            for record in schema::read(&step.snapshot)? {
+                match storage.update(&LogEntry::from(record)) {
+                    // Entries deleted since stay deleted
+                    Err(ITrackerError::EntryNotFound { .. }) => {}
+                    result => result?,
+                }
+            }
+        } else {
+            backups.save(&step.file)?;
+            copy_with_journal(&step.snapshot, &step.file)?;
+        }
+        remove_with_journal(&step.snapshot)?;
+        self.write(&steps)?;
+        Ok(step)
+    }
+
+    /// Keeps `step` as the latest one, dropping the oldest beyond `keep`.
+    fn push(&self, step: Step) -> Result<(), ITrackerError> {
+        let mut steps = self.steps()?;
+        steps.push(step);
+        let dropped = steps.len().saturating_sub(self.keep);
+        for step in steps.drain(..dropped) {
+            remove_with_journal(&step.snapshot)?;
+        }
+        self.write(&steps)
+    }
+
+    fn steps_path(&self) -> PathBuf {
+        self.dir.join("steps.json")
+    }
+
+    fn steps(&self) -> Result<Vec<Step>, ITrackerError> {
+        let path = self.steps_path();
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| ITrackerError::Backup(format!("{}: {}", path.display(), e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(ITrackerError::file(&path)(e)),
+        }
+    }
+
+    fn write(&self, steps: &[Step]) -> Result<(), ITrackerError> {
+        let path = self.steps_path();
+        let contents = serde_json::to_string_pretty(steps).map_err(io::Error::other)?;
+        fs::write(&path, contents).map_err(ITrackerError::file(&path))
+    }
+}
+
+/// The size and modification time of a log file and its journal, which
+/// tell a later change without reading them.
+fn stamps(log: &Path) -> Result<String, ITrackerError> {
+    let stamps = (
+        status::stamp(log)?,
+        status::stamp(&schema::journal_path(log))?,
+    );
+    Ok(serde_json::to_string(&stamps).map_err(io::Error::other)?)
+}
+
+/// The hash of a log file and its journal.
+fn fingerprint(log: &Path) -> Result<String, ITrackerError> {
+    let mut hasher = Sha256::new();
+    for path in [log.to_path_buf(), schema::journal_path(log)] {
+        match fs::read(&path) {
+            Ok(contents) => hasher.update(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(ITrackerError::file(&path)(e)),
+        }
+        // Keeps moving bytes between the file and the journal from going unnoticed
+        hasher.update([0]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -0,0 +1,271 @@
+//! Keeping the output file on a WebDAV server such as Nextcloud.
+//!
+//! The local output file stays the working copy every command reads and
+//! writes, so tracking works offline; `sync` brings it together with the
+//! copy on the server. The server's ETag of the last synced version is kept
+//! in `<log>.webdav` along with its contents: unchanged files are not
+//! downloaded again, both sides are merged entry by entry against that
+//! version, and the upload only succeeds if nobody replaced the file since
+//! it was downloaded.
+
+use crate::{
+    backup::Backups,
+    error::ITrackerError,
+    schema::{self, Record},
+    sync::merge_records,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// Attempts before giving up when the file keeps changing on the server.
+const ATTEMPTS: usize = 3;
+
+/// The `[webdav]` section of the configuration file.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebDavConfig {
+    /// URL of the log file on the server, e.g.
+    /// `https://cloud.example.com/remote.php/dav/files/me/time.csv`.
+    pub url: Option<String>,
+    pub username: Option<String>,
+    /// Password or app token, better given as `ITRACKER_WEBDAV__PASSWORD`.
+    pub password: Option<String>,
+}
+
+/// What `<log>.webdav` remembers about the last sync.
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+    /// ETag of the file on the server, `None` if the server sent none.
+    etag: Option<String>,
+    /// Contents of the file as synced, the common version of the next merge.
+    contents: String,
+}
+
+/// The server's copy of the log file.
+enum Remote {
+    /// There is no file on the server yet.
+    Missing,
+    /// The file did not change since the last sync.
+    Unchanged,
+    Changed {
+        records: Vec<Record>,
+        etag: Option<String>,
+    },
+}
+
+/// The answer to an upload.
+enum Upload {
+    /// The file was stored, with the new ETag if the server sent one.
+    Stored(Option<String>),
+    /// The file was replaced on the server since it was downloaded.
+    Outdated,
+}
+
+/// The outcome of a sync.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Synced {
+    /// Whether the local file changed.
+    pub pulled: bool,
+    /// Whether the file on the server was replaced.
+    pub pushed: bool,
+    /// Local entries given a new index because the server's copy used
+    /// theirs, as `(old, new)`.
+    pub renumbered: Vec<(usize, usize)>,
+}
+
+/// A log file on a WebDAV server.
+#[derive(Debug, Clone)]
+pub struct WebDav {
+    url: String,
+    /// Value of the `Authorization` header, if credentials are configured.
+    authorization: Option<String>,
+}
+
+impl WebDav {
+    pub fn new(url: impl Into<String>) -> Self {
+        WebDav {
+            url: url.into(),
+            authorization: None,
+        }
+    }
+
+    /// Sends the credentials with every request, using basic authentication.
+    pub fn with_credentials(mut self, username: &str, password: &str) -> Self {
+        let token = STANDARD.encode(format!("{}:{}", username, password));
+        self.authorization = Some(format!("Basic {}", token));
+        self
+    }
+
+    /// Resolves the `[webdav]` section.
+    ///
+    /// # Returns
+    /// - `Ok(Some(WebDav))`: If a URL is configured.
+    /// - `Ok(None)`: If the output file is only kept locally.
+    /// - `Err(ITrackerError)`: `Config` if the URL is not an http(s) URL.
+    pub fn from_config(config: Option<&WebDavConfig>) -> Result<Option<Self>, ITrackerError> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        let Some(url) = config.url.as_deref().filter(|url| !url.is_empty()) else {
+            return Ok(None);
+        };
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return Err(ITrackerError::Config(format!(
+                "url must start with https:// or http://, got {}",
+                url
+            )));
+        }
+        let webdav = WebDav::new(url);
+        Ok(Some(match &config.username {
+            Some(username) => {
+                webdav.with_credentials(username, config.password.as_deref().unwrap_or_default())
+            }
+            None => webdav,
+        }))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Merges the local log file with the copy on the server and uploads the
+    /// result where the server lacks something.
+    ///
+    /// # Arguments
+    /// - `log`: The local log file.
+    /// - `backups`: Where the local file is backed up before it is replaced.
+    ///
+    /// # Returns
+    /// - `Ok(Synced)`: What changed on either side.
+    /// - `Err(ITrackerError)`: `Sync` if the server cannot be reached, entries
+    ///   were changed differently on both sides, or the file kept changing on
+    ///   the server.
+    pub fn sync(&self, log: &str, backups: &Backups) -> Result<Synced, ITrackerError> {
+        let state_path = schema::webdav_path(log);
+        let state: Option<State> = match fs::read_to_string(&state_path) {
+            Ok(contents) => serde_json::from_str(&contents).ok(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(ITrackerError::file(&state_path)(e)),
+        };
+        let base = match &state {
+            Some(state) => schema::parse(&state_path, state.contents.as_bytes())?,
+            None => Vec::new(),
+        };
+        let ours = if Path::new(log).exists() {
+            schema::read(log)?
+        } else {
+            Vec::new()
+        };
+
+        let mut etag = state.as_ref().and_then(|state| state.etag.clone());
+        for _ in 0..ATTEMPTS {
+            let (theirs, exists) = match self.get(etag.as_deref())? {
+                Remote::Missing => (Vec::new(), false),
+                Remote::Unchanged => (base.clone(), true),
+                Remote::Changed { records, etag: tag } => {
+                    etag = tag;
+                    (records, true)
+                }
+            };
+
+            let merged = merge_records(&base, &ours, &theirs);
+            if !merged.conflicts.is_empty() {
+                let indices: Vec<String> = merged.conflicts.iter().map(usize::to_string).collect();
+                return Err(ITrackerError::Sync(format!(
+                    "{}: entries changed on the server and here: {}; edit them here to match the server and sync again",
+                    log,
+                    indices.join(", ")
+                )));
+            }
+
+            let contents = schema::contents(log, &merged.records)?;
+            let pushed = !exists || merged.records != theirs;
+            if pushed {
+                match self.put(&contents, etag.as_deref().filter(|_| exists), exists)? {
+                    Upload::Stored(tag) => etag = tag,
+                    Upload::Outdated => {
+                        etag = None;
+                        continue;
+                    }
+                }
+            }
+
+            let pulled = merged.records != ours;
+            if pulled {
+                backups.save(log)?;
+                schema::write(log, &merged.records)?;
+            }
+            let state = State { etag, contents };
+            let json = serde_json::to_string(&state)
+                .map_err(|e| ITrackerError::Sync(format!("Cannot save the sync state: {}", e)))?;
+            fs::write(&state_path, json).map_err(ITrackerError::file(&state_path))?;
+            return Ok(Synced {
+                pulled,
+                pushed,
+                renumbered: merged.renumbered,
+            });
+        }
+        Err(ITrackerError::Sync(format!(
+            "{} keeps changing on the server, sync again later",
+            self.url
+        )))
+    }
+
+    /// Downloads the file unless it still has the ETag `etag`.
+    fn get(&self, etag: Option<&str>) -> Result<Remote, ITrackerError> {
+        let mut request = self.request("GET");
+        if let Some(etag) = etag {
+            request = request.set("If-None-Match", etag);
+        }
+        match request.call() {
+            Ok(response) if response.status() == 304 => Ok(Remote::Unchanged),
+            Ok(response) => {
+                let etag = response.header("ETag").map(str::to_string);
+                let body = response
+                    .into_string()
+                    .map_err(|e| self.error(ureq::Error::from(e)))?;
+                let records = schema::parse(Path::new(&self.url), body.as_bytes())?;
+                Ok(Remote::Changed { records, etag })
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(Remote::Missing),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    /// Uploads `contents` if the file on the server is still the one with
+    /// ETag `etag`, or still missing if `exists` is false.
+    fn put(
+        &self,
+        contents: &str,
+        etag: Option<&str>,
+        exists: bool,
+    ) -> Result<Upload, ITrackerError> {
+        let mut request = self
+            .request("PUT")
+            .set("Content-Type", "text/csv; charset=utf-8");
+        request = match (exists, etag) {
+            (false, _) => request.set("If-None-Match", "*"),
+            (true, Some(etag)) => request.set("If-Match", etag),
+            // Without an ETag the server gives no way to detect concurrent uploads
+            (true, None) => request,
+        };
+        match request.send_string(contents) {
+            Ok(response) => Ok(Upload::Stored(response.header("ETag").map(str::to_string))),
+            Err(ureq::Error::Status(412, _)) => Ok(Upload::Outdated),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    fn request(&self, method: &str) -> ureq::Request {
+        let request = ureq::request(method, &self.url);
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+
+    fn error(&self, error: ureq::Error) -> ITrackerError {
+        ITrackerError::Sync(format!("{}: {}", self.url, error))
+    }
+}
@@ -0,0 +1,3206 @@
+//! End-to-end tests driving the `Itraker` binary against log files in
+//! temporary directories.
+
+mod common;
+
+use assert_cmd::Command;
+use common::{log_file, running, stopped};
+use itracker::{schema, Storage};
+use predicates::prelude::*;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    process,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tempfile::TempDir;
+
+/// The binary with its config and data directories inside `home`, so tests
+/// neither read nor change the settings of the user running them.
+fn itracker(home: &Path) -> Command {
+    Command::from_std(binary(home))
+}
+
+/// The binary set up like `itracker`, for processes that keep running.
+fn binary(home: &Path) -> process::Command {
+    let mut cmd = process::Command::new(assert_cmd::cargo::cargo_bin("Itraker"));
+    for (key, _) in std::env::vars() {
+        if key.starts_with("ITRACKER_") || key == "LC_ALL" || key == "LC_MESSAGES" {
+            cmd.env_remove(key);
+        }
+    }
+    cmd.current_dir(home)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("XDG_RUNTIME_DIR", home.join("run"))
+        .env("LANG", "C");
+    cmd
+}
+
+fn entries(path: &Path) -> Vec<itracker::Entry> {
+    Storage::new(path.to_str().unwrap()).entries().unwrap()
+}
+
+#[test]
+fn pause_resume_and_stop_the_running_entry() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), running(2, "current", 60)],
+    );
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("--pause")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Timer paused for log entry at index 2.",
+        ));
+    assert!(entries(&log)[1].paused_since().is_some());
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("--pause")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Log entry 2 is already paused"));
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("--resume")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Timer resumed for log entry at index 2.",
+        ));
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Timer stopped at"));
+
+    let entries = entries(&log);
+    assert_eq!(entries.len(), 2);
+    assert!(!entries[1].is_running());
+    assert!(entries[1].elapsed_time.parse::<u64>().unwrap() >= 3600);
+    assert_eq!(entries[0].elapsed_time, "3600");
+}
+
+#[test]
+fn stopping_an_entry_that_is_not_running_fails() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[stopped(1, "done")]);
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("--pause")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No active timer"));
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--resume", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Log entry 1 is not running"));
+    // Stopping again must not overwrite the tracked time
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["-t", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Log entry 1 is not running"));
+    assert_eq!(entries(&log)[0].elapsed_time, "3600");
+}
+
+#[test]
+fn missing_and_empty_files() {
+    let home = TempDir::new().unwrap();
+    let missing = home.path().join("missing.csv");
+    itracker(home.path())
+        .arg("-o")
+        .arg(&missing)
+        .arg("--pause")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.csv"));
+    assert!(!missing.exists());
+
+    let empty = home.path().join("empty.csv");
+    fs::write(&empty, "").unwrap();
+    itracker(home.path())
+        .arg("-o")
+        .arg(&empty)
+        .arg("--pause")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No active timer"));
+}
+
+#[test]
+fn corrupted_records_are_reported_and_left_alone() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[running(1, "fine", 5), "x,not a date,broken".to_string()],
+    );
+    let before = fs::read(&log).unwrap();
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("record 2: invalid Index 'x'"));
+    itracker(home.path())
+        .args(["-y", "-l"])
+        .arg(&log)
+        .args(["-d", "1"])
+        .assert()
+        .failure();
+
+    assert_eq!(fs::read(&log).unwrap(), before);
+}
+
+#[test]
+fn files_from_older_versions_are_upgraded_when_rewritten() {
+    let home = TempDir::new().unwrap();
+    let log = home.path().join("old.csv");
+    fs::write(
+        &log,
+        "Index,Start Time,Task Description,Elapsed Time (seconds),Paused Time (seconds)\n\
+         1,\"Mon, 3 Jun 2024 09:00:00 +0000\",a,3600,0\n\
+         2,\"Mon, 3 Jun 2024 11:00:00 +0000\",b,1800,60\n",
+    )
+    .unwrap();
+
+    itracker(home.path())
+        .args(["-y", "-l"])
+        .arg(&log)
+        .args(["-d", "1"])
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(&log).unwrap();
+    assert!(contents.starts_with(common::HEADER));
+    let entries = entries(&log);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].message, "b");
+    assert_eq!(entries[0].paused_time, "60");
+}
+
+#[test]
+fn deleting_needs_confirmation_without_a_terminal() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[stopped(1, "a"), stopped(2, "b")]);
+
+    itracker(home.path())
+        .arg("-l")
+        .arg(&log)
+        .args(["-d", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass --yes"));
+    assert_eq!(entries(&log).len(), 2);
+
+    itracker(home.path())
+        .arg("-l")
+        .arg(&log)
+        .args(["-y", "-d", "9"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Log entry 9 not found"));
+
+    itracker(home.path())
+        .arg("-l")
+        .arg(&log)
+        .args(["-y", "-d", "1"])
+        .assert()
+        .success();
+    let entries = entries(&log);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].index, 2);
+}
+
+#[test]
+fn compact_renumbers_and_reports_the_mapping() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(2, "b"), stopped(5, "e"), stopped(6, "f")],
+    );
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["-y", "compact"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 -> 1\n5 -> 2\n6 -> 3\n"));
+
+    let indices: Vec<usize> = entries(&log).iter().map(|entry| entry.index).collect();
+    assert_eq!(indices, [1, 2, 3]);
+}
+
+#[test]
+fn compact_sorts_and_normalizes_an_old_file() {
+    let home = TempDir::new().unwrap();
+    let log = home.path().join("time.csv");
+    // Written by a version without the columns after the title
+    fs::write(
+        &log,
+        "Index,Start Time,Task Description,Elapsed Time (seconds),Paused Time (seconds),Title\n\
+         3,\"Tue, 04 Jun 2024 09:00:00 +0000\",late,3600,0,b\n\
+         4,\"Mon, 03 Jun 2024 09:00:00 +0000\",early,3600,0,a\n",
+    )
+    .unwrap();
+    fs::write(
+        log.with_extension("csv.journal"),
+        format!(
+            "{}\n4,\"Mon, 03 Jun 2024 09:00:00 +0000\",early,3600,0, a ,,\" x,,y \",,,\n",
+            schema::HEADERS.join(",")
+        ),
+    )
+    .unwrap();
+    let compact = || {
+        let mut command = itracker(home.path());
+        command.arg("-o").arg(&log).args(["-y", "compact"]);
+        command
+    };
+
+    compact()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("4 -> 1\n3 -> 2\n"))
+        .stdout(predicate::str::contains(
+            "Entries moved to sort by start time: 2",
+        ))
+        .stdout(predicate::str::contains(
+            "Entries trimmed of stray whitespace or empty tags: 1",
+        ))
+        .stdout(predicate::str::contains(
+            "Rows given the columns they were missing: 2",
+        ))
+        .stdout(predicate::str::contains("Journal amendments folded in: 1"))
+        .stdout(predicate::str::contains(
+            "Headers rewritten in canonical order.",
+        ));
+
+    let contents = fs::read_to_string(&log).unwrap();
+    assert!(contents.starts_with(&schema::HEADERS.join(",")));
+    assert!(!log.with_extension("csv.journal").exists());
+    let entries = entries(&log);
+    assert_eq!(entries[0].index, 1);
+    assert_eq!(entries[0].title, "a");
+    assert_eq!(entries[0].tags, ["x", "y"]);
+    assert_eq!(entries[1].message, "late");
+
+    compact()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is already compact."));
+}
+
+#[test]
+fn entries_sharing_an_index_are_refused_until_compacted() {
+    let home = TempDir::new().unwrap();
+    // Written by a version that could give out an index twice
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(3, "done"), running(3, "running", 5)],
+    );
+    let before = fs::read(&log).unwrap();
+    let run = |file: &str, args: &[&str]| {
+        let mut command = itracker(home.path());
+        command.arg(file).arg(&log).arg("-y").args(args);
+        command
+    };
+
+    for (file, args) in [
+        ("-o", &["-t", "3"][..]),
+        ("-o", &["--pause"]),
+        ("-o", &["status"]),
+        ("-l", &["-d", "3"]),
+    ] {
+        run(file, args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "Log entry 3 appears more than once",
+            ))
+            .stderr(predicate::str::contains("run `compact`"));
+    }
+    assert_eq!(fs::read(&log).unwrap(), before);
+
+    run("-o", &["compact"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 -> 2\n"));
+    run("-o", &["-t", "2"]).assert().success();
+    let entries = entries(&log);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].title, "running");
+    assert!(!entries[1].is_running());
+}
+
+#[test]
+fn restore_rolls_back_a_delete() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[stopped(1, "a"), stopped(2, "b")]);
+    let original = fs::read(&log).unwrap();
+
+    itracker(home.path())
+        .arg("-l")
+        .arg(&log)
+        .args(["-y", "-d", "2"])
+        .assert()
+        .success();
+    assert_eq!(entries(&log).len(), 1);
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["-y", "restore"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored"));
+    assert_eq!(fs::read(&log).unwrap(), original);
+}
+
+#[test]
+fn stopping_in_a_huge_file_only_appends_to_the_journal() {
+    let home = TempDir::new().unwrap();
+    let mut rows: Vec<String> = (1..20_000).map(|index| stopped(index, "old")).collect();
+    rows.push(running(20_000, "current", 30));
+    let log = log_file(home.path(), "huge.csv", &rows);
+    let before = fs::read(&log).unwrap();
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "20000"])
+        .assert()
+        .success();
+
+    assert_eq!(fs::read(&log).unwrap(), before);
+    assert!(schema::journal_path(&log).exists());
+    let entries = entries(&log);
+    assert_eq!(entries.len(), 20_000);
+    assert!(entries.iter().all(|entry| !entry.is_running()));
+}
+
+#[test]
+fn durations_are_shown_in_the_configured_format() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[running(1, "current", 90)]);
+
+    itracker(home.path())
+        .env("ITRACKER_DURATION_FORMAT", "iso")
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Elapsed time: PT1H30M, Total paused time: PT0S",
+        ));
+    assert_eq!(entries(&log)[0].elapsed_time, "5400");
+}
+
+#[test]
+fn list_and_status_color_timers_by_state_when_asked() {
+    let home = TempDir::new().unwrap();
+    // Paused since now, the last column
+    let paused = format!(
+        "{}\"{}\"",
+        running(3, "paused", 30),
+        chrono::Utc::now().to_rfc2822()
+    );
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), running(2, "current", 90), paused],
+    );
+    let run = |args: &[&str]| {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    run(&["list", "--color", "always"])
+        .stdout(predicate::str::is_match(r"\x1b\[2m   1  .*old\x1b\[0m\n").unwrap())
+        .stdout(predicate::str::is_match(r"\x1b\[32m   2  .*1h 30m  ▶ current\x1b\[0m\n").unwrap())
+        .stdout(predicate::str::is_match(r"\x1b\[33m   3  .*⏸ paused\x1b\[0m\n").unwrap());
+    run(&["--color", "always", "status"])
+        .stdout(predicate::str::is_match(r"^\x1b\[33m⏸ paused 30m\x1b\[0m\n$").unwrap());
+    // Without a terminal auto leaves the output plain, like never
+    run(&["list"])
+        .stdout(predicate::str::contains("\x1b[").not())
+        .stdout(predicate::str::contains("   2  "));
+    run(&["list", "--color", "never"]).stdout(predicate::str::contains("\x1b[").not());
+    run(&["list"])
+        .stdout(predicate::str::contains("   2  90 minutes ago").not())
+        .stdout(predicate::str::is_match(r"   2  today \d\d:\d\d|   2  yesterday ").unwrap())
+        .stdout(predicate::str::contains("   3  30 minutes ago"));
+    run(&["list", "--absolute"])
+        .stdout(predicate::str::is_match(r"   3  \w{3}, \d+ \w{3} \d{4} ").unwrap());
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["list", "--color", "sometimes"])
+        .assert()
+        .stderr(predicate::str::contains("expected auto, always or never"));
+}
+
+#[test]
+fn read_commands_print_json_yaml_and_csv_for_scripts() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",Spec,3600,0,Review,acme,\"a,b\",30,".to_string(),
+            "2,\"Mon, 3 Jun 2024 11:00:00 +0000\",,1800,0,Mail,,,,".to_string(),
+        ],
+    );
+    let run = |args: &[&str]| {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(args)
+            .assert()
+    };
+    let range = ["--from", "2024-06-03", "--to", "2024-06-03"];
+
+    let output = run(&[&["list", "--format", "json"][..], &range[..2]].concat())
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let listed: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(listed[0]["start"], "2024-06-03T09:00:00+00:00");
+    assert_eq!(listed[0]["tracked_seconds"], 3600);
+    assert_eq!(listed[0]["tags"], serde_json::json!(["a", "b"]));
+    assert_eq!(listed[1]["state"], "stopped");
+    run(&[&["list", "--format", "csv"][..], &range[..2]].concat())
+        .success()
+        .stdout(
+            [
+                "index,start,tracked_seconds,paused_seconds,state,title,description,project,tags,estimate_minutes,metadata",
+                "1,2024-06-03T09:00:00+00:00,3600,0,stopped,Review,Spec,acme,\"a,b\",30,",
+                "2,2024-06-03T11:00:00+00:00,1800,0,stopped,Mail,,,,,",
+                "",
+            ]
+            .join("\n"),
+        );
+    run(&[&["report", "--format", "yaml"][..], &range[..]].concat())
+        .success()
+        .stdout(predicate::str::starts_with(
+            "- date: 2024-06-03\n  project: (no project)\n  tracked_seconds: 1800\n  entries: 1\n",
+        ))
+        .stdout(predicate::str::contains(
+            "  project: acme\n  tracked_seconds: 3600\n",
+        ));
+    run(&["status", "--format", "csv"])
+        .success()
+        .stdout("state,index,title,start,tracked_seconds,today_seconds,goal_seconds\nidle,,,,,,\n");
+    // Bars keep their formats, which only status prints
+    run(&["status", "--format", "waybar"])
+        .success()
+        .stdout(predicate::str::contains("\"class\":\"idle\""));
+    run(&["report", "--format", "waybar"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "the status bar formats are for status",
+        ));
+}
+
+#[test]
+fn status_and_list_print_entries_as_templates() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "[templates]\nbar = \"{{icon}} {{ title }} ({{state}})\"\n",
+    )
+    .unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), running(2, "current", 90)],
+    );
+    let run = |args: &[&str]| {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(args)
+            .assert()
+    };
+
+    run(&["status", "--template", "{{title}} [{{elapsed}}]"])
+        .success()
+        .stdout("current [1h 30m]\n");
+    run(&["--template", "bar", "status"])
+        .success()
+        .stdout("▶ current (running)\n");
+    run(&["list", "--template", "{{index}}:{{title}}:{{elapsed}}"])
+        .success()
+        .stdout(predicate::str::contains("\n1:old:1h\n2:current:1h 30m\n"));
+    run(&["status", "--template", "{{title}} {{duration}}"])
+        .failure()
+        .stderr(predicate::str::contains(
+            "Unknown template field 'duration'",
+        ));
+    run(&["status", "--template", "missing"])
+        .failure()
+        .stderr(predicate::str::contains("No template 'missing'"));
+}
+
+#[test]
+fn messages_follow_the_locale_or_the_language_setting() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[running(1, "current", 90)]);
+
+    itracker(home.path())
+        .env("LANG", "de_DE.UTF-8")
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Dauer: 1h 30m, Pausen insgesamt: 0s",
+        ));
+    itracker(home.path())
+        .env("ITRACKER_LANGUAGE", "de")
+        .arg("-o")
+        .arg(&log)
+        .args(["report", "--today"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pro Projekt"))
+        .stdout(predicate::str::is_match(r"Gesamt\s+1h 30m  ein Eintrag").unwrap());
+    itracker(home.path())
+        .env("ITRACKER_LANGUAGE", "de")
+        .arg("-o")
+        .arg(&log)
+        .args(["-y", "compact"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Ausgabedatei: "))
+        .stdout(predicate::str::contains(
+            "Kopfzeile in kanonische Reihenfolge gebracht.",
+        ));
+    itracker(home.path())
+        .env("ITRACKER_LANGUAGE", "de")
+        .arg("-l")
+        .arg(&log)
+        .args(["-y", "-d", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Eintrag mit Index 1 aus {} gelöscht.",
+            log.display()
+        )));
+    itracker(home.path())
+        .env("ITRACKER_LANGUAGE", "tlh")
+        .arg("-o")
+        .arg(&log)
+        .arg("status")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No translation for 'tlh'"));
+}
+
+#[test]
+fn numbers_and_dates_follow_the_locale_setting() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "rate = 800\ncurrency = \"EUR\"\nlocale = \"de_DE\"\nduration_format = \"decimal\"\n",
+    )
+    .unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &["1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,5400,0,Review,acme,,,".to_string()],
+    );
+    let run = |args: &[&str]| {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    // Messages stay English, the locale only changes how values are written
+    run(&["report", "--from", "2024-06-03", "--to", "2024-06-03"])
+        .stdout(predicate::str::contains("Report 03.06.2024\n"))
+        .stdout(predicate::str::is_match(r"acme\s+1,50h\s+1\.200,00 €\n").unwrap());
+    run(&["invoice", "--from", "2024-06-01", "--client", "acme"])
+        .stdout(predicate::str::contains(
+            "Invoice 01.06.2024 to 30.06.2024, acme\n",
+        ))
+        .stdout(predicate::str::is_match(r"Total\s+1,50\s+1\.200,00 €\n$").unwrap());
+
+    fs::write(
+        config.join("config.toml"),
+        "rate = 800\ncurrency = \"USD\"\n",
+    )
+    .unwrap();
+    itracker(home.path())
+        .env("LANG", "en_US.UTF-8")
+        .arg("-o")
+        .arg(&log)
+        .args(["report", "--from", "2024-06-03", "--to", "2024-06-03"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Report 06/03/2024\n"))
+        .stdout(predicate::str::is_match(r"acme\s+1h 30m\s+\$1,200\.00\n").unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn timer_commands_go_through_a_running_daemon() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[running(1, "current", 10)]);
+    let socket = home.path().join("run/itracker/daemon.sock");
+
+    let mut daemon = binary(home.path())
+        .arg("daemon")
+        .stdout(process::Stdio::null())
+        .spawn()
+        .unwrap();
+    for _ in 0..100 {
+        if socket.exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // A client that never finishes its request keeps no other waiting
+    let mut stalled = std::os::unix::net::UnixStream::connect(&socket).unwrap();
+    stalled.write_all(b"{\"file\":").unwrap();
+    let started = std::time::Instant::now();
+    let pause = itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("--pause")
+        .assert();
+    assert!(started.elapsed() < Duration::from_secs(4));
+    let paused_again = itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("--pause")
+        .assert();
+    daemon.kill().unwrap();
+    daemon.wait().unwrap();
+
+    assert!(socket.exists());
+    pause.success().stdout(predicate::str::contains(
+        "Timer paused for log entry at index 1.",
+    ));
+    paused_again
+        .failure()
+        .stderr(predicate::str::contains("Log entry 1 is already paused"));
+    assert!(entries(&log)[0].paused_since().is_some());
+}
+
+/// Sends a raw HTTP request to `127.0.0.1:port` and returns the whole answer.
+fn http(port: u16, request: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut answer = String::new();
+    stream.read_to_string(&mut answer).unwrap();
+    answer
+}
+
+#[test]
+fn the_rest_api_reads_and_changes_entries() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), running(2, "current", 10)],
+    );
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let mut server = binary(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["serve", "--port", &port.to_string()])
+        .stdout(process::Stdio::null())
+        .spawn()
+        .unwrap();
+    for _ in 0..100 {
+        if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let request = |host: &str, head: &str| {
+        http(
+            port,
+            &format!("{}\r\nHost: {}\r\nConnection: close\r\n\r\n", head, host),
+        )
+    };
+    let local = format!("127.0.0.1:{}", port);
+    let running = request(&local, "GET /running HTTP/1.1");
+    let forged = request(
+        &local,
+        "POST /running/pause HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 0",
+    );
+    let paused = request(
+        &format!("localhost:{}", port),
+        "POST /entries/2/pause HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 0",
+    );
+    let missing = request(&local, "GET /entries/9 HTTP/1.1");
+    let page = request(&local, "GET / HTTP/1.1");
+    // A site whose name resolves to 127.0.0.1 sends its own name
+    let rebound = request(&format!("evil.example:{}", port), "GET /entries HTTP/1.1");
+    let other_port = request("localhost:1", "GET / HTTP/1.1");
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    assert!(running.starts_with("HTTP/1.1 200"));
+    assert!(running.contains("\"title\":\"current\""));
+    assert!(forged.starts_with("HTTP/1.1 415"));
+    assert!(paused.starts_with("HTTP/1.1 200"));
+    assert!(missing.starts_with("HTTP/1.1 404"));
+    assert!(missing.contains("Log entry 9 not found"));
+    assert!(page.contains("Content-Type: text/html"));
+    assert!(page.contains("<title>ITracker</title>"));
+    assert!(rebound.starts_with("HTTP/1.1 403"));
+    assert!(!rebound.contains("\"title\""));
+    assert!(other_port.starts_with("HTTP/1.1 403"));
+    assert!(entries(&log)[1].paused_since().is_some());
+}
+
+#[test]
+fn clients_report_to_the_team_server_which_sums_up_the_team() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let configure = |home: &Path, settings: String| {
+        let config = home.join("config/itracker");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(config.join("config.toml"), settings).unwrap();
+    };
+    let server_home = TempDir::new().unwrap();
+    configure(
+        server_home.path(),
+        format!(
+            "[team]\nlisten = \"127.0.0.1:{}\"\n[team.users]\nalice = \"alice-token-0123456789\"\nbob = \"bob-token-0123456789\"\n",
+            port
+        ),
+    );
+    let client = |user: &str, token: &str, rows: &[String]| {
+        let home = TempDir::new().unwrap();
+        log_file(home.path(), "time.csv", rows);
+        configure(
+            home.path(),
+            format!(
+                "output_file = \"time.csv\"\n[team]\nserver = \"http://127.0.0.1:{}\"\nuser = \"{}\"\ntoken = \"{}\"\n",
+                port, user, token
+            ),
+        );
+        home
+    };
+    let alice = client(
+        "alice",
+        "alice-token-0123456789",
+        &[stopped(1, "Review"), stopped(2, "Deploy")],
+    );
+    let bob = client("bob", "bob-token-0123456789", &[stopped(1, "Support")]);
+    let impostor = client("alice", "bob-token-0123456789", &[]);
+
+    let mut server = binary(server_home.path())
+        .arg("--server")
+        .stdout(process::Stdio::null())
+        .spawn()
+        .unwrap();
+    for _ in 0..100 {
+        if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let pushed = itracker(alice.path()).args(["team", "push"]).assert();
+    let synced = itracker(bob.path()).arg("sync").assert();
+    let refused = itracker(impostor.path()).args(["team", "push"]).assert();
+    let yesterday = (chrono::Utc::now() - chrono::Duration::days(1)).date_naive();
+    let report = itracker(bob.path())
+        .args(["team", "report", "--from", &yesterday.to_string()])
+        .assert();
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    pushed
+        .success()
+        .stdout(predicate::str::contains("Reported 2 entries"));
+    synced
+        .success()
+        .stdout(predicate::str::contains("Reported 1 entries"));
+    refused.failure().stderr(predicate::str::contains(
+        "bob cannot replace the entries of alice",
+    ));
+    report
+        .success()
+        .stdout(predicate::str::is_match(r"alice\s+2h\s+2 entries").unwrap())
+        .stdout(predicate::str::is_match(r"bob\s+1h\s+1 entries").unwrap())
+        .stdout(predicate::str::is_match(r"\nTotal\s+3h").unwrap());
+    assert_eq!(
+        entries(&server_home.path().join("data/itracker/team/alice.csv")).len(),
+        2
+    );
+}
+
+#[test]
+fn status_follows_the_running_timer_through_its_cache() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[running(1, "Review", 65)]);
+    let status = |format: &str| {
+        let output = itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["status", "--format", format])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(status("text"), "▶ Review 1h 5m\n");
+    assert!(schema::active_path(&log).exists());
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("--pause")
+        .assert()
+        .success();
+    let waybar: serde_json::Value = serde_json::from_str(&status("waybar")).unwrap();
+    assert_eq!(waybar["class"], "paused");
+    assert_eq!(waybar["text"], "⏸ Review 1h 5m");
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "1"])
+        .assert()
+        .success();
+    assert_eq!(status("text"), "\n");
+    assert_eq!(status("i3blocks"), "\n");
+}
+
+#[test]
+fn status_and_report_show_the_progress_toward_the_daily_goal() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "daily_goal_hours = 1\n").unwrap();
+    let log = log_file(home.path(), "time.csv", &[running(1, "Review", 36)]);
+    let status = || {
+        let output = itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .arg("status")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(status(), "▶ Review 36m ██████░░░░ 60%\n");
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["report", "--today"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("36m  ██████░░░░ 60% of 1h"));
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "1"])
+        .assert()
+        .success();
+    // The goal stays in the bar while no timer runs
+    assert_eq!(status(), "██████░░░░ 60%\n");
+}
+
+#[test]
+fn exceeding_a_weekly_budget_warns_on_stop_and_shows_in_the_report() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "[budgets]\nacme = 1\n").unwrap();
+    let started = (chrono::Utc::now() - chrono::Duration::minutes(75)).to_rfc2822();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[format!("1,\"{}\",,0,0,Review,acme,,,", started)],
+    );
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Warning: acme tracked 1h 15m this week, 125% of its weekly budget of 1h.",
+        ));
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("report")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Budgets, week of"))
+        .stdout(predicate::str::is_match(r"acme\s+1h 15m  ██████████ 125% of 1h").unwrap());
+}
+
+#[test]
+fn invoices_group_rounded_entries_into_line_items() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "rate = 80\n[rounding]\nmode = \"up\"\nincrement = 15\n",
+    )
+    .unwrap();
+    // 50 and 20 minutes of Review billed as 1h and 30m, 45 minutes of Docs
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,3000,0,Review,acme,,,".to_string(),
+            "2,\"Mon, 3 Jun 2024 10:00:00 +0000\",,2700,0,Docs,acme,,,".to_string(),
+            "3,\"Tue, 4 Jun 2024 09:00:00 +0000\",,1200,0,review,acme,,,".to_string(),
+            "4,\"Tue, 4 Jun 2024 11:00:00 +0000\",,3600,0,Mail,,,,".to_string(),
+        ],
+    );
+    let invoice = |args: &[&str]| {
+        let output = itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["invoice", "--from", "2024-06-01", "--client", "acme"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let by_task = invoice(&[]);
+    assert!(by_task.contains("Invoice 2024-06-01 to 2024-06-30, acme\n"));
+    assert!(by_task.contains("acme     Docs        0.75     80.00       60.00\n"));
+    assert!(by_task.contains("acme     Review      1.50     80.00      120.00\n"));
+    assert!(!by_task.contains("Mail"));
+    assert!(by_task.ends_with("Total                2.25                180.00\n"));
+
+    let daily = invoice(&["--detail", "daily"]);
+    assert!(daily.contains("2024-06-03  acme     Review, Docs      1.75     80.00      140.00\n"));
+    assert!(daily.contains("2024-06-04  acme     review            0.50     80.00       40.00\n"));
+
+    let entries = invoice(&["--detail", "entries"]);
+    let lines = entries.lines().filter(|line| line.starts_with("2024-06-"));
+    assert_eq!(lines.count(), 3);
+}
+
+#[test]
+fn closing_a_month_needs_projects_on_billable_entries_and_archives_its_invoice() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,3600,0,Review,acme,,,".to_string(),
+            "2,\"Mon, 3 Jun 2024 11:00:00 +0000\",,1800,0,Mail,,,,".to_string(),
+        ],
+    );
+    let close = || {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["close-month", "2024-06"])
+            .assert()
+    };
+
+    // With a rate for every project, the entry without one cannot be billed
+    fs::write(config.join("config.toml"), "rate = 80\n").unwrap();
+    close().failure().stderr(predicate::str::contains(
+        "Entry 2 is billable but has no project",
+    ));
+    assert!(!home.path().join("archive").exists());
+
+    fs::write(config.join("config.toml"), "[clients.acme]\nrate = 100\n").unwrap();
+    close()
+        .success()
+        .stdout(predicate::str::contains("Invoice written to"));
+    let invoice = fs::read_to_string(home.path().join("archive/2024-06/invoice.txt")).unwrap();
+    assert!(invoice.contains("Invoice 2024-06-01 to 2024-06-30\n"));
+    assert!(invoice.contains("acme     Review      1.00    100.00      100.00\n"));
+    assert!(!invoice.contains("Mail"));
+    assert!(home.path().join("archive/2024-06/report.txt").exists());
+}
+
+#[test]
+fn clients_are_invoiced_in_their_currency_and_reports_convert_amounts() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "rate = 80\ncurrency = \"EUR\"\n\
+         [clients.acme]\nrate = 100\ncurrency = \"USD\"\n\
+         [currencies]\nreporting = \"EUR\"\nrates = { USD = 0.9 }\n",
+    )
+    .unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,5400,0,Review,acme,,,".to_string(),
+            "2,\"Tue, 4 Jun 2024 09:00:00 +0000\",,3600,0,Mail,beta,,,".to_string(),
+        ],
+    );
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["invoice", "--from", "2024-06-01", "--client", "acme"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "acme     Review      1.50   $100.00     $150.00\n",
+        ));
+    // One invoice cannot mix dollars and euros
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["invoice", "--from", "2024-06-01"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("billed in different currencies"));
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["report", "--from", "2024-06-03"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"acme\s+1h 30m\s+\$150\.00 \(€135\.00\)\n").unwrap())
+        .stdout(predicate::str::is_match(r"beta\s+1h\s+€80\.00\n").unwrap())
+        .stdout(predicate::str::is_match(r"Billable\s+€215\.00\n").unwrap());
+}
+
+#[test]
+fn invoices_round_by_the_rounding_profile_of_their_client() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "rate = 60\n\
+         [rounding.profiles.quarter]\nmode = \"up\"\nincrement = 15\n\
+         [clients.acme]\nrounding = \"quarter\"\n",
+    )
+    .unwrap();
+    // 50 minutes each, billed as 1h for acme and exactly for beta
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,3000,0,Review,acme,,,".to_string(),
+            "2,\"Mon, 3 Jun 2024 10:00:00 +0000\",,3000,0,Mail,beta,,,".to_string(),
+        ],
+    );
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["invoice", "--from", "2024-06-01", "--client", "acme"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Review      1.00     60.00       60.00\n",
+        ));
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["invoice", "--from", "2024-06-01", "--client", "beta"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Mail      0.83     60.00       50.00\n",
+        ));
+    // Reports keep the exact time and bill the rounded one
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["report", "--from", "2024-06-03"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"acme\s+50m\s+60\.00\n").unwrap());
+
+    fs::write(
+        config.join("config.toml"),
+        "[clients.acme]\nrounding = \"hourly\"\n",
+    )
+    .unwrap();
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["invoice", "--client", "acme"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Unknown rounding profile 'hourly'",
+        ));
+}
+
+#[test]
+fn issued_invoices_lock_their_entries_until_forced() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(config.join("config.toml"), "rate = 60\n").unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,3600,0,Review,acme,,,".to_string(),
+            "2,\"Mon, 3 Jun 2024 10:00:00 +0000\",,1800,0,Mail,acme,,,".to_string(),
+        ],
+    );
+    let invoice = |number: &str| {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["invoice", "--from", "2024-06-01", "--number", number])
+            .assert()
+    };
+
+    invoice("2024-001")
+        .success()
+        .stdout(predicate::str::contains(
+            "Invoice 2024-001, 2024-06-01 to 2024-06-30\n",
+        ))
+        .stderr(predicate::str::contains(
+            "Marked 2 entries as invoiced as 2024-001.",
+        ));
+    assert!(entries(&log)
+        .iter()
+        .all(|entry| entry.metadata["invoice"] == "2024-001"));
+    // Billed entries are not billed twice
+    invoice("2024-002")
+        .failure()
+        .stderr(predicate::str::contains("Nothing to invoice in this range"));
+
+    itracker(home.path())
+        .args(["--yes", "--log"])
+        .arg(&log)
+        .args(["--delete-log", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Log entry 2 was invoiced as 2024-001, pass --force to change it anyway",
+        ));
+    itracker(home.path())
+        .args(["--yes", "--force", "--log"])
+        .arg(&log)
+        .args(["--delete-log", "2"])
+        .assert()
+        .success();
+    assert_eq!(entries(&log).len(), 1);
+}
+
+#[test]
+fn invoices_charge_the_tax_of_their_client_and_print_tax_ids() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "rate = 80\ncurrency = \"EUR\"\ntax = \"de\"\ntax_id = \"DE123456789\"\n\
+         [taxes.de]\nrate = 19\nlabel = \"VAT\"\n\
+         [taxes.reverse]\nrate = 0\nlabel = \"Reverse charge\"\n\
+         [clients.acme]\ntax = \"reverse\"\ntax_id = \"ATU12345678\"\n",
+    )
+    .unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,3600,0,Review,acme,,,".to_string(),
+            "2,\"Mon, 3 Jun 2024 10:00:00 +0000\",,5400,0,Mail,beta,,,".to_string(),
+        ],
+    );
+    let invoice = |client: &str| {
+        let output = itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["invoice", "--from", "2024-06-01", "--client", client])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let beta = invoice("beta");
+    assert!(beta.contains("Tax ID: DE123456789\n"));
+    assert!(!beta.contains("Client tax ID"));
+    let lines = [
+        r"Net\s+1\.50\s+€120\.00",
+        r"VAT 19%\s+€22\.80",
+        r"Gross\s+€142\.80\n$",
+    ];
+    for line in lines {
+        assert!(
+            predicate::str::is_match(line).unwrap().eval(&beta),
+            "{}",
+            beta
+        );
+    }
+
+    let acme = invoice("acme");
+    assert!(acme.contains("Client tax ID: ATU12345678\n"));
+    assert!(acme.contains("Reverse charge 0%"));
+    assert!(acme.ends_with("€80.00\n"));
+}
+
+#[test]
+fn retainers_burn_down_in_client_reports_and_warn_from_80_percent() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "[clients.acme]\nretainer = 2\n[clients.beta]\nretainer = 0.2\n",
+    )
+    .unwrap();
+    let started = (chrono::Utc::now() - chrono::Duration::minutes(10)).to_rfc2822();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,3600,0,Review,acme,,,".to_string(),
+            "2,\"Tue, 4 Jun 2024 09:00:00 +0000\",,2400,0,Fix,acme,,,".to_string(),
+            "3,\"Tue, 4 Jun 2024 11:00:00 +0000\",,1800,0,Mail,other,,,".to_string(),
+            format!("4,\"{}\",,0,0,Review,beta,,,", started),
+        ],
+    );
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["report", "--from", "2024-06-03", "--client", "acme"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Retainer acme, June 2024: 2h\n"))
+        .stdout(predicate::str::is_match(r"2024-06-03\s+1h\s+1h left  █████░░░░░ 50%\n").unwrap())
+        .stdout(
+            predicate::str::is_match(r"2024-06-04\s+1h 40m\s+20m left  ████████░░ 83%\n").unwrap(),
+        )
+        .stdout(predicate::str::contains(
+            "Warning: the retainer of acme for June 2024 is nearly used up, 1h 40m of 2h (83%).",
+        ))
+        .stdout(predicate::str::contains("other").not());
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "4"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("the retainer of beta"))
+        .stderr(predicate::str::contains(
+            "is nearly used up, 10m of 12m (83%).",
+        ));
+}
+
+#[test]
+fn expenses_are_invoiced_with_their_client_and_counted_in_reports() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "rate = 80\ncurrency = \"EUR\"\n",
+    )
+    .unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &["1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,3600,0,Review,acme,,,".to_string()],
+    );
+    let expense = |amount: &str, description: &str, client: &str| {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["expense", amount, description, "--client", client])
+            .args(["--date", "2024-06-04"])
+            .assert()
+            .success()
+    };
+    expense("42.5", "train ticket", "acme").stdout(predicate::str::contains(
+        "Recorded expense 1: €42.50 for train ticket on 2024-06-04.",
+    ));
+    expense("12", "parking", "beta");
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["report", "--from", "2024-06-03"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"acme\s+1h\s+€122\.50 incl\. €42\.50 expenses\n").unwrap(),
+        )
+        .stdout(predicate::str::is_match(r"beta\s+0s\s+€12\.00 incl\. €12\.00 expenses\n").unwrap())
+        .stdout(predicate::str::is_match(r"Billable\s+€134\.50\n").unwrap());
+
+    let invoice = || {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["invoice", "--from", "2024-06-01", "--client", "acme"])
+            .args(["--number", "2024-7"])
+            .assert()
+    };
+    invoice()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"Expenses\n2024-06-04  acme  train ticket\s+€42\.50\n")
+                .unwrap(),
+        )
+        .stdout(predicate::str::is_match(r"Total\s+1\.00\s+€122\.50\n$").unwrap())
+        .stderr(predicate::str::contains(
+            "Marked 1 expenses as invoiced as 2024-7.",
+        ));
+    // The expense of beta stays open, the one of acme is billed
+    let expenses = fs::read_to_string(home.path().join("time.csv.expenses")).unwrap();
+    assert!(
+        expenses.contains("train ticket,acme,2024-7\n"),
+        "{}",
+        expenses
+    );
+    assert!(expenses.contains("parking,beta,\n"), "{}", expenses);
+    invoice()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to invoice"));
+}
+
+#[test]
+fn entries_export_to_the_csv_of_harvests_import() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "[clients.acme]\nname = \"ACME Corporation\"\n",
+    )
+    .unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 10:00:00 +0000\",\"Spec, part 2\",5400,0,Review,acme,,,"
+                .to_string(),
+            "2,\"Mon, 3 Jun 2024 09:00:00 +0000\",Answer mail,900,0,,beta,,,".to_string(),
+            "3,\"Tue, 2 Jul 2024 09:00:00 +0000\",,3600,0,Later,acme,,,".to_string(),
+            running(4, "Running", 10),
+        ],
+    );
+    let export = home.path().join("june.csv");
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["export", "harvest", "--to", "2024-06-30", "--output"])
+        .arg(&export)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exported 2 entries to"));
+    assert_eq!(
+        fs::read_to_string(&export).unwrap(),
+        "Date,Client,Project,Task,Notes,Hours\n\
+         2024-06-03,beta,beta,Answer mail,,0.25\n\
+         2024-06-03,ACME Corporation,acme,Review,\"Spec, part 2\",1.50\n"
+    );
+}
+
+#[test]
+fn tmux_prints_a_colored_segment_with_escaped_title() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[running(1, "Fix #12", 65)]);
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("tmux")
+        .assert()
+        .success()
+        .stdout("#[fg=#98c379]▶#[default] Fix ##12 1:05\n");
+}
+
+#[test]
+fn configured_icons_show_in_status_prompt_tmux_and_list() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        "[icons]\npreset = \"ascii\"\npaused = \"||\"\n",
+    )
+    .unwrap();
+    let paused = format!(
+        "{}\"{}\"",
+        running(3, "paused", 30),
+        chrono::Utc::now().to_rfc2822()
+    );
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), running(2, "current", 42), paused],
+    );
+    let run = |args: &[&str]| {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(args)
+            .assert()
+            .success()
+    };
+
+    run(&["status"]).stdout("|| paused 30m\n");
+    run(&["tmux"]).stdout("#[fg=#e5c07b]||#[default] paused 0:30\n");
+    run(&["list", "--template", "{{icon}} {{title}}"])
+        .stdout(predicate::str::contains("\n- old\n> current\n|| paused\n"));
+    run(&["--stop", "3"]);
+    run(&["prompt"]).stdout("* 0:42 current\n");
+
+    fs::write(config.join("config.toml"), "[icons]\npreset = \"runes\"\n").unwrap();
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("status")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown icons preset 'runes'"));
+}
+
+#[test]
+fn config_set_fixes_one_setting_at_a_time_in_an_invalid_file() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    let file = config.join("config.toml");
+    fs::write(&file, "[icons]\npreset = \"runes\"\n").unwrap();
+    let set = |key: &str, value: &str| {
+        itracker(home.path())
+            .args(["config", "set", key, value])
+            .assert()
+    };
+
+    set("language", "de").success();
+    set("language", "tlh")
+        .failure()
+        .stderr(predicate::str::contains("No translation for 'tlh'"));
+    set("icons.preset", "ascii").success();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "language = \"de\"\n\n[icons]\npreset = \"ascii\"\n"
+    );
+    // Invalid values are refused all the same
+    set("icons.preset", "runes")
+        .failure()
+        .stderr(predicate::str::contains("Unknown icons preset 'runes'"));
+
+    fs::write(&file, "rate = [\n").unwrap();
+    set("language", "de")
+        .failure()
+        .stderr(predicate::str::contains("Invalid config file"));
+}
+
+#[test]
+fn prompt_escapes_the_title_for_the_shell() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[running(1, "50% of $HOME", 42)]);
+    let prompt = |shell: &str| {
+        let output = itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["prompt", "--shell", shell])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(prompt("plain"), "⏱ 0:42 50% of $HOME\n");
+    assert_eq!(prompt("bash"), "⏱ 0:42 50% of \\\\$HOME\n");
+    assert_eq!(prompt("zsh"), "⏱ 0:42 50%% of $HOME\n");
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "1"])
+        .assert()
+        .success();
+    assert_eq!(prompt("plain"), "");
+}
+
+#[test]
+fn sync_merges_entries_tracked_on_two_machines() {
+    let root = TempDir::new().unwrap();
+    let remote = root.path().join("remote.git");
+    let status = process::Command::new("git")
+        .args(["init", "-q", "--bare"])
+        .arg(&remote)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let machines = [root.path().join("laptop"), root.path().join("desktop")];
+    let mut logs = Vec::new();
+    for (home, title) in machines.iter().zip(["on laptop", "on desktop"]) {
+        let config = home.join("config/itracker");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(
+            config.join("config.toml"),
+            format!("[sync]\nremote = {:?}\n", remote.to_str().unwrap()),
+        )
+        .unwrap();
+        let data = home.join("data/itracker");
+        fs::create_dir_all(&data).unwrap();
+        logs.push(log_file(
+            &data,
+            "default_output.txt",
+            &[stopped(1, "shared"), stopped(2, title)],
+        ));
+    }
+
+    itracker(&machines[0]).arg("sync").assert().success();
+    itracker(&machines[1])
+        .arg("sync")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Log entry 2 of default_output.txt is now 3.",
+        ));
+    itracker(&machines[0]).arg("sync").assert().success();
+
+    for log in &logs {
+        let titles: Vec<String> = entries(log).into_iter().map(|e| e.title).collect();
+        assert_eq!(titles, ["shared", "on laptop", "on desktop"]);
+    }
+}
+
+/// A WebDAV server holding one file, answering conditional GETs and PUTs
+/// like Nextcloud does; returns its URL, the file and the number of uploads.
+fn webdav_server(contents: String) -> (String, Arc<Mutex<(String, usize)>>) {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/time.csv", server.server_addr());
+    let file = Arc::new(Mutex::new((contents, 0)));
+    let shared = file.clone();
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let header = |name: &str| {
+                request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+                    .map(|h| h.value.to_string())
+            };
+            let (if_match, if_none_match) = (header("If-Match"), header("If-None-Match"));
+            let mut file = shared.lock().unwrap();
+            let etag = format!("\"v{}\"", file.1);
+            let response = match request.method() {
+                tiny_http::Method::Get if if_none_match.as_ref() == Some(&etag) => {
+                    tiny_http::Response::from_string("").with_status_code(304)
+                }
+                tiny_http::Method::Get => tiny_http::Response::from_string(file.0.clone()),
+                tiny_http::Method::Put if if_match.is_some_and(|tag| tag != etag) => {
+                    tiny_http::Response::from_string("").with_status_code(412)
+                }
+                _ => {
+                    let mut body = String::new();
+                    request.as_reader().read_to_string(&mut body).unwrap();
+                    *file = (body, file.1 + 1);
+                    tiny_http::Response::from_string("").with_status_code(201)
+                }
+            };
+            let etag = format!("ETag: \"v{}\"", file.1);
+            drop(file);
+            let response = response.with_header(etag.parse::<tiny_http::Header>().unwrap());
+            request.respond(response).unwrap();
+        }
+    });
+    (url, file)
+}
+
+#[test]
+fn sync_merges_the_output_file_with_its_copy_on_a_webdav_server() {
+    let home = TempDir::new().unwrap();
+    let on_server = log_file(
+        home.path(),
+        "server.csv",
+        &[stopped(1, "shared"), stopped(2, "on server")],
+    );
+    let (url, server) = webdav_server(fs::read_to_string(on_server).unwrap());
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "shared"), stopped(2, "here")],
+    );
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!(
+            "output_file = {:?}\n[webdav]\nurl = {:?}\n",
+            log.to_str().unwrap(),
+            url
+        ),
+    )
+    .unwrap();
+
+    itracker(home.path())
+        .arg("sync")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is now 3.").and(predicate::str::contains("merged with")));
+    let titles: Vec<String> = entries(&log).into_iter().map(|e| e.title).collect();
+    assert_eq!(titles, ["shared", "on server", "here"]);
+    let (contents, uploads) = server.lock().unwrap().clone();
+    assert_eq!(contents, fs::read_to_string(&log).unwrap());
+    assert_eq!(uploads, 1);
+
+    itracker(home.path())
+        .arg("sync")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("already in sync with"));
+    assert_eq!(server.lock().unwrap().1, 1);
+}
+
+/// Requests the fake Toggl API received, as `METHOD path body`.
+type Requests = Arc<Mutex<Vec<String>>>;
+
+/// A Toggl API whose user's default workspace 7 holds the project `Acme`
+/// with ID 1; created projects and time entries get IDs from 100 on.
+fn toggl_server() -> (String, Requests) {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/api/v9", server.server_addr());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let shared = requests.clone();
+    thread::spawn(move || {
+        for (id, mut request) in (100..).zip(server.incoming_requests()) {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let path = request.url().trim_start_matches("/api/v9").to_string();
+            let answer = match (request.method(), path.as_str()) {
+                (tiny_http::Method::Get, "/me") => r#"{"default_workspace_id":7}"#.to_string(),
+                (tiny_http::Method::Get, "/workspaces/7/projects") => {
+                    r#"[{"id":1,"name":"Acme"}]"#.to_string()
+                }
+                _ => format!(r#"{{"id":{},"name":"created"}}"#, id),
+            };
+            shared
+                .lock()
+                .unwrap()
+                .push(format!("{} {} {}", request.method(), path, body));
+            request
+                .respond(tiny_http::Response::from_string(answer))
+                .unwrap();
+        }
+    });
+    (url, requests)
+}
+
+#[test]
+fn sync_toggl_pushes_new_entries_once_and_maps_projects_by_name() {
+    let home = TempDir::new().unwrap();
+    let (url, requests) = toggl_server();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!("[toggl]\ntoken = \"secret\"\napi_url = {:?}\n", url),
+    )
+    .unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0200\",,3600,0,Review,acme,\"urgent,ops\",,".to_string(),
+            "2,\"Mon, 3 Jun 2024 11:00:00 +0200\",,1800,0,Plan,Beta,,,".to_string(),
+            running(3, "Running", 5),
+        ],
+    );
+    let sync = || {
+        itracker(home.path())
+            .arg("-o")
+            .arg(&log)
+            .args(["sync", "toggl"])
+            .assert()
+            .success()
+    };
+
+    sync().stdout(predicate::str::contains("Pushed 2 entries to"));
+    let sent = requests.lock().unwrap().clone();
+    assert_eq!(sent.len(), 5, "{:#?}", sent);
+    assert!(sent[0].starts_with("GET /me"));
+    assert!(sent[2].contains("\"start\":\"2024-06-03T07:00:00Z\""));
+    assert!(sent[2].contains("\"duration\":3600"));
+    assert!(sent[2].contains("\"project_id\":1"));
+    assert!(sent[2].contains("\"tags\":[\"urgent\",\"ops\"]"));
+    assert!(sent[3].starts_with("POST /workspaces/7/projects {\"active\":true,\"name\":\"Beta\"}"));
+    assert!(sent[4].contains("\"project_id\":103"));
+    let ids: Vec<Option<String>> = entries(&log)
+        .into_iter()
+        .map(|entry| entry.metadata.get("toggl").cloned())
+        .collect();
+    assert_eq!(
+        ids,
+        [Some("102".to_string()), Some("104".to_string()), None]
+    );
+
+    // Pushed entries are not sent again
+    sync().stdout(predicate::str::contains("Pushed 0 entries to"));
+    assert_eq!(requests.lock().unwrap().len(), 5);
+}
+
+/// A Clockify API whose workspace `Agency` (ID `ws1`) holds the project
+/// `Acme` and the tag `ops`; created items get IDs `c1`, `c2`, ... The
+/// first time entry is turned down once for the rate limit.
+fn clockify_server() -> (String, Requests) {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/api/v1", server.server_addr());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let shared = requests.clone();
+    thread::spawn(move || {
+        let (mut created, mut limited) = (0, false);
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let path = request.url().trim_start_matches("/api/v1").to_string();
+            shared
+                .lock()
+                .unwrap()
+                .push(format!("{} {} {}", request.method(), path, body));
+            let answer = match (request.method(), path.as_str()) {
+                (tiny_http::Method::Get, "/workspaces") => {
+                    r#"[{"id":"ws0","name":"Personal"},{"id":"ws1","name":"Agency"}]"#.to_string()
+                }
+                (tiny_http::Method::Get, "/workspaces/ws1/projects?page-size=5000") => {
+                    r#"[{"id":"p1","name":"Acme"}]"#.to_string()
+                }
+                (tiny_http::Method::Get, "/workspaces/ws1/tags?page-size=5000") => {
+                    r#"[{"id":"t1","name":"ops"}]"#.to_string()
+                }
+                (_, "/workspaces/ws1/time-entries") if !limited => {
+                    limited = true;
+                    let retry = "Retry-After: 0".parse::<tiny_http::Header>().unwrap();
+                    let response = tiny_http::Response::from_string("")
+                        .with_status_code(429)
+                        .with_header(retry);
+                    request.respond(response).unwrap();
+                    continue;
+                }
+                _ => {
+                    created += 1;
+                    format!(r#"{{"id":"c{}","name":"created"}}"#, created)
+                }
+            };
+            request
+                .respond(tiny_http::Response::from_string(answer))
+                .unwrap();
+        }
+    });
+    (url, requests)
+}
+
+#[test]
+fn sync_clockify_resolves_names_to_ids_and_retries_when_rate_limited() {
+    let home = TempDir::new().unwrap();
+    let (url, requests) = clockify_server();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!(
+            "[clockify]\napi_key = \"secret\"\nworkspace = \"agency\"\napi_url = {:?}\n",
+            url
+        ),
+    )
+    .unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            "1,\"Mon, 3 Jun 2024 09:00:00 +0000\",,3600,600,Review,acme,\"ops,urgent\",,"
+                .to_string(),
+        ],
+    );
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["sync", "clockify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pushed 1 entries to"));
+    let sent = requests.lock().unwrap().clone();
+    let posted: Vec<&String> = sent.iter().filter(|r| r.starts_with("POST")).collect();
+    assert_eq!(posted.len(), 3, "{:#?}", sent);
+    assert!(posted[0].starts_with("POST /workspaces/ws1/tags {\"name\":\"urgent\"}"));
+    // Turned down once, then sent again
+    assert_eq!(posted[1], posted[2]);
+    let entry = posted[2];
+    assert!(entry.contains("\"start\":\"2024-06-03T09:00:00Z\""));
+    assert!(entry.contains("\"end\":\"2024-06-03T09:50:00Z\""));
+    assert!(entry.contains("\"projectId\":\"p1\""));
+    assert!(entry.contains("\"tagIds\":[\"t1\",\"c1\"]"));
+    assert_eq!(
+        entries(&log)[0]
+            .metadata
+            .get("clockify")
+            .map(String::as_str),
+        Some("c2")
+    );
+}
+
+/// Objects of the fake S3 bucket by key.
+type Objects = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+/// An S3 bucket `bucket` that stores objects in memory and answers signed
+/// requests; returns its endpoint and the objects.
+fn s3_server() -> (String, Objects) {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", server.server_addr());
+    let objects = Arc::new(Mutex::new(BTreeMap::new()));
+    let shared = objects.clone();
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let signed = request.headers().iter().any(|h| {
+                h.field.equiv("Authorization")
+                    && h.value
+                        .as_str()
+                        .starts_with("AWS4-HMAC-SHA256 Credential=key/")
+            });
+            let url = request.url().to_string();
+            let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+            let key = path
+                .strip_prefix("/bucket/")
+                .unwrap_or_default()
+                .to_string();
+            let mut objects = shared.lock().unwrap();
+            let response = match request.method() {
+                _ if !signed => tiny_http::Response::from_string("").with_status_code(403),
+                tiny_http::Method::Put => {
+                    let mut body = Vec::new();
+                    request.as_reader().read_to_end(&mut body).unwrap();
+                    objects.insert(key, body);
+                    tiny_http::Response::from_string("")
+                }
+                tiny_http::Method::Get if query.contains("list-type=2") => {
+                    let listed: String = objects
+                        .iter()
+                        .map(|(key, body)| {
+                            format!(
+                                "<Contents><Key>{}</Key><Size>{}</Size></Contents>",
+                                key,
+                                body.len()
+                            )
+                        })
+                        .collect();
+                    tiny_http::Response::from_string(format!(
+                        "<ListBucketResult>{}</ListBucketResult>",
+                        listed
+                    ))
+                }
+                tiny_http::Method::Get => match objects.get(&key) {
+                    Some(body) => tiny_http::Response::from_data(body.clone()),
+                    None => tiny_http::Response::from_string("").with_status_code(404),
+                },
+                _ => tiny_http::Response::from_string("").with_status_code(405),
+            };
+            drop(objects);
+            request.respond(response).unwrap();
+        }
+    });
+    (endpoint, objects)
+}
+
+#[test]
+fn backups_are_encrypted_uploaded_to_s3_and_restored() {
+    use age::secrecy::ExposeSecret;
+
+    let home = TempDir::new().unwrap();
+    let (endpoint, objects) = s3_server();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "Review"), stopped(2, "Deploy")],
+    );
+    let original = fs::read_to_string(&log).unwrap();
+    let key_file = home.path().join("key.txt");
+    let identity = age::x25519::Identity::generate();
+    fs::write(&key_file, identity.to_string().expose_secret()).unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!(
+            "output_file = {:?}\n[backup.remote]\nendpoint = {:?}\nbucket = \"bucket\"\nprefix = \"laptop/\"\naccess_key = \"key\"\nsecret_key = \"secret\"\nkey_file = {:?}\n",
+            log.to_str().unwrap(),
+            endpoint,
+            key_file.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+
+    itracker(home.path())
+        .args(["backup", "now"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Uploaded itracker-"));
+    let (name, snapshot) = objects.lock().unwrap().pop_first().unwrap();
+    assert!(name.starts_with("laptop/itracker-") && name.ends_with(".tar.gz.age"));
+    assert!(snapshot.starts_with(b"age-encryption.org/v1"));
+    assert!(!String::from_utf8_lossy(&snapshot).contains("Review"));
+    objects.lock().unwrap().insert(name.clone(), snapshot);
+
+    itracker(home.path())
+        .args(["backup", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(name.trim_start_matches("laptop/")));
+
+    log_file(home.path(), "time.csv", &[stopped(1, "Review")]);
+    itracker(home.path())
+        .args(["backup", "restore"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--yes"));
+    itracker(home.path())
+        .args(["--yes", "backup", "restore"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored 1 files"));
+    assert_eq!(fs::read_to_string(&log).unwrap(), original);
+}
+
+#[test]
+fn devices_sharing_a_folder_merge_concurrent_edits_the_same_way() {
+    let shared = TempDir::new().unwrap();
+    let devices = [TempDir::new().unwrap(), TempDir::new().unwrap()];
+    for device in &devices {
+        let config = device.path().join("config/itracker");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(
+            config.join("config.toml"),
+            format!(
+                "output_file = \"time.csv\"\n[crdt]\ndir = {:?}\n",
+                shared.path().to_str().unwrap()
+            ),
+        )
+        .unwrap();
+    }
+    let [laptop, desktop] = &devices;
+    let sync = |device: &TempDir| itracker(device.path()).arg("sync").assert().success();
+    let edit = |device: &TempDir, change: &dyn Fn(&mut Vec<schema::Record>)| {
+        let log = device.path().join("time.csv");
+        let mut records = schema::read(&log).unwrap();
+        change(&mut records);
+        schema::write(&log, &records).unwrap();
+    };
+
+    log_file(
+        laptop.path(),
+        "time.csv",
+        &[stopped(1, "Review"), stopped(2, "Deploy")],
+    );
+    sync(laptop);
+    sync(desktop).stdout(predicate::str::contains(
+        "Entries of time.csv added on other devices: 1, 2.",
+    ));
+
+    // Different fields of one entry, a new entry and a delete, all at once
+    edit(laptop, &|records| {
+        records[0].title = "Code review".to_string();
+        records.retain(|record| record.index != 2);
+    });
+    edit(desktop, &|records| {
+        records[0].project = "acme".to_string();
+        let mut added = records[1].clone();
+        added.index = 3;
+        added.title = "Standup".to_string();
+        records.push(added);
+    });
+    // The same field on both: one of them wins, but the same everywhere
+    edit(laptop, &|records| records[0].tags = "laptop".to_string());
+    edit(desktop, &|records| records[0].tags = "desktop".to_string());
+    sync(laptop);
+    sync(desktop);
+    sync(laptop);
+
+    let titles = |device: &TempDir| -> Vec<(String, String, String)> {
+        schema::read(device.path().join("time.csv"))
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.title, record.project, record.tags))
+            .collect()
+    };
+    let merged = titles(laptop);
+    assert_eq!(merged, titles(desktop));
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0].0, "Code review");
+    assert_eq!(merged[0].1, "acme");
+    assert!(merged[0].2 == "laptop" || merged[0].2 == "desktop");
+    assert_eq!(merged[1].0, "Standup");
+    assert_eq!(fs::read_dir(shared.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn dump_and_load_move_the_data_and_config_to_another_home() {
+    let old = TempDir::new().unwrap();
+    let log = log_file(
+        old.path(),
+        "time.csv",
+        &[stopped(1, "Review"), stopped(2, "Deploy")],
+    );
+    let data = old.path().join("data/itracker");
+    fs::create_dir_all(data.join(".git")).unwrap();
+    fs::write(data.join("side.csv"), "from the data directory\n").unwrap();
+    fs::write(data.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    let config = old.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    let settings = "output_file = \"time.csv\"\nweek_start = \"sunday\"\n";
+    fs::write(config.join("config.toml"), settings).unwrap();
+
+    let dump = old.path().join("all.tar.gz");
+    itracker(old.path())
+        .arg("dump")
+        .arg(&dump)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote 3 files"));
+
+    let new = TempDir::new().unwrap();
+    itracker(new.path())
+        .arg("load")
+        .arg(&dump)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--yes"));
+    itracker(new.path())
+        .args(["--yes", "load"])
+        .arg(&dump)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Loaded 2 of 2 files"));
+    assert_eq!(
+        fs::read_to_string(new.path().join("config/itracker/config.toml")).unwrap(),
+        settings
+    );
+    assert_eq!(
+        fs::read_to_string(new.path().join("time.csv")).unwrap(),
+        fs::read_to_string(&log).unwrap()
+    );
+    assert_eq!(
+        fs::read_to_string(new.path().join("data/itracker/side.csv")).unwrap(),
+        "from the data directory\n"
+    );
+    assert!(!new.path().join("data/itracker/.git").exists());
+
+    // Loading again only replaces what changed since, backing it up first
+    log_file(new.path(), "time.csv", &[stopped(1, "Review")]);
+    itracker(new.path())
+        .args(["--yes", "load"])
+        .arg(&dump)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Loaded 1 of 2 files"));
+    assert_eq!(
+        fs::read_to_string(new.path().join("time.csv")).unwrap(),
+        fs::read_to_string(&log).unwrap()
+    );
+    assert!(new.path().join("data/itracker/backups").is_dir());
+
+    fs::write(old.path().join("not-a-dump.tar.gz"), "plain text").unwrap();
+    itracker(new.path())
+        .args(["--yes", "load", "not-a-dump.tar.gz"])
+        .current_dir(old.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is damaged"));
+}
+
+#[test]
+fn the_log_file_is_encrypted_at_rest_once_a_key_is_configured() {
+    use age::secrecy::ExposeSecret;
+
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "Acme"), running(2, "Initech audit", 65)],
+    );
+    let key_file = home.path().join("key.txt");
+    let identity = age::x25519::Identity::generate();
+    fs::write(&key_file, identity.to_string().expose_secret()).unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    let settings = format!(
+        "output_file = {:?}\n[encryption]\nkey_file = {:?}\n",
+        log.to_str().unwrap(),
+        key_file.to_str().unwrap()
+    );
+    fs::write(config.join("config.toml"), &settings).unwrap();
+
+    itracker(home.path()).arg("--pause").assert().success();
+    let stored = fs::read(&log).unwrap();
+    assert!(stored.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----"));
+    assert!(!String::from_utf8_lossy(&stored).contains("Initech"));
+    assert!(!schema::journal_path(&log).exists());
+
+    itracker(home.path())
+        .arg("status")
+        .assert()
+        .success()
+        .stdout("⏸ Initech audit 1h 5m\n");
+    assert!(!schema::active_path(&log).exists());
+    itracker(home.path())
+        .args(["--stop", "2"])
+        .assert()
+        .success();
+    let plain = age::decrypt(&identity, &fs::read(&log).unwrap()).unwrap();
+    let records = schema::parse(&log, plain.as_slice()).unwrap();
+    assert_eq!(records[1].title, "Initech audit");
+    assert_ne!(records[1].elapsed_time, "0");
+
+    fs::write(
+        config.join("config.toml"),
+        format!("output_file = {:?}\n", log.to_str().unwrap()),
+    )
+    .unwrap();
+    itracker(home.path())
+        .arg("status")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is encrypted"));
+}
+
+#[test]
+fn merge_unions_two_log_files_and_resolves_conflicts_as_told() {
+    let home = TempDir::new().unwrap();
+    let row = |index: usize, title: &str, time: &str, seconds: u64| {
+        format!(
+            "{},\"Mon, 14 Oct 2024 {} +0000\",,{},0,{},,,,",
+            index, time, seconds, title
+        )
+    };
+    let laptop = log_file(
+        home.path(),
+        "laptop.csv",
+        &[
+            row(1, "Shared", "09:00:00", 3600),
+            row(2, "Laptop only", "11:00:00", 1800),
+            row(3, "Meeting", "14:00:00", 3600),
+        ],
+    );
+    let desktop = log_file(
+        home.path(),
+        "desktop.csv",
+        &[
+            row(1, "Shared", "09:00:00", 3600),
+            row(2, "Desktop only", "12:00:00", 600),
+            row(3, "Meeting", "14:00:00", 5400),
+            row(4, "Call", "11:15:00", 600),
+        ],
+    );
+    let untouched = fs::read_to_string(&desktop).unwrap();
+
+    itracker(home.path())
+        .arg("merge")
+        .args([&laptop, &desktop])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--prefer"));
+    assert_eq!(entries(&laptop).len(), 3);
+
+    itracker(home.path())
+        .arg("merge")
+        .args([&laptop, &desktop])
+        .args(["--prefer", "theirs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "2 added, 1 already there, 2 replaced.",
+        ));
+    let merged: Vec<(usize, String, String)> = entries(&laptop)
+        .into_iter()
+        .map(|e| (e.index, e.title, e.elapsed_time))
+        .collect();
+    assert_eq!(
+        merged,
+        [
+            (1, "Shared".to_string(), "3600".to_string()),
+            (5, "Call".to_string(), "600".to_string()),
+            (4, "Desktop only".to_string(), "600".to_string()),
+            (3, "Meeting".to_string(), "5400".to_string()),
+        ]
+    );
+    assert_eq!(fs::read_to_string(&desktop).unwrap(), untouched);
+}
+
+#[test]
+fn backup_prune_applies_the_retention_policy_to_existing_backups() {
+    let home = TempDir::new().unwrap();
+    let backups = home.path().join("backups");
+    let dir = backups.join("home_me_time.csv");
+    fs::create_dir_all(&dir).unwrap();
+    for day in 1..=5 {
+        fs::write(dir.join(format!("202406{:02}T120000.000Z.csv", day)), "").unwrap();
+    }
+    fs::write(dir.join("by-hand.csv"), "").unwrap();
+    let config = home.path().join("config/itracker");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!(
+            "[backup]\ndir = {:?}\nkeep = 1\nkeep_weekly = 2\n",
+            backups.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+
+    itracker(home.path())
+        .args(["backup", "prune", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Would remove 3 local backups."));
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 6);
+
+    itracker(home.path())
+        .args(["backup", "prune"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 3 local backups."));
+    let mut left: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    left.sort();
+    // 2024-06-02 is the Sunday ending the week before
+    assert_eq!(
+        left,
+        [
+            "20240602T120000.000Z.csv",
+            "20240605T120000.000Z.csv",
+            "by-hand.csv"
+        ]
+    );
+}
+
+#[test]
+fn the_daemon_records_the_focused_window_and_suggests_untracked_time() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[running(1, "current", 10)]);
+    let config = home.path().join("config/itracker/config.toml");
+    fs::create_dir_all(config.parent().unwrap()).unwrap();
+    fs::write(
+        &config,
+        "[activity]\nenabled = true\ninterval_seconds = 1\ncommand = \"printf 'Firefox\\\\nDocs'\"\n",
+    )
+    .unwrap();
+
+    let mut daemon = binary(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("daemon")
+        .stdout(process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let activity = home.path().join("time.csv.activity");
+    for _ in 0..100 {
+        if activity.exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    daemon.kill().unwrap();
+    daemon.wait().unwrap();
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["activity", "--entry", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Firefox  Docs"));
+
+    let untracked = log_file(home.path(), "untracked.csv", &[stopped(1, "old")]);
+    let start = chrono::Utc::now() - chrono::Duration::hours(2);
+    let samples = [
+        (0, 300, "Slack", "General"),
+        (300, 60, "Slack", "Random"),
+        (360, 120, "Mail", "Inbox"),
+    ];
+    let mut contents = "Start,Seconds,App,Title,Entry\n".to_string();
+    for (offset, seconds, app, title) in samples {
+        let time = start + chrono::Duration::seconds(offset);
+        contents.push_str(&format!(
+            "{},{},{},{},\n",
+            time.to_rfc3339(),
+            seconds,
+            app,
+            title
+        ));
+    }
+    fs::write(home.path().join("untracked.csv.activity"), contents).unwrap();
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&untracked)
+        .args(["activity", "--suggest", "--add"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("6m  Slack  General"))
+        .stdout(predicate::str::contains("Mail").not())
+        .stdout(predicate::str::contains("Added 1 entries"));
+    let added = entries(&untracked);
+    assert_eq!(added.len(), 2);
+    assert_eq!(added[1].index, 2);
+    assert_eq!(added[1].title, "Slack");
+    assert_eq!(added[1].elapsed_time, "360");
+}
+
+#[test]
+fn adding_a_task_in_a_git_repository_describes_it_after_the_branch() {
+    let home = TempDir::new().unwrap();
+    let log = home.path().join("time.csv");
+    let repository = home.path().join("app");
+    fs::create_dir_all(repository.join("src")).unwrap();
+    let git = |args: &[&str]| {
+        let status = process::Command::new("git")
+            .arg("-C")
+            .arg(&repository)
+            .args(args)
+            .stdout(process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "--quiet"]);
+    git(&["checkout", "--quiet", "-b", "fix-login"]);
+
+    itracker(home.path())
+        .current_dir(repository.join("src"))
+        .arg("-o")
+        .arg(&log)
+        .arg("--add")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Timer started for log entry at index 1.",
+        ));
+
+    let config = home.path().join("config/itracker/config.toml");
+    fs::create_dir_all(config.parent().unwrap()).unwrap();
+    fs::write(&config, "task_template = \"{branch} ({repo})\"\n").unwrap();
+    itracker(home.path())
+        .current_dir(&repository)
+        .arg("-o")
+        .arg(&log)
+        .arg("--add")
+        .assert()
+        .success();
+
+    let added = entries(&log);
+    assert_eq!(added[0].message, "app:fix-login");
+    assert_eq!(added[1].message, "fix-login (app)");
+    let path = added[0].metadata["repo"].clone();
+    assert_eq!(
+        fs::canonicalize(path).unwrap(),
+        fs::canonicalize(&repository).unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn plugins_export_import_and_sync_through_json_on_stdin_and_stdout() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), stopped(2, "older")],
+    );
+    let plugins = home.path().join("config/itracker/plugins");
+    fs::create_dir_all(&plugins).unwrap();
+    let script = r#"#!/bin/sh
+read -r request
+case "$request" in
+  *'"action":"describe"'*)
+    echo '{"description":"Test plugin","capabilities":["export","import","sync"]}' ;;
+  *'"action":"export"'*)
+    count=$(printf '%s' "$request" | grep -o '"index"' | wc -l)
+    printf '{"output":"%s entries\\n"}\n' $count ;;
+  *'"action":"import"'*)
+    entry='{"start_time":"2024-06-03T09:00:00+02:00","elapsed_time":"1800","title":"Imported"}'
+    bad='{"start_time":"soon","elapsed_time":"60","title":"Bad"}'
+    echo "{\"entries\":[$entry,$entry,$bad]}" ;;
+  *'"action":"sync"'*)
+    echo '{"entries":[],"message":"pushed"}' ;;
+esac
+"#;
+    let plugin = plugins.join("test");
+    fs::write(&plugin, script).unwrap();
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+    let broken = plugins.join("broken");
+    fs::write(&broken, "#!/bin/sh\necho nonsense\n").unwrap();
+    fs::set_permissions(&broken, fs::Permissions::from_mode(0o755)).unwrap();
+
+    itracker(home.path())
+        .args(["plugin", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "test  [export, import, sync]  Test plugin",
+        ))
+        .stdout(predicate::str::contains("broken  unusable"));
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["plugin", "export", "test"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("\n2 entries\n"));
+
+    let input = home.path().join("input.txt");
+    fs::write(&input, "anything").unwrap();
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["plugin", "import", "test"])
+        .arg(&input)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 1 of 3 entries from"))
+        .stdout(predicate::str::contains(
+            ", 1 skipped as already there, 1 failed.",
+        ))
+        .stderr(predicate::str::contains(
+            "Entry 3 left out: Invalid start time 'soon'",
+        ));
+    let imported = entries(&log);
+    assert_eq!(imported.len(), 3);
+    assert_eq!(imported[2].index, 3);
+    assert_eq!(imported[2].title, "Imported");
+    assert_eq!(imported[2].start_time, "Mon, 3 Jun 2024 09:00:00 +0200");
+
+    let config = home.path().join("config/itracker/config.toml");
+    fs::write(&config, "[plugins]\nsync = [\"test\"]\n").unwrap();
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .arg("sync")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("test: pushed"))
+        .stdout(predicate::str::contains("0 entries added"));
+}
+
+#[test]
+fn timers_start_stop_and_list_at_times_given_in_words() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), running(2, "current", 60)],
+    );
+    // Inside a git repository the task is prefilled, so nothing asks for it
+    let status = process::Command::new("git")
+        .args(["init", "--quiet"])
+        .current_dir(home.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--stop", "2", "--at", "20 minutes ago"])
+        .assert()
+        .success();
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--add", "--at", "2 hours ago", "--for", "1h30m"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Logged entry at index 3 of 1h 30m.",
+        ));
+    let logged = entries(&log);
+    let elapsed = logged[1].elapsed_time.parse::<i64>().unwrap();
+    assert!((2390..=2410).contains(&elapsed), "{}", elapsed);
+    assert_eq!(logged[2].elapsed_time, "5400");
+    assert!(!logged[2].is_running());
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["list", "--since", "3 hours ago", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("current"))
+        .stdout(predicate::str::contains("old").not());
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--add", "--for", "1h", "--at", "in 10 minutes"])
+        .assert()
+        .stderr(predicate::str::contains("lies in the future"));
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["--add", "--at", "yesterday teatime"])
+        .assert()
+        .stderr(predicate::str::contains(
+            "Cannot read 'yesterday teatime' as a time",
+        ));
+    assert_eq!(entries(&log).len(), 3);
+}
+
+#[test]
+fn edit_opens_an_entry_in_the_editor_and_checks_it_before_writing() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), running(2, "current", 60)],
+    );
+    let edit = |index: &str, editor: &str| {
+        let mut command = itracker(home.path());
+        command
+            .env("VISUAL", "")
+            .env("EDITOR", editor)
+            .arg("-o")
+            .arg(&log)
+            .args(["edit", index]);
+        command
+    };
+
+    edit("1", "true")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Log entry 1 is unchanged."));
+    edit(
+        "1",
+        "sed -i -e 's/^title = .*/title = \"renamed\"/' -e 's/^elapsed = .*/elapsed = \"45m\"/' -e 's/^tags = .*/tags = [\"x\", \"y\"]/'",
+    )
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Log entry 1 updated."));
+    edit(
+        "2",
+        "sed -i 's/^paused = .*/elapsed = \"30 minutes\"\\npaused = \"5m\"/'",
+    )
+    .assert()
+    .success();
+
+    let edited = entries(&log);
+    assert_eq!(edited[0].title, "renamed");
+    assert_eq!(edited[0].elapsed_time, "2700");
+    assert_eq!(edited[0].tags, ["x", "y"]);
+    assert_eq!(edited[1].elapsed_time, "1800");
+    assert_eq!(edited[1].paused_time, "300");
+
+    edit("1", "sed -i 's/^start = .*/start = \"teatime\"/'")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("start: Cannot read 'teatime'"));
+    edit("1", "sed -i 's/^elapsed = .*/elapsed = \"0s\"/'")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "elapsed: must be longer than zero",
+        ));
+    edit("1", "sed -i 's/^project = /colour = /'")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown field `colour`"));
+    edit("1", "false")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("the entry is unchanged"));
+    edit("9", "true")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Log entry 9 not found"));
+    assert_eq!(entries(&log)[0].elapsed_time, "2700");
+}
+
+#[test]
+fn amend_moves_start_and_end_but_not_onto_other_entries() {
+    let home = TempDir::new().unwrap();
+    let day = (chrono::Utc::now() - chrono::Duration::days(2)).date_naive();
+    let at = |time: &str| {
+        format!("{}T{}", day, time)
+            .parse::<chrono::NaiveDateTime>()
+            .unwrap()
+            .and_utc()
+            .to_rfc2822()
+    };
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            format!("1,\"{}\",,3600,0,morning,,,,", at("09:00:00")),
+            format!("2,\"{}\",,3600,600,noon,,,,", at("12:00:00")),
+            running(3, "current", 30),
+        ],
+    );
+    let amend = |args: &[&str]| {
+        let mut command = itracker(home.path());
+        command
+            .args(["--timezone", "UTC", "-o"])
+            .arg(&log)
+            .arg("amend")
+            .args(args);
+        command
+    };
+
+    amend(&["1", "--start", "9:05", "--end", "11:00"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Log entry 1 now runs from"))
+        .stdout(predicate::str::contains("1h 55m"));
+    // The end stays in place when only the start moves
+    amend(&["2", "--start", "12:30"]).assert().success();
+    amend(&["3", "--end", "10 minutes ago"]).assert().success();
+
+    let amended = entries(&log);
+    assert_eq!(amended[0].elapsed_time, "6900");
+    assert_eq!(amended[0].start_time, at("09:05:00"));
+    assert_eq!(amended[1].elapsed_time, "1800");
+    assert_eq!(amended[1].paused_time, "600");
+    let elapsed = amended[2].elapsed_time.parse::<i64>().unwrap();
+    assert!((1190..=1210).contains(&elapsed), "{}", elapsed);
+
+    amend(&["2", "--start", "10:30"])
+        .assert()
+        .stderr(predicate::str::contains(
+            "Log entry 2 would overlap log entry 1",
+        ));
+    amend(&["1", "--end", "8:00"])
+        .assert()
+        .stderr(predicate::str::contains("has to stop after it started"));
+    amend(&["2", "--start", "12:55"])
+        .assert()
+        .stderr(predicate::str::contains("shorter than its pauses"));
+    amend(&["1"])
+        .assert()
+        .stderr(predicate::str::contains("--start"));
+    assert_eq!(entries(&log)[1].start_time, at("12:30:00"));
+}
+
+#[test]
+fn split_cuts_an_entry_in_two_at_a_time() {
+    let home = TempDir::new().unwrap();
+    let day = (chrono::Utc::now() - chrono::Duration::days(2)).date_naive();
+    let start = format!("{}T09:00:00", day)
+        .parse::<chrono::NaiveDateTime>()
+        .unwrap()
+        .and_utc()
+        .to_rfc2822();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            format!("1,\"{}\",Review,7200,1200,review,app,ui,,", start),
+            running(2, "current", 60),
+        ],
+    );
+    let split = |args: &[&str]| {
+        let mut command = itracker(home.path());
+        command
+            .args(["--timezone", "UTC", "-o"])
+            .arg(&log)
+            .arg("split")
+            .args(args);
+        command
+    };
+
+    split(&["1", "--at", "9:30", "--title", "deploy"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Log entry 1 split at"))
+        .stdout(predicate::str::contains(
+            "the rest is log entry 3 of 1h 30m.",
+        ));
+    split(&["2", "--at", "20 minutes ago"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("log entry 4, still running"));
+
+    let parts = entries(&log);
+    assert_eq!(parts.len(), 4);
+    assert_eq!(parts[0].elapsed_time, "1800");
+    assert_eq!(parts[0].paused_time, "1200");
+    assert_eq!(parts[2].title, "deploy");
+    assert_eq!(parts[2].message, "Review");
+    assert_eq!(parts[2].project, "app");
+    assert_eq!(parts[2].elapsed_time, "5400");
+    assert_eq!(parts[2].paused_time, "0");
+    assert!(!parts[1].is_running());
+    assert!(parts[3].is_running());
+    assert_eq!(parts[3].title, "current");
+
+    split(&["1", "--at", "8:00"])
+        .assert()
+        .stderr(predicate::str::contains(
+            "can only be split at a time after its start and before its end",
+        ));
+    assert_eq!(entries(&log).len(), 4);
+}
+
+#[test]
+fn merge_of_indices_folds_entries_into_the_first_one() {
+    let home = TempDir::new().unwrap();
+    let row =
+        |index: usize, hours_ago: i64, seconds: u64, paused: u64, message: &str, tags: &str| {
+            format!(
+                "{},\"{}\",{},{},{},fix,app,\"{}\",,",
+                index,
+                (chrono::Utc::now() - chrono::Duration::hours(hours_ago)).to_rfc2822(),
+                message,
+                seconds,
+                paused,
+                tags
+            )
+        };
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            row(1, 5, 1800, 60, "Login bug", "bug"),
+            row(2, 3, 600, 0, "Login bug", "bug,web"),
+            row(3, 4, 1200, 120, "Session timeout", ""),
+            running(4, "current", 10),
+            format!(
+                "5,\"{}\",,600,0,other,site,,,",
+                (chrono::Utc::now() - chrono::Duration::hours(2)).to_rfc2822()
+            ),
+        ],
+    );
+    let merge = |indices: &[&str]| {
+        let mut command = itracker(home.path());
+        command
+            .arg("-o")
+            .arg(&log)
+            .arg("--yes")
+            .arg("merge")
+            .args(indices);
+        command
+    };
+
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["merge", "3", "1"])
+        .assert()
+        .stderr(predicate::str::contains("pass --yes"));
+    merge(&["3", "2", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Log entries 3, 2, 1 merged into 1, 57m tracked.",
+        ));
+    let merged = entries(&log);
+    assert_eq!(merged.len(), 3);
+    assert_eq!(merged[0].index, 1);
+    assert_eq!(merged[0].elapsed_time, "3600");
+    assert_eq!(merged[0].paused_time, "180");
+    assert_eq!(merged[0].message, "Login bug\nSession timeout");
+    assert_eq!(merged[0].tags, ["bug", "web"]);
+
+    merge(&["1", "4"]).assert().stderr(predicate::str::contains(
+        "Log entry 4 is still running, stop it before merging",
+    ));
+    merge(&["1", "5"]).assert().stderr(predicate::str::contains(
+        "Entries of different projects cannot be merged: app, site",
+    ));
+    merge(&["1", "1"])
+        .assert()
+        .stderr(predicate::str::contains("at least two different entries"));
+    merge(&["1", "2"])
+        .assert()
+        .stderr(predicate::str::contains("Log entry 2 not found"));
+    assert_eq!(entries(&log).len(), 3);
+
+    // Indices of merged entries are not given out again
+    itracker(home.path())
+        .arg("-o")
+        .arg(&log)
+        .args(["split", "1", "--at", "4h30m ago"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("the rest is log entry 6"));
+}
+
+#[test]
+fn delete_log_without_an_index_deletes_the_entries_matching_filters() {
+    let home = TempDir::new().unwrap();
+    let row = |index: usize, start: &str, project: &str, tags: &str| {
+        format!(
+            "{},\"{}\",,600,0,task {},{},\"{}\",,",
+            index, start, index, project, tags
+        )
+    };
+    let mut rows = vec![
+        row(1, "Sun, 1 May 2022 09:00:00 +0000", "test", "scratch"),
+        row(2, "Mon, 2 May 2022 09:00:00 +0000", "acme", "scratch,bug"),
+        row(3, "Tue, 3 May 2022 09:00:00 +0000", "test", ""),
+    ];
+    rows.extend(
+        (4..=15).map(|index| row(index, "Mon, 2 Jan 2023 09:00:00 +0000", "test", "scratch")),
+    );
+    rows.push(row(16, "Mon, 2 Jan 2023 09:00:00 +0000", "acme", ""));
+    let log = log_file(home.path(), "time.csv", &rows);
+    let delete = |filters: &[&str]| {
+        let mut command = itracker(home.path());
+        command
+            .arg("-l")
+            .arg(&log)
+            .arg("--delete-log")
+            .args(filters);
+        command
+    };
+
+    delete(&["--before", "2023-01-01", "--tag", "scratch"])
+        .assert()
+        .stdout(predicate::str::contains("2 entries of"))
+        .stdout(predicate::str::contains("task 1"))
+        .stderr(predicate::str::contains("pass --yes"));
+    assert_eq!(entries(&log).len(), 16);
+    delete(&["--project", "test", "--tag", "scratch"])
+        .arg("--yes")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("13 entries of"))
+        .stdout(predicate::str::contains("... 3 more"))
+        .stdout(predicate::str::contains("task 9").not())
+        .stdout(predicate::str::contains("13 entries deleted"));
+    let left: Vec<usize> = entries(&log).iter().map(|entry| entry.index).collect();
+    assert_eq!(left, [2, 3, 16]);
+
+    delete(&["--project", "none", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No entries of"));
+    delete(&[])
+        .assert()
+        .stderr(predicate::str::contains("--before, --tag or --project"));
+    delete(&["2", "--tag", "bug"])
+        .assert()
+        .stderr(predicate::str::contains("Delete either"));
+    itracker(home.path())
+        .args(["--tag", "bug"])
+        .assert()
+        .stderr(predicate::str::contains("--delete-log"));
+    assert_eq!(entries(&log).len(), 3);
+}
+
+#[test]
+fn undo_takes_back_the_last_change_to_entries() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            stopped(1, "old"),
+            stopped(2, "older"),
+            running(3, "current", 30),
+        ],
+    );
+    let run = |args: &[&str]| {
+        let mut command = itracker(home.path());
+        command.arg("-o").arg(&log).args(args);
+        command
+    };
+    let titles = || -> Vec<String> { entries(&log).into_iter().map(|e| e.title).collect() };
+
+    run(&["undo"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to undo"));
+
+    run(&["--stop", "3"]).assert().success();
+    run(&["--yes", "merge", "1", "2"]).assert().success();
+    run(&["-l"])
+        .arg(&log)
+        .args(["--delete-log", "3", "--yes"])
+        .assert()
+        .success();
+    assert_eq!(titles(), ["old"]);
+
+    run(&["undo"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Undid the delete of entry 3 in"));
+    assert_eq!(titles(), ["old", "current"]);
+    run(&["undo"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Undid the merge of entries 1, 2"));
+    assert_eq!(titles(), ["old", "older", "current"]);
+
+    // Changes since the step are not lost without --force
+    fs::write(
+        &log,
+        fs::read_to_string(&log)
+            .unwrap()
+            .replace("older", "renamed"),
+    )
+    .unwrap();
+    run(&["undo"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "changed since the stop of entry 3, pass --force",
+        ));
+    // Only the stopped entry is put back, the other change stays
+    run(&["--force", "undo"]).assert().success();
+    assert_eq!(titles(), ["old", "renamed", "current"]);
+    assert!(entries(&log)[2].is_running());
+    run(&["undo"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to undo"));
+}
+
+#[test]
+fn dedupe_lists_and_removes_entries_started_twice() {
+    let home = TempDir::new().unwrap();
+    let row = |index: usize, start: &str, title: &str, seconds: u64| {
+        format!("{},\"{}\",,{},0,{},,,,", index, start, seconds, title)
+    };
+    let (nine, ten) = (
+        "Mon, 3 Jun 2024 09:00:00 +0000",
+        "Mon, 3 Jun 2024 10:00:00 +0000",
+    );
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            row(1, nine, "standup", 900),
+            row(2, ten, "review", 1800),
+            row(3, nine, "standup", 900),
+            // The same instant written in another offset
+            row(4, "Mon, 3 Jun 2024 11:00:00 +0200", "standup", 600),
+            row(5, ten, "deploy", 1800),
+        ],
+    );
+    let dedupe = |args: &[&str]| {
+        let mut command = itracker(home.path());
+        command.arg("-o").arg(&log).args(args);
+        command
+    };
+
+    dedupe(&["dedupe"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Log entry 1:"))
+        .stdout(predicate::str::contains("  duplicate 3:"))
+        .stdout(predicate::str::contains("  duplicate 4:"))
+        .stdout(predicate::str::contains("duplicate 5").not())
+        .stdout(predicate::str::contains("2 duplicates in"));
+    assert_eq!(entries(&log).len(), 5);
+
+    dedupe(&["--yes", "dedupe", "--remove"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 duplicates deleted"));
+    let left: Vec<usize> = entries(&log).iter().map(|entry| entry.index).collect();
+    assert_eq!(left, [1, 2, 5]);
+    dedupe(&["dedupe"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No duplicates in"));
+
+    dedupe(&["undo"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Undid the dedupe of 2 entries"));
+    assert_eq!(entries(&log).len(), 5);
+}
+
+#[test]
+fn move_rehomes_entries_to_another_project_or_file() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[
+            stopped(1, "misfiled"),
+            stopped(2, "other"),
+            stopped(3, "private"),
+        ],
+    );
+    let archive = log_file(home.path(), "archive.csv", &[stopped(1, "old")]);
+    let run = |args: &[&str]| {
+        let mut command = itracker(home.path());
+        command.arg("-o").arg(&log).args(args);
+        command
+    };
+
+    run(&["move", "1", "2", "--to-project", "acme"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Log entries 1, 2 moved to project acme.",
+        ));
+    let moved = entries(&log);
+    assert_eq!(moved[0].project, "acme");
+    assert_eq!(moved[1].project, "acme");
+    assert_eq!(moved[2].project, "");
+    run(&["undo"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Undid the move of entries 1, 2 to acme",
+        ));
+    assert_eq!(entries(&log)[0].project, "");
+
+    let start = moved[2].start_time.clone();
+    run(&["move", "3", "--to-file"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Log entry 3 of"))
+        .stdout(predicate::str::contains("is now log entry 2 of"));
+    assert_eq!(entries(&log).len(), 2);
+    let archived = entries(&archive);
+    assert_eq!(archived[1].index, 2);
+    assert_eq!(archived[1].title, "private");
+    assert_eq!(archived[1].start_time, start);
+    assert!(archived[1].metadata["moved_from"].ends_with("time.csv#3"));
+
+    run(&["move", "1", "--to-file"])
+        .arg(&log)
+        .assert()
+        .stderr(predicate::str::contains(
+            "is the file the entries are in already",
+        ));
+    run(&["move", "7", "--to-project", "acme"])
+        .assert()
+        .stderr(predicate::str::contains("Log entry 7 not found"));
+    run(&["move", "1"])
+        .assert()
+        .stderr(predicate::str::contains("--to-project"));
+}
+
+#[test]
+fn meta_values_are_stored_filtered_on_and_listed() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(home.path(), "time.csv", &[stopped(1, "other")]);
+    // Inside a git repository the task is prefilled, so nothing asks for it
+    let status = process::Command::new("git")
+        .args(["init", "--quiet"])
+        .current_dir(home.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let run = |args: &[&str]| {
+        let mut command = itracker(home.path());
+        command.arg("-o").arg(&log).args(args);
+        command
+    };
+
+    run(&[
+        "--add",
+        "--for",
+        "1h",
+        "--meta",
+        "ticket=PROJ-123",
+        "--meta",
+        "reviewer=alice",
+    ])
+    .assert()
+    .success();
+    let metadata = &entries(&log)[1].metadata;
+    assert_eq!(metadata["ticket"], "PROJ-123");
+    assert_eq!(metadata["reviewer"], "alice");
+
+    let output = run(&["list", "--meta", "ticket=PROJ-123", "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let listed: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["index"], 2);
+    assert_eq!(listed[0]["metadata"]["reviewer"], "alice");
+
+    run(&["list", "--meta", "ticket=PROJ-9", "--format", "json"])
+        .assert()
+        .success()
+        .stdout("[]\n");
+    run(&["report", "--meta", "reviewer=alice", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"tracked_seconds\": 3600"))
+        .stdout(predicate::str::contains("\"entries\": 1"));
+    run(&["list", "--meta", "ticket"])
+        .assert()
+        .stderr(predicate::str::contains("expected KEY=VALUE"));
+}
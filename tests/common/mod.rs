@@ -0,0 +1,40 @@
+use chrono::{Duration, Utc};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Header row written by the current version.
+pub const HEADER: &str = "Index,Start Time,Task Description,Elapsed Time (seconds),Paused Time (seconds),Title,Project,Tags,Estimate (minutes),Paused Since";
+
+/// Writes a log file with `HEADER` and the given rows into `dir`.
+pub fn log_file(dir: &Path, name: &str, rows: &[String]) -> PathBuf {
+    let path = dir.join(name);
+    let mut contents = format!("{}\n", HEADER);
+    for row in rows {
+        contents.push_str(row);
+        contents.push('\n');
+    }
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// A stopped entry that ran for an hour.
+pub fn stopped(index: usize, title: &str) -> String {
+    format!(
+        "{},\"{}\",,3600,0,{},,,,",
+        index,
+        (Utc::now() - Duration::days(1)).to_rfc2822(),
+        title
+    )
+}
+
+/// An entry started `minutes` ago that is still running.
+pub fn running(index: usize, title: &str, minutes: i64) -> String {
+    format!(
+        "{},\"{}\",,0,0,{},,,,",
+        index,
+        (Utc::now() - Duration::minutes(minutes)).to_rfc2822(),
+        title
+    )
+}
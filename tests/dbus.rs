@@ -0,0 +1,105 @@
+//! The D-Bus service of the daemon; needs `--features dbus` and a session
+//! bus, e.g. `dbus-run-session cargo test --features dbus`.
+#![cfg(feature = "dbus")]
+
+mod common;
+
+use common::{log_file, running, stopped};
+use itracker::dbus::{BUS_NAME, INTERFACE, OBJECT_PATH};
+use std::{process, thread, time::Duration};
+use tempfile::TempDir;
+use zbus::blocking::{Connection, MessageIterator};
+
+type Status = (String, u32, String, String, u64);
+
+#[test]
+fn the_daemon_controls_timers_over_dbus_and_signals_changes() {
+    if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_none() {
+        eprintln!("No session bus, skipping");
+        return;
+    }
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "Old"), running(2, "Review", 10)],
+    );
+    let mut daemon = process::Command::new(assert_cmd::cargo::cargo_bin("Itraker"))
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path().join("config"))
+        .env("XDG_DATA_HOME", home.path().join("data"))
+        .env("XDG_RUNTIME_DIR", home.path().join("run"))
+        .arg("-o")
+        .arg(&log)
+        .arg("daemon")
+        .stdout(process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let connection = Connection::session().unwrap();
+    let call = |method: &str| {
+        connection.call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE), method, &())
+    };
+    let status = loop {
+        match call("GetStatus") {
+            Ok(reply) => break reply.body().deserialize::<Status>().unwrap(),
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    };
+    assert_eq!(
+        (status.0.as_str(), status.1, status.2.as_str()),
+        ("running", 2, "Review")
+    );
+    assert!(status.4 >= 600);
+
+    connection
+        .call_method(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            Some("org.freedesktop.DBus"),
+            "AddMatch",
+            &format!(
+                "type='signal',interface='{}',member='StateChanged'",
+                INTERFACE
+            ),
+        )
+        .unwrap();
+    let mut signals = MessageIterator::from(&connection);
+
+    let mut next_state = || {
+        let signal = signals
+            .by_ref()
+            .filter_map(Result::ok)
+            .find(|message| {
+                message
+                    .header()
+                    .member()
+                    .is_some_and(|m| m == "StateChanged")
+            })
+            .unwrap();
+        let (state, index, _, _): (String, u32, String, String) =
+            signal.body().deserialize().unwrap();
+        (state, index)
+    };
+
+    let stopped = call("StopTimer").unwrap();
+    assert_eq!(stopped.body().deserialize::<u32>().unwrap(), 2);
+    assert!(call("StopTimer").is_err());
+    assert_eq!(next_state(), ("idle".to_string(), 0));
+
+    let started = connection
+        .call_method(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            Some(INTERFACE),
+            "StartTimer",
+            &("Deploy", "ops"),
+        )
+        .unwrap();
+    assert_eq!(started.body().deserialize::<u32>().unwrap(), 3);
+    let state = next_state();
+    daemon.kill().unwrap();
+    daemon.wait().unwrap();
+    assert_eq!(state, ("running".to_string(), 3));
+}
@@ -0,0 +1,84 @@
+//! The gRPC service, driven through the generated client; needs `--features grpc`.
+#![cfg(feature = "grpc")]
+
+mod common;
+
+use common::{log_file, running, stopped};
+use itracker::grpc::proto::{tracker_client::TrackerClient, EntryRequest, ListRequest, Task};
+use std::{net::TcpListener, process, thread, time::Duration};
+use tempfile::TempDir;
+use tonic::Code;
+
+#[test]
+fn the_grpc_service_starts_stops_and_lists_entries() {
+    let home = TempDir::new().unwrap();
+    let log = log_file(
+        home.path(),
+        "time.csv",
+        &[stopped(1, "old"), running(2, "current", 10)],
+    );
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut server = process::Command::new(assert_cmd::cargo::cargo_bin("Itraker"))
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path().join("config"))
+        .env("XDG_DATA_HOME", home.path().join("data"))
+        .env("XDG_RUNTIME_DIR", home.path().join("run"))
+        .arg("-o")
+        .arg(&log)
+        .args(["grpc", "--port", &port.to_string()])
+        .stdout(process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let result = runtime.block_on(async {
+        let address = format!("http://127.0.0.1:{}", port);
+        let mut client = loop {
+            match TrackerClient::connect(address.clone()).await {
+                Ok(client) => break client,
+                Err(_) => thread::sleep(Duration::from_millis(50)),
+            }
+        };
+
+        let stopped = client
+            .stop(EntryRequest { index: None })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(stopped.index, 2);
+        assert!(stopped.elapsed_time >= 600);
+
+        let missing = client.pause(EntryRequest { index: Some(9) }).await;
+        assert_eq!(missing.unwrap_err().code(), Code::NotFound);
+
+        let started = client
+            .start(Task {
+                title: "grpc".to_string(),
+                tags: vec!["api".to_string()],
+                ..Task::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(started.index, 3);
+
+        client.list(ListRequest {}).await.unwrap().into_inner()
+    });
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    let titles: Vec<String> = result
+        .entries
+        .into_iter()
+        .map(|entry| entry.title)
+        .collect();
+    assert_eq!(titles, ["old", "current", "grpc"]);
+}
@@ -0,0 +1,1009 @@
+//! Library tests running timers on temporary log files with a mock clock.
+
+use chrono::{Duration, TimeZone, Utc};
+use itracker::{
+    activity::{self, ActivityConfig, ActivityTracker},
+    backup::{Backups, Retention},
+    clock::{Clock, MockClock, SystemClock},
+    hooks::{Hooks, HooksConfig},
+    idle::{self, IdleChoice},
+    index::LogIndex,
+    mapped::MappedLog,
+    notify::{Event, NotificationsConfig, Notifier},
+    report, schema, suggest,
+    sync::merge_records,
+    table::{Align, Table},
+    timefmt, timeparse,
+    undo::UndoLog,
+    ITrackerError, Storage, TaskDetails, Tracker,
+};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn task(title: &str) -> TaskDetails {
+    TaskDetails {
+        title: title.to_string(),
+        ..TaskDetails::default()
+    }
+}
+
+/// A tracker on `time.csv` in `dir`, with a clock starting on 2024-06-03 09:00 UTC.
+fn tracker(dir: &TempDir) -> (Tracker, MockClock) {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap());
+    let path = dir.path().join("time.csv");
+    let tracker =
+        Tracker::new(Storage::new(path.to_str().unwrap())).with_clock(Arc::new(clock.clone()));
+    (tracker, clock)
+}
+
+#[test]
+fn elapsed_and_paused_time_follow_the_clock() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+
+    let entry = tracker.start(&task("Review")).unwrap();
+    assert_eq!(entry.index, 1);
+    assert_eq!(entry.start_time, "Mon, 3 Jun 2024 09:00:00 +0000");
+
+    clock.advance(Duration::minutes(30));
+    tracker.pause(None).unwrap();
+    clock.advance(Duration::minutes(10));
+    let resumed = tracker.resume(None).unwrap();
+    assert_eq!(resumed.paused_time, "600");
+
+    clock.advance(Duration::minutes(50));
+    let stopped = tracker.stop(entry.index).unwrap();
+    assert_eq!(stopped.elapsed_time, "5400");
+    assert_eq!(stopped.paused_time, "600");
+    assert!(tracker.running().unwrap().is_none());
+}
+
+#[test]
+fn stopping_a_paused_entry_ends_the_pause() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+
+    tracker.start(&task("Call")).unwrap();
+    clock.advance(Duration::minutes(5));
+    tracker.pause(Some(1)).unwrap();
+    clock.advance(Duration::minutes(15));
+    let stopped = tracker.stop_running().unwrap();
+
+    assert_eq!(stopped.elapsed_time, "1200");
+    assert_eq!(stopped.paused_time, "900");
+    assert!(stopped.paused_since().is_none());
+}
+
+#[test]
+fn pause_and_resume_target_the_latest_running_entry() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+
+    tracker.start(&task("first")).unwrap();
+    clock.advance(Duration::minutes(1));
+    tracker.start(&task("second")).unwrap();
+
+    assert_eq!(tracker.pause(None).unwrap().index, 2);
+    assert!(matches!(
+        tracker.pause(None),
+        Err(ITrackerError::AlreadyPaused { index: 2 })
+    ));
+    assert!(matches!(
+        tracker.resume(Some(1)),
+        Err(ITrackerError::NotPaused { index: 1 })
+    ));
+}
+
+#[test]
+fn idle_time_is_kept_discarded_or_moved_to_another_task_on_resume() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    tracker.start(&task("Write")).unwrap();
+
+    // Paused as of the last input, 20 minutes before idle detection noticed
+    clock.advance(Duration::minutes(60));
+    let paused = tracker
+        .pause_idle(None, clock.now() - Duration::minutes(20))
+        .unwrap();
+    assert_eq!(paused.paused_since, "Mon, 3 Jun 2024 09:40:00 +0000");
+    assert!(paused.metadata.contains_key(idle::METADATA_KEY));
+    clock.advance(Duration::minutes(10));
+    let kept = tracker.resume_idle(None, &IdleChoice::Keep).unwrap();
+    assert_eq!(kept.paused_time, "0");
+    assert!(kept.metadata.is_empty());
+
+    clock.advance(Duration::minutes(30));
+    tracker
+        .pause_idle(None, clock.now() - Duration::minutes(15))
+        .unwrap();
+    clock.advance(Duration::minutes(5));
+    let moved = tracker
+        .resume_idle(None, &IdleChoice::Move { task: task("Call") })
+        .unwrap();
+    assert_eq!(moved.paused_time, "1200");
+    let call = tracker.storage().entry(2).unwrap();
+    assert_eq!(call.title, "Call");
+    assert_eq!(call.start_time, "Mon, 3 Jun 2024 10:25:00 +0000");
+    assert_eq!(call.elapsed_time, "1200");
+
+    // A plain resume discards it, as a pause
+    clock.advance(Duration::minutes(10));
+    tracker.pause_idle(None, clock.now()).unwrap();
+    clock.advance(Duration::minutes(10));
+    let discarded = tracker.resume(None).unwrap();
+    assert_eq!(discarded.paused_time, "1800");
+    assert!(discarded.metadata.is_empty());
+}
+
+#[test]
+fn suggested_tasks_follow_the_time_of_day_and_the_weekday() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    // Two weeks of a morning standup and more frequent afternoon reviews
+    for day in 0..14 {
+        let midnight = Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap() + Duration::days(day);
+        if day % 7 >= 5 {
+            continue;
+        }
+        for (hour, title) in [(9, "Standup"), (14, "Review"), (16, "Review")] {
+            clock.set(midnight + Duration::hours(hour));
+            tracker.start(&task(title)).unwrap();
+            clock.advance(Duration::minutes(15));
+            tracker.stop_running().unwrap();
+        }
+    }
+    let entries = tracker.storage().entries().unwrap();
+    let titles = |now| -> Vec<String> {
+        suggest::likely_tasks(&entries, now, chrono_tz::UTC, suggest::MAX_TASKS)
+            .into_iter()
+            .map(|suggested| suggested.task.title)
+            .collect()
+    };
+
+    let monday = Utc.with_ymd_and_hms(2024, 6, 17, 0, 0, 0).unwrap();
+    assert_eq!(titles(monday + Duration::hours(9)), ["Standup", "Review"]);
+    assert_eq!(
+        titles(monday + Duration::minutes(14 * 60 + 30)),
+        ["Review", "Standup"]
+    );
+}
+
+#[test]
+fn hooks_run_after_each_change_with_the_entry_in_their_environment() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    let out = dir.path().join("hooks.txt");
+    let hook = format!(
+        "echo \"$ITRACKER_EVENT $ITRACKER_INDEX $ITRACKER_TITLE $ITRACKER_ELAPSED\" >> '{}'",
+        out.display()
+    );
+    let tracker = tracker.with_hooks(Hooks::from_config(Some(&HooksConfig {
+        on_start: Some(hook.clone()),
+        on_pause: Some(hook.clone()),
+        on_resume: None,
+        on_stop: Some(hook),
+    })));
+
+    tracker.start(&task("Deploy")).unwrap();
+    tracker.pause(None).unwrap();
+    tracker.resume(None).unwrap();
+    clock.advance(Duration::minutes(15));
+    tracker.stop_running().unwrap();
+    // Failed operations run no hooks
+    assert!(tracker.pause(None).is_err());
+
+    assert_eq!(
+        std::fs::read_to_string(out).unwrap(),
+        "start 1 Deploy 0\npause 1 Deploy 0\nstop 1 Deploy 900\n"
+    );
+}
+
+#[test]
+fn a_stopped_entry_never_records_zero_time_and_so_stays_stopped() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+
+    // A clock set back since the start
+    tracker.start(&task("Jump")).unwrap();
+    clock.advance(Duration::minutes(-10));
+    let stopped = tracker.stop(1).unwrap();
+    assert_eq!(stopped.elapsed_time, "1");
+    assert!(!stopped.is_running());
+
+    // A stop in the same second as the start
+    clock.advance(Duration::minutes(20));
+    let quick = tracker.start(&task("Blink")).unwrap();
+    assert_eq!(tracker.stop(quick.index).unwrap().elapsed_time, "1");
+    assert!(tracker.running().unwrap().is_none());
+    assert!(matches!(
+        tracker.stop(quick.index),
+        Err(ITrackerError::NotRunning { .. })
+    ));
+
+    // A stop given at the very start
+    let start = clock.now();
+    let given = tracker.start(&task("Given")).unwrap();
+    assert!(matches!(
+        tracker.stop_at(given.index, start),
+        Err(ITrackerError::StopsBeforeStart { .. })
+    ));
+    assert!(tracker.storage().entry(given.index).unwrap().is_running());
+}
+
+#[test]
+fn indices_of_deleted_entries_are_not_reused() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, _) = tracker(&dir);
+
+    tracker.start(&task("a")).unwrap();
+    tracker.start(&task("b")).unwrap();
+    tracker.storage().delete(2).unwrap();
+
+    assert_eq!(tracker.start(&task("c")).unwrap().index, 3);
+}
+
+#[test]
+fn the_journal_is_folded_in_after_enough_updates() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    let journal = schema::journal_path(tracker.storage().path());
+
+    tracker.start(&task("Busy")).unwrap();
+    tracker.pause(None).unwrap();
+    assert!(journal.exists());
+
+    for _ in 0..schema::JOURNAL_LIMIT / 2 {
+        clock.advance(Duration::minutes(1));
+        tracker.resume(None).unwrap();
+        tracker.pause(None).unwrap();
+    }
+
+    let entries = tracker.storage().entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].paused_time,
+        (60 * (schema::JOURNAL_LIMIT / 2)).to_string()
+    );
+    let records = schema::read(tracker.storage().path()).unwrap();
+    assert_eq!(records[0].paused_time, entries[0].paused_time);
+}
+
+#[test]
+fn notifications_are_due_once_when_timers_pass_their_limits() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    let mut notifier = Notifier::from_config(Some(&NotificationsConfig {
+        pomodoro: Some(true),
+        long_running_hours: Some(1.0),
+        ..NotificationsConfig::default()
+    }))
+    .unwrap();
+    let mut check = |clock: &MockClock| -> Vec<Event> {
+        let entries = tracker.storage().entries().unwrap();
+        notifier
+            .check("time.csv", &entries, clock.now())
+            .into_iter()
+            .map(|notification| notification.event)
+            .collect()
+    };
+
+    tracker
+        .start(&TaskDetails {
+            estimate: Some(40),
+            ..task("Write")
+        })
+        .unwrap();
+    assert!(check(&clock).is_empty());
+
+    clock.advance(Duration::minutes(26));
+    assert_eq!(check(&clock), [Event::Pomodoro(1)]);
+    assert!(check(&clock).is_empty());
+
+    clock.advance(Duration::minutes(15));
+    assert_eq!(check(&clock), [Event::EstimateReached]);
+
+    // Paused time does not count
+    tracker.pause(None).unwrap();
+    clock.advance(Duration::minutes(30));
+    assert!(check(&clock).is_empty());
+    tracker.resume(None).unwrap();
+
+    clock.advance(Duration::minutes(20));
+    assert_eq!(check(&clock), [Event::Pomodoro(2), Event::LongRunning]);
+}
+
+#[test]
+fn break_reminders_follow_work_across_entries_until_a_real_break() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    let mut notifier = Notifier::from_config(Some(&NotificationsConfig {
+        long_running: Some(false),
+        estimate: Some(false),
+        breaks: Some(true),
+        break_after_minutes: Some(60),
+        break_minutes: Some(10),
+        ..NotificationsConfig::default()
+    }))
+    .unwrap();
+    let mut check = |clock: &MockClock| -> Vec<Event> {
+        let entries = tracker.storage().entries().unwrap();
+        notifier
+            .check("time.csv", &entries, clock.now())
+            .into_iter()
+            .map(|notification| notification.event)
+            .collect()
+    };
+
+    // A short gap between two entries is no break
+    tracker.start(&task("Write")).unwrap();
+    clock.advance(Duration::minutes(40));
+    tracker.stop_running().unwrap();
+    clock.advance(Duration::minutes(3));
+    tracker.start(&task("Review")).unwrap();
+    clock.advance(Duration::minutes(20));
+    assert_eq!(check(&clock), [Event::Break(1)]);
+    assert!(check(&clock).is_empty());
+
+    // Nor is a short pause
+    tracker.pause(None).unwrap();
+    clock.advance(Duration::minutes(5));
+    assert!(check(&clock).is_empty());
+    tracker.resume(None).unwrap();
+    clock.advance(Duration::minutes(55));
+    assert_eq!(check(&clock), [Event::Break(2)]);
+
+    // A long one starts over
+    tracker.pause(None).unwrap();
+    assert!(check(&clock).is_empty());
+    clock.advance(Duration::minutes(15));
+    assert!(check(&clock).is_empty());
+    tracker.resume(None).unwrap();
+    clock.advance(Duration::minutes(59));
+    assert!(check(&clock).is_empty());
+    clock.advance(Duration::minutes(2));
+    assert_eq!(check(&clock), [Event::Break(1)]);
+}
+
+#[test]
+fn overtime_is_notified_once_a_day_when_a_running_timer_crosses_the_workday() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    let mut notifier = Notifier::from_config(Some(&NotificationsConfig {
+        long_running: Some(false),
+        overtime: Some(true),
+        ..NotificationsConfig::default()
+    }))
+    .unwrap()
+    .with_workday(Some(8 * 3600), chrono_tz::UTC);
+    let mut check = |clock: &MockClock| -> Vec<Event> {
+        let entries = tracker.storage().entries().unwrap();
+        notifier
+            .check("time.csv", &entries, clock.now())
+            .into_iter()
+            .map(|notification| notification.event)
+            .collect()
+    };
+
+    tracker.start(&task("Morning")).unwrap();
+    clock.advance(Duration::hours(5));
+    tracker.stop_running().unwrap();
+    tracker.start(&task("Afternoon")).unwrap();
+    clock.advance(Duration::hours(3));
+    assert!(check(&clock).is_empty());
+    clock.advance(Duration::minutes(1));
+    assert_eq!(check(&clock), [Event::Overtime]);
+    clock.advance(Duration::hours(1));
+    assert!(check(&clock).is_empty());
+}
+
+#[test]
+fn merging_log_versions_keeps_new_entries_of_both_sides_and_reports_conflicts() {
+    let record = |index: usize, title: &str, elapsed: &str| schema::Record {
+        index,
+        start_time: format!("Mon, 3 Jun 2024 0{}:00:00 +0000", index),
+        elapsed_time: elapsed.to_string(),
+        paused_time: "0".to_string(),
+        title: title.to_string(),
+        ..schema::Record::default()
+    };
+    let base = [record(1, "done", "60"), record(2, "running", "0")];
+    let ours = [
+        record(1, "done", "60"),
+        record(2, "running", "0"),
+        record(3, "here", "60"),
+    ];
+    let theirs = [
+        record(1, "done", "60"),
+        record(2, "running", "900"),
+        record(3, "there", "60"),
+    ];
+
+    let merged = merge_records(&base, &ours, &theirs);
+    assert!(merged.conflicts.is_empty());
+    assert_eq!(merged.renumbered, [(3, 4)]);
+    let summary: Vec<(usize, &str, &str)> = merged
+        .records
+        .iter()
+        .map(|r| (r.index, r.title.as_str(), r.elapsed_time.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (1, "done", "60"),
+            (2, "running", "900"),
+            (3, "there", "60"),
+            (4, "here", "60")
+        ]
+    );
+
+    let stopped_here = [record(1, "done", "60"), record(2, "running", "600")];
+    let merged = merge_records(&base, &stopped_here, &theirs);
+    assert_eq!(merged.conflicts, [2]);
+}
+
+#[test]
+fn retention_keeps_the_latest_backups_and_the_last_one_of_recent_days_and_weeks() {
+    // Every 12 hours for four weeks, from Monday 2024-06-03 00:00 UTC
+    let start = Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap();
+    let times: Vec<_> = (0..56).map(|i| start + Duration::hours(12 * i)).collect();
+    let retention = Retention {
+        last: 3,
+        daily: 4,
+        weekly: 3,
+    };
+
+    let kept: Vec<_> = retention
+        .select(&times)
+        .into_iter()
+        .zip(&times)
+        .filter(|(keep, _)| *keep)
+        .map(|(_, time)| time.format("%m-%d %H").to_string())
+        .collect();
+    assert_eq!(
+        kept,
+        [
+            "06-16 12", // last of the second week
+            "06-23 12", // last of the third week
+            "06-27 12", // last of Thursday
+            "06-28 12", // last of Friday
+            "06-29 12", // last of Saturday
+            "06-30 00", // the latest three
+            "06-30 12",
+        ]
+    );
+    assert!(Retention::default().select(&times).iter().all(|keep| !keep));
+}
+
+#[test]
+fn tables_shrink_titles_to_fit_and_keep_durations_whole() {
+    let mut table = Table::new()
+        .column("#", Align::Right)
+        .shrinking("Title")
+        .column("Tracked", Align::Right);
+    table.row(vec![
+        "1".into(),
+        "Review the pull request of the parser".into(),
+        "1h 30m".into(),
+    ]);
+    table.row(vec!["12".into(), "Mail".into(), "5m".into()]);
+
+    assert_eq!(
+        table.render(None),
+        [
+            " #  Title                                  Tracked",
+            " 1  Review the pull request of the parser   1h 30m",
+            "12  Mail                                        5m\n",
+        ]
+        .join("\n")
+    );
+    // 2 + 2 + 20 + 2 + 7 columns
+    assert_eq!(table.widths(Some(33)), [2, 20, 7]);
+    assert_eq!(
+        table.render(Some(33)),
+        [
+            " #  Title                 Tracked",
+            " 1  Review the pull req…   1h 30m",
+            "12  Mail                       5m\n",
+        ]
+        .join("\n")
+    );
+    // Titles keep a few characters even if the table then overflows
+    assert_eq!(table.widths(Some(10)), [2, 8, 7]);
+}
+
+#[test]
+fn relative_times_name_minutes_then_days_then_dates() {
+    // Wednesday 2024-06-12 15:00
+    let now = Utc.with_ymd_and_hms(2024, 6, 12, 15, 0, 0).unwrap();
+    let ago = |minutes: i64| timefmt::relative(&(now - Duration::minutes(minutes)), &now);
+
+    assert_eq!(ago(0), "just now");
+    assert_eq!(ago(1), "1 minute ago");
+    assert_eq!(ago(25), "25 minutes ago");
+    assert_eq!(ago(178), "today 12:02");
+    assert_eq!(ago(24 * 60 + 58), "yesterday 14:02");
+    assert_eq!(ago(2 * 24 * 60), "Monday 15:00");
+    assert_eq!(ago(7 * 24 * 60), "2024-06-05 15:00");
+    assert_eq!(ago(-90), "2024-06-12 16:30");
+}
+
+#[test]
+fn times_and_durations_read_as_people_write_them() {
+    // Wednesday 2024-06-12 15:00 in Berlin
+    let tz: chrono_tz::Tz = "Europe/Berlin".parse().unwrap();
+    let now = Utc.with_ymd_and_hms(2024, 6, 12, 13, 0, 0).unwrap();
+    let at = |text: &str| {
+        timeparse::moment(text, now, tz)
+            .map(|time| time.with_timezone(&tz).format("%a %F %R").to_string())
+    };
+    let minutes = |text: &str| timeparse::duration(text).map(|length| length.num_minutes());
+
+    assert_eq!(minutes("1h30m").unwrap(), 90);
+    assert_eq!(minutes("90 minutes").unwrap(), 90);
+    assert_eq!(minutes("1.5 hours").unwrap(), 90);
+    assert_eq!(minutes("2 hours and 15 min").unwrap(), 135);
+    assert_eq!(minutes("1:30").unwrap(), 90);
+    assert!(minutes("90")
+        .unwrap_err()
+        .to_string()
+        .contains("'90' has no unit"));
+    assert!(minutes("3 fortnights")
+        .unwrap_err()
+        .to_string()
+        .contains("unknown unit 'fortnights'"));
+
+    assert_eq!(at("yesterday 3pm").unwrap(), "Tue 2024-06-11 15:00");
+    assert_eq!(at("last monday").unwrap(), "Mon 2024-06-10 00:00");
+    assert_eq!(at("last wednesday").unwrap(), "Wed 2024-06-05 00:00");
+    assert_eq!(at("wednesday 9:30").unwrap(), "Wed 2024-06-12 09:30");
+    assert_eq!(at("friday at 5:15 pm").unwrap(), "Fri 2024-06-07 17:15");
+    assert_eq!(at("8:00").unwrap(), "Wed 2024-06-12 08:00");
+    let monday = chrono::NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+    let on_monday = timeparse::moment_on("9:05", now, tz, monday).unwrap();
+    assert_eq!(
+        on_monday.with_timezone(&tz).format("%F %R").to_string(),
+        "2024-06-10 09:05"
+    );
+    assert_eq!(at("20 minutes ago").unwrap(), "Wed 2024-06-12 14:40");
+    assert_eq!(at("2024-06-03 14:00").unwrap(), "Mon 2024-06-03 14:00");
+    assert_eq!(
+        at("2024-06-03T14:00:00+00:00").unwrap(),
+        "Mon 2024-06-03 16:00"
+    );
+    let error = at("yesterday teatime").unwrap_err().to_string();
+    assert!(
+        error.contains("'teatime' is neither a day nor a time"),
+        "{}",
+        error
+    );
+    assert!(at("13pm").is_err());
+}
+
+#[test]
+fn the_index_finds_entries_as_the_log_grows_and_changes() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    let path = dir.path().join("time.csv");
+    let storage = Storage::new(path.to_str().unwrap());
+
+    tracker.start(&task("Review")).unwrap();
+    clock.advance(Duration::minutes(30));
+    tracker.stop(1).unwrap();
+    // Descriptions may hold line breaks inside their quotes
+    let notes = TaskDetails {
+        description: "Notes\nover two lines".to_string(),
+        ..task("Write")
+    };
+    tracker.start(&notes).unwrap();
+    clock.advance(Duration::minutes(10));
+    tracker.start(&task("Call")).unwrap();
+    assert!(schema::index_path(&path).exists());
+
+    let index = LogIndex::load(&path).unwrap().unwrap();
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.highest_index(), 3);
+    assert_eq!(index.running().unwrap().unwrap().title, "Call");
+    assert_eq!(
+        index.entry(2).unwrap().unwrap().message,
+        "Notes\nover two lines"
+    );
+    assert_eq!(index.entry(1).unwrap().unwrap().elapsed_time, "1800");
+    assert!(index.entry(4).unwrap().is_none());
+    let since = Utc.with_ymd_and_hms(2024, 6, 3, 9, 30, 0).unwrap();
+    let titles: Vec<_> = index
+        .started_since(since)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.title)
+        .collect();
+    assert_eq!(titles, ["Write", "Call"]);
+
+    // Stops go to the journal, which the index reads on every use
+    clock.advance(Duration::minutes(5));
+    tracker.stop(3).unwrap();
+    assert_eq!(tracker.running().unwrap().unwrap().title, "Write");
+
+    // Deletes rewrite the log, so the index is built again
+    storage.delete(1).unwrap();
+    assert!(matches!(
+        storage.entry(1),
+        Err(ITrackerError::EntryNotFound { index: 1, .. })
+    ));
+    assert_eq!(storage.entry(3).unwrap().elapsed_time, "300");
+    std::fs::write(schema::index_path(&path), "not an index").unwrap();
+    assert_eq!(storage.entry(2).unwrap().title, "Write");
+    assert_eq!(LogIndex::load(&path).unwrap().unwrap().len(), 2);
+}
+
+#[test]
+fn the_index_finds_the_running_entry_without_reading_the_offsets_of_rows() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    let path = dir.path().join("time.csv");
+
+    schema::write(
+        &path,
+        &[schema::Record {
+            index: 1,
+            start_time: "Mon, 3 Jun 2024 08:00:00 +0000".to_string(),
+            elapsed_time: "600".to_string(),
+            title: "Mail".to_string(),
+            ..schema::Record::default()
+        }],
+    )
+    .unwrap();
+    for title in ["Chat", "Call"] {
+        tracker.start(&task(title)).unwrap();
+        clock.advance(Duration::minutes(10));
+    }
+    // The stop is amended into the summary rather than read from the journal again
+    tracker.stop(3).unwrap();
+    tracker.start(&task("Docs")).unwrap();
+    assert_eq!(LogIndex::load(&path).unwrap().unwrap().len(), 4);
+
+    let rows = schema::rows_path(&path);
+    let garbage = "x".repeat(std::fs::metadata(&rows).unwrap().len() as usize);
+    std::fs::write(&rows, &garbage).unwrap();
+    let index = LogIndex::load(&path).unwrap().unwrap();
+    assert_eq!(index.len(), 4);
+    assert_eq!(index.highest_index(), 4);
+    assert_eq!(index.running().unwrap().unwrap().title, "Docs");
+    assert_eq!(index.entry(2).unwrap().unwrap().title, "Chat");
+    assert_eq!(std::fs::read_to_string(&rows).unwrap(), garbage);
+
+    assert_eq!(index.entry(3).unwrap().unwrap().elapsed_time, "600");
+
+    // Entries neither running nor amended need the offsets, read from the log again
+    assert_eq!(index.entry(1).unwrap().unwrap().title, "Mail");
+    assert!(!schema::index_path(&path).exists());
+    assert_eq!(
+        LogIndex::load(&path)
+            .unwrap()
+            .unwrap()
+            .running()
+            .unwrap()
+            .unwrap()
+            .title,
+        "Docs"
+    );
+}
+
+#[test]
+fn rewriting_the_log_drops_the_index_even_if_the_last_row_stays_put() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("time.csv");
+    let record = |index: usize, title: &str| schema::Record {
+        index,
+        start_time: "Mon, 3 Jun 2024 09:00:00 +0000".to_string(),
+        elapsed_time: "600".to_string(),
+        title: title.to_string(),
+        ..schema::Record::default()
+    };
+    schema::write(
+        &path,
+        &[record(1, "Mail"), record(2, "Chat"), record(3, "Docs")],
+    )
+    .unwrap();
+    assert_eq!(LogIndex::load(&path).unwrap().unwrap().len(), 3);
+
+    // Swapping rows of the same length keeps the size and the last row
+    schema::write(
+        &path,
+        &[record(2, "Chat"), record(1, "Mail"), record(3, "Docs")],
+    )
+    .unwrap();
+    assert!(!schema::index_path(&path).exists());
+    let index = LogIndex::load(&path).unwrap().unwrap();
+    assert_eq!(index.indices().unwrap(), [2, 1, 3]);
+    assert_eq!(index.entry(1).unwrap().unwrap().title, "Mail");
+}
+
+#[test]
+fn the_index_reads_pages_of_entries_by_index_and_start() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("time.csv");
+    let rows: Vec<String> = (1..=500)
+        .map(|index| {
+            // Later indices started earlier, as after importing older entries
+            let start = Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap()
+                - Duration::minutes(index as i64);
+            format!(
+                "{},\"{}\",,60,0,task {},,,,",
+                index,
+                start.to_rfc2822(),
+                index
+            )
+        })
+        .collect();
+    std::fs::write(
+        &path,
+        format!("{}\n{}\n", schema::HEADERS.join(","), rows.join("\n")),
+    )
+    .unwrap();
+
+    let index = LogIndex::load(&path).unwrap().unwrap();
+    let indices = index.indices().unwrap();
+    assert_eq!(indices.len(), 500);
+    assert_eq!(indices[..3], [1, 2, 3]);
+    assert!(index.start(1).unwrap().unwrap() > index.start(500).unwrap().unwrap());
+    assert!(index.start(501).unwrap().is_none());
+
+    let page: Vec<_> = index
+        .entries(&[300, 7, 999, 450])
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.title)
+        .collect();
+    assert_eq!(page, ["task 300", "task 7", "task 450"]);
+}
+
+#[test]
+fn the_memory_map_reads_the_entries_a_report_needs() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("time.csv");
+    let day = |day: u32| Utc.with_ymd_and_hms(2024, 6, day, 9, 0, 0).unwrap();
+    let rows = [
+        schema::HEADERS.join(","),
+        // A row of a file from before the title column
+        format!("1,\"{}\",old,60,0", day(1).to_rfc2822()),
+        format!(
+            "2,\"{}\",\"said \"\"hi\"\", then left\",60,0,greet,acme,\"a,b\",30,,",
+            day(2).to_rfc2822()
+        ),
+        format!("3,\"{}\",,60,0,review,acme,,,,", day(3).to_rfc2822()),
+        "4,not a date,,60,0,broken,,,,,".to_string(),
+    ];
+    std::fs::write(&path, rows.join("\n") + "\n").unwrap();
+    let mut amended = schema::read(&path).unwrap()[2].clone();
+    amended.title = "review again".to_string();
+    schema::amend(&path, &amended).unwrap();
+
+    let storage = Storage::new(path.to_string_lossy());
+    let read = storage.entries_started(day(1), day(3)).unwrap();
+    assert_eq!(read.len(), 2);
+    assert_eq!(read[0].paused_time, "0");
+    assert_eq!(read[1].message, "said \"hi\", then left");
+    assert_eq!(read[1].tags, ["a", "b"]);
+    assert_eq!(read[1].estimate, Some(30));
+
+    let mapped = MappedLog::open(&path).unwrap().unwrap();
+    let records = |entries: Vec<itracker::Entry>| -> Vec<schema::Record> {
+        entries.iter().map(schema::Record::from).collect()
+    };
+    assert_eq!(
+        records(mapped.entries_where(|_| true).unwrap()),
+        records(storage.entries().unwrap())
+    );
+    let later = storage.entries_started(day(3), day(30)).unwrap();
+    assert_eq!(later.len(), 1);
+    assert_eq!(later[0].title, "review again");
+}
+
+#[test]
+fn reports_read_and_summarize_in_parallel_like_in_order() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("time.csv");
+    let first = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+    let mut rows = vec![schema::HEADERS.join(",")];
+    rows.extend((1..=3000).map(|index| {
+        let start = (first + Duration::hours(index as i64)).to_rfc2822();
+        match index % 3 {
+            // Newlines and quotes inside a field must not split the rows
+            0 => format!(
+                "{},\"{}\",\"line one\nline \"\"two\"\"\n\",60,0,task,a,,,,",
+                index, start
+            ),
+            1 => format!("{},\"{}\",say \"hi\",60,0,task,b,,,,", index, start),
+            _ => format!("{},\"{}\",,60,0,task,,,,,", index, start),
+        }
+    }));
+    std::fs::write(&path, rows.join("\r\n")).unwrap();
+
+    let storage = Storage::new(path.to_string_lossy());
+    let from = first + Duration::days(10);
+    let to = first + Duration::days(100);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(3)
+        .build()
+        .unwrap();
+    let parallel = pool.install(|| storage.entries_started(from, to)).unwrap();
+    let in_order = MappedLog::open(&path)
+        .unwrap()
+        .unwrap()
+        .entries_where(|row| row.start().is_some_and(|start| start >= from && start < to))
+        .unwrap();
+    assert_eq!(parallel.len(), 90 * 24);
+    let indices = |entries: &[itracker::Entry]| entries.iter().map(|e| e.index).collect::<Vec<_>>();
+    assert_eq!(indices(&parallel), indices(&in_order));
+    assert!(parallel
+        .iter()
+        .any(|entry| entry.message == "line one\nline \"two\"\n"));
+
+    let now = first + Duration::days(400);
+    let all: Vec<_> = parallel.iter().collect();
+    let summary = pool.install(|| report::summarize_in_parallel(&all, now, chrono_tz::UTC));
+    let expected = report::summarize(all.iter().copied(), now, chrono_tz::UTC);
+    assert_eq!(summary.total, 90 * 24 * 60);
+    assert_eq!(summary.per_day, expected.per_day);
+    assert_eq!(summary.per_project, expected.per_project);
+    assert_eq!(summary.entries, expected.entries);
+}
+
+#[test]
+fn a_cached_storage_reads_the_log_once_and_notices_other_writers() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("time.csv");
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap());
+    let cached = Storage::new(path.to_str().unwrap()).with_cache();
+    let tracker = Tracker::new(cached.clone()).with_clock(Arc::new(clock.clone()));
+    let first = tracker.start(&task("first")).unwrap();
+    clock.advance(Duration::minutes(30));
+    tracker.stop(first.index).unwrap();
+    let second = tracker.start(&task("second")).unwrap();
+    assert_eq!(second.index, 2);
+    assert_eq!(tracker.running().unwrap().unwrap().index, 2);
+
+    let records = |entries: Vec<itracker::Entry>| -> Vec<schema::Record> {
+        entries.iter().map(schema::Record::from).collect()
+    };
+    let other = Storage::new(path.to_str().unwrap());
+    assert_eq!(
+        records(cached.entries().unwrap()),
+        records(other.entries().unwrap())
+    );
+
+    // A change that keeps the size and modification time goes unnoticed,
+    // so the entries come from memory, as the running ones of the index do
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, contents.replace("second", "SECOND")).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert_eq!(cached.entry(2).unwrap().title, "second");
+    assert_eq!(other.entries().unwrap()[1].title, "SECOND");
+
+    // Writes of anything else are read again
+    other.delete(1).unwrap();
+    let titles: Vec<_> = cached
+        .entries()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.title)
+        .collect();
+    assert_eq!(titles, ["SECOND"]);
+    assert!(cached.entry(1).is_err());
+    assert_eq!(cached.next_index().unwrap(), 3);
+}
+
+#[test]
+fn the_system_clock_reads_the_wall_clock_on_every_call() {
+    let clock = SystemClock::new();
+    let before = Utc::now();
+    let first = clock.now();
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let second = clock.clone().now();
+    let after = Utc::now();
+    assert!(before <= first && first < second && second <= after);
+}
+
+#[test]
+fn restoring_a_backup_replaces_the_log_without_changing_a_mapped_one() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("time.csv");
+    let (tracker, _) = tracker(&dir);
+    tracker.start(&task("first")).unwrap();
+    let backups = Backups::new(dir.path().join("backups"), 5);
+    let backup = backups.save(&path).unwrap().unwrap();
+    tracker.start(&task("second")).unwrap();
+
+    let mapped = MappedLog::open(&path).unwrap().unwrap();
+    let before = std::fs::metadata(&path).unwrap();
+    backups.restore(&path, &backup).unwrap();
+    // A new file took the place of the mapped one
+    assert_ne!(std::fs::metadata(&path).unwrap().ino(), before.ino());
+    let titles: Vec<_> = mapped
+        .entries_where(|_| true)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.title)
+        .collect();
+    assert_eq!(titles, ["first", "second"]);
+    assert_eq!(
+        Storage::new(path.to_string_lossy())
+            .entries()
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn undoing_a_stop_copies_and_puts_back_only_that_entry() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("time.csv");
+    let (tracker, clock) = tracker(&dir);
+    for i in 0..200 {
+        let entry = tracker.start(&task(&format!("task {}", i))).unwrap();
+        clock.advance(Duration::minutes(1));
+        tracker.stop(entry.index).unwrap();
+    }
+    let running = tracker.start(&task("current")).unwrap();
+    clock.advance(Duration::minutes(5));
+
+    let undo = UndoLog::new(dir.path().join("undo"), 5);
+    let log = path.to_str().unwrap();
+    undo.record_entries(log, "stop of entry 201", &[running.index], || {
+        tracker.stop(running.index)
+    })
+    .unwrap();
+    assert!(!tracker.storage().entry(running.index).unwrap().is_running());
+    let copied: u64 = std::fs::read_dir(dir.path().join("undo"))
+        .unwrap()
+        .map(|file| file.unwrap().metadata().unwrap().len())
+        .sum();
+    assert!(copied < std::fs::metadata(&path).unwrap().len() / 20);
+
+    let backups = Backups::new(dir.path().join("backups"), 5);
+    undo.undo(&backups, false).unwrap();
+    assert!(tracker.storage().entry(running.index).unwrap().is_running());
+    assert_eq!(tracker.storage().entries().unwrap().len(), 201);
+}
+
+#[test]
+fn activity_samples_go_to_the_running_entry_unless_it_is_paused() {
+    let dir = TempDir::new().unwrap();
+    let (tracker, clock) = tracker(&dir);
+    let path = dir.path().join("time.csv");
+    let sampler = ActivityTracker::from_config(Some(&ActivityConfig {
+        enabled: Some(true),
+        interval_seconds: Some(30),
+        command: Some("printf 'Editor\\nnotes.md'".to_string()),
+        ..ActivityConfig::default()
+    }))
+    .unwrap()
+    .unwrap();
+    // One cached storage across samples, as the daemon keeps it
+    let storage = Storage::new(path.to_str().unwrap()).with_cache();
+
+    tracker.start(&task("Write")).unwrap();
+    sampler.sample(&storage, clock.now()).unwrap();
+    clock.advance(Duration::seconds(30));
+    tracker.pause(None).unwrap();
+    sampler.sample(&storage, clock.now()).unwrap();
+    clock.advance(Duration::seconds(30));
+    tracker.resume(None).unwrap();
+    sampler.sample(&storage, clock.now()).unwrap();
+
+    let entries: Vec<_> = activity::read_samples(&path)
+        .unwrap()
+        .into_iter()
+        .map(|sample| sample.entry)
+        .collect();
+    assert_eq!(entries, [Some(1), None, Some(1)]);
+}